@@ -0,0 +1,388 @@
+/*!
+Broker MQTT embarqué pour les tests
+
+Lance un broker MQTT 3.1.1 en process sur un port éphémère, pour tester avec un
+vrai client `rumqttc` sans broker externe :
+- CONNECT / SUBSCRIBE / UNSUBSCRIBE avec wildcards `+` et `#`
+- QoS 0/1/2 des deux côtés (livraison au min(QoS publiée, QoS accordée))
+- Messages retenus (rejoués à l'abonnement, effacés par un payload vide)
+- Journal des messages routés et attente d'abonnés pour les assertions
+
+Simplifications : sessions non persistantes, pas de will, pas de
+retransmission (les acquittements des abonnés ne sont pas suivis).
+
+```rust,no_run
+use symbion_devkit::EmbeddedBroker;
+use rumqttc::AsyncClient;
+
+# async fn example() -> anyhow::Result<()> {
+let broker = EmbeddedBroker::start().await?;
+let (client, mut eventloop) = AsyncClient::new(broker.mqtt_options("my-test"), 10);
+// ... publier / s'abonner via client, faire tourner eventloop.poll()
+broker.shutdown();
+# Ok(())
+# }
+```
+*/
+
+use anyhow::{bail, Result};
+use bytes::{Bytes, BytesMut};
+use rumqttc::{
+    matches, read, ConnAck, ConnectReturnCode, MqttOptions, Packet, PingResp, PubAck, PubComp, PubRec, PubRel,
+    Publish, QoS, SubAck, SubscribeReasonCode, UnsubAck,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+struct Session {
+    client_id: String,
+    /// Filtres abonnés et QoS accordée
+    filters: Vec<(String, QoS)>,
+    outgoing: mpsc::UnboundedSender<Packet>,
+    next_pkid: u16,
+}
+
+impl Session {
+    /// Envoie un message à l'abonné, en QoS `qos` (pkid attribué si QoS > 0)
+    fn deliver(&mut self, topic: &str, payload: &Bytes, qos: QoS, retain: bool) {
+        let mut publish = Publish::from_bytes(topic, qos, payload.clone());
+        publish.retain = retain;
+        if qos != QoS::AtMostOnce {
+            self.next_pkid = self.next_pkid.checked_add(1).unwrap_or(1);
+            publish.pkid = self.next_pkid;
+        }
+        let _ = self.outgoing.send(Packet::Publish(publish));
+    }
+
+    /// QoS de livraison pour `topic`, si un filtre correspond
+    fn granted(&self, topic: &str) -> Option<QoS> {
+        self.filters.iter().filter(|(f, _)| matches(topic, f)).map(|(_, qos)| *qos).max_by_key(|qos| *qos as u8)
+    }
+}
+
+#[derive(Default)]
+struct BrokerState {
+    sessions: HashMap<u64, Session>,
+    next_session: u64,
+    retained: HashMap<String, (Bytes, QoS)>,
+    /// Tous les messages routés (topic, payload), dans l'ordre d'arrivée
+    published: Vec<(String, Bytes)>,
+    connections: Vec<JoinHandle<()>>,
+}
+
+/// Broker embarqué sur 127.0.0.1, arrêté au drop
+pub struct EmbeddedBroker {
+    addr: SocketAddr,
+    state: Arc<Mutex<BrokerState>>,
+    acceptor: JoinHandle<()>,
+}
+
+impl EmbeddedBroker {
+    /// Démarre le broker sur un port éphémère
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state: Arc<Mutex<BrokerState>> = Arc::default();
+
+        let accepting = state.clone();
+        let acceptor = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let connection = tokio::spawn(serve(accepting.clone(), stream));
+                let mut state = accepting.lock().unwrap();
+                state.connections.retain(|c| !c.is_finished());
+                state.connections.push(connection);
+            }
+        });
+
+        log::debug!("🧪 Embedded MQTT broker listening on {}", addr);
+        Ok(Self { addr, state, acceptor })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// Options client prêtes à l'emploi pointant sur ce broker
+    pub fn mqtt_options(&self, client_id: &str) -> MqttOptions {
+        let mut options = MqttOptions::new(client_id, self.addr.ip().to_string(), self.addr.port());
+        options.set_keep_alive(Duration::from_secs(15));
+        options
+    }
+
+    /// Messages publiés sur `topic` depuis le démarrage
+    pub fn published(&self, topic: &str) -> Vec<Bytes> {
+        let state = self.state.lock().unwrap();
+        state.published.iter().filter(|(t, _)| t == topic).map(|(_, p)| p.clone()).collect()
+    }
+
+    /// Message actuellement retenu sur `topic`
+    pub fn retained(&self, topic: &str) -> Option<Bytes> {
+        self.state.lock().unwrap().retained.get(topic).map(|(payload, _)| payload.clone())
+    }
+
+    /// Attend qu'un client dont l'id commence par `client_prefix` soit abonné à `topic`
+    pub async fn wait_for_subscriber(&self, client_prefix: &str, topic: &str, timeout: Duration) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let subscribed = self.state.lock().unwrap().sessions.values().any(|s| {
+                s.client_id.starts_with(client_prefix) && s.granted(topic).is_some()
+            });
+            if subscribed {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() > deadline {
+                bail!("no {} subscriber on {} after {:?}", client_prefix, topic, timeout);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// Arrête le broker et coupe toutes les connexions clientes
+    pub fn shutdown(self) {
+        // Le travail est fait par Drop
+    }
+}
+
+impl Drop for EmbeddedBroker {
+    fn drop(&mut self) {
+        self.acceptor.abort();
+        let mut state = self.state.lock().unwrap();
+        for connection in state.connections.drain(..) {
+            connection.abort();
+        }
+        state.sessions.clear();
+    }
+}
+
+async fn serve(state: Arc<Mutex<BrokerState>>, stream: TcpStream) {
+    let (mut reader, mut writer) = stream.into_split();
+    let (outgoing, mut queue) = mpsc::unbounded_channel::<Packet>();
+    let writer_task = tokio::spawn(async move {
+        let mut buffer = BytesMut::new();
+        while let Some(packet) = queue.recv().await {
+            buffer.clear();
+            if write_packet(&packet, &mut buffer).is_err() || writer.write_all(&buffer).await.is_err() {
+                break;
+            }
+        }
+    });
+    // Arrête l'écriture même si cette tâche est annulée (shutdown)
+    let _writer_guard = AbortOnDrop(writer_task);
+
+    let mut session_id = None;
+    let mut buffer = BytesMut::with_capacity(4096);
+    loop {
+        let packet = match read(&mut buffer, MAX_PACKET_SIZE) {
+            Ok(packet) => packet,
+            Err(rumqttc::Error::InsufficientBytes(_)) => match reader.read_buf(&mut buffer).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => continue,
+            },
+            Err(e) => {
+                log::warn!("⚠️ Embedded broker: malformed packet: {:?}", e);
+                break;
+            }
+        };
+
+        match packet {
+            Packet::Connect(connect) => {
+                let mut state = state.lock().unwrap();
+                let id = state.next_session;
+                state.next_session += 1;
+                state.sessions.insert(id, Session {
+                    client_id: connect.client_id,
+                    filters: Vec::new(),
+                    outgoing: outgoing.clone(),
+                    next_pkid: 0,
+                });
+                session_id = Some(id);
+                let _ = outgoing.send(Packet::ConnAck(ConnAck::new(ConnectReturnCode::Success, false)));
+            }
+            Packet::Subscribe(subscribe) => {
+                let codes = subscribe.filters.iter().map(|f| SubscribeReasonCode::Success(f.qos)).collect();
+                let _ = outgoing.send(Packet::SubAck(SubAck::new(subscribe.pkid, codes)));
+
+                let mut guard = state.lock().unwrap();
+                let BrokerState { sessions, retained, .. } = &mut *guard;
+                if let Some(session) = session_id.and_then(|id| sessions.get_mut(&id)) {
+                    for filter in subscribe.filters {
+                        session.filters.retain(|(f, _)| *f != filter.path);
+                        for (topic, (payload, qos)) in retained.iter().filter(|(t, _)| matches(t, &filter.path)) {
+                            session.deliver(topic, payload, min_qos(*qos, filter.qos), true);
+                        }
+                        session.filters.push((filter.path, filter.qos));
+                    }
+                }
+            }
+            Packet::Unsubscribe(unsubscribe) => {
+                let mut state = state.lock().unwrap();
+                if let Some(session) = session_id.and_then(|id| state.sessions.get_mut(&id)) {
+                    session.filters.retain(|(f, _)| !unsubscribe.topics.contains(f));
+                }
+                let _ = outgoing.send(Packet::UnsubAck(UnsubAck::new(unsubscribe.pkid)));
+            }
+            Packet::Publish(publish) => {
+                match publish.qos {
+                    QoS::AtMostOnce => {}
+                    QoS::AtLeastOnce => { let _ = outgoing.send(Packet::PubAck(PubAck::new(publish.pkid))); }
+                    QoS::ExactlyOnce => { let _ = outgoing.send(Packet::PubRec(PubRec::new(publish.pkid))); }
+                }
+                route(&mut state.lock().unwrap(), publish);
+            }
+            // Flux QoS 2 : côté publieur (PUBREL → PUBCOMP) et côté abonné (PUBREC → PUBREL)
+            Packet::PubRel(pubrel) => {
+                let _ = outgoing.send(Packet::PubComp(PubComp::new(pubrel.pkid)));
+            }
+            Packet::PubRec(pubrec) => {
+                let _ = outgoing.send(Packet::PubRel(PubRel::new(pubrec.pkid)));
+            }
+            Packet::PingReq => {
+                let _ = outgoing.send(Packet::PingResp);
+            }
+            Packet::Disconnect => break,
+            _ => {}
+        }
+    }
+
+    if let Some(id) = session_id {
+        state.lock().unwrap().sessions.remove(&id);
+    }
+}
+
+/// Redistribue un message à chaque session abonnée (une fois par session)
+fn route(state: &mut BrokerState, publish: Publish) {
+    let Publish { topic, payload, qos, retain, .. } = publish;
+    if retain {
+        if payload.is_empty() {
+            state.retained.remove(&topic);
+        } else {
+            state.retained.insert(topic.clone(), (payload.clone(), qos));
+        }
+    }
+    for session in state.sessions.values_mut() {
+        if let Some(granted) = session.granted(&topic) {
+            session.deliver(&topic, &payload, min_qos(qos, granted), false);
+        }
+    }
+    state.published.push((topic, payload));
+}
+
+fn min_qos(a: QoS, b: QoS) -> QoS {
+    if (a as u8) <= (b as u8) { a } else { b }
+}
+
+fn write_packet(packet: &Packet, buffer: &mut BytesMut) -> Result<usize, rumqttc::Error> {
+    match packet {
+        Packet::ConnAck(p) => p.write(buffer),
+        Packet::SubAck(p) => p.write(buffer),
+        Packet::UnsubAck(p) => p.write(buffer),
+        Packet::PubAck(p) => p.write(buffer),
+        Packet::PubRec(p) => p.write(buffer),
+        Packet::PubRel(p) => p.write(buffer),
+        Packet::PubComp(p) => p.write(buffer),
+        Packet::Publish(p) => p.write(buffer),
+        Packet::PingResp => PingResp.write(buffer),
+        other => unreachable!("broker never sends {:?}", other),
+    }
+}
+
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rumqttc::{AsyncClient, Event, EventLoop, Incoming};
+
+    /// Fait tourner l'eventloop jusqu'au prochain PUBLISH reçu
+    async fn next_publish(eventloop: &mut EventLoop) -> Publish {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Event::Incoming(Incoming::Publish(publish)) = eventloop.poll().await.expect("mqtt error") {
+                    return publish;
+                }
+            }
+        }).await.expect("no publish received")
+    }
+
+    /// Connecte un client et fait tourner son eventloop en tâche de fond
+    fn background_client(broker: &EmbeddedBroker, client_id: &str) -> AsyncClient {
+        let (client, mut eventloop) = AsyncClient::new(broker.mqtt_options(client_id), 10);
+        tokio::spawn(async move { while eventloop.poll().await.is_ok() {} });
+        client
+    }
+
+    #[tokio::test]
+    async fn test_real_client_round_trip_with_wildcards_and_qos() {
+        let broker = EmbeddedBroker::start().await.unwrap();
+        let (subscriber, mut events) = AsyncClient::new(broker.mqtt_options("test-subscriber"), 10);
+        subscriber.subscribe("symbion/notes/+", QoS::ExactlyOnce).await.unwrap();
+        subscriber.subscribe("symbion/hosts/#", QoS::AtMostOnce).await.unwrap();
+        // Lance la connexion en attendant l'abonnement
+        let wait = broker.wait_for_subscriber("test-subscriber", "symbion/notes/created", Duration::from_secs(5));
+        tokio::select! {
+            result = wait => result.unwrap(),
+            _ = async { loop { events.poll().await.unwrap(); } } => unreachable!(),
+        }
+        broker.wait_for_subscriber("test-subscriber", "symbion/hosts/a/b", Duration::from_secs(5)).await.unwrap();
+
+        let publisher = background_client(&broker, "test-publisher");
+        publisher.publish("symbion/notes/created", QoS::ExactlyOnce, false, r#"{"id":1}"#).await.unwrap();
+        let received = next_publish(&mut events).await;
+        assert_eq!(received.topic, "symbion/notes/created");
+        assert_eq!(received.payload.as_ref(), br#"{"id":1}"#);
+        assert_eq!(received.qos, QoS::ExactlyOnce);
+
+        // Un seul abonnement QoS 0 : livraison dégradée en QoS 0
+        publisher.publish("symbion/hosts/a/b", QoS::AtLeastOnce, false, "ping").await.unwrap();
+        let received = next_publish(&mut events).await;
+        assert_eq!(received.topic, "symbion/hosts/a/b");
+        assert_eq!(received.qos, QoS::AtMostOnce);
+
+        assert_eq!(broker.published("symbion/notes/created").len(), 1);
+        broker.shutdown();
+    }
+
+    #[tokio::test]
+    async fn test_retained_message_replayed_to_late_subscriber() {
+        let broker = EmbeddedBroker::start().await.unwrap();
+        let publisher = background_client(&broker, "test-publisher");
+        publisher.publish("symbion/kernel/health", QoS::AtLeastOnce, true, "up").await.unwrap();
+        for _ in 0..100 {
+            if broker.retained("symbion/kernel/health").is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(broker.retained("symbion/kernel/health").unwrap().as_ref(), b"up");
+
+        let (late, mut events) = AsyncClient::new(broker.mqtt_options("test-late"), 10);
+        late.subscribe("symbion/kernel/#", QoS::AtLeastOnce).await.unwrap();
+        let received = next_publish(&mut events).await;
+        assert!(received.retain);
+        assert_eq!(received.payload.as_ref(), b"up");
+
+        // Payload vide : efface le message retenu
+        publisher.publish("symbion/kernel/health", QoS::AtLeastOnce, true, "").await.unwrap();
+        let cleared = next_publish(&mut events).await;
+        assert!(!cleared.retain);
+        assert_eq!(broker.retained("symbion/kernel/health"), None);
+    }
+}
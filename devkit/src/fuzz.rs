@@ -0,0 +1,478 @@
+/*!
+Fuzzing de payloads piloté par les contrats
+
+À partir du schéma d'un contrat, génère des payloads invalides ou limites et les
+soumet au handler d'un plugin, en vérifiant qu'il ne panique jamais et répond
+toujours par une réponse structurée :
+- Champs requis manquants, types incorrects, valeurs hors enum, `null`
+- Champs supplémentaires, chaînes énormes ou exotiques, nombres extrêmes
+- Racines non-objet, JSON tronqué, payload vide, UTF-8 invalide, imbrication profonde
+- Les contrats à `oneOf` (ex: `notes.command`) sont fuzzés branche par branche
+
+Les cas marqués `expect_error` violent le contrat : le plugin doit répondre
+par une erreur. Les autres (extras, limites) peuvent réussir ou échouer, mais
+la réponse doit rester conforme au contrat de réponse s'il est fourni.
+*/
+
+use crate::contract_helpers::Contract;
+use crate::schema_gen::{validate, SchemaGenerator};
+use serde_json::{json, Map, Value};
+use std::future::Future;
+use std::time::Duration;
+
+/// Longueur par défaut des chaînes « énormes »
+const DEFAULT_HUGE_STRING_LEN: usize = 64 * 1024;
+/// Profondeur du payload imbriqué (au-delà de la limite de récursion de serde_json)
+const DEEP_NESTING: usize = 512;
+/// Temps maximum accordé au handler par cas
+const CASE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Un payload de fuzzing
+#[derive(Debug, Clone)]
+pub struct FuzzCase {
+    pub description: String,
+    pub payload: Vec<u8>,
+    /// Le payload viole le contrat : une réponse d'erreur est attendue
+    pub expect_error: bool,
+}
+
+/// Cas ayant échoué
+#[derive(Debug, Clone)]
+pub struct FuzzFailure {
+    pub case: String,
+    pub reason: String,
+}
+
+/// Résultat d'une campagne de fuzzing
+#[derive(Debug, Default)]
+pub struct FuzzReport {
+    pub cases: usize,
+    pub failures: Vec<FuzzFailure>,
+}
+
+impl FuzzReport {
+    /// Erreur listant les cas en échec (10 premiers)
+    pub fn assert_clean(&self) -> anyhow::Result<()> {
+        if self.failures.is_empty() {
+            log::info!("✅ {} fuzz cases passed", self.cases);
+            return Ok(());
+        }
+        let details: Vec<String> = self.failures.iter()
+            .take(10)
+            .map(|f| format!("  - {}: {}", f.case, f.reason))
+            .collect();
+        anyhow::bail!("{}/{} fuzz cases failed:\n{}", self.failures.len(), self.cases, details.join("\n"));
+    }
+}
+
+/// Segment de chemin vers un champ du payload
+#[derive(Debug, Clone)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Champ présent dans le payload de base, avec son schéma
+struct Site {
+    path: Vec<Segment>,
+    schema: Value,
+    required: bool,
+}
+
+/// Fuzzer seedé : même seed ⇒ mêmes cas
+pub struct PayloadFuzzer {
+    seed: u64,
+    rounds: u64,
+    huge_string_len: usize,
+    response_contract: Option<Contract>,
+    is_error: fn(&Value) -> bool,
+}
+
+impl PayloadFuzzer {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rounds: 3,
+            huge_string_len: DEFAULT_HUGE_STRING_LEN,
+            response_contract: None,
+            is_error: default_is_error,
+        }
+    }
+
+    /// Nombre de payloads de base générés par variante du schéma
+    pub fn with_rounds(mut self, rounds: u64) -> Self {
+        self.rounds = rounds.max(1);
+        self
+    }
+
+    pub fn with_huge_string_len(mut self, len: usize) -> Self {
+        self.huge_string_len = len;
+        self
+    }
+
+    /// Valide chaque réponse contre ce contrat
+    pub fn with_response_contract(mut self, contract: Contract) -> Self {
+        self.response_contract = Some(contract);
+        self
+    }
+
+    /// Reconnaît une réponse d'erreur (défaut : `type` ou `status` vaut "error")
+    pub fn with_error_check(mut self, is_error: fn(&Value) -> bool) -> Self {
+        self.is_error = is_error;
+        self
+    }
+
+    /// Génère tous les cas de fuzzing pour un contrat
+    pub fn cases(&self, contract: &Contract) -> Vec<FuzzCase> {
+        let mut cases = raw_cases();
+
+        for (variant_index, variant) in schema_variants(&contract.schema).iter().enumerate() {
+            for round in 0..self.rounds {
+                let seed = self.seed.wrapping_add(round * 1000 + variant_index as u64);
+                let base = SchemaGenerator::new(seed).with_optional_probability(1.0).generate(variant);
+                let label = base.get("action").and_then(|a| a.as_str())
+                    .map(|a| a.to_string())
+                    .unwrap_or_else(|| format!("variant {}", variant_index));
+
+                let mut extra = base.clone();
+                if let Some(object) = extra.as_object_mut() {
+                    object.insert("__fuzz_extra".into(), json!({"nested": [1, "two", null]}));
+                }
+                cases.push(json_case(format!("{}: extra field", label), &extra, false));
+
+                let mut sites = Vec::new();
+                collect_sites(variant, &base, &mut Vec::new(), &mut sites);
+                for site in &sites {
+                    cases.extend(self.site_cases(&label, &base, site));
+                }
+            }
+        }
+        cases
+    }
+
+    fn site_cases(&self, label: &str, base: &Value, site: &Site) -> Vec<FuzzCase> {
+        let at = format_path(&site.path);
+        let current = get_path(base, &site.path).cloned().unwrap_or(Value::Null);
+        let types = schema_types(&site.schema);
+        let mut cases = Vec::new();
+        let mut replace = |what: &str, value: Value, expect_error: bool| {
+            let mut payload = base.clone();
+            if let Some(slot) = get_path_mut(&mut payload, &site.path) {
+                *slot = value;
+                cases.push(json_case(format!("{}: {} {}", label, what, at), &payload, expect_error));
+            }
+        };
+
+        if !types.is_empty() {
+            if let Some(wrong) = wrong_type_value(&current, &types) {
+                replace("wrong type at", wrong, true);
+            }
+            if !types.contains(&"null") {
+                replace("null at", Value::Null, site.required);
+            }
+        }
+        if site.schema.get("enum").is_some() || site.schema.get("const").is_some() {
+            replace("value outside enum at", json!("__fuzz__"), true);
+        }
+        match current {
+            Value::String(_) => {
+                replace("huge string at", Value::String("x".repeat(self.huge_string_len)), false);
+                replace("empty string at", json!(""), false);
+                replace("exotic string at", json!("\u{0}\u{202e}😀\n\"'; DROP TABLE notes;--"), false);
+            }
+            Value::Number(_) => {
+                replace("negative number at", json!(-1), false);
+                replace("zero at", json!(0), false);
+                replace("huge number at", json!(i64::MAX), false);
+                if types.contains(&"integer") && !types.contains(&"number") {
+                    replace("fractional number at", json!(1.5), true);
+                } else {
+                    replace("extreme float at", json!(1e308), false);
+                }
+            }
+            Value::Array(_) => {
+                let many: Vec<Value> = (0..1000).map(|i| Value::String(format!("item-{}", i))).collect();
+                replace("huge array at", Value::Array(many), false);
+            }
+            _ => {}
+        }
+
+        if site.required {
+            let mut payload = base.clone();
+            if remove_path(&mut payload, &site.path) {
+                cases.push(json_case(format!("{}: missing required {}", label, at), &payload, true));
+            }
+        }
+        cases
+    }
+
+    /// Soumet chaque cas au handler et vérifie panics, délais et réponses
+    pub async fn run<F, Fut>(&self, contract: &Contract, handler: F) -> FuzzReport
+    where
+        F: Fn(Vec<u8>) -> Fut,
+        Fut: Future<Output = Value> + Send + 'static,
+    {
+        let cases = self.cases(contract);
+        let mut report = FuzzReport { cases: cases.len(), failures: Vec::new() };
+
+        for case in cases {
+            let task = tokio::spawn(handler(case.payload.clone()));
+            let reason = match tokio::time::timeout(CASE_TIMEOUT, task).await {
+                Err(_) => Some(format!("no response within {:?}", CASE_TIMEOUT)),
+                Ok(Err(e)) if e.is_panic() => Some(format!("panicked: {}", panic_message(e.into_panic()))),
+                Ok(Err(e)) => Some(format!("handler task failed: {}", e)),
+                Ok(Ok(response)) => self.check_response(&case, &response).err(),
+            };
+            if let Some(reason) = reason {
+                report.failures.push(FuzzFailure { case: case.description, reason });
+            }
+        }
+        report
+    }
+
+    fn check_response(&self, case: &FuzzCase, response: &Value) -> Result<(), String> {
+        if let Some(contract) = &self.response_contract {
+            validate(&contract.schema, response)
+                .map_err(|e| format!("response violates {}: {} ({})", contract.name, e, response))?;
+        }
+        if case.expect_error && !(self.is_error)(response) {
+            return Err(format!("expected an error response, got {}", truncate(&response.to_string())));
+        }
+        Ok(())
+    }
+}
+
+fn default_is_error(response: &Value) -> bool {
+    response.get("type") == Some(&json!("error")) || response.get("status") == Some(&json!("error"))
+}
+
+/// Payloads bruts indépendants du schéma, tous invalides
+fn raw_cases() -> Vec<FuzzCase> {
+    let raw = |description: &str, payload: Vec<u8>| FuzzCase {
+        description: description.to_string(),
+        payload,
+        expect_error: true,
+    };
+    let deep = format!("{}{}", "[".repeat(DEEP_NESTING), "]".repeat(DEEP_NESTING));
+    vec![
+        raw("empty payload", Vec::new()),
+        raw("truncated JSON", br#"{"action": "#.to_vec()),
+        raw("invalid UTF-8", vec![0x7b, 0x22, 0xff, 0xfe, 0x22, 0x7d]),
+        raw("not JSON", b"hello plugin".to_vec()),
+        raw("root null", b"null".to_vec()),
+        raw("root array", b"[]".to_vec()),
+        raw("root string", br#""create""#.to_vec()),
+        raw("root number", b"42".to_vec()),
+        raw("empty object", b"{}".to_vec()),
+        raw("deeply nested arrays", deep.into_bytes()),
+    ]
+}
+
+fn json_case(description: String, payload: &Value, expect_error: bool) -> FuzzCase {
+    FuzzCase { description, payload: serde_json::to_vec(payload).unwrap_or_default(), expect_error }
+}
+
+/// Variantes d'un schéma : une par branche `oneOf`, fusionnée avec le schéma parent
+fn schema_variants(schema: &Value) -> Vec<Value> {
+    let Some(branches) = schema.get("oneOf").and_then(|o| o.as_array()).filter(|o| !o.is_empty()) else {
+        return vec![schema.clone()];
+    };
+    branches.iter().map(|branch| {
+        let mut merged = schema.clone();
+        let object = merged.as_object_mut().expect("schema with oneOf is an object");
+        object.remove("oneOf");
+
+        let mut properties = object.get("properties").and_then(|p| p.as_object()).cloned().unwrap_or_default();
+        if let Some(branch_props) = branch.get("properties").and_then(|p| p.as_object()) {
+            properties.extend(branch_props.clone());
+        }
+        let mut required: Vec<Value> = object.get("required").and_then(|r| r.as_array()).cloned().unwrap_or_default();
+        for field in branch.get("required").and_then(|r| r.as_array()).into_iter().flatten() {
+            if !required.contains(field) {
+                required.push(field.clone());
+            }
+        }
+        object.insert("type".into(), json!("object"));
+        object.insert("properties".into(), Value::Object(properties));
+        object.insert("required".into(), Value::Array(required));
+        merged
+    }).collect()
+}
+
+/// Recense les champs présents dans `value` (objets et premier élément des tableaux)
+fn collect_sites(schema: &Value, value: &Value, path: &mut Vec<Segment>, sites: &mut Vec<Site>) {
+    match value {
+        Value::Object(object) => {
+            let required: Vec<&str> = schema.get("required").and_then(|r| r.as_array())
+                .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+                .unwrap_or_default();
+            let properties = schema.get("properties").and_then(|p| p.as_object()).cloned().unwrap_or_else(Map::new);
+            for (name, prop_schema) in &properties {
+                let Some(prop_value) = object.get(name) else { continue };
+                path.push(Segment::Key(name.clone()));
+                sites.push(Site { path: path.clone(), schema: prop_schema.clone(), required: required.contains(&name.as_str()) });
+                collect_sites(prop_schema, prop_value, path, sites);
+                path.pop();
+            }
+        }
+        Value::Array(items) => {
+            if let (Some(first), Some(item_schema)) = (items.first(), schema.get("items")) {
+                path.push(Segment::Index(0));
+                sites.push(Site { path: path.clone(), schema: item_schema.clone(), required: false });
+                collect_sites(item_schema, first, path, sites);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn schema_types(schema: &Value) -> Vec<&str> {
+    match schema.get("type") {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(types)) => types.iter().filter_map(|t| t.as_str()).collect(),
+        _ => vec![],
+    }
+}
+
+/// Valeur d'un type que le schéma n'autorise pas
+fn wrong_type_value(current: &Value, types: &[&str]) -> Option<Value> {
+    let candidates = [
+        ("string", json!(42)),
+        ("integer", json!("not-a-number")),
+        ("number", json!("not-a-number")),
+        ("boolean", json!("true")),
+        ("object", json!(["not", "an", "object"])),
+        ("array", json!({"not": "an array"})),
+    ];
+    let preferred = match current {
+        Value::String(_) => "string",
+        Value::Number(_) => "number",
+        Value::Bool(_) => "boolean",
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::Null => "null",
+    };
+    candidates.iter()
+        .filter(|(t, _)| *t == preferred || (preferred == "number" && *t == "integer"))
+        .chain(candidates.iter())
+        .map(|(_, v)| v)
+        .find(|v| !types.iter().any(|t| value_has_type(v, t)))
+        .cloned()
+}
+
+fn value_has_type(value: &Value, schema_type: &str) -> bool {
+    match schema_type {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn get_path<'a>(value: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |current, segment| match segment {
+        Segment::Key(key) => current.get(key),
+        Segment::Index(index) => current.get(index),
+    })
+}
+
+fn get_path_mut<'a>(value: &'a mut Value, path: &[Segment]) -> Option<&'a mut Value> {
+    path.iter().try_fold(value, |current, segment| match segment {
+        Segment::Key(key) => current.get_mut(key),
+        Segment::Index(index) => current.get_mut(index),
+    })
+}
+
+fn remove_path(value: &mut Value, path: &[Segment]) -> bool {
+    let Some((last, parent)) = path.split_last() else { return false };
+    match (get_path_mut(value, parent), last) {
+        (Some(Value::Object(object)), Segment::Key(key)) => object.remove(key).is_some(),
+        (Some(Value::Array(items)), Segment::Index(index)) if *index < items.len() => {
+            items.remove(*index);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn format_path(path: &[Segment]) -> String {
+    path.iter().fold("$".to_string(), |mut out, segment| {
+        match segment {
+            Segment::Key(key) => out.push_str(&format!(".{}", key)),
+            Segment::Index(index) => out.push_str(&format!("[{}]", index)),
+        }
+        out
+    })
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic".to_string())
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(200) {
+        Some((index, _)) => format!("{}…", &text[..index]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract_helpers::ContractLoader;
+
+    fn contract(name: &str) -> Contract {
+        let mut loader = ContractLoader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../contracts"));
+        loader.load_mqtt_contracts().unwrap();
+        loader.get_contract(name).unwrap().clone()
+    }
+
+    #[test]
+    fn test_cases_cover_each_oneof_branch_and_mutation_kind() {
+        let cases = PayloadFuzzer::new(1).with_rounds(1).cases(&contract("notes.command"));
+        for action in ["create", "list", "delete", "update", "dedup", "stats"] {
+            assert!(cases.iter().any(|c| c.description.starts_with(&format!("{}:", action))), "{}", action);
+        }
+        let find = |description: &str| cases.iter().find(|c| c.description == description)
+            .unwrap_or_else(|| panic!("missing case '{}'", description));
+        assert!(find("create: missing required $.note.content").expect_error);
+        assert!(find("stats: wrong type at $.days").expect_error);
+        assert!(find("stats: fractional number at $.days").expect_error);
+        assert!(find("delete: value outside enum at $.action").expect_error);
+        assert!(!find("create: huge string at $.note.content").expect_error);
+        assert!(!find("list: extra field").expect_error);
+        assert!(find("invalid UTF-8").expect_error);
+
+        // Reproductible
+        let again = PayloadFuzzer::new(1).with_rounds(1).cases(&contract("notes.command"));
+        assert_eq!(cases.len(), again.len());
+        assert!(cases.iter().zip(&again).all(|(a, b)| a.payload == b.payload));
+    }
+
+    #[tokio::test]
+    async fn test_run_reports_panics_and_lenient_handlers() {
+        let fuzzer = PayloadFuzzer::new(3).with_rounds(1).with_huge_string_len(1024);
+
+        // Handler qui panique sur les chaînes énormes et accepte tout le reste
+        let report = fuzzer.run(&contract("notes.command"), |payload| async move {
+            assert!(payload.len() < 1024, "payload too large");
+            json!({"type": "success", "request_id": "r", "action": "create", "data": null})
+        }).await;
+        assert!(report.failures.iter().any(|f| f.reason.starts_with("panicked: payload too large")));
+        assert!(report.failures.iter().any(|f| f.case == "empty payload" && f.reason.starts_with("expected an error")));
+        assert!(report.assert_clean().is_err());
+
+        // Handler strict : rejette tout, ne panique jamais
+        let report = fuzzer.run(&contract("notes.command"), |_| async {
+            json!({"type": "error", "request_id": "unknown", "action": "parse", "error": "rejected"})
+        }).await;
+        report.assert_clean().unwrap();
+    }
+}
@@ -3,15 +3,29 @@
 
 Bibliothèque facilitant le développement de plugins Symbion avec:
 - Stubs MQTT pour tests sans broker
+- Broker MQTT embarqué pour tests avec un vrai client (feature `embedded-broker`)
 - Mocks des ports de données
 - Helpers pour contrats JSON
+- Génération de payloads factices conformes aux schémas
+- Fuzzing de payloads invalides à partir des contrats
+- Application de la configuration partagée diffusée par le kernel
 - Clients de développement simplifiés
 */
 
 pub mod mqtt_stub;
 pub mod contract_helpers;
 pub mod test_utils;
+pub mod schema_gen;
+pub mod fuzz;
+pub mod plugin_config;
+#[cfg(feature = "embedded-broker")]
+pub mod embedded_broker;
 
 pub use mqtt_stub::MockMqttClient;
 pub use contract_helpers::{ContractLoader, EventBuilder};
-pub use test_utils::TestHarness;
\ No newline at end of file
+pub use test_utils::TestHarness;
+pub use schema_gen::SchemaGenerator;
+pub use fuzz::PayloadFuzzer;
+pub use plugin_config::PluginConfigWatcher;
+#[cfg(feature = "embedded-broker")]
+pub use embedded_broker::EmbeddedBroker;
\ No newline at end of file
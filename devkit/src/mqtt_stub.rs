@@ -148,7 +148,7 @@ pub struct SymbionMessageBuilder {
 impl SymbionMessageBuilder {
     pub fn new<S: Into<String>>(service: S) -> Self {
         Self {
-            base_topic: format!("symbion/{}", service.into()),
+            base_topic: format!("{}/{}", symbion_topics::PREFIX, service.into()),
         }
     }
 
@@ -0,0 +1,237 @@
+/*!
+Configuration partagée diffusée par le kernel (`symbion/plugins/config@v1`)
+
+Le kernel publie (message retenu) la section `plugin_settings` de kernel.yaml à chaque
+`POST /config/reload`. [`PluginConfigWatcher`] garde la configuration effective d'un
+plugin : sa propre configuration, sur laquelle les réglages diffusés sont fusionnés
+(objets fusionnés récursivement, autres valeurs remplacées, clés inconnues ignorées).
+Le plugin se reconfigure ainsi sans redémarrer.
+
+```no_run
+# use symbion_devkit::PluginConfigWatcher;
+# #[derive(Clone, serde::Serialize, serde::Deserialize)] struct MyConfig { mqtt_password: String }
+# async fn run(client: rumqttc::AsyncClient, mut events: rumqttc::EventLoop) -> anyhow::Result<()> {
+let watcher = PluginConfigWatcher::new(MyConfig { mqtt_password: "initial".into() });
+watcher.subscribe(&client).await?;
+loop {
+    if let rumqttc::Event::Incoming(rumqttc::Packet::Publish(p)) = events.poll().await? {
+        if let Some(config) = watcher.handle_publish(&p.topic, &p.payload)? {
+            // reconfigurer le plugin avec `config`
+        }
+    }
+}
+# }
+```
+*/
+
+use anyhow::{Context, Result};
+use rumqttc::{AsyncClient, QoS};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex};
+
+/// Message `symbion/plugins/config@v1`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigBroadcast {
+    pub config_id: String,
+    /// Broker configuré côté kernel : `{ host, port }`
+    #[serde(default)]
+    pub mqtt: Option<Value>,
+    #[serde(default)]
+    pub settings: Map<String, Value>,
+    pub timestamp: String,
+}
+
+struct WatcherState<T> {
+    config: T,
+    last: Option<ConfigBroadcast>,
+}
+
+/// Configuration effective d'un plugin, mise à jour par les diffusions du kernel
+pub struct PluginConfigWatcher<T> {
+    state: Arc<Mutex<WatcherState<T>>>,
+}
+
+impl<T> Clone for PluginConfigWatcher<T> {
+    fn clone(&self) -> Self {
+        Self { state: self.state.clone() }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Clone> PluginConfigWatcher<T> {
+    /// Part de la configuration propre du plugin
+    pub fn new(initial: T) -> Self {
+        Self { state: Arc::new(Mutex::new(WatcherState { config: initial, last: None })) }
+    }
+
+    /// Topic de diffusion
+    pub fn topic() -> &'static str {
+        symbion_topics::plugins_config()
+    }
+
+    /// S'abonne à la diffusion (la dernière version retenue est reçue aussitôt)
+    pub async fn subscribe(&self, client: &AsyncClient) -> Result<()> {
+        client.subscribe(Self::topic(), QoS::AtLeastOnce).await?;
+        Ok(())
+    }
+
+    /// Configuration effective courante
+    pub fn current(&self) -> T {
+        self.state.lock().unwrap().config.clone()
+    }
+
+    /// Dernière diffusion appliquée
+    pub fn last_broadcast(&self) -> Option<ConfigBroadcast> {
+        self.state.lock().unwrap().last.clone()
+    }
+
+    /// Traite un message reçu : ignore les autres topics, applique la diffusion sinon
+    pub fn handle_publish(&self, topic: &str, payload: &[u8]) -> Result<Option<T>> {
+        if topic != Self::topic() {
+            return Ok(None);
+        }
+        self.apply(payload)
+    }
+
+    /// Applique une diffusion ; retourne la nouvelle configuration, `None` si déjà appliquée.
+    /// En cas d'erreur (payload invalide, réglage du mauvais type), la configuration est conservée.
+    pub fn apply(&self, payload: &[u8]) -> Result<Option<T>> {
+        let broadcast: ConfigBroadcast = serde_json::from_slice(payload)
+            .context("invalid plugins.config payload")?;
+        let mut state = self.state.lock().unwrap();
+        if state.last.as_ref().is_some_and(|last| last.config_id == broadcast.config_id) {
+            return Ok(None);
+        }
+
+        let mut merged = serde_json::to_value(&state.config)?;
+        merge(&mut merged, &Value::Object(broadcast.settings.clone()));
+        let config: T = serde_json::from_value(merged)
+            .with_context(|| format!("shared settings don't fit the plugin config (config_id {})", broadcast.config_id))?;
+
+        log::info!("Configuration partagée appliquée ({} réglages, id {})", broadcast.settings.len(), broadcast.config_id);
+        state.config = config.clone();
+        state.last = Some(broadcast);
+        Ok(Some(config))
+    }
+}
+
+/// Fusion récursive des objets, les autres valeurs sont remplacées
+fn merge(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct NotesConfig {
+        mqtt_username: String,
+        mqtt_password: String,
+        batch_size: u32,
+        display: Display,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Display {
+        locale: String,
+        timezone: String,
+    }
+
+    fn initial() -> NotesConfig {
+        NotesConfig {
+            mqtt_username: "plugins".into(),
+            mqtt_password: "old".into(),
+            batch_size: 50,
+            display: Display { locale: "en".into(), timezone: "UTC".into() },
+        }
+    }
+
+    fn broadcast(config_id: &str, settings: Value) -> Vec<u8> {
+        json!({
+            "config_id": config_id,
+            "mqtt": { "host": "192.168.1.100", "port": 1883 },
+            "settings": settings,
+            "timestamp": "2025-09-01T10:30:00Z"
+        }).to_string().into_bytes()
+    }
+
+    #[test]
+    fn test_broadcast_updates_effective_config() {
+        let watcher = PluginConfigWatcher::new(initial());
+        let payload = broadcast("c1", json!({ "mqtt_password": "rotated", "display": { "locale": "fr" }, "unrelated": true }));
+
+        let updated = watcher.handle_publish(symbion_topics::plugins_config(), &payload).unwrap().unwrap();
+        assert_eq!(updated.mqtt_password, "rotated");
+        assert_eq!(updated.display, Display { locale: "fr".into(), timezone: "UTC".into() });
+        assert_eq!(updated.batch_size, 50);
+        assert_eq!(watcher.current(), updated);
+        assert_eq!(watcher.last_broadcast().unwrap().mqtt.unwrap()["port"], 1883);
+
+        // Même diffusion rejouée (message retenu) : rien à faire
+        assert!(watcher.apply(&payload).unwrap().is_none());
+        // Autre topic : ignoré
+        assert!(watcher.handle_publish("symbion/notes/command@v1", &payload).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_mistyped_setting_keeps_previous_config() {
+        let watcher = PluginConfigWatcher::new(initial());
+        assert!(watcher.apply(&broadcast("c1", json!({ "batch_size": "many" }))).is_err());
+        assert!(watcher.apply(b"not json").is_err());
+        assert_eq!(watcher.current(), initial());
+        assert!(watcher.last_broadcast().is_none());
+    }
+
+    #[cfg(feature = "embedded-broker")]
+    #[tokio::test]
+    async fn test_plugin_receives_retained_broadcast_over_mqtt() {
+        use crate::EmbeddedBroker;
+        use rumqttc::{Event, Packet};
+        use std::time::Duration;
+
+        let broker = EmbeddedBroker::start().await.unwrap();
+        // Le kernel a diffusé avant le démarrage du plugin
+        let (kernel, mut kernel_events) = AsyncClient::new(broker.mqtt_options("kernel"), 10);
+        tokio::spawn(async move { while kernel_events.poll().await.is_ok() {} });
+        kernel.publish(symbion_topics::plugins_config(), QoS::AtLeastOnce, true,
+            broadcast("c1", json!({ "mqtt_password": "rotated" }))).await.unwrap();
+        for _ in 0..100 {
+            if broker.retained(symbion_topics::plugins_config()).is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let watcher = PluginConfigWatcher::new(initial());
+        let (plugin, mut events) = AsyncClient::new(broker.mqtt_options("plugin-notes"), 10);
+        watcher.subscribe(&plugin).await.unwrap();
+        let applied = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Event::Incoming(Packet::Publish(publish)) = events.poll().await.unwrap() {
+                    if let Some(config) = watcher.handle_publish(&publish.topic, &publish.payload).unwrap() {
+                        return config;
+                    }
+                }
+            }
+        }).await.unwrap();
+
+        assert_eq!(applied.mqtt_password, "rotated");
+        assert_eq!(watcher.current().mqtt_password, "rotated");
+        broker.shutdown();
+    }
+}
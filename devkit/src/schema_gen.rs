@@ -0,0 +1,436 @@
+/*!
+Générateur de données factices pilotées par JSON Schema
+
+Produit des payloads aléatoires mais valides à partir du schéma d'un contrat
+(ex: `agents.heartbeat@v1`), pour tester le kernel sans agents réels:
+- Respect des types, enums, bornes numériques, longueurs et `required`
+- Patterns simples supportés (`^[a-f0-9]{12}$`, littéraux, classes, quantifieurs)
+- Générateur seedé: même seed ⇒ mêmes payloads (reproductibilité des tests)
+- Validateur du même sous-ensemble de JSON Schema pour les assertions
+*/
+
+use crate::contract_helpers::{Contract, EventInstance};
+use serde_json::{Map, Number, Value};
+
+/// Borne par défaut des nombres sans `maximum`
+const DEFAULT_NUMBER_MAX: f64 = 1000.0;
+/// Taille maximale par défaut des tableaux sans `maxItems`
+const DEFAULT_MAX_ITEMS: u64 = 4;
+/// Longueur maximale par défaut des chaînes sans `maxLength`
+const DEFAULT_MAX_LENGTH: u64 = 12;
+
+/// Générateur pseudo-aléatoire seedé (splitmix64), sans dépendance externe
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Entier dans [min, max] (bornes incluses)
+    pub fn range_u64(&mut self, min: u64, max: u64) -> u64 {
+        if max <= min {
+            return min;
+        }
+        min + self.next_u64() % (max - min + 1)
+    }
+
+    /// Flottant dans [min, max]
+    pub fn range_f64(&mut self, min: f64, max: f64) -> f64 {
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        min + unit * (max - min)
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.range_f64(0.0, 1.0) < probability
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range_u64(0, items.len() as u64 - 1) as usize]
+    }
+}
+
+/// Génère des valeurs conformes à un JSON Schema
+pub struct SchemaGenerator {
+    rng: SeededRng,
+    /// Probabilité d'inclure une propriété optionnelle
+    optional_probability: f64,
+}
+
+impl SchemaGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self { rng: SeededRng::new(seed), optional_probability: 0.6 }
+    }
+
+    /// Ajuste la probabilité d'inclure les propriétés non requises (0.0 - 1.0)
+    pub fn with_optional_probability(mut self, probability: f64) -> Self {
+        self.optional_probability = probability.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Génère un événement complet (topic + payload) pour un contrat
+    pub fn generate_event(&mut self, contract: &Contract) -> EventInstance {
+        EventInstance {
+            topic: contract.topic.clone(),
+            payload: self.generate(&contract.schema),
+            contract_name: contract.name.clone(),
+        }
+    }
+
+    /// Génère une valeur aléatoire valide pour le schéma
+    pub fn generate(&mut self, schema: &Value) -> Value {
+        if let Some(value) = schema.get("const") {
+            return value.clone();
+        }
+        if let Some(values) = schema.get("enum").and_then(|e| e.as_array()).filter(|e| !e.is_empty()) {
+            return self.rng.pick(values).clone();
+        }
+
+        let schema_type = match schema.get("type") {
+            Some(Value::String(t)) => t.clone(),
+            Some(Value::Array(types)) => {
+                let types: Vec<&str> = types.iter().filter_map(|t| t.as_str()).collect();
+                let non_null: Vec<&str> = types.iter().copied().filter(|t| *t != "null").collect();
+                if non_null.is_empty() || (types.contains(&"null") && self.rng.chance(0.2)) {
+                    "null".to_string()
+                } else {
+                    self.rng.pick(&non_null).to_string()
+                }
+            }
+            _ if schema.get("properties").is_some() => "object".to_string(),
+            _ => "string".to_string(),
+        };
+
+        match schema_type.as_str() {
+            "object" => self.generate_object(schema),
+            "array" => self.generate_array(schema),
+            "integer" => self.generate_integer(schema),
+            "number" => self.generate_number(schema),
+            "boolean" => Value::Bool(self.rng.chance(0.5)),
+            "null" => Value::Null,
+            _ => self.generate_string(schema),
+        }
+    }
+
+    fn generate_object(&mut self, schema: &Value) -> Value {
+        let required = required_fields(schema);
+        let mut object = Map::new();
+        if let Some(props) = schema.get("properties").and_then(|p| p.as_object()) {
+            for (name, prop_schema) in props {
+                if required.contains(&name.as_str()) || self.rng.chance(self.optional_probability) {
+                    object.insert(name.clone(), self.generate(prop_schema));
+                }
+            }
+        }
+        Value::Object(object)
+    }
+
+    fn generate_array(&mut self, schema: &Value) -> Value {
+        let min = schema.get("minItems").and_then(|v| v.as_u64()).unwrap_or(0);
+        let max = schema.get("maxItems").and_then(|v| v.as_u64()).unwrap_or(DEFAULT_MAX_ITEMS.max(min));
+        let count = self.rng.range_u64(min, max);
+        let items = schema.get("items").cloned().unwrap_or(Value::Object(Map::new()));
+        Value::Array((0..count).map(|_| self.generate(&items)).collect())
+    }
+
+    fn generate_integer(&mut self, schema: &Value) -> Value {
+        let min = schema.get("minimum").and_then(|v| v.as_f64()).map(|m| m.ceil() as i64).unwrap_or(0);
+        let max = schema.get("maximum").and_then(|v| v.as_f64()).map(|m| m.floor() as i64)
+            .unwrap_or(min.saturating_add(100_000));
+        let span = max.saturating_sub(min).max(0) as u64;
+        Value::Number(Number::from(min + self.rng.range_u64(0, span) as i64))
+    }
+
+    fn generate_number(&mut self, schema: &Value) -> Value {
+        let min = schema.get("minimum").and_then(|v| v.as_f64()).unwrap_or(0.0);
+        let max = schema.get("maximum").and_then(|v| v.as_f64()).unwrap_or(min + DEFAULT_NUMBER_MAX);
+        // Deux décimales, comme les métriques remontées par les agents
+        let value = (self.rng.range_f64(min, max) * 100.0).round() / 100.0;
+        let value = value.clamp(min, max);
+        Number::from_f64(value).map(Value::Number).unwrap_or(Value::Null)
+    }
+
+    fn generate_string(&mut self, schema: &Value) -> Value {
+        if schema.get("format").and_then(|f| f.as_str()) == Some("date-time") {
+            // Entre 2025-01-01 et 2026-01-01
+            let secs = self.rng.range_u64(1_735_689_600, 1_767_225_600) as i64;
+            let dt = chrono::DateTime::from_timestamp(secs, 0).unwrap_or_default();
+            return Value::String(dt.to_rfc3339_opts(chrono::SecondsFormat::Secs, true));
+        }
+        if let Some(tokens) = schema.get("pattern").and_then(|p| p.as_str()).and_then(parse_pattern) {
+            let mut out = String::new();
+            for token in &tokens {
+                let count = self.rng.range_u64(token.min as u64, token.max as u64);
+                for _ in 0..count {
+                    out.push(*self.rng.pick(&token.chars));
+                }
+            }
+            return Value::String(out);
+        }
+
+        let min = schema.get("minLength").and_then(|v| v.as_u64()).unwrap_or(1);
+        let max = schema.get("maxLength").and_then(|v| v.as_u64()).unwrap_or(DEFAULT_MAX_LENGTH.max(min));
+        let len = self.rng.range_u64(min, max);
+        const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+        Value::String((0..len).map(|_| *self.rng.pick(ALPHABET) as char).collect())
+    }
+}
+
+fn required_fields(schema: &Value) -> Vec<&str> {
+    schema.get("required")
+        .and_then(|r| r.as_array())
+        .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default()
+}
+
+/// Valide une valeur contre le sous-ensemble de JSON Schema supporté par le générateur
+pub fn validate(schema: &Value, value: &Value) -> Result<(), String> {
+    validate_at(schema, value, "$")
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return Err(format!("{}: expected const {}", path, expected));
+        }
+    }
+    if let Some(values) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !values.contains(value) {
+            return Err(format!("{}: {} not in enum", path, value));
+        }
+    }
+
+    if let Some(schema_type) = schema.get("type") {
+        let allowed: Vec<&str> = match schema_type {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+            _ => vec![],
+        };
+        if !allowed.iter().any(|t| matches_type(t, value)) {
+            return Err(format!("{}: expected type {:?}, got {}", path, allowed, value));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            for field in required_fields(schema) {
+                if !object.contains_key(field) {
+                    return Err(format!("{}: missing required field '{}'", path, field));
+                }
+            }
+            if let Some(props) = schema.get("properties").and_then(|p| p.as_object()) {
+                for (name, prop_value) in object {
+                    if let Some(prop_schema) = props.get(name) {
+                        validate_at(prop_schema, prop_value, &format!("{}.{}", path, name))?;
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            let len = items.len() as u64;
+            if schema.get("minItems").and_then(|v| v.as_u64()).is_some_and(|min| len < min) {
+                return Err(format!("{}: fewer than minItems", path));
+            }
+            if schema.get("maxItems").and_then(|v| v.as_u64()).is_some_and(|max| len > max) {
+                return Err(format!("{}: more than maxItems", path));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item_schema, item, &format!("{}[{}]", path, i))?;
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if schema.get("minimum").and_then(|v| v.as_f64()).is_some_and(|min| n < min) {
+                return Err(format!("{}: {} below minimum", path, n));
+            }
+            if schema.get("maximum").and_then(|v| v.as_f64()).is_some_and(|max| n > max) {
+                return Err(format!("{}: {} above maximum", path, n));
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if schema.get("minLength").and_then(|v| v.as_u64()).is_some_and(|min| len < min) {
+                return Err(format!("{}: shorter than minLength", path));
+            }
+            if schema.get("maxLength").and_then(|v| v.as_u64()).is_some_and(|max| len > max) {
+                return Err(format!("{}: longer than maxLength", path));
+            }
+            if schema.get("format").and_then(|f| f.as_str()) == Some("date-time")
+                && chrono::DateTime::parse_from_rfc3339(s).is_err()
+            {
+                return Err(format!("{}: '{}' is not a date-time", path, s));
+            }
+            if let Some(tokens) = schema.get("pattern").and_then(|p| p.as_str()).and_then(parse_pattern) {
+                if !matches_pattern(&tokens, s) {
+                    return Err(format!("{}: '{}' does not match pattern", path, s));
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn matches_type(schema_type: &str, value: &Value) -> bool {
+    match schema_type {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// Élément de pattern: un ensemble de caractères répété entre min et max fois
+#[derive(Debug)]
+struct PatternToken {
+    chars: Vec<char>,
+    min: usize,
+    max: usize,
+}
+
+/// Parse un pattern ancré simple (`^...$`): littéraux, classes `[a-z0-9]`, quantifieurs `{n}`/`{n,m}`.
+/// Retourne None pour les patterns hors de ce sous-ensemble (ignorés).
+fn parse_pattern(pattern: &str) -> Option<Vec<PatternToken>> {
+    let body = pattern.strip_prefix('^')?.strip_suffix('$')?;
+    let mut chars = body.chars().peekable();
+    let mut tokens: Vec<PatternToken> = Vec::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                let mut set = Vec::new();
+                let mut prev: Option<char> = None;
+                loop {
+                    match chars.next()? {
+                        ']' => break,
+                        '-' if prev.is_some() && chars.peek() != Some(&']') => {
+                            let end = chars.next()?;
+                            let start = prev.take()?;
+                            set.extend((start as u32 + 1..=end as u32).filter_map(char::from_u32));
+                        }
+                        c => {
+                            set.push(c);
+                            prev = Some(c);
+                        }
+                    }
+                }
+                tokens.push(PatternToken { chars: set, min: 1, max: 1 });
+            }
+            '{' => {
+                let mut spec = String::new();
+                loop {
+                    match chars.next()? {
+                        '}' => break,
+                        c => spec.push(c),
+                    }
+                }
+                let (min, max) = match spec.split_once(',') {
+                    Some((min, max)) => (min.trim().parse().ok()?, max.trim().parse().ok()?),
+                    None => {
+                        let n = spec.trim().parse().ok()?;
+                        (n, n)
+                    }
+                };
+                let last = tokens.last_mut()?;
+                last.min = min;
+                last.max = max;
+            }
+            '\\' => tokens.push(PatternToken { chars: vec![chars.next()?], min: 1, max: 1 }),
+            '.' | '*' | '+' | '?' | '(' | ')' | '|' => return None,
+            c => tokens.push(PatternToken { chars: vec![c], min: 1, max: 1 }),
+        }
+    }
+    Some(tokens)
+}
+
+fn matches_pattern(tokens: &[PatternToken], s: &str) -> bool {
+    fn go(tokens: &[PatternToken], s: &[char]) -> bool {
+        let Some((token, rest)) = tokens.split_first() else {
+            return s.is_empty();
+        };
+        let available = s.iter().take(token.max).take_while(|c| token.chars.contains(c)).count();
+        (token.min..=available).rev().any(|n| go(rest, &s[n..]))
+    }
+    let chars: Vec<char> = s.chars().collect();
+    go(tokens, &chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract_helpers::ContractLoader;
+
+    fn heartbeat_contract() -> Contract {
+        let mut loader = ContractLoader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../contracts"));
+        loader.load_mqtt_contracts().unwrap();
+        loader.get_contract("agents.heartbeat").unwrap().clone()
+    }
+
+    fn heartbeat_schema() -> Value {
+        heartbeat_contract().schema
+    }
+
+    #[test]
+    fn test_generated_heartbeats_validate_against_contract() {
+        let schema = heartbeat_schema();
+        for seed in 0..200 {
+            let payload = SchemaGenerator::new(seed).generate(&schema);
+            if let Err(e) = validate(&schema, &payload) {
+                panic!("seed {} produced invalid heartbeat: {}\n{}", seed, e, payload);
+            }
+            let agent_id = payload["agent_id"].as_str().unwrap();
+            assert_eq!(agent_id.len(), 12);
+            assert!(agent_id.chars().all(|c| c.is_ascii_hexdigit()));
+            assert_eq!(payload["system"]["cpu"]["load_avg"].as_array().unwrap().len(), 3);
+        }
+    }
+
+    #[test]
+    fn test_same_seed_is_reproducible() {
+        let schema = heartbeat_schema();
+        let a = SchemaGenerator::new(42).generate(&schema);
+        let b = SchemaGenerator::new(42).generate(&schema);
+        let c = SchemaGenerator::new(43).generate(&schema);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        let event = SchemaGenerator::new(42).generate_event(&heartbeat_contract());
+        assert_eq!(event.topic, "symbion/agents/heartbeat@v1");
+        assert_eq!(event.payload, a);
+    }
+
+    #[test]
+    fn test_validator_rejects_invalid_payloads() {
+        let schema = heartbeat_schema();
+        let mut payload = SchemaGenerator::new(7).with_optional_probability(1.0).generate(&schema);
+        assert!(validate(&schema, &payload).is_ok());
+
+        payload["agent_id"] = Value::String("not-a-mac".into());
+        assert!(validate(&schema, &payload).is_err());
+
+        let mut payload = SchemaGenerator::new(7).generate(&schema);
+        payload["system"]["cpu"]["percent"] = serde_json::json!(140.0);
+        assert!(validate(&schema, &payload).is_err());
+
+        let mut payload = SchemaGenerator::new(7).generate(&schema);
+        payload.as_object_mut().unwrap().remove("status");
+        assert!(validate(&schema, &payload).is_err());
+    }
+}
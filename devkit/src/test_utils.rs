@@ -3,7 +3,7 @@ Test Harness pour plugins Symbion
 
 Facilite l'écriture de tests pour plugins avec:
 - Setup automatique des mocks MQTT
-- Assertions sur les événements échangés
+- Assertions sur les événements échangés (et sur ceux qui ne doivent pas l'être)
 - Simulation d'environnement Symbion complet
 */
 
@@ -77,7 +77,7 @@ impl TestHarness {
         let payload = SymbionMessageBuilder::heartbeat_v2(host_id, cpu, ram, ip);
         let payload_bytes = serde_json::to_vec(&payload)?;
         
-        self.mqtt_client.simulate_incoming("symbion/hosts/heartbeat@v2", payload_bytes).await?;
+        self.mqtt_client.simulate_incoming(symbion_topics::hosts_heartbeat(), payload_bytes).await?;
         log::info!("💓 Sent heartbeat for host: {}", host_id);
         Ok(())
     }
@@ -89,7 +89,7 @@ impl TestHarness {
         let payload = SymbionMessageBuilder::wake_v1(host_id, mac, broadcast);
         let payload_bytes = serde_json::to_vec(&payload)?;
         
-        self.mqtt_client.simulate_incoming("symbion/hosts/wake@v1", payload_bytes).await?;
+        self.mqtt_client.simulate_incoming(symbion_topics::hosts_wake(), payload_bytes).await?;
         log::info!("⚡ Sent wake command for host: {}", host_id);
         Ok(())
     }
@@ -101,7 +101,7 @@ impl TestHarness {
         let payload = SymbionMessageBuilder::notes_command_v1(action, data);
         let payload_bytes = serde_json::to_vec(&payload)?;
         
-        self.mqtt_client.simulate_incoming("symbion/notes/command@v1", payload_bytes).await?;
+        self.mqtt_client.simulate_incoming(symbion_topics::notes_command(), payload_bytes).await?;
         log::info!("📝 Sent notes command: {}", action);
         Ok(())
     }
@@ -160,6 +160,34 @@ impl TestHarness {
         anyhow::bail!("Expected message not found on topic: {}", topic);
     }
 
+    /// Assert qu'aucun message n'a été publié sur un topic (wildcards `+`/`#` acceptés)
+    pub fn assert_no_messages(&self, topic: &str) -> Result<()> {
+        let unexpected: Vec<String> = self.mqtt_client.get_published_messages()
+            .into_iter()
+            .filter(|msg| rumqttc::matches(&msg.topic, topic))
+            .map(|msg| msg.topic)
+            .collect();
+
+        if !unexpected.is_empty() {
+            anyhow::bail!("Expected no messages on '{}', got {}: {:?}", topic, unexpected.len(), unexpected);
+        }
+
+        log::info!("✅ No messages on {}", topic);
+        Ok(())
+    }
+
+    /// Assert le nombre total de messages publiés, tous topics confondus
+    pub fn assert_total_messages(&self, expected: usize) -> Result<()> {
+        let stats = self.get_stats();
+        if stats.total_messages != expected {
+            anyhow::bail!("Expected {} messages in total, got {}: {:?}",
+                         expected, stats.total_messages, stats.topic_counts);
+        }
+
+        log::info!("✅ {} messages in total as expected", expected);
+        Ok(())
+    }
+
     /// Assert qu'un champ spécifique existe dans le dernier message
     pub fn assert_field_exists(&self, topic: &str, field_path: &str) -> Result<()> {
         if let Some(msg) = self.mqtt_client.get_last_json_message::<Value>(topic)? {
@@ -262,8 +290,16 @@ macro_rules! plugin_test {
         async fn $name() {
             use $crate::test_utils::TestHarness;
             
+            // Le futur emprunte le harness pour la durée du test : signature explicite
+            fn borrow_harness<F>(f: F) -> F
+            where
+                F: for<'a> Fn(&'a mut TestHarness) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>> + 'a>>,
+            {
+                f
+            }
+
             let mut harness = TestHarness::new().with_contracts().await.unwrap();
-            let test_fn: Box<dyn Fn(&mut TestHarness) -> std::pin::Pin<Box<dyn std::future::Future<Output = anyhow::Result<()>>>>> = Box::new($body);
+            let test_fn = borrow_harness($body);
             
             match test_fn(&mut harness).await {
                 Ok(_) => {
@@ -305,6 +341,35 @@ mod tests {
         assert_eq!(stats.total_messages, 1);
     }
 
+    /// Plugin fictif qui répond deux fois à chaque commande et publie un événement parasite
+    async fn over_publishing_plugin(harness: &TestHarness) -> Result<()> {
+        let response = serde_json::to_vec(&serde_json::json!({"status": "ok"}))?;
+        harness.mqtt_client.publish(symbion_topics::notes_response(), rumqttc::QoS::AtLeastOnce, false, response.clone()).await?;
+        harness.mqtt_client.publish(symbion_topics::notes_response(), rumqttc::QoS::AtLeastOnce, false, response).await?;
+        harness.mqtt_client.publish("symbion/notes/debug", rumqttc::QoS::AtMostOnce, false, b"{}".to_vec()).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_over_publishing_plugin_fails_unexpected_publish_assertions() {
+        let harness = TestHarness::new();
+        harness.assert_total_messages(0).unwrap();
+        harness.assert_no_messages("symbion/notes/#").unwrap();
+
+        over_publishing_plugin(&harness).await.unwrap();
+
+        // Double réponse détectée
+        let error = harness.assert_total_messages(1).unwrap_err().to_string();
+        assert!(error.contains("got 3"), "{}", error);
+        // Publication parasite détectée, y compris via wildcard
+        assert!(harness.assert_no_messages("symbion/notes/debug").is_err());
+        let error = harness.assert_no_messages("symbion/notes/+").unwrap_err().to_string();
+        assert!(error.contains("got 3"), "{}", error);
+        // Les topics non touchés restent propres
+        harness.assert_no_messages(symbion_topics::hosts_heartbeat()).unwrap();
+        harness.assert_total_messages(3).unwrap();
+    }
+
     // Test avec la macro
     plugin_test!(test_macro_functionality, |harness: &mut TestHarness| {
         Box::pin(async move {
@@ -5,14 +5,17 @@
 //! - Process control (list, kill, monitor)
 //! - Command execution (shell commands with timeout)
 //! - Service management (systemd, Windows services)
+//! - GPU metrics (nvidia-smi, amdgpu sysfs)
 //! - File operations (future extension)
+//! - Command catalog (supported command types, parameters, availability)
 
+use crate::scheduler::CommandPriority;
 use serde::{Deserialize, Serialize};
 use std::process::Command;
 use tracing::debug;
 
 /// Supported capability types
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum CapabilityType {
     PowerManagement,
@@ -21,14 +24,70 @@ pub enum CapabilityType {
     SystemMetrics,
     ServiceManagement,
     FileOperations,
+    GpuMetrics,
+}
+
+impl CapabilityType {
+    /// Capability name as advertised in registration
+    pub fn name(self) -> &'static str {
+        match self {
+            CapabilityType::PowerManagement => "power_management",
+            CapabilityType::ProcessControl => "process_control",
+            CapabilityType::CommandExecution => "command_execution",
+            CapabilityType::SystemMetrics => "system_metrics",
+            CapabilityType::ServiceManagement => "service_management",
+            CapabilityType::FileOperations => "file_operations",
+            CapabilityType::GpuMetrics => "gpu_metrics",
+        }
+    }
 }
 
 /// Capability detection result
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CapabilityInfo {
+    #[serde(rename = "capability")]
     pub capability_type: CapabilityType,
     pub available: bool,
     pub reason: Option<String>,
+    /// Tools found on the host backing this capability (shutdown, systemctl, bash...)
+    pub tools: Vec<String>,
+}
+
+/// Capability missing on this host, reported at registration
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnavailableCapability {
+    pub capability: &'static str,
+    pub reason: Option<String>,
+}
+
+/// Detected gaps, leaving out capabilities the agent advertises anyway
+pub fn unavailable_capabilities(detected: &[CapabilityInfo], advertised: &[String]) -> Vec<UnavailableCapability> {
+    detected.iter()
+        .filter(|c| !c.available && !advertised.iter().any(|a| a == c.capability_type.name()))
+        .map(|c| UnavailableCapability { capability: c.capability_type.name(), reason: c.reason.clone() })
+        .collect()
+}
+
+/// Elevation settings relevant to privileged commands
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ElevationStatus {
+    pub auto_elevate: bool,
+    pub credentials_stored: bool,
+}
+
+/// Full capability report returned by the `describe` command
+#[derive(Debug, Clone, Serialize)]
+pub struct CapabilityReport {
+    pub capabilities: Vec<CapabilityInfo>,
+    pub available_count: usize,
+    pub elevation: ElevationStatus,
+}
+
+impl CapabilityReport {
+    pub fn new(capabilities: Vec<CapabilityInfo>, elevation: ElevationStatus) -> Self {
+        let available_count = capabilities.iter().filter(|c| c.available).count();
+        Self { capabilities, available_count, elevation }
+    }
 }
 
 /// Cross-platform capability detector
@@ -39,14 +98,15 @@ impl CapabilityDetector {
     pub async fn detect_all() -> Vec<CapabilityInfo> {
         debug!("Detecting system capabilities...");
         
-        let mut capabilities = Vec::new();
-        
-        capabilities.push(Self::detect_power_management().await);
-        capabilities.push(Self::detect_process_control().await);
-        capabilities.push(Self::detect_command_execution().await);
-        capabilities.push(Self::detect_system_metrics().await);
-        capabilities.push(Self::detect_service_management().await);
-        capabilities.push(Self::detect_file_operations().await);
+        let capabilities = vec![
+            Self::detect_power_management().await,
+            Self::detect_process_control().await,
+            Self::detect_command_execution().await,
+            Self::detect_system_metrics().await,
+            Self::detect_service_management().await,
+            Self::detect_file_operations().await,
+            Self::detect_gpu_metrics().await,
+        ];
         
         let available_count = capabilities.iter().filter(|c| c.available).count();
         debug!("Detected {}/{} capabilities available", available_count, capabilities.len());
@@ -54,37 +114,19 @@ impl CapabilityDetector {
         capabilities
     }
     
-    /// Get list of available capability names for registration
-    pub async fn get_available_capabilities() -> Vec<String> {
-        Self::detect_all().await
-            .into_iter()
-            .filter(|c| c.available)
-            .map(|c| match c.capability_type {
-                CapabilityType::PowerManagement => "power_management",
-                CapabilityType::ProcessControl => "process_control", 
-                CapabilityType::CommandExecution => "command_execution",
-                CapabilityType::SystemMetrics => "system_metrics",
-                CapabilityType::ServiceManagement => "service_management",
-                CapabilityType::FileOperations => "file_operations",
-            })
-            .map(String::from)
-            .collect()
-    }
-    
     /// Detect power management capabilities
     async fn detect_power_management() -> CapabilityInfo {
-        let available = if cfg!(target_os = "linux") {
+        let tools = if cfg!(target_os = "linux") {
             // Check for shutdown command and systemctl
-            Self::command_exists("shutdown").await || Self::command_exists("systemctl").await
+            Self::existing_commands(&["shutdown", "systemctl"]).await
         } else if cfg!(target_os = "windows") {
             // Windows has built-in shutdown command
-            Self::command_exists("shutdown").await
-        } else if cfg!(target_os = "android") {
-            // Android in Termux might have limited power control
-            false
+            Self::existing_commands(&["shutdown"]).await
         } else {
-            false
+            // Android in Termux might have limited power control
+            Vec::new()
         };
+        let available = !tools.is_empty();
         
         let reason = if !available {
             Some("No power management commands found".to_string())
@@ -96,20 +138,21 @@ impl CapabilityDetector {
             capability_type: CapabilityType::PowerManagement,
             available,
             reason,
+            tools,
         }
     }
     
     /// Detect process control capabilities
     async fn detect_process_control() -> CapabilityInfo {
-        let available = if cfg!(target_os = "linux") {
-            Self::command_exists("ps").await && Self::command_exists("kill").await
-        } else if cfg!(target_os = "windows") {
-            Self::command_exists("tasklist").await && Self::command_exists("taskkill").await
-        } else if cfg!(target_os = "android") {
-            Self::command_exists("ps").await && Self::command_exists("kill").await
+        let required: &[&str] = if cfg!(target_os = "windows") {
+            &["tasklist", "taskkill"]
+        } else if cfg!(any(target_os = "linux", target_os = "android")) {
+            &["ps", "kill"]
         } else {
-            false
+            &[]
         };
+        let tools = Self::existing_commands(required).await;
+        let available = !required.is_empty() && tools.len() == required.len();
         
         let reason = if !available {
             Some("Process control commands not found".to_string())
@@ -121,20 +164,22 @@ impl CapabilityDetector {
             capability_type: CapabilityType::ProcessControl,
             available,
             reason,
+            tools,
         }
     }
     
     /// Detect command execution capabilities
     async fn detect_command_execution() -> CapabilityInfo {
-        let available = if cfg!(target_os = "linux") {
-            Self::command_exists("bash").await || Self::command_exists("sh").await
+        let tools = if cfg!(target_os = "linux") {
+            Self::existing_commands(&["bash", "sh"]).await
         } else if cfg!(target_os = "windows") {
-            Self::command_exists("cmd").await || Self::command_exists("powershell").await
+            Self::existing_commands(&["cmd", "powershell"]).await
         } else if cfg!(target_os = "android") {
-            Self::command_exists("sh").await
+            Self::existing_commands(&["sh"]).await
         } else {
-            false
+            Vec::new()
         };
+        let available = !tools.is_empty();
         
         let reason = if !available {
             Some("No shell interpreters found".to_string())
@@ -146,6 +191,7 @@ impl CapabilityDetector {
             capability_type: CapabilityType::CommandExecution,
             available,
             reason,
+            tools,
         }
     }
     
@@ -155,18 +201,20 @@ impl CapabilityDetector {
             capability_type: CapabilityType::SystemMetrics,
             available: true,
             reason: None,
+            tools: Vec::new(),
         }
     }
     
     /// Detect service management capabilities
     async fn detect_service_management() -> CapabilityInfo {
-        let available = if cfg!(target_os = "linux") {
-            Self::command_exists("systemctl").await
+        let tools = if cfg!(target_os = "linux") {
+            Self::existing_commands(&["systemctl"]).await
         } else if cfg!(target_os = "windows") {
-            Self::command_exists("sc").await || Self::command_exists("net").await
+            Self::existing_commands(&["sc", "net"]).await
         } else {
-            false
+            Vec::new()
         };
+        let available = !tools.is_empty();
         
         let reason = if !available {
             Some("Service management tools not found".to_string())
@@ -178,6 +226,7 @@ impl CapabilityDetector {
             capability_type: CapabilityType::ServiceManagement,
             available,
             reason,
+            tools,
         }
     }
     
@@ -187,11 +236,39 @@ impl CapabilityDetector {
             capability_type: CapabilityType::FileOperations,
             available: false,
             reason: Some("File operations not implemented yet".to_string()),
+            tools: Vec::new(),
         }
     }
     
+    /// GPU metrics: nvidia-smi in PATH or an amdgpu device in sysfs
+    async fn detect_gpu_metrics() -> CapabilityInfo {
+        let mut tools = Self::existing_commands(&["nvidia-smi"]).await;
+        if crate::gpu::amd_gpu_present() {
+            tools.push("amdgpu".to_string());
+        }
+        let available = !tools.is_empty();
+
+        CapabilityInfo {
+            capability_type: CapabilityType::GpuMetrics,
+            available,
+            reason: (!available).then(|| "No NVIDIA or AMD GPU found".to_string()),
+            tools,
+        }
+    }
+    
+    /// Subset of the given commands present in PATH
+    async fn existing_commands(commands: &[&str]) -> Vec<String> {
+        let mut found = Vec::new();
+        for command in commands {
+            if Self::command_exists(command).await {
+                found.push(command.to_string());
+            }
+        }
+        found
+    }
+    
     /// Check if a command exists in PATH
-    async fn command_exists(command: &str) -> bool {
+    pub async fn command_exists(command: &str) -> bool {
         let check_command = if cfg!(target_os = "windows") {
             Command::new("where").arg(command).output()
         } else {
@@ -205,6 +282,183 @@ impl CapabilityDetector {
     }
 }
 
+/// Command types handled by the agent - single source of truth for
+/// `process_command` dispatch and the `list_commands` catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandKind {
+    Shutdown,
+    Reboot,
+    Hibernate,
+    KillProcess,
+    RunCommand,
+    GetMetrics,
+    ListProcesses,
+    RelayWake,
+    ListCommands,
+    SetCron,
+    ListCron,
+    RemoveCron,
+    Describe,
+    TailFile,
+    NetCheck,
+    ReadFile,
+    GetEnv,
+    SetEnv,
+    ListListeners,
+    Renice,
+    SetAffinity,
+    Ping,
+    FirewallList,
+    FirewallAdd,
+    FirewallRemove,
+    Diagnostics,
+    SetPowerSchedule,
+}
+
+/// Static description of a command type
+#[derive(Debug, Clone, Copy)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub required_parameters: &'static [&'static str],
+    pub optional_parameters: &'static [&'static str],
+    /// Capability that must be advertised for the command to work (None = always available)
+    pub capability: Option<&'static str>,
+    /// Commands in the same group never run concurrently (None = independent)
+    pub conflict_group: Option<&'static str>,
+    /// Default scheduling priority when the kernel doesn't set one
+    pub priority: CommandPriority,
+    pub description: &'static str,
+}
+
+/// Catalog entry returned by `list_commands`
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandCatalogEntry {
+    pub command_type: &'static str,
+    pub required_parameters: &'static [&'static str],
+    pub optional_parameters: &'static [&'static str],
+    pub capability: Option<&'static str>,
+    pub available: bool,
+    pub description: &'static str,
+}
+
+impl CommandKind {
+    pub const ALL: &'static [CommandKind] = &[
+        CommandKind::Shutdown,
+        CommandKind::Reboot,
+        CommandKind::Hibernate,
+        CommandKind::KillProcess,
+        CommandKind::RunCommand,
+        CommandKind::GetMetrics,
+        CommandKind::ListProcesses,
+        CommandKind::RelayWake,
+        CommandKind::ListCommands,
+        CommandKind::SetCron,
+        CommandKind::ListCron,
+        CommandKind::RemoveCron,
+        CommandKind::Describe,
+        CommandKind::TailFile,
+        CommandKind::NetCheck,
+        CommandKind::ReadFile,
+        CommandKind::GetEnv,
+        CommandKind::SetEnv,
+        CommandKind::ListListeners,
+        CommandKind::Renice,
+        CommandKind::SetAffinity,
+        CommandKind::Ping,
+        CommandKind::FirewallList,
+        CommandKind::FirewallAdd,
+        CommandKind::FirewallRemove,
+        CommandKind::Diagnostics,
+        CommandKind::SetPowerSchedule,
+    ];
+
+    pub fn spec(self) -> CommandSpec {
+        let spec = |name, required_parameters, optional_parameters, capability, description| CommandSpec {
+            name, required_parameters, optional_parameters, capability, conflict_group: None,
+            priority: CommandPriority::Normal, description,
+        };
+        // Urgent commands jump ahead of queued ones, informational ones yield
+        let urgent = |spec: CommandSpec| CommandSpec { priority: CommandPriority::High, ..spec };
+        let background = |spec: CommandSpec| CommandSpec { priority: CommandPriority::Low, ..spec };
+        // Power transitions are mutually exclusive
+        let power = |name, description| CommandSpec {
+            conflict_group: Some("power"),
+            priority: CommandPriority::High,
+            ..spec(name, &[], &["message", "warn_seconds"], Some("power_management"), description)
+        };
+        // Crontab / Task Scheduler edits are read-modify-write
+        let cron = |name, required_parameters, description| CommandSpec {
+            conflict_group: Some("cron"),
+            ..spec(name, required_parameters, &[], Some("scheduled_tasks"), description)
+        };
+        // Rule changes are serialized so a list never sees half an update
+        let firewall = |name, required_parameters, optional_parameters, description| CommandSpec {
+            conflict_group: Some("firewall"),
+            ..spec(name, required_parameters, optional_parameters, Some("firewall_management"), description)
+        };
+        match self {
+            CommandKind::Shutdown => power("shutdown", "Power off the host"),
+            CommandKind::Reboot => power("reboot", "Restart the host"),
+            CommandKind::Hibernate => power("hibernate", "Hibernate the host"),
+            CommandKind::KillProcess => urgent(spec("kill_process", &["pid"], &[], Some("process_control"), "Terminate a process by PID")),
+            CommandKind::RunCommand => spec("run_command", &["command"], &["stream", "stdin", "stdin_encoding"], Some("command_execution"), "Run an allow-listed shell command, optionally fed stdin (stream: output published on agents.output@v1)"),
+            CommandKind::GetMetrics => background(spec("get_metrics", &[], &[], Some("system_metrics"), "Collect system, process and service metrics")),
+            CommandKind::ListProcesses => background(spec("list_processes", &[], &[], Some("process_control"), "List top processes by CPU and memory")),
+            CommandKind::RelayWake => spec("relay_wake", &["mac"], &["broadcast"], Some("wol_relay"), "Send a Wake-on-LAN packet on the local subnet"),
+            CommandKind::ListCommands => background(spec("list_commands", &[], &[], None, "List supported command types")),
+            CommandKind::SetCron => cron("set_cron", &["id", "schedule", "command"], "Install a recurring allow-listed command"),
+            CommandKind::ListCron => cron("list_cron", &[], "List scheduled tasks installed by the agent"),
+            CommandKind::RemoveCron => cron("remove_cron", &["id"], "Remove a scheduled task"),
+            CommandKind::Describe => background(spec("describe", &[], &[], None, "Detailed capability detection with reasons and elevation settings")),
+            CommandKind::TailFile => spec("tail_file", &["path"], &["lines", "follow_secs", "rate_limit_kbps"], Some("file_read"), "Last lines of an allow-listed file, optionally followed for a bounded time"),
+            CommandKind::NetCheck => spec("net_check", &[], &["targets"], None, "Probe gateway, DNS, configured URL or allow-listed targets for reachability and latency"),
+            CommandKind::ReadFile => spec("read_file", &["path"], &["offset", "rate_limit_kbps"], Some("file_read"), "Chunked transfer of an allow-listed file, optionally rate-limited"),
+            CommandKind::GetEnv => spec("get_env", &[], &["keys"], Some("env_management"), "Read persistent values of allow-listed environment keys"),
+            CommandKind::SetEnv => CommandSpec {
+                // Profile script / registry edits are read-modify-write
+                conflict_group: Some("env"),
+                ..spec("set_env", &["values"], &[], Some("env_management"), "Persist allow-listed environment values (null removes a key)")
+            },
+            CommandKind::ListListeners => background(spec("list_listeners", &[], &[], None, "List listening TCP/UDP sockets with their owning process")),
+            // Taming a runaway process must not wait behind the commands it slows down
+            CommandKind::Renice => urgent(spec("renice", &["pid", "nice"], &[], Some("process_control"), "Set the scheduling priority (nice -20..19) of a process")),
+            CommandKind::SetAffinity => urgent(spec("set_affinity", &["pid", "cpus"], &[], Some("process_control"), "Pin a process to a list of CPUs")),
+            // Answered ahead of queued work: measures the round-trip, not the backlog
+            CommandKind::Ping => urgent(spec("ping", &[], &[], None, "Immediate pong to measure the kernel round-trip")),
+            CommandKind::FirewallList => firewall("firewall_list", &[], &[], "List active host firewall rules (ufw, iptables or netsh)"),
+            // Blocking an attacker must not wait behind routine work
+            CommandKind::FirewallAdd => urgent(firewall("firewall_add", &["rule"], &["dry_run"], "Add a validated allow/deny rule (dry_run returns the planned command)")),
+            CommandKind::FirewallRemove => firewall("firewall_remove", &["rule"], &["dry_run"], "Remove a rule previously added with the same fields"),
+            CommandKind::Diagnostics => spec("diagnostics", &[], &[], None, "Self-check of config, MQTT link, capabilities, disk and elevation"),
+            CommandKind::SetPowerSchedule => spec("set_power_schedule", &[],
+                &["enabled", "idle_minutes", "start_hour", "end_hour", "warn_seconds", "maintenance"], Some("power_management"),
+                "Shut down automatically after idle_minutes without user input within allowed hours (maintenance pauses it)"),
+        }
+    }
+
+    /// Resolve a `command_type` string
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.spec().name == name)
+    }
+}
+
+/// Build the command catalog against the capabilities this agent advertises
+pub fn command_catalog(available_capabilities: &[String]) -> Vec<CommandCatalogEntry> {
+    CommandKind::ALL.iter()
+        .map(|kind| {
+            let spec = kind.spec();
+            CommandCatalogEntry {
+                command_type: spec.name,
+                required_parameters: spec.required_parameters,
+                optional_parameters: spec.optional_parameters,
+                capability: spec.capability,
+                available: spec.capability.is_none_or(|cap| available_capabilities.iter().any(|c| c == cap)),
+                description: spec.description,
+            }
+        })
+        .collect()
+}
+
 /// Platform-specific capability implementations
 pub mod linux {
     use super::*;
@@ -282,7 +536,143 @@ mod tests {
     
     #[tokio::test]
     async fn test_available_capabilities_list() {
-        let available = CapabilityDetector::get_available_capabilities().await;
-        assert!(available.contains(&"system_metrics".to_string()));
+        let available: Vec<_> = CapabilityDetector::detect_all().await
+            .into_iter()
+            .filter(|c| c.available)
+            .map(|c| c.capability_type.name())
+            .collect();
+        assert!(available.contains(&"system_metrics"));
+    }
+    
+    #[test]
+    fn test_unavailable_capabilities_keep_reasons() {
+        let info = |capability_type, available, reason: Option<&str>| CapabilityInfo {
+            capability_type, available, reason: reason.map(str::to_string), tools: Vec::new(),
+        };
+        let detected = [
+            info(CapabilityType::SystemMetrics, true, None),
+            info(CapabilityType::GpuMetrics, false, Some("No NVIDIA or AMD GPU found")),
+            info(CapabilityType::ServiceManagement, false, Some("Service management tools not found")),
+        ];
+        let advertised = vec!["system_metrics".to_string(), "service_management".to_string()];
+
+        // service_management is advertised: not reported as a gap
+        assert_eq!(unavailable_capabilities(&detected, &advertised), vec![UnavailableCapability {
+            capability: "gpu_metrics",
+            reason: Some("No NVIDIA or AMD GPU found".to_string()),
+        }]);
     }
-}
\ No newline at end of file
+    
+    /// Exhaustive on purpose: adding a CommandKind without listing it here fails to compile
+    fn command_index(kind: CommandKind) -> usize {
+        match kind {
+            CommandKind::Shutdown => 0,
+            CommandKind::Reboot => 1,
+            CommandKind::Hibernate => 2,
+            CommandKind::KillProcess => 3,
+            CommandKind::RunCommand => 4,
+            CommandKind::GetMetrics => 5,
+            CommandKind::ListProcesses => 6,
+            CommandKind::RelayWake => 7,
+            CommandKind::ListCommands => 8,
+            CommandKind::SetCron => 9,
+            CommandKind::ListCron => 10,
+            CommandKind::RemoveCron => 11,
+            CommandKind::Describe => 12,
+            CommandKind::TailFile => 13,
+            CommandKind::NetCheck => 14,
+            CommandKind::ReadFile => 15,
+            CommandKind::GetEnv => 16,
+            CommandKind::SetEnv => 17,
+            CommandKind::ListListeners => 18,
+            CommandKind::Renice => 19,
+            CommandKind::SetAffinity => 20,
+            CommandKind::Ping => 21,
+            CommandKind::FirewallList => 22,
+            CommandKind::FirewallAdd => 23,
+            CommandKind::FirewallRemove => 24,
+            CommandKind::Diagnostics => 25,
+            CommandKind::SetPowerSchedule => 26,
+        }
+    }
+    
+    #[test]
+    fn test_catalog_covers_every_handled_command() {
+        let mut indexes: Vec<usize> = CommandKind::ALL.iter().map(|k| command_index(*k)).collect();
+        indexes.sort();
+        assert_eq!(indexes, (0..27).collect::<Vec<_>>());
+        
+        // Every catalog name resolves back to its kind (names are unique)
+        for kind in CommandKind::ALL {
+            assert_eq!(CommandKind::from_name(kind.spec().name), Some(*kind));
+        }
+        assert_eq!(CommandKind::from_name("format_disk"), None);
+        
+        // Power transitions conflict with each other, not with the rest
+        assert_eq!(CommandKind::Shutdown.spec().conflict_group, CommandKind::Reboot.spec().conflict_group);
+        assert!(CommandKind::Shutdown.spec().conflict_group.is_some());
+        assert!(CommandKind::RunCommand.spec().conflict_group.is_none());
+    }
+
+    #[test]
+    fn test_power_commands_outrank_metrics() {
+        assert_eq!(CommandKind::Shutdown.spec().priority, CommandPriority::High);
+        assert_eq!(CommandKind::RunCommand.spec().priority, CommandPriority::Normal);
+        assert_eq!(CommandKind::GetMetrics.spec().priority, CommandPriority::Low);
+        assert!(CommandKind::Shutdown.spec().priority > CommandKind::GetMetrics.spec().priority);
+    }
+    
+    #[test]
+    fn test_catalog_availability_follows_capabilities() {
+        let catalog = command_catalog(&["system_metrics".to_string(), "wol_relay".to_string()]);
+        assert_eq!(catalog.len(), CommandKind::ALL.len());
+        
+        let entry = |name: &str| catalog.iter().find(|e| e.command_type == name).unwrap();
+        assert!(entry("get_metrics").available);
+        assert!(entry("relay_wake").available);
+        assert_eq!(entry("relay_wake").required_parameters, &["mac"]);
+        assert!(entry("list_commands").available);
+        assert!(!entry("shutdown").available);
+        assert!(!entry("kill_process").available);
+    }
+    
+    #[test]
+    fn test_capability_report_maps_detector_output() {
+        let detected = vec![
+            CapabilityInfo {
+                capability_type: CapabilityType::PowerManagement,
+                available: true,
+                reason: None,
+                tools: vec!["shutdown".to_string(), "systemctl".to_string()],
+            },
+            CapabilityInfo {
+                capability_type: CapabilityType::ServiceManagement,
+                available: false,
+                reason: Some("Service management tools not found".to_string()),
+                tools: Vec::new(),
+            },
+        ];
+        let report = CapabilityReport::new(detected, ElevationStatus { auto_elevate: true, credentials_stored: false });
+        assert_eq!(report.available_count, 1);
+        
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["capabilities"][0]["capability"], "power_management");
+        assert_eq!(json["capabilities"][0]["tools"], serde_json::json!(["shutdown", "systemctl"]));
+        assert!(json["capabilities"][0]["reason"].is_null());
+        assert_eq!(json["capabilities"][1]["capability"], "service_management");
+        assert_eq!(json["capabilities"][1]["available"], false);
+        assert_eq!(json["capabilities"][1]["reason"], "Service management tools not found");
+        assert_eq!(json["elevation"]["auto_elevate"], true);
+        assert_eq!(json["elevation"]["credentials_stored"], false);
+    }
+    
+    #[tokio::test]
+    async fn test_detected_names_match_registration_names() {
+        let detected = CapabilityDetector::detect_all().await;
+        // Unavailable capabilities always explain why
+        assert!(detected.iter().filter(|c| !c.available).all(|c| c.reason.is_some()));
+        let names: Vec<&str> = detected.iter().map(|c| c.capability_type.name()).collect();
+        assert!(names.contains(&"system_metrics"));
+        assert!(names.contains(&"power_management"));
+    }
+}
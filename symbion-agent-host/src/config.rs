@@ -4,6 +4,12 @@
 //! - MQTT broker settings
 //! - Elevation credentials (encrypted)
 //! - Auto-update preferences  
+//! - Command execution limits
+//! - File read allow-list and size caps
+//! - Network self-test targets
+//! - Environment keys operators may push
+//! - Firewall management opt-in and Linux backend
+//! - Idle auto-shutdown policy
 //! - Cross-platform storage
 
 use anyhow::Result;
@@ -17,6 +23,18 @@ pub struct AgentConfig {
     pub elevation: ElevationConfig,  
     pub update: UpdateConfig,
     pub agent: AgentInfo,
+    #[serde(default)]
+    pub commands: CommandsConfig,
+    #[serde(default)]
+    pub file_ops: FileOpsConfig,
+    #[serde(default)]
+    pub net_check: NetCheckConfig,
+    #[serde(default)]
+    pub environment: EnvConfig,
+    #[serde(default)]
+    pub firewall: FirewallConfig,
+    #[serde(default)]
+    pub power_schedule: PowerScheduleConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +59,10 @@ pub struct UpdateConfig {
     pub channel: UpdateChannel,
     pub check_interval_hours: u32,
     pub github_repo: String,
+    /// SHA-256 fingerprints (hex) of trusted public keys (SPKI); empty = system trust only.
+    /// A pin may name any key of the chain, e.g. the issuing CA, so leaf renewals keep working.
+    #[serde(default)]
+    pub pinned_spki_sha256: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +72,176 @@ pub struct AgentInfo {
     pub version: String,
 }
 
+/// Command execution settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandsConfig {
+    /// Maximum number of commands executed concurrently
+    pub max_concurrency: usize,
+    /// Bytes of stdout/stderr kept per command stream, the rest is dropped (`truncated: true`)
+    pub max_output_bytes: usize,
+    /// Size limit of the `stdin` input a `run_command` may carry (decoded bytes)
+    pub max_stdin_bytes: usize,
+    /// Programs `run_command` and scheduled tasks may start, matched against argv[0];
+    /// shell chaining (`;`, `|`, `&`, backticks, `$(`) is refused. Empty = no restriction
+    pub allowed_commands: Vec<String>,
+}
+
+impl Default for CommandsConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: crate::scheduler::DEFAULT_MAX_CONCURRENCY,
+            max_output_bytes: crate::execution::DEFAULT_MAX_OUTPUT_BYTES,
+            max_stdin_bytes: crate::execution::DEFAULT_MAX_STDIN_BYTES,
+            allowed_commands: crate::execution::DEFAULT_ALLOWED_COMMANDS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+/// Read-only file access (`tail_file`, `read_file`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileOpsConfig {
+    /// Directories whose files may be read (nothing outside is reachable)
+    pub allowed_roots: Vec<String>,
+    /// Maximum bytes read from the end of a file, and per follow poll
+    pub max_read_bytes: u64,
+    /// Maximum number of lines returned by a tail
+    pub max_lines: usize,
+    /// Maximum follow duration in seconds (kept under the kernel command timeout)
+    pub max_follow_secs: u64,
+    /// Chunk size of `read_file` transfers (smaller when rate-limited below one chunk per second)
+    pub chunk_bytes: u64,
+    /// Maximum duration of one `read_file` call; the rest is fetched with `offset`
+    pub max_transfer_secs: u64,
+    /// Bandwidth cap of `read_file` in kilobits per second, also used when the kernel sets none
+    pub max_rate_limit_kbps: u64,
+}
+
+impl Default for FileOpsConfig {
+    fn default() -> Self {
+        let allowed_roots = if cfg!(windows) {
+            vec!["C:\\ProgramData\\Symbion\\logs".to_string(), "C:\\Windows\\Logs".to_string()]
+        } else {
+            vec!["/var/log".to_string()]
+        };
+        Self {
+            allowed_roots,
+            max_read_bytes: 256 * 1024,
+            max_lines: 1000,
+            max_follow_secs: 20,
+            chunk_bytes: 32 * 1024,
+            max_transfer_secs: 20,
+            max_rate_limit_kbps: 8 * 1024,
+        }
+    }
+}
+
+/// Network self-test (`net_check`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetCheckConfig {
+    /// Extra `host:port` targets the kernel may ask to probe (gateway/DNS/url are implicit)
+    pub allowed_targets: Vec<String>,
+    /// URL checked by default, e.g. the kernel API
+    pub url: Option<String>,
+    /// Per-probe connect timeout in milliseconds
+    pub timeout_ms: u64,
+    /// Maximum number of targets in one check
+    pub max_targets: usize,
+}
+
+impl Default for NetCheckConfig {
+    fn default() -> Self {
+        Self {
+            allowed_targets: Vec::new(),
+            url: None,
+            timeout_ms: 2000,
+            max_targets: 8,
+        }
+    }
+}
+
+/// Persistent environment values (`get_env`, `set_env`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvConfig {
+    /// Keys that may be read or written (empty = commands refused)
+    pub allowed_keys: Vec<String>,
+    /// Agent-owned profile script holding the values (Linux/Android)
+    pub profile_path: String,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            allowed_keys: Vec::new(),
+            profile_path: "/etc/profile.d/symbion.sh".to_string(),
+        }
+    }
+}
+
+/// Host firewall rules (`firewall_list`, `firewall_add`, `firewall_remove`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FirewallConfig {
+    /// Off by default: a wrong rule can cut the host off the network
+    pub enable_firewall_mgmt: bool,
+    /// Tool driving the rules on Linux (Windows always uses netsh)
+    pub linux_backend: FirewallBackend,
+}
+
+/// Firewall tool behind the firewall commands
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirewallBackend {
+    #[default]
+    Ufw,
+    Iptables,
+    Netsh,
+}
+
+impl FirewallBackend {
+    pub fn program(self) -> &'static str {
+        match self {
+            FirewallBackend::Ufw => "ufw",
+            FirewallBackend::Iptables => "iptables",
+            FirewallBackend::Netsh => "netsh",
+        }
+    }
+}
+
+/// Auto shutdown when nobody uses the host (`set_power_schedule` changes it at runtime)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerScheduleConfig {
+    /// Off by default: the host powers itself off
+    pub enabled: bool,
+    /// Minutes without user input before shutting down
+    pub idle_minutes: u32,
+    /// Local hour (0-23) the shutdown window opens
+    pub start_hour: u32,
+    /// Local hour the window closes (before `start_hour` = overnight, equal = all day)
+    pub end_hour: u32,
+    /// Warning given to logged-in users before the shutdown
+    pub warn_seconds: u32,
+    /// Pauses the policy; heartbeats report the `maintenance` status
+    pub maintenance: bool,
+}
+
+impl Default for PowerScheduleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_minutes: 60,
+            start_hour: 22,
+            end_hour: 6,
+            warn_seconds: 300,
+            maintenance: false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum UpdateChannel {
     Stable,
@@ -76,12 +268,19 @@ impl Default for AgentConfig {
                 channel: UpdateChannel::Stable,
                 check_interval_hours: 24,
                 github_repo: "anthropics/NewSymbion".to_string(), // À ajuster
+                pinned_spki_sha256: Vec::new(),
             },
             agent: AgentInfo {
                 agent_id: uuid::Uuid::new_v4().to_string(),
                 hostname: hostname::get().unwrap_or_default().to_string_lossy().to_string(),
                 version: env!("CARGO_PKG_VERSION").to_string(),
             },
+            commands: CommandsConfig::default(),
+            file_ops: FileOpsConfig::default(),
+            net_check: NetCheckConfig::default(),
+            environment: EnvConfig::default(),
+            firewall: FirewallConfig::default(),
+            power_schedule: PowerScheduleConfig::default(),
         }
     }
 }
@@ -130,6 +329,14 @@ impl AgentConfig {
         Ok(())
     }
     
+    /// Write a policy set by the kernel to the config file, so the host boots with it
+    /// (notably after its own idle shutdown)
+    pub async fn persist_power_schedule(policy: &PowerScheduleConfig) -> Result<()> {
+        let mut config = Self::load().await?;
+        config.power_schedule = policy.clone();
+        config.save().await
+    }
+    
     /// Get OS-specific config file path
     pub fn config_file_path() -> Result<PathBuf> {
         let mut path = dirs::config_dir()
@@ -174,7 +381,21 @@ mod tests {
     async fn test_default_config() {
         let config = AgentConfig::default();
         assert_eq!(config.mqtt.broker_port, 1883);
-        assert_eq!(config.update.channel, UpdateChannel::Stable);
+        assert!(matches!(config.update.channel, UpdateChannel::Stable));
+        assert!(config.update.pinned_spki_sha256.is_empty());
+        assert!(!config.firewall.enable_firewall_mgmt);
+        assert_eq!(config.firewall.linux_backend, FirewallBackend::Ufw);
+        assert!(config.commands.allowed_commands.iter().any(|c| c == "uptime"));
+    }
+    
+    #[test]
+    fn test_power_schedule_survives_config_file_round_trip() {
+        let config = AgentConfig {
+            power_schedule: PowerScheduleConfig { enabled: true, idle_minutes: 30, start_hour: 20, end_hour: 7, warn_seconds: 120, maintenance: false },
+            ..Default::default()
+        };
+        let reloaded: AgentConfig = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(reloaded.power_schedule, config.power_schedule);
     }
     
     #[test] 
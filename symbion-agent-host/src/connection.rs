@@ -0,0 +1,237 @@
+//! MQTT connection supervision
+//!
+//! rumqttc re-establishes the network connection on the next `poll` after an
+//! error, but the agent uses clean sessions: after a broker restart the broker
+//! has forgotten every subscription and the agent would silently stop
+//! receiving commands. This loop:
+//! - Re-issues the agent's subscriptions ahead of any queued request on every ConnAck
+//! - Reports each reconnect so the agent re-registers immediately
+//! - Backs off exponentially between failed attempts (capped), with a warning per attempt
+//! - Forwards publishes on subscribed topics to the main loop
+
+use rumqttc::{Event, EventLoop, Incoming, QoS, Request, Subscribe, SubscribeFilter};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn};
+
+/// First delay after a connection error
+pub const INITIAL_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Longest delay between two reconnection attempts
+pub const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Received message for internal processing
+#[derive(Debug, Clone)]
+pub struct ReceivedCommand {
+    pub topic: String,
+    pub payload: String,
+}
+
+/// What the connection loop hands to the agent's main loop
+#[derive(Debug, Clone)]
+pub enum ConnectionEvent {
+    /// Publish on one of the subscribed topics
+    Message(ReceivedCommand),
+    /// Connection re-established after a loss (subscriptions already re-issued)
+    Reconnected,
+}
+
+/// Connection state shared with the rest of the agent (diagnostics)
+#[derive(Debug, Default)]
+pub struct ConnectionStatus {
+    connected: AtomicBool,
+    reconnects: AtomicU32,
+}
+
+impl ConnectionStatus {
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Connections re-established since startup
+    pub fn reconnects(&self) -> u32 {
+        self.reconnects.load(Ordering::Relaxed)
+    }
+}
+
+/// Exponential backoff between reconnection attempts
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    next: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self { initial, max, next: initial, attempt: 0 }
+    }
+
+    /// Attempt number and delay to wait before it
+    pub fn next_delay(&mut self) -> (u32, Duration) {
+        let delay = self.next;
+        self.attempt += 1;
+        self.next = (self.next * 2).min(self.max);
+        (self.attempt, delay)
+    }
+
+    /// Connected again: next failure starts from the initial delay
+    pub fn reset(&mut self) {
+        self.next = self.initial;
+        self.attempt = 0;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(INITIAL_RECONNECT_BACKOFF, MAX_RECONNECT_BACKOFF)
+    }
+}
+
+/// Drives the event loop until the agent drops its receiver
+pub async fn supervise(
+    mut eventloop: EventLoop,
+    subscriptions: Vec<(&'static str, QoS)>,
+    mut backoff: Backoff,
+    status: Arc<ConnectionStatus>,
+    events: mpsc::Sender<ConnectionEvent>,
+) {
+    let mut connected_once = false;
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                // Pending requests from the lost session are replayed after the subscription
+                let filters = subscriptions.iter().map(|(topic, qos)| SubscribeFilter::new(topic.to_string(), *qos));
+                eventloop.pending.push_front(Request::Subscribe(Subscribe::new_many(filters)));
+                backoff.reset();
+                status.connected.store(true, Ordering::Relaxed);
+                if connected_once {
+                    status.reconnects.fetch_add(1, Ordering::Relaxed);
+                    info!("MQTT connection re-established, re-subscribing to {} topics", subscriptions.len());
+                    if events.send(ConnectionEvent::Reconnected).await.is_err() {
+                        return;
+                    }
+                } else {
+                    info!("Connected to MQTT broker");
+                    connected_once = true;
+                }
+            }
+            Ok(Event::Incoming(Incoming::Publish(publish))) => {
+                debug!("Received MQTT message on topic: {}", publish.topic);
+                if !subscriptions.iter().any(|(topic, _)| *topic == publish.topic) {
+                    continue;
+                }
+                let command = ReceivedCommand {
+                    topic: publish.topic.clone(),
+                    payload: String::from_utf8_lossy(&publish.payload).to_string(),
+                };
+                if let Err(e) = events.send(ConnectionEvent::Message(command)).await {
+                    error!("Failed to forward command: {}", e);
+                    return;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                status.connected.store(false, Ordering::Relaxed);
+                let (attempt, delay) = backoff.next_delay();
+                warn!("MQTT connection error: {}; reconnect attempt {} in {:?}", e, attempt, delay);
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rumqttc::{AsyncClient, MqttOptions};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap_and_resets() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(60));
+        let delays: Vec<u64> = (0..8).map(|_| backoff.next_delay().1.as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), (1, Duration::from_secs(1)));
+    }
+
+    /// Reads one MQTT packet: (first header byte, body)
+    async fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let header = stream.read_u8().await.unwrap();
+        let (mut length, mut shift) = (0usize, 0);
+        loop {
+            let byte = stream.read_u8().await.unwrap();
+            length |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await.unwrap();
+        (header, body)
+    }
+
+    /// Stub broker session: accepts the CONNECT, then returns the topics of the SUBSCRIBE
+    async fn accept_session(listener: &TcpListener) -> (TcpStream, Vec<String>) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let (header, _) = read_packet(&mut stream).await;
+        assert_eq!(header >> 4, 1, "expected CONNECT");
+        stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+        let (header, body) = read_packet(&mut stream).await;
+        assert_eq!(header >> 4, 8, "expected SUBSCRIBE");
+        let mut topics = Vec::new();
+        let mut at = 2;
+        while at < body.len() {
+            let len = u16::from_be_bytes([body[at], body[at + 1]]) as usize;
+            topics.push(String::from_utf8(body[at + 2..at + 2 + len].to_vec()).unwrap());
+            at += 2 + len + 1;
+        }
+        stream.write_all(&[0x90, 0x02 + topics.len() as u8, body[0], body[1]]).await.unwrap();
+        stream.write_all(&vec![0x01; topics.len()]).await.unwrap();
+        (stream, topics)
+    }
+
+    #[tokio::test]
+    async fn test_subscription_is_reissued_after_broker_disconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut options = MqttOptions::new("agent-test", "127.0.0.1", port);
+        options.set_clean_session(true);
+        let (_client, eventloop) = AsyncClient::new(options, 10);
+        let (sender, mut receiver) = mpsc::channel(10);
+        let subscriptions = vec![("symbion/agents/command@v1", QoS::ExactlyOnce), ("symbion/agents/announce@v1", QoS::AtLeastOnce)];
+        let status = Arc::new(ConnectionStatus::default());
+        tokio::spawn(supervise(eventloop, subscriptions, Backoff::new(Duration::from_millis(10), Duration::from_millis(50)), status.clone(), sender));
+
+        let (first, topics) = accept_session(&listener).await;
+        assert_eq!(topics, vec!["symbion/agents/command@v1", "symbion/agents/announce@v1"]);
+
+        // Broker restart: the connection drops and the session is lost
+        drop(first);
+        let (mut second, topics) = tokio::time::timeout(Duration::from_secs(5), accept_session(&listener)).await.unwrap();
+        assert_eq!(topics, vec!["symbion/agents/command@v1", "symbion/agents/announce@v1"]);
+        let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap();
+        assert!(matches!(event, Some(ConnectionEvent::Reconnected)));
+        assert!(status.is_connected());
+        assert_eq!(status.reconnects(), 1);
+
+        // Commands flow again on the new session
+        let topic = b"symbion/agents/command@v1";
+        let payload = b"{}";
+        let mut publish = vec![0x30, (2 + topic.len() + payload.len()) as u8, 0, topic.len() as u8];
+        publish.extend_from_slice(topic);
+        publish.extend_from_slice(payload);
+        second.write_all(&publish).await.unwrap();
+        match tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap() {
+            Some(ConnectionEvent::Message(command)) => assert_eq!((command.topic.as_str(), command.payload.as_str()), ("symbion/agents/command@v1", "{}")),
+            other => panic!("expected command, got {:?}", other),
+        }
+    }
+}
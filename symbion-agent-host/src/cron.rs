@@ -0,0 +1,401 @@
+//! Local scheduled tasks for Symbion agents
+//!
+//! Installs recurring tasks directly on the host so they keep running when the
+//! kernel is down:
+//! - Linux/Android: user crontab lines tagged with `# symbion-task:<id>`
+//! - Windows: Task Scheduler entries under the `\Symbion\` folder (schtasks)
+//! - Only allow-listed commands, without shell metacharacters
+//! - Schedules use the 5-field cron syntax; Windows supports the subset
+//!   that maps onto schtasks (every N minutes, hourly, daily, weekly, monthly)
+//! - Heartbeats read a cached listing; set/remove invalidate it, and edits made
+//!   outside the agent show up once the cache expires
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command as AsyncCommand;
+
+/// Marker appended to crontab lines owned by the agent
+const CRONTAB_MARKER: &str = "# symbion-task:";
+
+/// Task Scheduler folder for agent-owned tasks
+const SCHTASKS_FOLDER: &str = "\\Symbion\\";
+
+/// How long a host listing is reused before `crontab -l` / schtasks runs again
+const LIST_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// A recurring task installed on the host
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CronTask {
+    pub id: String,
+    /// 5-field cron expression: minute hour day-of-month month day-of-week
+    pub schedule: String,
+    pub command: String,
+}
+
+impl CronTask {
+    /// Validate id, schedule and command before installing
+    pub fn validate(&self, allowed_commands: &[String]) -> Result<()> {
+        if self.id.is_empty() || !self.id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            bail!("Invalid task id '{}': use letters, digits, '-' or '_'", self.id);
+        }
+        let fields: Vec<&str> = self.schedule.split_whitespace().collect();
+        if fields.len() != 5 || !fields.iter().all(|f| f.chars().all(|c| c.is_ascii_digit() || "*/,-".contains(c))) {
+            bail!("Invalid cron schedule '{}': expected 5 fields", self.schedule);
+        }
+        // Checked even without an allow-list: the line ends up in a crontab / schtasks entry
+        if self.command.contains(crate::execution::SHELL_METACHARACTERS) {
+            bail!("Command contains shell metacharacters: {}", self.command);
+        }
+        crate::execution::check_command_allowed(&self.command, allowed_commands)
+    }
+}
+
+/// Crontab line for a task, tagged so the agent can find it again
+pub fn crontab_line(task: &CronTask) -> String {
+    format!("{} {} {}{}", task.schedule, task.command, CRONTAB_MARKER, task.id)
+}
+
+/// Parse agent-owned tasks out of a crontab
+pub fn parse_crontab(crontab: &str) -> Vec<CronTask> {
+    crontab.lines()
+        .filter_map(|line| {
+            let (entry, id) = line.rsplit_once(CRONTAB_MARKER)?;
+            let fields: Vec<&str> = entry.split_whitespace().collect();
+            if fields.len() < 6 {
+                return None;
+            }
+            Some(CronTask {
+                id: id.trim().to_string(),
+                schedule: fields[..5].join(" "),
+                command: fields[5..].join(" "),
+            })
+        })
+        .collect()
+}
+
+/// Crontab with the task with `id` removed and, if given, `task` appended
+pub fn update_crontab(crontab: &str, id: &str, task: Option<&CronTask>) -> String {
+    let marker = format!("{}{}", CRONTAB_MARKER, id);
+    let mut lines: Vec<String> = crontab.lines()
+        .filter(|line| !line.trim_end().ends_with(&marker))
+        .map(str::to_string)
+        .collect();
+    if let Some(task) = task {
+        lines.push(crontab_line(task));
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// Map a cron schedule onto schtasks `/Create` arguments
+pub fn schtasks_create_args(task: &CronTask) -> Result<Vec<String>> {
+    let fields: Vec<&str> = task.schedule.split_whitespace().collect();
+    let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+        bail!("Invalid cron schedule '{}'", task.schedule);
+    };
+    if month != "*" {
+        bail!("Month restrictions are not supported by the Windows mapping");
+    }
+
+    let number = |field: &str, max: u32| -> Result<u32> {
+        field.parse::<u32>().ok().filter(|n| *n <= max)
+            .ok_or_else(|| anyhow!("Unsupported cron field '{}' for Windows", field))
+    };
+    let start_time = |hour: u32, minute: u32| format!("{:02}:{:02}", hour, minute);
+
+    let mut schedule: Vec<String> = match (minute, hour, day_of_month, day_of_week) {
+        (m, "*", "*", "*") if m.starts_with("*/") => {
+            vec!["/SC".into(), "MINUTE".into(), "/MO".into(), number(&m[2..], 1439)?.to_string()]
+        }
+        (m, "*", "*", "*") => {
+            vec!["/SC".into(), "HOURLY".into(), "/ST".into(), start_time(0, number(m, 59)?)]
+        }
+        (m, h, "*", "*") => {
+            vec!["/SC".into(), "DAILY".into(), "/ST".into(), start_time(number(h, 23)?, number(m, 59)?)]
+        }
+        (m, h, "*", dow) => {
+            const DAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+            let days = dow.split(',')
+                .map(|d| number(d, 7).map(|n| DAYS[n as usize % 7]))
+                .collect::<Result<Vec<_>>>()?
+                .join(",");
+            vec!["/SC".into(), "WEEKLY".into(), "/D".into(), days, "/ST".into(), start_time(number(h, 23)?, number(m, 59)?)]
+        }
+        (m, h, dom, "*") => {
+            vec!["/SC".into(), "MONTHLY".into(), "/D".into(), number(dom, 31)?.to_string(), "/ST".into(), start_time(number(h, 23)?, number(m, 59)?)]
+        }
+        _ => bail!("Schedule '{}' cannot be mapped to Task Scheduler", task.schedule),
+    };
+
+    let mut args: Vec<String> = vec![
+        "/Create".into(),
+        "/TN".into(), format!("{}{}", SCHTASKS_FOLDER, task.id),
+        "/TR".into(), format!("cmd /C {}", task.command),
+    ];
+    args.append(&mut schedule);
+    args.push("/F".into());
+    Ok(args)
+}
+
+/// Parse `schtasks /Query /FO CSV /NH /V` output into agent-owned tasks
+pub fn parse_schtasks_csv(output: &str) -> Vec<CronTask> {
+    output.lines()
+        .filter_map(|line| {
+            let columns: Vec<String> = line.split("\",\"").map(|c| c.trim_matches('"').to_string()).collect();
+            // Verbose CSV: HostName, TaskName, Next Run Time, Status, Logon Mode, Last Run Time,
+            // Last Result, Author, Task To Run, ... Schedule Type is further right
+            let name = columns.get(1)?;
+            let id = name.strip_prefix(SCHTASKS_FOLDER)?;
+            let command = columns.get(8)?.strip_prefix("cmd /C ").unwrap_or(columns.get(8)?).to_string();
+            Some(CronTask { id: id.to_string(), schedule: "windows".to_string(), command })
+        })
+        .collect()
+}
+
+/// Install (or replace) a task on the host
+pub async fn install(os: &str, task: &CronTask, allowed_commands: &[String]) -> Result<()> {
+    task.validate(allowed_commands)?;
+    match os {
+        "windows" => {
+            let output = AsyncCommand::new("schtasks").args(schtasks_create_args(task)?).output().await
+                .context("Failed to run schtasks")?;
+            if !output.status.success() {
+                bail!("schtasks failed: {}", String::from_utf8_lossy(&output.stderr));
+            }
+            Ok(())
+        }
+        _ => {
+            let current = read_crontab().await?;
+            write_crontab(&update_crontab(&current, &task.id, Some(task))).await
+        }
+    }
+}
+
+/// Remove a task; returns false if no such task was installed
+pub async fn remove(os: &str, id: &str) -> Result<bool> {
+    let installed = list(os).await?;
+    if !installed.iter().any(|t| t.id == id) {
+        return Ok(false);
+    }
+    match os {
+        "windows" => {
+            let name = format!("{}{}", SCHTASKS_FOLDER, id);
+            let output = AsyncCommand::new("schtasks").args(["/Delete", "/TN", &name, "/F"]).output().await
+                .context("Failed to run schtasks")?;
+            if !output.status.success() {
+                bail!("schtasks failed: {}", String::from_utf8_lossy(&output.stderr));
+            }
+        }
+        _ => {
+            let current = read_crontab().await?;
+            write_crontab(&update_crontab(&current, id, None)).await?;
+        }
+    }
+    Ok(true)
+}
+
+/// Tasks installed by the agent on this host
+pub async fn list(os: &str) -> Result<Vec<CronTask>> {
+    match os {
+        "windows" => {
+            let output = AsyncCommand::new("schtasks").args(["/Query", "/FO", "CSV", "/NH", "/V"]).output().await
+                .context("Failed to run schtasks")?;
+            Ok(parse_schtasks_csv(&String::from_utf8_lossy(&output.stdout)))
+        }
+        _ => Ok(parse_crontab(&read_crontab().await?)),
+    }
+}
+
+/// Cached task listing, so heartbeats do not spawn `crontab -l` / schtasks every beat
+#[derive(Default)]
+pub struct TaskCache {
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Bumped by `invalidate`: a listing started before it is not stored
+    generation: u64,
+    listed: Option<(Instant, Vec<CronTask>)>,
+}
+
+impl TaskCache {
+    /// Tasks installed on the host, from the cache while it is fresh
+    pub async fn list(&self, os: &str) -> Result<Vec<CronTask>> {
+        self.get_or_load(Instant::now(), list(os)).await
+    }
+
+    /// Forget the cached listing (after installing or removing a task)
+    pub fn invalidate(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.generation += 1;
+        state.listed = None;
+    }
+
+    async fn get_or_load(&self, now: Instant, load: impl Future<Output = Result<Vec<CronTask>>>) -> Result<Vec<CronTask>> {
+        let generation = {
+            let state = self.state.lock().unwrap();
+            if let Some((at, tasks)) = &state.listed {
+                if now.duration_since(*at) < LIST_CACHE_TTL {
+                    return Ok(tasks.clone());
+                }
+            }
+            state.generation
+        };
+        let tasks = load.await?;
+        let mut state = self.state.lock().unwrap();
+        if state.generation == generation {
+            state.listed = Some((now, tasks.clone()));
+        }
+        Ok(tasks)
+    }
+}
+
+async fn read_crontab() -> Result<String> {
+    let output = AsyncCommand::new("crontab").arg("-l").output().await
+        .context("Failed to run crontab")?;
+    // `crontab -l` exits non-zero when the user has no crontab yet
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Ok(String::new())
+    }
+}
+
+async fn write_crontab(content: &str) -> Result<()> {
+    let mut child = AsyncCommand::new("crontab")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run crontab")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(content.as_bytes()).await?;
+    }
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        bail!("crontab failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, schedule: &str, command: &str) -> CronTask {
+        CronTask { id: id.to_string(), schedule: schedule.to_string(), command: command.to_string() }
+    }
+
+    #[test]
+    fn test_crontab_line_roundtrip() {
+        let t = task("nightly-uptime", "30 2 * * *", "uptime");
+        let line = crontab_line(&t);
+        assert_eq!(line, "30 2 * * * uptime # symbion-task:nightly-uptime");
+
+        let crontab = format!("MAILTO=root\n0 * * * * /usr/bin/backup\n{}\n", line);
+        assert_eq!(parse_crontab(&crontab), vec![t]);
+    }
+
+    #[test]
+    fn test_update_crontab_replaces_and_removes_only_own_line() {
+        let crontab = "0 * * * * /usr/bin/backup\n*/5 * * * * date # symbion-task:probe\n";
+        let updated = update_crontab(crontab, "probe", Some(&task("probe", "*/10 * * * *", "date")));
+        assert_eq!(updated, "0 * * * * /usr/bin/backup\n*/10 * * * * date # symbion-task:probe\n");
+
+        let removed = update_crontab(&updated, "probe", None);
+        assert_eq!(removed, "0 * * * * /usr/bin/backup\n");
+    }
+
+    #[test]
+    fn test_validation_rejects_unsafe_tasks() {
+        let allowed = crate::config::CommandsConfig::default().allowed_commands;
+        assert!(task("ok", "0 3 * * 1", "uptime").validate(&allowed).is_ok());
+        assert!(task("bad id", "0 3 * * 1", "uptime").validate(&allowed).is_err());
+        assert!(task("x", "0 3 * *", "uptime").validate(&allowed).is_err());
+        assert!(task("x", "0 3 * * 1", "rm -rf /").validate(&allowed).is_err());
+        assert!(task("x", "0 3 * * 1", "lsblk").validate(&allowed).is_err());
+        // argv0 must equal an allow-listed program, not merely start with one
+        assert!(task("x", "0 3 * * 1", "uptimex").validate(&allowed).is_err());
+        assert!(task("x", "0 3 * * 1", "datelocal --evil").validate(&allowed).is_err());
+        assert!(task("x", "0 3 * * 1", "ls; rm -rf /").validate(&allowed).is_err());
+        assert!(task("x", "0 3 * * 1", "date > /etc/motd").validate(&allowed).is_err());
+        // Redirections stay refused even with the allowlist disabled
+        assert!(task("x", "0 3 * * 1", "date > /etc/motd").validate(&[]).is_err());
+        assert!(task("x", "0 3 * * 1", "backup.sh").validate(&[]).is_ok());
+    }
+
+    #[test]
+    fn test_schtasks_argument_mapping() {
+        let args = schtasks_create_args(&task("probe", "*/15 * * * *", "hostname")).unwrap();
+        assert_eq!(args, vec!["/Create", "/TN", "\\Symbion\\probe", "/TR", "cmd /C hostname", "/SC", "MINUTE", "/MO", "15", "/F"]);
+
+        let daily = schtasks_create_args(&task("daily", "30 2 * * *", "dir")).unwrap();
+        assert_eq!(&daily[5..9], &["/SC", "DAILY", "/ST", "02:30"]);
+
+        let hourly = schtasks_create_args(&task("hourly", "5 * * * *", "dir")).unwrap();
+        assert_eq!(&hourly[5..9], &["/SC", "HOURLY", "/ST", "00:05"]);
+
+        let weekly = schtasks_create_args(&task("weekly", "0 8 * * 1,5", "dir")).unwrap();
+        assert_eq!(&weekly[5..11], &["/SC", "WEEKLY", "/D", "MON,FRI", "/ST", "08:00"]);
+
+        let monthly = schtasks_create_args(&task("monthly", "0 6 15 * *", "dir")).unwrap();
+        assert_eq!(&monthly[5..11], &["/SC", "MONTHLY", "/D", "15", "/ST", "06:00"]);
+
+        assert!(schtasks_create_args(&task("x", "0 6 * 1 *", "dir")).is_err());
+        assert!(schtasks_create_args(&task("x", "0-10 6 * * *", "dir")).is_err());
+    }
+
+    #[test]
+    fn test_parse_schtasks_csv() {
+        let output = "\"PC\",\"\\Symbion\\probe\",\"N/A\",\"Ready\",\"Interactive only\",\"N/A\",\"0\",\"admin\",\"cmd /C hostname\",\"N/A\"\n\
+                      \"PC\",\"\\Microsoft\\Windows\\Defrag\",\"N/A\",\"Ready\",\"Interactive only\",\"N/A\",\"0\",\"admin\",\"defrag.exe\",\"N/A\"";
+        let tasks = parse_schtasks_csv(output);
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, "probe");
+        assert_eq!(tasks[0].command, "hostname");
+    }
+
+    #[tokio::test]
+    async fn test_task_cache_reuses_listing_until_invalidated_or_expired() {
+        let cache = TaskCache::default();
+        let loads = std::sync::atomic::AtomicUsize::new(0);
+        let load = |id: &'static str| {
+            let loads = &loads;
+            async move {
+                loads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(vec![task(id, "0 3 * * *", "uptime")])
+            }
+        };
+        let start = Instant::now();
+
+        assert_eq!(cache.get_or_load(start, load("first")).await.unwrap()[0].id, "first");
+        assert_eq!(cache.get_or_load(start + Duration::from_secs(60), load("second")).await.unwrap()[0].id, "first");
+        assert_eq!(loads.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        cache.invalidate();
+        assert_eq!(cache.get_or_load(start + Duration::from_secs(61), load("after-set")).await.unwrap()[0].id, "after-set");
+        let expired = start + Duration::from_secs(61) + LIST_CACHE_TTL;
+        assert_eq!(cache.get_or_load(expired, load("external-edit")).await.unwrap()[0].id, "external-edit");
+    }
+
+    #[tokio::test]
+    async fn test_task_cache_drops_a_listing_raced_by_invalidate() {
+        let cache = TaskCache::default();
+        let now = Instant::now();
+        let stale = cache.get_or_load(now, async {
+            cache.invalidate();
+            Ok(vec![task("removed", "0 3 * * *", "uptime")])
+        }).await.unwrap();
+        assert_eq!(stale[0].id, "removed");
+        let fresh = cache.get_or_load(now, async { Ok(Vec::new()) }).await.unwrap();
+        assert!(fresh.is_empty());
+    }
+}
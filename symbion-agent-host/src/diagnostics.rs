@@ -0,0 +1,247 @@
+//! Agent self-diagnostics
+//!
+//! Backs the `diagnostics` command: a set of internal checks an operator can
+//! run when an agent misbehaves, without shell access to the machine:
+//! - `config`: the config file parses (missing = defaults in use, a warning)
+//! - `mqtt`: the broker connection is up, with the reconnect count since startup
+//! - `capabilities`: advertised capabilities whose tools are missing on the host
+//! - `disk`: the config and temp directories accept writes
+//! - `elevation`: privileged commands (power, firewall, services) can run
+//!
+//! Each check yields ok / warn / fail; the report status is the worst of them.
+
+use crate::capabilities::CapabilityInfo;
+use crate::config::AgentConfig;
+use crate::connection::ConnectionStatus;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Outcome of a single check, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// One diagnostic check
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl CheckResult {
+    pub fn new(name: &'static str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self { name, status, message: message.into() }
+    }
+}
+
+/// Number of checks per status
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CheckSummary {
+    pub ok: usize,
+    pub warn: usize,
+    pub fail: usize,
+}
+
+/// Structured report returned by the `diagnostics` command
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    /// Worst status among the checks
+    pub status: CheckStatus,
+    pub summary: CheckSummary,
+    pub checks: Vec<CheckResult>,
+    pub generated_at: DateTime<Utc>,
+}
+
+/// Folds the sub-check results into a report
+pub fn aggregate(checks: Vec<CheckResult>) -> DiagnosticsReport {
+    let mut summary = CheckSummary::default();
+    for check in &checks {
+        match check.status {
+            CheckStatus::Ok => summary.ok += 1,
+            CheckStatus::Warn => summary.warn += 1,
+            CheckStatus::Fail => summary.fail += 1,
+        }
+    }
+    let status = checks.iter().map(|c| c.status).max().unwrap_or(CheckStatus::Ok);
+    DiagnosticsReport { status, summary, checks, generated_at: Utc::now() }
+}
+
+/// The config file on disk still parses (the running agent may predate an edit)
+pub fn check_config(path: &Path) -> CheckResult {
+    match std::fs::read_to_string(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            CheckResult::new("config", CheckStatus::Warn, format!("{} not found, running on defaults", path.display()))
+        }
+        Err(e) => CheckResult::new("config", CheckStatus::Fail, format!("cannot read {}: {}", path.display(), e)),
+        Ok(content) => match toml::from_str::<AgentConfig>(&content) {
+            Ok(config) if config.mqtt.broker_host.trim().is_empty() => {
+                CheckResult::new("config", CheckStatus::Fail, "mqtt.broker_host is empty")
+            }
+            Ok(_) => CheckResult::new("config", CheckStatus::Ok, format!("{} is valid", path.display())),
+            Err(e) => CheckResult::new("config", CheckStatus::Fail, format!("{} is invalid: {}", path.display(), e)),
+        },
+    }
+}
+
+pub fn check_mqtt(status: &ConnectionStatus) -> CheckResult {
+    let reconnects = status.reconnects();
+    if status.is_connected() {
+        CheckResult::new("mqtt", CheckStatus::Ok, format!("connected ({} reconnects since startup)", reconnects))
+    } else {
+        CheckResult::new("mqtt", CheckStatus::Fail, format!("disconnected ({} reconnects since startup)", reconnects))
+    }
+}
+
+/// Advertised capabilities the host cannot actually back
+pub fn check_capabilities(detected: &[CapabilityInfo], advertised: &[String]) -> CheckResult {
+    let missing: Vec<String> = detected.iter()
+        .filter(|c| !c.available && advertised.iter().any(|a| a == c.capability_type.name()))
+        .map(|c| match &c.reason {
+            Some(reason) => format!("{} ({})", c.capability_type.name(), reason),
+            None => c.capability_type.name().to_string(),
+        })
+        .collect();
+    let available = detected.iter().filter(|c| c.available).count();
+    if missing.is_empty() {
+        CheckResult::new("capabilities", CheckStatus::Ok, format!("{}/{} detected capabilities available", available, detected.len()))
+    } else {
+        CheckResult::new("capabilities", CheckStatus::Warn, format!("advertised but unavailable: {}", missing.join(", ")))
+    }
+}
+
+/// Every directory accepts a probe file
+pub fn check_writable(dirs: &[PathBuf]) -> CheckResult {
+    let failures: Vec<String> = dirs.iter()
+        .filter_map(|dir| {
+            let probe = dir.join(format!(".symbion-diagnostics-{}", std::process::id()));
+            let result = std::fs::create_dir_all(dir)
+                .and_then(|_| std::fs::write(&probe, b"probe"))
+                .and_then(|_| std::fs::remove_file(&probe));
+            result.err().map(|e| format!("{}: {}", dir.display(), e))
+        })
+        .collect();
+    if failures.is_empty() {
+        CheckResult::new("disk", CheckStatus::Ok, format!("{} directories writable", dirs.len()))
+    } else {
+        CheckResult::new("disk", CheckStatus::Fail, format!("not writable: {}", failures.join("; ")))
+    }
+}
+
+/// Privileged commands run directly as root/administrator, or through sudo when auto-elevation is on
+pub fn check_elevation(is_admin: bool, auto_elevate: bool, sudo_available: bool, credentials_stored: bool) -> CheckResult {
+    if is_admin {
+        return CheckResult::new("elevation", CheckStatus::Ok, "running with administrator privileges");
+    }
+    match (auto_elevate, sudo_available) {
+        (true, true) if credentials_stored => CheckResult::new("elevation", CheckStatus::Ok, "auto-elevation through sudo with stored credentials"),
+        (true, true) => CheckResult::new("elevation", CheckStatus::Ok, "auto-elevation through sudo (passwordless sudo required)"),
+        (true, false) => CheckResult::new("elevation", CheckStatus::Warn, "auto_elevate is enabled but sudo was not found"),
+        (false, _) => CheckResult::new("elevation", CheckStatus::Warn, "not elevated and auto_elevate is disabled: privileged commands will fail"),
+    }
+}
+
+/// Whether the agent runs as root (Unix) or from an elevated session (Windows)
+pub async fn is_admin() -> bool {
+    let output = if cfg!(target_os = "windows") {
+        tokio::process::Command::new("net").arg("session").output().await
+    } else {
+        tokio::process::Command::new("id").arg("-u").output().await
+    };
+    match output {
+        Ok(output) if cfg!(target_os = "windows") => output.status.success(),
+        Ok(output) => String::from_utf8_lossy(&output.stdout).trim() == "0",
+        Err(_) => false,
+    }
+}
+
+/// Directories the agent needs to write to
+pub fn writable_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![std::env::temp_dir()];
+    if let Some(config_dir) = AgentConfig::config_file_path().ok().and_then(|p| p.parent().map(Path::to_path_buf)) {
+        dirs.insert(0, config_dir);
+    }
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capabilities::CapabilityType;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("symbion-diag-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn capability(capability_type: CapabilityType, available: bool) -> CapabilityInfo {
+        CapabilityInfo { capability_type, available, reason: (!available).then(|| "tool missing".to_string()), tools: Vec::new() }
+    }
+
+    #[test]
+    fn test_report_status_is_the_worst_check() {
+        let report = aggregate(vec![
+            CheckResult::new("config", CheckStatus::Ok, ""),
+            CheckResult::new("capabilities", CheckStatus::Warn, ""),
+            CheckResult::new("mqtt", CheckStatus::Ok, ""),
+        ]);
+        assert_eq!(report.status, CheckStatus::Warn);
+        assert_eq!(report.summary, CheckSummary { ok: 2, warn: 1, fail: 0 });
+
+        let report = aggregate(vec![
+            CheckResult::new("disk", CheckStatus::Fail, "read-only"),
+            CheckResult::new("elevation", CheckStatus::Warn, ""),
+        ]);
+        assert_eq!(report.status, CheckStatus::Fail);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["status"], "fail");
+        assert_eq!(json["checks"][0]["name"], "disk");
+        assert_eq!(json["checks"][0]["message"], "read-only");
+
+        assert_eq!(aggregate(Vec::new()).status, CheckStatus::Ok);
+    }
+
+    #[test]
+    fn test_config_check_distinguishes_missing_invalid_and_valid() {
+        let dir = temp_dir();
+        let path = dir.join("config.toml");
+        assert_eq!(check_config(&path).status, CheckStatus::Warn);
+
+        std::fs::write(&path, "mqtt = \"not a table\"").unwrap();
+        assert_eq!(check_config(&path).status, CheckStatus::Fail);
+
+        std::fs::write(&path, toml::to_string_pretty(&AgentConfig::default()).unwrap()).unwrap();
+        assert_eq!(check_config(&path).status, CheckStatus::Ok);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_sub_checks_report_problems() {
+        let advertised = vec!["power_management".to_string(), "process_control".to_string()];
+        let detected = [capability(CapabilityType::PowerManagement, false), capability(CapabilityType::ProcessControl, true), capability(CapabilityType::GpuMetrics, false)];
+        let check = check_capabilities(&detected, &advertised);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert_eq!(check.message, "advertised but unavailable: power_management (tool missing)");
+        assert_eq!(check_capabilities(&detected[1..], &advertised).status, CheckStatus::Ok);
+
+        let dir = temp_dir();
+        assert_eq!(check_writable(std::slice::from_ref(&dir)).status, CheckStatus::Ok);
+        let file = dir.join("plain-file");
+        std::fs::write(&file, b"x").unwrap();
+        assert_eq!(check_writable(&[dir.clone(), file]).status, CheckStatus::Fail);
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(check_mqtt(&ConnectionStatus::default()).status, CheckStatus::Fail);
+
+        assert_eq!(check_elevation(true, false, false, false).status, CheckStatus::Ok);
+        assert_eq!(check_elevation(false, true, true, false).status, CheckStatus::Ok);
+        assert_eq!(check_elevation(false, true, false, false).status, CheckStatus::Warn);
+        assert_eq!(check_elevation(false, false, true, true).status, CheckStatus::Warn);
+    }
+}
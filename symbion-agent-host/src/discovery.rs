@@ -2,8 +2,11 @@
 //! 
 //! This module handles:
 //! - Primary MAC address detection with priority (Ethernet > WiFi > Other)
-//! - Network interface enumeration with IP addresses  
+//! - Network interface enumeration with IP addresses, link speed and MTU
+//! - Interface type classification from OS facts (Linux sysfs) with a name-based
+//!   fallback (Windows friendly names, macOS)
 //! - System identification (hostname, OS, architecture)
+//! - OS details (kernel version, distro from /etc/os-release, Windows build)
 //! - Agent ID generation from MAC address
 
 use anyhow::{Result, Context};
@@ -21,10 +24,15 @@ pub struct NetworkInterface {
     pub ip: String,
     #[serde(rename = "type")]
     pub interface_type: InterfaceType,
+    /// Negotiated link speed in Mbit/s (None when unknown, down or not reported)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed_mbps: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
 }
 
 /// Interface type classification
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum InterfaceType {
     Ethernet,
@@ -48,8 +56,90 @@ pub struct SystemInfo {
     pub os: String,
     pub architecture: String,
     pub network: NetworkInfo,
+    pub os_details: OsDetails,
+}
+
+/// Detailed OS identification (beyond the coarse `os` string)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OsDetails {
+    pub kernel_version: Option<String>,
+    pub distro_name: Option<String>,
+    pub distro_version: Option<String>,
+    pub windows_build: Option<u32>,
+}
+
+/// Windows build numbers -> (product, release) for known feature updates
+const WINDOWS_BUILDS: &[(u32, &str, &str)] = &[
+    (10240, "Windows 10", "1507"),
+    (10586, "Windows 10", "1511"),
+    (14393, "Windows 10", "1607"),
+    (15063, "Windows 10", "1703"),
+    (16299, "Windows 10", "1709"),
+    (17134, "Windows 10", "1803"),
+    (17763, "Windows 10", "1809"),
+    (18362, "Windows 10", "1903"),
+    (18363, "Windows 10", "1909"),
+    (19041, "Windows 10", "2004"),
+    (19042, "Windows 10", "20H2"),
+    (19043, "Windows 10", "21H1"),
+    (19044, "Windows 10", "21H2"),
+    (19045, "Windows 10", "22H2"),
+    (22000, "Windows 11", "21H2"),
+    (22621, "Windows 11", "22H2"),
+    (22631, "Windows 11", "23H2"),
+    (26100, "Windows 11", "24H2"),
+];
+
+/// OS-reported facts about an interface, when available
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterfaceFacts {
+    pub loopback: bool,
+    /// Backed by a wireless PHY (Linux: `wireless`/`phy80211` in sysfs)
+    pub wireless: bool,
+    /// Software device: bridge, veth, tunnel... (Linux: under /sys/devices/virtual)
+    pub virtual_device: bool,
+    /// ARPHRD hardware type (1 = Ethernet, 772 = loopback)
+    pub hardware_type: Option<u16>,
+    pub speed_mbps: Option<u32>,
+    pub mtu: Option<u32>,
 }
 
+impl InterfaceFacts {
+    /// Read facts for `name` from /sys/class/net (empty on other platforms)
+    fn read(name: &str) -> Self {
+        if !cfg!(any(target_os = "linux", target_os = "android")) {
+            return Self::default();
+        }
+        let base = std::path::Path::new("/sys/class/net").join(name);
+        let read_number = |file: &str| -> Option<i64> {
+            std::fs::read_to_string(base.join(file)).ok()?.trim().parse().ok()
+        };
+        let hardware_type = read_number("type").and_then(|t| u16::try_from(t).ok());
+        Self {
+            loopback: hardware_type == Some(772),
+            wireless: base.join("wireless").exists() || base.join("phy80211").exists(),
+            virtual_device: std::fs::canonicalize(&base)
+                .map(|path| path.starts_with("/sys/devices/virtual"))
+                .unwrap_or(false),
+            hardware_type,
+            // -1 (or an error) when the link is down or the driver doesn't report it
+            speed_mbps: read_number("speed").filter(|s| *s > 0).and_then(|s| u32::try_from(s).ok()),
+            mtu: read_number("mtu").and_then(|m| u32::try_from(m).ok()),
+        }
+    }
+}
+
+/// Name prefixes of software interfaces (containers, VPNs, hypervisors)
+const VIRTUAL_NAME_PREFIXES: &[&str] = &[
+    "docker", "br-", "veth", "virbr", "vmnet", "vboxnet", "tun", "tap", "tailscale", "wg", "zt", "utun",
+];
+
+/// Name fragments of software interfaces (Windows friendly names)
+const VIRTUAL_NAME_FRAGMENTS: &[&str] = &["virtual", "hyper-v", "vpn", "bridge", "pseudo"];
+
+/// Name fragments of wireless interfaces (Linux predictable names, Windows friendly names)
+const WIRELESS_NAME_FRAGMENTS: &[&str] = &["wlan", "wlp", "wlo", "wifi", "wi-fi", "wireless"];
+
 /// Priority order for interface selection
 const INTERFACE_PRIORITY: &[&str] = &[
     "eth", "en", "ens", "enp", "eno",  // Ethernet (Linux/macOS patterns)
@@ -72,6 +162,7 @@ impl SystemInfo {
             
         let os = std::env::consts::OS.to_string();
         let architecture = std::env::consts::ARCH.to_string();
+        let os_details = OsDetails::discover(&os).await;
         
         // Generate agent ID from primary MAC (remove colons)
         let agent_id = network.primary_mac.replace(":", "");
@@ -85,10 +176,82 @@ impl SystemInfo {
             os,
             architecture,
             network,
+            os_details,
         })
     }
 }
 
+impl OsDetails {
+    /// Collect kernel version and distro/build information for the current OS
+    pub async fn discover(os: &str) -> Self {
+        let kernel_version = sysinfo::System::kernel_version();
+        
+        match os {
+            "windows" => {
+                // sysinfo reports the Windows build number as kernel version
+                let windows_build = kernel_version.as_deref()
+                    .and_then(|k| k.trim().parse::<u32>().ok());
+                let (distro_name, distro_version) = match windows_build.and_then(Self::windows_release) {
+                    Some((name, release)) => (Some(name), Some(release)),
+                    None => (Some("Windows".to_string()), sysinfo::System::os_version()),
+                };
+                Self { kernel_version, distro_name, distro_version, windows_build }
+            }
+            "linux" | "android" => {
+                let content = match tokio::fs::read_to_string("/etc/os-release").await {
+                    Ok(content) => Some(content),
+                    Err(_) => tokio::fs::read_to_string("/usr/lib/os-release").await.ok(),
+                };
+                let (distro_name, distro_version) = match content {
+                    Some(content) => Self::parse_os_release(&content),
+                    None => (sysinfo::System::name(), sysinfo::System::os_version()),
+                };
+                Self { kernel_version, distro_name, distro_version, windows_build: None }
+            }
+            _ => Self {
+                kernel_version,
+                distro_name: sysinfo::System::name(),
+                distro_version: sysinfo::System::os_version(),
+                windows_build: None,
+            },
+        }
+    }
+    
+    /// Parse `/etc/os-release` content into (NAME, VERSION_ID)
+    fn parse_os_release(content: &str) -> (Option<String>, Option<String>) {
+        let mut fields: HashMap<&str, String> = HashMap::new();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+                fields.insert(key.trim(), value.to_string());
+            }
+        }
+        
+        let name = fields.remove("NAME").or_else(|| fields.remove("ID"));
+        let version = fields.remove("VERSION_ID").or_else(|| fields.remove("BUILD_ID"));
+        (name.filter(|n| !n.is_empty()), version.filter(|v| !v.is_empty()))
+    }
+    
+    /// Map a Windows build number to (product, release), e.g. 22631 -> ("Windows 11", "23H2")
+    fn windows_release(build: u32) -> Option<(String, String)> {
+        if let Some((_, name, release)) = WINDOWS_BUILDS.iter().find(|(b, _, _)| *b == build) {
+            return Some((name.to_string(), release.to_string()));
+        }
+        
+        // Unknown build: product line only, release reported as the build number
+        let product = match build {
+            b if b >= 22000 => "Windows 11",
+            b if b >= 10240 => "Windows 10",
+            _ => return None,
+        };
+        Some((product.to_string(), build.to_string()))
+    }
+}
+
 impl NetworkInfo {
     /// Discover network interfaces and determine primary MAC
     pub async fn discover() -> Result<Self> {
@@ -117,11 +280,14 @@ impl NetworkInfo {
                     mac.bytes()[0], mac.bytes()[1], mac.bytes()[2],
                     mac.bytes()[3], mac.bytes()[4], mac.bytes()[5]);
                     
+                let facts = InterfaceFacts::read(&if_addr.name);
                 let interface = NetworkInterface {
                     name: if_addr.name.clone(),
                     mac: mac_str.clone(),
                     ip,
-                    interface_type: Self::classify_interface(&if_addr.name),
+                    interface_type: Self::classify_interface(&if_addr.name, &facts),
+                    speed_mbps: facts.speed_mbps,
+                    mtu: facts.mtu,
                 };
                 
                 debug!("Found interface: {} ({})", interface.name, interface.mac);
@@ -133,7 +299,7 @@ impl NetworkInfo {
         // Determine primary MAC address based on priority
         let primary_mac = Self::select_primary_mac(&interfaces)?;
         
-        info!("Selected primary MAC: {} from {} interfaces", primary_mac, interfaces.len());
+        debug!("Selected primary MAC: {} from {} interfaces", primary_mac, interfaces.len());
         
         Ok(NetworkInfo {
             primary_mac,
@@ -153,24 +319,31 @@ impl NetworkInfo {
         None
     }
     
-    /// Classify interface type based on name patterns
-    fn classify_interface(name: &str) -> InterfaceType {
+    /// Classify an interface: OS facts first, then name patterns
+    fn classify_interface(name: &str, facts: &InterfaceFacts) -> InterfaceType {
         let name_lower = name.to_lowercase();
         
-        if name_lower.contains("lo") {
+        // "lo", "lo0" (macOS) or Windows "Loopback Pseudo-Interface 1"
+        let loopback_name = name_lower.strip_prefix("lo")
+            .is_some_and(|rest| rest.chars().all(|c| c.is_ascii_digit()))
+            || name_lower.starts_with("loopback");
+        if facts.loopback || loopback_name {
             return InterfaceType::Loopback;
         }
-        
-        // Check for wireless patterns
-        if name_lower.contains("wlan") || name_lower.contains("wifi") || 
-           name_lower.contains("wlp") || name_lower.contains("wlo") {
+        if facts.wireless {
             return InterfaceType::Wireless;
         }
-        
-        // Check for ethernet patterns  
-        if name_lower.starts_with("eth") || name_lower.starts_with("en") ||
-           name_lower.starts_with("ens") || name_lower.starts_with("enp") ||
-           name_lower.starts_with("eno") {
+        // Bridges and veths report an Ethernet hardware type: not a physical NIC
+        let virtual_name = VIRTUAL_NAME_PREFIXES.iter().any(|p| name_lower.starts_with(p))
+            || VIRTUAL_NAME_FRAGMENTS.iter().any(|f| name_lower.contains(f));
+        if facts.virtual_device || virtual_name {
+            return InterfaceType::Other;
+        }
+        if WIRELESS_NAME_FRAGMENTS.iter().any(|f| name_lower.contains(f)) {
+            return InterfaceType::Wireless;
+        }
+        // eth0, enp3s0, eno1, en0 (macOS), Windows "Ethernet 2"
+        if facts.hardware_type == Some(1) || name_lower.starts_with("eth") || name_lower.starts_with("en") {
             return InterfaceType::Ethernet;
         }
         
@@ -216,23 +389,108 @@ mod tests {
     #[test]
     fn test_interface_classification() {
         assert!(matches!(
-            NetworkInfo::classify_interface("eth0"), 
+            NetworkInfo::classify_interface("eth0", &InterfaceFacts::default()), 
             InterfaceType::Ethernet
         ));
         assert!(matches!(
-            NetworkInfo::classify_interface("wlan0"), 
+            NetworkInfo::classify_interface("wlan0", &InterfaceFacts::default()), 
             InterfaceType::Wireless
         ));
         assert!(matches!(
-            NetworkInfo::classify_interface("lo"), 
+            NetworkInfo::classify_interface("lo", &InterfaceFacts::default()), 
             InterfaceType::Loopback
         ));
     }
     
+    #[test]
+    fn test_interface_classification_by_name() {
+        let none = InterfaceFacts::default();
+        let cases = [
+            // Predictable Linux names, macOS
+            ("enp3s0", InterfaceType::Ethernet),
+            ("eno1", InterfaceType::Ethernet),
+            ("en0", InterfaceType::Ethernet),
+            ("wlp2s0", InterfaceType::Wireless),
+            // "wlo1" used to be taken for a loopback because it contains "lo"
+            ("wlo1", InterfaceType::Wireless),
+            ("lo0", InterfaceType::Loopback),
+            // Windows friendly names
+            ("Ethernet 2", InterfaceType::Ethernet),
+            ("Wi-Fi", InterfaceType::Wireless),
+            ("Loopback Pseudo-Interface 1", InterfaceType::Loopback),
+            ("vEthernet (Default Switch)", InterfaceType::Other),
+            ("VirtualBox Host-Only Network", InterfaceType::Other),
+            // Containers, bridges, VPNs
+            ("docker0", InterfaceType::Other),
+            ("br-1a2b3c", InterfaceType::Other),
+            ("veth12ab34", InterfaceType::Other),
+            ("virbr0", InterfaceType::Other),
+            ("tailscale0", InterfaceType::Other),
+            ("wg0", InterfaceType::Other),
+            ("tun0", InterfaceType::Other),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(NetworkInfo::classify_interface(name, &none), expected, "{}", name);
+        }
+    }
+    
+    #[test]
+    fn test_interface_classification_prefers_os_facts() {
+        let ethernet = InterfaceFacts { hardware_type: Some(1), ..InterfaceFacts::default() };
+        let wireless = InterfaceFacts { wireless: true, hardware_type: Some(1), ..InterfaceFacts::default() };
+        let bridge = InterfaceFacts { virtual_device: true, hardware_type: Some(1), ..InterfaceFacts::default() };
+        let loopback = InterfaceFacts { loopback: true, ..InterfaceFacts::default() };
+        
+        // Renamed NICs are classified by their hardware, not their name
+        assert_eq!(NetworkInfo::classify_interface("lan", &ethernet), InterfaceType::Ethernet);
+        assert_eq!(NetworkInfo::classify_interface("eth1", &wireless), InterfaceType::Wireless);
+        assert_eq!(NetworkInfo::classify_interface("lan-bridge", &bridge), InterfaceType::Other);
+        assert_eq!(NetworkInfo::classify_interface("enp0s3", &bridge), InterfaceType::Other);
+        assert_eq!(NetworkInfo::classify_interface("host", &loopback), InterfaceType::Loopback);
+    }
+    
     #[test]
     fn test_agent_id_generation() {
         let mac = "a1:b2:c3:d4:e5:f6";
         let expected = "a1b2c3d4e5f6";
         assert_eq!(mac.replace(":", ""), expected);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_parse_os_release() {
+        let content = r#"PRETTY_NAME="Ubuntu 22.04.4 LTS"
+NAME="Ubuntu"
+VERSION_ID="22.04"
+# comment line
+ID=ubuntu
+"#;
+        let (name, version) = OsDetails::parse_os_release(content);
+        assert_eq!(name.as_deref(), Some("Ubuntu"));
+        assert_eq!(version.as_deref(), Some("22.04"));
+        
+        // Rolling distros have no VERSION_ID
+        let (name, version) = OsDetails::parse_os_release("ID=arch\nBUILD_ID=rolling\n");
+        assert_eq!(name.as_deref(), Some("arch"));
+        assert_eq!(version.as_deref(), Some("rolling"));
+        
+        assert_eq!(OsDetails::parse_os_release(""), (None, None));
+    }
+    
+    #[test]
+    fn test_windows_build_mapping() {
+        assert_eq!(
+            OsDetails::windows_release(22631),
+            Some(("Windows 11".to_string(), "23H2".to_string()))
+        );
+        assert_eq!(
+            OsDetails::windows_release(19045),
+            Some(("Windows 10".to_string(), "22H2".to_string()))
+        );
+        // Unknown builds fall back to the product line
+        assert_eq!(
+            OsDetails::windows_release(26200),
+            Some(("Windows 11".to_string(), "26200".to_string()))
+        );
+        assert_eq!(OsDetails::windows_release(9600), None);
+    }
+}
@@ -0,0 +1,341 @@
+//! Persistent environment values for Symbion agents
+//!
+//! Backs the `get_env` / `set_env` commands so operators can push the same
+//! configuration to every machine:
+//! - Only keys listed in `environment.allowed_keys` can be read or written
+//! - Linux: `export KEY='value'` lines in an agent-owned profile script
+//!   (`/etc/profile.d/symbion.sh` by default), rewritten atomically
+//! - Windows: machine-wide variables (`setx /M`, `reg delete` to unset),
+//!   read back from the registry; one command per key, so a failure part-way
+//!   restores the keys already written
+//! - Values apply to new sessions; running processes keep their environment
+
+use crate::config::EnvConfig;
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use tokio::process::Command as AsyncCommand;
+use tracing::warn;
+
+/// Registry key holding machine-wide environment variables
+const WINDOWS_ENV_KEY: &str = "HKLM\\SYSTEM\\CurrentControlSet\\Control\\Session Manager\\Environment";
+
+/// `setx` silently truncates longer values
+const SETX_MAX_VALUE_LEN: usize = 1024;
+
+/// Header of the agent-owned profile script
+const PROFILE_HEADER: &str = "# Managed by symbion-agent-host (set_env); manual edits are overwritten\n";
+
+/// One applied `set_env` change
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnvChange {
+    pub key: String,
+    pub previous: Option<String>,
+    /// New value (None = removed)
+    pub value: Option<String>,
+    pub changed: bool,
+}
+
+/// Check a key against the allow-list (and the shell identifier syntax)
+pub fn check_key(key: &str, config: &EnvConfig) -> Result<()> {
+    let valid = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        bail!("Invalid variable name '{}'", key);
+    }
+    if !config.allowed_keys.iter().any(|allowed| allowed == key) {
+        bail!("Key '{}' is not in environment.allowed_keys", key);
+    }
+    Ok(())
+}
+
+/// Values must fit on one line (profile script) and within `setx` limits
+pub fn check_value(value: &str) -> Result<()> {
+    if value.contains(['\n', '\r', '\0']) {
+        bail!("Value must be a single line");
+    }
+    if value.len() > SETX_MAX_VALUE_LEN {
+        bail!("Value longer than {} bytes", SETX_MAX_VALUE_LEN);
+    }
+    Ok(())
+}
+
+/// Single-quote a value for POSIX shells
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Parse the `export KEY='value'` lines written by `render_profile`
+pub fn parse_profile(content: &str) -> BTreeMap<String, String> {
+    content.lines()
+        .filter_map(|line| line.strip_prefix("export "))
+        .filter_map(|assignment| assignment.split_once('='))
+        .filter_map(|(key, quoted)| {
+            let inner = quoted.strip_prefix('\'')?.strip_suffix('\'')?;
+            Some((key.to_string(), inner.replace("'\\''", "'")))
+        })
+        .collect()
+}
+
+/// Profile script content for the given values
+pub fn render_profile(values: &BTreeMap<String, String>) -> String {
+    let mut content = PROFILE_HEADER.to_string();
+    for (key, value) in values {
+        content.push_str(&format!("export {}={}\n", key, shell_quote(value)));
+    }
+    content
+}
+
+/// Program and arguments persisting (or removing) a machine-wide Windows variable
+pub fn windows_set_command(key: &str, value: Option<&str>) -> (&'static str, Vec<String>) {
+    match value {
+        Some(value) => ("setx", vec![key.to_string(), value.to_string(), "/M".to_string()]),
+        None => ("reg", vec![
+            "delete".to_string(),
+            WINDOWS_ENV_KEY.to_string(),
+            "/v".to_string(),
+            key.to_string(),
+            "/f".to_string(),
+        ]),
+    }
+}
+
+/// Value of `key` in `reg query <key> /v <name>` output
+pub fn parse_reg_query(output: &str, key: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        // "    NAME    REG_SZ    value with spaces"
+        let (name, rest) = line.trim().split_once(char::is_whitespace)?;
+        let rest = rest.trim_start();
+        let (kind, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        (name.eq_ignore_ascii_case(key) && kind.starts_with("REG_")).then(|| value.trim().to_string())
+    })
+}
+
+/// Current persistent values of `keys` (None = not set)
+pub async fn read_values(os: &str, keys: &[String], config: &EnvConfig) -> Result<BTreeMap<String, Option<String>>> {
+    for key in keys {
+        check_key(key, config)?;
+    }
+    match os {
+        "windows" => {
+            let mut values = BTreeMap::new();
+            for key in keys {
+                let output = AsyncCommand::new("reg").args(["query", WINDOWS_ENV_KEY, "/v", key]).output().await
+                    .context("Failed to run reg")?;
+                // reg exits non-zero when the value does not exist
+                values.insert(key.clone(), parse_reg_query(&String::from_utf8_lossy(&output.stdout), key));
+            }
+            Ok(values)
+        }
+        _ => {
+            let profile = read_profile(config).await?;
+            Ok(keys.iter().map(|key| (key.clone(), profile.get(key).cloned())).collect())
+        }
+    }
+}
+
+/// Validate then apply all changes (None removes the key); nothing is written if one is invalid
+pub async fn apply(os: &str, changes: &BTreeMap<String, Option<String>>, config: &EnvConfig) -> Result<Vec<EnvChange>> {
+    for (key, value) in changes {
+        check_key(key, config)?;
+        if let Some(value) = value {
+            check_value(value)?;
+        }
+    }
+    let keys: Vec<String> = changes.keys().cloned().collect();
+    let previous = read_values(os, &keys, config).await?;
+    let applied: Vec<EnvChange> = changes.iter()
+        .map(|(key, value)| EnvChange {
+            key: key.clone(),
+            previous: previous.get(key).cloned().flatten(),
+            value: value.clone(),
+            changed: previous.get(key).cloned().flatten() != *value,
+        })
+        .collect();
+
+    match os {
+        "windows" => apply_windows(&applied, run_windows_command).await?,
+        _ => {
+            if applied.iter().any(|c| c.changed) {
+                let mut profile = read_profile(config).await?;
+                for change in &applied {
+                    match &change.value {
+                        Some(value) => profile.insert(change.key.clone(), value.clone()),
+                        None => profile.remove(&change.key),
+                    };
+                }
+                write_profile(config, &render_profile(&profile)).await?;
+            }
+        }
+    }
+    Ok(applied)
+}
+
+/// Write changed keys one by one; on a failure, keys already written get their previous
+/// value back so the batch applies entirely or not at all
+async fn apply_windows<F, Fut>(applied: &[EnvChange], mut run: F) -> Result<()>
+where
+    F: FnMut(&'static str, Vec<String>) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut written: Vec<&EnvChange> = Vec::new();
+    for change in applied.iter().filter(|c| c.changed) {
+        let (program, args) = windows_set_command(&change.key, change.value.as_deref());
+        if let Err(e) = run(program, args).await {
+            for done in written.iter().rev() {
+                let (program, args) = windows_set_command(&done.key, done.previous.as_deref());
+                if let Err(undo) = run(program, args).await {
+                    warn!("Could not restore {} after a failed set_env: {}", done.key, undo);
+                }
+            }
+            return Err(e.context(format!("Cannot set {} (batch rolled back)", change.key)));
+        }
+        written.push(change);
+    }
+    Ok(())
+}
+
+async fn run_windows_command(program: &'static str, args: Vec<String>) -> Result<()> {
+    let output = AsyncCommand::new(program).args(&args).output().await
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        bail!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr));
+    }
+    Ok(())
+}
+
+async fn read_profile(config: &EnvConfig) -> Result<BTreeMap<String, String>> {
+    match tokio::fs::read_to_string(&config.profile_path).await {
+        Ok(content) => Ok(parse_profile(&content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e).with_context(|| format!("Cannot read {}", config.profile_path)),
+    }
+}
+
+/// Write through a temporary file so a login shell never sources a half-written script
+async fn write_profile(config: &EnvConfig, content: &str) -> Result<()> {
+    let temp = format!("{}.tmp", config.profile_path);
+    tokio::fs::write(&temp, content).await
+        .with_context(|| format!("Cannot write {}", temp))?;
+    tokio::fs::rename(&temp, &config.profile_path).await
+        .with_context(|| format!("Cannot replace {}", config.profile_path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(keys: &[&str]) -> EnvConfig {
+        EnvConfig {
+            allowed_keys: keys.iter().map(|k| k.to_string()).collect(),
+            profile_path: std::env::temp_dir()
+                .join(format!("symbion-env-{}.sh", uuid::Uuid::new_v4()))
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
+
+    #[test]
+    fn test_key_allowlist() {
+        let config = config(&["HTTP_PROXY", "SYMBION_SITE"]);
+        assert!(check_key("HTTP_PROXY", &config).is_ok());
+        assert!(check_key("PATH", &config).is_err());
+        assert!(check_key("http_proxy", &config).is_err());
+        assert!(check_key("BAD-NAME", &EnvConfig { allowed_keys: vec!["BAD-NAME".to_string()], ..config.clone() }).is_err());
+        assert!(check_key("", &config).is_err());
+
+        assert!(check_value("http://proxy.lan:3128").is_ok());
+        assert!(check_value("a\nexport PATH=/tmp").is_err());
+        assert!(check_value(&"x".repeat(SETX_MAX_VALUE_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn test_profile_roundtrip_quotes_values() {
+        let mut values = BTreeMap::new();
+        values.insert("SYMBION_SITE".to_string(), "it's $HOME; `id`".to_string());
+        values.insert("HTTP_PROXY".to_string(), "http://proxy.lan:3128".to_string());
+        let rendered = render_profile(&values);
+        assert!(rendered.contains("export HTTP_PROXY='http://proxy.lan:3128'\n"));
+        assert!(rendered.contains("export SYMBION_SITE='it'\\''s $HOME; `id`'\n"));
+        assert_eq!(parse_profile(&rendered), values);
+    }
+
+    #[test]
+    fn test_windows_command_construction() {
+        let (program, args) = windows_set_command("HTTP_PROXY", Some("http://proxy lan:3128"));
+        assert_eq!(program, "setx");
+        assert_eq!(args, vec!["HTTP_PROXY", "http://proxy lan:3128", "/M"]);
+
+        let (program, args) = windows_set_command("HTTP_PROXY", None);
+        assert_eq!(program, "reg");
+        assert_eq!(args, vec!["delete", WINDOWS_ENV_KEY, "/v", "HTTP_PROXY", "/f"]);
+
+        let output = "\r\nHKEY_LOCAL_MACHINE\\SYSTEM\\...\\Environment\r\n    HTTP_PROXY    REG_SZ    http://proxy lan:3128\r\n\r\n";
+        assert_eq!(parse_reg_query(output, "HTTP_PROXY").as_deref(), Some("http://proxy lan:3128"));
+        assert_eq!(parse_reg_query(output, "NO_PROXY"), None);
+    }
+
+    #[tokio::test]
+    async fn test_apply_reports_changes_and_rejects_whole_batch() {
+        let config = config(&["HTTP_PROXY", "SYMBION_SITE"]);
+        let mut changes = BTreeMap::new();
+        changes.insert("HTTP_PROXY".to_string(), Some("http://proxy.lan:3128".to_string()));
+        changes.insert("SYMBION_SITE".to_string(), None);
+        let applied = apply("linux", &changes, &config).await.unwrap();
+        assert_eq!(applied[0], EnvChange {
+            key: "HTTP_PROXY".to_string(),
+            previous: None,
+            value: Some("http://proxy.lan:3128".to_string()),
+            changed: true,
+        });
+        assert!(!applied[1].changed);
+
+        // Same value again: reported as unchanged
+        let again = apply("linux", &changes, &config).await.unwrap();
+        assert_eq!(again[0].previous.as_deref(), Some("http://proxy.lan:3128"));
+        assert!(!again[0].changed);
+
+        // One refused key: nothing written
+        changes.insert("PATH".to_string(), Some("/tmp".to_string()));
+        changes.insert("HTTP_PROXY".to_string(), Some("http://other:8080".to_string()));
+        assert!(apply("linux", &changes, &config).await.is_err());
+        let values = read_values("linux", &["HTTP_PROXY".to_string()], &config).await.unwrap();
+        assert_eq!(values["HTTP_PROXY"].as_deref(), Some("http://proxy.lan:3128"));
+
+        let _ = std::fs::remove_file(&config.profile_path);
+    }
+
+    #[tokio::test]
+    async fn test_windows_failure_restores_keys_already_written() {
+        let change = |key: &str, previous: Option<&str>, value: Option<&str>| EnvChange {
+            key: key.to_string(),
+            previous: previous.map(str::to_string),
+            value: value.map(str::to_string),
+            changed: previous != value,
+        };
+        let applied = [
+            change("HTTP_PROXY", Some("http://old:3128"), Some("http://new:3128")),
+            change("NO_PROXY", Some("localhost"), Some("localhost")),
+            change("SYMBION_SITE", None, Some("lyon")),
+            change("ZONE", None, Some("refused")),
+        ];
+        let calls = std::sync::Mutex::new(Vec::new());
+        let result = apply_windows(&applied, |program, args| {
+            calls.lock().unwrap().push(format!("{} {}", program, args.join(" ")));
+            let refused = args.first().is_some_and(|key| key == "ZONE");
+            async move { if refused { bail!("access denied") } else { Ok(()) } }
+        }).await;
+
+        assert!(result.unwrap_err().to_string().contains("ZONE"));
+        assert_eq!(*calls.lock().unwrap(), vec![
+            "setx HTTP_PROXY http://new:3128 /M".to_string(),
+            "setx SYMBION_SITE lyon /M".to_string(),
+            "setx ZONE refused /M".to_string(),
+            // Rolled back newest first: the new key is removed, the old value restored
+            format!("reg delete {} /v SYMBION_SITE /f", WINDOWS_ENV_KEY),
+            "setx HTTP_PROXY http://old:3128 /M".to_string(),
+        ]);
+    }
+}
@@ -3,15 +3,20 @@
 //! Handles secure execution of system commands:
 //! - Power management commands (shutdown, reboot, hibernate)
 //! - Process control (list, kill by PID)  
-//! - Shell command execution with timeout
+//! - Shell command execution with timeout and bounded output capture, or streamed output
 //! - Service management (start/stop/status)
+//! - Wake-on-LAN relay for hosts on the local subnet
 //! - Cross-platform implementation
 
 use anyhow::{Result, Context, anyhow};
 use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
 use std::process::Stdio;
 use std::time::{Duration, Instant};
-use tokio::process::Command as AsyncCommand;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, Command as AsyncCommand};
+use tokio::sync::mpsc;
 use tracing::{info, debug};
 
 /// Command execution result
@@ -22,6 +27,198 @@ pub struct ExecutionResult {
     pub error: Option<String>,
     pub exit_code: Option<i32>,
     pub execution_time_ms: u128,
+    /// Output exceeded the size limit and was cut
+    pub truncated: bool,
+}
+
+/// Default per-stream output limit of shell commands (1 MiB)
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Appended to a stream cut at the output limit
+pub const TRUNCATION_MARKER: &str = "\n[output truncated]";
+
+/// Default size limit of the input fed to a command's stdin (256 KiB)
+pub const DEFAULT_MAX_STDIN_BYTES: usize = 256 * 1024;
+
+/// Decode a `stdin` parameter (`encoding`: "text", the default, or "base64"), refusing
+/// input larger than `max_bytes` once decoded
+pub fn decode_stdin(stdin: &str, encoding: Option<&str>, max_bytes: usize) -> Result<Vec<u8>> {
+    let input = match encoding.unwrap_or("text") {
+        "text" => stdin.as_bytes().to_vec(),
+        "base64" => BASE64.decode(stdin.trim()).context("stdin is not valid base64")?,
+        other => return Err(anyhow!("unsupported stdin encoding '{}' (text or base64)", other)),
+    };
+    if input.len() > max_bytes {
+        return Err(anyhow!("stdin is {} bytes, limit is {}", input.len(), max_bytes));
+    }
+    Ok(input)
+}
+
+/// Captured output of a command, each stream capped
+#[derive(Debug)]
+pub struct CappedOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    pub truncated: bool,
+}
+
+/// Run a command reading stdout/stderr incrementally, keeping at most `max_output_bytes` of each.
+/// The rest is drained and dropped so the child never blocks on a full pipe; the child is
+/// killed if the returned future is dropped (e.g. by a timeout).
+pub async fn run_capped(command: AsyncCommand, max_output_bytes: usize) -> Result<CappedOutput> {
+    run_capped_with_stdin(command, max_output_bytes, None).await
+}
+
+/// `run_capped`, writing `stdin` to the child's standard input (closed once written)
+pub async fn run_capped_with_stdin(command: AsyncCommand, max_output_bytes: usize, stdin: Option<Vec<u8>>) -> Result<CappedOutput> {
+    let (mut child, input) = spawn_piped(command, stdin)?;
+    let stdout = child.stdout.take().context("stdout not captured")?;
+    let stderr = child.stderr.take().context("stderr not captured")?;
+
+    let (stdout, stderr, _, status) = tokio::join!(
+        read_capped(stdout, max_output_bytes),
+        read_capped(stderr, max_output_bytes),
+        input,
+        child.wait(),
+    );
+    let (stdout, stdout_truncated) = stdout?;
+    let (stderr, stderr_truncated) = stderr?;
+    Ok(CappedOutput {
+        stdout,
+        stderr,
+        exit_code: status?.code(),
+        truncated: stdout_truncated || stderr_truncated,
+    })
+}
+
+/// Output stream of a child process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Output of a streamed command, as read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputChunk {
+    pub stream: OutputStream,
+    pub data: String,
+}
+
+impl OutputChunk {
+    /// Cut the chunk so that `sent` + its length stays within `max_bytes` (never inside a
+    /// character); true when it was cut, i.e. the output limit is reached
+    pub fn cap(&mut self, sent: usize, max_bytes: usize) -> bool {
+        let room = max_bytes.saturating_sub(sent);
+        if self.data.len() <= room {
+            return false;
+        }
+        let mut end = room;
+        while !self.data.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.data.truncate(end);
+        true
+    }
+}
+
+/// Run a command forwarding stdout/stderr to `chunks` as they are produced (nothing is kept),
+/// returning the exit code. Like `run_capped`, the child is killed if the future is dropped.
+pub async fn run_streaming(command: AsyncCommand, stdin: Option<Vec<u8>>, chunks: mpsc::UnboundedSender<OutputChunk>) -> Result<Option<i32>> {
+    let (mut child, input) = spawn_piped(command, stdin)?;
+    let stdout = child.stdout.take().context("stdout not captured")?;
+    let stderr = child.stderr.take().context("stderr not captured")?;
+
+    let (stdout, stderr, _, status) = tokio::join!(
+        forward_output(stdout, OutputStream::Stdout, &chunks),
+        forward_output(stderr, OutputStream::Stderr, &chunks),
+        input,
+        child.wait(),
+    );
+    stdout?;
+    stderr?;
+    Ok(status?.code())
+}
+
+/// Spawn with stdout/stderr piped and, when input is given, stdin piped. The returned future
+/// writes the input then closes stdin; it runs alongside the output readers so a child that
+/// writes before reading can't deadlock on a full pipe.
+fn spawn_piped(mut command: AsyncCommand, stdin: Option<Vec<u8>>) -> Result<(Child, impl std::future::Future<Output = ()>)> {
+    if stdin.is_some() {
+        command.stdin(Stdio::piped());
+    }
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+    let pipe = child.stdin.take();
+    let input = async move {
+        if let (Some(mut pipe), Some(input)) = (pipe, stdin) {
+            // A child that exits without reading its input closes the pipe: not an error
+            if let Err(e) = pipe.write_all(&input).await {
+                debug!("stdin not fully written: {}", e);
+            }
+        }
+    };
+    Ok((child, input))
+}
+
+async fn forward_output<R: AsyncRead + Unpin>(mut reader: R, stream: OutputStream, chunks: &mpsc::UnboundedSender<OutputChunk>) -> std::io::Result<()> {
+    let mut buffer = [0u8; 8192];
+    let mut pending = Vec::new();
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        pending.extend_from_slice(&buffer[..read]);
+        let data = take_utf8(&mut pending);
+        if !data.is_empty() {
+            let _ = chunks.send(OutputChunk { stream, data });
+        }
+    }
+    if !pending.is_empty() {
+        let _ = chunks.send(OutputChunk { stream, data: String::from_utf8_lossy(&pending).into_owned() });
+    }
+    Ok(())
+}
+
+/// Decodable part of `pending`: a character cut between two reads waits for the next one
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let complete = match std::str::from_utf8(pending) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => pending.len(),
+    };
+    let rest = pending.split_off(complete);
+    let data = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+    data
+}
+
+/// Read a stream to the end, keeping the first `max_bytes` (marker appended when cut)
+async fn read_capped<R: AsyncRead + Unpin>(mut reader: R, max_bytes: usize) -> std::io::Result<(String, bool)> {
+    let mut kept = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let mut truncated = false;
+    loop {
+        let read = reader.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        let room = max_bytes.saturating_sub(kept.len());
+        if read > room {
+            truncated = true;
+        }
+        kept.extend_from_slice(&chunk[..read.min(room)]);
+    }
+    let mut text = String::from_utf8_lossy(&kept).into_owned();
+    if truncated {
+        text.push_str(TRUNCATION_MARKER);
+    }
+    Ok((text, truncated))
 }
 
 /// Process information for listing
@@ -35,6 +232,32 @@ pub struct ProcessInfo {
     pub user: Option<String>,
 }
 
+/// Default `commands.allowed_commands`: programs accepted by run_command and scheduled tasks
+pub const DEFAULT_ALLOWED_COMMANDS: &[&str] = &["dir", "ls", "whoami", "hostname", "date", "uptime", "ps", "tasklist", "shutdown"];
+
+/// Characters that would chain, substitute or redirect behind an allowed argv[0] once the line
+/// reaches `sh -c` / `cmd /C` (shared by run_command and scheduled tasks)
+pub const SHELL_METACHARACTERS: &[char] = &[';', '|', '&', '`', '$', '>', '<', '\n', '\r'];
+
+/// Program name of a command line (first whitespace-separated token)
+pub fn argv0(command: &str) -> Option<&str> {
+    command.split_whitespace().next()
+}
+
+/// Check a command line against the allowlist (an empty allowlist accepts everything)
+pub fn check_command_allowed(command: &str, allowed: &[String]) -> Result<()> {
+    if allowed.is_empty() {
+        return Ok(());
+    }
+    if let Some(metacharacter) = command.chars().find(|c| SHELL_METACHARACTERS.contains(c)) {
+        return Err(anyhow!("Command contains shell metacharacter {:?}: {}", metacharacter, command));
+    }
+    match argv0(command) {
+        Some(program) if allowed.iter().any(|a| a == program) => Ok(()),
+        _ => Err(anyhow!("Command not allowed: {}", command)),
+    }
+}
+
 /// Cross-platform command executor
 pub struct CommandExecutor;
 
@@ -60,6 +283,7 @@ impl CommandExecutor {
                 error: None,
                 exit_code: Some(0),
                 execution_time_ms: execution_time,
+                truncated: false,
             }),
             Err(e) => Ok(ExecutionResult {
                 success: false,
@@ -67,30 +291,32 @@ impl CommandExecutor {
                 error: Some(e.to_string()),
                 exit_code: Some(1),
                 execution_time_ms: execution_time,
+                truncated: false,
             }),
         }
     }
     
-    /// Execute shell command with timeout
-    pub async fn execute_shell_command(command: &str, timeout_secs: u32) -> Result<ExecutionResult> {
+    /// Execute shell command with timeout, keeping at most `max_output_bytes` per stream
+    pub async fn execute_shell_command(command: &str, timeout_secs: u32, max_output_bytes: usize) -> Result<ExecutionResult> {
         let start_time = Instant::now();
         debug!("Executing shell command: {} (timeout: {}s)", command, timeout_secs);
         
         let result = if cfg!(target_os = "windows") {
-            Self::execute_windows_command(command, timeout_secs).await
+            Self::execute_windows_command(command, timeout_secs, max_output_bytes).await
         } else {
-            Self::execute_unix_command(command, timeout_secs).await
+            Self::execute_unix_command(command, timeout_secs, max_output_bytes).await
         };
         
         let execution_time = start_time.elapsed().as_millis();
         
         match result {
-            Ok((output, exit_code)) => Ok(ExecutionResult {
+            Ok((output, exit_code, truncated)) => Ok(ExecutionResult {
                 success: exit_code == 0,
                 output,
                 error: None,
                 exit_code: Some(exit_code),
                 execution_time_ms: execution_time,
+                truncated,
             }),
             Err(e) => Ok(ExecutionResult {
                 success: false,
@@ -98,6 +324,7 @@ impl CommandExecutor {
                 error: Some(e.to_string()),
                 exit_code: Some(-1),
                 execution_time_ms: execution_time,
+                truncated: false,
             }),
         }
     }
@@ -122,6 +349,7 @@ impl CommandExecutor {
                 error: None,
                 exit_code: Some(0),
                 execution_time_ms: execution_time,
+                truncated: false,
             }),
             Err(e) => Ok(ExecutionResult {
                 success: false,
@@ -129,6 +357,7 @@ impl CommandExecutor {
                 error: Some(e.to_string()),
                 exit_code: Some(1),
                 execution_time_ms: execution_time,
+                truncated: false,
             }),
         }
     }
@@ -247,57 +476,77 @@ impl CommandExecutor {
         }
     }
     
-    async fn execute_unix_command(command: &str, timeout_secs: u32) -> Result<(String, i32)> {
+    async fn execute_unix_command(command: &str, timeout_secs: u32, max_output_bytes: usize) -> Result<(String, i32, bool)> {
+        let mut bash = AsyncCommand::new("bash");
+        bash.arg("-c").arg(command);
+        Self::execute_capped(bash, timeout_secs, max_output_bytes).await
+    }
+    
+    async fn execute_windows_command(command: &str, timeout_secs: u32, max_output_bytes: usize) -> Result<(String, i32, bool)> {
+        let mut cmd = AsyncCommand::new("cmd");
+        cmd.args(["/C", command]);
+        Self::execute_capped(cmd, timeout_secs, max_output_bytes).await
+    }
+    
+    async fn execute_capped(command: AsyncCommand, timeout_secs: u32, max_output_bytes: usize) -> Result<(String, i32, bool)> {
         let output = tokio::time::timeout(
             Duration::from_secs(timeout_secs as u64),
-            AsyncCommand::new("bash")
-                .arg("-c")
-                .arg(command)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .output()
+            run_capped(command, max_output_bytes)
         )
         .await
         .context("Command timed out")?
         .context("Failed to execute command")?;
         
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let combined_output = if stderr.is_empty() {
-            stdout.to_string()
+        let combined_output = if output.stderr.is_empty() {
+            output.stdout
         } else {
-            format!("{}\nSTDERR:\n{}", stdout, stderr)
+            format!("{}\nSTDERR:\n{}", output.stdout, output.stderr)
         };
         
-        let exit_code = output.status.code().unwrap_or(-1);
-        
-        Ok((combined_output, exit_code))
+        Ok((combined_output, output.exit_code.unwrap_or(-1), output.truncated))
     }
     
-    async fn execute_windows_command(command: &str, timeout_secs: u32) -> Result<(String, i32)> {
-        let output = tokio::time::timeout(
-            Duration::from_secs(timeout_secs as u64),
-            AsyncCommand::new("cmd")
-                .args(["/C", command])
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .output()
-        )
-        .await
-        .context("Command timed out")?
-        .context("Failed to execute command")?;
+    /// Send a Wake-on-LAN magic packet on the local network (UDP ports 9 and 7)
+    pub fn wake_on_lan(mac: &str, broadcast: Ipv4Addr) -> Result<()> {
+        let packet = Self::magic_packet(Self::parse_mac(mac)?);
         
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let combined_output = if stderr.is_empty() {
-            stdout.to_string()
-        } else {
-            format!("{}\nSTDERR:\n{}", stdout, stderr)
-        };
+        let sock = UdpSocket::bind(("0.0.0.0", 0)).context("Failed to bind UDP socket")?;
+        sock.set_broadcast(true).context("Failed to enable broadcast")?;
         
-        let exit_code = output.status.code().unwrap_or(-1);
+        let mut sent = false;
+        for port in [9u16, 7u16] {
+            match sock.send_to(&packet, SocketAddrV4::new(broadcast, port)) {
+                Ok(_) => sent = true,
+                Err(e) => debug!("WOL send to {}:{} failed: {}", broadcast, port, e),
+            }
+        }
         
-        Ok((combined_output, exit_code))
+        if sent {
+            info!("Magic packet sent for {} via {}", mac, broadcast);
+            Ok(())
+        } else {
+            Err(anyhow!("Failed to send magic packet for {}", mac))
+        }
+    }
+    
+    fn parse_mac(mac: &str) -> Result<[u8; 6]> {
+        let hex: String = mac.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+        if hex.len() != 12 {
+            return Err(anyhow!("Invalid MAC address: {}", mac));
+        }
+        let mut bytes = [0u8; 6];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)?;
+        }
+        Ok(bytes)
+    }
+    
+    fn magic_packet(mac: [u8; 6]) -> [u8; 102] {
+        let mut packet = [0xFFu8; 102];
+        for chunk in packet[6..].chunks_mut(6) {
+            chunk.copy_from_slice(&mac);
+        }
+        packet
     }
     
     async fn kill_process_unix(pid: u32) -> Result<String> {
@@ -336,16 +585,159 @@ mod tests {
     #[tokio::test]
     async fn test_shell_command_execution() {
         let result = if cfg!(target_os = "windows") {
-            CommandExecutor::execute_shell_command("echo Hello World", 5).await.unwrap()
+            CommandExecutor::execute_shell_command("echo Hello World", 5, DEFAULT_MAX_OUTPUT_BYTES).await.unwrap()
         } else {
-            CommandExecutor::execute_shell_command("echo 'Hello World'", 5).await.unwrap()
+            CommandExecutor::execute_shell_command("echo 'Hello World'", 5, DEFAULT_MAX_OUTPUT_BYTES).await.unwrap()
         };
         
         assert!(result.success);
         assert!(result.output.contains("Hello World"));
         assert!(result.execution_time_ms < 5000);
+        assert!(!result.truncated);
+    }
+    
+    #[tokio::test]
+    async fn test_large_output_is_truncated_at_limit() {
+        let command = if cfg!(target_os = "windows") {
+            "for /L %i in (1,1,20000) do @echo 0123456789"
+        } else {
+            "yes 0123456789 | head -c 1000000"
+        };
+        let result = CommandExecutor::execute_shell_command(command, 10, 4096).await.unwrap();
+        
+        assert!(result.success);
+        assert!(result.truncated);
+        assert!(result.output.ends_with(TRUNCATION_MARKER));
+        assert_eq!(result.output.len(), 4096 + TRUNCATION_MARKER.len());
     }
     
+    #[test]
+    fn test_allowlist_matches_argv0_and_rejects_chaining() {
+        let allowed: Vec<String> = DEFAULT_ALLOWED_COMMANDS.iter().map(|c| c.to_string()).collect();
+        assert!(check_command_allowed("ls -la /tmp", &allowed).is_ok());
+        assert!(check_command_allowed("  uptime", &allowed).is_ok());
+        assert!(check_command_allowed("shutdown /a", &allowed).is_ok());
+
+        // Prefix matches used to let these through
+        assert!(check_command_allowed("lsblk", &allowed).is_err());
+        assert!(check_command_allowed("datelocal --evil", &allowed).is_err());
+        assert!(check_command_allowed("pskill 4", &allowed).is_err());
+        assert!(check_command_allowed("rm -rf /", &allowed).is_err());
+        assert!(check_command_allowed("", &allowed).is_err());
+
+        // Injection behind an allowed program
+        for command in ["ls; rm -rf /", "ls | sh", "ls && reboot", "ls & rm x", "ls `rm x`", "ls $(rm x)", "ls\nrm x"] {
+            assert!(check_command_allowed(command, &allowed).is_err(), "{command} should be rejected");
+        }
+        // Redirections and expansions run by the shell
+        for command in ["date > /root/.ssh/authorized_keys", "ls >> /etc/passwd", "ls < /etc/shadow", "ls${IFS}/root", "ls $HOME"] {
+            assert!(check_command_allowed(command, &allowed).is_err(), "{command} should be rejected");
+        }
+
+        // Allowlist disabled
+        assert!(check_command_allowed("ls; rm -rf /", &[]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_streamed_output_is_forwarded_per_stream() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let command = if cfg!(target_os = "windows") {
+            let mut command = AsyncCommand::new("cmd");
+            command.args(["/C", "echo out & echo err 1>&2"]);
+            command
+        } else {
+            let mut command = AsyncCommand::new("sh");
+            command.args(["-c", "echo out; echo err >&2; exit 3"]);
+            command
+        };
+        let exit_code = run_streaming(command, None, sender).await.unwrap();
+        if !cfg!(target_os = "windows") {
+            assert_eq!(exit_code, Some(3));
+        }
+
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        while let Some(chunk) = receiver.recv().await {
+            match chunk.stream {
+                OutputStream::Stdout => stdout.push_str(&chunk.data),
+                OutputStream::Stderr => stderr.push_str(&chunk.data),
+            }
+        }
+        assert_eq!(stdout.trim(), "out");
+        assert_eq!(stderr.trim(), "err");
+    }
+
+    #[test]
+    fn test_streamed_chunk_is_capped_at_the_output_limit() {
+        let mut chunk = OutputChunk { stream: OutputStream::Stdout, data: "abcdef".to_string() };
+        assert!(!chunk.cap(4, 10));
+        assert_eq!(chunk.data, "abcdef");
+
+        assert!(chunk.cap(6, 10));
+        assert_eq!(chunk.data, "abcd");
+
+        // Never cut inside a character
+        let mut chunk = OutputChunk { stream: OutputStream::Stderr, data: "ééé".to_string() };
+        assert!(chunk.cap(0, 3));
+        assert_eq!(chunk.data, "é");
+        assert!(chunk.cap(10, 10));
+        assert!(chunk.data.is_empty());
+    }
+
+    #[test]
+    fn test_characters_split_between_reads_are_kept_whole() {
+        let bytes = "déjà".as_bytes();
+        // "d" + first byte of "é"
+        let mut pending = bytes[..2].to_vec();
+        assert_eq!(take_utf8(&mut pending), "d");
+        assert_eq!(pending, vec![0xc3]);
+        pending.extend_from_slice(&bytes[2..]);
+        assert_eq!(take_utf8(&mut pending), "éjà");
+        assert!(pending.is_empty());
+
+        // Invalid bytes are not held back forever
+        let mut pending = vec![b'a', 0xff, b'b'];
+        assert_eq!(take_utf8(&mut pending), "a\u{fffd}b");
+        assert!(pending.is_empty());
+    }
+
+    fn stdin_echo() -> AsyncCommand {
+        if cfg!(target_os = "windows") {
+            let mut command = AsyncCommand::new("cmd");
+            command.args(["/C", "findstr", "^"]);
+            command
+        } else {
+            AsyncCommand::new("cat")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stdin_is_piped_to_the_command() {
+        let input = decode_stdin("line one\nline two\n", None, DEFAULT_MAX_STDIN_BYTES).unwrap();
+        let output = run_capped_with_stdin(stdin_echo(), DEFAULT_MAX_OUTPUT_BYTES, Some(input)).await.unwrap();
+        assert_eq!(output.exit_code, Some(0));
+        assert_eq!(output.stdout.lines().collect::<Vec<_>>(), vec!["line one", "line two"]);
+
+        // Streaming mode, binary-safe input given as base64
+        let input = decode_stdin(&BASE64.encode("streamed input\n"), Some("base64"), DEFAULT_MAX_STDIN_BYTES).unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        run_streaming(stdin_echo(), Some(input), sender).await.unwrap();
+        let mut stdout = String::new();
+        while let Some(chunk) = receiver.recv().await {
+            stdout.push_str(&chunk.data);
+        }
+        assert_eq!(stdout.trim(), "streamed input");
+    }
+
+    #[test]
+    fn test_stdin_decoding_and_size_limit() {
+        assert_eq!(decode_stdin("aGVsbG8=", Some("base64"), 16).unwrap(), b"hello");
+        assert!(decode_stdin("not base64!", Some("base64"), 16).is_err());
+        assert!(decode_stdin("hello", Some("hex"), 16).is_err());
+        assert_eq!(decode_stdin("0123456789", None, 10).unwrap().len(), 10);
+        assert!(decode_stdin("0123456789a", None, 10).is_err());
+    }
+
     #[tokio::test]
     async fn test_process_listing() {
         let processes = CommandExecutor::list_processes().await.unwrap();
@@ -356,13 +748,24 @@ mod tests {
     #[tokio::test]
     async fn test_command_timeout() {
         let result = if cfg!(target_os = "windows") {
-            CommandExecutor::execute_shell_command("ping -t 127.0.0.1", 2).await.unwrap()
+            CommandExecutor::execute_shell_command("ping -t 127.0.0.1", 2, DEFAULT_MAX_OUTPUT_BYTES).await.unwrap()
         } else {
-            CommandExecutor::execute_shell_command("sleep 10", 2).await.unwrap()
+            CommandExecutor::execute_shell_command("sleep 10", 2, DEFAULT_MAX_OUTPUT_BYTES).await.unwrap()
         };
         
         // Command should timeout and fail
         assert!(!result.success);
         assert!(result.error.is_some());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_magic_packet_layout() {
+        let mac = CommandExecutor::parse_mac("a1:b2:c3:d4:e5:f6").unwrap();
+        assert_eq!(mac, [0xa1, 0xb2, 0xc3, 0xd4, 0xe5, 0xf6]);
+        assert!(CommandExecutor::parse_mac("a1:b2:c3").is_err());
+        
+        let packet = CommandExecutor::magic_packet(mac);
+        assert_eq!(&packet[..6], &[0xFF; 6]);
+        assert_eq!(&packet[96..], &mac);
+    }
+}
@@ -0,0 +1,302 @@
+//! Read-only file operations for Symbion agents
+//!
+//! Backs the `tail_file` and `read_file` commands:
+//! - Paths are canonicalized and must live under an allow-listed root
+//!   (`file_ops.allowed_roots`), so `..` and symlinks cannot escape it
+//! - At most `max_read_bytes` are read from the end of the file; a line cut
+//!   by that cap is dropped rather than returned half-way
+//! - Follow mode polls the file for appended bytes and restarts from the
+//!   beginning when the file shrinks (rotation, truncation)
+//! - Chunked transfers can be paced by a token bucket (`rate_limit_kbps`) so a
+//!   bulk copy leaves room on a slow link for heartbeats and other commands
+
+use crate::config::FileOpsConfig;
+use anyhow::{bail, Context, Result};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Last lines of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct TailResult {
+    pub lines: Vec<String>,
+    /// File size when read
+    pub size: u64,
+    /// True if the read cap hid older content
+    pub truncated: bool,
+}
+
+/// Resolve `path` and check it is inside one of the allowed roots
+pub fn resolve_allowed(path: &str, config: &FileOpsConfig) -> Result<PathBuf> {
+    let resolved = std::fs::canonicalize(path)
+        .with_context(|| format!("Cannot access '{}'", path))?;
+    let allowed = config.allowed_roots.iter()
+        .filter_map(|root| std::fs::canonicalize(root).ok())
+        .any(|root| resolved.starts_with(root));
+    if !allowed {
+        bail!("'{}' is outside the allowed roots {:?}", path, config.allowed_roots);
+    }
+    if !resolved.is_file() {
+        bail!("'{}' is not a regular file", path);
+    }
+    Ok(resolved)
+}
+
+/// Last `count` lines of `content`; a trailing newline does not start an empty line
+pub fn last_lines(content: &str, count: usize) -> Vec<String> {
+    let mut lines: Vec<&str> = content.lines().collect();
+    let skip = lines.len().saturating_sub(count);
+    lines.drain(..skip);
+    lines.into_iter().map(str::to_string).collect()
+}
+
+/// Read the last `count` lines, reading no more than `max_bytes` from the end
+pub async fn read_tail(path: &Path, count: usize, max_bytes: u64) -> Result<TailResult> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let size = file.metadata().await?.len();
+    let start = size.saturating_sub(max_bytes);
+    // One extra byte tells whether `start` is a line boundary
+    file.seek(SeekFrom::Start(start.saturating_sub(1))).await?;
+
+    let mut buffer = Vec::with_capacity((size - start + 1) as usize);
+    file.read_to_end(&mut buffer).await?;
+    let mut content = String::from_utf8_lossy(&buffer).into_owned();
+
+    // Reading mid-file: drop everything up to the first line boundary
+    if start > 0 {
+        content = match content.find('\n') {
+            Some(newline) => content.split_off(newline + 1),
+            None => String::new(),
+        };
+    }
+
+    let lines = last_lines(&content, count);
+    let truncated = start > 0 && lines.len() < count;
+    Ok(TailResult { lines, size, truncated })
+}
+
+/// Incremental reader for follow mode
+#[derive(Debug)]
+pub struct FileFollower {
+    path: PathBuf,
+    offset: u64,
+    /// Bytes of a line not terminated yet
+    pending: String,
+}
+
+impl FileFollower {
+    /// Start following from `offset` (usually the size returned by `read_tail`)
+    pub fn new(path: PathBuf, offset: u64) -> Self {
+        Self { path, offset, pending: String::new() }
+    }
+
+    /// Complete lines appended since the last poll (at most `max_bytes` read per poll)
+    pub async fn poll(&mut self, max_bytes: u64) -> Result<Vec<String>> {
+        let mut file = tokio::fs::File::open(&self.path).await?;
+        let size = file.metadata().await?.len();
+        if size < self.offset {
+            // Rotated or truncated: read the new content from the start
+            self.offset = 0;
+            self.pending.clear();
+        }
+        if size == self.offset {
+            return Ok(Vec::new());
+        }
+
+        file.seek(SeekFrom::Start(self.offset)).await?;
+        let mut buffer = Vec::new();
+        (&mut file).take(max_bytes).read_to_end(&mut buffer).await?;
+        self.offset += buffer.len() as u64;
+        self.pending.push_str(&String::from_utf8_lossy(&buffer));
+
+        let Some(last_newline) = self.pending.rfind('\n') else {
+            return Ok(Vec::new());
+        };
+        let rest = self.pending.split_off(last_newline + 1);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        Ok(complete.lines().map(str::to_string).collect())
+    }
+}
+
+/// Read up to `len` bytes starting at `offset` (empty at end of file)
+pub async fn read_chunk(path: &Path, offset: u64, len: u64) -> Result<Vec<u8>> {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut buffer = Vec::new();
+    file.take(len).read_to_end(&mut buffer).await?;
+    Ok(buffer)
+}
+
+/// Token bucket pacing a chunked transfer
+///
+/// Tokens are bytes. A chunk larger than the available tokens is still sent,
+/// leaving the bucket in debt: the caller waits until the debt is repaid, so
+/// the long-run rate never exceeds the limit whatever the chunk size.
+#[derive(Debug)]
+pub struct TokenBucket {
+    bytes_per_sec: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Bucket for `rate_kbps` kilobits per second, allowing bursts of `burst_bytes`
+    pub fn new(rate_kbps: u64, burst_bytes: u64) -> Self {
+        let capacity = burst_bytes as f64;
+        Self {
+            bytes_per_sec: rate_kbps as f64 * 1000.0 / 8.0,
+            capacity,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Bytes allowed per second
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec as u64
+    }
+
+    /// Take `bytes` tokens at `now`; returns how long to wait before sending them
+    pub fn reserve(&mut self, bytes: u64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.bytes_per_sec).min(self.capacity);
+        self.last_refill = now;
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.bytes_per_sec)
+        }
+    }
+
+    /// Wait until `bytes` may be sent
+    pub async fn acquire(&mut self, bytes: u64) {
+        let wait = self.reserve(bytes, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("symbion-tail-{}.log", uuid::Uuid::new_v4()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_last_lines() {
+        assert_eq!(last_lines("a\nb\nc\n", 2), vec!["b", "c"]);
+        assert_eq!(last_lines("a\r\nb\r\nc", 2), vec!["b", "c"]);
+        assert_eq!(last_lines("a\nb\n", 10), vec!["a", "b"]);
+        assert_eq!(last_lines("a\n\nb\n", 2), vec!["", "b"]);
+        assert!(last_lines("a\nb\n", 0).is_empty());
+        assert!(last_lines("", 5).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_read_tail_respects_byte_cap() {
+        let content: String = (1..=100).map(|i| format!("line {:03}\n", i)).collect();
+        let path = temp_file(&content);
+
+        let full = read_tail(&path, 3, 1024 * 1024).await.unwrap();
+        assert_eq!(full.lines, vec!["line 098", "line 099", "line 100"]);
+        assert_eq!(full.size, content.len() as u64);
+        assert!(!full.truncated);
+
+        // 9 bytes per line: a 40-byte cap cuts "line 096" and keeps 4 whole lines
+        let capped = read_tail(&path, 10, 40).await.unwrap();
+        assert_eq!(capped.lines, vec!["line 097", "line 098", "line 099", "line 100"]);
+        assert!(capped.truncated);
+
+        // Cap landing exactly on a line start keeps that line
+        let aligned = read_tail(&path, 10, 18).await.unwrap();
+        assert_eq!(aligned.lines, vec!["line 099", "line 100"]);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_follower_streams_appended_lines() {
+        let path = temp_file("old\n");
+        let mut follower = FileFollower::new(path.clone(), 4);
+        assert!(follower.poll(1024).await.unwrap().is_empty());
+
+        std::fs::write(&path, "old\nnew 1\nnew 2\npart").unwrap();
+        assert_eq!(follower.poll(1024).await.unwrap(), vec!["new 1", "new 2"]);
+        std::fs::write(&path, "old\nnew 1\nnew 2\npartial\n").unwrap();
+        assert_eq!(follower.poll(1024).await.unwrap(), vec!["partial"]);
+
+        // Rotation: smaller file is read from the start
+        std::fs::write(&path, "fresh\n").unwrap();
+        assert_eq!(follower.poll(1024).await.unwrap(), vec!["fresh"]);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_resolve_allowed_rejects_escape() {
+        let path = temp_file("x\n");
+        let root = path.parent().unwrap().to_string_lossy().to_string();
+        let config = FileOpsConfig { allowed_roots: vec![root.clone()], ..FileOpsConfig::default() };
+        assert!(resolve_allowed(path.to_str().unwrap(), &config).is_ok());
+
+        let outside = FileOpsConfig { allowed_roots: vec![format!("{}/symbion-none", root)], ..FileOpsConfig::default() };
+        assert!(resolve_allowed(path.to_str().unwrap(), &outside).is_err());
+        let escape = format!("{}/../etc/passwd", root);
+        assert!(resolve_allowed(&escape, &config).is_err());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_token_bucket_accounting() {
+        // 80 kbps = 10 000 bytes/s, one 10 000-byte chunk of burst
+        let mut bucket = TokenBucket::new(80, 10_000);
+        assert_eq!(bucket.bytes_per_sec(), 10_000);
+        let mut now = bucket.last_refill;
+        let start = now;
+        for _ in 0..10 {
+            now += bucket.reserve(10_000, now);
+        }
+        // First chunk rides the burst, the nine others are paced
+        assert_eq!(now - start, Duration::from_secs(9));
+
+        // An idle period refills at most the burst capacity
+        now += Duration::from_secs(60);
+        assert_eq!(bucket.reserve(10_000, now), Duration::ZERO);
+        assert_eq!(bucket.reserve(5_000, now), Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_transfer_timing() {
+        let content = vec![b'x'; 50_000];
+        let path = std::env::temp_dir().join(format!("symbion-transfer-{}.bin", uuid::Uuid::new_v4()));
+        std::fs::write(&path, &content).unwrap();
+
+        // 800 kbps = 100 000 bytes/s: 50 000 bytes in 10 000-byte chunks, first one free
+        let mut bucket = TokenBucket::new(800, 10_000);
+        let start = Instant::now();
+        let mut offset = 0;
+        loop {
+            let chunk = read_chunk(&path, offset, 10_000).await.unwrap();
+            if chunk.is_empty() {
+                break;
+            }
+            bucket.acquire(chunk.len() as u64).await;
+            offset += chunk.len() as u64;
+        }
+        let elapsed = start.elapsed();
+        assert_eq!(offset, 50_000);
+        assert!(elapsed >= Duration::from_millis(380), "too fast: {:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1500), "too slow: {:?}", elapsed);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
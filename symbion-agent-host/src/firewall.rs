@@ -0,0 +1,489 @@
+//! Host firewall rules for Symbion agents
+//!
+//! Backs the `firewall_list` / `firewall_add` / `firewall_remove` commands (remote security response):
+//! - Refused unless `firewall.enable_firewall_mgmt` is set in the agent config
+//! - Linux: `ufw` (default) or `iptables` (`ip6tables` for IPv6 remotes), chosen by `firewall.linux_backend`
+//! - Windows: `netsh advfirewall firewall`, rules named after their fields so they can be removed
+//! - A rule targets a port and/or a remote address (no blanket rule that could cut the kernel off):
+//!   a `/0` remote is refused, and so is an added deny rule matching the MQTT broker
+//! - Commands are built from validated fields only, never from kernel-supplied strings
+//! - `dry_run` returns the planned command without running it
+
+use crate::config::FirewallBackend;
+use crate::execution::{self, DEFAULT_MAX_OUTPUT_BYTES};
+use crate::priority::PlannedCommand;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::process::Command as AsyncCommand;
+
+/// Maximum time given to ufw/iptables/netsh
+pub const FIREWALL_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Comment tagging iptables rules added by the agent
+const IPTABLES_COMMENT: &str = "symbion";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirewallAction {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    In,
+    Out,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Tcp,
+    Udp,
+    Any,
+}
+
+impl Protocol {
+    fn name(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "tcp",
+            Protocol::Udp => "udp",
+            Protocol::Any => "any",
+        }
+    }
+}
+
+/// Rule as sent by the kernel (`rule` parameter)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FirewallRule {
+    pub action: FirewallAction,
+    #[serde(default)]
+    pub direction: Direction,
+    #[serde(default)]
+    pub protocol: Protocol,
+    /// Local port for inbound rules, remote port for outbound ones
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Peer address or CIDR: source of inbound traffic, destination of outbound traffic
+    #[serde(default)]
+    pub remote: Option<String>,
+}
+
+/// `remote` as an address with its prefix length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Remote {
+    addr: IpAddr,
+    prefix: Option<u8>,
+}
+
+impl std::fmt::Display for Remote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.prefix {
+            Some(prefix) => write!(f, "{}/{}", self.addr, prefix),
+            None => write!(f, "{}", self.addr),
+        }
+    }
+}
+
+impl Remote {
+    /// Whether `addr` falls in this address or network
+    fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let shift = 32 - u32::from(self.prefix.unwrap_or(32));
+                u32::from(net).checked_shr(shift).unwrap_or(0) == u32::from(addr).checked_shr(shift).unwrap_or(0)
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let shift = 128 - u32::from(self.prefix.unwrap_or(128));
+                u128::from(net).checked_shr(shift).unwrap_or(0) == u128::from(addr).checked_shr(shift).unwrap_or(0)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parse an address or CIDR, the prefix bounded by the address family (0 = everything, refused)
+fn parse_remote(remote: &str) -> Result<Remote> {
+    let (addr, prefix) = match remote.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (remote, None),
+    };
+    let addr: IpAddr = addr.parse().map_err(|_| anyhow!("remote '{}' is not an IP address or CIDR", remote))?;
+    let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        None => None,
+        Some(prefix) => match prefix.parse::<u8>() {
+            Ok(0) => bail!("remote '{}' matches every address", remote),
+            Ok(prefix) if prefix <= max_prefix => Some(prefix),
+            _ => bail!("remote '{}' has an invalid prefix length (1-{})", remote, max_prefix),
+        },
+    };
+    Ok(Remote { addr, prefix })
+}
+
+/// Rule after validation, the only input of command construction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidatedRule {
+    action: FirewallAction,
+    direction: Direction,
+    protocol: Protocol,
+    port: Option<u16>,
+    remote: Option<Remote>,
+}
+
+/// Check a rule: a port needs tcp/udp, and the rule must target a port or a remote address
+pub fn validate_rule(rule: &FirewallRule) -> Result<ValidatedRule> {
+    if rule.port == Some(0) {
+        bail!("port must be between 1 and 65535");
+    }
+    if rule.port.is_some() && rule.protocol == Protocol::Any {
+        bail!("a port needs protocol tcp or udp");
+    }
+    let remote = rule.remote.as_deref().map(parse_remote).transpose()?;
+    if rule.port.is_none() && remote.is_none() {
+        bail!("a rule needs a port or a remote address");
+    }
+    Ok(ValidatedRule {
+        action: rule.action,
+        direction: rule.direction,
+        protocol: rule.protocol,
+        port: rule.port,
+        remote,
+    })
+}
+
+/// Whether a deny rule would block the agent's MQTT link: outbound to a broker address and port,
+/// or inbound from a broker address on any port (broker replies)
+pub fn blocks_broker(rule: &ValidatedRule, broker: &[SocketAddr]) -> bool {
+    if rule.action != FirewallAction::Deny || rule.protocol == Protocol::Udp {
+        return false;
+    }
+    broker.iter().any(|broker| {
+        let remote_matches = rule.remote.is_none_or(|remote| remote.contains(broker.ip()));
+        match rule.direction {
+            Direction::Out => remote_matches && rule.port.is_none_or(|port| port == broker.port()),
+            Direction::In => remote_matches && rule.remote.is_some() && rule.port.is_none(),
+        }
+    })
+}
+
+/// Rule change requested by the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirewallChange {
+    Add,
+    Remove,
+}
+
+impl FirewallChange {
+    pub fn plan(self, backend: FirewallBackend, rule: &ValidatedRule) -> PlannedCommand {
+        match self {
+            FirewallChange::Add => add_command(backend, rule),
+            FirewallChange::Remove => remove_command(backend, rule),
+        }
+    }
+}
+
+/// iptables keeps one table per family: IPv6 remotes go through ip6tables
+fn iptables_program(rule: &ValidatedRule) -> &'static str {
+    match rule.remote {
+        Some(Remote { addr: IpAddr::V6(_), .. }) => "ip6tables",
+        _ => "iptables",
+    }
+}
+
+/// Firewall tool driving the rules on this OS
+pub fn backend_for(os: &str, linux_backend: FirewallBackend) -> Result<FirewallBackend> {
+    match os {
+        "linux" => Ok(linux_backend),
+        "windows" => Ok(FirewallBackend::Netsh),
+        other => bail!("firewall management not supported on OS: {}", other),
+    }
+}
+
+/// Deterministic Windows rule name, so `firewall_remove` finds what `firewall_add` created
+pub fn netsh_rule_name(rule: &ValidatedRule) -> String {
+    let action = match rule.action {
+        FirewallAction::Allow => "allow",
+        FirewallAction::Deny => "deny",
+    };
+    let direction = match rule.direction {
+        Direction::In => "in",
+        Direction::Out => "out",
+    };
+    let port = rule.port.map_or("any".to_string(), |p| p.to_string());
+    let remote = rule.remote.map_or("any".to_string(), |r| r.to_string());
+    format!("symbion-{}-{}-{}-{}-{}", action, direction, rule.protocol.name(), port, remote)
+}
+
+/// ufw rule specification shared by add and delete
+fn ufw_spec(rule: &ValidatedRule) -> Vec<String> {
+    let mut args = vec![
+        match rule.action {
+            FirewallAction::Allow => "allow",
+            FirewallAction::Deny => "deny",
+        }.to_string(),
+    ];
+    let remote = rule.remote.map_or("any".to_string(), |r| r.to_string());
+    let (from, to) = match rule.direction {
+        Direction::In => (remote, "any".to_string()),
+        Direction::Out => ("any".to_string(), remote),
+    };
+    args.push(match rule.direction {
+        Direction::In => "in",
+        Direction::Out => "out",
+    }.to_string());
+    if rule.protocol != Protocol::Any {
+        args.extend(["proto".to_string(), rule.protocol.name().to_string()]);
+    }
+    args.extend(["from".to_string(), from, "to".to_string(), to]);
+    if let Some(port) = rule.port {
+        args.extend(["port".to_string(), port.to_string()]);
+    }
+    args
+}
+
+/// iptables rule specification shared by insert and delete
+fn iptables_spec(rule: &ValidatedRule) -> Vec<String> {
+    let (chain, remote_flag) = match rule.direction {
+        Direction::In => ("INPUT", "-s"),
+        Direction::Out => ("OUTPUT", "-d"),
+    };
+    let mut args = vec![chain.to_string()];
+    if rule.protocol != Protocol::Any {
+        args.extend(["-p".to_string(), rule.protocol.name().to_string()]);
+    }
+    if let Some(remote) = rule.remote {
+        args.extend([remote_flag.to_string(), remote.to_string()]);
+    }
+    if let Some(port) = rule.port {
+        args.extend(["--dport".to_string(), port.to_string()]);
+    }
+    args.extend([
+        "-m".to_string(), "comment".to_string(), "--comment".to_string(), IPTABLES_COMMENT.to_string(),
+        "-j".to_string(),
+        match rule.action {
+            FirewallAction::Allow => "ACCEPT",
+            FirewallAction::Deny => "DROP",
+        }.to_string(),
+    ]);
+    args
+}
+
+/// netsh fields of a rule (after `name=`)
+fn netsh_spec(rule: &ValidatedRule) -> Vec<String> {
+    let mut args = vec![
+        format!("dir={}", match rule.direction {
+            Direction::In => "in",
+            Direction::Out => "out",
+        }),
+        format!("action={}", match rule.action {
+            FirewallAction::Allow => "allow",
+            FirewallAction::Deny => "block",
+        }),
+        format!("protocol={}", rule.protocol.name()),
+    ];
+    if let Some(port) = rule.port {
+        // Inbound rules match the local port, outbound ones the remote port
+        let field = match rule.direction {
+            Direction::In => "localport",
+            Direction::Out => "remoteport",
+        };
+        args.push(format!("{}={}", field, port));
+    }
+    if let Some(remote) = rule.remote {
+        args.push(format!("remoteip={}", remote));
+    }
+    args
+}
+
+/// Command adding a rule (iptables rules are inserted first so a deny takes effect at once)
+pub fn add_command(backend: FirewallBackend, rule: &ValidatedRule) -> PlannedCommand {
+    match backend {
+        FirewallBackend::Ufw => PlannedCommand { program: "ufw", args: ufw_spec(rule) },
+        FirewallBackend::Iptables => {
+            let mut args = vec!["-I".to_string()];
+            args.extend(iptables_spec(rule));
+            PlannedCommand { program: iptables_program(rule), args }
+        }
+        FirewallBackend::Netsh => {
+            let mut args: Vec<String> = ["advfirewall", "firewall", "add", "rule"].map(String::from).into();
+            args.push(format!("name={}", netsh_rule_name(rule)));
+            args.extend(netsh_spec(rule));
+            PlannedCommand { program: "netsh", args }
+        }
+    }
+}
+
+/// Command removing a rule previously added with the same fields
+pub fn remove_command(backend: FirewallBackend, rule: &ValidatedRule) -> PlannedCommand {
+    match backend {
+        FirewallBackend::Ufw => {
+            let mut args = vec!["delete".to_string()];
+            args.extend(ufw_spec(rule));
+            PlannedCommand { program: "ufw", args }
+        }
+        FirewallBackend::Iptables => {
+            let mut args = vec!["-D".to_string()];
+            args.extend(iptables_spec(rule));
+            PlannedCommand { program: iptables_program(rule), args }
+        }
+        FirewallBackend::Netsh => {
+            let mut args: Vec<String> = ["advfirewall", "firewall", "delete", "rule"].map(String::from).into();
+            args.push(format!("name={}", netsh_rule_name(rule)));
+            args.push(format!("dir={}", match rule.direction {
+                Direction::In => "in",
+                Direction::Out => "out",
+            }));
+            PlannedCommand { program: "netsh", args }
+        }
+    }
+}
+
+/// Command listing the active rules
+pub fn list_command(backend: FirewallBackend) -> PlannedCommand {
+    let args: &[&str] = match backend {
+        FirewallBackend::Ufw => &["status", "numbered"],
+        FirewallBackend::Iptables => &["-S"],
+        FirewallBackend::Netsh => &["advfirewall", "firewall", "show", "rule", "name=all"],
+    };
+    PlannedCommand { program: backend.program(), args: args.iter().map(|a| a.to_string()).collect() }
+}
+
+/// Run a planned command under the timeout; stdout on success
+pub async fn run(planned: &PlannedCommand) -> Result<String> {
+    let mut command = AsyncCommand::new(planned.program);
+    command.args(&planned.args);
+    let output = tokio::time::timeout(FIREWALL_TIMEOUT, execution::run_capped(command, DEFAULT_MAX_OUTPUT_BYTES))
+        .await
+        .map_err(|_| anyhow!("{} timed out after {:?}", planned.program, FIREWALL_TIMEOUT))?
+        .with_context(|| format!("Failed to run {}", planned.program))?;
+    if output.exit_code != Some(0) {
+        let message = if output.stderr.trim().is_empty() { &output.stdout } else { &output.stderr };
+        bail!("{} failed: {}", planned.program, message.trim());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(action: FirewallAction, direction: Direction, protocol: Protocol, port: Option<u16>, remote: Option<&str>) -> ValidatedRule {
+        validate_rule(&FirewallRule { action, direction, protocol, port, remote: remote.map(str::to_string) }).unwrap()
+    }
+
+    fn args(planned: &PlannedCommand) -> Vec<&str> {
+        planned.args.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn test_rule_validation() {
+        let base = FirewallRule { action: FirewallAction::Deny, direction: Direction::In, protocol: Protocol::Tcp, port: Some(22), remote: None };
+        assert!(validate_rule(&base).is_ok());
+        assert!(validate_rule(&FirewallRule { port: Some(0), ..base.clone() }).is_err());
+        assert!(validate_rule(&FirewallRule { protocol: Protocol::Any, ..base.clone() }).is_err());
+        // Blanket rule: neither port nor remote
+        assert!(validate_rule(&FirewallRule { port: None, ..base.clone() }).is_err());
+
+        let with_remote = |remote: &str| validate_rule(&FirewallRule { port: None, protocol: Protocol::Any, remote: Some(remote.to_string()), ..base.clone() });
+        assert!(with_remote("203.0.113.7").is_ok());
+        assert!(with_remote("10.0.0.0/8").is_ok());
+        assert!(with_remote("2001:db8::/32").is_ok());
+        assert!(with_remote("10.0.0.0/33").is_err());
+        // Blanket remotes
+        assert!(with_remote("0.0.0.0/0").is_err());
+        assert!(with_remote("::/0").is_err());
+        assert!(with_remote("10.0.0.1; reboot").is_err());
+        assert!(with_remote("any").is_err());
+
+        // Unknown fields are refused rather than silently ignored
+        let parsed: Result<FirewallRule, _> = serde_json::from_value(serde_json::json!({ "action": "deny", "port": 22, "interface": "eth0" }));
+        assert!(parsed.is_err());
+        let parsed: FirewallRule = serde_json::from_value(serde_json::json!({ "action": "deny", "port": 22 })).unwrap();
+        assert_eq!((parsed.direction, parsed.protocol), (Direction::In, Protocol::Tcp));
+    }
+
+    #[test]
+    fn test_ufw_translation() {
+        let deny_ssh = rule(FirewallAction::Deny, Direction::In, Protocol::Tcp, Some(22), Some("203.0.113.0/24"));
+        let add = add_command(FirewallBackend::Ufw, &deny_ssh);
+        assert_eq!(add.program, "ufw");
+        assert_eq!(args(&add), ["deny", "in", "proto", "tcp", "from", "203.0.113.0/24", "to", "any", "port", "22"]);
+        assert_eq!(args(&remove_command(FirewallBackend::Ufw, &deny_ssh)),
+            ["delete", "deny", "in", "proto", "tcp", "from", "203.0.113.0/24", "to", "any", "port", "22"]);
+
+        let block_host = rule(FirewallAction::Deny, Direction::Out, Protocol::Any, None, Some("198.51.100.9"));
+        assert_eq!(args(&add_command(FirewallBackend::Ufw, &block_host)), ["deny", "out", "from", "any", "to", "198.51.100.9"]);
+        assert_eq!(args(&list_command(FirewallBackend::Ufw)), ["status", "numbered"]);
+    }
+
+    #[test]
+    fn test_iptables_translation() {
+        let allow_dns = rule(FirewallAction::Allow, Direction::Out, Protocol::Udp, Some(53), Some("192.0.2.53"));
+        let add = add_command(FirewallBackend::Iptables, &allow_dns);
+        assert_eq!(add.program, "iptables");
+        assert_eq!(args(&add), ["-I", "OUTPUT", "-p", "udp", "-d", "192.0.2.53", "--dport", "53", "-m", "comment", "--comment", "symbion", "-j", "ACCEPT"]);
+
+        let deny_ssh = rule(FirewallAction::Deny, Direction::In, Protocol::Tcp, Some(22), None);
+        assert_eq!(args(&remove_command(FirewallBackend::Iptables, &deny_ssh)),
+            ["-D", "INPUT", "-p", "tcp", "--dport", "22", "-m", "comment", "--comment", "symbion", "-j", "DROP"]);
+        assert_eq!(args(&list_command(FirewallBackend::Iptables)), ["-S"]);
+
+        // IPv6 remotes live in the ip6tables tables
+        let deny_v6 = rule(FirewallAction::Deny, Direction::In, Protocol::Tcp, Some(22), Some("2001:db8::/32"));
+        assert_eq!(add_command(FirewallBackend::Iptables, &deny_v6).program, "ip6tables");
+        assert_eq!(remove_command(FirewallBackend::Iptables, &deny_v6).program, "ip6tables");
+    }
+
+    #[test]
+    fn test_rules_cutting_off_the_broker() {
+        let broker: Vec<SocketAddr> = vec!["192.168.1.10:1883".parse().unwrap()];
+        let blocks = |action, direction, protocol, port, remote| blocks_broker(&rule(action, direction, protocol, port, remote), &broker);
+        use FirewallAction::*;
+
+        assert!(blocks(Deny, Direction::Out, Protocol::Tcp, Some(1883), None));
+        assert!(blocks(Deny, Direction::Out, Protocol::Any, None, Some("192.168.1.0/24")));
+        assert!(blocks(Deny, Direction::Out, Protocol::Tcp, Some(1883), Some("192.168.1.10")));
+        assert!(blocks(Deny, Direction::In, Protocol::Any, None, Some("192.168.0.0/16")));
+
+        assert!(!blocks(Allow, Direction::Out, Protocol::Tcp, Some(1883), None));
+        assert!(!blocks(Deny, Direction::Out, Protocol::Tcp, Some(443), Some("192.168.1.10")));
+        assert!(!blocks(Deny, Direction::Out, Protocol::Udp, Some(1883), None));
+        assert!(!blocks(Deny, Direction::Out, Protocol::Any, None, Some("192.168.2.0/24")));
+        assert!(!blocks(Deny, Direction::Out, Protocol::Any, None, Some("2001:db8::1")));
+        assert!(!blocks(Deny, Direction::In, Protocol::Tcp, Some(22), Some("192.168.1.10")));
+    }
+
+    #[test]
+    fn test_netsh_translation() {
+        let deny_rdp = rule(FirewallAction::Deny, Direction::In, Protocol::Tcp, Some(3389), Some("203.0.113.7"));
+        let add = add_command(FirewallBackend::Netsh, &deny_rdp);
+        assert_eq!(add.program, "netsh");
+        assert_eq!(args(&add), [
+            "advfirewall", "firewall", "add", "rule", "name=symbion-deny-in-tcp-3389-203.0.113.7",
+            "dir=in", "action=block", "protocol=tcp", "localport=3389", "remoteip=203.0.113.7",
+        ]);
+        assert_eq!(args(&remove_command(FirewallBackend::Netsh, &deny_rdp)), [
+            "advfirewall", "firewall", "delete", "rule", "name=symbion-deny-in-tcp-3389-203.0.113.7", "dir=in",
+        ]);
+
+        // Outbound rules match the remote port
+        let allow_https = rule(FirewallAction::Allow, Direction::Out, Protocol::Tcp, Some(443), None);
+        assert!(args(&add_command(FirewallBackend::Netsh, &allow_https)).contains(&"remoteport=443"));
+        assert_eq!(args(&list_command(FirewallBackend::Netsh))[4], "name=all");
+    }
+
+    #[test]
+    fn test_backend_per_platform() {
+        assert_eq!(backend_for("linux", FirewallBackend::Ufw).unwrap(), FirewallBackend::Ufw);
+        assert_eq!(backend_for("linux", FirewallBackend::Iptables).unwrap(), FirewallBackend::Iptables);
+        assert_eq!(backend_for("windows", FirewallBackend::Ufw).unwrap(), FirewallBackend::Netsh);
+        assert!(backend_for("android", FirewallBackend::Ufw).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! GPU metrics for Symbion agents (ML and render nodes)
+//!
+//! Backs the `gpu_metrics` capability and the `system.gpu` heartbeat field:
+//! - NVIDIA: `nvidia-smi --query-gpu=... --format=csv,noheader,nounits`
+//! - AMD (Linux): amdgpu sysfs under `/sys/class/drm/card*/device`
+//! - Values a driver does not report (`[N/A]`, missing sysfs file) are omitted
+//! - Hosts without a GPU report nothing: collection never fails the heartbeat
+
+use crate::execution::{self, DEFAULT_MAX_OUTPUT_BYTES};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command as AsyncCommand;
+use tracing::debug;
+
+/// Maximum time given to `nvidia-smi` (a wedged driver can hang it)
+pub const NVIDIA_SMI_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Fields requested from `nvidia-smi`, in CSV column order
+const NVIDIA_QUERY: &str = "index,name,utilization.gpu,memory.used,memory.total,temperature.gpu,power.draw";
+
+/// PCI vendor id of AMD GPUs
+const AMD_VENDOR_ID: &str = "0x1002";
+
+/// GPU vendor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GpuVendor {
+    Nvidia,
+    Amd,
+}
+
+/// Usage of one GPU
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GpuMetrics {
+    pub index: u32,
+    pub vendor: GpuVendor,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub utilization_percent: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_used_mb: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_total_mb: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature_celsius: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub power_watts: Option<f32>,
+}
+
+/// `nvidia-smi` numeric value, `None` for `[N/A]`/`[Not Supported]`
+fn nvidia_value(field: &str) -> Option<f64> {
+    field.trim().parse().ok()
+}
+
+/// Parse `nvidia-smi --query-gpu=<NVIDIA_QUERY> --format=csv,noheader,nounits` output
+pub fn parse_nvidia_smi(output: &str) -> Vec<GpuMetrics> {
+    output.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() < 7 {
+                return None;
+            }
+            Some(GpuMetrics {
+                index: fields[0].parse().ok()?,
+                vendor: GpuVendor::Nvidia,
+                name: fields[1].to_string(),
+                utilization_percent: nvidia_value(fields[2]).map(|v| v as f32),
+                memory_used_mb: nvidia_value(fields[3]).map(|v| v as u64),
+                memory_total_mb: nvidia_value(fields[4]).map(|v| v as u64),
+                temperature_celsius: nvidia_value(fields[5]).map(|v| v as f32),
+                power_watts: nvidia_value(fields[6]).map(|v| v as f32),
+            })
+        })
+        .collect()
+}
+
+async fn collect_nvidia() -> Vec<GpuMetrics> {
+    let mut command = AsyncCommand::new("nvidia-smi");
+    command.arg(format!("--query-gpu={}", NVIDIA_QUERY)).arg("--format=csv,noheader,nounits");
+    match tokio::time::timeout(NVIDIA_SMI_TIMEOUT, execution::run_capped(command, DEFAULT_MAX_OUTPUT_BYTES)).await {
+        Ok(Ok(output)) if output.exit_code == Some(0) => parse_nvidia_smi(&output.stdout),
+        Ok(Ok(output)) => {
+            debug!("nvidia-smi failed: {}", output.stderr.trim());
+            Vec::new()
+        }
+        // Not installed: no NVIDIA GPU (or no driver)
+        Ok(Err(_)) => Vec::new(),
+        Err(_) => {
+            debug!("nvidia-smi timed out after {:?}", NVIDIA_SMI_TIMEOUT);
+            Vec::new()
+        }
+    }
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// First `hwmon*` directory of a DRM device
+fn hwmon_dir(device: &Path) -> Option<std::path::PathBuf> {
+    std::fs::read_dir(device.join("hwmon")).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .next()
+}
+
+/// Read one amdgpu device directory (`/sys/class/drm/cardN/device`)
+fn read_amd_device(device: &Path, index: u32) -> Option<GpuMetrics> {
+    let vendor = std::fs::read_to_string(device.join("vendor")).ok()?;
+    if vendor.trim() != AMD_VENDOR_ID {
+        return None;
+    }
+    let hwmon = hwmon_dir(device);
+    let name = std::fs::read_to_string(device.join("product_name")).ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "AMD GPU".to_string());
+    Some(GpuMetrics {
+        index,
+        vendor: GpuVendor::Amd,
+        name,
+        utilization_percent: read_number(&device.join("gpu_busy_percent")).map(|v| v as f32),
+        memory_used_mb: read_number(&device.join("mem_info_vram_used")).map(|bytes| (bytes / (1024.0 * 1024.0)) as u64),
+        memory_total_mb: read_number(&device.join("mem_info_vram_total")).map(|bytes| (bytes / (1024.0 * 1024.0)) as u64),
+        // hwmon: millidegrees and microwatts
+        temperature_celsius: hwmon.as_ref().and_then(|dir| read_number(&dir.join("temp1_input"))).map(|v| (v / 1000.0) as f32),
+        power_watts: hwmon.as_ref().and_then(|dir| read_number(&dir.join("power1_average"))).map(|v| (v / 1_000_000.0) as f32),
+    })
+}
+
+/// AMD GPUs exposed by the amdgpu driver (`card0`, `card1`... - not `card0-DP-1` connectors)
+fn collect_amd() -> Vec<GpuMetrics> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/drm") else {
+        return Vec::new();
+    };
+    let mut cards: Vec<(u32, std::path::PathBuf)> = entries.flatten()
+        .filter_map(|entry| {
+            let index = entry.file_name().to_str()?.strip_prefix("card")?.parse().ok()?;
+            Some((index, entry.path().join("device")))
+        })
+        .collect();
+    cards.sort();
+    cards.iter().filter_map(|(index, device)| read_amd_device(device, *index)).collect()
+}
+
+/// amdgpu device present (capability detection)
+pub fn amd_gpu_present() -> bool {
+    cfg!(target_os = "linux") && !collect_amd().is_empty()
+}
+
+/// Usage of every GPU of this host, empty without a supported GPU
+pub async fn collect() -> Vec<GpuMetrics> {
+    let mut gpus = collect_nvidia().await;
+    if cfg!(target_os = "linux") {
+        gpus.extend(collect_amd());
+    }
+    gpus
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nvidia_smi_csv() {
+        let output = "\
+0, NVIDIA GeForce RTX 3090, 87, 20312, 24576, 71, 312.45
+1, Tesla T4, 0, 0, 15360, 38, [N/A]
+";
+        assert_eq!(parse_nvidia_smi(output), vec![
+            GpuMetrics {
+                index: 0,
+                vendor: GpuVendor::Nvidia,
+                name: "NVIDIA GeForce RTX 3090".to_string(),
+                utilization_percent: Some(87.0),
+                memory_used_mb: Some(20312),
+                memory_total_mb: Some(24576),
+                temperature_celsius: Some(71.0),
+                power_watts: Some(312.45),
+            },
+            GpuMetrics {
+                index: 1,
+                vendor: GpuVendor::Nvidia,
+                name: "Tesla T4".to_string(),
+                utilization_percent: Some(0.0),
+                memory_used_mb: Some(0),
+                memory_total_mb: Some(15360),
+                temperature_celsius: Some(38.0),
+                power_watts: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_nvidia_smi_skips_garbage() {
+        let output = "\
+NVIDIA-SMI has failed because it couldn't communicate with the NVIDIA driver.
+0, Quadro P2000, [Not Supported], 512, 5120, [N/A], [N/A]
+";
+        let gpus = parse_nvidia_smi(output);
+        assert_eq!(gpus.len(), 1);
+        assert_eq!(gpus[0].utilization_percent, None);
+        assert_eq!(gpus[0].memory_used_mb, Some(512));
+
+        // Unreported values are left out of the heartbeat
+        let json = serde_json::to_value(&gpus[0]).unwrap();
+        assert_eq!(json["vendor"], "nvidia");
+        assert!(json.get("utilization_percent").is_none());
+        assert!(parse_nvidia_smi("").is_empty());
+    }
+}
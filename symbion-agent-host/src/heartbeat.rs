@@ -0,0 +1,59 @@
+//! Heartbeat sections requested by the kernel
+//!
+//! Process summaries, service status and temperature sensors make heartbeats heavy; the
+//! kernel tells agents which of them to include on `symbion/agents/heartbeat_config@v1`:
+//! - Retained broadcast (no `agent_id`): fleet default, received again on every reconnect
+//! - Message addressed to this agent: on-demand override, kept until the next broadcast
+//! - Until the kernel says otherwise, every optional section is omitted
+
+use serde::{Deserialize, Serialize};
+
+/// Kernel messages selecting heartbeat sections
+pub const HEARTBEAT_CONFIG_TOPIC: &str = symbion_topics::agents_heartbeat_config();
+
+/// Optional heartbeat sections (absent fields mean omitted)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeartbeatSections {
+    pub processes: bool,
+    pub services: bool,
+    pub temperatures: bool,
+}
+
+/// Message on `HEARTBEAT_CONFIG_TOPIC` (matches agents.heartbeat_config@v1 contract)
+#[derive(Debug, Deserialize)]
+pub struct HeartbeatConfigMessage {
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    pub sections: HeartbeatSections,
+}
+
+impl HeartbeatConfigMessage {
+    /// Broadcast, or addressed to this agent
+    pub fn applies_to(&self, agent_id: &str) -> bool {
+        self.agent_id.as_deref().is_none_or(|target| target == agent_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_message_targets_and_defaults() {
+        let broadcast: HeartbeatConfigMessage = serde_json::from_str(
+            r#"{"sections": {"services": true}, "timestamp": "2025-09-01T10:30:00Z"}"#,
+        ).unwrap();
+        assert!(broadcast.applies_to("a1b2c3d4e5f6"));
+        assert_eq!(broadcast.sections, HeartbeatSections { processes: false, services: true, temperatures: false });
+
+        let targeted: HeartbeatConfigMessage = serde_json::from_str(
+            r#"{"agent_id": "a1b2c3d4e5f6", "sections": {"processes": true, "temperatures": true}, "timestamp": "2025-09-01T10:30:00Z"}"#,
+        ).unwrap();
+        assert!(targeted.applies_to("a1b2c3d4e5f6"));
+        assert!(!targeted.applies_to("000000000001"));
+
+        // Nothing heavy is sent before the kernel asks for it
+        assert_eq!(HeartbeatSections::default(), HeartbeatSections { processes: false, services: false, temperatures: false });
+    }
+}
@@ -0,0 +1,247 @@
+//! Listening sockets inventory for Symbion agents
+//!
+//! Backs the `list_listeners` command (security audits):
+//! - Linux/Android: `ss -tulnp`, falling back to `netstat -tulnp` when `ss` is missing
+//! - Windows: `netstat -ano` (TCP in LISTENING state, every bound UDP socket)
+//! - Tool output is parsed into structured entries; process names missing from the
+//!   output (Windows, or sockets owned by other users) are resolved from the PID
+//! - The tool runs under a timeout and with a capped output
+
+use crate::execution::{self, DEFAULT_MAX_OUTPUT_BYTES};
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::process::Command as AsyncCommand;
+use tracing::debug;
+
+/// Maximum time given to `ss`/`netstat`
+pub const LISTENERS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Transport protocol of a listener
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// One listening socket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Listener {
+    pub protocol: Protocol,
+    /// Bound address without brackets or interface scope (`0.0.0.0`, `::`, `127.0.0.53`)
+    pub address: String,
+    pub port: u16,
+    pub pid: Option<u32>,
+    pub process: Option<String>,
+}
+
+/// Result of `list_listeners`
+#[derive(Debug, Clone, Serialize)]
+pub struct ListenersReport {
+    /// Tool the entries were parsed from
+    pub tool: &'static str,
+    pub count: usize,
+    pub listeners: Vec<Listener>,
+}
+
+/// Split `addr:port` (`0.0.0.0:22`, `[::]:80`, `:::80`, `127.0.0.53%lo:53`); `*` ports are skipped
+fn split_endpoint(endpoint: &str) -> Option<(String, u16)> {
+    let (address, port) = endpoint.rsplit_once(':')?;
+    let port = port.parse().ok()?;
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+    let address = address.split('%').next().unwrap_or(address);
+    Some((address.to_string(), port))
+}
+
+/// First `("name",pid=N,...)` of an `ss` process column: `users:(("sshd",pid=901,fd=3))`
+fn parse_ss_process(column: &str) -> (Option<u32>, Option<String>) {
+    let Some(start) = column.find("((") else {
+        return (None, None);
+    };
+    let first = &column[start + 2..];
+    let first = first.split(')').next().unwrap_or(first);
+    let mut name = None;
+    let mut pid = None;
+    for part in first.split(',') {
+        if let Some(value) = part.strip_prefix("pid=") {
+            pid = value.parse().ok();
+        } else if part.starts_with('"') {
+            name = Some(part.trim_matches('"').to_string());
+        }
+    }
+    (pid, name)
+}
+
+/// Parse `ss -tulnp` output
+pub fn parse_ss(output: &str) -> Vec<Listener> {
+    output.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let protocol = match *fields.first()? {
+                "tcp" => Protocol::Tcp,
+                "udp" => Protocol::Udp,
+                _ => return None, // header, other netids
+            };
+            let (address, port) = split_endpoint(fields.get(4)?)?;
+            let (pid, process) = fields.get(6).map(|column| parse_ss_process(column)).unwrap_or((None, None));
+            Some(Listener { protocol, address, port, pid, process })
+        })
+        .collect()
+}
+
+/// `PID/Program name` column of Linux `netstat -p` (`901/sshd`, `-` when not visible)
+fn parse_netstat_program(column: &str) -> (Option<u32>, Option<String>) {
+    match column.split_once('/') {
+        Some((pid, name)) => (pid.parse().ok(), Some(name.trim().to_string()).filter(|n| !n.is_empty())),
+        None => (None, None),
+    }
+}
+
+/// Parse Linux `netstat -tulnp` output
+pub fn parse_netstat_linux(output: &str) -> Vec<Listener> {
+    output.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let protocol = match *fields.first()? {
+                "tcp" | "tcp6" => Protocol::Tcp,
+                "udp" | "udp6" => Protocol::Udp,
+                _ => return None,
+            };
+            let (address, port) = split_endpoint(fields.get(3)?)?;
+            // UDP sockets have no state column
+            let program_index = if protocol == Protocol::Tcp { 6 } else { 5 };
+            let (pid, process) = match fields.get(program_index) {
+                Some(_) => parse_netstat_program(&fields[program_index..].join(" ")),
+                None => (None, None),
+            };
+            Some(Listener { protocol, address, port, pid, process })
+        })
+        .collect()
+}
+
+/// Parse Windows `netstat -ano` output (TCP LISTENING and all UDP sockets)
+pub fn parse_netstat_windows(output: &str) -> Vec<Listener> {
+    output.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (protocol, pid) = match fields.as_slice() {
+                ["TCP", _, _, "LISTENING", pid] => (Protocol::Tcp, pid),
+                ["UDP", _, _, pid] => (Protocol::Udp, pid),
+                _ => return None,
+            };
+            let (address, port) = split_endpoint(fields[1])?;
+            Some(Listener { protocol, address, port, pid: pid.parse().ok(), process: None })
+        })
+        .collect()
+}
+
+/// Fill missing process names from the PID
+fn resolve_process_names(listeners: &mut [Listener]) {
+    if listeners.iter().all(|l| l.process.is_some() || l.pid.is_none()) {
+        return;
+    }
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes();
+    for listener in listeners.iter_mut().filter(|l| l.process.is_none()) {
+        if let Some(process) = listener.pid.and_then(|pid| sys.process(sysinfo::Pid::from_u32(pid))) {
+            listener.process = Some(process.name().to_string());
+        }
+    }
+}
+
+async fn run_tool(program: &str, args: &[&str]) -> Result<String> {
+    let mut command = AsyncCommand::new(program);
+    command.args(args);
+    let output = tokio::time::timeout(LISTENERS_TIMEOUT, execution::run_capped(command, DEFAULT_MAX_OUTPUT_BYTES))
+        .await
+        .map_err(|_| anyhow!("{} timed out after {:?}", program, LISTENERS_TIMEOUT))?
+        .with_context(|| format!("Failed to run {}", program))?;
+    if output.exit_code != Some(0) {
+        return Err(anyhow!("{} failed: {}", program, output.stderr.trim()));
+    }
+    Ok(output.stdout)
+}
+
+/// Collect the listening sockets of this host
+pub async fn list() -> Result<ListenersReport> {
+    let (tool, mut listeners) = if cfg!(target_os = "windows") {
+        ("netstat", parse_netstat_windows(&run_tool("netstat", &["-ano"]).await?))
+    } else {
+        match run_tool("ss", &["-tulnp"]).await {
+            Ok(output) => ("ss", parse_ss(&output)),
+            Err(e) => {
+                debug!("ss unavailable ({}), falling back to netstat", e);
+                ("netstat", parse_netstat_linux(&run_tool("netstat", &["-tulnp"]).await?))
+            }
+        }
+    };
+    resolve_process_names(&mut listeners);
+    listeners.sort_by(|a, b| (a.protocol, a.port, &a.address).cmp(&(b.protocol, b.port, &b.address)));
+    listeners.dedup();
+    Ok(ListenersReport { tool, count: listeners.len(), listeners })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listener(protocol: Protocol, address: &str, port: u16, pid: Option<u32>, process: Option<&str>) -> Listener {
+        Listener { protocol, address: address.to_string(), port, pid, process: process.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_parse_ss_output() {
+        let output = "\
+Netid State  Recv-Q Send-Q      Local Address:Port  Peer Address:PortProcess
+udp   UNCONN 0      0       127.0.0.53%lo:53         0.0.0.0:*    users:((\"systemd-resolve\",pid=612,fd=13))
+tcp   LISTEN 0      4096          0.0.0.0:22         0.0.0.0:*    users:((\"sshd\",pid=901,fd=3))
+tcp   LISTEN 0      511              [::]:80            [::]:*    users:((\"nginx\",pid=1200,fd=7),(\"nginx\",pid=1201,fd=7))
+tcp   LISTEN 0      128         127.0.0.1:5432       0.0.0.0:*
+";
+        assert_eq!(parse_ss(output), vec![
+            listener(Protocol::Udp, "127.0.0.53", 53, Some(612), Some("systemd-resolve")),
+            listener(Protocol::Tcp, "0.0.0.0", 22, Some(901), Some("sshd")),
+            listener(Protocol::Tcp, "::", 80, Some(1200), Some("nginx")),
+            listener(Protocol::Tcp, "127.0.0.1", 5432, None, None),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_linux_netstat_output() {
+        let output = "\
+Active Internet connections (only servers)
+Proto Recv-Q Send-Q Local Address           Foreign Address         State       PID/Program name
+tcp        0      0 0.0.0.0:22              0.0.0.0:*               LISTEN      901/sshd
+tcp6       0      0 :::80                   :::*                    LISTEN      1200/nginx: master
+tcp        0      0 127.0.0.1:5432          0.0.0.0:*               LISTEN      -
+udp        0      0 127.0.0.53:53           0.0.0.0:*                           612/systemd-resolve
+";
+        assert_eq!(parse_netstat_linux(output), vec![
+            listener(Protocol::Tcp, "0.0.0.0", 22, Some(901), Some("sshd")),
+            listener(Protocol::Tcp, "::", 80, Some(1200), Some("nginx: master")),
+            listener(Protocol::Tcp, "127.0.0.1", 5432, None, None),
+            listener(Protocol::Udp, "127.0.0.53", 53, Some(612), Some("systemd-resolve")),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_windows_netstat_output() {
+        let output = "\r
+Active Connections\r
+\r
+  Proto  Local Address          Foreign Address        State           PID\r
+  TCP    0.0.0.0:135            0.0.0.0:0              LISTENING       1000\r
+  TCP    [::]:445               [::]:0                 LISTENING       4\r
+  TCP    192.168.1.10:50000     140.82.112.4:443       ESTABLISHED     5000\r
+  UDP    0.0.0.0:5353           *:*                                    2332\r
+  UDP    [fe80::1%12]:1900      *:*                                    3100\r
+";
+        assert_eq!(parse_netstat_windows(output), vec![
+            listener(Protocol::Tcp, "0.0.0.0", 135, Some(1000), None),
+            listener(Protocol::Tcp, "::", 445, Some(4), None),
+            listener(Protocol::Udp, "0.0.0.0", 5353, Some(2332), None),
+            listener(Protocol::Udp, "fe80::1", 1900, Some(3100), None),
+        ]);
+    }
+}
@@ -16,12 +16,35 @@ mod execution;
 mod config;
 mod updater;
 mod wizard;
+mod outbound;
+mod scheduler;
+mod cron;
+mod fileops;
+mod netcheck;
+mod envvars;
+mod listeners;
+mod pinning;
+mod gpu;
+mod priority;
+mod heartbeat;
+mod firewall;
+mod connection;
+mod diagnostics;
+mod power;
+mod power_schedule;
 
 use anyhow::{Result, Context};
-use chrono::{DateTime, Utc};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{DateTime, Timelike, Utc};
+use capabilities::CommandKind;
+use connection::{ConnectionEvent, ReceivedCommand};
 use discovery::SystemInfo;
-use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
+use outbound::{OutboundMessage, OutboundQueue, Priority};
+use scheduler::CommandPriority;
+use rumqttc::{AsyncClient, MqttOptions, QoS};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::time::interval;
 use tokio::sync::mpsc;
@@ -36,6 +59,20 @@ struct AgentConfig {
     mqtt_client_id: String,
     heartbeat_interval_secs: u64,
     registration_retry_secs: u64,
+    auto_elevate: bool,
+    store_credentials: bool,
+    file_ops: config::FileOpsConfig,
+    net_check: config::NetCheckConfig,
+    environment: config::EnvConfig,
+    firewall: config::FirewallConfig,
+    /// Per-stream output limit of `run_command`
+    max_output_bytes: usize,
+    /// Size limit of `run_command` stdin input
+    max_stdin_bytes: usize,
+    /// Programs `run_command` and `set_cron` accept (empty = unrestricted)
+    allowed_commands: Vec<String>,
+    /// Idle auto-shutdown policy at startup
+    power_schedule: config::PowerScheduleConfig,
 }
 
 impl Default for AgentConfig {
@@ -46,6 +83,16 @@ impl Default for AgentConfig {
             mqtt_client_id: "symbion-agent-unknown".to_string(),
             heartbeat_interval_secs: 30,
             registration_retry_secs: 10,
+            auto_elevate: false,
+            store_credentials: false,
+            file_ops: config::FileOpsConfig::default(),
+            net_check: config::NetCheckConfig::default(),
+            environment: config::EnvConfig::default(),
+            firewall: config::FirewallConfig::default(),
+            max_output_bytes: execution::DEFAULT_MAX_OUTPUT_BYTES,
+            max_stdin_bytes: execution::DEFAULT_MAX_STDIN_BYTES,
+            allowed_commands: execution::DEFAULT_ALLOWED_COMMANDS.iter().map(|c| c.to_string()).collect(),
+            power_schedule: config::PowerScheduleConfig::default(),
         }
     }
 }
@@ -57,24 +104,67 @@ struct RegistrationMessage {
     hostname: String,
     os: String,
     architecture: String,
+    os_details: discovery::OsDetails,
     capabilities: Vec<String>,
+    /// Detected gaps with their reason (not advertised in `capabilities`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unavailable_capabilities: Vec<capabilities::UnavailableCapability>,
     network: discovery::NetworkInfo,
     version: String,
+    /// Schema of the heartbeats this agent emits
+    heartbeat_version: &'static str,
     timestamp: DateTime<Utc>,
 }
 
+/// Heartbeat schema version declared at registration (`HeartbeatMessage` layout)
+const HEARTBEAT_VERSION: &str = "v1";
+
 /// Agent heartbeat message (matches agents.heartbeat@v1 contract)
 #[derive(Debug, Serialize)]
 struct HeartbeatMessage {
     agent_id: String,
     status: String,
     system: metrics::SystemMetrics,
+    /// Only when requested by the kernel (see `heartbeat`)
+    #[serde(skip_serializing_if = "Option::is_none")]
     processes: Option<metrics::ProcessInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     services: Option<Vec<metrics::ServiceStatus>>,
+    os_details: discovery::OsDetails,
+    /// Current interfaces, so the kernel follows DHCP and roaming IP changes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    network: Option<discovery::NetworkInfo>,
     last_command: Option<CommandInfo>,
+    /// Last commands executed, newest first (at most `RECENT_COMMANDS_CAPACITY`)
+    recent_commands: Vec<CommandInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scheduled_tasks: Option<Vec<cron::CronTask>>,
+    /// Commands queued or running on the scheduler
+    queue_depth: usize,
+    /// No free slot or commands waiting: new commands will be delayed
+    busy: bool,
+    /// Time since the last user input, while the idle auto-shutdown is active
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_secs: Option<u64>,
     timestamp: DateTime<Utc>,
 }
 
+impl HeartbeatMessage {
+    /// Drop the optional sections the kernel did not ask for
+    fn retain_sections(mut self, sections: heartbeat::HeartbeatSections) -> Self {
+        if !sections.processes {
+            self.processes = None;
+        }
+        if !sections.services {
+            self.services = None;
+        }
+        if !sections.temperatures {
+            self.system.temperature = None;
+        }
+        self
+    }
+}
+
 /// Command information for heartbeat
 #[derive(Debug, Clone, Serialize)]
 struct CommandInfo {
@@ -93,6 +183,12 @@ struct IncomingCommand {
     parameters: Option<serde_json::Value>,
     timestamp: DateTime<Utc>,
     requester: Option<String>,
+    /// Overrides the command type's default priority
+    #[serde(default)]
+    priority: Option<CommandPriority>,
+    /// Kernel-side deadline; bounds streamed commands, which may never end on their own
+    #[serde(default)]
+    timeout_seconds: Option<u32>,
 }
 
 /// Command response to kernel (matches agents.response@v1 contract)
@@ -107,6 +203,24 @@ struct CommandResponse {
     timestamp: DateTime<Utc>,
 }
 
+/// Output chunk of a streamed `run_command` (matches agents.output@v1 contract)
+#[derive(Debug, Serialize)]
+struct OutputMessage {
+    command_id: String,
+    agent_id: String,
+    seq: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<execution::OutputStream>,
+    data: String,
+    /// Final marker: no chunk follows
+    completed: bool,
+    exit_code: Option<i32>,
+    timed_out: bool,
+    /// Command killed once its output reached `max_output_bytes`
+    truncated: bool,
+    timestamp: DateTime<Utc>,
+}
+
 /// Error information for failed commands
 #[derive(Debug, Serialize)]
 struct ErrorInfo {
@@ -114,25 +228,143 @@ struct ErrorInfo {
     message: String,
 }
 
-/// Received command for internal processing
-#[derive(Debug, Clone)]
-struct ReceivedCommand {
-    topic: String,
-    payload: String,
+/// Commands from the kernel (all agents listen, filter by agent_id)
+const COMMAND_TOPIC: &str = symbion_topics::agents_command();
+
+/// Kernel request for every agent to re-register immediately
+const ANNOUNCE_TOPIC: &str = symbion_topics::agents_announce();
+
+/// Maximum spread of announce replies, so a fleet doesn't register in the same instant
+const ANNOUNCE_MAX_DELAY_MS: u64 = 2000;
+
+/// Topics the agent subscribes to, re-issued on every (re)connection.
+/// Commands use QoS 2 so power commands published ExactlyOnce are not downgraded by the broker
+fn subscriptions() -> Vec<(&'static str, QoS)> {
+    vec![
+        (COMMAND_TOPIC, QoS::ExactlyOnce),
+        (ANNOUNCE_TOPIC, QoS::AtLeastOnce),
+        (heartbeat::HEARTBEAT_CONFIG_TOPIC, QoS::AtLeastOnce),
+    ]
+}
+
+/// Deterministic per-agent delay before answering an announce
+fn announce_delay(agent_id: &str) -> Duration {
+    let hash = agent_id.bytes().fold(0u64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as u64));
+    Duration::from_millis(hash % ANNOUNCE_MAX_DELAY_MS)
+}
+
+/// Deadline of a streamed command when the kernel did not send one (kernel default)
+const DEFAULT_STREAM_TIMEOUT_SECS: u32 = 30;
+
+/// Lines returned by `tail_file` when not specified
+const DEFAULT_TAIL_LINES: usize = 50;
+
+/// Poll interval while following a file
+const TAIL_FOLLOW_POLL: Duration = Duration::from_millis(500);
+
+/// Smallest `read_file` chunk, however low the rate limit
+const MIN_TRANSFER_CHUNK: u64 = 512;
+
+/// Partial responses a transfer may leave in the outbound queue before pausing
+const MAX_QUEUED_PARTS: usize = 8;
+
+/// Number of recent command ids remembered for de-duplication
+const SEEN_COMMANDS_CAPACITY: usize = 256;
+
+/// Optional `rate_limit_kbps` parameter of file transfers (must be positive)
+fn rate_limit_param(params: &serde_json::Value) -> Result<Option<u64>, ErrorInfo> {
+    match params.get("rate_limit_kbps") {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(value) => match value.as_u64() {
+            Some(kbps) if kbps > 0 => Ok(Some(kbps)),
+            _ => Err(ErrorInfo {
+                code: "INVALID_PARAMETERS".to_string(),
+                message: "'rate_limit_kbps' must be a positive integer".to_string(),
+            }),
+        },
+    }
+}
+
+/// Recently accepted command ids. QoS 2 guarantees single delivery only within
+/// one session; with clean sessions a redelivery after reconnect must still be dropped.
+#[derive(Debug, Default)]
+struct SeenCommands {
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+impl SeenCommands {
+    /// Records the id; false if it was already seen
+    fn first_seen(&mut self, command_id: &str) -> bool {
+        if self.ids.contains(command_id) {
+            return false;
+        }
+        if self.order.len() == SEEN_COMMANDS_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        self.order.push_back(command_id.to_string());
+        self.ids.insert(command_id.to_string());
+        true
+    }
+}
+
+/// Number of executed commands reported in heartbeats
+const RECENT_COMMANDS_CAPACITY: usize = 10;
+
+/// Last executed commands, newest first; the oldest is evicted once full
+#[derive(Debug, Default)]
+struct RecentCommands {
+    entries: VecDeque<CommandInfo>,
+}
+
+impl RecentCommands {
+    fn record(&mut self, info: CommandInfo) {
+        if self.entries.len() == RECENT_COMMANDS_CAPACITY {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(info);
+    }
+    
+    fn last(&self) -> Option<CommandInfo> {
+        self.entries.front().cloned()
+    }
+    
+    fn snapshot(&self) -> Vec<CommandInfo> {
+        self.entries.iter().cloned().collect()
+    }
 }
 
 /// Main agent state
 struct Agent {
     config: AgentConfig,
     system_info: SystemInfo,
-    mqtt_client: AsyncClient,
-    last_command: Option<CommandInfo>,
-    command_receiver: mpsc::Receiver<ReceivedCommand>,
+    /// Prioritized outbound queue drained by the publisher task
+    outbound: Arc<OutboundQueue>,
+    /// Executed commands reported in heartbeats (the newest is also `last_command`)
+    recent_commands: Mutex<RecentCommands>,
+    /// Command ids already accepted (duplicate deliveries are ignored)
+    seen_commands: Mutex<SeenCommands>,
+    /// Bounded pool running commands concurrently (conflicting ones serialized)
+    scheduler: scheduler::CommandScheduler,
+    /// Optional heartbeat sections requested by the kernel
+    heartbeat_sections: Mutex<heartbeat::HeartbeatSections>,
+    /// Interface counters of the previous heartbeat (heartbeats report per-interval traffic)
+    network_collector: metrics::NetworkCollector,
+    /// Separate counters for `get_metrics`, so on-demand reads leave heartbeat intervals intact
+    metrics_network_collector: metrics::NetworkCollector,
+    /// Broker link state maintained by the connection loop
+    connection: Arc<connection::ConnectionStatus>,
+    /// Idle auto-shutdown policy (changed by `set_power_schedule`) and idle clock
+    power_schedule: Mutex<power_schedule::PowerSchedule>,
+    /// Scheduled tasks reported in heartbeats (refreshed after set_cron / remove_cron)
+    scheduled_tasks: cron::TaskCache,
 }
 
 impl Agent {
-    /// Create new agent instance with loaded configuration
-    async fn new_with_config(agent_config: config::AgentConfig) -> Result<Self> {
+    /// Create new agent instance with loaded configuration, plus the receiver of incoming commands
+    async fn new_with_config(agent_config: config::AgentConfig) -> Result<(Self, mpsc::Receiver<ConnectionEvent>)> {
         info!("Initializing Symbion Agent Host v{}", env!("CARGO_PKG_VERSION"));
         
         // Discover system information
@@ -140,11 +372,29 @@ impl Agent {
             .context("Failed to discover system information")?;
             
         // Configure MQTT client from loaded config
+        let max_concurrency = agent_config.commands.max_concurrency;
         let mut config = AgentConfig::default();
         config.mqtt_broker = agent_config.mqtt.broker_host;
         config.mqtt_port = agent_config.mqtt.broker_port;
         config.mqtt_client_id = agent_config.mqtt.client_id
             .unwrap_or_else(|| format!("symbion-agent-{}", system_info.agent_id));
+        config.auto_elevate = agent_config.elevation.auto_elevate;
+        config.store_credentials = agent_config.elevation.store_credentials && agent_config.elevation.cached_password.is_some();
+        config.file_ops = agent_config.file_ops;
+        config.net_check = agent_config.net_check;
+        config.environment = agent_config.environment;
+        config.firewall = agent_config.firewall;
+        config.max_output_bytes = agent_config.commands.max_output_bytes;
+        config.max_stdin_bytes = agent_config.commands.max_stdin_bytes;
+        config.allowed_commands = agent_config.commands.allowed_commands;
+        if config.allowed_commands.is_empty() {
+            warn!("commands.allowed_commands is empty: run_command accepts any shell command");
+        }
+        config.power_schedule = agent_config.power_schedule;
+        if let Err(e) = power_schedule::validate(&config.power_schedule) {
+            warn!("Invalid power_schedule section, idle auto-shutdown disabled: {}", e);
+            config.power_schedule.enabled = false;
+        }
         
         let mut mqtt_options = MqttOptions::new(
             &config.mqtt_client_id,
@@ -154,62 +404,46 @@ impl Agent {
         mqtt_options.set_keep_alive(Duration::from_secs(30));
         mqtt_options.set_clean_session(true);
         
-        let (mqtt_client, mut eventloop) = AsyncClient::new(mqtt_options, 10);
+        let (mqtt_client, eventloop) = AsyncClient::new(mqtt_options, 10);
+        
+        // Publishes go through a prioritized queue so responses are never starved by heartbeats
+        let outbound = Arc::new(OutboundQueue::new(outbound::OUTBOUND_QUEUE_CAPACITY));
+        outbound::spawn_publisher(outbound.clone(), mqtt_client.clone());
         
         // Create command channel
-        let (command_sender, command_receiver) = mpsc::channel::<ReceivedCommand>(100);
+        let (command_sender, command_receiver) = mpsc::channel::<ConnectionEvent>(100);
         
-        // Start MQTT event loop in background
-        tokio::spawn(async move {
-            loop {
-                match eventloop.poll().await {
-                    Ok(Event::Incoming(Incoming::Publish(publish))) => {
-                        debug!("Received MQTT message on topic: {}", publish.topic);
-                        
-                        // Forward command messages to main loop
-                        if publish.topic == "symbion/agents/command@v1" {
-                            let payload = String::from_utf8_lossy(&publish.payload).to_string();
-                            let command = ReceivedCommand {
-                                topic: publish.topic.clone(),
-                                payload,
-                            };
-                            
-                            if let Err(e) = command_sender.send(command).await {
-                                error!("Failed to forward command: {}", e);
-                            }
-                        }
-                    }
-                    Ok(_) => {}
-                    Err(e) => {
-                        error!("MQTT connection error: {}", e);
-                        tokio::time::sleep(Duration::from_secs(5)).await;
-                    }
-                }
-            }
-        });
+        // Start MQTT event loop in background (subscriptions re-issued after every reconnect)
+        let connection = Arc::new(connection::ConnectionStatus::default());
+        tokio::spawn(connection::supervise(eventloop, subscriptions(), connection::Backoff::default(), connection.clone(), command_sender));
         
         info!("Agent initialized - ID: {}, Hostname: {}", 
               system_info.agent_id, system_info.hostname);
         
-        Ok(Agent {
+        let power_schedule = Mutex::new(power_schedule::PowerSchedule::new(config.power_schedule.clone(), std::time::Instant::now()));
+        Ok((Agent {
             config,
             system_info,
-            mqtt_client,
-            last_command: None,
-            command_receiver,
-        })
+            outbound,
+            recent_commands: Mutex::new(RecentCommands::default()),
+            seen_commands: Mutex::new(SeenCommands::default()),
+            scheduler: scheduler::CommandScheduler::new(max_concurrency),
+            heartbeat_sections: Mutex::new(heartbeat::HeartbeatSections::default()),
+            network_collector: metrics::NetworkCollector::new(),
+            metrics_network_collector: metrics::NetworkCollector::new(),
+            connection,
+            power_schedule,
+            scheduled_tasks: cron::TaskCache::default(),
+        }, command_receiver))
     }
     
     /// Start agent main loop
-    async fn run(&mut self) -> Result<()> {
+    async fn run(self: Arc<Self>, mut command_receiver: mpsc::Receiver<ConnectionEvent>) -> Result<()> {
         info!("Starting agent main loop...");
         
-        // Subscribe to command topic (all agents listen to same topic, filter by agent_id)
-        let command_topic = "symbion/agents/command@v1";
-        self.mqtt_client.subscribe(command_topic, QoS::AtLeastOnce).await
-            .context("Failed to subscribe to command topic")?;
-            
-        info!("Subscribed to commands on: {}", command_topic);
+        // Subscriptions (all agents listen to the same command topic, filter by agent_id)
+        // are issued by the connection loop on every ConnAck
+        info!("Listening for commands on: {}", COMMAND_TOPIC);
         
         // Initial registration
         self.register().await?;
@@ -217,6 +451,7 @@ impl Agent {
         // Set up periodic tasks
         let mut heartbeat_timer = interval(Duration::from_secs(self.config.heartbeat_interval_secs));
         let mut registration_timer = interval(Duration::from_secs(self.config.registration_retry_secs * 6)); // Re-register every minute
+        let mut power_schedule_timer = interval(power_schedule::CHECK_INTERVAL);
         
         loop {
             tokio::select! {
@@ -232,12 +467,47 @@ impl Agent {
                     }
                 }
                 
-                command = self.command_receiver.recv() => {
+                _ = power_schedule_timer.tick() => {
+                    self.check_power_schedule().await;
+                }
+                
+                event = command_receiver.recv() => {
+                    let command = match event {
+                        Some(ConnectionEvent::Reconnected) => {
+                            // The kernel may have marked us offline while the broker was away
+                            if let Err(e) = self.register().await {
+                                error!("Failed to re-register after reconnect: {}", e);
+                            }
+                            continue;
+                        }
+                        Some(ConnectionEvent::Message(cmd)) => Some(cmd),
+                        None => None,
+                    };
                     match command {
+                        Some(cmd) if cmd.topic == ANNOUNCE_TOPIC => {
+                            self.answer_announce(announce_delay(&self.system_info.agent_id));
+                        }
+                        Some(cmd) if cmd.topic == heartbeat::HEARTBEAT_CONFIG_TOPIC => {
+                            self.apply_heartbeat_config(&cmd.payload);
+                        }
                         Some(cmd) => {
                             info!("Processing command from topic: {}", cmd.topic);
-                            if let Err(e) = self.process_command(cmd).await {
-                                error!("Failed to process command: {}", e);
+                            match self.accept_command(&cmd) {
+                                Ok(Some(incoming)) => {
+                                    let spec = CommandKind::from_name(&incoming.command_type).map(|kind| kind.spec());
+                                    let conflict_group = spec.and_then(|spec| spec.conflict_group);
+                                    let priority = incoming.priority
+                                        .or(spec.map(|spec| spec.priority))
+                                        .unwrap_or_default();
+                                    let agent = self.clone();
+                                    self.scheduler.spawn(conflict_group, priority, async move {
+                                        if let Err(e) = agent.process_command(incoming).await {
+                                            error!("Failed to process command: {}", e);
+                                        }
+                                    });
+                                }
+                                Ok(None) => {}
+                                Err(e) => error!("Failed to process command: {}", e),
                             }
                         }
                         None => {
@@ -251,66 +521,120 @@ impl Agent {
     }
     
     /// Register agent with kernel
+    /// Kernel lost its registry (restart): re-register after `delay` instead of waiting for the timer
+    fn answer_announce(self: &Arc<Self>, delay: Duration) -> tokio::task::JoinHandle<()> {
+        info!("Announce requested by kernel, re-registering in {:?}", delay);
+        let agent = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            if let Err(e) = agent.register().await {
+                error!("Failed to re-register on announce: {}", e);
+            }
+        })
+    }
+
     async fn register(&self) -> Result<()> {
         let capabilities = self.get_capabilities();
+        let unavailable_capabilities = capabilities::unavailable_capabilities(
+            &capabilities::CapabilityDetector::detect_all().await,
+            &capabilities,
+        );
         
         let registration = RegistrationMessage {
             agent_id: self.system_info.agent_id.clone(),
             hostname: self.system_info.hostname.clone(),
             os: self.system_info.os.clone(),
             architecture: self.system_info.architecture.clone(),
+            os_details: self.system_info.os_details.clone(),
             capabilities,
+            unavailable_capabilities,
             network: self.system_info.network.clone(),
             version: "1.0.0".to_string(),
+            heartbeat_version: HEARTBEAT_VERSION,
             timestamp: Utc::now(),
         };
         
         let payload = serde_json::to_string(&registration)
             .context("Failed to serialize registration message")?;
             
-        self.mqtt_client
-            .publish("symbion/agents/registration@v1", QoS::AtLeastOnce, false, payload)
-            .await
-            .context("Failed to publish registration")?;
+        self.outbound.push(OutboundMessage::new(symbion_topics::agents_registration(), payload, Priority::Registration));
             
-        info!("Agent registered successfully");
+        info!("Agent registration queued");
         Ok(())
     }
     
+    /// Apply the heartbeat sections selected by the kernel (broadcast or addressed to this agent)
+    fn apply_heartbeat_config(&self, payload: &str) {
+        let message: heartbeat::HeartbeatConfigMessage = match serde_json::from_str(payload) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Invalid heartbeat config message: {}", e);
+                return;
+            }
+        };
+        if message.applies_to(&self.system_info.agent_id) {
+            info!("Heartbeat sections set by kernel: {:?}", message.sections);
+            *self.heartbeat_sections.lock().unwrap() = message.sections;
+        }
+    }
+    
     /// Send heartbeat with system metrics
     async fn send_heartbeat(&self) -> Result<()> {
-        let system_metrics = metrics::SystemMetrics::collect().await
+        let sections = *self.heartbeat_sections.lock().unwrap();
+        let system_metrics = metrics::SystemMetrics::collect(&self.network_collector).await
             .context("Failed to collect system metrics")?;
             
-        let process_info = metrics::ProcessInfo::collect().await.ok();
-        let services = metrics::ServiceStatus::collect_critical().await.ok();
+        // Heavy sections are only collected when the kernel asked for them
+        let process_info = if sections.processes { metrics::ProcessInfo::collect().await.ok() } else { None };
+        let services = if sections.services { metrics::ServiceStatus::collect_critical().await.ok() } else { None };
+        let scheduled_tasks = if self.get_capabilities().iter().any(|c| c == "scheduled_tasks") {
+            self.scheduled_tasks.list(&self.system_info.os).await.ok()
+        } else {
+            None
+        };
+        let network = match discovery::NetworkInfo::discover().await {
+            Ok(network) => Some(network),
+            Err(e) => {
+                debug!("Network rediscovery failed: {}", e);
+                None
+            }
+        };
+        let backlog = self.scheduler.backlog();
+        let (maintenance, idle_secs) = {
+            let schedule = self.power_schedule.lock().unwrap();
+            let active = schedule.policy.enabled && !schedule.policy.maintenance;
+            (schedule.policy.maintenance, schedule.last_idle.filter(|_| active).map(|idle| idle.as_secs()))
+        };
         
         let heartbeat = HeartbeatMessage {
             agent_id: self.system_info.agent_id.clone(),
-            status: "online".to_string(),
+            status: if maintenance { "maintenance" } else { "online" }.to_string(),
             system: system_metrics,
             processes: process_info,
             services,
-            last_command: self.last_command.clone(),
+            os_details: self.system_info.os_details.clone(),
+            network,
+            last_command: self.recent_commands.lock().unwrap().last(),
+            recent_commands: self.recent_commands.lock().unwrap().snapshot(),
+            scheduled_tasks,
+            queue_depth: backlog.depth(),
+            busy: backlog.is_busy(),
+            idle_secs,
             timestamp: Utc::now(),
-        };
+        }.retain_sections(sections);
         
         let payload = serde_json::to_string(&heartbeat)
             .context("Failed to serialize heartbeat message")?;
             
-        self.mqtt_client
-            .publish("symbion/agents/heartbeat@v1", QoS::AtLeastOnce, false, payload)
-            .await
-            .context("Failed to publish heartbeat")?;
-            
-        debug!("Heartbeat sent");
+        let outcome = self.outbound.push(OutboundMessage::new(symbion_topics::agents_heartbeat(), payload, Priority::Heartbeat));
+        let stats = self.outbound.stats();
+        debug!("Heartbeat queued ({:?}) - outbound depth {}, dropped {}, merged {}",
+               outcome, stats.depth, stats.dropped, stats.merged);
         Ok(())
     }
     
-    /// Process incoming command from MQTT
-    async fn process_command(&mut self, cmd: ReceivedCommand) -> Result<()> {
-        let start_time = std::time::Instant::now();
-        
+    /// Parse incoming command from MQTT, None if addressed to another agent
+    fn accept_command(&self, cmd: &ReceivedCommand) -> Result<Option<IncomingCommand>> {
         // Parse the incoming command
         let incoming: IncomingCommand = serde_json::from_str(&cmd.payload)
             .context("Failed to parse incoming command")?;
@@ -319,31 +643,63 @@ impl Agent {
         if incoming.agent_id != self.system_info.agent_id {
             debug!("Ignoring command {} for agent {} (this agent is {})", 
                    incoming.command_id, incoming.agent_id, self.system_info.agent_id);
-            return Ok(());
+            return Ok(None);
         }
         
+        if !self.seen_commands.lock().unwrap().first_seen(&incoming.command_id) {
+            warn!("Ignoring duplicate delivery of command {} ({})", incoming.command_id, incoming.command_type);
+            return Ok(None);
+        }
+        
+        Ok(Some(incoming))
+    }
+    
+    /// Execute a command and publish its response (runs on the command scheduler)
+    async fn process_command(&self, incoming: IncomingCommand) -> Result<()> {
+        let start_time = std::time::Instant::now();
+        
         info!("Executing command: {} ({})", incoming.command_type, incoming.command_id);
         
         // Execute the command based on type
-        let (status, data, error) = match incoming.command_type.as_str() {
-            "shutdown" => self.execute_shutdown(&incoming).await,
-            "reboot" => self.execute_reboot(&incoming).await,
-            "hibernate" => self.execute_hibernate(&incoming).await,
-            "kill_process" => self.execute_kill_process(&incoming).await,
-            "run_command" => self.execute_shell_command(&incoming).await,
-            "get_metrics" => self.execute_get_metrics(&incoming).await,
-            "list_processes" => self.execute_list_processes(&incoming).await,
-            _ => {
+        let (status, data, error) = match CommandKind::from_name(&incoming.command_type) {
+            Some(CommandKind::Shutdown) => self.execute_power(&incoming, "shutdown").await,
+            Some(CommandKind::Reboot) => self.execute_power(&incoming, "reboot").await,
+            Some(CommandKind::Hibernate) => self.execute_power(&incoming, "hibernate").await,
+            Some(CommandKind::KillProcess) => self.execute_kill_process(&incoming).await,
+            Some(CommandKind::RunCommand) => self.execute_shell_command(&incoming).await,
+            Some(CommandKind::GetMetrics) => self.execute_get_metrics(&incoming).await,
+            Some(CommandKind::ListProcesses) => self.execute_list_processes(&incoming).await,
+            Some(CommandKind::RelayWake) => self.execute_relay_wake(&incoming).await,
+            Some(CommandKind::ListCommands) => self.execute_list_commands(&incoming).await,
+            Some(CommandKind::SetCron) => self.execute_set_cron(&incoming).await,
+            Some(CommandKind::ListCron) => self.execute_list_cron(&incoming).await,
+            Some(CommandKind::RemoveCron) => self.execute_remove_cron(&incoming).await,
+            Some(CommandKind::Describe) => self.execute_describe(&incoming).await,
+            Some(CommandKind::TailFile) => self.execute_tail_file(&incoming).await,
+            Some(CommandKind::NetCheck) => self.execute_net_check(&incoming).await,
+            Some(CommandKind::ReadFile) => self.execute_read_file(&incoming).await,
+            Some(CommandKind::GetEnv) => self.execute_get_env(&incoming).await,
+            Some(CommandKind::SetEnv) => self.execute_set_env(&incoming).await,
+            Some(CommandKind::ListListeners) => self.execute_list_listeners(&incoming).await,
+            Some(CommandKind::Renice) => self.execute_renice(&incoming).await,
+            Some(CommandKind::SetAffinity) => self.execute_set_affinity(&incoming).await,
+            Some(CommandKind::Ping) => self.execute_ping(),
+            Some(CommandKind::FirewallList) => self.execute_firewall_list().await,
+            Some(CommandKind::FirewallAdd) => self.execute_firewall_change(&incoming, firewall::FirewallChange::Add).await,
+            Some(CommandKind::FirewallRemove) => self.execute_firewall_change(&incoming, firewall::FirewallChange::Remove).await,
+            Some(CommandKind::Diagnostics) => self.execute_diagnostics().await,
+            Some(CommandKind::SetPowerSchedule) => self.execute_set_power_schedule(&incoming).await,
+            None => {
                 let err = ErrorInfo {
                     code: "UNKNOWN_COMMAND".to_string(),
-                    message: format!("Unknown command type: {}", incoming.command_type),
+                    message: format!("Unknown command type: {} (see list_commands)", incoming.command_type),
                 };
                 ("error".to_string(), None, Some(err))
             }
         };
         
-        // Update last command info
-        self.last_command = Some(CommandInfo {
+        // Update recent commands info
+        self.recent_commands.lock().unwrap().record(CommandInfo {
             command_id: incoming.command_id.clone(),
             command_type: incoming.command_type.clone(),
             status: status.clone(),
@@ -365,225 +721,171 @@ impl Agent {
         let payload = serde_json::to_string(&response)
             .context("Failed to serialize command response")?;
             
-        self.mqtt_client
-            .publish("symbion/agents/response@v1", QoS::AtLeastOnce, false, payload)
-            .await
-            .context("Failed to publish command response")?;
+        self.outbound.push(OutboundMessage::new(symbion_topics::agents_response(), payload, Priority::Response));
             
         Ok(())
     }
     
-    /// Execute shutdown command
-    async fn execute_shutdown(&self, _cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
-        info!("Executing shutdown command...");
-        
-        match self.system_info.os.as_str() {
-            "windows" => {
-                // Try immediate shutdown with wininit.exe for maximum force
-                match tokio::process::Command::new("cmd")
-                    .args(&["/C", "shutdown /s /t 0 /f"])
-                    .output()
-                    .await
-                {
-                    Ok(output) => {
-                        if output.status.success() {
-                            info!("Shutdown command executed successfully");
-                            ("success".to_string(), Some(serde_json::json!({"message": "Shutdown initiated"})), None)
-                        } else {
-                            let stderr = String::from_utf8_lossy(&output.stderr);
-                            error!("Shutdown failed: {}", stderr);
-                            let err = ErrorInfo {
-                                code: "SHUTDOWN_FAILED".to_string(),
-                                message: format!("Command failed: {}", stderr),
-                            };
-                            ("error".to_string(), None, Some(err))
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to execute shutdown: {}", e);
-                        let err = ErrorInfo {
-                            code: "EXECUTION_ERROR".to_string(),
-                            message: format!("Failed to execute shutdown: {}", e),
-                        };
-                        ("error".to_string(), None, Some(err))
-                    }
-                }
-            }
-            "linux" => {
-                match tokio::process::Command::new("sudo")
-                    .args(&["shutdown", "-h", "+1", "Shutdown initiated by Symbion"])
-                    .output()
-                    .await
-                {
-                    Ok(output) => {
-                        if output.status.success() {
-                            info!("Shutdown command executed successfully");
-                            ("success".to_string(), Some(serde_json::json!({"message": "Shutdown initiated"})), None)
-                        } else {
-                            let stderr = String::from_utf8_lossy(&output.stderr);
-                            error!("Shutdown failed: {}", stderr);
-                            let err = ErrorInfo {
-                                code: "SHUTDOWN_FAILED".to_string(),
-                                message: format!("Command failed: {}", stderr),
-                            };
-                            ("error".to_string(), None, Some(err))
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to execute shutdown: {}", e);
-                        let err = ErrorInfo {
-                            code: "EXECUTION_ERROR".to_string(),
-                            message: format!("Failed to execute shutdown: {}", e),
-                        };
-                        ("error".to_string(), None, Some(err))
-                    }
-                }
-            }
-            _ => {
+    /// Execute a power transition (shutdown, reboot, hibernate), warning logged-in users first
+    /// when the command carries a `message` / `warn_seconds` notice
+    async fn execute_power(&self, cmd: &IncomingCommand, action: &'static str) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        info!("Executing {} command...", action);
+        match power::PowerNotice::from_params(cmd.parameters.as_ref(), action) {
+            Ok(notice) => self.power_transition(action, notice).await,
+            Err(e) => {
                 let err = ErrorInfo {
-                    code: "UNSUPPORTED_OS".to_string(),
-                    message: format!("Shutdown not supported on OS: {}", self.system_info.os),
+                    code: "INVALID_PARAMETERS".to_string(),
+                    message: e.to_string(),
                 };
                 ("error".to_string(), None, Some(err))
             }
         }
     }
     
-    /// Execute reboot command
-    async fn execute_reboot(&self, _cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
-        info!("Executing reboot command...");
+    /// Run a power transition, broadcasting `notice` first
+    async fn power_transition(&self, action: &'static str, notice: Option<power::PowerNotice>) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let title = format!("{}{}", action[..1].to_uppercase(), &action[1..]);
+        let Some(plan) = power::plan(&self.system_info.os, action, notice.as_ref()) else {
+            let err = ErrorInfo {
+                code: "UNSUPPORTED_OS".to_string(),
+                message: format!("{} not supported on OS: {}", title, self.system_info.os),
+            };
+            return ("error".to_string(), None, Some(err));
+        };
         
-        match self.system_info.os.as_str() {
-            "windows" => {
-                match tokio::process::Command::new("shutdown")
-                    .args(&["/r", "/t", "5", "/c", "Reboot initiated by Symbion"])
-                    .output()
-                    .await
-                {
-                    Ok(output) => {
-                        if output.status.success() {
-                            info!("Reboot command executed successfully");
-                            ("success".to_string(), Some(serde_json::json!({"message": "Reboot initiated"})), None)
-                        } else {
-                            let stderr = String::from_utf8_lossy(&output.stderr);
-                            error!("Reboot failed: {}", stderr);
-                            let err = ErrorInfo {
-                                code: "REBOOT_FAILED".to_string(),
-                                message: format!("Command failed: {}", stderr),
-                            };
-                            ("error".to_string(), None, Some(err))
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to execute reboot: {}", e);
-                        let err = ErrorInfo {
-                            code: "EXECUTION_ERROR".to_string(),
-                            message: format!("Failed to execute reboot: {}", e),
-                        };
-                        ("error".to_string(), None, Some(err))
-                    }
-                }
+        // A user who can't be reached must not block the transition
+        if let Some(broadcast) = &plan.broadcast {
+            match tokio::process::Command::new(broadcast.program).args(&broadcast.args).output().await {
+                Ok(output) if output.status.success() => info!("Users notified of {} in {}s", action, plan.effective_delay_secs),
+                Ok(output) => warn!("{} notification failed: {}", broadcast.program, String::from_utf8_lossy(&output.stderr).trim()),
+                Err(e) => warn!("{} notification failed: {}", broadcast.program, e),
             }
-            "linux" => {
-                match tokio::process::Command::new("sudo")
-                    .args(&["reboot"])
-                    .output()
-                    .await
-                {
-                    Ok(output) => {
-                        if output.status.success() {
-                            info!("Reboot command executed successfully");
-                            ("success".to_string(), Some(serde_json::json!({"message": "Reboot initiated"})), None)
-                        } else {
-                            let stderr = String::from_utf8_lossy(&output.stderr);
-                            error!("Reboot failed: {}", stderr);
-                            let err = ErrorInfo {
-                                code: "REBOOT_FAILED".to_string(),
-                                message: format!("Command failed: {}", stderr),
-                            };
-                            ("error".to_string(), None, Some(err))
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to execute reboot: {}", e);
-                        let err = ErrorInfo {
-                            code: "EXECUTION_ERROR".to_string(),
-                            message: format!("Failed to execute reboot: {}", e),
-                        };
-                        ("error".to_string(), None, Some(err))
-                    }
+        }
+        let data = serde_json::json!({
+            "message": format!("{} initiated", title),
+            "notified": plan.broadcast.is_some(),
+            "delay_secs": plan.effective_delay_secs,
+        });
+        
+        // No native delay: wait in the background so the response isn't held for the whole warning
+        if !plan.wait.is_zero() {
+            let command = plan.command.clone();
+            let wait = plan.wait;
+            tokio::spawn(async move {
+                tokio::time::sleep(wait).await;
+                match tokio::process::Command::new(command.program).args(&command.args).output().await {
+                    Ok(output) if output.status.success() => info!("Delayed {} executed", action),
+                    Ok(output) => error!("Delayed {} failed: {}", action, String::from_utf8_lossy(&output.stderr)),
+                    Err(e) => error!("Failed to execute delayed {}: {}", action, e),
+                }
+            });
+            info!("{} scheduled in {}s", title, wait.as_secs());
+            return ("success".to_string(), Some(data), None);
+        }
+        
+        match tokio::process::Command::new(plan.command.program)
+            .args(&plan.command.args)
+            .output()
+            .await
+        {
+            Ok(output) => {
+                if output.status.success() {
+                    info!("{} command executed successfully", title);
+                    ("success".to_string(), Some(data), None)
+                } else {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    error!("{} failed: {}", title, stderr);
+                    let err = ErrorInfo {
+                        code: format!("{}_FAILED", action.to_uppercase()),
+                        message: format!("Command failed: {}", stderr),
+                    };
+                    ("error".to_string(), None, Some(err))
                 }
             }
-            _ => {
+            Err(e) => {
+                error!("Failed to execute {}: {}", action, e);
                 let err = ErrorInfo {
-                    code: "UNSUPPORTED_OS".to_string(),
-                    message: format!("Reboot not supported on OS: {}", self.system_info.os),
+                    code: "EXECUTION_ERROR".to_string(),
+                    message: format!("Failed to execute {}: {}", action, e),
                 };
                 ("error".to_string(), None, Some(err))
             }
         }
     }
     
-    /// Execute hibernate command  
-    async fn execute_hibernate(&self, _cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
-        info!("Executing hibernate command...");
+    /// Execute kill process command
+    async fn execute_kill_process(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        info!("Executing kill process command...");
+        
+        let pid = match cmd.parameters.as_ref()
+            .and_then(|p| p.get("pid"))
+            .and_then(|p| p.as_u64()) {
+            Some(pid) => pid,
+            None => {
+                let err = ErrorInfo {
+                    code: "INVALID_PARAMETERS".to_string(),
+                    message: "Missing 'pid' parameter".to_string(),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
         
         match self.system_info.os.as_str() {
             "windows" => {
-                match tokio::process::Command::new("rundll32.exe")
-                    .args(&["powrprof.dll,SetSuspendState", "Hibernate"])
+                match tokio::process::Command::new("taskkill")
+                    .args(&["/PID", &pid.to_string(), "/F"])
                     .output()
                     .await
                 {
                     Ok(output) => {
                         if output.status.success() {
-                            info!("Hibernate command executed successfully");
-                            ("success".to_string(), Some(serde_json::json!({"message": "Hibernate initiated"})), None)
+                            info!("Process {} killed successfully", pid);
+                            ("success".to_string(), Some(serde_json::json!({"message": format!("Process {} killed", pid)})), None)
                         } else {
                             let stderr = String::from_utf8_lossy(&output.stderr);
-                            error!("Hibernate failed: {}", stderr);
+                            error!("Kill process failed: {}", stderr);
                             let err = ErrorInfo {
-                                code: "HIBERNATE_FAILED".to_string(),
+                                code: "KILL_FAILED".to_string(),
                                 message: format!("Command failed: {}", stderr),
                             };
                             ("error".to_string(), None, Some(err))
                         }
                     }
                     Err(e) => {
-                        error!("Failed to execute hibernate: {}", e);
+                        error!("Failed to execute kill: {}", e);
                         let err = ErrorInfo {
                             code: "EXECUTION_ERROR".to_string(),
-                            message: format!("Failed to execute hibernate: {}", e),
+                            message: format!("Failed to execute kill: {}", e),
                         };
                         ("error".to_string(), None, Some(err))
                     }
                 }
             }
             "linux" => {
-                match tokio::process::Command::new("systemctl")
-                    .args(&["hibernate"])
+                match tokio::process::Command::new("kill")
+                    .args(&["-9", &pid.to_string()])
                     .output()
                     .await
                 {
                     Ok(output) => {
                         if output.status.success() {
-                            info!("Hibernate command executed successfully");
-                            ("success".to_string(), Some(serde_json::json!({"message": "Hibernate initiated"})), None)
+                            info!("Process {} killed successfully", pid);
+                            ("success".to_string(), Some(serde_json::json!({"message": format!("Process {} killed", pid)})), None)
                         } else {
                             let stderr = String::from_utf8_lossy(&output.stderr);
-                            error!("Hibernate failed: {}", stderr);
+                            error!("Kill process failed: {}", stderr);
                             let err = ErrorInfo {
-                                code: "HIBERNATE_FAILED".to_string(),
+                                code: "KILL_FAILED".to_string(),
                                 message: format!("Command failed: {}", stderr),
                             };
                             ("error".to_string(), None, Some(err))
                         }
                     }
                     Err(e) => {
-                        error!("Failed to execute hibernate: {}", e);
+                        error!("Failed to execute kill: {}", e);
                         let err = ErrorInfo {
                             code: "EXECUTION_ERROR".to_string(),
-                            message: format!("Failed to execute hibernate: {}", e),
+                            message: format!("Failed to execute kill: {}", e),
                         };
                         ("error".to_string(), None, Some(err))
                     }
@@ -592,244 +894,1049 @@ impl Agent {
             _ => {
                 let err = ErrorInfo {
                     code: "UNSUPPORTED_OS".to_string(),
-                    message: format!("Hibernate not supported on OS: {}", self.system_info.os),
+                    message: format!("Kill process not supported on OS: {}", self.system_info.os),
                 };
                 ("error".to_string(), None, Some(err))
             }
         }
     }
     
-    /// Execute kill process command
-    async fn execute_kill_process(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
-        info!("Executing kill process command...");
+    /// Execute shell command
+    async fn execute_shell_command(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        info!("Executing shell command...");
         
-        let pid = match cmd.parameters.as_ref()
-            .and_then(|p| p.get("pid"))
-            .and_then(|p| p.as_u64()) {
-            Some(pid) => pid,
+        let command = match cmd.parameters.as_ref()
+            .and_then(|p| p.get("command"))
+            .and_then(|p| p.as_str()) {
+            Some(command) => command,
             None => {
                 let err = ErrorInfo {
                     code: "INVALID_PARAMETERS".to_string(),
-                    message: "Missing 'pid' parameter".to_string(),
+                    message: "Missing 'command' parameter".to_string(),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
+        
+        // Security check - only allow-listed programs, without command chaining
+        if let Err(e) = execution::check_command_allowed(command, &self.config.allowed_commands) {
+            let err = ErrorInfo {
+                code: "UNSAFE_COMMAND".to_string(),
+                message: e.to_string(),
+            };
+            return ("error".to_string(), None, Some(err));
+        }
+        
+        // Optional input piped to the process (text or base64, size-limited)
+        let params = cmd.parameters.as_ref();
+        let stdin = match params.and_then(|p| p.get("stdin")).and_then(|v| v.as_str()) {
+            Some(stdin) => {
+                let encoding = params.and_then(|p| p.get("stdin_encoding")).and_then(|v| v.as_str());
+                match execution::decode_stdin(stdin, encoding, self.config.max_stdin_bytes) {
+                    Ok(input) => Some(input),
+                    Err(e) => {
+                        let err = ErrorInfo {
+                            code: "INVALID_PARAMETERS".to_string(),
+                            message: e.to_string(),
+                        };
+                        return ("error".to_string(), None, Some(err));
+                    }
+                }
+            }
+            None => None,
+        };
+        
+        let shell = match self.system_info.os.as_str() {
+            "windows" => {
+                let mut shell = tokio::process::Command::new("cmd");
+                shell.args(["/C", command]);
+                shell
+            }
+            "linux" => {
+                let mut shell = tokio::process::Command::new("sh");
+                shell.args(["-c", command]);
+                shell
+            }
+            _ => {
+                let err = ErrorInfo {
+                    code: "UNSUPPORTED_OS".to_string(),
+                    message: format!("Shell commands not supported on OS: {}", self.system_info.os),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
+        
+        if cmd.parameters.as_ref().and_then(|p| p.get("stream")).and_then(|v| v.as_bool()).unwrap_or(false) {
+            return self.stream_shell_command(cmd, shell, stdin).await;
+        }
+        
+        // Output is read incrementally and capped, a runaway command can't exhaust memory
+        match execution::run_capped_with_stdin(shell, self.config.max_output_bytes, stdin).await {
+            Ok(output) => {
+                if output.truncated {
+                    warn!("Shell command output truncated at {} bytes", self.config.max_output_bytes);
+                }
+                let data = serde_json::json!({
+                    "stdout": output.stdout,
+                    "stderr": output.stderr,
+                    "exit_code": output.exit_code,
+                    "truncated": output.truncated
+                });
+                if output.exit_code == Some(0) {
+                    info!("Shell command executed successfully");
+                    ("success".to_string(), Some(data), None)
+                } else {
+                    error!("Shell command failed: {}", output.stderr);
+                    let err = ErrorInfo {
+                        code: "COMMAND_FAILED".to_string(),
+                        message: format!("Command failed with exit code: {:?}", output.exit_code),
+                    };
+                    ("error".to_string(), Some(data), Some(err))
+                }
+            }
+            Err(e) => {
+                error!("Failed to execute shell command: {}", e);
+                let err = ErrorInfo {
+                    code: "EXECUTION_ERROR".to_string(),
+                    message: format!("Failed to execute command: {}", e),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Run a shell command publishing its output on agents.output@v1 as it is produced,
+    /// then a completed marker. Killed at the command's timeout (`journalctl -f` never exits).
+    async fn stream_shell_command(&self, cmd: &IncomingCommand, shell: tokio::process::Command, stdin: Option<Vec<u8>>) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let limit = Duration::from_secs(cmd.timeout_seconds.unwrap_or(DEFAULT_STREAM_TIMEOUT_SECS) as u64);
+        let max_bytes = self.config.max_output_bytes;
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let cap_reached = tokio::sync::Notify::new();
+        // Dropping the run kills the command: output is never published past max_output_bytes
+        let run = async {
+            tokio::select! {
+                result = tokio::time::timeout(limit, execution::run_streaming(shell, stdin, sender)) => Some(result),
+                _ = cap_reached.notified() => None,
+            }
+        };
+        // Ends when the run drops its sender (exit, failure, timeout or output cap)
+        let forward = async {
+            let (mut seq, mut bytes) = (0u64, 0usize);
+            while let Some(mut chunk) = receiver.recv().await {
+                let capped = chunk.cap(bytes, max_bytes);
+                bytes += chunk.data.len();
+                if !chunk.data.is_empty() {
+                    self.publish_output(cmd, seq, Some(chunk), None, false, false);
+                    seq += 1;
+                }
+                if capped {
+                    cap_reached.notify_one();
+                    break;
+                }
+            }
+            (seq, bytes)
+        };
+        let (result, (chunks, bytes)) = tokio::join!(run, forward);
+        
+        let truncated = result.is_none();
+        let (exit_code, timed_out) = match &result {
+            Some(Ok(Ok(exit_code))) => (*exit_code, false),
+            Some(Ok(Err(_))) | None => (None, false),
+            Some(Err(_)) => (None, true),
+        };
+        self.publish_output(cmd, chunks, None, exit_code, timed_out, truncated);
+        let data = serde_json::json!({
+            "streamed": true,
+            "chunks": chunks,
+            "bytes": bytes,
+            "exit_code": exit_code,
+            "timed_out": timed_out,
+            "truncated": truncated,
+        });
+        let Some(result) = result else {
+            warn!("Streamed shell command killed after {} bytes of output", bytes);
+            let err = ErrorInfo {
+                code: "OUTPUT_LIMIT".to_string(),
+                message: format!("Command killed once its output reached {} bytes (max_output_bytes)", max_bytes),
+            };
+            return ("error".to_string(), Some(data), Some(err));
+        };
+        match result {
+            Ok(Ok(Some(0))) => {
+                info!("Streamed shell command completed ({} chunks, {} bytes)", chunks, bytes);
+                ("success".to_string(), Some(data), None)
+            }
+            Ok(Ok(exit_code)) => {
+                let err = ErrorInfo {
+                    code: "COMMAND_FAILED".to_string(),
+                    message: format!("Command failed with exit code: {:?}", exit_code),
+                };
+                ("error".to_string(), Some(data), Some(err))
+            }
+            Ok(Err(e)) => {
+                error!("Failed to execute streamed shell command: {}", e);
+                let err = ErrorInfo {
+                    code: "EXECUTION_ERROR".to_string(),
+                    message: format!("Failed to execute command: {}", e),
+                };
+                ("error".to_string(), Some(data), Some(err))
+            }
+            Err(_) => {
+                warn!("Streamed shell command killed after {:?}", limit);
+                let err = ErrorInfo {
+                    code: "TIMEOUT".to_string(),
+                    message: format!("Command killed after {} seconds", limit.as_secs()),
+                };
+                ("timeout".to_string(), Some(data), Some(err))
+            }
+        }
+    }
+    
+    /// Queue one agents.output@v1 message (`chunk` = None: completed marker)
+    fn publish_output(&self, cmd: &IncomingCommand, seq: u64, chunk: Option<execution::OutputChunk>, exit_code: Option<i32>, timed_out: bool, truncated: bool) {
+        let completed = chunk.is_none();
+        let (stream, data) = chunk.map_or((None, String::new()), |c| (Some(c.stream), c.data));
+        let message = OutputMessage {
+            command_id: cmd.command_id.clone(),
+            agent_id: self.system_info.agent_id.clone(),
+            seq,
+            stream,
+            data,
+            completed,
+            exit_code,
+            timed_out,
+            truncated,
+            timestamp: Utc::now(),
+        };
+        match serde_json::to_string(&message) {
+            Ok(payload) => {
+                self.outbound.push(OutboundMessage::new(symbion_topics::agents_output(), payload, Priority::Response));
+            }
+            Err(e) => error!("Failed to serialize command output: {}", e),
+        }
+    }
+    
+    /// Execute get metrics command
+    async fn execute_get_metrics(&self, _cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        info!("Collecting system metrics...");
+        
+        match metrics::SystemMetrics::collect(&self.metrics_network_collector).await {
+            Ok(system_metrics) => {
+                let process_info = metrics::ProcessInfo::collect().await.ok();
+                let services = metrics::ServiceStatus::collect_critical().await.ok();
+                
+                let metrics_data = serde_json::json!({
+                    "system": system_metrics,
+                    "processes": process_info,
+                    "services": services,
+                    "timestamp": Utc::now()
+                });
+                
+                ("success".to_string(), Some(metrics_data), None)
+            }
+            Err(e) => {
+                error!("Failed to collect metrics: {}", e);
+                let err = ErrorInfo {
+                    code: "METRICS_ERROR".to_string(),
+                    message: format!("Failed to collect metrics: {}", e),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Execute relay wake command (emit magic packet on behalf of the kernel)
+    async fn execute_relay_wake(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let params = cmd.parameters.as_ref();
+        let mac = match params.and_then(|p| p.get("mac")).and_then(|m| m.as_str()) {
+            Some(mac) => mac,
+            None => {
+                let err = ErrorInfo {
+                    code: "INVALID_PARAMETERS".to_string(),
+                    message: "Missing 'mac' parameter".to_string(),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
+        let broadcast = params
+            .and_then(|p| p.get("broadcast"))
+            .and_then(|b| b.as_str())
+            .and_then(|b| b.parse::<std::net::Ipv4Addr>().ok())
+            .unwrap_or(std::net::Ipv4Addr::BROADCAST);
+        
+        info!("Relaying wake for {} via {}", mac, broadcast);
+        
+        match execution::CommandExecutor::wake_on_lan(mac, broadcast) {
+            Ok(()) => ("success".to_string(), Some(serde_json::json!({"mac": mac, "broadcast": broadcast.to_string()})), None),
+            Err(e) => {
+                error!("Relay wake failed: {}", e);
+                let err = ErrorInfo {
+                    code: "WOL_FAILED".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Execute list commands command (catalog of supported command types)
+    async fn execute_list_commands(&self, _cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let catalog = capabilities::command_catalog(&self.get_capabilities());
+        ("success".to_string(), Some(serde_json::json!({ "commands": catalog })), None)
+    }
+    
+    /// Answer a ping right away (responsiveness check)
+    fn execute_ping(&self) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        ("success".to_string(), Some(serde_json::json!({ "pong": true, "timestamp": Utc::now() })), None)
+    }
+    
+    /// Publish an intermediate `partial` response (streamed commands)
+    fn publish_partial(&self, cmd: &IncomingCommand, data: serde_json::Value) {
+        let response = CommandResponse {
+            command_id: cmd.command_id.clone(),
+            agent_id: self.system_info.agent_id.clone(),
+            status: "partial".to_string(),
+            data: Some(data),
+            error: None,
+            execution_time_ms: 0,
+            timestamp: Utc::now(),
+        };
+        match serde_json::to_string(&response) {
+            Ok(payload) => {
+                self.outbound.push(OutboundMessage::new(symbion_topics::agents_response(), payload, Priority::Response));
+            }
+            Err(e) => error!("Failed to serialize partial response: {}", e),
+        }
+    }
+    
+    /// Execute tail file command (last lines, then optional bounded follow)
+    async fn execute_tail_file(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let params = cmd.parameters.clone().unwrap_or_default();
+        let Some(path) = params.get("path").and_then(|v| v.as_str()) else {
+            let err = ErrorInfo {
+                code: "INVALID_PARAMETERS".to_string(),
+                message: "Expected 'path' parameter".to_string(),
+            };
+            return ("error".to_string(), None, Some(err));
+        };
+        let limits = &self.config.file_ops;
+        let lines = params.get("lines").and_then(|v| v.as_u64())
+            .map_or(DEFAULT_TAIL_LINES, |n| n as usize)
+            .min(limits.max_lines);
+        let follow_secs = params.get("follow_secs").and_then(|v| v.as_u64())
+            .unwrap_or(0)
+            .min(limits.max_follow_secs);
+        let rate_limit_kbps = match rate_limit_param(&params) {
+            Ok(rate) => rate,
+            Err(err) => return ("error".to_string(), None, Some(err)),
+        };
+        
+        let resolved = match fileops::resolve_allowed(path, limits) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                warn!("Refused tail_file on {}: {}", path, e);
+                let err = ErrorInfo {
+                    code: "PATH_NOT_ALLOWED".to_string(),
+                    message: e.to_string(),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
+        let tail = match fileops::read_tail(&resolved, lines, limits.max_read_bytes).await {
+            Ok(tail) => tail,
+            Err(e) => {
+                let err = ErrorInfo {
+                    code: "FILE_READ_ERROR".to_string(),
+                    message: e.to_string(),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
+        
+        let data = serde_json::json!({
+            "path": resolved,
+            "lines": tail.lines,
+            "size": tail.size,
+            "truncated": tail.truncated,
+        });
+        if follow_secs == 0 {
+            return ("success".to_string(), Some(data), None);
+        }
+        
+        // Follow: initial lines first, then each batch of appended lines as a partial response
+        info!("Following {} for {}s", resolved.display(), follow_secs);
+        self.publish_partial(cmd, data);
+        let mut follower = fileops::FileFollower::new(resolved.clone(), tail.size);
+        let mut bucket = rate_limit_kbps.map(|kbps| fileops::TokenBucket::new(kbps, limits.chunk_bytes));
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(follow_secs);
+        let mut poll = interval(TAIL_FOLLOW_POLL);
+        let mut streamed = 0;
+        while tokio::time::Instant::now() < deadline {
+            poll.tick().await;
+            match follower.poll(limits.max_read_bytes).await {
+                Ok(new_lines) if !new_lines.is_empty() => {
+                    if let Some(bucket) = bucket.as_mut() {
+                        bucket.acquire(new_lines.iter().map(|l| l.len() as u64 + 1).sum()).await;
+                    }
+                    streamed += new_lines.len();
+                    self.publish_partial(cmd, serde_json::json!({ "path": resolved, "lines": new_lines }));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("Stopped following {}: {}", resolved.display(), e);
+                    break;
+                }
+            }
+        }
+        
+        ("success".to_string(), Some(serde_json::json!({
+            "path": resolved,
+            "lines": [],
+            "follow_secs": follow_secs,
+            "lines_streamed": streamed,
+        })), None)
+    }
+    
+    /// Execute read file command (chunks streamed as partial responses, optionally paced)
+    async fn execute_read_file(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let params = cmd.parameters.clone().unwrap_or_default();
+        let Some(path) = params.get("path").and_then(|v| v.as_str()) else {
+            let err = ErrorInfo {
+                code: "INVALID_PARAMETERS".to_string(),
+                message: "Expected 'path' parameter".to_string(),
+            };
+            return ("error".to_string(), None, Some(err));
+        };
+        let rate_limit_kbps = match rate_limit_param(&params) {
+            Ok(rate) => rate,
+            Err(err) => return ("error".to_string(), None, Some(err)),
+        };
+        let limits = &self.config.file_ops;
+        
+        let resolved = match fileops::resolve_allowed(path, limits) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                warn!("Refused read_file on {}: {}", path, e);
+                let err = ErrorInfo {
+                    code: "PATH_NOT_ALLOWED".to_string(),
+                    message: e.to_string(),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
+        let size = match tokio::fs::metadata(&resolved).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                let err = ErrorInfo {
+                    code: "FILE_READ_ERROR".to_string(),
+                    message: e.to_string(),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
+        let start_offset = params.get("offset").and_then(|v| v.as_u64()).unwrap_or(0).min(size);
+        
+        // Never unlimited: the agent's cap bounds what the kernel asks for
+        let rate_limit_kbps = rate_limit_kbps.unwrap_or(u64::MAX).min(limits.max_rate_limit_kbps).max(1);
+        // A chunk never takes more than about a second of the rate budget
+        let mut bucket = fileops::TokenBucket::new(rate_limit_kbps, limits.chunk_bytes);
+        let chunk_bytes = bucket.bytes_per_sec().clamp(MIN_TRANSFER_CHUNK, limits.chunk_bytes);
+        
+        info!("Reading {} from offset {} ({} kbps)", resolved.display(), start_offset, rate_limit_kbps);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(limits.max_transfer_secs);
+        let mut offset = start_offset;
+        while offset < size && tokio::time::Instant::now() < deadline {
+            let chunk = match fileops::read_chunk(&resolved, offset, chunk_bytes).await {
+                Ok(chunk) if !chunk.is_empty() => chunk,
+                Ok(_) => break,
+                Err(e) => {
+                    let err = ErrorInfo {
+                        code: "FILE_READ_ERROR".to_string(),
+                        message: e.to_string(),
+                    };
+                    return ("error".to_string(), None, Some(err));
+                }
+            };
+            bucket.acquire(chunk.len() as u64).await;
+            // Backpressure: a slow broker pauses the read instead of filling agent memory
+            if tokio::time::timeout_at(deadline, self.outbound.wait_for_room(MAX_QUEUED_PARTS)).await.is_err() {
+                break;
+            }
+            self.publish_partial(cmd, serde_json::json!({
+                "path": resolved,
+                "offset": offset,
+                "length": chunk.len(),
+                "data": BASE64.encode(&chunk),
+            }));
+            offset += chunk.len() as u64;
+        }
+        
+        // Cut by the time budget: the kernel resumes with `offset` = `next_offset`
+        ("success".to_string(), Some(serde_json::json!({
+            "path": resolved,
+            "size": size,
+            "bytes_sent": offset - start_offset,
+            "next_offset": offset,
+            "complete": offset >= size,
+            "rate_limit_kbps": rate_limit_kbps,
+        })), None)
+    }
+    
+    /// Execute list listeners command (listening sockets with owning process)
+    async fn execute_list_listeners(&self, _cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        match listeners::list().await {
+            Ok(report) => {
+                info!("Listed {} listening sockets via {}", report.count, report.tool);
+                match serde_json::to_value(&report) {
+                    Ok(data) => ("success".to_string(), Some(data), None),
+                    Err(e) => {
+                        let err = ErrorInfo {
+                            code: "LISTENERS_ERROR".to_string(),
+                            message: e.to_string(),
+                        };
+                        ("error".to_string(), None, Some(err))
+                    }
+                }
+            }
+            Err(e) => {
+                error!("Failed to list listeners: {}", e);
+                let err = ErrorInfo {
+                    code: "LISTENERS_ERROR".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Validated `pid` parameter of a scheduling command: must exist and not be init/System
+    fn priority_target(&self, cmd: &IncomingCommand) -> Result<u32, ErrorInfo> {
+        let invalid = |message: String| ErrorInfo { code: "INVALID_PARAMETERS".to_string(), message };
+        let pid = cmd.parameters.as_ref()
+            .and_then(|p| p.get("pid"))
+            .and_then(|p| p.as_u64())
+            .ok_or_else(|| invalid("Missing 'pid' parameter".to_string()))?;
+        let pid = priority::validate_pid(pid, &self.system_info.os).map_err(|e| invalid(e.to_string()))?;
+        if !priority::process_exists(pid) {
+            return Err(ErrorInfo {
+                code: "PROCESS_NOT_FOUND".to_string(),
+                message: format!("No process with pid {}", pid),
+            });
+        }
+        Ok(pid)
+    }
+    
+    /// Run a planned scheduling change and report it
+    async fn run_priority_change(&self, planned: priority::PlannedCommand, data: serde_json::Value) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        match priority::run(&planned).await {
+            Ok(_) => {
+                info!("Scheduling change applied: {} {}", planned.program, planned.args.join(" "));
+                ("success".to_string(), Some(data), None)
+            }
+            Err(e) => {
+                error!("Scheduling change failed: {}", e);
+                let err = ErrorInfo {
+                    code: "PRIORITY_FAILED".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Execute renice command (scheduling priority of a process)
+    async fn execute_renice(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let pid = match self.priority_target(cmd) {
+            Ok(pid) => pid,
+            Err(err) => return ("error".to_string(), None, Some(err)),
+        };
+        let nice = cmd.parameters.as_ref()
+            .and_then(|p| p.get("nice"))
+            .and_then(|n| n.as_i64())
+            .ok_or_else(|| anyhow::anyhow!("Missing 'nice' parameter"))
+            .and_then(priority::validate_nice);
+        let planned = nice.and_then(|nice| priority::renice_command(&self.system_info.os, pid, nice).map(|planned| (nice, planned)));
+        match planned {
+            Ok((nice, planned)) => self.run_priority_change(planned, serde_json::json!({ "pid": pid, "nice": nice })).await,
+            Err(e) => {
+                let err = ErrorInfo {
+                    code: "INVALID_PARAMETERS".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Execute set affinity command (CPUs a process may run on)
+    async fn execute_set_affinity(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let pid = match self.priority_target(cmd) {
+            Ok(pid) => pid,
+            Err(err) => return ("error".to_string(), None, Some(err)),
+        };
+        let cpus = cmd.parameters.as_ref()
+            .and_then(|p| p.get("cpus"))
+            .and_then(|c| c.as_array())
+            .and_then(|cpus| cpus.iter().map(|c| c.as_u64()).collect::<Option<Vec<u64>>>())
+            .ok_or_else(|| anyhow::anyhow!("Missing or invalid 'cpus' parameter (list of CPU indexes)"))
+            .and_then(|cpus| priority::validate_cpus(&cpus, priority::cpu_count()));
+        let planned = cpus.and_then(|cpus| priority::affinity_command(&self.system_info.os, pid, &cpus).map(|planned| (cpus, planned)));
+        match planned {
+            Ok((cpus, planned)) => self.run_priority_change(planned, serde_json::json!({ "pid": pid, "cpus": cpus })).await,
+            Err(e) => {
+                let err = ErrorInfo {
+                    code: "INVALID_PARAMETERS".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Firewall tool of this host, refused unless `firewall.enable_firewall_mgmt` is set
+    fn firewall_backend(&self) -> Result<config::FirewallBackend, ErrorInfo> {
+        if !self.config.firewall.enable_firewall_mgmt {
+            warn!("Refused firewall command: firewall management is disabled");
+            return Err(ErrorInfo {
+                code: "FIREWALL_DISABLED".to_string(),
+                message: "Firewall management is disabled (firewall.enable_firewall_mgmt)".to_string(),
+            });
+        }
+        firewall::backend_for(&self.system_info.os, self.config.firewall.linux_backend).map_err(|e| ErrorInfo {
+            code: "UNSUPPORTED_OS".to_string(),
+            message: e.to_string(),
+        })
+    }
+    
+    /// Execute firewall list command (raw rule listing of the backend)
+    async fn execute_firewall_list(&self) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let backend = match self.firewall_backend() {
+            Ok(backend) => backend,
+            Err(err) => return ("error".to_string(), None, Some(err)),
+        };
+        match firewall::run(&firewall::list_command(backend)).await {
+            Ok(output) => {
+                let rules: Vec<&str> = output.lines().map(str::trim_end).filter(|l| !l.trim().is_empty()).collect();
+                ("success".to_string(), Some(serde_json::json!({ "backend": backend, "rules": rules })), None)
+            }
+            Err(e) => {
+                error!("Failed to list firewall rules: {}", e);
+                let err = ErrorInfo {
+                    code: "FIREWALL_ERROR".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Execute firewall add/remove command (validated rule, optional dry run)
+    async fn execute_firewall_change(
+        &self,
+        cmd: &IncomingCommand,
+        change: firewall::FirewallChange,
+    ) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let backend = match self.firewall_backend() {
+            Ok(backend) => backend,
+            Err(err) => return ("error".to_string(), None, Some(err)),
+        };
+        let params = cmd.parameters.clone().unwrap_or_default();
+        let rule = params.get("rule")
+            .ok_or_else(|| anyhow::anyhow!("Missing 'rule' parameter"))
+            .and_then(|rule| serde_json::from_value::<firewall::FirewallRule>(rule.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid 'rule': {}", e)))
+            .and_then(|rule| firewall::validate_rule(&rule).map(|validated| (rule, validated)));
+        let (rule, validated) = match rule {
+            Ok(rule) => rule,
+            Err(e) => {
+                let err = ErrorInfo {
+                    code: "INVALID_PARAMETERS".to_string(),
+                    message: e.to_string(),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
+        
+        // A rule stranding the agent could not be undone remotely
+        if change == firewall::FirewallChange::Add {
+            let (host, port) = (self.config.mqtt_broker.as_str(), self.config.mqtt_port);
+            let refusal = match tokio::net::lookup_host((host, port)).await.map(|addrs| addrs.collect::<Vec<_>>()) {
+                Ok(broker) if firewall::blocks_broker(&validated, &broker) => {
+                    Some(format!("rule would cut the agent off its MQTT broker {}:{}", host, port))
+                }
+                Ok(_) => None,
+                Err(e) => Some(format!("cannot resolve MQTT broker {} to check the rule: {}", host, e)),
+            };
+            if let Some(message) = refusal {
+                warn!("Refused firewall rule: {}", message);
+                let err = ErrorInfo {
+                    code: "INVALID_PARAMETERS".to_string(),
+                    message,
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        }
+        
+        let planned = change.plan(backend, &validated);
+        let command_line = format!("{} {}", planned.program, planned.args.join(" "));
+        let dry_run = params.get("dry_run").and_then(|d| d.as_bool()).unwrap_or(false);
+        let data = serde_json::json!({ "rule": rule, "backend": backend, "command": command_line, "dry_run": dry_run });
+        if dry_run {
+            return ("success".to_string(), Some(data), None);
+        }
+        match firewall::run(&planned).await {
+            Ok(_) => {
+                info!("Firewall updated: {}", command_line);
+                ("success".to_string(), Some(data), None)
+            }
+            Err(e) => {
+                error!("Firewall change failed: {}", e);
+                let err = ErrorInfo {
+                    code: "FIREWALL_ERROR".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Execute get env command (persistent values of allow-listed keys)
+    async fn execute_get_env(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let limits = &self.config.environment;
+        let keys: Vec<String> = match cmd.parameters.as_ref().and_then(|p| p.get("keys")) {
+            None => limits.allowed_keys.clone(),
+            Some(value) => match serde_json::from_value(value.clone()) {
+                Ok(keys) => keys,
+                Err(_) => {
+                    let err = ErrorInfo {
+                        code: "INVALID_PARAMETERS".to_string(),
+                        message: "'keys' must be an array of strings".to_string(),
+                    };
+                    return ("error".to_string(), None, Some(err));
+                }
+            },
+        };
+        if let Some(e) = keys.iter().find_map(|key| envvars::check_key(key, limits).err()) {
+            warn!("Refused get_env: {}", e);
+            let err = ErrorInfo {
+                code: "KEY_NOT_ALLOWED".to_string(),
+                message: e.to_string(),
+            };
+            return ("error".to_string(), None, Some(err));
+        }
+        
+        match envvars::read_values(&self.system_info.os, &keys, limits).await {
+            Ok(values) => ("success".to_string(), Some(serde_json::json!({ "values": values })), None),
+            Err(e) => {
+                let err = ErrorInfo {
+                    code: "ENV_READ_ERROR".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Execute set env command (all-or-nothing, reports each applied change)
+    async fn execute_set_env(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let limits = &self.config.environment;
+        let changes: std::collections::BTreeMap<String, Option<String>> = match cmd.parameters.as_ref()
+            .and_then(|p| p.get("values"))
+            .map(|v| serde_json::from_value(v.clone()))
+        {
+            Some(Ok(changes)) => changes,
+            _ => {
+                let err = ErrorInfo {
+                    code: "INVALID_PARAMETERS".to_string(),
+                    message: "Expected 'values' object of key -> string (or null to remove)".to_string(),
                 };
                 return ("error".to_string(), None, Some(err));
             }
         };
+        if let Some(e) = changes.keys().find_map(|key| envvars::check_key(key, limits).err()) {
+            warn!("Refused set_env: {}", e);
+            let err = ErrorInfo {
+                code: "KEY_NOT_ALLOWED".to_string(),
+                message: e.to_string(),
+            };
+            return ("error".to_string(), None, Some(err));
+        }
         
-        match self.system_info.os.as_str() {
-            "windows" => {
-                match tokio::process::Command::new("taskkill")
-                    .args(&["/PID", &pid.to_string(), "/F"])
-                    .output()
-                    .await
-                {
-                    Ok(output) => {
-                        if output.status.success() {
-                            info!("Process {} killed successfully", pid);
-                            ("success".to_string(), Some(serde_json::json!({"message": format!("Process {} killed", pid)})), None)
-                        } else {
-                            let stderr = String::from_utf8_lossy(&output.stderr);
-                            error!("Kill process failed: {}", stderr);
-                            let err = ErrorInfo {
-                                code: "KILL_FAILED".to_string(),
-                                message: format!("Command failed: {}", stderr),
-                            };
-                            ("error".to_string(), None, Some(err))
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to execute kill: {}", e);
-                        let err = ErrorInfo {
-                            code: "EXECUTION_ERROR".to_string(),
-                            message: format!("Failed to execute kill: {}", e),
-                        };
-                        ("error".to_string(), None, Some(err))
-                    }
-                }
+        match envvars::apply(&self.system_info.os, &changes, limits).await {
+            Ok(applied) => {
+                let changed = applied.iter().filter(|c| c.changed).count();
+                info!("Environment updated: {} of {} keys changed", changed, applied.len());
+                ("success".to_string(), Some(serde_json::json!({ "changes": applied, "changed": changed })), None)
             }
-            "linux" => {
-                match tokio::process::Command::new("kill")
-                    .args(&["-9", &pid.to_string()])
-                    .output()
-                    .await
-                {
-                    Ok(output) => {
-                        if output.status.success() {
-                            info!("Process {} killed successfully", pid);
-                            ("success".to_string(), Some(serde_json::json!({"message": format!("Process {} killed", pid)})), None)
-                        } else {
-                            let stderr = String::from_utf8_lossy(&output.stderr);
-                            error!("Kill process failed: {}", stderr);
-                            let err = ErrorInfo {
-                                code: "KILL_FAILED".to_string(),
-                                message: format!("Command failed: {}", stderr),
-                            };
-                            ("error".to_string(), None, Some(err))
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to execute kill: {}", e);
-                        let err = ErrorInfo {
-                            code: "EXECUTION_ERROR".to_string(),
-                            message: format!("Failed to execute kill: {}", e),
-                        };
-                        ("error".to_string(), None, Some(err))
-                    }
+            Err(e) => {
+                let err = ErrorInfo {
+                    code: "ENV_WRITE_ERROR".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Execute network self-test (gateway, DNS, configured URL, allow-listed targets)
+    async fn execute_net_check(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let params = cmd.parameters.clone().unwrap_or_default();
+        let requested: Option<Vec<String>> = match params.get("targets") {
+            None => None,
+            Some(value) => match serde_json::from_value(value.clone()) {
+                Ok(targets) => Some(targets),
+                Err(_) => {
+                    let err = ErrorInfo {
+                        code: "INVALID_PARAMETERS".to_string(),
+                        message: "'targets' must be an array of strings".to_string(),
+                    };
+                    return ("error".to_string(), None, Some(err));
                 }
+            },
+        };
+        
+        let limits = &self.config.net_check;
+        let targets = match netcheck::resolve_targets(
+            requested.as_deref(),
+            limits,
+            netcheck::default_gateway(),
+            netcheck::system_nameserver(),
+        ) {
+            Ok(targets) => targets,
+            Err(message) => {
+                warn!("Refused net_check: {}", message);
+                let err = ErrorInfo {
+                    code: "TARGET_NOT_ALLOWED".to_string(),
+                    message,
+                };
+                return ("error".to_string(), None, Some(err));
             }
-            _ => {
+        };
+        
+        let report = netcheck::run(&targets, Duration::from_millis(limits.timeout_ms)).await;
+        info!("Network check: {} ({}/{} reachable)", report.status, report.reachable, report.total);
+        
+        match serde_json::to_value(&report) {
+            Ok(data) => ("success".to_string(), Some(data), None),
+            Err(e) => {
                 let err = ErrorInfo {
-                    code: "UNSUPPORTED_OS".to_string(),
-                    message: format!("Kill process not supported on OS: {}", self.system_info.os),
+                    code: "NET_CHECK_ERROR".to_string(),
+                    message: e.to_string(),
                 };
                 ("error".to_string(), None, Some(err))
             }
         }
     }
     
-    /// Execute shell command
-    async fn execute_shell_command(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
-        info!("Executing shell command...");
+    /// Execute diagnostics command (internal self-checks aggregated into one report)
+    async fn execute_diagnostics(&self) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let config_check = match config::AgentConfig::config_file_path() {
+            Ok(path) => diagnostics::check_config(&path),
+            Err(e) => diagnostics::CheckResult::new("config", diagnostics::CheckStatus::Fail, e.to_string()),
+        };
+        let detected = capabilities::CapabilityDetector::detect_all().await;
+        let sudo_available = !cfg!(target_os = "windows") && capabilities::CapabilityDetector::command_exists("sudo").await;
+        let report = diagnostics::aggregate(vec![
+            config_check,
+            diagnostics::check_mqtt(&self.connection),
+            diagnostics::check_capabilities(&detected, &self.get_capabilities()),
+            diagnostics::check_writable(&diagnostics::writable_dirs()),
+            diagnostics::check_elevation(diagnostics::is_admin().await, self.config.auto_elevate, sudo_available, self.config.store_credentials),
+        ]);
+        info!("Diagnostics: {:?} ({} ok, {} warn, {} fail)", report.status, report.summary.ok, report.summary.warn, report.summary.fail);
         
-        let command = match cmd.parameters.as_ref()
-            .and_then(|p| p.get("command"))
-            .and_then(|p| p.as_str()) {
-            Some(command) => command,
-            None => {
+        match serde_json::to_value(&report) {
+            Ok(data) => ("success".to_string(), Some(data), None),
+            Err(e) => {
+                let err = ErrorInfo {
+                    code: "DIAGNOSTICS_ERROR".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Execute set power schedule command (update the idle auto-shutdown policy, omitted fields kept)
+    async fn execute_set_power_schedule(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let current = self.power_schedule.lock().unwrap().policy.clone();
+        let policy = match power_schedule::update(&current, cmd.parameters.as_ref()) {
+            Ok(policy) => policy,
+            Err(e) => {
+                warn!("Refused set_power_schedule: {}", e);
                 let err = ErrorInfo {
                     code: "INVALID_PARAMETERS".to_string(),
-                    message: "Missing 'command' parameter".to_string(),
+                    message: e.to_string(),
                 };
                 return ("error".to_string(), None, Some(err));
             }
         };
+        // Kept across restarts: the host may wake from an idle shutdown long after the kernel set it
+        let persisted = match config::AgentConfig::persist_power_schedule(&policy).await {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Power schedule not saved to the config file: {}", e);
+                false
+            }
+        };
+        
+        // The idle clock restarts: enabling the policy never powers off on the next check
+        let mut schedule = self.power_schedule.lock().unwrap();
+        schedule.policy = policy;
+        schedule.rearm(std::time::Instant::now());
+        info!("Power schedule updated: {:?}", schedule.policy);
+        
+        let data = serde_json::json!({
+            "policy": schedule.policy,
+            "persisted": persisted,
+            "idle_secs": schedule.last_idle.map(|idle| idle.as_secs()),
+            "sessions": schedule.last_sessions,
+        });
+        ("success".to_string(), Some(data), None)
+    }
+    
+    /// Periodic idle check: shut the host down, with a warning, once the power schedule allows it
+    async fn check_power_schedule(&self) {
+        let policy = self.power_schedule.lock().unwrap().policy.clone();
+        if !policy.enabled || policy.maintenance {
+            return;
+        }
+        let sessions = match power_schedule::sample_sessions(&self.system_info.os).await {
+            Ok(sessions) => sessions,
+            Err(e) => {
+                // Unknown activity never counts as idle
+                debug!("Idle check skipped: {}", e);
+                return;
+            }
+        };
+        let now = std::time::Instant::now();
+        let (decision, idle) = {
+            let mut schedule = self.power_schedule.lock().unwrap();
+            let idle = schedule.observe(&sessions, now);
+            (power_schedule::decide(&schedule.policy, idle, chrono::Local::now().hour()), idle)
+        };
+        debug!("Idle check: {:?} (idle {}s, {} sessions)", decision, idle.as_secs(), sessions.count);
+        if decision != power_schedule::Decision::Shutdown {
+            return;
+        }
         
-        // Security check - only allow safe commands
-        let safe_commands = ["dir", "ls", "whoami", "hostname", "date", "uptime", "ps", "tasklist", "shutdown"];
-        let is_safe = safe_commands.iter().any(|&safe_cmd| command.starts_with(safe_cmd));
+        info!("Idle for {} minutes, shutting down", idle.as_secs() / 60);
+        self.power_schedule.lock().unwrap().rearm(now);
+        let notice = power::PowerNotice {
+            message: format!("No activity for {} minutes: this machine will shut down (Symbion power schedule)", idle.as_secs() / 60),
+            warn_seconds: policy.warn_seconds,
+        };
+        let (status, _, error) = self.power_transition("shutdown", Some(notice)).await;
+        if let Some(error) = error {
+            error!("Idle shutdown failed ({}): {}", status, error.message);
+        }
+    }
+    
+    /// Execute describe command (detailed capability detection)
+    async fn execute_describe(&self, _cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let elevation = capabilities::ElevationStatus {
+            auto_elevate: self.config.auto_elevate,
+            credentials_stored: self.config.store_credentials,
+        };
+        let report = capabilities::CapabilityReport::new(capabilities::CapabilityDetector::detect_all().await, elevation);
+        info!("Described {}/{} capabilities", report.available_count, report.capabilities.len());
         
-        if !is_safe {
+        match serde_json::to_value(&report) {
+            Ok(data) => ("success".to_string(), Some(data), None),
+            Err(e) => {
+                let err = ErrorInfo {
+                    code: "DESCRIBE_ERROR".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
+            }
+        }
+    }
+    
+    /// Execute set cron command (install or replace a recurring local task)
+    async fn execute_set_cron(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let task = match cmd.parameters.clone().map(serde_json::from_value::<cron::CronTask>) {
+            Some(Ok(task)) => task,
+            _ => {
+                let err = ErrorInfo {
+                    code: "INVALID_PARAMETERS".to_string(),
+                    message: "Expected 'id', 'schedule' and 'command' parameters".to_string(),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
+        if let Err(e) = task.validate(&self.config.allowed_commands) {
             let err = ErrorInfo {
-                code: "UNSAFE_COMMAND".to_string(),
-                message: format!("Command not allowed: {}", command),
+                code: "INVALID_TASK".to_string(),
+                message: e.to_string(),
             };
             return ("error".to_string(), None, Some(err));
         }
         
-        match self.system_info.os.as_str() {
-            "windows" => {
-                match tokio::process::Command::new("cmd")
-                    .args(&["/C", command])
-                    .output()
-                    .await
-                {
-                    Ok(output) => {
-                        let stdout = String::from_utf8_lossy(&output.stdout);
-                        let stderr = String::from_utf8_lossy(&output.stderr);
-                        
-                        if output.status.success() {
-                            info!("Shell command executed successfully");
-                            ("success".to_string(), Some(serde_json::json!({
-                                "stdout": stdout,
-                                "stderr": stderr,
-                                "exit_code": output.status.code()
-                            })), None)
-                        } else {
-                            error!("Shell command failed: {}", stderr);
-                            let err = ErrorInfo {
-                                code: "COMMAND_FAILED".to_string(),
-                                message: format!("Command failed with exit code: {:?}", output.status.code()),
-                            };
-                            ("error".to_string(), Some(serde_json::json!({
-                                "stdout": stdout,
-                                "stderr": stderr,
-                                "exit_code": output.status.code()
-                            })), Some(err))
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to execute shell command: {}", e);
-                        let err = ErrorInfo {
-                            code: "EXECUTION_ERROR".to_string(),
-                            message: format!("Failed to execute command: {}", e),
-                        };
-                        ("error".to_string(), None, Some(err))
-                    }
-                }
-            }
-            "linux" => {
-                match tokio::process::Command::new("sh")
-                    .args(&["-c", command])
-                    .output()
-                    .await
-                {
-                    Ok(output) => {
-                        let stdout = String::from_utf8_lossy(&output.stdout);
-                        let stderr = String::from_utf8_lossy(&output.stderr);
-                        
-                        if output.status.success() {
-                            info!("Shell command executed successfully");
-                            ("success".to_string(), Some(serde_json::json!({
-                                "stdout": stdout,
-                                "stderr": stderr,
-                                "exit_code": output.status.code()
-                            })), None)
-                        } else {
-                            error!("Shell command failed: {}", stderr);
-                            let err = ErrorInfo {
-                                code: "COMMAND_FAILED".to_string(),
-                                message: format!("Command failed with exit code: {:?}", output.status.code()),
-                            };
-                            ("error".to_string(), Some(serde_json::json!({
-                                "stdout": stdout,
-                                "stderr": stderr,
-                                "exit_code": output.status.code()
-                            })), Some(err))
-                        }
-                    }
-                    Err(e) => {
-                        error!("Failed to execute shell command: {}", e);
-                        let err = ErrorInfo {
-                            code: "EXECUTION_ERROR".to_string(),
-                            message: format!("Failed to execute command: {}", e),
-                        };
-                        ("error".to_string(), None, Some(err))
-                    }
-                }
+        info!("Installing scheduled task {} ({}): {}", task.id, task.schedule, task.command);
+        
+        let installed = cron::install(&self.system_info.os, &task, &self.config.allowed_commands).await;
+        self.scheduled_tasks.invalidate();
+        match installed {
+            Ok(()) => ("success".to_string(), Some(serde_json::json!({ "task": task })), None),
+            Err(e) => {
+                error!("Failed to install scheduled task {}: {}", task.id, e);
+                let err = ErrorInfo {
+                    code: "CRON_ERROR".to_string(),
+                    message: e.to_string(),
+                };
+                ("error".to_string(), None, Some(err))
             }
-            _ => {
+        }
+    }
+    
+    /// Execute list cron command
+    async fn execute_list_cron(&self, _cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        match cron::list(&self.system_info.os).await {
+            Ok(tasks) => ("success".to_string(), Some(serde_json::json!({ "tasks": tasks })), None),
+            Err(e) => {
+                error!("Failed to list scheduled tasks: {}", e);
                 let err = ErrorInfo {
-                    code: "UNSUPPORTED_OS".to_string(),
-                    message: format!("Shell commands not supported on OS: {}", self.system_info.os),
+                    code: "CRON_ERROR".to_string(),
+                    message: e.to_string(),
                 };
                 ("error".to_string(), None, Some(err))
             }
         }
     }
     
-    /// Execute get metrics command
-    async fn execute_get_metrics(&self, _cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
-        info!("Collecting system metrics...");
+    /// Execute remove cron command
+    async fn execute_remove_cron(&self, cmd: &IncomingCommand) -> (String, Option<serde_json::Value>, Option<ErrorInfo>) {
+        let id = match cmd.parameters.as_ref().and_then(|p| p.get("id")).and_then(|v| v.as_str()) {
+            Some(id) => id,
+            None => {
+                let err = ErrorInfo {
+                    code: "INVALID_PARAMETERS".to_string(),
+                    message: "Missing 'id' parameter".to_string(),
+                };
+                return ("error".to_string(), None, Some(err));
+            }
+        };
         
-        match metrics::SystemMetrics::collect().await {
-            Ok(system_metrics) => {
-                let process_info = metrics::ProcessInfo::collect().await.ok();
-                let services = metrics::ServiceStatus::collect_critical().await.ok();
-                
-                let metrics_data = serde_json::json!({
-                    "system": system_metrics,
-                    "processes": process_info,
-                    "services": services,
-                    "timestamp": Utc::now()
-                });
-                
-                ("success".to_string(), Some(metrics_data), None)
+        let removed = cron::remove(&self.system_info.os, id).await;
+        self.scheduled_tasks.invalidate();
+        match removed {
+            Ok(true) => {
+                info!("Removed scheduled task {}", id);
+                ("success".to_string(), Some(serde_json::json!({ "removed": id })), None)
+            }
+            Ok(false) => {
+                let err = ErrorInfo {
+                    code: "TASK_NOT_FOUND".to_string(),
+                    message: format!("No scheduled task with id {}", id),
+                };
+                ("error".to_string(), None, Some(err))
             }
             Err(e) => {
-                error!("Failed to collect metrics: {}", e);
+                error!("Failed to remove scheduled task {}: {}", id, e);
                 let err = ErrorInfo {
-                    code: "METRICS_ERROR".to_string(),
-                    message: format!("Failed to collect metrics: {}", e),
+                    code: "CRON_ERROR".to_string(),
+                    message: e.to_string(),
                 };
                 ("error".to_string(), None, Some(err))
             }
@@ -867,6 +1974,8 @@ impl Agent {
     fn get_capabilities(&self) -> Vec<String> {
         let mut capabilities = vec![
             "system_metrics".to_string(),
+            "wol_relay".to_string(),
+            "file_read".to_string(),
         ];
         
         // Add OS-specific capabilities
@@ -877,6 +1986,8 @@ impl Agent {
                     "process_control".to_string(),
                     "command_execution".to_string(),
                     "service_management".to_string(),
+                    "scheduled_tasks".to_string(),
+                    "env_management".to_string(),
                 ]);
             }
             "windows" => {
@@ -885,6 +1996,8 @@ impl Agent {
                     "process_control".to_string(),
                     "command_execution".to_string(),
                     "service_management".to_string(),
+                    "scheduled_tasks".to_string(),
+                    "env_management".to_string(),
                 ]);
             }
             "android" => {
@@ -898,6 +2011,13 @@ impl Agent {
             }
         }
         
+        // Opt-in: only advertised where a backend exists
+        if self.config.firewall.enable_firewall_mgmt
+            && firewall::backend_for(&self.system_info.os, self.config.firewall.linux_backend).is_ok()
+        {
+            capabilities.push("firewall_management".to_string());
+        }
+        
         capabilities
     }
 }
@@ -975,10 +2095,10 @@ async fn main() -> Result<()> {
     }
     
     // Create and run agent
-    let mut agent = Agent::new_with_config(agent_config).await
+    let (agent, command_receiver) = Agent::new_with_config(agent_config).await
         .context("Failed to create agent")?;
         
-    agent.run().await
+    Arc::new(agent).run(command_receiver).await
         .context("Agent execution failed")?;
         
     Ok(())
@@ -995,4 +2115,129 @@ mod tests {
         assert!(!system_info.hostname.is_empty());
         assert!(!system_info.network.interfaces.is_empty());
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_announce_delay_is_bounded_and_stable() {
+        let ids = ["a1b2c3d4e5f6", "000000000001", "000000000002", "ffffffffffff"];
+        for id in ids {
+            assert!(announce_delay(id) < Duration::from_millis(ANNOUNCE_MAX_DELAY_MS));
+            assert_eq!(announce_delay(id), announce_delay(id));
+        }
+        // Agents spread out instead of answering together
+        assert_ne!(announce_delay("000000000001"), announce_delay("000000000002"));
+    }
+
+    /// Agent wired to no broker: published messages stay in its outbound queue
+    async fn offline_agent() -> Arc<Agent> {
+        Arc::new(Agent {
+            config: AgentConfig::default(),
+            system_info: SystemInfo::discover().await.unwrap(),
+            outbound: Arc::new(OutboundQueue::new(outbound::OUTBOUND_QUEUE_CAPACITY)),
+            recent_commands: Mutex::new(RecentCommands::default()),
+            seen_commands: Mutex::new(SeenCommands::default()),
+            scheduler: scheduler::CommandScheduler::new(1),
+            heartbeat_sections: Mutex::new(heartbeat::HeartbeatSections::default()),
+            network_collector: metrics::NetworkCollector::new(),
+            metrics_network_collector: metrics::NetworkCollector::new(),
+            connection: Arc::new(connection::ConnectionStatus::default()),
+            power_schedule: Mutex::new(power_schedule::PowerSchedule::new(config::PowerScheduleConfig::default(), std::time::Instant::now())),
+            scheduled_tasks: cron::TaskCache::default(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_announce_re_registers_after_the_delay() {
+        let agent = offline_agent().await;
+        let answer = agent.answer_announce(Duration::from_millis(200));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(agent.outbound.pop().is_none(), "registration must wait for the announce delay");
+
+        answer.await.unwrap();
+        let message = agent.outbound.pop().expect("registration queued");
+        assert_eq!(message.topic, symbion_topics::agents_registration());
+        assert_eq!(message.priority, Priority::Registration);
+        let registration: serde_json::Value = serde_json::from_str(&message.payload).unwrap();
+        assert_eq!(registration["agent_id"], agent.system_info.agent_id.as_str());
+        assert!(agent.outbound.pop().is_none());
+    }
+    
+    #[test]
+    fn test_seen_commands_drops_duplicates() {
+        let mut seen = SeenCommands::default();
+        assert!(seen.first_seen("cmd-1"));
+        assert!(!seen.first_seen("cmd-1"));
+        assert!(seen.first_seen("cmd-2"));
+        
+        // Bounded: the oldest id is forgotten once capacity is exceeded
+        for i in 0..SEEN_COMMANDS_CAPACITY {
+            assert!(seen.first_seen(&format!("filler-{}", i)));
+        }
+        assert_eq!(seen.ids.len(), SEEN_COMMANDS_CAPACITY);
+        assert!(seen.first_seen("cmd-1"));
+    }
+    
+    #[tokio::test]
+    async fn test_heartbeat_honors_kernel_sections() {
+        let build = |sections| async move {
+            let mut system = metrics::SystemMetrics::collect(&metrics::NetworkCollector::new()).await.unwrap();
+            system.temperature = Some(metrics::TemperatureMetrics { cpu_celsius: Some(65.5), sensors: vec![] });
+            let heartbeat = HeartbeatMessage {
+                agent_id: "a1b2c3d4e5f6".to_string(),
+                status: "online".to_string(),
+                system,
+                processes: Some(metrics::ProcessInfo { total_count: 245, running_count: 12, top_cpu: vec![], top_memory: vec![] }),
+                services: Some(vec![]),
+                os_details: discovery::OsDetails::default(),
+                network: None,
+                last_command: None,
+                recent_commands: vec![],
+                scheduled_tasks: None,
+                queue_depth: 0,
+                busy: false,
+                idle_secs: None,
+                timestamp: Utc::now(),
+            }.retain_sections(sections);
+            serde_json::to_value(&heartbeat).unwrap()
+        };
+        
+        // Default: heavy sections left out of the payload
+        let light = build(heartbeat::HeartbeatSections::default()).await;
+        assert!(light.get("processes").is_none());
+        assert!(light.get("services").is_none());
+        assert!(light["system"]["temperature"].is_null());
+        assert!(light["system"]["cpu"].is_object());
+        
+        let requested = build(heartbeat::HeartbeatSections { processes: true, services: false, temperatures: true }).await;
+        assert_eq!(requested["processes"]["total_count"], 245);
+        assert!(requested.get("services").is_none());
+        assert_eq!(requested["system"]["temperature"]["cpu_celsius"], 65.5);
+    }
+    
+    #[test]
+    fn test_recent_commands_keep_newest_first_with_eviction() {
+        let info = |i: usize| CommandInfo {
+            command_id: format!("cmd-{}", i),
+            command_type: "get_metrics".to_string(),
+            status: "success".to_string(),
+            timestamp: Utc::now(),
+        };
+        let ids = |recent: &RecentCommands| recent.snapshot().into_iter().map(|c| c.command_id).collect::<Vec<_>>();
+        
+        let mut recent = RecentCommands::default();
+        assert!(recent.last().is_none());
+        recent.record(info(0));
+        recent.record(info(1));
+        assert_eq!(ids(&recent), ["cmd-1", "cmd-0"]);
+        assert_eq!(recent.last().unwrap().command_id, "cmd-1");
+        
+        // Full: the oldest commands are dropped
+        for i in 2..RECENT_COMMANDS_CAPACITY + 2 {
+            recent.record(info(i));
+        }
+        let kept = ids(&recent);
+        assert_eq!(kept.len(), RECENT_COMMANDS_CAPACITY);
+        assert_eq!(kept.first().unwrap(), &format!("cmd-{}", RECENT_COMMANDS_CAPACITY + 1));
+        assert_eq!(kept.last().unwrap(), "cmd-2");
+    }
+}
@@ -4,14 +4,21 @@
 //! - CPU usage and load averages
 //! - Memory usage statistics  
 //! - Disk usage for mounted filesystems
-//! - Network interface statistics (placeholder)
+//! - Network interface traffic since the previous sample (see `NetworkCollector`)
+//! - Temperature sensors from Linux hwmon, CPU package temperature
+//! - GPU utilization, memory, temperature and power (NVIDIA/AMD, see `gpu`)
 //! - Process information and top consumers
 //! - System service status (placeholder)
 
 use anyhow::Result;
 use serde::Serialize;
-use sysinfo::{System, ProcessStatus};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+use sysinfo::{Networks, System, ProcessStatus};
 use tracing::debug;
+use crate::gpu::{self, GpuMetrics};
 
 /// Complete system metrics (matches agents.heartbeat@v1 schema)
 #[derive(Debug, Serialize)]
@@ -22,6 +29,9 @@ pub struct SystemMetrics {
     pub disk: Vec<DiskMetrics>,
     pub network: Option<NetworkMetrics>,
     pub temperature: Option<TemperatureMetrics>,
+    /// Absent on hosts without a supported GPU
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpu: Option<Vec<GpuMetrics>>,
 }
 
 /// CPU usage metrics
@@ -51,13 +61,15 @@ pub struct DiskMetrics {
     pub percent_used: f32,
 }
 
-/// Network interface statistics (placeholder)
+/// Network interface statistics over the last sampling interval
 #[derive(Debug, Serialize)]
 pub struct NetworkMetrics {
+    /// Seconds covered by the counters (divide to get per-second rates)
+    pub interval_secs: f64,
     pub interfaces: Vec<NetworkInterfaceStats>,
 }
 
-/// Per-interface network statistics
+/// Per-interface traffic since the previous sample
 #[derive(Debug, Serialize)]
 pub struct NetworkInterfaceStats {
     pub name: String,
@@ -68,7 +80,7 @@ pub struct NetworkInterfaceStats {
     pub is_up: bool,
 }
 
-/// Temperature sensor readings (placeholder)
+/// Temperature sensor readings (Linux hwmon)
 #[derive(Debug, Serialize)]
 pub struct TemperatureMetrics {
     pub cpu_celsius: Option<f32>,
@@ -123,7 +135,7 @@ pub enum ServiceState {
 
 impl SystemMetrics {
     /// Collect complete system metrics
-    pub async fn collect() -> Result<Self> {
+    pub async fn collect(network: &NetworkCollector) -> Result<Self> {
         debug!("Collecting system metrics...");
         
         let mut sys = System::new_all();
@@ -138,8 +150,13 @@ impl SystemMetrics {
         let cpu = CpuMetrics::collect(&sys)?;
         let memory = MemoryMetrics::collect(&sys)?;
         let disk = DiskMetrics::collect(&sys)?;
-        let network = None; // Placeholder - will implement later
-        let temperature = None; // Placeholder - will implement later
+        let network = Some(network.collect());
+        let temperature = TemperatureMetrics::collect().unwrap_or_else(|e| {
+            debug!("Temperature sensors unavailable: {}", e);
+            None
+        });
+        let gpus = gpu::collect().await;
+        let gpu = (!gpus.is_empty()).then_some(gpus);
         
         Ok(SystemMetrics {
             uptime_seconds,
@@ -148,10 +165,186 @@ impl SystemMetrics {
             disk,
             network,
             temperature,
+            gpu,
         })
     }
 }
 
+/// Cumulative counters of one interface
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct InterfaceCounters {
+    bytes_sent: u64,
+    bytes_recv: u64,
+    packets_sent: u64,
+    packets_recv: u64,
+}
+
+impl InterfaceCounters {
+    /// Traffic since `previous`; a counter reset (interface re-created) counts from zero
+    fn since(self, previous: Self) -> Self {
+        let delta = |current: u64, before: u64| if current >= before { current - before } else { current };
+        Self {
+            bytes_sent: delta(self.bytes_sent, previous.bytes_sent),
+            bytes_recv: delta(self.bytes_recv, previous.bytes_recv),
+            packets_sent: delta(self.packets_sent, previous.packets_sent),
+            packets_recv: delta(self.packets_recv, previous.packets_recv),
+        }
+    }
+}
+
+struct NetworkSnapshot {
+    taken_at: Instant,
+    counters: HashMap<String, InterfaceCounters>,
+}
+
+/// Turns cumulative interface counters into per-interval traffic.
+///
+/// Each `collect` reports the traffic since the previous one (or since the
+/// collector was created), so each consumer (heartbeat, `get_metrics`) keeps its
+/// own instance for the agent's lifetime.
+pub struct NetworkCollector {
+    previous: Mutex<NetworkSnapshot>,
+}
+
+impl NetworkCollector {
+    pub fn new() -> Self {
+        Self { previous: Mutex::new(NetworkSnapshot { taken_at: Instant::now(), counters: Self::read_counters() }) }
+    }
+
+    fn read_counters() -> HashMap<String, InterfaceCounters> {
+        Networks::new_with_refreshed_list().list().iter()
+            .map(|(name, data)| (name.clone(), InterfaceCounters {
+                bytes_sent: data.total_transmitted(),
+                bytes_recv: data.total_received(),
+                packets_sent: data.total_packets_transmitted(),
+                packets_recv: data.total_packets_received(),
+            }))
+            .collect()
+    }
+
+    /// Traffic per interface since the previous call
+    pub fn collect(&self) -> NetworkMetrics {
+        let counters = Self::read_counters();
+        let now = Instant::now();
+        let mut previous = self.previous.lock().unwrap();
+        let interval_secs = now.duration_since(previous.taken_at).as_secs_f64();
+
+        let mut interfaces: Vec<NetworkInterfaceStats> = counters.iter()
+            .map(|(name, current)| {
+                // Interface that just appeared: no baseline, no traffic reported yet
+                let delta = current.since(previous.counters.get(name).copied().unwrap_or(*current));
+                NetworkInterfaceStats {
+                    name: name.clone(),
+                    bytes_sent: delta.bytes_sent,
+                    bytes_recv: delta.bytes_recv,
+                    packets_sent: delta.packets_sent,
+                    packets_recv: delta.packets_recv,
+                    is_up: interface_is_up(name, current),
+                }
+            })
+            .collect();
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+
+        *previous = NetworkSnapshot { taken_at: now, counters };
+        NetworkMetrics { interval_secs, interfaces }
+    }
+}
+
+impl Default for NetworkCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Administrative state from the IFF_UP flag on Linux; elsewhere, whether the interface ever carried traffic
+fn interface_is_up(name: &str, counters: &InterfaceCounters) -> bool {
+    if cfg!(target_os = "linux") {
+        if let Ok(flags) = std::fs::read_to_string(format!("/sys/class/net/{}/flags", name)) {
+            return u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).is_ok_and(|f| f & 0x1 != 0);
+        }
+    }
+    counters.packets_sent > 0 || counters.packets_recv > 0
+}
+
+/// Processes kept in each top list (by CPU, by memory)
+const TOP_PROCESSES: usize = 15;
+
+/// Linux hardware monitoring class (one `hwmonN` directory per sensor chip)
+const HWMON_ROOT: &str = "/sys/class/hwmon";
+
+/// hwmon drivers of Intel and AMD CPUs
+const CPU_HWMON_DRIVERS: &[&str] = &["coretemp", "k10temp"];
+
+/// Labels of the package-level CPU sensor, by preference (Tdie is Tctl without the fan-curve offset)
+const CPU_PACKAGE_LABELS: &[&str] = &["Package id", "Tdie", "Tctl"];
+
+impl TemperatureMetrics {
+    /// Sensor readings, `None` when the host exposes none (and on non-Linux targets)
+    pub fn collect() -> Result<Option<Self>> {
+        if !cfg!(target_os = "linux") {
+            return Ok(None);
+        }
+        Self::read_hwmon(Path::new(HWMON_ROOT))
+    }
+
+    /// Read every `temp*_input` under an hwmon root (millidegrees Celsius)
+    fn read_hwmon(root: &Path) -> Result<Option<Self>> {
+        let mut chips: Vec<std::path::PathBuf> = match std::fs::read_dir(root) {
+            Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        chips.sort();
+
+        let mut sensors = Vec::new();
+        let mut cpu_celsius: Option<(usize, f32)> = None;
+        for chip in &chips {
+            let driver = read_trimmed(&chip.join("name")).unwrap_or_else(|| "hwmon".to_string());
+            let Ok(entries) = std::fs::read_dir(chip) else { continue };
+            let mut inputs: Vec<(u32, String)> = entries.flatten()
+                .filter_map(|entry| {
+                    let file = entry.file_name().into_string().ok()?;
+                    let index = file.strip_prefix("temp")?.strip_suffix("_input")?.parse().ok()?;
+                    Some((index, file))
+                })
+                .collect();
+            inputs.sort();
+
+            for (index, input) in inputs {
+                let Some(millidegrees) = read_trimmed(&chip.join(&input)).and_then(|v| v.parse::<f64>().ok()) else { continue };
+                let label = read_trimmed(&chip.join(format!("temp{}_label", index)));
+                let value = (millidegrees / 1000.0) as f32;
+                if CPU_HWMON_DRIVERS.contains(&driver.as_str()) {
+                    // Package sensor when labelled, otherwise the chip's first sensor
+                    let rank = label.as_deref()
+                        .and_then(|l| CPU_PACKAGE_LABELS.iter().position(|p| l.starts_with(p)))
+                        .unwrap_or(CPU_PACKAGE_LABELS.len());
+                    if cpu_celsius.is_none_or(|(best, _)| rank < best) {
+                        cpu_celsius = Some((rank, value));
+                    }
+                }
+                sensors.push(TemperatureSensor {
+                    name: format!("{}/{}", driver, label.unwrap_or_else(|| format!("temp{}", index))),
+                    value,
+                    unit: "°C".to_string(),
+                    critical: read_trimmed(&chip.join(format!("temp{}_crit", index)))
+                        .and_then(|v| v.parse::<f64>().ok())
+                        .map(|v| (v / 1000.0) as f32),
+                });
+            }
+        }
+
+        if sensors.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(TemperatureMetrics { cpu_celsius: cpu_celsius.map(|(_, value)| value), sensors }))
+    }
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok().map(|content| content.trim().to_string())
+}
+
 impl CpuMetrics {
     fn collect(sys: &System) -> Result<Self> {
         let cpus = sys.cpus();
@@ -272,11 +465,11 @@ impl ProcessInfo {
             .filter(|p| matches!(p.status(), ProcessStatus::Run))
             .count();
         
-        // Sort by CPU usage (top 15)
+        // Sort by CPU usage
         let mut cpu_sorted = processes.clone();
         cpu_sorted.sort_by(|a, b| b.cpu_usage().partial_cmp(&a.cpu_usage()).unwrap_or(std::cmp::Ordering::Equal));
         let top_cpu = cpu_sorted.into_iter()
-            .take(15)
+            .take(TOP_PROCESSES)
             .map(|p| ProcessEntry {
                 pid: p.pid().as_u32(),
                 name: p.name().to_string(),
@@ -286,11 +479,11 @@ impl ProcessInfo {
             })
             .collect();
         
-        // Sort by memory usage (top 15)  
+        // Sort by memory usage
         let mut mem_sorted = processes;
         mem_sorted.sort_by(|a, b| b.memory().cmp(&a.memory()));
         let top_memory = mem_sorted.into_iter()
-            .take(15)
+            .take(TOP_PROCESSES)
             .map(|p| ProcessEntry {
                 pid: p.pid().as_u32(),
                 name: p.name().to_string(),
@@ -339,18 +532,97 @@ mod tests {
     
     #[tokio::test]
     async fn test_metrics_collection() {
-        let metrics = SystemMetrics::collect().await.unwrap();
+        let metrics = SystemMetrics::collect(&NetworkCollector::new()).await.unwrap();
         assert!(metrics.uptime_seconds > 0);
         assert!(metrics.cpu.core_count > 0);
         assert!(metrics.memory.total_mb > 0);
         assert!(!metrics.disk.is_empty());
+        assert!(metrics.network.is_some());
+    }
+
+    #[test]
+    fn test_network_reports_loopback_traffic_since_previous_sample() {
+        let collector = NetworkCollector::new();
+        let loopback = |metrics: &NetworkMetrics| metrics.interfaces.iter()
+            .any(|i| i.name == "lo" || i.name.to_lowercase().contains("loopback"));
+        let first = collector.collect();
+        assert!(loopback(&first), "no loopback in {:?}", first.interfaces);
+
+        let second = collector.collect();
+        assert!(loopback(&second));
+        assert!(second.interval_secs < 5.0);
+        if cfg!(target_os = "linux") {
+            assert!(second.interfaces.iter().any(|i| i.name == "lo" && i.is_up));
+        }
+    }
+
+    /// Fake `/sys/class/hwmon` with a k10temp CPU, an NVMe drive and a chip without sensors
+    fn hwmon_fixture() -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("symbion-hwmon-{}", uuid::Uuid::new_v4()));
+        let write = |chip: &str, file: &str, content: &str| {
+            std::fs::create_dir_all(root.join(chip)).unwrap();
+            std::fs::write(root.join(chip).join(file), content).unwrap();
+        };
+        write("hwmon0", "name", "nvme\n");
+        write("hwmon0", "temp1_input", "38850\n");
+        write("hwmon0", "temp1_label", "Composite\n");
+        write("hwmon0", "temp1_crit", "84850\n");
+        write("hwmon1", "name", "k10temp\n");
+        write("hwmon1", "temp1_input", "71250\n");
+        write("hwmon1", "temp1_label", "Tctl\n");
+        write("hwmon1", "temp2_input", "61250\n");
+        write("hwmon1", "temp2_label", "Tdie\n");
+        write("hwmon1", "temp3_input", "54000\n");
+        write("hwmon1", "temp3_label", "Tccd1\n");
+        write("hwmon2", "name", "acpi_fan\n");
+        write("hwmon2", "fan1_input", "1200\n");
+        root
+    }
+
+    #[test]
+    fn test_hwmon_sensors_and_cpu_package_temperature() {
+        let root = hwmon_fixture();
+        let metrics = TemperatureMetrics::read_hwmon(&root).unwrap().unwrap();
+        let names: Vec<&str> = metrics.sensors.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["nvme/Composite", "k10temp/Tctl", "k10temp/Tdie", "k10temp/Tccd1"]);
+        // Tdie preferred over the offset Tctl
+        assert_eq!(metrics.cpu_celsius, Some(61.25));
+        assert_eq!(metrics.sensors[0].value, 38.85);
+        assert_eq!(metrics.sensors[0].critical, Some(84.85));
+        assert_eq!(metrics.sensors[1].critical, None);
+        assert_eq!(metrics.sensors[0].unit, "°C");
+
+        // Intel: the package sensor, not the first core
+        let coretemp = root.join("hwmon3");
+        std::fs::create_dir_all(&coretemp).unwrap();
+        std::fs::write(coretemp.join("name"), "coretemp\n").unwrap();
+        std::fs::write(coretemp.join("temp2_input"), "49000\n").unwrap();
+        std::fs::write(coretemp.join("temp2_label"), "Core 0\n").unwrap();
+        std::fs::write(coretemp.join("temp1_input"), "52000\n").unwrap();
+        std::fs::write(coretemp.join("temp1_label"), "Package id 0\n").unwrap();
+        std::fs::remove_dir_all(root.join("hwmon1")).unwrap();
+        let metrics = TemperatureMetrics::read_hwmon(&root).unwrap().unwrap();
+        assert_eq!(metrics.cpu_celsius, Some(52.0));
+        std::fs::remove_dir_all(&root).unwrap();
+
+        // No hwmon at all
+        assert!(TemperatureMetrics::read_hwmon(&root).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_counters_become_deltas() {
+        let counters = |bytes: u64, packets: u64| InterfaceCounters { bytes_sent: bytes, bytes_recv: bytes * 2, packets_sent: packets, packets_recv: packets * 2 };
+        assert_eq!(counters(1500, 10).since(counters(1000, 4)), counters(500, 6));
+        assert_eq!(counters(1000, 4).since(counters(1000, 4)), InterfaceCounters::default());
+        // Counters reset (interface re-created): traffic since the reset
+        assert_eq!(counters(300, 2).since(counters(1000, 4)), counters(300, 2));
     }
     
     #[tokio::test] 
     async fn test_process_info() {
         let process_info = ProcessInfo::collect().await.unwrap();
         assert!(process_info.total_count > 0);
-        assert!(process_info.top_cpu.len() <= 5);
-        assert!(process_info.top_memory.len() <= 5);
+        assert!(process_info.top_cpu.len() <= TOP_PROCESSES);
+        assert!(process_info.top_memory.len() <= TOP_PROCESSES);
     }
 }
\ No newline at end of file
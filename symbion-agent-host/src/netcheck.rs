@@ -0,0 +1,295 @@
+//! Network connectivity self-test for Symbion agents
+//!
+//! Backs the `net_check` command:
+//! - Targets are the default gateway, the first system DNS server, the configured
+//!   URL (`net_check.url`) or an explicit `host:port` from `net_check.allowed_targets`
+//! - Probes are TCP connects with a timeout (no raw sockets needed): a refused
+//!   connection still proves the host answered, only timeouts and routing errors
+//!   count as unreachable
+//! - Results are aggregated into a single verdict so the kernel can tell a dead
+//!   uplink from a broken resolver
+
+use crate::config::NetCheckConfig;
+use serde::Serialize;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+/// Port probed on the gateway when nothing else is known (DNS forwarders are common on routers)
+const GATEWAY_PROBE_PORT: u16 = 53;
+
+/// What a target stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetKind {
+    Gateway,
+    Dns,
+    Url,
+    Host,
+}
+
+/// A resolved probe target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub kind: TargetKind,
+    /// `host:port` to connect to
+    pub address: String,
+}
+
+/// Raw outcome of a single probe
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeOutcome {
+    /// Connection accepted
+    Connected(Duration),
+    /// Connection actively refused: the host is up, the port is closed
+    Refused(Duration),
+    /// Timeout, unreachable network, resolution failure...
+    Failed(String),
+}
+
+/// Per-target result returned to the kernel
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeResult {
+    pub kind: TargetKind,
+    pub target: String,
+    pub reachable: bool,
+    pub latency_ms: Option<f64>,
+    pub error: Option<String>,
+}
+
+impl ProbeResult {
+    pub fn new(target: &Target, outcome: ProbeOutcome) -> Self {
+        let (reachable, latency, error) = match outcome {
+            ProbeOutcome::Connected(latency) => (true, Some(latency), None),
+            ProbeOutcome::Refused(latency) => (true, Some(latency), Some("connection refused".to_string())),
+            ProbeOutcome::Failed(error) => (false, None, Some(error)),
+        };
+        Self {
+            kind: target.kind,
+            target: target.address.clone(),
+            reachable,
+            latency_ms: latency.map(|l| l.as_secs_f64() * 1000.0),
+            error,
+        }
+    }
+}
+
+/// Aggregated report (`data` of the `net_check` response)
+#[derive(Debug, Clone, Serialize)]
+pub struct NetCheckReport {
+    /// "ok" (all reachable), "degraded" (some) or "offline" (none)
+    pub status: &'static str,
+    pub reachable: usize,
+    pub total: usize,
+    pub avg_latency_ms: Option<f64>,
+    pub max_latency_ms: Option<f64>,
+    pub results: Vec<ProbeResult>,
+}
+
+/// Aggregate probe results into a report
+pub fn aggregate(results: Vec<ProbeResult>) -> NetCheckReport {
+    let total = results.len();
+    let latencies: Vec<f64> = results.iter().filter_map(|r| r.latency_ms).collect();
+    let reachable = results.iter().filter(|r| r.reachable).count();
+    let status = match reachable {
+        0 => "offline",
+        n if n == total => "ok",
+        _ => "degraded",
+    };
+    let avg_latency_ms = (!latencies.is_empty()).then(|| latencies.iter().sum::<f64>() / latencies.len() as f64);
+    let max_latency_ms = latencies.iter().copied().reduce(f64::max);
+    NetCheckReport { status, reachable, total, avg_latency_ms, max_latency_ms, results }
+}
+
+/// Build the target list from the requested names, enforcing the allow-list.
+///
+/// `gateway`, `dns` and `url` are resolved locally; any other entry must appear
+/// verbatim in `allowed_targets`. No request means every default target.
+pub fn resolve_targets(
+    requested: Option<&[String]>,
+    config: &NetCheckConfig,
+    gateway: Option<IpAddr>,
+    dns: Option<IpAddr>,
+) -> Result<Vec<Target>, String> {
+    let defaults = ["gateway".to_string(), "dns".to_string(), "url".to_string()];
+    let explicit = requested.is_some();
+    let names = requested.unwrap_or(&defaults);
+    if names.len() > config.max_targets {
+        return Err(format!("At most {} targets per check", config.max_targets));
+    }
+
+    let mut targets = Vec::new();
+    for name in names {
+        let target = match name.as_str() {
+            "gateway" => gateway.map(|ip| Target {
+                kind: TargetKind::Gateway,
+                address: SocketAddr::new(ip, GATEWAY_PROBE_PORT).to_string(),
+            }),
+            "dns" => dns.map(|ip| Target {
+                kind: TargetKind::Dns,
+                address: SocketAddr::new(ip, 53).to_string(),
+            }),
+            "url" => match &config.url {
+                Some(url) => Some(Target { kind: TargetKind::Url, address: url_address(url)? }),
+                None if explicit => return Err("No net_check.url configured".to_string()),
+                None => continue,
+            },
+            other if config.allowed_targets.iter().any(|allowed| allowed == other) => {
+                Some(Target { kind: TargetKind::Host, address: other.to_string() })
+            }
+            other => return Err(format!("Target '{}' is not in net_check.allowed_targets", other)),
+        };
+        // Undetectable gateway/DNS: keep it in the report as a failure
+        targets.push(target.unwrap_or_else(|| Target {
+            kind: if name == "gateway" { TargetKind::Gateway } else { TargetKind::Dns },
+            address: String::new(),
+        }));
+    }
+    Ok(targets)
+}
+
+/// `host:port` of an http(s) URL (default port from the scheme)
+pub fn url_address(url: &str) -> Result<String, String> {
+    let (scheme, rest) = url.split_once("://").ok_or_else(|| format!("Invalid URL '{}'", url))?;
+    let default_port = match scheme {
+        "http" => 80,
+        "https" => 443,
+        _ => return Err(format!("Unsupported URL scheme '{}'", scheme)),
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    if host_port.is_empty() {
+        return Err(format!("Invalid URL '{}'", url));
+    }
+    // Explicit port unless the host is a bare IPv6 literal
+    let has_port = host_port.rsplit_once(':').is_some_and(|(host, port)| {
+        port.parse::<u16>().is_ok() && (!host.contains(':') || host.ends_with(']'))
+    });
+    Ok(if has_port { host_port.to_string() } else { format!("{}:{}", host_port, default_port) })
+}
+
+/// Probe one target with a TCP connect
+pub async fn probe(target: &Target, timeout: Duration) -> ProbeOutcome {
+    if target.address.is_empty() {
+        return ProbeOutcome::Failed("not detected on this host".to_string());
+    }
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(&target.address)).await {
+        Ok(Ok(_)) => ProbeOutcome::Connected(start.elapsed()),
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => ProbeOutcome::Refused(start.elapsed()),
+        Ok(Err(e)) => ProbeOutcome::Failed(e.to_string()),
+        Err(_) => ProbeOutcome::Failed(format!("timed out after {}ms", timeout.as_millis())),
+    }
+}
+
+/// Probe all targets concurrently and aggregate
+pub async fn run(targets: &[Target], timeout: Duration) -> NetCheckReport {
+    let outcomes = futures::future::join_all(targets.iter().map(|t| probe(t, timeout))).await;
+    aggregate(targets.iter().zip(outcomes).map(|(t, o)| ProbeResult::new(t, o)).collect())
+}
+
+/// Default IPv4 gateway from `/proc/net/route` content
+pub fn parse_default_gateway(route_table: &str) -> Option<IpAddr> {
+    route_table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(1) != Some(&"00000000") {
+            return None;
+        }
+        // Little-endian hex
+        let raw = u32::from_str_radix(fields.get(2)?, 16).ok()?;
+        (raw != 0).then(|| IpAddr::V4(Ipv4Addr::from(raw.swap_bytes())))
+    })
+}
+
+/// First nameserver from `resolv.conf` content
+pub fn parse_nameserver(resolv_conf: &str) -> Option<IpAddr> {
+    resolv_conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .find_map(|addr| addr.trim().parse().ok())
+}
+
+/// Default gateway of this host (Linux/Android only)
+pub fn default_gateway() -> Option<IpAddr> {
+    std::fs::read_to_string("/proc/net/route").ok().as_deref().and_then(parse_default_gateway)
+}
+
+/// System DNS server (Unix only)
+pub fn system_nameserver() -> Option<IpAddr> {
+    std::fs::read_to_string("/etc/resolv.conf").ok().as_deref().and_then(parse_nameserver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(kind: TargetKind, address: &str) -> Target {
+        Target { kind, address: address.to_string() }
+    }
+
+    #[test]
+    fn test_aggregate_mixed_outcomes() {
+        let results = vec![
+            ProbeResult::new(&target(TargetKind::Gateway, "192.168.1.1:53"), ProbeOutcome::Connected(Duration::from_millis(2))),
+            ProbeResult::new(&target(TargetKind::Dns, "1.1.1.1:53"), ProbeOutcome::Refused(Duration::from_millis(10))),
+            ProbeResult::new(&target(TargetKind::Url, "example.org:443"), ProbeOutcome::Failed("timed out after 2000ms".to_string())),
+        ];
+        let report = aggregate(results);
+        assert_eq!(report.status, "degraded");
+        assert_eq!((report.reachable, report.total), (2, 3));
+        assert_eq!(report.avg_latency_ms, Some(6.0));
+        assert_eq!(report.max_latency_ms, Some(10.0));
+        assert_eq!(report.results[1].error.as_deref(), Some("connection refused"));
+        assert!(report.results[2].latency_ms.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_verdicts() {
+        let up = ProbeResult::new(&target(TargetKind::Host, "nas:22"), ProbeOutcome::Connected(Duration::from_millis(1)));
+        let down = ProbeResult::new(&target(TargetKind::Gateway, ""), ProbeOutcome::Failed("not detected".to_string()));
+        assert_eq!(aggregate(vec![up.clone(), up]).status, "ok");
+        let offline = aggregate(vec![down]);
+        assert_eq!(offline.status, "offline");
+        assert_eq!(offline.avg_latency_ms, None);
+        assert_eq!(aggregate(Vec::new()).status, "offline");
+    }
+
+    #[test]
+    fn test_resolve_targets_enforces_allowlist() {
+        let config = NetCheckConfig {
+            allowed_targets: vec!["nas.local:445".to_string()],
+            url: Some("https://kernel.local/health".to_string()),
+            ..NetCheckConfig::default()
+        };
+        let gateway = Some("192.168.1.1".parse().unwrap());
+
+        let defaults = resolve_targets(None, &config, gateway, None).unwrap();
+        assert_eq!(defaults, vec![
+            target(TargetKind::Gateway, "192.168.1.1:53"),
+            target(TargetKind::Dns, ""),
+            target(TargetKind::Url, "kernel.local:443"),
+        ]);
+
+        let requested = vec!["nas.local:445".to_string()];
+        assert_eq!(resolve_targets(Some(&requested), &config, None, None).unwrap(), vec![target(TargetKind::Host, "nas.local:445")]);
+        let refused = vec!["evil.example:25".to_string()];
+        assert!(resolve_targets(Some(&refused), &config, None, None).is_err());
+        let too_many = vec!["gateway".to_string(); config.max_targets + 1];
+        assert!(resolve_targets(Some(&too_many), &config, gateway, None).is_err());
+    }
+
+    #[test]
+    fn test_parse_system_sources() {
+        let route = "Iface\tDestination\tGateway \tFlags\n\
+                     eth0\t0001A8C0\t00000000\t0001\n\
+                     eth0\t00000000\t0101A8C0\t0003\n";
+        assert_eq!(parse_default_gateway(route), Some("192.168.1.1".parse().unwrap()));
+        assert_eq!(parse_default_gateway("Iface\tDestination\tGateway\n"), None);
+
+        let resolv = "# generated\nsearch lan\nnameserver 9.9.9.9\nnameserver 1.1.1.1\n";
+        assert_eq!(parse_nameserver(resolv), Some("9.9.9.9".parse().unwrap()));
+
+        assert_eq!(url_address("http://kernel.local:8080/health").unwrap(), "kernel.local:8080");
+        assert_eq!(url_address("https://user@[::1]/x").unwrap(), "[::1]:443");
+        assert!(url_address("ftp://host").is_err());
+    }
+}
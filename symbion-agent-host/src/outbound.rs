@@ -0,0 +1,297 @@
+//! Backpressure-aware outbound MQTT queue
+//!
+//! Sits in front of rumqttc's small request channel so a burst of publishes
+//! never blocks the main loop, and command responses are never starved by
+//! telemetry:
+//! - Priority ordering: responses > registrations > heartbeats (FIFO within a priority)
+//! - Heartbeats are merged: a new heartbeat replaces the one still waiting
+//! - When the queue is full, the oldest lower-priority message is dropped
+//! - Responses are never dropped (they may exceed the soft capacity)
+//! - Saturation is logged once when entering and once when recovering
+//! - Bulk producers (file transfers, streamed output) pace themselves with `wait_for_room`
+
+use rumqttc::{AsyncClient, QoS};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tracing::{debug, error, info, warn};
+
+/// Default number of messages buffered before low-priority messages are dropped
+pub const OUTBOUND_QUEUE_CAPACITY: usize = 64;
+
+/// Message priority, lowest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Heartbeat,
+    Registration,
+    Response,
+}
+
+/// A message waiting to be published
+#[derive(Debug, Clone)]
+pub struct OutboundMessage {
+    pub topic: String,
+    pub payload: String,
+    pub priority: Priority,
+}
+
+impl OutboundMessage {
+    pub fn new(topic: &str, payload: String, priority: Priority) -> Self {
+        Self { topic: topic.to_string(), payload, priority }
+    }
+}
+
+/// What happened to a message handed to the queue
+#[derive(Debug, PartialEq, Eq)]
+pub enum EnqueueOutcome {
+    Queued,
+    /// Replaced a heartbeat that was still waiting
+    MergedHeartbeat,
+    /// Queued after evicting an older lower-priority message
+    EvictedOlder,
+    /// Queue full of higher-priority messages, this one was discarded
+    Dropped,
+}
+
+/// Queue counters for monitoring
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    pub depth: usize,
+    pub dropped: u64,
+    pub merged: u64,
+    pub saturation_events: u64,
+}
+
+#[derive(Default)]
+struct QueueState {
+    messages: VecDeque<OutboundMessage>,
+    saturated: bool,
+    dropped: u64,
+    merged: u64,
+    saturation_events: u64,
+}
+
+/// Bounded priority queue drained by the publisher task
+pub struct OutboundQueue {
+    state: Mutex<QueueState>,
+    notify: Notify,
+    /// Woken whenever a message leaves the queue
+    drained: Notify,
+    capacity: usize,
+}
+
+impl OutboundQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(QueueState::default()),
+            notify: Notify::new(),
+            drained: Notify::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Enqueue a message, applying the merge/drop policy
+    pub fn push(&self, message: OutboundMessage) -> EnqueueOutcome {
+        let mut state = self.state.lock().unwrap();
+        let mut outcome = EnqueueOutcome::Queued;
+
+        if message.priority == Priority::Heartbeat {
+            let before = state.messages.len();
+            state.messages.retain(|m| m.priority != Priority::Heartbeat);
+            if state.messages.len() != before {
+                state.merged += 1;
+                outcome = EnqueueOutcome::MergedHeartbeat;
+            }
+        }
+
+        if state.messages.len() >= self.capacity {
+            // Oldest message among the lowest priority strictly below the new one
+            let victim = state.messages.iter()
+                .enumerate()
+                .filter(|(_, m)| m.priority < message.priority)
+                .min_by_key(|(i, m)| (m.priority, *i))
+                .map(|(i, _)| i);
+
+            match victim {
+                Some(index) => {
+                    if let Some(evicted) = state.messages.remove(index) {
+                        debug!("Outbound queue full, dropped {:?} message for {}", evicted.priority, evicted.topic);
+                    }
+                    state.dropped += 1;
+                    outcome = EnqueueOutcome::EvictedOlder;
+                }
+                None if message.priority != Priority::Response => {
+                    state.dropped += 1;
+                    self.update_saturation(&mut state);
+                    return EnqueueOutcome::Dropped;
+                }
+                None => {}
+            }
+        }
+
+        state.messages.push_back(message);
+        self.update_saturation(&mut state);
+        drop(state);
+        self.notify.notify_one();
+        outcome
+    }
+
+    /// Remove the highest-priority message, oldest first
+    pub fn pop(&self) -> Option<OutboundMessage> {
+        let mut state = self.state.lock().unwrap();
+        let index = state.messages.iter()
+            .enumerate()
+            .max_by_key(|(i, m)| (m.priority, std::cmp::Reverse(*i)))
+            .map(|(i, _)| i)?;
+        let message = state.messages.remove(index);
+        self.update_saturation(&mut state);
+        drop(state);
+        self.drained.notify_waiters();
+        message
+    }
+
+    /// Wait for the next message to publish
+    pub async fn next(&self) -> OutboundMessage {
+        loop {
+            if let Some(message) = self.pop() {
+                return message;
+            }
+            self.notify.notified().await;
+        }
+    }
+
+    /// Wait until fewer than `depth` messages are queued: responses are never dropped, so
+    /// a producer of many of them must not outrun the publisher
+    pub async fn wait_for_room(&self, depth: usize) {
+        loop {
+            // Registered before the check so a pop in between is not missed
+            let drained = self.drained.notified();
+            if self.state.lock().unwrap().messages.len() < depth.max(1) {
+                return;
+            }
+            drained.await;
+        }
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let state = self.state.lock().unwrap();
+        QueueStats {
+            depth: state.messages.len(),
+            dropped: state.dropped,
+            merged: state.merged,
+            saturation_events: state.saturation_events,
+        }
+    }
+
+    /// Log transitions into and out of saturation (hysteresis: full -> half empty)
+    fn update_saturation(&self, state: &mut QueueState) {
+        let depth = state.messages.len();
+        if !state.saturated && depth >= self.capacity {
+            state.saturated = true;
+            state.saturation_events += 1;
+            warn!("Outbound MQTT queue saturated ({} messages, {} dropped so far) - broker slow or unreachable",
+                  depth, state.dropped);
+        } else if state.saturated && depth <= self.capacity / 2 {
+            state.saturated = false;
+            info!("Outbound MQTT queue recovered ({} messages)", depth);
+        }
+    }
+}
+
+/// Drain the queue into the MQTT client; `publish` blocking on rumqttc's
+/// request channel is what applies backpressure to the queue
+pub fn spawn_publisher(queue: Arc<OutboundQueue>, client: AsyncClient) {
+    tokio::spawn(async move {
+        loop {
+            let message = queue.next().await;
+            if let Err(e) = client.publish(&message.topic, QoS::AtLeastOnce, false, message.payload).await {
+                error!("Failed to publish to {}: {}", message.topic, e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(priority: Priority, payload: &str) -> OutboundMessage {
+        OutboundMessage::new("symbion/test", payload.to_string(), priority)
+    }
+
+    #[test]
+    fn test_responses_are_published_before_heartbeats() {
+        let queue = OutboundQueue::new(8);
+        queue.push(msg(Priority::Heartbeat, "hb"));
+        queue.push(msg(Priority::Response, "r1"));
+        queue.push(msg(Priority::Registration, "reg"));
+        queue.push(msg(Priority::Response, "r2"));
+
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop()).map(|m| m.payload).collect();
+        assert_eq!(order, vec!["r1", "r2", "reg", "hb"]);
+    }
+
+    #[test]
+    fn test_heartbeats_are_merged() {
+        let queue = OutboundQueue::new(8);
+        assert_eq!(queue.push(msg(Priority::Heartbeat, "hb1")), EnqueueOutcome::Queued);
+        assert_eq!(queue.push(msg(Priority::Heartbeat, "hb2")), EnqueueOutcome::MergedHeartbeat);
+        assert_eq!(queue.stats().depth, 1);
+        assert_eq!(queue.pop().unwrap().payload, "hb2");
+    }
+
+    #[test]
+    fn test_burst_of_responses_under_pressure() {
+        let queue = OutboundQueue::new(4);
+        queue.push(msg(Priority::Heartbeat, "hb"));
+        queue.push(msg(Priority::Registration, "reg"));
+
+        // Burst of responses: low-priority messages are evicted, responses are all kept
+        for i in 0..10 {
+            queue.push(msg(Priority::Response, &format!("r{}", i)));
+        }
+        let stats = queue.stats();
+        assert_eq!(stats.depth, 10);
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(stats.saturation_events, 1);
+
+        // A heartbeat arriving while saturated with responses is discarded
+        assert_eq!(queue.push(msg(Priority::Heartbeat, "late")), EnqueueOutcome::Dropped);
+
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop()).map(|m| m.payload).collect();
+        assert_eq!(order, (0..10).map(|i| format!("r{}", i)).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_room_until_publisher_drains() {
+        let queue = Arc::new(OutboundQueue::new(8));
+        for i in 0..3 {
+            queue.push(msg(Priority::Response, &format!("r{}", i)));
+        }
+        let producer = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.wait_for_room(2).await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!producer.is_finished());
+
+        queue.pop();
+        tokio::task::yield_now().await;
+        assert!(!producer.is_finished());
+        queue.pop();
+        tokio::time::timeout(std::time::Duration::from_secs(1), producer).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_next_waits_for_message() {
+        let queue = Arc::new(OutboundQueue::new(4));
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.next().await })
+        };
+        tokio::task::yield_now().await;
+        queue.push(msg(Priority::Response, "done"));
+        let message = tokio::time::timeout(std::time::Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        assert_eq!(message.payload, "done");
+    }
+}
@@ -0,0 +1,294 @@
+//! TLS certificate pinning for the updater
+//!
+//! When `update.pinned_spki_sha256` is set, update downloads only trust servers
+//! whose chain carries a public key matching one of the pinned SHA-256 fingerprints:
+//! - Pins hash the SubjectPublicKeyInfo, so a renewed certificate on the same key still matches
+//! - Any certificate of the presented chain may match, so pinning the issuing CA survives leaf rotation
+//! - The normal WebPKI chain validation still runs first
+//! - A valid chain without a pinned key (e.g. a MITM proxy CA) is rejected
+//! - Without pins, the default reqwest client is used unchanged
+
+use anyhow::{Context, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use std::sync::Arc;
+use tracing::warn;
+
+/// SHA-256 fingerprint of a DER SubjectPublicKeyInfo
+pub type Fingerprint = [u8; 32];
+
+/// Parse a hex SHA-256 fingerprint, with or without `:` separators
+pub fn parse_fingerprint(text: &str) -> Result<Fingerprint> {
+    let hex: String = text.chars().filter(|c| *c != ':' && !c.is_whitespace()).collect();
+    if hex.len() != 64 || !hex.is_ascii() {
+        anyhow::bail!("Invalid SHA-256 fingerprint (expected 64 hex digits): {}", text);
+    }
+    let mut fingerprint = [0u8; 32];
+    for (i, byte) in fingerprint.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .with_context(|| format!("Invalid SHA-256 fingerprint: {}", text))?;
+    }
+    Ok(fingerprint)
+}
+
+/// One DER element: its tag, its content, the whole encoding and what follows it
+struct DerElement<'a> {
+    tag: u8,
+    content: &'a [u8],
+    encoded: &'a [u8],
+    rest: &'a [u8],
+}
+
+fn der_element(input: &[u8]) -> Option<DerElement<'_>> {
+    let (&tag, after_tag) = input.split_first()?;
+    if tag & 0x1f == 0x1f {
+        return None; // multi-byte tags do not occur in a certificate header
+    }
+    let (&first, after_len) = after_tag.split_first()?;
+    let (len, header) = if first < 0x80 {
+        (first as usize, 2)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || after_len.len() < count {
+            return None;
+        }
+        let len = after_len[..count].iter().fold(0usize, |len, b| (len << 8) | *b as usize);
+        (len, 2 + count)
+    };
+    let end = header.checked_add(len)?;
+    if input.len() < end {
+        return None;
+    }
+    Some(DerElement { tag, content: &input[header..end], encoded: &input[..end], rest: &input[end..] })
+}
+
+/// SubjectPublicKeyInfo element of a DER certificate
+fn spki(cert: &[u8]) -> Option<&[u8]> {
+    let certificate = der_element(cert).filter(|e| e.tag == 0x30)?;
+    let tbs = der_element(certificate.content).filter(|e| e.tag == 0x30)?;
+    let mut fields = tbs.content;
+    // Optional [0] version
+    if fields.first() == Some(&0xa0) {
+        fields = der_element(fields)?.rest;
+    }
+    // serialNumber, signature, issuer, validity, subject
+    for _ in 0..5 {
+        fields = der_element(fields)?.rest;
+    }
+    der_element(fields).filter(|e| e.tag == 0x30).map(|e| e.encoded)
+}
+
+/// SHA-256 fingerprint of a certificate's public key (SPKI), `None` if the DER is malformed
+pub fn fingerprint(cert: &CertificateDer<'_>) -> Option<Fingerprint> {
+    let digest = ring::digest::digest(&ring::digest::SHA256, spki(cert.as_ref())?);
+    let mut fingerprint = [0u8; 32];
+    fingerprint.copy_from_slice(digest.as_ref());
+    Some(fingerprint)
+}
+
+/// Pin decision: does any certificate of the chain carry a pinned key?
+pub fn pin_matches(pins: &[Fingerprint], end_entity: &CertificateDer<'_>, intermediates: &[CertificateDer<'_>]) -> bool {
+    std::iter::once(end_entity)
+        .chain(intermediates)
+        .filter_map(fingerprint)
+        .any(|fingerprint| pins.contains(&fingerprint))
+}
+
+/// Verifier wrapping chain validation with a public key pin check on the chain
+#[derive(Debug)]
+pub struct PinnedCertVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Vec<Fingerprint>,
+}
+
+impl PinnedCertVerifier {
+    pub fn new(inner: Arc<dyn ServerCertVerifier>, pins: Vec<Fingerprint>) -> Self {
+        Self { inner, pins }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        if !pin_matches(&self.pins, end_entity, intermediates) {
+            warn!("Rejecting {:?}: no certificate in the chain carries a pinned public key", server_name);
+            return Err(rustls::Error::General("certificate pin mismatch".to_string()));
+        }
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// HTTP client for update traffic, pinned when fingerprints are configured
+pub fn http_client(pinned_spki_sha256: &[String]) -> Result<reqwest::Client> {
+    if pinned_spki_sha256.is_empty() {
+        return Ok(reqwest::Client::new());
+    }
+    let pins = pinned_spki_sha256.iter()
+        .map(|pin| parse_fingerprint(pin))
+        .collect::<Result<Vec<_>>>()?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let roots = rustls::RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+    let chain_verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .context("Failed to build certificate verifier")?;
+    let tls = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier::new(chain_verifier, pins)))
+        .with_no_client_auth();
+
+    reqwest::Client::builder()
+        .use_preconfigured_tls(tls)
+        .build()
+        .context("Failed to build pinned HTTP client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for chain validation: accepts every certificate
+    #[derive(Debug)]
+    struct AcceptAll;
+
+    impl ServerCertVerifier for AcceptAll {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            _message: &[u8],
+            _cert: &CertificateDer<'_>,
+            _dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            Ok(HandshakeSignatureValid::assertion())
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            _message: &[u8],
+            _cert: &CertificateDer<'_>,
+            _dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, rustls::Error> {
+            Ok(HandshakeSignatureValid::assertion())
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            vec![SignatureScheme::ECDSA_NISTP256_SHA256]
+        }
+    }
+
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        if content.len() < 0x80 {
+            out.push(content.len() as u8);
+        } else {
+            out.extend([0x82, (content.len() >> 8) as u8, content.len() as u8]);
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    /// Minimal certificate shape: only the fields walked before the SPKI matter
+    fn cert(serial: u8, key: &[u8]) -> CertificateDer<'static> {
+        let spki = der(0x30, &[der(0x30, &der(0x06, b"key-alg")), der(0x03, key)].concat());
+        let tbs = der(0x30, &[
+            der(0xa0, &der(0x02, &[2])),
+            der(0x02, &[serial]),
+            der(0x30, b""),
+            der(0x30, b"issuer"),
+            der(0x30, b"validity"),
+            der(0x30, b"subject"),
+            spki,
+            der(0xa3, &[0u8; 200]),
+        ].concat());
+        CertificateDer::from(der(0x30, &[tbs, der(0x30, b""), der(0x03, b"signature")].concat()))
+    }
+
+    fn verify(pins: Vec<Fingerprint>, chain: &[CertificateDer<'_>]) -> Result<ServerCertVerified, rustls::Error> {
+        let verifier = PinnedCertVerifier::new(Arc::new(AcceptAll), pins);
+        let server_name = ServerName::try_from("api.github.com").unwrap();
+        verifier.verify_server_cert(&chain[0], &chain[1..], &server_name, &[], UnixTime::now())
+    }
+
+    #[test]
+    fn test_renewed_certificate_on_the_same_key_is_accepted() {
+        let leaf = cert(1, b"github leaf key");
+        let renewed = cert(2, b"github leaf key");
+        assert_ne!(leaf, renewed);
+        assert_eq!(fingerprint(&leaf), fingerprint(&renewed));
+        assert!(verify(vec![[0u8; 32], fingerprint(&leaf).unwrap()], &[renewed]).is_ok());
+    }
+
+    #[test]
+    fn test_pin_on_an_intermediate_is_accepted() {
+        let ca = cert(1, b"issuing ca key");
+        let chain = [cert(7, b"rotated leaf key"), ca.clone()];
+        assert!(verify(vec![fingerprint(&ca).unwrap()], &chain).is_ok());
+    }
+
+    #[test]
+    fn test_unpinned_certificate_is_rejected() {
+        let pinned = cert(1, b"github leaf key");
+        let proxy = [cert(1, b"proxy leaf key"), cert(2, b"proxy ca key")];
+        let pins = [fingerprint(&pinned).unwrap()];
+        assert!(pin_matches(&pins, &pinned, &[]));
+        assert!(!pin_matches(&pins, &proxy[0], &proxy[1..]));
+        assert!(verify(pins.to_vec(), &proxy).is_err());
+        let garbage = CertificateDer::from(b"not a certificate".to_vec());
+        assert_eq!(fingerprint(&garbage), None);
+        assert!(verify(pins.to_vec(), &[garbage]).is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_parsing() {
+        let plain = "ab".repeat(32);
+        let colons = vec!["AB"; 32].join(":");
+        assert_eq!(parse_fingerprint(&plain).unwrap(), [0xab; 32]);
+        assert_eq!(parse_fingerprint(&colons).unwrap(), [0xab; 32]);
+        assert!(parse_fingerprint("abcd").is_err());
+        assert!(parse_fingerprint(&"zz".repeat(32)).is_err());
+        assert!(http_client(&["not-a-fingerprint".to_string()]).is_err());
+        assert!(http_client(&[plain]).is_ok());
+    }
+}
@@ -0,0 +1,200 @@
+//! Power transitions for Symbion agents (`shutdown`, `reboot`, `hibernate`)
+//!
+//! With a `message` and/or `warn_seconds`, logged-in users are warned before the machine goes down:
+//! - Linux: `wall <message>` broadcast, then `shutdown -h|-r +<minutes> <message>` (shutdown keeps
+//!   warning sessions until it fires); the delay is rounded up to whole minutes
+//! - Windows: `msg * /TIME:<secs> <message>` popup, then `shutdown /s|/r /t <secs> /c <message>`
+//! - Hibernate has no native delay on either OS: the agent waits `warn_seconds` itself
+//! - Without a notice the historical immediate commands are used unchanged
+
+use anyhow::{anyhow, bail, Result};
+use std::time::Duration;
+
+/// Warning given when only a `message` is supplied
+pub const DEFAULT_WARN_SECONDS: u32 = 60;
+/// Longest warning accepted (a power command shouldn't linger for hours)
+pub const MAX_WARN_SECONDS: u32 = 3600;
+/// `shutdown /c` refuses longer comments
+pub const MAX_MESSAGE_CHARS: usize = 512;
+
+/// Program and arguments of a power step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedCommand {
+    pub program: &'static str,
+    pub args: Vec<String>,
+}
+
+fn planned(program: &'static str, args: &[&str]) -> PlannedCommand {
+    PlannedCommand { program, args: args.iter().map(|a| a.to_string()).collect() }
+}
+
+/// Warning shown to logged-in users before a power transition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerNotice {
+    pub message: String,
+    pub warn_seconds: u32,
+}
+
+impl PowerNotice {
+    /// `message` / `warn_seconds` command parameters; None when neither is given
+    pub fn from_params(params: Option<&serde_json::Value>, action: &str) -> Result<Option<Self>> {
+        let message = params.and_then(|p| p.get("message")).filter(|v| !v.is_null());
+        let warn_seconds = params.and_then(|p| p.get("warn_seconds")).filter(|v| !v.is_null());
+        if message.is_none() && warn_seconds.is_none() {
+            return Ok(None);
+        }
+        let message = match message {
+            Some(message) => message.as_str().ok_or_else(|| anyhow!("message must be a string"))?,
+            None => "",
+        };
+        // One line of printable text: it ends up in wall/msg/shutdown arguments
+        let message: String = message.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+        let message = match message.trim() {
+            "" => format!("System {} initiated by Symbion", action),
+            message => message.to_string(),
+        };
+        if message.chars().count() > MAX_MESSAGE_CHARS {
+            bail!("message is longer than {} characters", MAX_MESSAGE_CHARS);
+        }
+        // wall and shutdown would parse a leading dash as one of their options
+        if message.starts_with('-') {
+            bail!("message must not start with '-'");
+        }
+        let warn_seconds = match warn_seconds {
+            Some(value) => value.as_u64()
+                .filter(|secs| *secs <= MAX_WARN_SECONDS as u64)
+                .ok_or_else(|| anyhow!("warn_seconds must be between 0 and {}", MAX_WARN_SECONDS))? as u32,
+            None => DEFAULT_WARN_SECONDS,
+        };
+        Ok(Some(Self { message, warn_seconds }))
+    }
+}
+
+/// Steps of a power transition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PowerPlan {
+    /// Warning broadcast to sessions first
+    pub broadcast: Option<PlannedCommand>,
+    /// Time the agent waits itself before `command` (OS without native delay)
+    pub wait: Duration,
+    pub command: PlannedCommand,
+    /// Delay before the machine actually goes down, as scheduled
+    pub effective_delay_secs: u32,
+}
+
+/// Plan `action` (shutdown, reboot, hibernate) on `os`; None if unsupported
+pub fn plan(os: &str, action: &str, notice: Option<&PowerNotice>) -> Option<PowerPlan> {
+    let Some(notice) = notice else {
+        let command = match (os, action) {
+            ("windows", "shutdown") => planned("cmd", &["/C", "shutdown /s /t 0 /f"]),
+            ("windows", "reboot") => planned("shutdown", &["/r", "/t", "5", "/c", "Reboot initiated by Symbion"]),
+            ("windows", "hibernate") => planned("rundll32.exe", &["powrprof.dll,SetSuspendState", "Hibernate"]),
+            ("linux", "shutdown") => planned("sudo", &["shutdown", "-h", "+1", "Shutdown initiated by Symbion"]),
+            ("linux", "reboot") => planned("sudo", &["reboot"]),
+            ("linux", "hibernate") => planned("systemctl", &["hibernate"]),
+            _ => return None,
+        };
+        let effective_delay_secs = match (os, action) {
+            ("windows", "reboot") => 5,
+            ("linux", "shutdown") => 60,
+            _ => 0,
+        };
+        return Some(PowerPlan { broadcast: None, wait: Duration::ZERO, command, effective_delay_secs });
+    };
+
+    let secs = notice.warn_seconds;
+    let message = notice.message.as_str();
+    match os {
+        "windows" => {
+            let broadcast = planned("msg", &["*", &format!("/TIME:{}", secs.max(1)), message]);
+            let (command, wait) = match action {
+                "shutdown" => (planned("shutdown", &["/s", "/t", &secs.to_string(), "/c", message]), 0),
+                "reboot" => (planned("shutdown", &["/r", "/t", &secs.to_string(), "/c", message]), 0),
+                "hibernate" => (planned("rundll32.exe", &["powrprof.dll,SetSuspendState", "Hibernate"]), secs),
+                _ => return None,
+            };
+            Some(PowerPlan { broadcast: Some(broadcast), wait: Duration::from_secs(wait as u64), command, effective_delay_secs: secs })
+        }
+        "linux" => {
+            let broadcast = planned("wall", &[message]);
+            // shutdown counts in minutes: never fire earlier than announced
+            let minutes = secs.div_ceil(60);
+            let when = if minutes == 0 { "now".to_string() } else { format!("+{}", minutes) };
+            let (command, wait, effective_delay_secs) = match action {
+                "shutdown" => (planned("sudo", &["shutdown", "-h", &when, message]), 0, minutes * 60),
+                "reboot" => (planned("sudo", &["shutdown", "-r", &when, message]), 0, minutes * 60),
+                "hibernate" => (planned("systemctl", &["hibernate"]), secs, secs),
+                _ => return None,
+            };
+            Some(PowerPlan { broadcast: Some(broadcast), wait: Duration::from_secs(wait as u64), command, effective_delay_secs })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notice(message: &str, warn_seconds: u32) -> PowerNotice {
+        PowerNotice { message: message.to_string(), warn_seconds }
+    }
+
+    #[test]
+    fn test_notice_parameters() {
+        assert_eq!(PowerNotice::from_params(None, "shutdown").unwrap(), None);
+        assert_eq!(PowerNotice::from_params(Some(&serde_json::json!({ "delay": 10 })), "shutdown").unwrap(), None);
+
+        let params = serde_json::json!({ "message": "Maintenance\nsave your work" });
+        assert_eq!(PowerNotice::from_params(Some(&params), "shutdown").unwrap(),
+            Some(notice("Maintenance save your work", DEFAULT_WARN_SECONDS)));
+        let params = serde_json::json!({ "warn_seconds": 120 });
+        assert_eq!(PowerNotice::from_params(Some(&params), "reboot").unwrap(),
+            Some(notice("System reboot initiated by Symbion", 120)));
+
+        assert!(PowerNotice::from_params(Some(&serde_json::json!({ "warn_seconds": MAX_WARN_SECONDS + 1 })), "reboot").is_err());
+        assert!(PowerNotice::from_params(Some(&serde_json::json!({ "warn_seconds": -5 })), "reboot").is_err());
+        assert!(PowerNotice::from_params(Some(&serde_json::json!({ "message": 42 })), "reboot").is_err());
+        let long = "x".repeat(MAX_MESSAGE_CHARS + 1);
+        assert!(PowerNotice::from_params(Some(&serde_json::json!({ "message": long })), "reboot").is_err());
+        assert!(PowerNotice::from_params(Some(&serde_json::json!({ "message": "-c" })), "shutdown").is_err());
+        assert!(PowerNotice::from_params(Some(&serde_json::json!({ "message": "  --help" })), "reboot").is_err());
+        assert!(PowerNotice::from_params(Some(&serde_json::json!({ "message": "a - b" })), "reboot").is_ok());
+    }
+
+    #[test]
+    fn test_notification_commands() {
+        let warning = notice("Back in 5 minutes", 90);
+        let linux = plan("linux", "reboot", Some(&warning)).unwrap();
+        assert_eq!(linux.broadcast, Some(planned("wall", &["Back in 5 minutes"])));
+        let windows = plan("windows", "reboot", Some(&warning)).unwrap();
+        assert_eq!(windows.broadcast, Some(planned("msg", &["*", "/TIME:90", "Back in 5 minutes"])));
+
+        // No notice: unchanged immediate commands, nobody warned
+        let silent = plan("windows", "shutdown", None).unwrap();
+        assert_eq!(silent.broadcast, None);
+        assert_eq!(silent.command, planned("cmd", &["/C", "shutdown /s /t 0 /f"]));
+        assert!(plan("macos", "shutdown", Some(&warning)).is_none());
+        assert!(plan("linux", "suspend", None).is_none());
+    }
+
+    #[test]
+    fn test_warning_delay_is_honored() {
+        // Linux shutdown counts in minutes, rounded up
+        let linux = plan("linux", "shutdown", Some(&notice("bye", 90))).unwrap();
+        assert_eq!(linux.command, planned("sudo", &["shutdown", "-h", "+2", "bye"]));
+        assert_eq!((linux.wait, linux.effective_delay_secs), (Duration::ZERO, 120));
+        let now = plan("linux", "reboot", Some(&notice("bye", 0))).unwrap();
+        assert_eq!(now.command, planned("sudo", &["shutdown", "-r", "now", "bye"]));
+
+        let windows = plan("windows", "shutdown", Some(&notice("bye", 90))).unwrap();
+        assert_eq!(windows.command, planned("shutdown", &["/s", "/t", "90", "/c", "bye"]));
+        assert_eq!((windows.wait, windows.effective_delay_secs), (Duration::ZERO, 90));
+
+        // No native delay: the agent waits before hibernating
+        for os in ["linux", "windows"] {
+            let hibernate = plan(os, "hibernate", Some(&notice("bye", 45))).unwrap();
+            assert_eq!((hibernate.wait, hibernate.effective_delay_secs), (Duration::from_secs(45), 45));
+        }
+    }
+}
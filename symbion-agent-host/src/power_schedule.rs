@@ -0,0 +1,325 @@
+//! Idle auto-shutdown for Symbion agents (`[power_schedule]`, `set_power_schedule`)
+//!
+//! The host powers itself off once nobody has used it for `idle_minutes` within the allowed hours:
+//! - Idle time comes from user sessions: `who -u` IDLE column (Linux), `quser` (Windows);
+//!   disconnected Windows sessions don't count
+//! - A session with recent input keeps the host up, and so does a session whose idle time is
+//!   unknown (`who -u` prints `?` for graphical logins); without any session idle time runs from
+//!   the last input seen, or from agent start
+//! - Maintenance mode pauses the policy (heartbeats then report the `maintenance` status)
+//! - The shutdown goes through `power::plan` with a warning, so users can still cancel it
+//!   (`shutdown -c`, `shutdown /a`); the idle clock restarts after each attempt
+//! - The kernel changes the policy with `set_power_schedule`; changes are written to the config
+//!   file, so the host boots with them after its own idle shutdown
+
+use crate::config::PowerScheduleConfig;
+use crate::execution::{self, DEFAULT_MAX_OUTPUT_BYTES};
+use crate::power::MAX_WARN_SECONDS;
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tokio::process::Command as AsyncCommand;
+
+/// Interval between two idle checks
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Longest idle threshold accepted (one week)
+pub const MAX_IDLE_MINUTES: u32 = 7 * 24 * 60;
+/// Maximum time given to `who` / `quser`
+const SESSION_QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Idle time reported by `who -u` as `old`
+const WHO_OLD_IDLE: Duration = Duration::from_secs(24 * 3600);
+
+/// Outcome of an idle check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Disabled,
+    Maintenance,
+    OutsideHours,
+    InUse,
+    Shutdown,
+}
+
+/// Whether `hour` (0-23, local) falls in the allowed window
+pub fn in_window(policy: &PowerScheduleConfig, hour: u32) -> bool {
+    let (start, end) = (policy.start_hour, policy.end_hour);
+    if start == end {
+        true
+    } else if start < end {
+        (start..end).contains(&hour)
+    } else {
+        hour >= start || hour < end
+    }
+}
+
+/// Shut down when the policy is active, `hour` is allowed and the host has been idle long enough
+pub fn decide(policy: &PowerScheduleConfig, idle: Duration, hour: u32) -> Decision {
+    if !policy.enabled {
+        Decision::Disabled
+    } else if policy.maintenance {
+        Decision::Maintenance
+    } else if !in_window(policy, hour) {
+        Decision::OutsideHours
+    } else if idle < Duration::from_secs(policy.idle_minutes as u64 * 60) {
+        Decision::InUse
+    } else {
+        Decision::Shutdown
+    }
+}
+
+/// Check a policy from the config file or the kernel
+pub fn validate(policy: &PowerScheduleConfig) -> Result<()> {
+    if !(1..=MAX_IDLE_MINUTES).contains(&policy.idle_minutes) {
+        bail!("idle_minutes must be between 1 and {}", MAX_IDLE_MINUTES);
+    }
+    if policy.start_hour > 23 || policy.end_hour > 23 {
+        bail!("start_hour and end_hour must be between 0 and 23");
+    }
+    if policy.warn_seconds > MAX_WARN_SECONDS {
+        bail!("warn_seconds must be between 0 and {}", MAX_WARN_SECONDS);
+    }
+    Ok(())
+}
+
+/// `policy` with the fields given in `set_power_schedule` parameters replaced
+pub fn update(policy: &PowerScheduleConfig, params: Option<&serde_json::Value>) -> Result<PowerScheduleConfig> {
+    let mut merged = serde_json::to_value(policy)?;
+    if let Some(params) = params.filter(|p| !p.is_null()) {
+        let fields = params.as_object().ok_or_else(|| anyhow!("parameters must be an object"))?;
+        for (key, value) in fields {
+            let slot = merged.get_mut(key).ok_or_else(|| anyhow!("unknown parameter: {}", key))?;
+            *slot = value.clone();
+        }
+    }
+    let updated: PowerScheduleConfig = serde_json::from_value(merged).map_err(|e| anyhow!("invalid parameter: {}", e))?;
+    validate(&updated)?;
+    Ok(updated)
+}
+
+/// Logged-in user sessions at one sample
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sessions {
+    pub count: usize,
+    /// Idle time of the most recently used session (None = no session)
+    pub min_idle: Option<Duration>,
+}
+
+impl Sessions {
+    /// A session whose idle time can't be read counts as in use: never shut down under a user
+    fn add(&mut self, idle: Option<Duration>) {
+        self.count += 1;
+        let idle = idle.unwrap_or(Duration::ZERO);
+        self.min_idle = Some(self.min_idle.map_or(idle, |min| min.min(idle)));
+    }
+}
+
+/// `who -u` idle column: `.` (input in the last minute), `HH:MM`, `old` (over 24h)
+fn parse_who_idle(field: &str) -> Option<Duration> {
+    match field {
+        "." => Some(Duration::ZERO),
+        "old" => Some(WHO_OLD_IDLE),
+        _ => {
+            let (hours, minutes) = field.split_once(':')?;
+            Some(Duration::from_secs(hours.parse::<u64>().ok()? * 3600 + minutes.parse::<u64>().ok()? * 60))
+        }
+    }
+}
+
+/// Sessions listed by `who -u` (`user line date time idle pid (comment)`, date format varies)
+pub fn parse_who(output: &str) -> Sessions {
+    let mut sessions = Sessions::default();
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // The PID is the last all-digit field (the comment is parenthesized); the idle column precedes it
+        let Some(pid) = fields.iter().rposition(|f| f.chars().all(|c| c.is_ascii_digit())).filter(|pid| *pid > 3) else { continue };
+        sessions.add(parse_who_idle(fields[pid - 1]));
+    }
+    sessions
+}
+
+/// `quser` idle column: `none`/`.` (active), minutes, `H:MM`, `D+H:MM`
+fn parse_quser_idle(field: &str) -> Option<Duration> {
+    if field == "none" || field == "." {
+        return Some(Duration::ZERO);
+    }
+    let (days, rest) = match field.split_once('+') {
+        Some((days, rest)) => (days.parse::<u64>().ok()?, rest),
+        None => (0, field),
+    };
+    let minutes = match rest.split_once(':') {
+        Some((hours, minutes)) => hours.parse::<u64>().ok()? * 60 + minutes.parse::<u64>().ok()?,
+        None => rest.parse::<u64>().ok()?,
+    };
+    Some(Duration::from_secs((days * 24 * 60 + minutes) * 60))
+}
+
+/// Connected sessions listed by `quser` (SESSIONNAME may be blank, so fields are found from the ID)
+pub fn parse_quser(output: &str) -> Sessions {
+    let mut sessions = Sessions::default();
+    for line in output.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some(id) = fields.iter().skip(1).position(|f| f.chars().all(|c| c.is_ascii_digit())).map(|p| p + 1) else { continue };
+        let (Some(state), Some(idle)) = (fields.get(id + 1), fields.get(id + 2)) else { continue };
+        // Nobody sits in front of a disconnected session
+        if state.starts_with("Disc") {
+            continue;
+        }
+        sessions.add(parse_quser_idle(idle));
+    }
+    sessions
+}
+
+/// Sample the user sessions of this host
+pub async fn sample_sessions(os: &str) -> Result<Sessions> {
+    let (program, args, parse): (&str, &[&str], fn(&str) -> Sessions) = match os {
+        "linux" => ("who", &["-u"], parse_who),
+        "windows" => ("quser", &[], parse_quser),
+        other => bail!("Session idle time not supported on OS: {}", other),
+    };
+    let mut command = AsyncCommand::new(program);
+    command.args(args);
+    let output = tokio::time::timeout(SESSION_QUERY_TIMEOUT, execution::run_capped(command, DEFAULT_MAX_OUTPUT_BYTES))
+        .await
+        .map_err(|_| anyhow!("{} timed out after {:?}", program, SESSION_QUERY_TIMEOUT))??;
+    // quser exits with an error when nobody is logged in
+    if output.exit_code != Some(0) && !output.stdout.trim().is_empty() {
+        bail!("{} failed: {}", program, output.stderr.trim());
+    }
+    Ok(parse(&output.stdout))
+}
+
+/// Runtime policy and idle clock
+#[derive(Debug)]
+pub struct PowerSchedule {
+    pub policy: PowerScheduleConfig,
+    last_input: Instant,
+    /// Idle time at the last check (None until the first sample)
+    pub last_idle: Option<Duration>,
+    pub last_sessions: usize,
+}
+
+impl PowerSchedule {
+    pub fn new(policy: PowerScheduleConfig, now: Instant) -> Self {
+        Self { policy, last_input: now, last_idle: None, last_sessions: 0 }
+    }
+
+    /// Record a session sample taken at `now`; returns how long the host has been idle
+    pub fn observe(&mut self, sessions: &Sessions, now: Instant) -> Duration {
+        if let Some(input) = sessions.min_idle.and_then(|idle| now.checked_sub(idle)) {
+            self.last_input = self.last_input.max(input);
+        }
+        let idle = now.saturating_duration_since(self.last_input);
+        self.last_idle = Some(idle);
+        self.last_sessions = sessions.count;
+        idle
+    }
+
+    /// Restart the idle clock (after a shutdown attempt, or a policy change)
+    pub fn rearm(&mut self, now: Instant) {
+        self.last_input = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(idle_minutes: u32, start_hour: u32, end_hour: u32) -> PowerScheduleConfig {
+        PowerScheduleConfig { enabled: true, idle_minutes, start_hour, end_hour, ..Default::default() }
+    }
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_window_wraps_midnight() {
+        let overnight = policy(30, 22, 6);
+        assert!(in_window(&overnight, 23));
+        assert!(in_window(&overnight, 0));
+        assert!(in_window(&overnight, 5));
+        assert!(!in_window(&overnight, 6));
+        assert!(!in_window(&overnight, 12));
+
+        let office = policy(30, 9, 18);
+        assert!(in_window(&office, 9));
+        assert!(!in_window(&office, 18));
+        assert!(in_window(&policy(30, 0, 0), 13));
+    }
+
+    #[test]
+    fn test_shutdown_only_when_idle_in_window() {
+        let overnight = policy(30, 22, 6);
+        assert_eq!(decide(&overnight, 45 * MINUTE, 23), Decision::Shutdown);
+        assert_eq!(decide(&overnight, 30 * MINUTE, 2), Decision::Shutdown);
+        assert_eq!(decide(&overnight, 29 * MINUTE, 23), Decision::InUse);
+        assert_eq!(decide(&overnight, 45 * MINUTE, 14), Decision::OutsideHours);
+
+        let paused = PowerScheduleConfig { maintenance: true, ..overnight.clone() };
+        assert_eq!(decide(&paused, 45 * MINUTE, 23), Decision::Maintenance);
+        let disabled = PowerScheduleConfig { enabled: false, ..overnight };
+        assert_eq!(decide(&disabled, 45 * MINUTE, 23), Decision::Disabled);
+    }
+
+    #[test]
+    fn test_active_session_keeps_the_host_up() {
+        let start = Instant::now();
+        let mut schedule = PowerSchedule::new(policy(30, 0, 0), start);
+
+        // Nobody logged in: idle since agent start
+        let idle = schedule.observe(&Sessions::default(), start + 40 * MINUTE);
+        assert_eq!(decide(&schedule.policy, idle, 12), Decision::Shutdown);
+
+        // A session typed 5 minutes ago, another one is idle for hours
+        let who = "alice    pts/0        2025-09-01 10:30 00:05        1234 (10.0.0.5)\n\
+                   bob      tty2         Sep  1 08:00   old          987\n";
+        let sessions = parse_who(who);
+        assert_eq!(sessions, Sessions { count: 2, min_idle: Some(5 * MINUTE) });
+        let idle = schedule.observe(&sessions, start + 41 * MINUTE);
+        assert_eq!(idle, 5 * MINUTE);
+        assert_eq!(decide(&schedule.policy, idle, 12), Decision::InUse);
+
+        // The user left: idle runs from their last input, not from the logout
+        let idle = schedule.observe(&Sessions::default(), start + 70 * MINUTE);
+        assert_eq!(idle, 34 * MINUTE);
+        assert_eq!(decide(&schedule.policy, idle, 12), Decision::Shutdown);
+        schedule.rearm(start + 70 * MINUTE);
+        assert_eq!(schedule.observe(&Sessions::default(), start + 71 * MINUTE), MINUTE);
+    }
+
+    #[test]
+    fn test_session_with_unknown_idle_time_is_in_use() {
+        let start = Instant::now();
+        let mut schedule = PowerSchedule::new(policy(30, 0, 0), start);
+
+        // Graphical login: `who -u` has no tty to time, the idle column is `?`
+        let who = "alice    seat0        2025-09-01 08:00   ?          1502 (login screen)\n\
+                   alice    tty2         2025-09-01 08:00   old        1502 (tty2)\n";
+        let sessions = parse_who(who);
+        assert_eq!(sessions, Sessions { count: 2, min_idle: Some(Duration::ZERO) });
+        let idle = schedule.observe(&sessions, start + 3 * 60 * MINUTE);
+        assert_eq!(decide(&schedule.policy, idle, 12), Decision::InUse);
+    }
+
+    #[test]
+    fn test_quser_sessions() {
+        let quser = " USERNAME              SESSIONNAME        ID  STATE   IDLE TIME  LOGON TIME\n\
+                     >alice                 console             1  Active      none   9/1/2025 8:00 AM\n\
+                      bob                   rdp-tcp#3           2  Active      1:05   9/1/2025 8:00 AM\n\
+                      carol                                     3  Disc          12   9/1/2025 8:00 AM\n";
+        assert_eq!(parse_quser(quser), Sessions { count: 2, min_idle: Some(Duration::ZERO) });
+        assert_eq!(parse_quser_idle("2+03:04"), Some(Duration::from_secs(((2 * 24 + 3) * 60 + 4) * 60)));
+        assert_eq!(parse_quser(""), Sessions::default());
+    }
+
+    #[test]
+    fn test_kernel_updates_are_validated() {
+        let current = PowerScheduleConfig::default();
+        let updated = update(&current, Some(&serde_json::json!({ "enabled": true, "idle_minutes": 15 }))).unwrap();
+        assert_eq!(updated, PowerScheduleConfig { enabled: true, idle_minutes: 15, ..current.clone() });
+        assert_eq!(update(&current, None).unwrap(), current);
+
+        assert!(update(&current, Some(&serde_json::json!({ "start_hour": 24 }))).is_err());
+        assert!(update(&current, Some(&serde_json::json!({ "idle_minutes": 0 }))).is_err());
+        assert!(update(&current, Some(&serde_json::json!({ "enabled": "yes" }))).is_err());
+        assert!(update(&current, Some(&serde_json::json!({ "idle_hours": 2 }))).is_err());
+    }
+}
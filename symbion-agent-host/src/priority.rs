@@ -0,0 +1,210 @@
+//! Process scheduling adjustments for Symbion agents
+//!
+//! Backs the `renice` and `set_affinity` commands (taming a runaway process without killing it):
+//! - Linux/Android/macOS: `renice -n <nice> -p <pid>`, `taskset -p -c <cpus> <pid>` (Linux/Android only)
+//! - Windows: PowerShell `PriorityClass` (nice mapped to a priority class) and `ProcessorAffinity` mask
+//! - The PID must exist and not be the init/System process; nice is bounded to -20..=19 and CPUs
+//!   to the cores of this host
+//! - Commands are built from validated numbers only, never from kernel-supplied strings
+
+use crate::execution::{self, DEFAULT_MAX_OUTPUT_BYTES};
+use anyhow::{anyhow, bail, Context, Result};
+use std::time::Duration;
+use tokio::process::Command as AsyncCommand;
+
+/// Highest scheduling priority accepted (lowering below 0 usually needs root)
+pub const MIN_NICE: i64 = -20;
+/// Lowest scheduling priority
+pub const MAX_NICE: i64 = 19;
+
+/// Maximum time given to `renice`/`taskset`/PowerShell
+pub const PRIORITY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Windows affinity masks are 64 bits (one processor group)
+const MAX_WINDOWS_CPUS: u32 = 64;
+
+/// Program and arguments of a scheduling change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedCommand {
+    pub program: &'static str,
+    pub args: Vec<String>,
+}
+
+/// PID the kernel may target: positive, 32 bits, not init (1) nor the Windows System process (4)
+pub fn validate_pid(pid: u64, os: &str) -> Result<u32> {
+    let pid = u32::try_from(pid).map_err(|_| anyhow!("pid {} out of range", pid))?;
+    let protected: &[u32] = if os == "windows" { &[0, 4] } else { &[0, 1] };
+    if protected.contains(&pid) {
+        bail!("pid {} cannot be adjusted", pid);
+    }
+    Ok(pid)
+}
+
+/// Nice value within -20..=19
+pub fn validate_nice(nice: i64) -> Result<i32> {
+    if !(MIN_NICE..=MAX_NICE).contains(&nice) {
+        bail!("nice must be between {} and {}, got {}", MIN_NICE, MAX_NICE, nice);
+    }
+    Ok(nice as i32)
+}
+
+/// CPU list: non-empty, every index below the host core count, deduplicated and sorted
+pub fn validate_cpus(cpus: &[u64], cpu_count: usize) -> Result<Vec<u32>> {
+    if cpus.is_empty() {
+        bail!("cpus must list at least one CPU");
+    }
+    let mut validated = Vec::with_capacity(cpus.len());
+    for &cpu in cpus {
+        if cpu >= cpu_count as u64 {
+            bail!("cpu {} does not exist (host has {} CPUs)", cpu, cpu_count);
+        }
+        validated.push(cpu as u32);
+    }
+    validated.sort_unstable();
+    validated.dedup();
+    Ok(validated)
+}
+
+/// Windows priority class closest to a nice value (RealTime is never used: it can starve the host)
+fn windows_priority_class(nice: i32) -> &'static str {
+    match nice {
+        i32::MIN..=-11 => "High",
+        -10..=-1 => "AboveNormal",
+        0 => "Normal",
+        1..=10 => "BelowNormal",
+        _ => "Idle",
+    }
+}
+
+/// Command changing the scheduling priority of `pid`
+pub fn renice_command(os: &str, pid: u32, nice: i32) -> Result<PlannedCommand> {
+    match os {
+        "linux" | "android" | "macos" => Ok(PlannedCommand {
+            program: "renice",
+            args: vec!["-n".into(), nice.to_string(), "-p".into(), pid.to_string()],
+        }),
+        "windows" => Ok(PlannedCommand {
+            program: "powershell",
+            args: vec![
+                "-NoProfile".into(),
+                "-Command".into(),
+                format!("(Get-Process -Id {} -ErrorAction Stop).PriorityClass = '{}'", pid, windows_priority_class(nice)),
+            ],
+        }),
+        other => bail!("renice not supported on OS: {}", other),
+    }
+}
+
+/// Command pinning `pid` to the given CPUs
+pub fn affinity_command(os: &str, pid: u32, cpus: &[u32]) -> Result<PlannedCommand> {
+    match os {
+        "linux" | "android" => Ok(PlannedCommand {
+            program: "taskset",
+            args: vec![
+                "-p".into(),
+                "-c".into(),
+                cpus.iter().map(u32::to_string).collect::<Vec<_>>().join(","),
+                pid.to_string(),
+            ],
+        }),
+        "windows" => {
+            if let Some(cpu) = cpus.iter().find(|cpu| **cpu >= MAX_WINDOWS_CPUS) {
+                bail!("cpu {} is outside the first processor group", cpu);
+            }
+            let mask = cpus.iter().fold(0u64, |mask, cpu| mask | (1u64 << cpu));
+            Ok(PlannedCommand {
+                program: "powershell",
+                args: vec![
+                    "-NoProfile".into(),
+                    "-Command".into(),
+                    format!("(Get-Process -Id {} -ErrorAction Stop).ProcessorAffinity = {}", pid, mask),
+                ],
+            })
+        }
+        other => bail!("set_affinity not supported on OS: {}", other),
+    }
+}
+
+/// Logical CPUs of this host
+pub fn cpu_count() -> usize {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_cpu();
+    sys.cpus().len()
+}
+
+/// Process currently running on this host
+pub fn process_exists(pid: u32) -> bool {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_processes();
+    sys.process(sysinfo::Pid::from_u32(pid)).is_some()
+}
+
+/// Run a planned command under the timeout; stdout on success
+pub async fn run(planned: &PlannedCommand) -> Result<String> {
+    let mut command = AsyncCommand::new(planned.program);
+    command.args(&planned.args);
+    let output = tokio::time::timeout(PRIORITY_TIMEOUT, execution::run_capped(command, DEFAULT_MAX_OUTPUT_BYTES))
+        .await
+        .map_err(|_| anyhow!("{} timed out after {:?}", planned.program, PRIORITY_TIMEOUT))?
+        .with_context(|| format!("Failed to run {}", planned.program))?;
+    if output.exit_code != Some(0) {
+        bail!("{} failed: {}", planned.program, output.stderr.trim());
+    }
+    Ok(output.stdout.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(planned: &PlannedCommand) -> Vec<&str> {
+        planned.args.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn test_renice_command_per_platform() {
+        let linux = renice_command("linux", 4242, 10).unwrap();
+        assert_eq!(linux.program, "renice");
+        assert_eq!(args(&linux), ["-n", "10", "-p", "4242"]);
+        assert_eq!(args(&renice_command("macos", 4242, -5).unwrap()), ["-n", "-5", "-p", "4242"]);
+
+        let windows = renice_command("windows", 4242, 15).unwrap();
+        assert_eq!(windows.program, "powershell");
+        assert_eq!(args(&windows)[2], "(Get-Process -Id 4242 -ErrorAction Stop).PriorityClass = 'Idle'");
+        assert!(renice_command("windows", 1, -20).unwrap().args[2].ends_with("'High'"));
+        assert!(renice_command("windows", 1, 0).unwrap().args[2].ends_with("'Normal'"));
+        assert!(renice_command("freebsd", 4242, 0).is_err());
+    }
+
+    #[test]
+    fn test_affinity_command_per_platform() {
+        let linux = affinity_command("linux", 4242, &[0, 2, 3]).unwrap();
+        assert_eq!(linux.program, "taskset");
+        assert_eq!(args(&linux), ["-p", "-c", "0,2,3", "4242"]);
+
+        // CPUs 0, 2 and 3: mask 0b1101
+        let windows = affinity_command("windows", 4242, &[0, 2, 3]).unwrap();
+        assert_eq!(args(&windows)[2], "(Get-Process -Id 4242 -ErrorAction Stop).ProcessorAffinity = 13");
+        assert!(affinity_command("windows", 4242, &[64]).is_err());
+        assert!(affinity_command("macos", 4242, &[0]).is_err());
+    }
+
+    #[test]
+    fn test_pid_nice_and_cpu_bounds() {
+        assert_eq!(validate_pid(4242, "linux").unwrap(), 4242);
+        assert!(validate_pid(0, "linux").is_err());
+        assert!(validate_pid(1, "linux").is_err());
+        assert!(validate_pid(4, "windows").is_err());
+        assert_eq!(validate_pid(4, "linux").unwrap(), 4);
+        assert!(validate_pid(u64::from(u32::MAX) + 1, "linux").is_err());
+
+        assert_eq!(validate_nice(-20).unwrap(), -20);
+        assert_eq!(validate_nice(19).unwrap(), 19);
+        assert!(validate_nice(20).is_err());
+        assert!(validate_nice(-21).is_err());
+
+        assert_eq!(validate_cpus(&[3, 0, 3], 4).unwrap(), vec![0, 3]);
+        assert!(validate_cpus(&[4], 4).is_err());
+        assert!(validate_cpus(&[], 4).is_err());
+    }
+}
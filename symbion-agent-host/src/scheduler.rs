@@ -0,0 +1,398 @@
+//! Concurrent command scheduling for Symbion agents
+//!
+//! Commands used to run one after the other, so a long shell command delayed
+//! an urgent shutdown. The scheduler runs them on a bounded pool instead:
+//! - At most `max_concurrency` commands execute at the same time
+//! - Commands sharing a conflict group (e.g. power commands) never overlap
+//! - Each command keeps its own context, so responses correlate by command_id
+//! - Commands waiting for a slot are dequeued by priority (then arrival), so an
+//!   urgent shutdown jumps ahead of queued metrics requests
+//! - The backlog (queued + running) is reported in heartbeats so the kernel
+//!   can see an overloaded agent
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BinaryHeap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+/// Default number of commands allowed to run at once
+pub const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Scheduling priority of a command (`priority` field of agents.command@v1)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Commands accepted by the scheduler and not finished yet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Backlog {
+    /// Waiting for their conflict group or a pool slot
+    pub queued: usize,
+    /// Currently executing
+    pub running: usize,
+    pub max_concurrency: usize,
+}
+
+impl Backlog {
+    /// Commands queued or in flight
+    pub fn depth(&self) -> usize {
+        self.queued + self.running
+    }
+
+    /// Every slot taken or commands waiting: new work will not start right away
+    pub fn is_busy(&self) -> bool {
+        self.queued > 0 || self.running >= self.max_concurrency
+    }
+}
+
+/// Decrements a backlog counter when dropped (task finished or cancelled)
+struct CountGuard(Arc<AtomicUsize>);
+
+impl CountGuard {
+    fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter.clone())
+    }
+}
+
+impl Drop for CountGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Command waiting for a pool slot
+struct Waiter {
+    priority: CommandPriority,
+    /// Arrival order, breaks ties between equal priorities
+    seq: u64,
+    grant: oneshot::Sender<Slot>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    /// Max-heap order: higher priority first, then earlier arrival
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.priority.cmp(&other.priority).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    free: usize,
+    waiters: BinaryHeap<Waiter>,
+    next_seq: u64,
+}
+
+/// Fixed number of execution slots handed out by priority
+struct SlotPool {
+    state: Mutex<PoolState>,
+}
+
+/// A held execution slot, given back to the pool on drop
+struct Slot(Option<Arc<SlotPool>>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Some(pool) = self.0.take() {
+            SlotPool::release(&pool);
+        }
+    }
+}
+
+impl SlotPool {
+    fn new(slots: usize) -> Arc<Self> {
+        Arc::new(Self { state: Mutex::new(PoolState { free: slots, ..PoolState::default() }) })
+    }
+
+    async fn acquire(pool: &Arc<Self>, priority: CommandPriority) -> Option<Slot> {
+        let granted = {
+            let mut state = pool.state.lock().unwrap();
+            if state.free > 0 && state.waiters.is_empty() {
+                state.free -= 1;
+                return Some(Slot(Some(pool.clone())));
+            }
+            let (grant, granted) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiters.push(Waiter { priority, seq, grant });
+            granted
+        };
+        // A slot sent to a cancelled waiter is dropped with the channel, which releases it
+        granted.await.ok()
+    }
+
+    /// Hand the slot to the best waiter still listening, or return it to the pool
+    fn release(pool: &Arc<Self>) {
+        let mut state = pool.state.lock().unwrap();
+        while let Some(waiter) = state.waiters.pop() {
+            match waiter.grant.send(Slot(Some(pool.clone()))) {
+                Ok(()) => return,
+                // Receiver gone: disarm the slot so it doesn't release again under the lock
+                Err(mut slot) => {
+                    slot.0.take();
+                }
+            }
+        }
+        state.free += 1;
+    }
+}
+
+/// Bounded command pool with per-group serialization
+pub struct CommandScheduler {
+    slots: Arc<SlotPool>,
+    max_concurrency: usize,
+    groups: Mutex<HashMap<&'static str, Arc<tokio::sync::Mutex<()>>>>,
+    queued: Arc<AtomicUsize>,
+    running: Arc<AtomicUsize>,
+}
+
+impl CommandScheduler {
+    pub fn new(max_concurrency: usize) -> Self {
+        Self {
+            slots: SlotPool::new(max_concurrency.max(1)),
+            max_concurrency: max_concurrency.max(1),
+            groups: Mutex::new(HashMap::new()),
+            queued: Arc::new(AtomicUsize::new(0)),
+            running: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Run a command task, waiting for its conflict group (if any) then for a pool slot
+    pub fn spawn<F>(&self, conflict_group: Option<&'static str>, priority: CommandPriority, task: F) -> JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let slots = self.slots.clone();
+        let group_lock = conflict_group.map(|group| {
+            self.groups.lock().unwrap()
+                .entry(group)
+                .or_insert_with(|| Arc::new(tokio::sync::Mutex::new(())))
+                .clone()
+        });
+
+        // Counted as queued from now on, not only once the task gets polled
+        let queued_guard = CountGuard::new(&self.queued);
+        let running = self.running.clone();
+
+        tokio::spawn(async move {
+            // Group first: a command waiting on a conflicting one must not hold a pool slot
+            let _group_guard = match &group_lock {
+                Some(lock) => {
+                    debug!("Waiting for conflict group {:?}", conflict_group);
+                    Some(lock.lock().await)
+                }
+                None => None,
+            };
+            let Some(_slot) = SlotPool::acquire(&slots, priority).await else {
+                return;
+            };
+            let _running_guard = CountGuard::new(&running);
+            drop(queued_guard);
+            task.await;
+        })
+    }
+
+    /// Current queue state, as reported in heartbeats
+    pub fn backlog(&self) -> Backlog {
+        Backlog {
+            queued: self.queued.load(Ordering::SeqCst),
+            running: self.running.load(Ordering::SeqCst),
+            max_concurrency: self.max_concurrency,
+        }
+    }
+
+    /// Pool slots currently free
+    #[cfg(test)]
+    pub fn available_slots(&self) -> usize {
+        self.slots.state.lock().unwrap().free
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_slow_command_does_not_delay_fast_one() {
+        let scheduler = CommandScheduler::new(DEFAULT_MAX_CONCURRENCY);
+        let start = Instant::now();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let slow = {
+            let order = order.clone();
+            scheduler.spawn(None, CommandPriority::Normal, async move {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                order.lock().unwrap().push("slow");
+            })
+        };
+        let fast = {
+            let order = order.clone();
+            scheduler.spawn(Some("power"), CommandPriority::High, async move {
+                order.lock().unwrap().push("fast");
+            })
+        };
+
+        fast.await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(250));
+        slow.await.unwrap();
+        assert_eq!(*order.lock().unwrap(), vec!["fast", "slow"]);
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_commands_are_serialized() {
+        let scheduler = CommandScheduler::new(DEFAULT_MAX_CONCURRENCY);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_seen = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                let running = running.clone();
+                let max_seen = max_seen.clone();
+                scheduler.spawn(Some("power"), CommandPriority::High, async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_seen.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(max_seen.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrency_is_enforced() {
+        let scheduler = CommandScheduler::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_seen = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..6)
+            .map(|_| {
+                let running = running.clone();
+                let max_seen = max_seen.clone();
+                scheduler.spawn(None, CommandPriority::Normal, async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_seen.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(max_seen.load(Ordering::SeqCst), 2);
+        assert_eq!(scheduler.available_slots(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_backlog_matches_queued_and_running_commands() {
+        let scheduler = CommandScheduler::new(2);
+        assert_eq!(scheduler.backlog(), Backlog { queued: 0, running: 0, max_concurrency: 2 });
+        assert!(!scheduler.backlog().is_busy());
+
+        // Three commands gated on a release signal: two run, one waits for a slot
+        let (release, gate) = tokio::sync::watch::channel(false);
+        let spawn_gated = |group: Option<&'static str>| {
+            let mut gate = gate.clone();
+            scheduler.spawn(group, CommandPriority::Normal, async move {
+                let _ = gate.wait_for(|released| *released).await;
+            })
+        };
+        let mut handles: Vec<_> = (0..3).map(|_| spawn_gated(None)).collect();
+        // Two power commands: both wait, the second one behind the first (conflict group)
+        handles.push(spawn_gated(Some("power")));
+        handles.push(spawn_gated(Some("power")));
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while scheduler.backlog().running < 2 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let backlog = scheduler.backlog();
+        assert_eq!(backlog.running, 2);
+        assert_eq!(backlog.queued, 3);
+        assert_eq!(backlog.depth(), 5);
+        assert!(backlog.is_busy());
+
+        release.send(true).unwrap();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(scheduler.backlog().depth(), 0);
+        assert!(!scheduler.backlog().is_busy());
+    }
+
+    #[tokio::test]
+    async fn test_high_priority_command_is_dequeued_before_earlier_low_one() {
+        let scheduler = CommandScheduler::new(1);
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        // Occupy the only slot until released
+        let (release, gate) = tokio::sync::watch::channel(false);
+        let blocker = scheduler.spawn(None, CommandPriority::Normal, async move {
+            let mut gate = gate;
+            let _ = gate.wait_for(|released| *released).await;
+        });
+        let spawn_tracked = |name: &'static str, priority| {
+            let order = order.clone();
+            scheduler.spawn(None, priority, async move {
+                order.lock().unwrap().push(name);
+            })
+        };
+        let waiting = || scheduler.slots.state.lock().unwrap().waiters.len();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let metrics = spawn_tracked("metrics", CommandPriority::Low);
+        while waiting() < 1 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let shutdown = spawn_tracked("shutdown", CommandPriority::High);
+        while waiting() < 2 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(scheduler.backlog().running, 1);
+
+        release.send(true).unwrap();
+        for handle in [blocker, metrics, shutdown] {
+            handle.await.unwrap();
+        }
+        assert_eq!(*order.lock().unwrap(), vec!["shutdown", "metrics"]);
+        assert_eq!(scheduler.available_slots(), 1);
+    }
+
+    #[test]
+    fn test_priority_parses_from_command_payload() {
+        let priority: CommandPriority = serde_json::from_str("\"high\"").unwrap();
+        assert_eq!(priority, CommandPriority::High);
+        assert!(CommandPriority::High > CommandPriority::Normal);
+        assert!(CommandPriority::Normal > CommandPriority::Low);
+        assert_eq!(CommandPriority::default(), CommandPriority::Normal);
+    }
+}
@@ -3,6 +3,7 @@
 //! Features:
 //! - Check for updates from GitHub releases
 //! - Download and verify binaries
+//! - Optional certificate pinning of the update hosts (see `pinning`)
 //! - Safe replacement with rollback
 //! - Background update checks
 
@@ -47,7 +48,7 @@ impl AgentUpdater {
         
         // Get latest release from GitHub API
         let url = format!("https://api.github.com/repos/{}/{}/releases/latest", owner, repo);
-        let client = reqwest::Client::new();
+        let client = crate::pinning::http_client(&self.config.update.pinned_spki_sha256)?;
         
         let response = client
             .get(&url)
@@ -151,7 +152,7 @@ impl AgentUpdater {
     }
     
     async fn download_update(&self, url: &str) -> Result<PathBuf> {
-        let client = reqwest::Client::new();
+        let client = crate::pinning::http_client(&self.config.update.pinned_spki_sha256)?;
         let response = client.get(url).send().await?;
         
         if !response.status().is_success() {
@@ -6,7 +6,7 @@
 
 use anyhow::{Result, Context};
 use std::io::{self, Write};
-use crate::config::{AgentConfig, MqttConfig, ElevationConfig, UpdateConfig, UpdateChannel, AgentInfo};
+use crate::config::{AgentConfig, MqttConfig, ElevationConfig, UpdateConfig, UpdateChannel, AgentInfo, CommandsConfig, FileOpsConfig, NetCheckConfig, EnvConfig, FirewallConfig, PowerScheduleConfig};
 
 pub struct SetupWizard;
 
@@ -40,6 +40,12 @@ impl SetupWizard {
             elevation: elevation_config,
             update: update_config,
             agent: agent_config,
+            commands: CommandsConfig::default(),
+            file_ops: FileOpsConfig::default(),
+            net_check: NetCheckConfig::default(),
+            environment: EnvConfig::default(),
+            firewall: FirewallConfig::default(),
+            power_schedule: PowerScheduleConfig::default(),
         };
         
         // Display summary and confirm
@@ -196,6 +202,7 @@ impl SetupWizard {
             channel,
             check_interval_hours,
             github_repo,
+            pinned_spki_sha256: Vec::new(),
         })
     }
     
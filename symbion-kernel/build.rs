@@ -0,0 +1,30 @@
+/*!
+Script de build du kernel : commit git embarqué pour GET /version
+
+`SYMBION_GIT_COMMIT` vaut le hash court du commit construit, ou `unknown` hors d'un
+dépôt git (archive source, git absent).
+*/
+
+use std::path::Path;
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn main() {
+    let commit = git(&["rev-parse", "--short=12", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=SYMBION_GIT_COMMIT={}", commit);
+
+    // Recalcul à chaque commit ou changement de branche, pas à chaque build
+    for head in ["../.git/HEAD", "../.git/refs/heads"] {
+        if Path::new(head).exists() {
+            println!("cargo:rerun-if-changed={}", head);
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
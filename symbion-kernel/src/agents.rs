@@ -4,18 +4,27 @@
  * RÔLE : Registration, persistance, télémétrie et contrôle des agents multi-OS.
  * Système de contrôle à distance avec Wake-on-LAN, power management, processus.
  * 
- * ARCHITECTURE : Registry agents avec persistance JSON + MQTT events + API REST.
+ * ARCHITECTURE : Registry agents avec persistance JSON ou MessagePack + MQTT events + API REST.
  * UTILITÉ : Contrôle infrastructure réseau local depuis dashboard centralisé.
  */
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use time::OffsetDateTime;
 use tokio::sync::RwLock;
 use std::sync::Arc;
 use rumqttc::AsyncClient;
 use uuid::Uuid;
 use anyhow::Result;
+use crate::commands::{AgentCommandResponse, CommandTracker};
+use crate::contracts::{ContractRegistry, PublishSettings};
+use crate::config::{AgentMonitoringConf, CommandCacheConf, CommandRateLimitConf, CommandResultsConf, DuplicateAgentPolicy, FlappingConf, HeartbeatSections};
+use crate::rate_limit::CommandRateLimiter;
+use crate::availability::{AvailabilityLog, AvailabilityReport};
+use crate::flapping::{FlappingAlert, FlappingTracker, LivenessStats};
+use crate::persistence::{self, PersistFormat};
+use crate::outbox::{Delivery, SharedOutbox};
+use crate::registry_integrity::{self, IntegrityReport};
 
 // Structures basées sur les contrats agents.registration@v1 et agents.heartbeat@v1
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,20 +33,56 @@ pub struct Agent {
     pub hostname: String,
     pub os: String,                 // linux, windows, android, macos
     pub architecture: String,       // x86_64, aarch64, arm, i686
+    #[serde(default)]
+    pub os_details: Option<AgentOsDetails>,
     pub capabilities: Vec<String>,  // power_management, process_control, etc.
+    /// Capacités absentes et leur raison (agents récents), pour expliquer les manques
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unavailable_capabilities: Vec<UnavailableCapability>,
     pub network: AgentNetwork,
     pub version: Option<String>,
+    /// Version du schéma de heartbeat déclarée à la registration
+    #[serde(default = "crate::heartbeat_versions::default_heartbeat_version")]
+    pub heartbeat_version: String,
     pub status: AgentStatus,
     pub last_seen: OffsetDateTime,
     pub registration_time: OffsetDateTime,
 }
 
+/// Capacité non disponible sur l'agent, avec la raison détectée ("No NVIDIA or AMD GPU found")
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnavailableCapability {
+    pub capability: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Détails OS remontés par l'agent (kernel, distribution, build Windows)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentOsDetails {
+    pub kernel_version: Option<String>,
+    pub distro_name: Option<String>,     // Ubuntu, Debian GNU/Linux, Windows 11...
+    pub distro_version: Option<String>,  // 22.04, 12, 23H2...
+    pub windows_build: Option<u32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentNetwork {
     pub primary_mac: String,        // Format avec colons (ex: a1:b2:c3:d4:e5:f6)
     pub interfaces: Vec<AgentInterface>,
 }
 
+impl AgentNetwork {
+    /// IP de l'interface portant la MAC principale, à défaut celle de la première interface
+    pub fn primary_ip(&self) -> Option<&str> {
+        let primary = normalize_mac(&self.primary_mac);
+        self.interfaces.iter()
+            .find(|i| normalize_mac(&i.mac) == primary)
+            .or_else(|| self.interfaces.first())
+            .map(|i| i.ip.as_str())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentInterface {
     pub name: String,               // eth0, wlan0, etc.
@@ -45,6 +90,11 @@ pub struct AgentInterface {
     pub ip: String,
     #[serde(rename = "type")]
     pub interface_type: String,     // ethernet, wireless, loopback, other
+    /// Débit du lien en Mb/s (absent : inconnu, lien coupé ou agent ancien)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed_mbps: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,6 +104,33 @@ pub struct AgentStatus {
     pub system: Option<AgentSystemMetrics>,
     pub processes: Option<AgentProcesses>,
     pub services: Option<Vec<AgentService>>,
+    /// Commandes en file ou en cours sur l'agent (dernier heartbeat)
+    #[serde(default)]
+    pub queue_depth: Option<u32>,
+    /// Agent saturé : plus de slot libre ou commandes en attente
+    #[serde(default)]
+    pub busy: Option<bool>,
+    /// Avance de l'horloge agent sur le kernel au dernier heartbeat, en secondes (négatif : retard)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_skew_secs: Option<i64>,
+    /// Écart au-delà de agent_monitoring.clock_skew_threshold_secs : horodatages agent peu fiables
+    #[serde(default)]
+    pub clock_skewed: bool,
+    /// Dernières commandes exécutées par l'agent, la plus récente en tête (dernier heartbeat)
+    #[serde(default)]
+    pub recent_commands: Vec<AgentRecentCommand>,
+}
+
+/// Nombre maximal de commandes récentes conservées par agent
+pub const MAX_RECENT_COMMANDS: usize = 10;
+
+/// Commande récente rapportée par l'agent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentRecentCommand {
+    pub command_id: String,
+    pub command_type: String,
+    pub status: String,
+    pub timestamp: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,6 +141,9 @@ pub struct AgentSystemMetrics {
     pub disk: Option<Vec<AgentDiskMetrics>>,
     pub network: Option<AgentNetworkMetrics>,
     pub temperature: Option<AgentTemperatureMetrics>,
+    /// Agents avec la capacité gpu_metrics (absent sans GPU)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gpu: Option<Vec<AgentGpuMetrics>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,6 +172,9 @@ pub struct AgentDiskMetrics {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentNetworkMetrics {
+    /// Durée couverte par les compteurs, en secondes (agents récents : trafic depuis le heartbeat précédent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_secs: Option<f64>,
     pub interfaces: Vec<AgentNetworkInterface>,
 }
 
@@ -105,6 +188,19 @@ pub struct AgentNetworkInterface {
     pub is_up: bool,
 }
 
+/// Usage d'un GPU (nvidia-smi ou sysfs amdgpu côté agent)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentGpuMetrics {
+    pub index: u32,
+    pub vendor: String,             // nvidia, amd
+    pub name: String,
+    pub utilization_percent: Option<f32>,
+    pub memory_used_mb: Option<u64>,
+    pub memory_total_mb: Option<u64>,
+    pub temperature_celsius: Option<f32>,
+    pub power_watts: Option<f32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentTemperatureMetrics {
     pub cpu_celsius: Option<f32>,
@@ -152,22 +248,8 @@ pub struct AgentCommand {
     pub parameters: Option<serde_json::Value>,
     pub timeout_seconds: Option<u32>,
     pub timestamp: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct AgentCommandResponse {
-    #[allow(dead_code)]
-    pub command_id: String,
-    #[allow(dead_code)]
-    pub agent_id: String,
-    #[allow(dead_code)]
-    pub status: String,             // success, error, timeout
-    #[allow(dead_code)]
-    pub result: Option<serde_json::Value>,
-    #[allow(dead_code)]
-    pub error_message: Option<String>,
-    #[allow(dead_code)]
-    pub timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,   // low, normal, high ; absent : priorité par défaut du type de commande, côté agent
 }
 
 // Messages MQTT entrants (agent → kernel)
@@ -177,9 +259,16 @@ pub struct AgentRegistrationMessage {
     pub hostname: String,
     pub os: String,
     pub architecture: String,
+    #[serde(default)]
+    pub os_details: Option<AgentOsDetails>,
     pub capabilities: Vec<String>,
+    #[serde(default)]
+    pub unavailable_capabilities: Vec<UnavailableCapability>,
     pub network: AgentNetwork,
     pub version: Option<String>,
+    /// Version du schéma de heartbeat parlée par l'agent (absente : v1)
+    #[serde(default = "crate::heartbeat_versions::default_heartbeat_version")]
+    pub heartbeat_version: String,
     #[allow(dead_code)]
     pub timestamp: String,
 }
@@ -191,9 +280,21 @@ pub struct AgentHeartbeatMessage {
     pub system: AgentSystemMetrics,
     pub processes: Option<AgentProcesses>,
     pub services: Option<Vec<AgentService>>,
+    #[serde(default)]
+    pub os_details: Option<AgentOsDetails>,
+    #[serde(default)]
+    pub queue_depth: Option<u32>,
+    #[serde(default)]
+    pub busy: Option<bool>,
+    /// Interfaces courantes (agents récents) : suit les changements d'IP entre deux registrations
+    #[serde(default)]
+    pub network: Option<AgentNetwork>,
     #[allow(dead_code)]
     pub last_command: Option<AgentLastCommand>,
-    #[allow(dead_code)]
+    /// Agents récents : dernières commandes, la plus récente en tête
+    #[serde(default)]
+    pub recent_commands: Vec<AgentRecentCommand>,
+    /// Horloge de l'agent à l'envoi (comparée à la réception pour détecter les dérives)
     pub timestamp: String,
 }
 
@@ -211,10 +312,311 @@ pub struct AgentLastCommand {
 
 pub type AgentsMap = HashMap<String, Agent>;
 
+/// Commandes dont une double exécution serait visible (extinction répétée, double reboot)
+const EXACTLY_ONCE_COMMANDS: &[&str] = &["shutdown", "reboot", "hibernate"];
+
+/// QoS MQTT par défaut d'une commande : ExactlyOnce pour l'alimentation, AtLeastOnce sinon
+pub fn command_qos(command_type: &str) -> rumqttc::QoS {
+    if EXACTLY_ONCE_COMMANDS.contains(&command_type) {
+        rumqttc::QoS::ExactlyOnce
+    } else {
+        rumqttc::QoS::AtLeastOnce
+    }
+}
+
+/// Délai d'exécution d'une commande agent sans surcharge
+pub const DEFAULT_COMMAND_TIMEOUT_SECONDS: u32 = 30;
+/// Délai maximal accepté en surcharge (?timeout_secs=, mises à jour de paquets)
+pub const MAX_COMMAND_TIMEOUT_SECONDS: u32 = 3600;
+
+/// Échec de publication d'une commande (broker), distinct d'un refus de l'agent
+#[derive(Debug, thiserror::Error)]
+pub enum CommandSendError {
+    #[error("MQTT client not configured")]
+    MqttNotConfigured,
+    #[error("MQTT publish failed: {0}")]
+    Publish(String),
+}
+
+/// Priorités acceptées par l'ordonnanceur de l'agent
+pub const COMMAND_PRIORITIES: &[&str] = &["low", "normal", "high"];
+
+/// Commandes réservées aux appels admin (x-admin-key) : pare-feu de l'hôte
+const ADMIN_COMMANDS: &[&str] = &["firewall_list", "firewall_add", "firewall_remove"];
+
+/// Commande qui exige la clé admin, quelle que soit la route qui l'envoie
+pub fn requires_admin(command_type: &str) -> bool {
+    ADMIN_COMMANDS.contains(&command_type)
+}
+
+/// Demande de re-registration immédiate adressée à tous les agents
+pub const ANNOUNCE_TOPIC: &str = symbion_topics::agents_announce();
+
+/// Publie une demande d'annonce (kernel redémarré avec un registre vide) ; retourne son request_id
+pub fn request_announce(publisher: &dyn crate::mqtt_publish::MqttPublisher) -> Result<String> {
+    let request_id = Uuid::new_v4().to_string();
+    let payload = serde_json::json!({
+        "request_id": request_id,
+        "timestamp": OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339)?,
+    });
+    publisher
+        .publish(ANNOUNCE_TOPIC, rumqttc::QoS::AtLeastOnce, false, payload.to_string().into_bytes())
+        .map_err(|e| anyhow::anyhow!(e))?;
+    println!("[agents] announce requested ({})", request_id);
+    Ok(request_id)
+}
+
+/// Sections des heartbeats demandées aux agents
+pub const HEARTBEAT_CONFIG_TOPIC: &str = symbion_topics::agents_heartbeat_config();
+
+/// Publie les sections attendues dans les heartbeats. Sans agent_id : diffusion retenue à tous
+/// les agents (heartbeat_sections de kernel.yaml) ; avec : demande ponctuelle non retenue,
+/// valable jusqu'à la prochaine diffusion
+pub fn publish_heartbeat_sections(publisher: &dyn crate::mqtt_publish::MqttPublisher, agent_id: Option<&str>, sections: HeartbeatSections) -> Result<()> {
+    let payload = serde_json::json!({
+        "agent_id": agent_id,
+        "sections": sections,
+        "timestamp": OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339)?,
+    });
+    publisher
+        .publish(HEARTBEAT_CONFIG_TOPIC, rumqttc::QoS::AtLeastOnce, agent_id.is_none(), payload.to_string().into_bytes())
+        .map_err(|e| anyhow::anyhow!(e))?;
+    println!("[agents] heartbeat sections {:?} sent to {}", sections, agent_id.unwrap_or("all agents"));
+    Ok(())
+}
+
+/// Agrégats du parc d'agents (GET /agents/summary)
+#[derive(Debug, Default, Serialize)]
+pub struct AgentsSummary {
+    pub total: usize,
+    pub by_os: BTreeMap<String, usize>,
+    pub by_status: BTreeMap<String, usize>,
+    pub by_capability: BTreeMap<String, usize>,
+    /// Agents signalant une file de commandes saturée (busy)
+    pub overloaded: usize,
+    pub total_cpu_cores: u64,
+    pub total_memory_mb: u64,
+}
+
+impl AgentsSummary {
+    /// Calcule les agrégats en une seule passe sur les agents
+    pub fn from_agents(agents: &AgentsMap) -> Self {
+        let mut summary = Self { total: agents.len(), ..Self::default() };
+
+        for agent in agents.values() {
+            *summary.by_os.entry(agent.os.clone()).or_default() += 1;
+            *summary.by_status.entry(agent.status.status.clone()).or_default() += 1;
+            if agent.status.busy == Some(true) && agent.status.status != "offline" {
+                summary.overloaded += 1;
+            }
+            for capability in &agent.capabilities {
+                *summary.by_capability.entry(capability.clone()).or_default() += 1;
+            }
+            if let Some(system) = &agent.status.system {
+                summary.total_cpu_cores += system.cpu.core_count.unwrap_or(0) as u64;
+                summary.total_memory_mb += system.memory.total_mb;
+            }
+        }
+
+        summary
+    }
+}
+
+/// Dernières métriques d'un agent dans la réponse groupée (GET /agents/metrics)
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentMetricsEntry {
+    pub hostname: String,
+    pub status: String,
+    /// RFC3339
+    pub last_heartbeat: Option<String>,
+    /// None tant que l'agent n'a envoyé aucun heartbeat
+    pub system: Option<AgentSystemMetrics>,
+}
+
+/// Métriques des agents retenus par `filter`, indexées par agent_id
+pub fn bulk_metrics(agents: &AgentsMap, filter: impl Fn(&Agent) -> bool) -> BTreeMap<String, AgentMetricsEntry> {
+    agents.values()
+        .filter(|agent| filter(agent))
+        .map(|agent| (agent.agent_id.clone(), AgentMetricsEntry {
+            hostname: agent.hostname.clone(),
+            status: agent.status.status.clone(),
+            last_heartbeat: agent.status.last_heartbeat.and_then(|t| t.format(&time::format_description::well_known::Rfc3339).ok()),
+            system: agent.status.system.clone(),
+        }))
+        .collect()
+}
+
+/// Poids du CPU et de la RAM dans le score de charge (normalisés par leur somme)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct LoadWeights {
+    #[serde(default = "default_load_weight")]
+    pub cpu: f32,
+    #[serde(default = "default_load_weight")]
+    pub memory: f32,
+}
+
+fn default_load_weight() -> f32 {
+    0.5
+}
+
+impl Default for LoadWeights {
+    fn default() -> Self {
+        Self { cpu: default_load_weight(), memory: default_load_weight() }
+    }
+}
+
+impl LoadWeights {
+    /// Poids positifs ou nuls, au moins un non nul
+    pub fn is_valid(&self) -> bool {
+        self.cpu >= 0.0 && self.memory >= 0.0 && self.cpu + self.memory > 0.0
+    }
+
+    /// Score 0-100 : moyenne pondérée des pourcentages CPU et RAM
+    pub fn score(&self, cpu_percent: f32, memory_percent: f32) -> f32 {
+        (self.cpu * cpu_percent + self.memory * memory_percent) / (self.cpu + self.memory)
+    }
+}
+
+/// Agent candidat à une commande de groupe, avec sa charge au dernier heartbeat
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadCandidate {
+    pub agent_id: String,
+    pub hostname: String,
+    pub score: f32,
+    pub cpu_percent: f32,
+    pub memory_percent: f32,
+    /// Agent saturé (plus de slot libre) au dernier heartbeat
+    pub busy: bool,
+}
+
+/// Agents joignables ayant des métriques, du moins chargé au plus chargé
+/// Les agents saturés passent après les autres ; à score égal, l'agent_id le plus petit d'abord
+pub fn rank_by_load<'a>(agents: impl IntoIterator<Item = &'a Agent>, weights: LoadWeights) -> Vec<LoadCandidate> {
+    let mut candidates: Vec<LoadCandidate> = agents.into_iter()
+        .filter(|agent| !matches!(agent.status.status.as_str(), "offline" | "maintenance"))
+        .filter_map(|agent| {
+            let system = agent.status.system.as_ref()?;
+            Some(LoadCandidate {
+                agent_id: agent.agent_id.clone(),
+                hostname: agent.hostname.clone(),
+                score: weights.score(system.cpu.percent, system.memory.percent_used),
+                cpu_percent: system.cpu.percent,
+                memory_percent: system.memory.percent_used,
+                busy: agent.status.busy.unwrap_or(false),
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        a.busy.cmp(&b.busy)
+            .then(a.score.total_cmp(&b.score))
+            .then_with(|| a.agent_id.cmp(&b.agent_id))
+    });
+    candidates
+}
+
+pub const NETWORK_CHANGED_TOPIC: &str = symbion_topics::agents_network_changed();
+
+/// Événement agents.network_changed@v1 : IP principale ou hostname modifié
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentNetworkChanged {
+    pub agent_id: String,
+    pub hostname: String,
+    /// Présent si le hostname a changé
+    pub previous_hostname: Option<String>,
+    pub ip: Option<String>,
+    pub previous_ip: Option<String>,
+    pub primary_mac: String,
+    pub timestamp: String,
+}
+
+/// Compare l'agent enregistré à son hostname et ses interfaces actuels
+pub fn network_change(existing: &Agent, hostname: &str, network: &AgentNetwork, at: OffsetDateTime) -> Option<AgentNetworkChanged> {
+    let previous_ip = existing.network.primary_ip();
+    let ip = network.primary_ip();
+    let hostname_changed = !existing.hostname.eq_ignore_ascii_case(hostname);
+    if !hostname_changed && previous_ip == ip {
+        return None;
+    }
+    Some(AgentNetworkChanged {
+        agent_id: existing.agent_id.clone(),
+        hostname: hostname.to_string(),
+        previous_hostname: hostname_changed.then(|| existing.hostname.clone()),
+        ip: ip.map(str::to_string),
+        previous_ip: previous_ip.map(str::to_string),
+        primary_mac: network.primary_mac.clone(),
+        timestamp: at.format(&time::format_description::well_known::Rfc3339).unwrap_or_default(),
+    })
+}
+
+/// Alerte agents.alert@v1 (kind clock_skew) : horloge agent désynchronisée
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockSkewAlert {
+    pub agent_id: String,
+    pub kind: &'static str,
+    pub skew_secs: i64,
+    pub threshold: u64,
+    pub timestamp: String,
+}
+
+/// Avance de l'horloge agent sur la réception, en secondes ; None si l'horodatage est illisible.
+/// Inclut la latence de transport, négligeable devant les seuils usuels.
+pub fn clock_skew(reported: &str, received: OffsetDateTime) -> Option<i64> {
+    let reported = OffsetDateTime::parse(reported, &time::format_description::well_known::Rfc3339).ok()?;
+    Some((reported - received).whole_seconds())
+}
+
+/// Écart hors tolérance, dans un sens comme dans l'autre
+pub fn is_clock_skewed(skew_secs: i64, threshold_secs: u64) -> bool {
+    skew_secs.unsigned_abs() > threshold_secs
+}
+
+/// Compare deux MAC indépendamment du format (séparateurs, casse)
+pub(crate) fn normalize_mac(mac: &str) -> String {
+    mac.chars().filter(|c| c.is_ascii_hexdigit()).collect::<String>().to_ascii_lowercase()
+}
+
+/// Décrit le conflit si la registration provient d'une autre machine que l'agent enregistré
+/// sous le même id. Un agent offline peut être repris sans conflit (réinstallation, renommage).
+pub fn registration_conflict(existing: &Agent, msg: &AgentRegistrationMessage) -> Option<String> {
+    if existing.status.status == "offline" {
+        return None;
+    }
+    let mut differences = Vec::new();
+    if !existing.hostname.eq_ignore_ascii_case(&msg.hostname) {
+        differences.push(format!("hostname {} vs {}", existing.hostname, msg.hostname));
+    }
+    if normalize_mac(&existing.network.primary_mac) != normalize_mac(&msg.network.primary_mac) {
+        differences.push(format!("mac {} vs {}", existing.network.primary_mac, msg.network.primary_mac));
+    }
+    if differences.is_empty() { None } else { Some(differences.join(", ")) }
+}
+
 pub struct AgentRegistry {
     agents: Arc<RwLock<AgentsMap>>,
     data_file: String,
     mqtt_client: Option<AsyncClient>,
+    /// Commandes émises broker coupé, rejouées à la reconnexion (None = publication directe)
+    outbox: Option<SharedOutbox>,
+    /// Suivi des commandes envoyées et corrélation des réponses
+    commands: CommandTracker,
+    /// Traitement des agent_id revendiqués par deux machines
+    duplicate_policy: DuplicateAgentPolicy,
+    /// Écarts entre heartbeats et retours online (flapping)
+    liveness: FlappingTracker,
+    /// Format d'écriture du fichier de persistance (extension dérivée)
+    persist_format: PersistFormat,
+    /// Commandes de lecture servies depuis le cache (None = toujours envoyées)
+    command_cache: Option<CommandCacheConf>,
+    /// Débit de commandes par agent (None = illimité)
+    rate_limiter: Option<CommandRateLimiter>,
+    /// Transitions online/offline pour les rapports de disponibilité
+    availability: AvailabilityLog,
+    /// Écart d'horloge toléré avant de signaler un agent (secondes)
+    clock_skew_threshold_secs: u64,
+    /// Dernier contrôle d'intégrité du registre (None avant la première passe)
+    integrity_report: parking_lot::Mutex<Option<IntegrityReport>>,
+    /// Contrats chargés : QoS / retain déclarés pour le topic des commandes (None = défauts du code)
+    contracts: Option<ContractRegistry>,
 }
 
 impl AgentRegistry {
@@ -223,6 +625,17 @@ impl AgentRegistry {
             agents: Arc::new(RwLock::new(HashMap::new())),
             data_file: data_file.to_string(),
             mqtt_client: None,
+            outbox: None,
+            commands: CommandTracker::new(),
+            duplicate_policy: DuplicateAgentPolicy::default(),
+            liveness: FlappingTracker::new(FlappingConf::default()),
+            persist_format: PersistFormat::default(),
+            command_cache: None,
+            rate_limiter: None,
+            availability: AvailabilityLog::default(),
+            clock_skew_threshold_secs: AgentMonitoringConf::default().clock_skew_threshold_secs,
+            integrity_report: parking_lot::Mutex::new(None),
+            contracts: None,
         }
     }
 
@@ -231,81 +644,293 @@ impl AgentRegistry {
         self
     }
 
-    /// Charge les agents depuis le fichier JSON de persistance
+    pub fn with_contracts(mut self, contracts: ContractRegistry) -> Self {
+        self.contracts = Some(contracts);
+        self
+    }
+
+    pub fn with_outbox(mut self, outbox: SharedOutbox) -> Self {
+        self.outbox = Some(outbox);
+        self
+    }
+
+    pub fn with_duplicate_policy(mut self, policy: DuplicateAgentPolicy) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    pub fn with_persist_format(mut self, format: PersistFormat) -> Self {
+        self.persist_format = format;
+        self
+    }
+
+    pub fn with_flapping(mut self, conf: FlappingConf) -> Self {
+        self.liveness = FlappingTracker::new(conf);
+        self
+    }
+
+    pub fn with_command_cache(mut self, conf: CommandCacheConf) -> Self {
+        self.command_cache = Some(conf);
+        self
+    }
+
+    pub fn with_command_results(mut self, conf: CommandResultsConf) -> Self {
+        self.commands = CommandTracker::with_retention(conf);
+        self
+    }
+
+    pub fn with_clock_skew_threshold(mut self, threshold_secs: u64) -> Self {
+        self.clock_skew_threshold_secs = threshold_secs;
+        self
+    }
+
+    pub fn with_command_rate_limit(mut self, conf: CommandRateLimitConf) -> Self {
+        self.rate_limiter = conf.is_enabled().then(|| CommandRateLimiter::new(conf));
+        self
+    }
+
+    /// Persiste les transitions online/offline dans un journal JSON lines
+    pub fn with_availability_log(mut self, path: &str) -> Self {
+        match AvailabilityLog::open(path) {
+            Ok(log) => self.availability = log,
+            Err(e) => eprintln!("[agents] cannot load availability log {}: {} (history kept in memory)", path, e),
+        }
+        self
+    }
+
+    /// Disponibilité d'un agent sur les `days` derniers jours
+    pub fn availability(&self, agent_id: &str, days: u32) -> AvailabilityReport {
+        self.availability.report(agent_id, days, OffsetDateTime::now_utc())
+    }
+
+    /// Compte un retour offline → online et publie l'alerte si le seuil de flapping est franchi
+    fn note_reconnect(&self, agent_id: &str, at: OffsetDateTime) {
+        if let Some(alert) = self.liveness.record_reconnect(agent_id, at) {
+            eprintln!("[agents] agent {} is flapping ({} reconnects in {}s)", agent_id, alert.flapping_score, alert.window_secs);
+            self.publish_alert(alert);
+        }
+    }
+
+    fn publish_alert(&self, alert: FlappingAlert) {
+        self.publish_event(symbion_topics::agents_alert(), "alert", &alert.agent_id, &alert);
+    }
+
+    /// Met à jour l'écart d'horloge d'un agent ; alerte au passage hors tolérance
+    fn check_clock_skew(&self, agent: &mut Agent, reported: &str, received: OffsetDateTime) {
+        let Some(skew) = clock_skew(reported, received) else {
+            eprintln!("[agents] agent {} sent an unreadable heartbeat timestamp: {}", agent.agent_id, reported);
+            return;
+        };
+        let skewed = is_clock_skewed(skew, self.clock_skew_threshold_secs);
+        match (agent.status.clock_skewed, skewed) {
+            (false, true) => {
+                eprintln!("[agents] agent {} clock is off by {}s (threshold {}s)", agent.agent_id, skew, self.clock_skew_threshold_secs);
+                let alert = ClockSkewAlert {
+                    agent_id: agent.agent_id.clone(),
+                    kind: "clock_skew",
+                    skew_secs: skew,
+                    threshold: self.clock_skew_threshold_secs,
+                    timestamp: received.format(&time::format_description::well_known::Rfc3339).unwrap_or_default(),
+                };
+                self.publish_event(symbion_topics::agents_alert(), "alert", &alert.agent_id, &alert);
+            }
+            (true, false) => println!("[agents] agent {} clock back in sync ({}s)", agent.agent_id, skew),
+            _ => {}
+        }
+        agent.status.clock_skew_secs = Some(skew);
+        agent.status.clock_skewed = skewed;
+    }
+
+    fn publish_network_change(&self, change: AgentNetworkChanged) {
+        println!("[agents] agent {} network changed: {} {} -> {} {}",
+            change.agent_id,
+            change.previous_hostname.as_deref().unwrap_or(&change.hostname),
+            change.previous_ip.as_deref().unwrap_or("unknown"),
+            change.hostname,
+            change.ip.as_deref().unwrap_or("unknown"));
+        self.publish_event(NETWORK_CHANGED_TOPIC, "network change", &change.agent_id, &change);
+    }
+
+    /// Événement kernel → abonnés (sans effet sans client MQTT)
+    fn publish_event<T: Serialize>(&self, topic: &str, kind: &str, agent_id: &str, event: &T) {
+        let Some(mqtt_client) = &self.mqtt_client else { return };
+        match serde_json::to_string(event) {
+            Ok(payload) => {
+                if let Err(e) = mqtt_client.try_publish(topic, rumqttc::QoS::AtLeastOnce, false, payload) {
+                    eprintln!("[agents] failed to publish {} for {}: {}", kind, agent_id, e);
+                }
+            }
+            Err(e) => eprintln!("[agents] failed to serialize {}: {}", kind, e),
+        }
+    }
+
+    /// Demande à un agent d'inclure (ou d'omettre) des sections dans ses heartbeats
+    /// Broker absent ou publication refusée : erreur `CommandSendError`
+    pub fn request_heartbeat_sections(&self, agent_id: &str, sections: HeartbeatSections) -> Result<()> {
+        let mqtt_client = self.mqtt_client.as_ref().ok_or(CommandSendError::MqttNotConfigured)?;
+        publish_heartbeat_sections(mqtt_client, Some(agent_id), sections)
+            .map_err(|e| CommandSendError::Publish(e.to_string()).into())
+    }
+
+    /// Écarts entre heartbeats et score de flapping d'un agent
+    pub fn liveness(&self, agent_id: &str) -> Option<LivenessStats> {
+        self.liveness.stats(agent_id, OffsetDateTime::now_utc())
+    }
+
+    /// Id sous lequel enregistrer l'agent, selon la politique de doublons
+    fn resolve_agent_id(&self, agents: &AgentsMap, msg: &AgentRegistrationMessage) -> Result<String> {
+        let conflict = match agents.get(&msg.agent_id).and_then(|existing| registration_conflict(existing, msg)) {
+            Some(conflict) => conflict,
+            None => return Ok(msg.agent_id.clone()),
+        };
+
+        match self.duplicate_policy {
+            DuplicateAgentPolicy::Reject => {
+                eprintln!("[agents] duplicate agent_id {} ({}) - registration rejected", msg.agent_id, conflict);
+                Err(anyhow::anyhow!("agent_id {} already registered by another machine", msg.agent_id))
+            }
+            DuplicateAgentPolicy::Rename => {
+                // Réutilise le suffixe déjà attribué à cette machine, sinon le premier libre
+                let renamed = (2..)
+                    .map(|n| format!("{}-{}", msg.agent_id, n))
+                    .find(|candidate| agents.get(candidate).is_none_or(|a| registration_conflict(a, msg).is_none()))
+                    .expect("unbounded suffix range");
+                eprintln!("[agents] duplicate agent_id {} ({}) - registered as {}", msg.agent_id, conflict, renamed);
+                Ok(renamed)
+            }
+        }
+    }
+
+    /// Charge les agents depuis le fichier de persistance (JSON ou MessagePack, détecté au contenu)
     pub async fn load_agents(&mut self) -> Result<()> {
-        if !std::path::Path::new(&self.data_file).exists() {
+        let Some(path) = persistence::existing_path(&self.data_file, self.persist_format) else {
             println!("[agents] no existing agents file, starting fresh");
             return Ok(());
-        }
+        };
 
-        let content = tokio::fs::read_to_string(&self.data_file).await?;
-        let agents: AgentsMap = serde_json::from_str(&content)?;
+        let content = tokio::fs::read(&path).await?;
+        let agents: AgentsMap = persistence::decode(&content)?;
         
         let mut agents_map = self.agents.write().await;
         *agents_map = agents;
         
-        println!("[agents] loaded {} agents from {}", agents_map.len(), self.data_file);
+        println!("[agents] loaded {} agents from {}", agents_map.len(), path.display());
         Ok(())
     }
 
-    /// Sauvegarde les agents dans le fichier JSON
+    /// Sauvegarde les agents dans le format configuré
     pub async fn save_agents(&self) -> Result<()> {
         let agents_map = self.agents.read().await;
-        let content = serde_json::to_string_pretty(&*agents_map)?;
-        tokio::fs::write(&self.data_file, content).await?;
+        let content = persistence::encode(&*agents_map, self.persist_format)?;
+        tokio::fs::write(persistence::path_for(&self.data_file, self.persist_format), content).await?;
         Ok(())
     }
 
-    /// Traite un message de registration d'agent
-    pub async fn handle_agent_registration(&self, msg: AgentRegistrationMessage) -> Result<()> {
+    /// Traite un message de registration d'agent ; retourne l'id effectivement enregistré
+    pub async fn handle_agent_registration(&self, msg: AgentRegistrationMessage) -> Result<String> {
+        if !crate::heartbeat_versions::is_supported(&msg.heartbeat_version) {
+            eprintln!("[agents] agent {} speaks heartbeat {} - registration rejected", msg.agent_id, msg.heartbeat_version);
+            return Err(anyhow::anyhow!("unsupported heartbeat version {}", msg.heartbeat_version));
+        }
         let now = OffsetDateTime::now_utc();
+        let mut agents_map = self.agents.write().await;
+        let agent_id = self.resolve_agent_id(&agents_map, &msg)?;
+        if agents_map.get(&agent_id).is_some_and(|a| a.status.status == "offline") {
+            self.note_reconnect(&agent_id, now);
+        }
+        let changed = agents_map.get(&agent_id)
+            .and_then(|existing| network_change(existing, &msg.hostname, &msg.network, now));
+        // L'état d'horloge suit l'agent : pas de nouvelle alerte à chaque re-registration
+        let (clock_skew_secs, clock_skewed) = agents_map.get(&agent_id)
+            .map(|existing| (existing.status.clock_skew_secs, existing.status.clock_skewed))
+            .unwrap_or_default();
+        let recent_commands = agents_map.get(&agent_id)
+            .map(|existing| existing.status.recent_commands.clone())
+            .unwrap_or_default();
+        self.availability.record(&agent_id, true, now);
         
         let agent = Agent {
-            agent_id: msg.agent_id.clone(),
+            agent_id: agent_id.clone(),
             hostname: msg.hostname,
             os: msg.os,
             architecture: msg.architecture,
+            os_details: msg.os_details,
             capabilities: msg.capabilities,
+            unavailable_capabilities: msg.unavailable_capabilities,
             network: msg.network,
             version: msg.version,
+            heartbeat_version: msg.heartbeat_version,
             status: AgentStatus {
                 status: "online".to_string(),
                 last_heartbeat: Some(now),
                 system: None,
                 processes: None,
                 services: None,
+                queue_depth: None,
+                busy: None,
+                clock_skew_secs,
+                clock_skewed,
+                recent_commands,
             },
             last_seen: now,
             registration_time: now,
         };
 
         let hostname = agent.hostname.clone();
-        
-        {
-            let mut agents_map = self.agents.write().await;
-            agents_map.insert(msg.agent_id.clone(), agent);
-        }
+        agents_map.insert(agent_id.clone(), agent);
+        drop(agents_map);
 
         if let Err(e) = self.save_agents().await {
             eprintln!("[agents] failed to save agents after registration: {}", e);
         }
+        if let Some(change) = changed {
+            self.publish_network_change(change);
+        }
 
-        println!("[agents] registered agent {} ({})", msg.agent_id, hostname);
-        Ok(())
+        println!("[agents] registered agent {} ({})", agent_id, hostname);
+        Ok(agent_id)
+    }
+
+    /// Version de heartbeat déclarée par un agent (v1 pour un agent inconnu)
+    pub async fn heartbeat_version(&self, agent_id: &str) -> String {
+        self.agents.read().await.get(agent_id)
+            .map(|agent| agent.heartbeat_version.clone())
+            .unwrap_or_else(crate::heartbeat_versions::default_heartbeat_version)
     }
 
     /// Traite un message de heartbeat d'agent
     pub async fn handle_agent_heartbeat(&self, msg: AgentHeartbeatMessage) -> Result<()> {
         let now = OffsetDateTime::now_utc();
-        
+        let mut changed = None;
+
         {
             let mut agents_map = self.agents.write().await;
             if let Some(agent) = agents_map.get_mut(&msg.agent_id) {
+                if agent.status.status == "offline" {
+                    self.note_reconnect(&msg.agent_id, now);
+                }
+                self.liveness.record_heartbeat(&msg.agent_id, now);
+                self.availability.record(&msg.agent_id, true, now);
                 agent.status.status = msg.status;
                 agent.status.last_heartbeat = Some(now);
                 agent.status.system = Some(msg.system);
                 agent.status.processes = msg.processes;
                 agent.status.services = msg.services;
+                agent.status.queue_depth = msg.queue_depth;
+                agent.status.busy = msg.busy;
+                // Liste vide (agent redémarré ou ancien) : l'historique connu est conservé
+                if !msg.recent_commands.is_empty() {
+                    agent.status.recent_commands = msg.recent_commands;
+                    agent.status.recent_commands.truncate(MAX_RECENT_COMMANDS);
+                }
+                self.check_clock_skew(agent, &msg.timestamp, now);
+                if msg.os_details.is_some() {
+                    agent.os_details = msg.os_details;
+                }
+                if let Some(network) = msg.network {
+                    changed = network_change(agent, &agent.hostname, &network, now);
+                    agent.network = network;
+                }
                 agent.last_seen = now;
             } else {
                 println!("[agents] received heartbeat from unknown agent {}", msg.agent_id);
@@ -315,6 +940,12 @@ impl AgentRegistry {
 
         // Sauvegarde périodique moins fréquente (on ne sauvegarde pas chaque heartbeat)
         // La sauvegarde sera fait par un job périodique ou lors d'events importants
+        if let Some(change) = changed {
+            if let Err(e) = self.save_agents().await {
+                eprintln!("[agents] failed to save agents after network change: {}", e);
+            }
+            self.publish_network_change(change);
+        }
         Ok(())
     }
 
@@ -323,6 +954,22 @@ impl AgentRegistry {
         self.agents.read().await.clone()
     }
 
+    /// Agrégats par OS, statut et capacité
+    pub async fn summary(&self) -> AgentsSummary {
+        AgentsSummary::from_agents(&*self.agents.read().await)
+    }
+
+    /// Dernières métriques de tous les agents retenus par `filter`, en une lecture du registre
+    pub async fn metrics_snapshot(&self, filter: impl Fn(&Agent) -> bool) -> BTreeMap<String, AgentMetricsEntry> {
+        bulk_metrics(&*self.agents.read().await, filter)
+    }
+
+    /// Agent le moins chargé parmi ceux retenus par `filter` (None : aucun candidat avec métriques)
+    pub async fn least_loaded(&self, filter: impl Fn(&Agent) -> bool, weights: LoadWeights) -> Option<LoadCandidate> {
+        let agents = self.agents.read().await;
+        rank_by_load(agents.values().filter(|agent| filter(agent)), weights).into_iter().next()
+    }
+
     /// Obtient le nombre d'agents de façon synchrone (pour health check)
     pub fn agents_count(&self) -> u32 {
         self.agents.try_read().map(|agents| agents.len() as u32).unwrap_or(0)
@@ -335,6 +982,62 @@ impl AgentRegistry {
 
     /// Envoie une commande à un agent via MQTT
     pub async fn send_command(&self, agent_id: &str, command_type: &str, parameters: Option<serde_json::Value>) -> Result<String> {
+        self.dispatch_command(agent_id, command_type, parameters, None, DEFAULT_COMMAND_TIMEOUT_SECONDS, None).await
+    }
+
+    /// Envoie une commande avec un QoS MQTT explicite (prioritaire sur le contrat) ; réservé aux tests
+    #[cfg(test)]
+    pub async fn send_command_with_qos(&self, agent_id: &str, command_type: &str, parameters: Option<serde_json::Value>, qos: rumqttc::QoS) -> Result<String> {
+        self.dispatch_command(agent_id, command_type, parameters, Some(qos), DEFAULT_COMMAND_TIMEOUT_SECONDS, None).await
+    }
+
+    /// Envoie une commande avec un délai d'exécution choisi par l'appelant (défaut si `None`)
+    /// Le délai est transmis à l'agent et borne l'attente de la réponse côté kernel
+    pub async fn send_command_with_timeout(&self, agent_id: &str, command_type: &str, parameters: Option<serde_json::Value>, timeout_seconds: Option<u32>) -> Result<String> {
+        self.send_command_with_priority(agent_id, command_type, parameters, timeout_seconds, None).await
+    }
+
+    /// Comme `send_command_with_timeout`, avec une priorité imposée à l'ordonnanceur de l'agent
+    /// Sans priorité, le champ n'est pas envoyé : l'agent applique celle du type de commande
+    pub async fn send_command_with_priority(&self, agent_id: &str, command_type: &str, parameters: Option<serde_json::Value>, timeout_seconds: Option<u32>, priority: Option<&str>) -> Result<String> {
+        let timeout_seconds = timeout_seconds.unwrap_or(DEFAULT_COMMAND_TIMEOUT_SECONDS);
+        self.dispatch_command(agent_id, command_type, parameters, None, timeout_seconds, priority).await
+    }
+
+    /// QoS / retain d'une commande : `qos` explicite > contrat agents.command > défaut du type de commande
+    /// Jamais retenue (rejouée à chaque reconnexion d'agent) ; le contrat ne descend jamais sous le
+    /// QoS par défaut d'une commande d'alimentation
+    fn command_publish_settings(&self, command_type: &str, qos: Option<rumqttc::QoS>) -> PublishSettings {
+        let defaults = PublishSettings { qos: command_qos(command_type), retain: false };
+        let contract_qos = match &self.contracts {
+            Some(contracts) => contracts.publish_settings(symbion_topics::agents_command(), defaults).qos,
+            None => defaults.qos,
+        };
+        let floor = if EXACTLY_ONCE_COMMANDS.contains(&command_type) { defaults.qos } else { rumqttc::QoS::AtMostOnce };
+        let contract_qos = if (contract_qos as u8) < (floor as u8) { floor } else { contract_qos };
+        PublishSettings { qos: qos.unwrap_or(contract_qos), retain: false }
+    }
+
+    /// Commande de lecture identique récente : renvoie son command_id sans la renvoyer à l'agent
+    /// Débit de l'agent dépassé : erreur `RateLimited` (rien n'est publié)
+    /// Broker absent ou publication refusée : erreur `CommandSendError` (avec outbox : mise en file)
+    async fn dispatch_command(&self, agent_id: &str, command_type: &str, parameters: Option<serde_json::Value>, qos: Option<rumqttc::QoS>, timeout_seconds: u32, priority: Option<&str>) -> Result<String> {
+        let cache = self.command_cache.as_ref()
+            .filter(|conf| conf.applies_to(command_type))
+            .map(|conf| (crate::commands::cache_key(agent_id, command_type, parameters.as_ref()), conf.ttl_secs));
+        if let Some((key, _)) = &cache {
+            if let Some(command_id) = self.commands.cached(key, OffsetDateTime::now_utc()) {
+                println!("[agents] {} for agent {} served from cache (command {})", command_type, agent_id, command_id);
+                return Ok(command_id);
+            }
+        }
+        if let Some(limiter) = &self.rate_limiter {
+            if let Err(limited) = limiter.try_acquire(agent_id, std::time::Instant::now()) {
+                eprintln!("[agents] {} to agent {} rejected: {}", command_type, agent_id, limited);
+                return Err(limited.into());
+            }
+        }
+
         let command_id = Uuid::new_v4().to_string();
         
         let command = AgentCommand {
@@ -342,28 +1045,68 @@ impl AgentRegistry {
             agent_id: agent_id.to_string(),
             command_type: command_type.to_string(),
             parameters,
-            timeout_seconds: Some(30),
+            timeout_seconds: Some(timeout_seconds),
             timestamp: OffsetDateTime::now_utc().format(&time::format_description::well_known::Iso8601::DEFAULT)?,
+            priority: priority.map(str::to_string),
         };
 
         if let Some(mqtt_client) = &self.mqtt_client {
-            let topic = "symbion/agents/command@v1";
+            let topic = symbion_topics::agents_command();
             let payload = serde_json::to_string(&command)?;
+            let PublishSettings { qos, retain } = self.command_publish_settings(command_type, qos);
             
-            mqtt_client.publish(topic, rumqttc::QoS::AtLeastOnce, false, payload).await?;
-            println!("[agents] sent command {} to agent {}: {}", command_id, agent_id, command_type);
+            // Suivi avant publication : la réponse peut arriver avant le retour de publish
+            self.commands.track(&command_id, agent_id, command_type, timeout_seconds);
+            if let Some((key, ttl_secs)) = cache {
+                let expires_at = OffsetDateTime::now_utc() + time::Duration::seconds(ttl_secs as i64);
+                self.commands.remember(key, &command_id, expires_at);
+            }
+            if let Some(outbox) = &self.outbox {
+                // Jamais rejouée après son timeout : l'appelant a déjà reçu l'expiration
+                let ttl = time::Duration::seconds(timeout_seconds as i64);
+                if outbox.send(mqtt_client, topic, qos, retain, payload, Some(ttl)) == Delivery::Queued {
+                    println!("[agents] broker unavailable, command {} to agent {} queued in outbox: {}", command_id, agent_id, command_type);
+                    return Ok(command_id);
+                }
+            } else if let Err(e) = mqtt_client.publish(topic, qos, retain, payload).await {
+                self.commands.forget(&command_id);
+                self.refund_rate_limit(agent_id);
+                return Err(CommandSendError::Publish(e.to_string()).into());
+            }
+            println!("[agents] sent command {} to agent {}: {} ({:?})", command_id, agent_id, command_type, qos);
             
             Ok(command_id)
         } else {
-            Err(anyhow::anyhow!("MQTT client not configured"))
+            self.refund_rate_limit(agent_id);
+            Err(CommandSendError::MqttNotConfigured.into())
         }
     }
 
+    /// Commande jamais publiée : son jeton de débit est rendu à l'agent
+    fn refund_rate_limit(&self, agent_id: &str) {
+        if let Some(limiter) = &self.rate_limiter {
+            limiter.refund(agent_id);
+        }
+    }
+
+    /// Traite une réponse d'agent à une commande (corrélation par command_id)
+    pub fn handle_command_response(&self, response: AgentCommandResponse) {
+        println!("[agents] received response for command {} from agent {}: {}",
+                 response.command_id, response.agent_id, response.status);
+        self.commands.resolve(response);
+    }
+
+    /// Accès au suivi des commandes (résultats, long-poll)
+    pub fn commands(&self) -> &CommandTracker {
+        &self.commands
+    }
+
     /// Marque un agent comme offline après timeout
     pub async fn mark_agent_offline(&self, agent_id: &str) {
         let mut agents_map = self.agents.write().await;
         if let Some(agent) = agents_map.get_mut(agent_id) {
             agent.status.status = "offline".to_string();
+            self.availability.record(agent_id, false, OffsetDateTime::now_utc());
             println!("[agents] marked agent {} as offline", agent_id);
         }
     }
@@ -395,42 +1138,836 @@ impl AgentRegistry {
         Ok(())
     }
 
+    /// Marque offline les agents online sans heartbeat depuis timeout + grâce ;
+    /// ceux encore dans la période de grâce restent online. Retourne les agents passés offline.
+    pub async fn check_liveness(&self, now: OffsetDateTime, conf: &AgentMonitoringConf) -> Vec<String> {
+        let timeout_threshold = now - time::Duration::seconds(conf.offline_timeout_secs as i64);
+        let offline_threshold = timeout_threshold - time::Duration::seconds(conf.grace_secs as i64);
+        let mut agents_to_mark_offline = Vec::new();
+
+        // Identifier les agents qui ont timeout
+        {
+            let agents_map = self.agents.read().await;
+            for (agent_id, agent) in agents_map.iter() {
+                // Tout statut rapporté par l'agent (online, busy, maintenance...) vaut présence
+                if agent.status.status == "offline" || agent.last_seen >= timeout_threshold {
+                    continue;
+                }
+                if agent.last_seen < offline_threshold {
+                    agents_to_mark_offline.push(agent_id.clone());
+                } else {
+                    println!("[agents] agent {} heartbeat late (last seen: {}), within grace period", agent_id, agent.last_seen);
+                }
+            }
+        }
+
+        // Marquer les agents timeout comme offline
+        for agent_id in &agents_to_mark_offline {
+            self.mark_agent_offline(agent_id).await;
+        }
+        agents_to_mark_offline
+    }
+
     /// Surveille périodiquement les agents et marque ceux inactifs comme offline
-    pub fn start_agent_monitoring(registry: SharedAgentRegistry, timeout_minutes: i64) {
-        println!("[agents] starting agent monitoring (timeout: {}min)", timeout_minutes);
+    pub fn start_agent_monitoring(registry: SharedAgentRegistry, conf: AgentMonitoringConf) {
+        println!("[agents] starting agent monitoring (every {}s, timeout: {}s, grace: {}s)",
+                 conf.check_interval_secs, conf.offline_timeout_secs, conf.grace_secs);
         
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(std::time::Duration::from_secs(60)); // Check toutes les minutes
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(conf.check_interval_secs.max(1)));
             
             loop {
                 interval.tick().await;
                 
-                let now = OffsetDateTime::now_utc();
-                let timeout_threshold = now - time::Duration::minutes(timeout_minutes);
-                let mut agents_to_mark_offline = Vec::new();
-                
-                // Identifier les agents qui ont timeout
-                {
-                    let agents_map = registry.agents.read().await;
-                    for (agent_id, agent) in agents_map.iter() {
-                        if agent.status.status == "online" && agent.last_seen < timeout_threshold {
-                            agents_to_mark_offline.push(agent_id.clone());
-                        }
-                    }
-                }
+                registry.check_liveness(OffsetDateTime::now_utc(), &conf).await;
                 
-                // Marquer les agents timeout comme offline
-                for agent_id in agents_to_mark_offline {
-                    registry.mark_agent_offline(&agent_id).await;
-                }
-                
-                // Sauvegarder les changements
+                // Sauvegarder les changements (heartbeats compris)
                 if let Err(e) = registry.save_agents().await {
                     eprintln!("[agents] failed to save agents during monitoring: {}", e);
                 }
             }
         });
     }
+
+    /// Vérifie et répare le registre, persiste si des entrées ont changé
+    pub async fn check_integrity(&self) -> IntegrityReport {
+        let report = registry_integrity::check_and_repair(&mut *self.agents.write().await, OffsetDateTime::now_utc());
+        for issue in &report.issues {
+            eprintln!("[agents] integrity: {} {:?} ({:?}): {}", issue.agent_id, issue.problem, issue.repair, issue.detail);
+        }
+        if report.changed() {
+            println!("[agents] integrity check repaired {} issues, dropped {} of {} agents",
+                     report.issues.len(), report.agents_dropped, report.agents_checked);
+            if let Err(e) = self.save_agents().await {
+                eprintln!("[agents] failed to save agents after integrity repair: {}", e);
+            }
+        }
+        *self.integrity_report.lock() = Some(report.clone());
+        report
+    }
+
+    /// Dernier rapport d'intégrité
+    pub fn integrity_report(&self) -> Option<IntegrityReport> {
+        self.integrity_report.lock().clone()
+    }
+
+    /// Contrôle d'intégrité périodique (0 = désactivé)
+    pub fn start_integrity_checks(registry: SharedAgentRegistry, interval_secs: u64) {
+        if interval_secs == 0 {
+            println!("[agents] registry integrity checks disabled");
+            return;
+        }
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+            loop {
+                // Premier tick immédiat : le fichier chargé au démarrage est vérifié tout de suite
+                interval.tick().await;
+                registry.check_integrity().await;
+            }
+        });
+    }
+}
+
+pub type SharedAgentRegistry = Arc<AgentRegistry>;
+
+/// Fichier de persistance jetable pour les tests (hors du dossier du crate)
+#[cfg(test)]
+pub(crate) fn temp_data_file() -> String {
+    std::env::temp_dir().join(format!("symbion-agents-{}.json", Uuid::new_v4())).to_string_lossy().into_owned()
 }
 
-pub type SharedAgentRegistry = Arc<AgentRegistry>;
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn registration(agent_id: &str, os: &str, capabilities: &[&str]) -> AgentRegistrationMessage {
+        serde_json::from_value(json!({
+            "agent_id": agent_id,
+            "hostname": format!("host-{}", agent_id),
+            "os": os,
+            "architecture": "x86_64",
+            "capabilities": capabilities,
+            "network": { "primary_mac": "aa:bb:cc:dd:ee:ff", "interfaces": [] },
+            "version": "1.0.0",
+            "timestamp": "2025-09-01T10:30:00Z"
+        })).unwrap()
+    }
+
+    fn registration_from(agent_id: &str, hostname: &str, mac: &str) -> AgentRegistrationMessage {
+        let mut msg = registration(agent_id, "linux", &["system_metrics"]);
+        msg.hostname = hostname.to_string();
+        msg.network.primary_mac = mac.to_string();
+        msg
+    }
+
+    fn temp_registry(policy: DuplicateAgentPolicy) -> (AgentRegistry, std::path::PathBuf) {
+        let data_file = std::env::temp_dir().join(format!("symbion-agents-{}.json", Uuid::new_v4()));
+        let registry = AgentRegistry::new(data_file.to_str().unwrap()).with_duplicate_policy(policy);
+        (registry, data_file)
+    }
+
+    fn heartbeat(agent_id: &str, cores: u32, total_mb: u64) -> AgentHeartbeatMessage {
+        serde_json::from_value(json!({
+            "agent_id": agent_id,
+            "status": "busy",
+            "system": {
+                "uptime_seconds": 100,
+                "cpu": { "percent": 10.0, "core_count": cores },
+                "memory": { "total_mb": total_mb, "used_mb": 1024, "percent_used": 12.5 }
+            },
+            // Horloge agent synchronisée
+            "timestamp": OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap()
+        })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_summary_aggregates_seeded_registry() {
+        let data_file = std::env::temp_dir().join(format!("symbion-agents-{}.json", Uuid::new_v4()));
+        let registry = AgentRegistry::new(data_file.to_str().unwrap());
+
+        registry.handle_agent_registration(registration("000000000001", "linux", &["system_metrics", "power_management"])).await.unwrap();
+        registry.handle_agent_registration(registration("000000000002", "linux", &["system_metrics"])).await.unwrap();
+        registry.handle_agent_registration(registration("000000000003", "windows", &["system_metrics", "power_management"])).await.unwrap();
+        registry.handle_agent_heartbeat(heartbeat("000000000001", 8, 16000)).await.unwrap();
+        registry.handle_agent_heartbeat(heartbeat("000000000003", 4, 8000)).await.unwrap();
+        registry.mark_agent_offline("000000000002").await;
+
+        let summary = registry.summary().await;
+        assert_eq!(summary.total, 3);
+        assert_eq!(summary.by_os["linux"], 2);
+        assert_eq!(summary.by_os["windows"], 1);
+        assert_eq!(summary.by_status["busy"], 2);
+        assert_eq!(summary.by_status["offline"], 1);
+        assert_eq!(summary.by_capability["system_metrics"], 3);
+        assert_eq!(summary.by_capability["power_management"], 2);
+        assert_eq!(summary.total_cpu_cores, 12);
+        assert_eq!(summary.total_memory_mb, 24000);
+
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_metrics_match_per_agent_values() {
+        let data_file = std::env::temp_dir().join(format!("symbion-agents-{}.json", Uuid::new_v4()));
+        let registry = AgentRegistry::new(data_file.to_str().unwrap());
+        for (id, os) in [("000000000001", "linux"), ("000000000002", "linux"), ("000000000003", "windows")] {
+            registry.handle_agent_registration(registration(id, os, &["system_metrics"])).await.unwrap();
+        }
+        registry.handle_agent_heartbeat(heartbeat("000000000001", 8, 16000)).await.unwrap();
+        registry.handle_agent_heartbeat(heartbeat("000000000003", 4, 8000)).await.unwrap();
+
+        let bulk = registry.metrics_snapshot(|_| true).await;
+        assert_eq!(bulk.len(), 3);
+        for (id, entry) in &bulk {
+            let agent = registry.get_agent(id).await.unwrap();
+            assert_eq!(entry.status, agent.status.status);
+            assert_eq!(serde_json::to_value(&entry.system).unwrap(), serde_json::to_value(&agent.status.system).unwrap());
+        }
+        assert!(bulk["000000000002"].system.is_none());
+        assert_eq!(bulk["000000000003"].system.as_ref().unwrap().memory.total_mb, 8000);
+
+        let busy = registry.metrics_snapshot(|a| a.status.status == "busy" && a.os == "linux").await;
+        assert_eq!(busy.keys().collect::<Vec<_>>(), vec!["000000000001"]);
+
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    fn loaded_heartbeat(agent_id: &str, cpu: f32, memory: f32, busy: bool) -> AgentHeartbeatMessage {
+        let mut msg = heartbeat(agent_id, 4, 8000);
+        msg.status = "online".to_string();
+        msg.busy = Some(busy);
+        let system = &mut msg.system;
+        system.cpu.percent = cpu;
+        system.memory.percent_used = memory;
+        msg
+    }
+
+    #[tokio::test]
+    async fn test_least_loaded_selection_uses_weighted_metrics() {
+        let (registry, data_file) = temp_registry(DuplicateAgentPolicy::Reject);
+        for (id, os) in [("000000000001", "linux"), ("000000000002", "linux"), ("000000000003", "linux"), ("000000000004", "windows"), ("000000000005", "linux")] {
+            registry.handle_agent_registration(registration(id, os, &["system_metrics"])).await.unwrap();
+        }
+        registry.handle_agent_heartbeat(loaded_heartbeat("000000000001", 80.0, 20.0, false)).await.unwrap();
+        registry.handle_agent_heartbeat(loaded_heartbeat("000000000002", 30.0, 60.0, false)).await.unwrap();
+        registry.handle_agent_heartbeat(loaded_heartbeat("000000000003", 5.0, 5.0, true)).await.unwrap();
+        registry.handle_agent_heartbeat(loaded_heartbeat("000000000004", 1.0, 1.0, false)).await.unwrap();
+        // 000000000005 : aucun heartbeat, pas de métriques
+
+        let linux = |a: &Agent| a.os == "linux";
+        // 50/50 : 50.0 contre 45.0 ; l'agent saturé passe après malgré son score
+        let picked = registry.least_loaded(linux, LoadWeights::default()).await.unwrap();
+        assert_eq!((picked.agent_id.as_str(), picked.score), ("000000000002", 45.0));
+
+        // CPU seul : 30 < 80
+        let cpu_only = LoadWeights { cpu: 1.0, memory: 0.0 };
+        assert_eq!(registry.least_loaded(linux, cpu_only).await.unwrap().agent_id, "000000000002");
+        // RAM prépondérante : 0.9 * 20 + 0.1 * 80 = 26 < 0.9 * 60 + 0.1 * 30 = 57
+        let memory_heavy = LoadWeights { cpu: 0.1, memory: 0.9 };
+        assert_eq!(registry.least_loaded(linux, memory_heavy).await.unwrap().agent_id, "000000000001");
+
+        // Agent offline exclu, sélecteur sans candidat
+        registry.mark_agent_offline("000000000002").await;
+        assert_eq!(registry.least_loaded(linux, LoadWeights::default()).await.unwrap().agent_id, "000000000001");
+        assert!(registry.least_loaded(|a| a.os == "android", LoadWeights::default()).await.is_none());
+
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    #[tokio::test]
+    async fn test_least_loaded_ties_break_on_agent_id() {
+        let (registry, data_file) = temp_registry(DuplicateAgentPolicy::Reject);
+        for id in ["00000000000c", "00000000000a", "00000000000b"] {
+            registry.handle_agent_registration(registration(id, "linux", &["system_metrics"])).await.unwrap();
+            registry.handle_agent_heartbeat(loaded_heartbeat(id, 40.0, 40.0, false)).await.unwrap();
+        }
+        let ranked = rank_by_load(registry.list_agents().await.values(), LoadWeights::default());
+        let order: Vec<&str> = ranked.iter().map(|c| c.agent_id.as_str()).collect();
+        assert_eq!(order, vec!["00000000000a", "00000000000b", "00000000000c"]);
+
+        assert!(!LoadWeights { cpu: 0.0, memory: 0.0 }.is_valid());
+        assert!(!LoadWeights { cpu: -1.0, memory: 2.0 }.is_valid());
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    #[tokio::test]
+    async fn test_registration_conflict_detection() {
+        let (registry, data_file) = temp_registry(DuplicateAgentPolicy::Reject);
+        registry.handle_agent_registration(registration_from("a1b2c3d4e5f6", "vm-original", "a1:b2:c3:d4:e5:f6")).await.unwrap();
+        let existing = registry.get_agent("a1b2c3d4e5f6").await.unwrap();
+
+        // Même machine qui se ré-enregistre (MAC dans un autre format) : pas de conflit
+        assert!(registration_conflict(&existing, &registration_from("a1b2c3d4e5f6", "VM-ORIGINAL", "A1-B2-C3-D4-E5-F6")).is_none());
+        // Clone : hostname différent
+        let conflict = registration_conflict(&existing, &registration_from("a1b2c3d4e5f6", "vm-clone", "a1:b2:c3:d4:e5:f6")).unwrap();
+        assert!(conflict.contains("vm-clone"));
+        // Agent offline : reprise autorisée
+        registry.mark_agent_offline("a1b2c3d4e5f6").await;
+        let offline = registry.get_agent("a1b2c3d4e5f6").await.unwrap();
+        assert!(registration_conflict(&offline, &registration_from("a1b2c3d4e5f6", "vm-clone", "a1:b2:c3:d4:e5:f6")).is_none());
+
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_registration_rejected_by_default() {
+        let (registry, data_file) = temp_registry(DuplicateAgentPolicy::default());
+        registry.handle_agent_registration(registration_from("a1b2c3d4e5f6", "vm-original", "a1:b2:c3:d4:e5:f6")).await.unwrap();
+
+        assert!(registry.handle_agent_registration(registration_from("a1b2c3d4e5f6", "vm-clone", "a1:b2:c3:d4:e5:f6")).await.is_err());
+        let agents = registry.list_agents().await;
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents["a1b2c3d4e5f6"].hostname, "vm-original");
+
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_registration_renamed_with_suffix() {
+        let (registry, data_file) = temp_registry(DuplicateAgentPolicy::Rename);
+        registry.handle_agent_registration(registration_from("a1b2c3d4e5f6", "vm-original", "a1:b2:c3:d4:e5:f6")).await.unwrap();
+
+        let clone_id = registry.handle_agent_registration(registration_from("a1b2c3d4e5f6", "vm-clone", "a1:b2:c3:d4:e5:f6")).await.unwrap();
+        assert_eq!(clone_id, "a1b2c3d4e5f6-2");
+        // Le clone qui se ré-enregistre garde son suffixe, un second clone prend le suivant
+        assert_eq!(registry.handle_agent_registration(registration_from("a1b2c3d4e5f6", "vm-clone", "a1:b2:c3:d4:e5:f6")).await.unwrap(), "a1b2c3d4e5f6-2");
+        assert_eq!(registry.handle_agent_registration(registration_from("a1b2c3d4e5f6", "vm-clone-b", "a1:b2:c3:d4:e5:f6")).await.unwrap(), "a1b2c3d4e5f6-3");
+
+        let agents = registry.list_agents().await;
+        assert_eq!(agents.len(), 3);
+        assert_eq!(agents["a1b2c3d4e5f6"].hostname, "vm-original");
+        assert_eq!(agents["a1b2c3d4e5f6-2"].hostname, "vm-clone");
+        assert_eq!(agents["a1b2c3d4e5f6-2"].agent_id, "a1b2c3d4e5f6-2");
+
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    #[tokio::test]
+    async fn test_offline_online_cycles_feed_flapping_score() {
+        let data_file = std::env::temp_dir().join(format!("symbion-agents-{}.json", Uuid::new_v4()));
+        let registry = AgentRegistry::new(data_file.to_str().unwrap())
+            .with_flapping(FlappingConf { window_secs: 600, threshold: 2 });
+
+        registry.handle_agent_registration(registration("000000000001", "linux", &["system_metrics"])).await.unwrap();
+        for _ in 0..2 {
+            registry.mark_agent_offline("000000000001").await;
+            registry.handle_agent_heartbeat(heartbeat("000000000001", 4, 8000)).await.unwrap();
+        }
+        // Retour par re-registration : compte aussi comme transition
+        registry.mark_agent_offline("000000000001").await;
+        registry.handle_agent_registration(registration("000000000001", "linux", &["system_metrics"])).await.unwrap();
+
+        let stats = registry.liveness("000000000001").unwrap();
+        assert_eq!(stats.flapping_score, 3);
+        assert!(stats.flapping);
+        assert_eq!(stats.heartbeats, 2);
+
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    #[tokio::test]
+    async fn test_registry_round_trip_json_and_msgpack() {
+        let base = std::env::temp_dir().join(format!("symbion-agents-{}.json", Uuid::new_v4()));
+        let base = base.to_str().unwrap();
+
+        let mut snapshots = Vec::new();
+        for format in [PersistFormat::Json, PersistFormat::Msgpack] {
+            let registry = AgentRegistry::new(base).with_persist_format(format);
+            registry.handle_agent_registration(registration("000000000001", "linux", &["system_metrics"])).await.unwrap();
+            registry.handle_agent_heartbeat(heartbeat("000000000001", 8, 16000)).await.unwrap();
+            registry.save_agents().await.unwrap();
+            let saved = serde_json::to_value(registry.list_agents().await).unwrap();
+
+            let mut reloaded = AgentRegistry::new(base).with_persist_format(format);
+            reloaded.load_agents().await.unwrap();
+            let loaded = serde_json::to_value(reloaded.list_agents().await).unwrap();
+            assert_eq!(loaded, saved);
+            snapshots.push(loaded);
+        }
+        assert_eq!(snapshots[0]["000000000001"]["hostname"], snapshots[1]["000000000001"]["hostname"]);
+
+        for format in [PersistFormat::Json, PersistFormat::Msgpack] {
+            let _ = std::fs::remove_file(persistence::path_for(base, format));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_announce_triggers_registration_from_mock_agent() {
+        let data_file = std::env::temp_dir().join(format!("symbion-agents-{}.json", Uuid::new_v4()));
+        let registry = Arc::new(AgentRegistry::new(data_file.to_str().unwrap()));
+        // Agent factice branché sur le "broker" : répond à l'announce par une registration
+        let agent = registry.clone();
+        let broker = crate::mqtt_publish::RecordingPublisher::on_publish(move |topic, payload| {
+            let request: serde_json::Value = serde_json::from_slice(payload).unwrap();
+            assert!(request["request_id"].is_string());
+            if topic == ANNOUNCE_TOPIC {
+                let agent = agent.clone();
+                tokio::spawn(async move {
+                    agent.handle_agent_registration(registration("000000000007", "linux", &["system_metrics"])).await.unwrap();
+                });
+            }
+        });
+        assert_eq!(registry.agents_count(), 0);
+
+        request_announce(&broker).unwrap();
+        assert_eq!(broker.topics(), vec![ANNOUNCE_TOPIC.to_string()]);
+
+        for _ in 0..100 {
+            if registry.get_agent("000000000007").await.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(registry.get_agent("000000000007").await.unwrap().hostname, "host-000000000007");
+
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    /// Client MQTT dont les requêtes sont lues directement (pas de broker)
+    fn capturing_client() -> (AsyncClient, flume::Receiver<rumqttc::Request>) {
+        let (tx, rx) = flume::bounded(10);
+        (AsyncClient::from_senders(tx), rx)
+    }
+
+    fn published_qos(rx: &flume::Receiver<rumqttc::Request>) -> rumqttc::QoS {
+        match rx.try_recv().expect("command published") {
+            rumqttc::Request::Publish(publish) => {
+                assert_eq!(publish.topic, "symbion/agents/command@v1");
+                publish.qos
+            }
+            other => panic!("unexpected request {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_power_command_uses_exactly_once() {
+        let (client, rx) = capturing_client();
+        let registry = AgentRegistry::new(&temp_data_file()).with_mqtt_client(client);
+
+        registry.send_command("a1b2c3d4e5f6", "shutdown", None).await.unwrap();
+        assert_eq!(published_qos(&rx), rumqttc::QoS::ExactlyOnce);
+
+        registry.send_command("a1b2c3d4e5f6", "get_metrics", None).await.unwrap();
+        assert_eq!(published_qos(&rx), rumqttc::QoS::AtLeastOnce);
+
+        // QoS explicite prioritaire sur le défaut
+        registry.send_command_with_qos("a1b2c3d4e5f6", "get_metrics", None, rumqttc::QoS::AtMostOnce).await.unwrap();
+        assert_eq!(published_qos(&rx), rumqttc::QoS::AtMostOnce);
+    }
+
+    #[tokio::test]
+    async fn test_command_contract_never_retains_nor_downgrades_power_commands() {
+        let dir = std::env::temp_dir().join(format!("symbion-contracts-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("agents.command.v1.json"), serde_json::json!({
+            "topic": "symbion/agents/command@v1",
+            "schema": {},
+            "qos": 0,
+            "retain": true
+        }).to_string()).unwrap();
+        let contracts = ContractRegistry::load_contracts_from_dir(&dir).await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let (client, rx) = capturing_client();
+        let registry = AgentRegistry::new(&temp_data_file()).with_mqtt_client(client).with_contracts(contracts);
+        let published = |rx: &flume::Receiver<rumqttc::Request>| match rx.try_recv().expect("command published") {
+            rumqttc::Request::Publish(publish) => (publish.qos, publish.retain),
+            other => panic!("unexpected request {:?}", other),
+        };
+
+        // Commande retenue = rejouée à chaque reconnexion : retain du contrat ignoré,
+        // et une commande d'alimentation garde son QoS 2
+        registry.send_command("a1b2c3d4e5f6", "shutdown", None).await.unwrap();
+        assert_eq!(published(&rx), (rumqttc::QoS::ExactlyOnce, false));
+        // Autres commandes : QoS du contrat
+        registry.send_command("a1b2c3d4e5f6", "get_metrics", None).await.unwrap();
+        assert_eq!(published(&rx), (rumqttc::QoS::AtMostOnce, false));
+        // QoS explicite de l'appelant prioritaire sur le contrat
+        registry.send_command_with_qos("a1b2c3d4e5f6", "get_metrics", None, rumqttc::QoS::ExactlyOnce).await.unwrap();
+        assert_eq!(published(&rx), (rumqttc::QoS::ExactlyOnce, false));
+    }
+
+    #[tokio::test]
+    async fn test_identical_read_within_ttl_is_served_from_cache() {
+        let (client, rx) = capturing_client();
+        let registry = AgentRegistry::new(&temp_data_file())
+            .with_mqtt_client(client)
+            .with_command_cache(CommandCacheConf { ttl_secs: 60, commands: vec!["get_metrics".to_string()] });
+
+        let first = registry.send_command("a1b2c3d4e5f6", "get_metrics", None).await.unwrap();
+        // En vol : même command_id, rien de republié
+        assert_eq!(registry.send_command("a1b2c3d4e5f6", "get_metrics", None).await.unwrap(), first);
+        registry.handle_command_response(AgentCommandResponse {
+            command_id: first.clone(),
+            agent_id: "a1b2c3d4e5f6".to_string(),
+            status: "success".to_string(),
+            data: Some(serde_json::json!({ "cpu": 12.5 })),
+            error: None,
+            execution_time_ms: Some(40),
+            timestamp: "2025-01-01T00:00:00Z".to_string(),
+        });
+        let second = registry.send_command("a1b2c3d4e5f6", "get_metrics", None).await.unwrap();
+        assert_eq!(second, first);
+        assert_eq!(registry.commands().get(&second).unwrap().status, "success");
+        published_qos(&rx);
+        assert!(rx.try_recv().is_err());
+
+        // Autres paramètres, autre agent ou commande hors cache : envoyés
+        registry.send_command("a1b2c3d4e5f6", "get_metrics", Some(serde_json::json!({ "detail": true }))).await.unwrap();
+        registry.send_command("ffeeddccbbaa", "get_metrics", None).await.unwrap();
+        registry.send_command("a1b2c3d4e5f6", "shutdown", None).await.unwrap();
+        registry.send_command("a1b2c3d4e5f6", "shutdown", None).await.unwrap();
+        assert_eq!(rx.try_iter().count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_agent_rejects_then_recovers() {
+        let (client, rx) = capturing_client();
+        let registry = AgentRegistry::new(&temp_data_file())
+            .with_mqtt_client(client)
+            .with_command_rate_limit(CommandRateLimitConf { burst: 2, per_second: 20.0 });
+
+        registry.send_command("a1b2c3d4e5f6", "list_processes", None).await.unwrap();
+        registry.send_command("a1b2c3d4e5f6", "list_processes", None).await.unwrap();
+        let err = registry.send_command("a1b2c3d4e5f6", "list_processes", None).await.unwrap_err();
+        let limited = err.downcast_ref::<crate::rate_limit::RateLimited>().expect("rate limit error");
+        assert_eq!((limited.burst, limited.per_second), (2, 20.0));
+        assert!(limited.retry_after_ms <= 50);
+        assert_eq!(rx.try_iter().count(), 2);
+
+        // Autre agent non affecté, puis recharge (1 jeton toutes les 50 ms)
+        registry.send_command("ffeeddccbbaa", "list_processes", None).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(60)).await;
+        registry.send_command("a1b2c3d4e5f6", "list_processes", None).await.unwrap();
+        assert_eq!(rx.try_iter().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_override_reaches_agent_and_tracker() {
+        let (client, rx) = capturing_client();
+        let registry = AgentRegistry::new(&temp_data_file()).with_mqtt_client(client);
+
+        let upgrade = registry.send_command_with_timeout("a1b2c3d4e5f6", "run_command", Some(serde_json::json!({ "command": "apt_upgrade" })), Some(1800)).await.unwrap();
+        let default = registry.send_command_with_timeout("a1b2c3d4e5f6", "get_metrics", None, None).await.unwrap();
+
+        let timeouts: Vec<serde_json::Value> = rx.try_iter().map(|request| match request {
+            rumqttc::Request::Publish(publish) => serde_json::from_slice::<serde_json::Value>(&publish.payload).unwrap()["timeout_seconds"].clone(),
+            other => panic!("unexpected request {:?}", other),
+        }).collect();
+        assert_eq!(timeouts, vec![serde_json::json!(1800), serde_json::json!(DEFAULT_COMMAND_TIMEOUT_SECONDS)]);
+        assert_eq!(registry.commands().get(&upgrade).unwrap().timeout_seconds, 1800);
+        assert_eq!(registry.commands().get(&default).unwrap().timeout_seconds, DEFAULT_COMMAND_TIMEOUT_SECONDS);
+    }
+
+    #[tokio::test]
+    async fn test_send_failures_are_typed() {
+        let registry = AgentRegistry::new(&temp_data_file());
+        let err = registry.send_command("a1b2c3d4e5f6", "get_metrics", None).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<CommandSendError>(), Some(CommandSendError::MqttNotConfigured)));
+
+        // Eventloop arrêtée : la publication échoue et la commande n'est pas suivie
+        let (client, rx) = capturing_client();
+        drop(rx);
+        let registry = AgentRegistry::new(&temp_data_file()).with_mqtt_client(client);
+        let err = registry.send_command("a1b2c3d4e5f6", "get_metrics", None).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<CommandSendError>(), Some(CommandSendError::Publish(_))));
+        assert!(registry.commands().cancel_pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unsent_command_does_not_spend_a_rate_limit_token() {
+        let limit = CommandRateLimitConf { burst: 1, per_second: 0.001 };
+        let registry = AgentRegistry::new(&temp_data_file()).with_command_rate_limit(limit.clone());
+        for _ in 0..3 {
+            let err = registry.send_command("a1b2c3d4e5f6", "get_metrics", None).await.unwrap_err();
+            assert!(matches!(err.downcast_ref::<CommandSendError>(), Some(CommandSendError::MqttNotConfigured)));
+        }
+
+        let (client, rx) = capturing_client();
+        drop(rx);
+        let registry = AgentRegistry::new(&temp_data_file()).with_mqtt_client(client).with_command_rate_limit(limit);
+        for _ in 0..3 {
+            let err = registry.send_command("a1b2c3d4e5f6", "get_metrics", None).await.unwrap_err();
+            assert!(matches!(err.downcast_ref::<CommandSendError>(), Some(CommandSendError::Publish(_))));
+        }
+    }
+
+    fn network(primary_mac: &str, interfaces: &[(&str, &str)]) -> AgentNetwork {
+        serde_json::from_value(json!({
+            "primary_mac": primary_mac,
+            "interfaces": interfaces.iter()
+                .map(|(mac, ip)| json!({ "name": "eth0", "mac": mac, "ip": ip, "type": "ethernet" }))
+                .collect::<Vec<_>>()
+        })).unwrap()
+    }
+
+    fn network_events(rx: &flume::Receiver<rumqttc::Request>) -> Vec<AgentNetworkChanged> {
+        rx.try_iter()
+            .filter_map(|request| match request {
+                rumqttc::Request::Publish(publish) if publish.topic == NETWORK_CHANGED_TOPIC => {
+                    Some(serde_json::from_slice(&publish.payload).unwrap())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_primary_ip_change_is_published_and_recorded() {
+        let (registry, data_file) = temp_registry(DuplicateAgentPolicy::Reject);
+        let (client, rx) = capturing_client();
+        let registry = registry.with_mqtt_client(client);
+
+        let mut msg = registration_from("a1b2c3d4e5f6", "laptop", "a1:b2:c3:d4:e5:f6");
+        msg.network = network("a1:b2:c3:d4:e5:f6", &[("02:42:ac:11:00:02", "172.17.0.1"), ("A1-B2-C3-D4-E5-F6", "192.168.1.42")]);
+        registry.handle_agent_registration(msg).await.unwrap();
+        assert_eq!(registry.get_agent("a1b2c3d4e5f6").await.unwrap().network.primary_ip(), Some("192.168.1.42"));
+
+        // Même réseau : rien à signaler
+        let mut hb = heartbeat("a1b2c3d4e5f6", 4, 8192);
+        hb.network = Some(network("a1:b2:c3:d4:e5:f6", &[("a1:b2:c3:d4:e5:f6", "192.168.1.42")]));
+        registry.handle_agent_heartbeat(hb).await.unwrap();
+        assert!(network_events(&rx).is_empty());
+
+        // Nouveau bail DHCP
+        let mut hb = heartbeat("a1b2c3d4e5f6", 4, 8192);
+        hb.network = Some(network("a1:b2:c3:d4:e5:f6", &[("a1:b2:c3:d4:e5:f6", "192.168.1.57")]));
+        registry.handle_agent_heartbeat(hb).await.unwrap();
+        let events = network_events(&rx);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].previous_ip.as_deref(), Some("192.168.1.42"));
+        assert_eq!(events[0].ip.as_deref(), Some("192.168.1.57"));
+        assert_eq!(events[0].previous_hostname, None);
+        assert_eq!(registry.get_agent("a1b2c3d4e5f6").await.unwrap().network.primary_ip(), Some("192.168.1.57"));
+
+        // Renommage de la machine à la re-registration
+        let mut msg = registration_from("a1b2c3d4e5f6", "laptop-alice", "a1:b2:c3:d4:e5:f6");
+        msg.network = network("a1:b2:c3:d4:e5:f6", &[("a1:b2:c3:d4:e5:f6", "192.168.1.57")]);
+        registry.mark_agent_offline("a1b2c3d4e5f6").await;
+        registry.handle_agent_registration(msg).await.unwrap();
+        let events = network_events(&rx);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].hostname, "laptop-alice");
+        assert_eq!(events[0].previous_hostname.as_deref(), Some("laptop"));
+        assert_eq!(events[0].ip, events[0].previous_ip);
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    #[tokio::test]
+    async fn test_unavailable_capability_reasons_are_stored() {
+        let (registry, data_file) = temp_registry(DuplicateAgentPolicy::Reject);
+        let msg: AgentRegistrationMessage = serde_json::from_value(json!({
+            "agent_id": "a1b2c3d4e5f6",
+            "hostname": "nas",
+            "os": "linux",
+            "architecture": "x86_64",
+            "capabilities": ["system_metrics"],
+            "unavailable_capabilities": [
+                { "capability": "power_management", "reason": "No power management commands found" },
+                { "capability": "gpu_metrics" }
+            ],
+            "network": { "primary_mac": "a1:b2:c3:d4:e5:f6", "interfaces": [] },
+            "version": "1.0.0",
+            "timestamp": "2025-09-01T10:30:00Z"
+        })).unwrap();
+        assert_eq!(msg.unavailable_capabilities[1].reason, None);
+        registry.handle_agent_registration(msg).await.unwrap();
+
+        let stored = registry.get_agent("a1b2c3d4e5f6").await.unwrap();
+        assert_eq!(stored.unavailable_capabilities[0], UnavailableCapability {
+            capability: "power_management".to_string(),
+            reason: Some("No power management commands found".to_string()),
+        });
+        // Survit à la persistance
+        let mut reloaded = AgentRegistry::new(data_file.to_str().unwrap());
+        reloaded.load_agents().await.unwrap();
+        assert_eq!(reloaded.get_agent("a1b2c3d4e5f6").await.unwrap().unavailable_capabilities, stored.unavailable_capabilities);
+
+        // Agents plus anciens : champ absent, liste vide
+        registry.handle_agent_registration(registration("0a0b0c0d0e0f", "linux", &["system_metrics"])).await.unwrap();
+        assert!(registry.get_agent("0a0b0c0d0e0f").await.unwrap().unavailable_capabilities.is_empty());
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    #[tokio::test]
+    async fn test_registration_records_heartbeat_version() {
+        let (registry, data_file) = temp_registry(DuplicateAgentPolicy::Reject);
+        registry.handle_agent_registration(registration("a1b2c3d4e5f6", "linux", &[])).await.unwrap();
+        assert_eq!(registry.heartbeat_version("a1b2c3d4e5f6").await, "v1");
+
+        let mut msg = registration("a1b2c3d4e5f6", "linux", &[]);
+        msg.heartbeat_version = "v2".to_string();
+        registry.handle_agent_registration(msg).await.unwrap();
+        assert_eq!(registry.heartbeat_version("a1b2c3d4e5f6").await, "v2");
+
+        let mut msg = registration("0a0b0c0d0e0f", "linux", &[]);
+        msg.heartbeat_version = "v9".to_string();
+        assert!(registry.handle_agent_registration(msg).await.is_err());
+        assert_eq!(registry.heartbeat_version("0a0b0c0d0e0f").await, "v1");
+        let _ = std::fs::remove_file(data_file);
+    }
+
+    #[tokio::test]
+    async fn test_recent_commands_follow_heartbeats() {
+        let registry = AgentRegistry::new(&temp_data_file());
+        registry.handle_agent_registration(registration("a1b2c3d4e5f6", "linux", &["system_metrics"])).await.unwrap();
+
+        let with_recent = |count: usize| {
+            let mut msg = heartbeat("a1b2c3d4e5f6", 8, 16000);
+            msg.recent_commands = (0..count).rev()
+                .map(|i| AgentRecentCommand {
+                    command_id: format!("cmd-{}", i),
+                    command_type: "get_metrics".to_string(),
+                    status: "success".to_string(),
+                    timestamp: "2025-09-01T10:30:00Z".to_string(),
+                })
+                .collect();
+            msg
+        };
+        registry.handle_agent_heartbeat(with_recent(MAX_RECENT_COMMANDS + 2)).await.unwrap();
+        let recent = registry.get_agent("a1b2c3d4e5f6").await.unwrap().status.recent_commands;
+        assert_eq!(recent.len(), MAX_RECENT_COMMANDS);
+        assert_eq!(recent[0].command_id, format!("cmd-{}", MAX_RECENT_COMMANDS + 1));
+
+        // Ni un heartbeat sans liste ni une re-registration n'effacent l'historique
+        registry.handle_agent_heartbeat(with_recent(0)).await.unwrap();
+        registry.handle_agent_registration(registration("a1b2c3d4e5f6", "linux", &["system_metrics"])).await.unwrap();
+        assert_eq!(registry.get_agent("a1b2c3d4e5f6").await.unwrap().status.recent_commands, recent);
+    }
+
+    #[test]
+    fn test_clock_skew_computation_and_threshold() {
+        let received = OffsetDateTime::parse("2025-09-01T10:31:00Z", &time::format_description::well_known::Rfc3339).unwrap();
+        assert_eq!(clock_skew("2025-09-01T10:31:00.400Z", received), Some(0));
+        assert_eq!(clock_skew("2025-09-01T10:32:30Z", received), Some(90));
+        assert_eq!(clock_skew("2025-09-01T12:29:00+02:00", received), Some(-120));
+        assert_eq!(clock_skew("yesterday", received), None);
+
+        assert!(!is_clock_skewed(30, 30));
+        assert!(!is_clock_skewed(-30, 30));
+        assert!(is_clock_skewed(31, 30));
+        assert!(is_clock_skewed(-120, 30));
+    }
+
+    fn alerts(rx: &flume::Receiver<rumqttc::Request>) -> Vec<serde_json::Value> {
+        rx.try_iter()
+            .filter_map(|request| match request {
+                rumqttc::Request::Publish(publish) if publish.topic == symbion_topics::agents_alert() => {
+                    Some(serde_json::from_slice(&publish.payload).unwrap())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_skewed_agent_is_flagged_and_alerted_once() {
+        let (client, rx) = capturing_client();
+        let registry = AgentRegistry::new(&temp_data_file()).with_mqtt_client(client).with_clock_skew_threshold(60);
+        registry.handle_agent_registration(registration("a1b2c3d4e5f6", "linux", &[])).await.unwrap();
+
+        registry.handle_agent_heartbeat(heartbeat("a1b2c3d4e5f6", 4, 8192)).await.unwrap();
+        let status = registry.get_agent("a1b2c3d4e5f6").await.unwrap().status;
+        assert!(!status.clock_skewed);
+        assert!(status.clock_skew_secs.unwrap().abs() <= 1);
+        assert!(alerts(&rx).is_empty());
+
+        // Horloge agent en retard de 5 minutes, deux heartbeats : une seule alerte
+        for _ in 0..2 {
+            let mut hb = heartbeat("a1b2c3d4e5f6", 4, 8192);
+            hb.timestamp = (OffsetDateTime::now_utc() - time::Duration::minutes(5))
+                .format(&time::format_description::well_known::Rfc3339).unwrap();
+            registry.handle_agent_heartbeat(hb).await.unwrap();
+        }
+        let status = registry.get_agent("a1b2c3d4e5f6").await.unwrap().status;
+        assert!(status.clock_skewed);
+        assert!((-301..=-299).contains(&status.clock_skew_secs.unwrap()));
+        let alerts = alerts(&rx);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0]["kind"], "clock_skew");
+        assert_eq!(alerts[0]["threshold"], 60);
+
+        // Resynchronisée
+        registry.handle_agent_heartbeat(heartbeat("a1b2c3d4e5f6", 4, 8192)).await.unwrap();
+        assert!(!registry.get_agent("a1b2c3d4e5f6").await.unwrap().status.clock_skewed);
+    }
+
+    #[test]
+    fn test_command_qos_defaults() {
+        for power in ["shutdown", "reboot", "hibernate"] {
+            assert_eq!(command_qos(power), rumqttc::QoS::ExactlyOnce);
+        }
+        for other in ["get_metrics", "list_processes", "run_command"] {
+            assert_eq!(command_qos(other), rumqttc::QoS::AtLeastOnce);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_backlog_is_stored_and_counted_as_overloaded() {
+        let registry = AgentRegistry::new(&temp_data_file());
+        registry.handle_agent_registration(registration("000000000001", "linux", &["system_metrics"])).await.unwrap();
+        registry.handle_agent_registration(registration("000000000002", "linux", &["system_metrics"])).await.unwrap();
+
+        let mut saturated = heartbeat("000000000001", 4, 8000);
+        saturated.queue_depth = Some(6);
+        saturated.busy = Some(true);
+        registry.handle_agent_heartbeat(saturated).await.unwrap();
+        registry.handle_agent_heartbeat(heartbeat("000000000002", 4, 8000)).await.unwrap();
+
+        let agent = registry.get_agent("000000000001").await.unwrap();
+        assert_eq!(agent.status.queue_depth, Some(6));
+        assert_eq!(agent.status.busy, Some(true));
+        // Agent plus ancien, sans ces champs : rien de supposé
+        assert_eq!(registry.get_agent("000000000002").await.unwrap().status.busy, None);
+        assert_eq!(registry.summary().await.overloaded, 1);
+
+        registry.mark_agent_offline("000000000001").await;
+        assert_eq!(registry.summary().await.overloaded, 0);
+    }
+
+    #[tokio::test]
+    async fn test_agent_within_grace_window_stays_online() {
+        let registry = AgentRegistry::new(&temp_data_file());
+        registry.handle_agent_registration(registration("000000000001", "linux", &["system_metrics"])).await.unwrap();
+        let last_seen = registry.get_agent("000000000001").await.unwrap().last_seen;
+        let conf = AgentMonitoringConf { check_interval_secs: 10, offline_timeout_secs: 120, grace_secs: 30, ..Default::default() };
+
+        // Timeout dépassé mais encore dans la grâce : heartbeat en retard, pas offline
+        for elapsed in [60, 121, 135, 150] {
+            let now = last_seen + time::Duration::seconds(elapsed);
+            assert!(registry.check_liveness(now, &conf).await.is_empty(), "offline after {}s", elapsed);
+            assert_eq!(registry.get_agent("000000000001").await.unwrap().status.status, "online");
+        }
+
+        // Au-delà de timeout + grâce : offline, une seule fois
+        let now = last_seen + time::Duration::seconds(151);
+        assert_eq!(registry.check_liveness(now, &conf).await, vec!["000000000001".to_string()]);
+        assert_eq!(registry.get_agent("000000000001").await.unwrap().status.status, "offline");
+        assert!(registry.check_liveness(now, &conf).await.is_empty());
+
+        // Sans grâce, le timeout seul suffit
+        registry.handle_agent_registration(registration("000000000002", "linux", &["system_metrics"])).await.unwrap();
+        let last_seen = registry.get_agent("000000000002").await.unwrap().last_seen;
+        let strict = AgentMonitoringConf { grace_secs: 0, ..conf };
+        let now = last_seen + time::Duration::seconds(121);
+        assert_eq!(registry.check_liveness(now, &strict).await, vec!["000000000002".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_silent_agent_in_maintenance_goes_offline() {
+        let registry = AgentRegistry::new(&temp_data_file());
+        registry.handle_agent_registration(registration("000000000001", "linux", &["system_metrics"])).await.unwrap();
+        let mut msg = heartbeat("000000000001", 4, 8000);
+        msg.status = "maintenance".to_string();
+        registry.handle_agent_heartbeat(msg).await.unwrap();
+        let agent = registry.get_agent("000000000001").await.unwrap();
+        assert_eq!(agent.status.status, "maintenance");
+
+        let conf = AgentMonitoringConf { check_interval_secs: 10, offline_timeout_secs: 120, grace_secs: 0, ..Default::default() };
+        assert!(registry.check_liveness(agent.last_seen + time::Duration::seconds(60), &conf).await.is_empty());
+        let now = agent.last_seen + time::Duration::seconds(121);
+        assert_eq!(registry.check_liveness(now, &conf).await, vec!["000000000001".to_string()]);
+        assert_eq!(registry.get_agent("000000000001").await.unwrap().status.status, "offline");
+    }
+}
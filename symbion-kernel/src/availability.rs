@@ -0,0 +1,271 @@
+/**
+ * AVAILABILITY - Historique online/offline des agents et disponibilité type SLA
+ *
+ * RÔLE :
+ * Répond à "quel pourcentage du temps cet agent était-il joignable ces N derniers
+ * jours, et quand est-il tombé ?" (GET /agents/{id}/availability?days=N).
+ *
+ * FONCTIONNEMENT :
+ * - Chaque passage online/offline est ajouté à un journal JSON lines
+ *   ({"agent_id":..., "at": <unix>, "online": bool}), rechargé au démarrage :
+ *   un redémarrage du kernel ne perd pas l'historique
+ * - Un événement identique à l'état courant est ignoré (heartbeats successifs)
+ * - Historique limité à MAX_HISTORY_DAYS, le journal est compacté au chargement
+ * - Disponibilité = temps online / temps observé dans la fenêtre ; avant le premier
+ *   événement connu l'état de l'agent est inconnu et ce temps n'est pas compté
+ */
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+/// Fenêtre maximale conservée (et interrogeable)
+pub const MAX_HISTORY_DAYS: u32 = 90;
+
+/// Ligne du journal de transitions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TransitionRecord {
+    agent_id: String,
+    /// Timestamp unix (secondes)
+    at: i64,
+    online: bool,
+}
+
+/// Passage d'un agent à l'état online (true) ou offline (false)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transition {
+    pub at: OffsetDateTime,
+    pub online: bool,
+}
+
+/// Période offline dans la fenêtre demandée
+#[derive(Debug, Clone, Serialize)]
+pub struct Downtime {
+    pub start: String,
+    /// None si l'agent est toujours offline
+    pub end: Option<String>,
+    pub duration_secs: i64,
+}
+
+/// Disponibilité d'un agent sur une fenêtre glissante
+#[derive(Debug, Clone, Serialize)]
+pub struct AvailabilityReport {
+    pub agent_id: String,
+    pub days: u32,
+    pub from: String,
+    pub to: String,
+    /// Temps pendant lequel l'état de l'agent est connu
+    pub observed_secs: i64,
+    pub uptime_secs: i64,
+    /// None si rien n'a été observé dans la fenêtre
+    pub uptime_percent: Option<f64>,
+    pub downtime: Vec<Downtime>,
+}
+
+/// Calcule la disponibilité entre `from` et `now` à partir des transitions triées
+pub fn compute(agent_id: &str, days: u32, transitions: &[Transition], from: OffsetDateTime, now: OffsetDateTime) -> AvailabilityReport {
+    let format = |t: OffsetDateTime| t.format(&Rfc3339).unwrap_or_default();
+    let mut observed_secs = 0;
+    let mut uptime_secs = 0;
+    let mut downtime = Vec::new();
+
+    let mut account = |start: OffsetDateTime, end: OffsetDateTime, online: bool, ongoing: bool| {
+        let secs = (end - start).whole_seconds();
+        observed_secs += secs;
+        if online {
+            uptime_secs += secs;
+        } else {
+            downtime.push(Downtime {
+                start: format(start),
+                end: (!ongoing).then(|| format(end)),
+                duration_secs: secs,
+            });
+        }
+    };
+
+    // État connu au début de la fenêtre (dernière transition avant `from`)
+    let mut state = transitions.iter().rev().find(|t| t.at <= from).map(|t| t.online);
+    let mut cursor = from;
+    for transition in transitions.iter().filter(|t| t.at > from && t.at <= now) {
+        if let Some(online) = state {
+            account(cursor, transition.at, online, false);
+        }
+        cursor = transition.at;
+        state = Some(transition.online);
+    }
+    if let Some(online) = state {
+        account(cursor, now, online, true);
+    }
+
+    AvailabilityReport {
+        agent_id: agent_id.to_string(),
+        days,
+        from: format(from),
+        to: format(now),
+        observed_secs,
+        uptime_secs,
+        uptime_percent: (observed_secs > 0).then(|| uptime_secs as f64 * 100.0 / observed_secs as f64),
+        downtime,
+    }
+}
+
+/// Transitions de tous les agents, persistées si un journal est configuré
+#[derive(Debug, Default)]
+pub struct AvailabilityLog {
+    path: Option<PathBuf>,
+    agents: Mutex<HashMap<String, Vec<Transition>>>,
+}
+
+impl AvailabilityLog {
+    /// Charge (et compacte) le journal, créé au premier événement s'il n'existe pas
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let cutoff = OffsetDateTime::now_utc() - time::Duration::days(MAX_HISTORY_DAYS as i64);
+        let mut agents: HashMap<String, Vec<Transition>> = HashMap::new();
+        let mut dropped = 0;
+
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                for line in content.lines().filter(|l| !l.trim().is_empty()) {
+                    let Ok(record) = serde_json::from_str::<TransitionRecord>(line) else {
+                        dropped += 1;
+                        continue;
+                    };
+                    let Ok(at) = OffsetDateTime::from_unix_timestamp(record.at) else {
+                        dropped += 1;
+                        continue;
+                    };
+                    agents.entry(record.agent_id).or_default().push(Transition { at, online: record.online });
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        // Garde la dernière transition avant la limite : elle donne l'état au début de l'historique
+        for transitions in agents.values_mut() {
+            transitions.sort_by_key(|t| t.at);
+            let keep_from = transitions.iter().rposition(|t| t.at <= cutoff).unwrap_or(0);
+            dropped += keep_from;
+            transitions.drain(..keep_from);
+        }
+
+        let log = Self { path: Some(path), agents: Mutex::new(agents) };
+        if dropped > 0 {
+            log.compact()?;
+        }
+        Ok(log)
+    }
+
+    /// Enregistre l'état d'un agent s'il diffère du dernier connu
+    pub fn record(&self, agent_id: &str, online: bool, at: OffsetDateTime) {
+        let mut agents = self.agents.lock();
+        let transitions = agents.entry(agent_id.to_string()).or_default();
+        if transitions.last().is_some_and(|last| last.online == online) {
+            return;
+        }
+        transitions.push(Transition { at, online });
+        drop(agents);
+
+        if let Err(e) = self.append(&TransitionRecord { agent_id: agent_id.to_string(), at: at.unix_timestamp(), online }) {
+            eprintln!("[availability] failed to persist transition for {}: {}", agent_id, e);
+        }
+    }
+
+    /// Disponibilité d'un agent sur les `days` derniers jours (bornés à MAX_HISTORY_DAYS)
+    pub fn report(&self, agent_id: &str, days: u32, now: OffsetDateTime) -> AvailabilityReport {
+        let days = days.clamp(1, MAX_HISTORY_DAYS);
+        let from = now - time::Duration::days(days as i64);
+        let agents = self.agents.lock();
+        let transitions = agents.get(agent_id).map(Vec::as_slice).unwrap_or_default();
+        compute(agent_id, days, transitions, from, now)
+    }
+
+    fn append(&self, record: &TransitionRecord) -> io::Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)
+    }
+
+    /// Réécrit le journal avec l'historique conservé
+    fn compact(&self) -> io::Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        let mut content = String::new();
+        for (agent_id, transitions) in self.agents.lock().iter() {
+            for t in transitions {
+                let record = TransitionRecord { agent_id: agent_id.clone(), at: t.at.unix_timestamp(), online: t.online };
+                content.push_str(&serde_json::to_string(&record)?);
+                content.push('\n');
+            }
+        }
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, content)?;
+        fs::rename(&temp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_availability_from_transitions_with_ongoing_uptime() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let from = now - time::Duration::hours(10);
+        let at = |hours_ago: i64| Transition { at: now - time::Duration::hours(hours_ago), online: false };
+        let transitions = vec![
+            // Online bien avant la fenêtre
+            Transition { online: true, ..at(30) },
+            at(8),
+            Transition { online: true, ..at(7) },
+            at(4),
+            Transition { online: true, ..at(2) },
+        ];
+
+        let report = compute("a1", 1, &transitions, from, now);
+        // 10 h observées, 1 h + 2 h offline
+        assert_eq!(report.observed_secs, 10 * 3600);
+        assert_eq!(report.uptime_secs, 7 * 3600);
+        assert_eq!(report.uptime_percent, Some(70.0));
+        assert_eq!(report.downtime.len(), 2);
+        assert_eq!(report.downtime[0].duration_secs, 3600);
+        assert_eq!(report.downtime[1].duration_secs, 2 * 3600);
+        assert!(report.downtime.iter().all(|d| d.end.is_some()));
+
+        // Agent apparu dans la fenêtre puis tombé : le temps avant son apparition ne compte pas
+        let transitions = vec![Transition { online: true, ..at(6) }, at(3)];
+        let report = compute("a1", 1, &transitions, from, now);
+        assert_eq!(report.observed_secs, 6 * 3600);
+        assert_eq!(report.uptime_percent, Some(50.0));
+        assert_eq!(report.downtime[0].end, None);
+
+        assert_eq!(compute("a1", 1, &[], from, now).uptime_percent, None);
+    }
+
+    #[test]
+    fn test_transitions_survive_reload() {
+        let path = std::env::temp_dir().join(format!("symbion-availability-{}.jsonl", uuid::Uuid::new_v4()));
+        let now = OffsetDateTime::now_utc();
+        let log = AvailabilityLog::open(&path).unwrap();
+        log.record("a1", true, now - time::Duration::hours(4));
+        log.record("a1", true, now - time::Duration::hours(3));
+        log.record("a1", false, now - time::Duration::hours(2));
+        log.record("a1", true, now - time::Duration::hours(1));
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        let reloaded = AvailabilityLog::open(&path).unwrap();
+        let report = reloaded.report("a1", 1, now);
+        assert_eq!(report.observed_secs, 4 * 3600);
+        assert_eq!(report.uptime_secs, 3 * 3600);
+        assert_eq!(report.downtime.len(), 1);
+
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,202 @@
+/**
+ * COMMAND OUTPUT - Sortie incrémentale des commandes en flux (agents.output@v1)
+ *
+ * RÔLE :
+ * Un run_command lancé avec `stream: true` (journalctl -f, build long) publie sa
+ * sortie par morceaux au fil de l'exécution au lieu d'une réponse unique bufferisée.
+ * Le kernel accumule ces morceaux par command_id ; les clients HTTP les relèvent par
+ * polling (GET /agents/{id}/command/{command_id}/output?since=N).
+ *
+ * FONCTIONNEMENT :
+ * - Morceaux rangés par `seq` : doublons (QoS 1) ignorés, désordre corrigé
+ * - Marqueur final `completed` (exit_code, timed_out, truncated) : la sortie est complète quand
+ *   tous les morceaux qui le précèdent sont arrivés
+ * - Taille bornée par commande : au-delà, les plus anciens morceaux sont abandonnés
+ *   (comptés dans dropped_chunks)
+ * - Stockage porté par le CommandTracker : purgé avec l'enregistrement de la commande
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Suivi en direct des commandes longues sans WebSocket
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// Sortie conservée par commande (les morceaux les plus anciens sont abandonnés au-delà)
+pub const MAX_OUTPUT_BYTES_PER_COMMAND: usize = 1024 * 1024;
+
+/// Morceau de sortie publié par un agent (contrat agents.output@v1)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentOutputChunk {
+    pub command_id: String,
+    pub agent_id: String,
+    pub seq: u64,
+    #[serde(default)]
+    pub stream: Option<String>,     // stdout, stderr
+    #[serde(default)]
+    pub data: String,
+    /// Marqueur final : aucun morceau ne suit
+    pub completed: bool,
+    #[serde(default)]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub timed_out: bool,
+    /// Commande arrêtée par l'agent à sa limite de sortie (max_output_bytes)
+    #[serde(default)]
+    pub truncated: bool,
+    #[allow(dead_code)]
+    pub timestamp: String,
+}
+
+/// Morceau conservé par le kernel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutputChunk {
+    pub seq: u64,
+    pub stream: String,
+    pub data: String,
+}
+
+/// Sortie accumulée d'une commande en flux
+#[derive(Debug, Default)]
+pub struct CommandOutput {
+    /// Triés par seq
+    chunks: Vec<OutputChunk>,
+    bytes: usize,
+    dropped_chunks: u64,
+    /// seq du marqueur final = nombre de morceaux émis par l'agent
+    completed_seq: Option<u64>,
+    exit_code: Option<i32>,
+    timed_out: bool,
+    truncated: bool,
+}
+
+/// Morceaux à partir d'un seq, pour un client qui relève la sortie
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputPage {
+    pub chunks: Vec<OutputChunk>,
+    /// `since` à passer au prochain appel
+    pub next_seq: u64,
+    /// Marqueur final reçu et aucun morceau manquant
+    pub completed: bool,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub truncated: bool,
+    pub dropped_chunks: u64,
+}
+
+impl CommandOutput {
+    /// Ajoute un morceau ; faux s'il était déjà connu
+    pub fn push(&mut self, chunk: AgentOutputChunk) -> bool {
+        if chunk.completed {
+            let first = self.completed_seq.is_none();
+            self.completed_seq = Some(chunk.seq);
+            self.exit_code = chunk.exit_code;
+            self.timed_out = chunk.timed_out;
+            self.truncated = chunk.truncated;
+            return first;
+        }
+        let position = match self.chunks.binary_search_by_key(&chunk.seq, |c| c.seq) {
+            Ok(_) => return false,
+            Err(position) => position,
+        };
+        // Morceau en retard déjà sorti de la fenêtre conservée
+        if position == 0 && self.dropped_chunks > 0 {
+            return false;
+        }
+        self.bytes += chunk.data.len();
+        self.chunks.insert(position, OutputChunk {
+            seq: chunk.seq,
+            stream: chunk.stream.unwrap_or_else(|| "stdout".to_string()),
+            data: chunk.data,
+        });
+        while self.bytes > MAX_OUTPUT_BYTES_PER_COMMAND && self.chunks.len() > 1 {
+            let oldest = self.chunks.remove(0);
+            self.bytes -= oldest.data.len();
+            self.dropped_chunks += 1;
+        }
+        true
+    }
+
+    /// Tous les morceaux précédant le marqueur final sont arrivés (ou ont été abandonnés)
+    pub fn is_complete(&self) -> bool {
+        self.completed_seq.is_some_and(|total| self.chunks.len() as u64 + self.dropped_chunks >= total)
+    }
+
+    /// Morceaux de seq >= `since`
+    pub fn page(&self, since: u64) -> OutputPage {
+        let chunks: Vec<OutputChunk> = self.chunks.iter().filter(|c| c.seq >= since).cloned().collect();
+        let next_seq = chunks.last().map_or(since, |c| c.seq + 1);
+        OutputPage {
+            chunks,
+            next_seq,
+            completed: self.is_complete(),
+            exit_code: self.exit_code,
+            timed_out: self.timed_out,
+            truncated: self.truncated,
+            dropped_chunks: self.dropped_chunks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(seq: u64, data: &str) -> AgentOutputChunk {
+        AgentOutputChunk {
+            command_id: "cmd-1".to_string(),
+            agent_id: "a1b2c3d4e5f6".to_string(),
+            seq,
+            stream: Some("stdout".to_string()),
+            data: data.to_string(),
+            completed: false,
+            exit_code: None,
+            timed_out: false,
+            truncated: false,
+            timestamp: "2025-09-01T10:30:00Z".to_string(),
+        }
+    }
+
+    fn marker(seq: u64, exit_code: Option<i32>) -> AgentOutputChunk {
+        AgentOutputChunk { completed: true, exit_code, data: String::new(), stream: None, ..chunk(seq, "") }
+    }
+
+    #[test]
+    fn test_chunks_are_ordered_deduplicated_and_completed_by_the_marker() {
+        let mut output = CommandOutput::default();
+        assert!(output.push(chunk(0, "line 1\n")));
+        assert!(output.push(chunk(2, "line 3\n")));
+        assert!(!output.push(chunk(0, "line 1\n")));
+        // Marqueur arrivé avant le morceau 1 : pas encore complet
+        assert!(output.push(marker(3, Some(0))));
+        assert!(!output.page(0).completed);
+        assert!(output.push(chunk(1, "line 2\n")));
+
+        let page = output.page(0);
+        assert!(page.completed);
+        assert_eq!(page.exit_code, Some(0));
+        assert_eq!(page.chunks.iter().map(|c| c.data.as_str()).collect::<String>(), "line 1\nline 2\nline 3\n");
+        assert_eq!(page.next_seq, 3);
+
+        // Polling incrémental
+        let page = output.page(2);
+        assert_eq!(page.chunks.len(), 1);
+        assert_eq!(output.page(3).chunks.len(), 0);
+        assert_eq!(output.page(3).next_seq, 3);
+    }
+
+    #[test]
+    fn test_oldest_chunks_are_dropped_beyond_the_size_limit() {
+        let mut output = CommandOutput::default();
+        let block = "x".repeat(MAX_OUTPUT_BYTES_PER_COMMAND / 4);
+        for seq in 0..6 {
+            output.push(chunk(seq, &block));
+        }
+        output.push(marker(6, None));
+        let page = output.page(0);
+        assert_eq!(page.dropped_chunks, 2);
+        assert_eq!(page.chunks.first().unwrap().seq, 2);
+        assert!(page.completed);
+        // Morceau en retard hors fenêtre : ignoré
+        assert!(!output.push(chunk(1, "late")));
+    }
+}
@@ -0,0 +1,621 @@
+/**
+ * COMMAND TRACKER - Corrélation commandes kernel → réponses agents
+ *
+ * RÔLE :
+ * Garde la trace de chaque commande envoyée à un agent (command_id) et de la
+ * réponse reçue sur symbion/agents/response@v1. Permet aux clients HTTP
+ * d'attendre le résultat d'une commande "fire-and-forget".
+ *
+ * FONCTIONNEMENT :
+ * - send_command enregistre la commande en état "pending"
+ * - Le listener MQTT résout la commande à l'arrivée de la réponse agent
+ * - Les clients en attente (long-poll) sont réveillés via oneshot
+ * - Un sweeper périodique expire les commandes sans réponse (timeout) et purge les
+ *   commandes terminées au-delà de command_results.retention_secs
+ * - Nombre de commandes conservées borné par agent (command_results.max_per_agent) :
+ *   les plus anciennes commandes terminées de l'agent sont oubliées en premier
+ * - cancel_pending abandonne toutes les commandes en vol (self-heal sur coupure MQTT)
+ * - Réponse finale arrivée après un timeout ou une annulation : le statut n'est pas réécrit
+ *   (les clients ont déjà reçu l'échec), la réponse est gardée à part dans late_response
+ * - Réponses "partial" (commandes en flux, ex. tail_file en follow) : la commande
+ *   reste pending, chaque morceau est relayé aux abonnés jusqu'à la réponse finale
+ * - Sortie des commandes en flux (agents.output@v1, voir command_output) accumulée par
+ *   command_id, purgée avec l'enregistrement de la commande
+ * - Cache des commandes de lecture : une commande identique (agent, type, paramètres)
+ *   dans le TTL réutilise le command_id en vol ou réussi au lieu d'être renvoyée
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Résultats de commandes accessibles sans WebSocket
+ * 🎯 Base commune pour historique, timeouts et rejeu des commandes
+ */
+
+use crate::command_output::{AgentOutputChunk, CommandOutput, OutputPage};
+use crate::config::CommandResultsConf;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task;
+use tokio::time::{timeout, Duration};
+
+/// Réponse d'un agent à une commande (contrat agents.response@v1)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentCommandResponse {
+    pub command_id: String,
+    pub agent_id: String,
+    pub status: String,             // success, error, timeout, unauthorized, partial
+    pub data: Option<serde_json::Value>,
+    pub error: Option<AgentCommandError>,
+    pub execution_time_ms: Option<u64>,
+    pub timestamp: String,
+}
+
+/// Détail d'erreur renvoyé par l'agent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentCommandError {
+    pub code: String,
+    pub message: String,
+}
+
+/// État d'une commande suivie par le kernel
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandRecord {
+    pub command_id: String,
+    pub agent_id: String,
+    pub command_type: String,
+    /// pending tant qu'aucune réponse n'est arrivée, puis statut de la réponse
+    pub status: String,
+    pub sent_at: OffsetDateTime,
+    pub timeout_seconds: u32,
+    pub response: Option<AgentCommandResponse>,
+    /// Réponse de l'agent arrivée après le timeout ou l'annulation de la commande
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub late_response: Option<AgentCommandResponse>,
+}
+
+impl CommandRecord {
+    pub fn is_pending(&self) -> bool {
+        self.status == "pending"
+    }
+}
+
+impl AgentCommandResponse {
+    /// Morceau intermédiaire d'une commande en flux (une réponse finale suit)
+    pub fn is_partial(&self) -> bool {
+        self.status == "partial"
+    }
+}
+
+/// Flux des réponses d'une commande ; le récepteur reste stocké tant que personne ne s'abonne
+struct ResponseStream {
+    sender: mpsc::UnboundedSender<AgentCommandResponse>,
+    receiver: Option<mpsc::UnboundedReceiver<AgentCommandResponse>>,
+}
+
+impl ResponseStream {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self { sender, receiver: Some(receiver) }
+    }
+}
+
+/// Commande réutilisable pour une clé de cache
+struct CachedCommand {
+    command_id: String,
+    expires_at: OffsetDateTime,
+}
+
+/// Clé de cache d'une commande (les objets JSON sérialisent leurs clés triées)
+pub fn cache_key(agent_id: &str, command_type: &str, parameters: Option<&serde_json::Value>) -> String {
+    let parameters = parameters.map(|p| p.to_string()).unwrap_or_default();
+    format!("{}|{}|{}", agent_id, command_type, parameters)
+}
+
+#[derive(Default)]
+struct TrackerInner {
+    /// Map command_id -> état de la commande
+    records: HashMap<String, CommandRecord>,
+    /// Map command_id -> clients en attente du résultat
+    waiters: HashMap<String, Vec<oneshot::Sender<CommandRecord>>>,
+    /// Map command_id -> flux des réponses partielles (fermé par la réponse finale)
+    streams: HashMap<String, ResponseStream>,
+    /// Map clé de cache -> commande de lecture réutilisable
+    cache: HashMap<String, CachedCommand>,
+    /// Map command_id -> sortie accumulée d'une commande en flux
+    outputs: HashMap<String, CommandOutput>,
+    /// Durée et nombre par agent des commandes terminées conservées (les commandes en vol
+    /// ne comptent pas : seul le sweeper les fait sortir)
+    retention: CommandResultsConf,
+}
+
+impl TrackerInner {
+    /// Oublie les plus anciennes commandes terminées de l'agent au-delà de max_per_agent
+    fn enforce_agent_limit(&mut self, agent_id: &str) {
+        let mut finished: Vec<(OffsetDateTime, String)> = self.records.values()
+            .filter(|r| r.agent_id == agent_id && !r.is_pending())
+            .map(|r| (r.sent_at, r.command_id.clone()))
+            .collect();
+        let excess = finished.len().saturating_sub(self.retention.max_per_agent);
+        finished.sort();
+        for (_, command_id) in finished.into_iter().take(excess) {
+            self.records.remove(&command_id);
+            self.outputs.remove(&command_id);
+        }
+    }
+}
+
+/// Registre partagé des commandes en vol et de leurs résultats
+#[derive(Clone, Default)]
+pub struct CommandTracker {
+    inner: Arc<Mutex<TrackerInner>>,
+}
+
+impl CommandTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_retention(retention: CommandResultsConf) -> Self {
+        let tracker = Self::new();
+        tracker.inner.lock().retention = retention;
+        tracker
+    }
+
+    /// Enregistre une commande envoyée, en attente de réponse
+    pub fn track(&self, command_id: &str, agent_id: &str, command_type: &str, timeout_seconds: u32) {
+        let record = CommandRecord {
+            command_id: command_id.to_string(),
+            agent_id: agent_id.to_string(),
+            command_type: command_type.to_string(),
+            status: "pending".to_string(),
+            sent_at: OffsetDateTime::now_utc(),
+            timeout_seconds,
+            response: None,
+            late_response: None,
+        };
+        self.inner.lock().records.insert(command_id.to_string(), record);
+    }
+
+    /// Résout une commande à la réception de la réponse agent et réveille les clients en attente
+    pub fn resolve(&self, response: AgentCommandResponse) {
+        let mut inner = self.inner.lock();
+        let command_id = response.command_id.clone();
+
+        match inner.records.get(&command_id) {
+            None => {
+                eprintln!("[commands] received response for unknown command {}", command_id);
+                return;
+            }
+            Some(record) if record.agent_id != response.agent_id => {
+                eprintln!("[commands] response for command {} from agent {} ignored (sent to {})", command_id, response.agent_id, record.agent_id);
+                return;
+            }
+            Some(record) if !record.is_pending() => {
+                // Déjà close par le kernel (timeout, cancelled) : les attentes ont reçu cet état
+                let closed_by_kernel = record.response.is_none() && record.late_response.is_none();
+                if closed_by_kernel && !response.is_partial() {
+                    eprintln!("[commands] late {} response for command {} kept aside (already {})", response.status, command_id, record.status);
+                    if let Some(record) = inner.records.get_mut(&command_id) {
+                        record.late_response = Some(response);
+                    }
+                }
+                return;
+            }
+            Some(_) => {}
+        }
+        if response.is_partial() {
+            // Morceau arrivé avant l'abonnement : conservé dans le canal
+            let stream = inner.streams.entry(command_id).or_insert_with(ResponseStream::new);
+            let _ = stream.sender.send(response);
+            return;
+        }
+        if let Some(stream) = inner.streams.remove(&command_id) {
+            let _ = stream.sender.send(response.clone());
+        }
+
+        let Some(record) = inner.records.get_mut(&command_id) else { return };
+        record.status = response.status.clone();
+        record.response = Some(response);
+        let resolved = record.clone();
+
+        if let Some(waiters) = inner.waiters.remove(&command_id) {
+            for waiter in waiters {
+                let _ = waiter.send(resolved.clone());
+            }
+        }
+        inner.enforce_agent_limit(&resolved.agent_id);
+    }
+
+    /// Associe une commande à sa clé de cache jusqu'à `expires_at`
+    pub fn remember(&self, key: String, command_id: &str, expires_at: OffsetDateTime) {
+        let cached = CachedCommand { command_id: command_id.to_string(), expires_at };
+        self.inner.lock().cache.insert(key, cached);
+    }
+
+    /// command_id réutilisable pour la clé : commande non expirée, encore en vol ou réussie
+    /// (une erreur ou un timeout n'est jamais resservi)
+    pub fn cached(&self, key: &str, now: OffsetDateTime) -> Option<String> {
+        let mut inner = self.inner.lock();
+        let entry = inner.cache.get(key)?;
+        let command_id = entry.command_id.clone();
+        let usable = entry.expires_at > now
+            && inner.records.get(&command_id).is_some_and(|r| r.is_pending() || r.status == "success");
+        if usable {
+            Some(command_id)
+        } else {
+            inner.cache.remove(key);
+            None
+        }
+    }
+
+    /// Oublie une commande (publication échouée, jamais envoyée)
+    pub fn forget(&self, command_id: &str) {
+        let mut inner = self.inner.lock();
+        inner.records.remove(command_id);
+        inner.waiters.remove(command_id);
+        inner.streams.remove(command_id);
+        inner.outputs.remove(command_id);
+    }
+
+    /// Ajoute un morceau de sortie d'une commande en flux
+    pub fn record_output(&self, chunk: AgentOutputChunk) {
+        let mut inner = self.inner.lock();
+        match inner.records.get(&chunk.command_id) {
+            None => {
+                eprintln!("[commands] received output for unknown command {}", chunk.command_id);
+                return;
+            }
+            Some(record) if record.agent_id != chunk.agent_id => {
+                eprintln!("[commands] output for command {} from agent {} ignored (sent to {})", chunk.command_id, chunk.agent_id, record.agent_id);
+                return;
+            }
+            Some(_) => {}
+        }
+        inner.outputs.entry(chunk.command_id.clone()).or_default().push(chunk);
+    }
+
+    /// Sortie d'une commande à partir du morceau `since` ; None si la commande est inconnue.
+    /// Complète aussi quand la commande est terminée sans marqueur (timeout, annulation)
+    pub fn output(&self, command_id: &str, since: u64) -> Option<(CommandRecord, OutputPage)> {
+        let inner = self.inner.lock();
+        let record = inner.records.get(command_id)?.clone();
+        let mut page = inner.outputs.get(command_id).map(|o| o.page(since)).unwrap_or_else(|| CommandOutput::default().page(since));
+        page.completed |= !record.is_pending();
+        Some((record, page))
+    }
+
+    /// S'abonne aux réponses d'une commande en flux (partielles puis finale, le flux se ferme ensuite)
+    /// None si la commande est inconnue, déjà terminée ou déjà suivie par un autre abonné
+    pub fn subscribe(&self, command_id: &str) -> Option<mpsc::UnboundedReceiver<AgentCommandResponse>> {
+        let mut inner = self.inner.lock();
+        if !inner.records.get(command_id)?.is_pending() {
+            return None;
+        }
+        inner.streams.entry(command_id.to_string()).or_insert_with(ResponseStream::new).receiver.take()
+    }
+
+    /// Récupère l'état courant d'une commande
+    pub fn get(&self, command_id: &str) -> Option<CommandRecord> {
+        self.inner.lock().records.get(command_id).cloned()
+    }
+
+    /// Attend jusqu'à `wait` que la commande soit résolue
+    /// None si la commande est inconnue, sinon l'état (éventuellement encore pending)
+    pub async fn wait_for_result(&self, command_id: &str, wait: Duration) -> Option<CommandRecord> {
+        let rx = {
+            let mut inner = self.inner.lock();
+            let record = inner.records.get(command_id)?.clone();
+            if !record.is_pending() || wait.is_zero() {
+                return Some(record);
+            }
+            let (tx, rx) = oneshot::channel();
+            inner.waiters.entry(command_id.to_string()).or_default().push(tx);
+            rx
+        };
+
+        match timeout(wait, rx).await {
+            Ok(Ok(record)) => Some(record),
+            // Timeout ou canal fermé : renvoyer l'état courant
+            _ => self.get(command_id),
+        }
+    }
+
+    /// Expire les commandes pending dont le timeout est dépassé et purge les anciens résultats
+    /// Retourne les command_id passés en timeout
+    pub fn sweep_expired(&self, now: OffsetDateTime) -> Vec<String> {
+        let mut inner = self.inner.lock();
+        let mut expired = Vec::new();
+
+        for record in inner.records.values_mut() {
+            let deadline = record.sent_at + time::Duration::seconds(record.timeout_seconds as i64);
+            if record.is_pending() && now >= deadline {
+                record.status = "timeout".to_string();
+                expired.push(record.command_id.clone());
+            }
+        }
+        wake_finished(&mut inner, &expired);
+
+        // Purge des commandes terminées trop anciennes
+        let retention_cutoff = now - time::Duration::seconds(inner.retention.retention_secs as i64);
+        inner.records.retain(|_, r| r.is_pending() || r.sent_at >= retention_cutoff);
+        inner.cache.retain(|_, c| c.expires_at > now);
+        let TrackerInner { records, outputs, .. } = &mut *inner;
+        outputs.retain(|command_id, _| records.contains_key(command_id));
+
+        expired
+    }
+
+    /// Abandonne toutes les commandes en vol (statut "cancelled") ; retourne leurs command_id
+    pub fn cancel_pending(&self) -> Vec<String> {
+        let mut inner = self.inner.lock();
+        let mut cancelled = Vec::new();
+        for record in inner.records.values_mut().filter(|r| r.is_pending()) {
+            record.status = "cancelled".to_string();
+            cancelled.push(record.command_id.clone());
+        }
+        wake_finished(&mut inner, &cancelled);
+        cancelled
+    }
+}
+
+/// Réveille les clients en attente avec l'état final et ferme les flux partiels
+fn wake_finished(inner: &mut TrackerInner, command_ids: &[String]) {
+    for command_id in command_ids {
+        inner.streams.remove(command_id);
+        let Some(record) = inner.records.get(command_id).cloned() else { continue };
+        if let Some(waiters) = inner.waiters.remove(command_id) {
+            for waiter in waiters {
+                let _ = waiter.send(record.clone());
+            }
+        }
+    }
+}
+
+/// Démarre le sweeper des commandes sans réponse (vérification toutes les 5s)
+pub fn spawn_command_sweeper(tracker: CommandTracker) {
+    task::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(5));
+
+        loop {
+            interval.tick().await;
+
+            let expired = tracker.sweep_expired(OffsetDateTime::now_utc());
+            for command_id in &expired {
+                eprintln!("[commands] command {} timed out without agent response", command_id);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(command_id: &str, status: &str) -> AgentCommandResponse {
+        AgentCommandResponse {
+            command_id: command_id.to_string(),
+            agent_id: "a1b2c3d4e5f6".to_string(),
+            status: status.to_string(),
+            data: Some(serde_json::json!({"output": "ok"})),
+            error: None,
+            execution_time_ms: Some(12),
+            timestamp: "2025-09-01T10:30:01Z".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_resolved_before_timeout() {
+        let tracker = CommandTracker::new();
+        tracker.track("cmd-1", "a1b2c3d4e5f6", "get_metrics", 30);
+
+        let resolver = tracker.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            resolver.resolve(response("cmd-1", "success"));
+        });
+
+        let record = tracker.wait_for_result("cmd-1", Duration::from_secs(5)).await.unwrap();
+        assert_eq!(record.status, "success");
+        assert!(record.response.is_some());
+    }
+
+    #[test]
+    fn test_cached_command_expires_and_skips_failures() {
+        let tracker = CommandTracker::new();
+        let now = OffsetDateTime::now_utc();
+        let key = cache_key("a1b2c3d4e5f6", "get_metrics", None);
+        tracker.track("cmd-4", "a1b2c3d4e5f6", "get_metrics", 30);
+        tracker.remember(key.clone(), "cmd-4", now + time::Duration::seconds(10));
+
+        tracker.resolve(response("cmd-4", "success"));
+        assert_eq!(tracker.cached(&key, now + time::Duration::seconds(5)).as_deref(), Some("cmd-4"));
+        assert_eq!(tracker.cached(&key, now + time::Duration::seconds(10)), None);
+        // Entrée expirée retirée
+        assert_eq!(tracker.cached(&key, now), None);
+
+        // Une erreur n'est pas resservie
+        tracker.track("cmd-5", "a1b2c3d4e5f6", "get_metrics", 30);
+        tracker.remember(key.clone(), "cmd-5", now + time::Duration::seconds(10));
+        tracker.resolve(response("cmd-5", "error"));
+        assert_eq!(tracker.cached(&key, now), None);
+
+        // Clé stable quel que soit l'ordre des paramètres
+        let a: serde_json::Value = serde_json::from_str(r#"{"b":1,"a":2}"#).unwrap();
+        let b: serde_json::Value = serde_json::from_str(r#"{"a":2,"b":1}"#).unwrap();
+        assert_eq!(cache_key("x", "describe", Some(&a)), cache_key("x", "describe", Some(&b)));
+    }
+
+    #[tokio::test]
+    async fn test_wait_still_pending() {
+        let tracker = CommandTracker::new();
+        tracker.track("cmd-2", "a1b2c3d4e5f6", "reboot", 30);
+
+        let record = tracker.wait_for_result("cmd-2", Duration::from_millis(50)).await.unwrap();
+        assert!(record.is_pending());
+        assert!(record.response.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_response_from_another_agent_is_ignored() {
+        let tracker = CommandTracker::new();
+        tracker.track("cmd-3", "0a0b0c0d0e0f", "shutdown", 30);
+
+        tracker.resolve(response("cmd-3", "success"));
+        let record = tracker.wait_for_result("cmd-3", Duration::from_millis(50)).await.unwrap();
+        assert!(record.is_pending());
+        assert!(record.response.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_command_is_swept() {
+        let tracker = CommandTracker::new();
+        tracker.track("cmd-3", "a1b2c3d4e5f6", "shutdown", 1);
+
+        let waiter = tracker.clone();
+        let handle = tokio::spawn(async move {
+            waiter.wait_for_result("cmd-3", Duration::from_secs(5)).await
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // Pas encore expirée
+        assert!(tracker.sweep_expired(OffsetDateTime::now_utc()).is_empty());
+
+        let later = OffsetDateTime::now_utc() + time::Duration::seconds(2);
+        assert_eq!(tracker.sweep_expired(later), vec!["cmd-3".to_string()]);
+
+        let record = handle.await.unwrap().unwrap();
+        assert_eq!(record.status, "timeout");
+        assert_eq!(tracker.get("cmd-3").unwrap().status, "timeout");
+    }
+
+    #[tokio::test]
+    async fn test_late_response_does_not_reopen_a_timed_out_command() {
+        let tracker = CommandTracker::new();
+        tracker.track("cmd-7", "a1b2c3d4e5f6", "run_command", 1);
+        tracker.track("cmd-8", "a1b2c3d4e5f6", "get_metrics", 30);
+        let later = OffsetDateTime::now_utc() + time::Duration::seconds(2);
+        assert_eq!(tracker.sweep_expired(later), vec!["cmd-7".to_string()]);
+        tracker.cancel_pending();
+
+        tracker.resolve(response("cmd-7", "partial"));
+        assert!(tracker.get("cmd-7").unwrap().late_response.is_none());
+        for command_id in ["cmd-7", "cmd-8"] {
+            tracker.resolve(response(command_id, "success"));
+        }
+        let timed_out = tracker.get("cmd-7").unwrap();
+        assert_eq!(timed_out.status, "timeout");
+        assert!(timed_out.response.is_none());
+        assert_eq!(timed_out.late_response.unwrap().status, "success");
+        let cancelled = tracker.get("cmd-8").unwrap();
+        assert_eq!(cancelled.status, "cancelled");
+        assert_eq!(cancelled.late_response.unwrap().status, "success");
+
+        // Une commande déjà résolue par l'agent ignore les doublons
+        tracker.track("cmd-9", "a1b2c3d4e5f6", "get_metrics", 30);
+        tracker.resolve(response("cmd-9", "success"));
+        tracker.resolve(response("cmd-9", "error"));
+        let resolved = tracker.get("cmd-9").unwrap();
+        assert_eq!((resolved.status.as_str(), resolved.response.unwrap().status.as_str()), ("success", "success"));
+        assert!(resolved.late_response.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_pending_wakes_waiters() {
+        let tracker = CommandTracker::new();
+        tracker.track("cmd-5", "a1b2c3d4e5f6", "get_metrics", 30);
+        tracker.track("cmd-6", "a1b2c3d4e5f6", "get_metrics", 30);
+        tracker.resolve(response("cmd-6", "success"));
+
+        let waiter = tracker.clone();
+        let handle = tokio::spawn(async move { waiter.wait_for_result("cmd-5", Duration::from_secs(5)).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(tracker.cancel_pending(), vec!["cmd-5".to_string()]);
+        assert_eq!(handle.await.unwrap().unwrap().status, "cancelled");
+        assert_eq!(tracker.get("cmd-6").unwrap().status, "success");
+    }
+
+    #[tokio::test]
+    async fn test_wait_unknown_command() {
+        let tracker = CommandTracker::new();
+        assert!(tracker.wait_for_result("missing", Duration::from_millis(10)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_partial_responses_are_streamed_until_final() {
+        let tracker = CommandTracker::new();
+        tracker.track("cmd-9", "a1b2c3d4e5f6", "tail_file", 30);
+
+        // Premier morceau reçu avant l'abonnement : pas perdu
+        tracker.resolve(response("cmd-9", "partial"));
+        let mut stream = tracker.subscribe("cmd-9").unwrap();
+        assert!(tracker.subscribe("cmd-9").is_none());
+        tracker.resolve(response("cmd-9", "partial"));
+        assert!(tracker.get("cmd-9").unwrap().is_pending());
+
+        tracker.resolve(response("cmd-9", "success"));
+        let statuses: Vec<String> = std::iter::from_fn(|| stream.try_recv().ok()).map(|r| r.status).collect();
+        assert_eq!(statuses, vec!["partial", "partial", "success"]);
+        assert!(stream.recv().await.is_none());
+        assert_eq!(tracker.get("cmd-9").unwrap().status, "success");
+        assert!(tracker.subscribe("cmd-9").is_none());
+    }
+
+    #[test]
+    fn test_finished_commands_are_bounded_per_agent_and_expire_after_ttl() {
+        let tracker = CommandTracker::with_retention(CommandResultsConf { retention_secs: 60, max_per_agent: 2 });
+        for command_id in ["cmd-1", "cmd-2", "cmd-3", "cmd-4"] {
+            tracker.track(command_id, "a1b2c3d4e5f6", "run_command", 300);
+        }
+        tracker.track("other-1", "0a0b0c0d0e0f", "run_command", 300);
+        for command_id in ["cmd-1", "cmd-2", "cmd-3"] {
+            tracker.resolve(response(command_id, "success"));
+        }
+        tracker.resolve(AgentCommandResponse { agent_id: "0a0b0c0d0e0f".to_string(), ..response("other-1", "success") });
+
+        // Plus ancienne réponse de l'agent oubliée ; commande en vol et autre agent intacts
+        assert!(tracker.get("cmd-1").is_none());
+        assert_eq!(tracker.get("cmd-2").unwrap().status, "success");
+        assert_eq!(tracker.get("cmd-3").unwrap().status, "success");
+        assert!(tracker.get("cmd-4").unwrap().is_pending());
+        assert!(tracker.get("other-1").is_some());
+
+        // TTL configuré : les commandes terminées sont purgées, les commandes en vol restent
+        tracker.sweep_expired(OffsetDateTime::now_utc() + time::Duration::seconds(20));
+        assert!(tracker.get("cmd-2").is_some());
+        tracker.sweep_expired(OffsetDateTime::now_utc() + time::Duration::seconds(61));
+        assert!(tracker.get("cmd-2").is_none() && tracker.get("other-1").is_none());
+        assert!(tracker.get("cmd-4").unwrap().is_pending());
+    }
+
+    #[test]
+    fn test_streamed_output_is_kept_per_tracked_command() {
+        let chunk = |command_id: &str, agent_id: &str, seq: u64| -> AgentOutputChunk {
+            serde_json::from_value(serde_json::json!({
+                "command_id": command_id, "agent_id": agent_id, "seq": seq,
+                "data": format!("line {}\n", seq), "completed": false, "timestamp": "2025-09-01T10:30:00Z"
+            })).unwrap()
+        };
+        let tracker = CommandTracker::new();
+        tracker.track("cmd-1", "a1b2c3d4e5f6", "run_command", 1);
+        tracker.record_output(chunk("cmd-1", "a1b2c3d4e5f6", 0));
+        tracker.record_output(chunk("cmd-1", "0a0b0c0d0e0f", 1));
+        tracker.record_output(chunk("cmd-unknown", "a1b2c3d4e5f6", 0));
+        assert!(tracker.output("cmd-unknown", 0).is_none());
+
+        let (record, page) = tracker.output("cmd-1", 0).unwrap();
+        assert_eq!(record.status, "pending");
+        assert_eq!(page.chunks.len(), 1);
+        assert_eq!(page.chunks[0].stream, "stdout");
+        assert!(!page.completed);
+
+        // Tué par le timeout sans marqueur : le client arrête quand même de relever
+        tracker.sweep_expired(OffsetDateTime::now_utc() + time::Duration::seconds(5));
+        let (record, page) = tracker.output("cmd-1", 1).unwrap();
+        assert_eq!(record.status, "timeout");
+        assert!(page.completed);
+        assert!(page.chunks.is_empty());
+    }
+}
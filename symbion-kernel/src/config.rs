@@ -28,11 +28,125 @@
  *     hint: "192.168.1.44"
  * wol:
  *   command: "wakeonlan {mac}"
+ * stale_after_secs: 90
+ * webhooks:
+ *   - topic_filter: "symbion/agents/alert@v1"
+ *     url: "https://hooks.example.org/symbion"
+ *     method: "POST"
+ * mqtt_publish_allowlist:
+ *   - "symbion/external/#"
+ * duplicate_agent_policy: "reject"
+ * persistence_format: "json"
+ * plugin_logs:
+ *   dir: "./data/logs"
+ *   max_bytes: 10485760
+ *   keep: 5
+ *   compress: true
+ *   max_age_days: 14
+ * plugin_startup:
+ *   max_concurrent_starts: 2
+ *   ready_after_ms: 2000
+ * outbox:
+ *   path: "./data/outbox.json"
+ *   max_messages: 1000
+ * command_cache:
+ *   ttl_secs: 10
+ *   commands: ["get_metrics", "list_processes"]
+ * command_results:
+ *   retention_secs: 3600
+ *   max_per_agent: 200
+ * command_rate_limit:
+ *   burst: 20
+ *   per_second: 2.0
+ * flapping:
+ *   window_secs: 600
+ *   threshold: 3
+ * agent_monitoring:
+ *   check_interval_secs: 60
+ *   offline_timeout_secs: 120
+ *   grace_secs: 30
+ *   clock_skew_threshold_secs: 30
+ *   integrity_check_interval_secs: 3600
+ * heartbeat_sections:
+ *   processes: true
+ *   services: false
+ *   temperatures: false
+ * plugin_settings:
+ *   mqtt_username: "plugins"
+ *   mqtt_password: "change-me"
+ * api_access:
+ *   allowlist: ["192.168.1.0/24", "10.8.0.0/16", "127.0.0.1"]
+ *   trusted_proxy_header: "x-forwarded-for"
+ *   trusted_proxies: ["127.0.0.1/32"]
+ * self_heal:
+ *   check_interval_secs: 30
+ *   rules:
+ *     - condition: "plugin_failed"
+ *       action: "restart_plugin"
+ *       cooldown_secs: 300
+ *     - condition: "mqtt_disconnected"
+ *       action: "reconnect_mqtt"
+ *       cooldown_secs: 120
+ * ports:
+ *   journal:
+ *     backend: "sqlite"
+ *     path: "./data/journal.sqlite"
+ *     indexed_fields: ["context", "urgent"]
  * ```
+ *
+ * SCHÉMA (exposé en lecture via GET /config, secrets masqués) :
+ * - mqtt  : { host: string, port: u16 }                      (optionnel)
+ * - hosts : { <host_id>: { mac: string, hint: string? } }
+ * - wol   : { command: string }                              (optionnel)
+ * - ports : { <port>: { backend: "sqlite" | "json", path: string?, indexed_fields: [string] } }
+ * - stale_after_secs : u64 (défaut 90) — âge au-delà duquel un host est "stale"
+ * - webhooks : [ { topic_filter: string, url: string, method: string (défaut POST) } ]
+ * - mqtt_publish_allowlist : [string] filtres de topics publiables via POST /mqtt/publish (vide = aucun)
+ * - duplicate_agent_policy : "reject" | "rename" (défaut reject) — agent_id déjà pris par une autre machine
+ * - persistence_format : "json" | "msgpack" (défaut json) — format de data/agents.*
+ * - flapping : { window_secs: u64 (défaut 600), threshold: u32 (défaut 3) } — retours online avant alerte
+ * - plugin_logs : { dir: string (défaut ./data/logs), max_bytes: u64 (défaut 10 Mio), keep: usize (défaut 5),
+ *   compress: bool (défaut true), max_age_days: u64? } — capture stdout/stderr des plugins et rotation
+ *   (surchargeable par plugin via "log_rotation" dans le manifest)
+ * - plugin_startup : { max_concurrent_starts: usize (défaut 0 = illimité), ready_after_ms: u64 (défaut 2000) }
+ *   — démarrages de plugins simultanés au boot ; un plugin compte comme "en démarrage" tant que son processus
+ *   n'a pas tenu ready_after_ms (ou s'est déjà terminé)
+ * - outbox : { path: string (défaut ./data/outbox.json), max_messages: usize (défaut 1000) } — commandes
+ *   agents et santé kernel émises broker coupé, persistées puis publiées dans l'ordre à la reconnexion
+ *   (au-delà de max_messages le plus ancien est abandonné, voir outbox.rs)
+ * - command_cache : { ttl_secs: u64 (défaut 10, 0 = désactivé), commands: [string] (défaut get_metrics,
+ *   list_processes, list_commands, describe) } — commandes de lecture servies depuis le cache
+ * - command_results : { retention_secs: u64 (défaut 3600), max_per_agent: usize (défaut 200) } — réponses
+ *   d'agents conservées pour GET /agents/{id}/commands/{command_id} ; au-delà de max_per_agent les plus
+ *   anciennes commandes terminées de l'agent sont oubliées
+ * - command_rate_limit : { burst: u32 (défaut 20, 0 = désactivé), per_second: f64 (défaut 2) } — débit de
+ *   commandes par agent, excédent refusé en HTTP 429
+ * - agent_monitoring : { check_interval_secs: u64 (défaut 60), offline_timeout_secs: u64 (défaut 120),
+ *   grace_secs: u64 (défaut 30), clock_skew_threshold_secs: u64 (défaut 30),
+ *   integrity_check_interval_secs: u64 (défaut 3600, 0 = désactivé) } — un agent passe offline
+ *   sans heartbeat depuis timeout + grâce ; horloge agent décalée au-delà du seuil → signalée et alerte ;
+ *   registre vérifié et réparé périodiquement (doublons, entrées invalides, voir GET /agents/integrity)
+ * - heartbeat_sections : { processes: bool, services: bool, temperatures: bool } (défaut false partout) —
+ *   sections volumineuses incluses par les agents dans leurs heartbeats, diffusées sur
+ *   symbion/agents/heartbeat_config@v1 au démarrage et à chaque POST /config/reload
+ * - plugin_settings : { <clé>: valeur JSON } — réglages partagés diffusés aux plugins sur
+ *   symbion/plugins/config@v1 à chaque POST /config/reload (voir plugin_config.rs)
+ * - api_access : { allowlist: [CIDR ou IP] (vide = toutes adresses), trusted_proxy_header: string?,
+ *   trusted_proxies: [CIDR ou IP] (défaut loopback) } — adresses clientes admises sur l'API (vérifié avant
+ *   la clé API, /health exempté) ; l'en-tête du proxy n'est lu que si la connexion vient d'un proxy de confiance
+ * - self_heal : { check_interval_secs: u64 (défaut 30), rules: [ { condition: "plugin_failed" | "mqtt_disconnected",
+ *   action: "restart_plugin" | "reconnect_mqtt" | "clear_pending_commands", cooldown_secs: u64 (défaut 300) } ] }
+ *   — remédiations automatiques, au plus une par cooldown et par cible (voir self_heal.rs) ; aucune règle par défaut
+ *
+ * Toute clé ressemblant à un secret (password, token, secret, api_key...)
+ * est remplacée par "***" avant exposition.
  */
 
+use crate::persistence::PersistFormat;
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::Path};
+use serde_json::Value;
+use std::{collections::HashMap, net::IpAddr, path::Path};
 use tokio::fs;
 
 /// Configuration principale du kernel Symbion
@@ -45,6 +159,441 @@ pub struct HostsConfig {
     pub wol: Option<WolConf>,
     /// Configuration du broker MQTT (host, port)
     pub mqtt: Option<MqttConf>,
+    /// Data Ports persistés par le kernel : nom du port -> backend
+    #[serde(default)]
+    pub ports: HashMap<String, PortConf>,
+    /// Secondes sans heartbeat avant qu'un host soit considéré stale
+    #[serde(default = "default_stale_after_secs")]
+    pub stale_after_secs: u64,
+    /// Relais MQTT → HTTP vers des endpoints externes
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConf>,
+    /// Filtres de topics autorisés pour POST /mqtt/publish (vide = publication désactivée)
+    #[serde(default)]
+    pub mqtt_publish_allowlist: Vec<String>,
+    /// Traitement d'une registration dont l'agent_id est déjà pris par une autre machine
+    #[serde(default)]
+    pub duplicate_agent_policy: DuplicateAgentPolicy,
+    /// Détection des agents qui décrochent et reviennent en boucle
+    #[serde(default)]
+    pub flapping: FlappingConf,
+    /// Format des fichiers d'état (registre d'agents)
+    #[serde(default)]
+    pub persistence_format: PersistFormat,
+    /// Capture et rotation des logs des plugins
+    #[serde(default)]
+    pub plugin_logs: PluginLogsConf,
+    /// Nombre de plugins démarrés simultanément par auto_start_plugins
+    #[serde(default)]
+    pub plugin_startup: PluginStartupConf,
+    /// File persistante des messages MQTT émis broker coupé
+    #[serde(default)]
+    pub outbox: OutboxConf,
+    /// Cache des résultats de commandes de lecture
+    #[serde(default)]
+    pub command_cache: CommandCacheConf,
+    /// Conservation des réponses d'agents (durée, nombre par agent)
+    #[serde(default)]
+    pub command_results: CommandResultsConf,
+    /// Débit maximal de commandes envoyées à chaque agent
+    #[serde(default)]
+    pub command_rate_limit: CommandRateLimitConf,
+    /// Détection des agents offline (fréquence, timeout, grâce)
+    #[serde(default)]
+    pub agent_monitoring: AgentMonitoringConf,
+    /// Sections optionnelles demandées aux agents dans leurs heartbeats
+    #[serde(default)]
+    pub heartbeat_sections: HeartbeatSections,
+    /// Réglages partagés par tous les plugins (identifiants broker...)
+    #[serde(default)]
+    pub plugin_settings: serde_json::Map<String, Value>,
+    /// Restriction de l'API par adresse cliente
+    #[serde(default)]
+    pub api_access: ApiAccessConf,
+    /// Remédiations automatiques des conditions anormales
+    #[serde(default)]
+    pub self_heal: SelfHealConf,
+}
+
+/// Règles de self-heal : condition détectée → action, rejouée au plus une fois par cooldown
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SelfHealConf {
+    #[serde(default = "default_self_heal_check_interval_secs")]
+    pub check_interval_secs: u64,
+    #[serde(default)]
+    pub rules: Vec<SelfHealRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SelfHealRule {
+    pub condition: crate::self_heal::HealCondition,
+    pub action: crate::self_heal::HealAction,
+    #[serde(default = "default_self_heal_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+fn default_self_heal_check_interval_secs() -> u64 {
+    30
+}
+
+fn default_self_heal_cooldown_secs() -> u64 {
+    300
+}
+
+impl Default for SelfHealConf {
+    fn default() -> Self {
+        Self { check_interval_secs: default_self_heal_check_interval_secs(), rules: Vec::new() }
+    }
+}
+
+/// Accès à l'API par adresse IP. Les entrées invalides sont ignorées (avec un log) :
+/// une liste dont aucune entrée n'est valide refuse donc tout
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ApiAccessConf {
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+    /// En-tête posé par le reverse proxy (x-forwarded-for, x-real-ip...)
+    #[serde(default)]
+    pub trusted_proxy_header: Option<String>,
+    #[serde(default = "default_trusted_proxies")]
+    pub trusted_proxies: Vec<String>,
+}
+
+fn default_trusted_proxies() -> Vec<String> {
+    vec!["127.0.0.1/32".to_string(), "::1/128".to_string()]
+}
+
+impl Default for ApiAccessConf {
+    fn default() -> Self {
+        Self { allowlist: Vec::new(), trusted_proxy_header: None, trusted_proxies: default_trusted_proxies() }
+    }
+}
+
+/// Vrai si `ip` appartient à l'une des entrées (CIDR ou adresse seule)
+fn ip_in_list(list: &[String], ip: IpAddr) -> bool {
+    list.iter().any(|entry| {
+        let entry = entry.trim();
+        match entry.parse::<IpNet>().or_else(|_| entry.parse::<IpAddr>().map(IpNet::from)) {
+            Ok(net) => net.contains(&ip),
+            Err(_) => {
+                eprintln!("[config] api_access: entrée invalide ignorée: {}", entry);
+                false
+            }
+        }
+    })
+}
+
+impl ApiAccessConf {
+    /// Adresse admise sur l'API ; adresse inconnue refusée dès qu'une liste est configurée
+    pub fn allows(&self, ip: Option<IpAddr>) -> bool {
+        if self.allowlist.is_empty() {
+            return true;
+        }
+        ip.is_some_and(|ip| ip_in_list(&self.allowlist, ip.to_canonical()))
+    }
+
+    /// Connexion directe venant d'un proxy dont l'en-tête fait foi
+    pub fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        ip_in_list(&self.trusted_proxies, ip.to_canonical())
+    }
+}
+
+/// Surveillance des agents : vérifié toutes les `check_interval_secs`, un agent sans
+/// heartbeat depuis `offline_timeout_secs` reste online pendant `grace_secs`
+/// (gigue des heartbeats, micro-coupures réseau) avant d'être marqué offline
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct AgentMonitoringConf {
+    #[serde(default = "default_monitoring_check_interval_secs")]
+    pub check_interval_secs: u64,
+    #[serde(default = "default_monitoring_offline_timeout_secs")]
+    pub offline_timeout_secs: u64,
+    #[serde(default = "default_monitoring_grace_secs")]
+    pub grace_secs: u64,
+    /// Écart maximal toléré entre l'horodatage d'un heartbeat et sa réception
+    #[serde(default = "default_monitoring_clock_skew_threshold_secs")]
+    pub clock_skew_threshold_secs: u64,
+    /// Intervalle du contrôle d'intégrité du registre (doublons, entrées invalides) ; 0 = désactivé
+    #[serde(default = "default_monitoring_integrity_check_interval_secs")]
+    pub integrity_check_interval_secs: u64,
+}
+
+fn default_monitoring_check_interval_secs() -> u64 {
+    60
+}
+
+fn default_monitoring_offline_timeout_secs() -> u64 {
+    120
+}
+
+fn default_monitoring_grace_secs() -> u64 {
+    30
+}
+
+fn default_monitoring_clock_skew_threshold_secs() -> u64 {
+    30
+}
+
+fn default_monitoring_integrity_check_interval_secs() -> u64 {
+    3600
+}
+
+impl Default for AgentMonitoringConf {
+    fn default() -> Self {
+        Self {
+            check_interval_secs: default_monitoring_check_interval_secs(),
+            offline_timeout_secs: default_monitoring_offline_timeout_secs(),
+            grace_secs: default_monitoring_grace_secs(),
+            clock_skew_threshold_secs: default_monitoring_clock_skew_threshold_secs(),
+            integrity_check_interval_secs: default_monitoring_integrity_check_interval_secs(),
+        }
+    }
+}
+
+/// Seau à jetons par agent : `burst` commandes d'affilée, puis `per_second` en continu
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CommandRateLimitConf {
+    #[serde(default = "default_rate_limit_burst")]
+    pub burst: u32,
+    #[serde(default = "default_rate_limit_per_second")]
+    pub per_second: f64,
+}
+
+fn default_rate_limit_burst() -> u32 {
+    20
+}
+
+fn default_rate_limit_per_second() -> f64 {
+    2.0
+}
+
+impl Default for CommandRateLimitConf {
+    fn default() -> Self {
+        Self { burst: default_rate_limit_burst(), per_second: default_rate_limit_per_second() }
+    }
+}
+
+impl CommandRateLimitConf {
+    /// burst à 0 (ou débit nul) désactive la limitation
+    pub fn is_enabled(&self) -> bool {
+        self.burst > 0 && self.per_second > 0.0
+    }
+}
+
+/// Commandes idempotentes dont le résultat est réutilisé pendant ttl_secs
+/// (même agent, même type, mêmes paramètres)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CommandCacheConf {
+    #[serde(default = "default_command_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    #[serde(default = "default_cached_commands")]
+    pub commands: Vec<String>,
+}
+
+fn default_command_cache_ttl_secs() -> u64 {
+    10
+}
+
+fn default_cached_commands() -> Vec<String> {
+    ["get_metrics", "list_processes", "list_commands", "describe"].iter().map(|c| c.to_string()).collect()
+}
+
+impl Default for CommandCacheConf {
+    fn default() -> Self {
+        Self { ttl_secs: default_command_cache_ttl_secs(), commands: default_cached_commands() }
+    }
+}
+
+impl CommandCacheConf {
+    /// Vrai si les résultats de `command_type` peuvent être réutilisés
+    pub fn applies_to(&self, command_type: &str) -> bool {
+        self.ttl_secs > 0 && self.commands.iter().any(|c| c == command_type)
+    }
+}
+
+/// Réponses d'agents conservées après la fin des commandes
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CommandResultsConf {
+    /// Durée de conservation d'une commande terminée (secondes)
+    #[serde(default = "default_command_results_retention_secs")]
+    pub retention_secs: u64,
+    /// Commandes conservées par agent (les commandes en vol ne sont jamais oubliées)
+    #[serde(default = "default_command_results_max_per_agent")]
+    pub max_per_agent: usize,
+}
+
+fn default_command_results_retention_secs() -> u64 {
+    3600
+}
+
+fn default_command_results_max_per_agent() -> usize {
+    200
+}
+
+impl Default for CommandResultsConf {
+    fn default() -> Self {
+        Self {
+            retention_secs: default_command_results_retention_secs(),
+            max_per_agent: default_command_results_max_per_agent(),
+        }
+    }
+}
+
+/// Capture des sorties plugins dans {dir}/{name}.log, rotation par défaut
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PluginLogsConf {
+    #[serde(default = "default_plugin_logs_dir")]
+    pub dir: String,
+    #[serde(flatten)]
+    pub rotation: LogRotationConf,
+}
+
+fn default_plugin_logs_dir() -> String {
+    "./data/logs".to_string()
+}
+
+impl Default for PluginLogsConf {
+    fn default() -> Self {
+        Self { dir: default_plugin_logs_dir(), rotation: LogRotationConf::default() }
+    }
+}
+
+/// Étalement du démarrage des plugins (hosts contraints)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PluginStartupConf {
+    /// Plugins en cours de démarrage au même moment (0 = pas de limite)
+    #[serde(default)]
+    pub max_concurrent_starts: usize,
+    /// Durée de vie du processus au-delà de laquelle un plugin est considéré prêt
+    #[serde(default = "default_plugin_ready_after_ms")]
+    pub ready_after_ms: u64,
+}
+
+fn default_plugin_ready_after_ms() -> u64 {
+    2000
+}
+
+impl Default for PluginStartupConf {
+    fn default() -> Self {
+        Self { max_concurrent_starts: 0, ready_after_ms: default_plugin_ready_after_ms() }
+    }
+}
+
+/// File d'attente disque des publications MQTT du kernel
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct OutboxConf {
+    #[serde(default = "default_outbox_path")]
+    pub path: String,
+    /// Messages conservés au plus (le plus ancien est abandonné au-delà)
+    #[serde(default = "default_outbox_max_messages")]
+    pub max_messages: usize,
+}
+
+fn default_outbox_path() -> String {
+    "./data/outbox.json".into()
+}
+
+fn default_outbox_max_messages() -> usize {
+    1000
+}
+
+impl Default for OutboxConf {
+    fn default() -> Self {
+        Self { path: default_outbox_path(), max_messages: default_outbox_max_messages() }
+    }
+}
+
+/// Rotation d'un fichier de log : taille max, fichiers conservés, compression, âge max
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LogRotationConf {
+    /// Taille au-delà de laquelle le fichier courant est archivé
+    #[serde(default = "default_log_max_bytes")]
+    pub max_bytes: u64,
+    /// Nombre d'archives conservées ({name}.log.1 la plus récente)
+    #[serde(default = "default_log_keep")]
+    pub keep: usize,
+    /// Archives compressées en gzip ({name}.log.N.gz)
+    #[serde(default = "default_log_compress")]
+    pub compress: bool,
+    /// Archives plus anciennes supprimées même sous le quota (None = pas de limite d'âge)
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+}
+
+fn default_log_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_log_keep() -> usize {
+    5
+}
+
+fn default_log_compress() -> bool {
+    true
+}
+
+impl Default for LogRotationConf {
+    fn default() -> Self {
+        Self {
+            max_bytes: default_log_max_bytes(),
+            keep: default_log_keep(),
+            compress: default_log_compress(),
+            max_age_days: None,
+        }
+    }
+}
+
+/// Seuil de flapping : nombre de retours offline → online tolérés sur la fenêtre
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct FlappingConf {
+    #[serde(default = "default_flapping_window_secs")]
+    pub window_secs: u64,
+    #[serde(default = "default_flapping_threshold")]
+    pub threshold: u32,
+}
+
+fn default_flapping_window_secs() -> u64 {
+    600
+}
+
+fn default_flapping_threshold() -> u32 {
+    3
+}
+
+impl Default for FlappingConf {
+    fn default() -> Self {
+        Self { window_secs: default_flapping_window_secs(), threshold: default_flapping_threshold() }
+    }
+}
+
+/// Sections volumineuses des heartbeats agents, omises tant qu'elles ne sont pas demandées
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct HeartbeatSections {
+    /// Résumé des processus (top CPU/mémoire)
+    pub processes: bool,
+    /// Statut des services critiques
+    pub services: bool,
+    /// Capteurs de température
+    pub temperatures: bool,
+}
+
+/// Politique face à deux machines annonçant le même agent_id (VM clonées...)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateAgentPolicy {
+    /// Refuse la seconde registration, l'agent en place est conservé
+    #[default]
+    Reject,
+    /// Enregistre la seconde machine sous l'id suffixé (<agent_id>-2, -3...) pour l'inventaire ;
+    /// ses heartbeats et commandes MQTT portent toujours l'id d'origine
+    Rename,
+}
+
+/// Seuil stale par défaut (3 heartbeats manqués à 30s)
+pub const DEFAULT_STALE_AFTER_SECS: u64 = 90;
+
+fn default_stale_after_secs() -> u64 {
+    DEFAULT_STALE_AFTER_SECS
 }
 
 /// Configuration d'un host spécifique à monitorer
@@ -76,6 +625,33 @@ pub struct MqttConf {
     pub port: u16,
 }
 
+/// Configuration d'un Data Port géré par le kernel
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PortConf {
+    /// Backend de stockage : "sqlite" ou "json"
+    pub backend: String,
+    /// Chemin du fichier de données (défaut : ./data/<port>.sqlite ou .json)
+    pub path: Option<String>,
+    /// Champs JSON indexés pour le filtrage (sqlite uniquement)
+    #[serde(default)]
+    pub indexed_fields: Vec<String>,
+}
+
+/// Webhook externe alimenté par les messages MQTT d'un filtre de topic
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebhookConf {
+    /// Filtre MQTT (wildcards + et # acceptés)
+    pub topic_filter: String,
+    pub url: String,
+    /// Méthode HTTP (défaut POST)
+    #[serde(default = "default_webhook_method")]
+    pub method: String,
+}
+
+fn default_webhook_method() -> String {
+    "POST".to_string()
+}
+
 impl Default for HostsConfig {
     /// Configuration par défaut si aucun fichier kernel.yaml trouvé
     /// MQTT localhost:1883, pas de hosts ni WOL configurés
@@ -87,32 +663,244 @@ impl Default for HostsConfig {
                 host: "localhost".into(), 
                 port: 1883 
             }),
+            ports: HashMap::new(),
+            stale_after_secs: DEFAULT_STALE_AFTER_SECS,
+            webhooks: Vec::new(),
+            mqtt_publish_allowlist: Vec::new(),
+            duplicate_agent_policy: DuplicateAgentPolicy::default(),
+            flapping: FlappingConf::default(),
+            persistence_format: PersistFormat::default(),
+            plugin_logs: PluginLogsConf::default(),
+            plugin_startup: PluginStartupConf::default(),
+            outbox: OutboxConf::default(),
+            command_cache: CommandCacheConf::default(),
+            command_results: CommandResultsConf::default(),
+            command_rate_limit: CommandRateLimitConf::default(),
+            agent_monitoring: AgentMonitoringConf::default(),
+            heartbeat_sections: HeartbeatSections::default(),
+            plugin_settings: serde_json::Map::new(),
+            api_access: ApiAccessConf::default(),
+            self_heal: SelfHealConf::default(),
         }
     }
 }
 
+/// Échec de lecture du fichier de configuration
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("config file {0} not found")]
+    NotFound(String),
+    #[error("cannot read {path}: {source}")]
+    Io { path: String, source: std::io::Error },
+    #[error("config file {0} is empty")]
+    Empty(String),
+    #[error("invalid YAML in {path}: {source}")]
+    Yaml { path: String, source: serde_yaml::Error },
+}
+
+/// Chemin du fichier de configuration (variable SYMBION_KERNEL_CONFIG, défaut kernel.yaml)
+fn config_path() -> String {
+    std::env::var("SYMBION_KERNEL_CONFIG").unwrap_or_else(|_| "kernel.yaml".into())
+}
+
+/// Lit et parse `path` sans jamais substituer la config par défaut
+pub async fn read_config(path: &str) -> Result<HostsConfig, ConfigError> {
+    if !Path::new(path).exists() {
+        return Err(ConfigError::NotFound(path.to_string()));
+    }
+    let txt = fs::read_to_string(path).await.map_err(|source| ConfigError::Io { path: path.to_string(), source })?;
+    if txt.trim().is_empty() {
+        return Err(ConfigError::Empty(path.to_string()));
+    }
+    serde_yaml::from_str(&txt).map_err(|source| ConfigError::Yaml { path: path.to_string(), source })
+}
+
 /// Charge la configuration depuis le fichier YAML
-/// Gère les erreurs gracieusement avec fallback vers config par défaut
+/// Gère les erreurs gracieusement avec fallback vers config par défaut (démarrage)
 pub async fn load_config() -> HostsConfig {
-    // Chemin configurable via variable d'environnement
-    let path = std::env::var("SYMBION_KERNEL_CONFIG")
-        .unwrap_or_else(|_| "kernel.yaml".into());
-    
-    if Path::new(&path).exists() {
-        // Lecture du fichier YAML
-        let txt = fs::read_to_string(&path).await.unwrap_or_default();
-        if txt.trim().is_empty() { 
-            return HostsConfig::default(); 
+    match read_config(&config_path()).await {
+        Ok(cfg) => cfg,
+        Err(ConfigError::Empty(_)) => HostsConfig::default(),
+        Err(ConfigError::NotFound(path)) => {
+            eprintln!("[config] fichier {} non trouvé, config par défaut", path);
+            HostsConfig::default()
         }
-        
-        // Parsing YAML -> structures Rust avec fallback
-        serde_yaml::from_str(&txt).unwrap_or_else(|e| {
-            eprintln!("[config] YAML invalide dans {}: {}", path, e);
+        Err(e) => {
+            eprintln!("[config] {}", e);
             eprintln!("[config] utilisation de la config par défaut");
             HostsConfig::default()
-        })
-    } else {
-        eprintln!("[config] fichier {} non trouvé, config par défaut", path);
-        HostsConfig::default()
+        }
+    }
+}
+
+/// Relit la configuration (POST /config/reload) : toute erreur est rapportée, la config
+/// en cours reste alors en place au lieu d'être remplacée par les défauts
+pub async fn try_load_config() -> Result<HostsConfig, ConfigError> {
+    read_config(&config_path()).await
+}
+
+/// Valeur de remplacement des champs sensibles
+const REDACTED: &str = "***";
+
+/// Fragments de noms de clés considérées comme secrètes
+const SECRET_KEY_HINTS: &[&str] = &["password", "passwd", "secret", "token", "api_key", "apikey", "credential", "private_key"];
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SECRET_KEY_HINTS.iter().any(|hint| key.contains(hint))
+}
+
+/// Masque récursivement les valeurs dont la clé ressemble à un secret
+pub fn redact_secrets(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if is_secret_key(key) && !v.is_null() {
+                    *v = Value::String(REDACTED.into());
+                } else {
+                    redact_secrets(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+impl HostsConfig {
+    /// Configuration effective sérialisée, secrets masqués (exposition HTTP)
+    pub fn redacted(&self) -> Value {
+        let mut value = serde_json::to_value(self).unwrap_or(Value::Null);
+        redact_secrets(&mut value);
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_secret_like_fields() {
+        let mut value = json!({
+            "mqtt": { "host": "broker", "port": 1883, "password": "hunter2", "username": "kernel" },
+            "integrations": [ { "name": "meteo", "api_key": "abc", "AuthToken": "xyz" } ],
+            "client_secret": null
+        });
+        redact_secrets(&mut value);
+
+        assert_eq!(value["mqtt"]["password"], "***");
+        assert_eq!(value["mqtt"]["username"], "kernel");
+        assert_eq!(value["mqtt"]["host"], "broker");
+        assert_eq!(value["integrations"][0]["api_key"], "***");
+        assert_eq!(value["integrations"][0]["AuthToken"], "***");
+        assert_eq!(value["integrations"][0]["name"], "meteo");
+        assert!(value["client_secret"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_read_config_reports_errors_instead_of_defaults() {
+        let path = std::env::temp_dir().join(format!("symbion-kernel-{}.yaml", uuid::Uuid::new_v4()));
+        let path_str = path.to_str().unwrap();
+        assert!(matches!(read_config(path_str).await, Err(ConfigError::NotFound(_))));
+
+        std::fs::write(&path, "  \n").unwrap();
+        assert!(matches!(read_config(path_str).await, Err(ConfigError::Empty(_))));
+
+        std::fs::write(&path, "hosts: [unterminated\n").unwrap();
+        assert!(matches!(read_config(path_str).await, Err(ConfigError::Yaml { .. })));
+
+        std::fs::write(&path, "hosts:\n  desktop:\n    mac: AA:BB:CC:DD:EE:FF\n").unwrap();
+        assert_eq!(read_config(path_str).await.unwrap().hosts["desktop"].mac, "AA:BB:CC:DD:EE:FF");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_redacted_config_keeps_public_settings() {
+        let mut cfg = HostsConfig::default();
+        cfg.hosts.insert("desktop".into(), HostConf { mac: "AA:BB:CC:DD:EE:FF".into(), hint: None });
+        let value = cfg.redacted();
+
+        assert_eq!(value["mqtt"]["host"], "localhost");
+        assert_eq!(value["hosts"]["desktop"]["mac"], "AA:BB:CC:DD:EE:FF");
+    }
+
+    #[test]
+    fn test_agent_monitoring_defaults_and_overrides() {
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\n").unwrap();
+        assert_eq!(cfg.agent_monitoring, AgentMonitoringConf { check_interval_secs: 60, offline_timeout_secs: 120, grace_secs: 30, clock_skew_threshold_secs: 30, integrity_check_interval_secs: 3600 });
+
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\nagent_monitoring:\n  grace_secs: 5\n").unwrap();
+        assert_eq!(cfg.agent_monitoring.grace_secs, 5);
+        assert_eq!(cfg.agent_monitoring.offline_timeout_secs, 120);
+    }
+
+    #[test]
+    fn test_plugin_settings_are_parsed_and_redacted() {
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\n").unwrap();
+        assert!(cfg.plugin_settings.is_empty());
+
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\nplugin_settings:\n  mqtt_password: s3cret\n  locale: fr\n").unwrap();
+        assert_eq!(cfg.plugin_settings["locale"], "fr");
+        let value = cfg.redacted();
+        assert_eq!(value["plugin_settings"]["mqtt_password"], "***");
+        assert_eq!(value["plugin_settings"]["locale"], "fr");
+    }
+
+    #[test]
+    fn test_api_access_allowlist_matching() {
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\n").unwrap();
+        assert!(cfg.api_access.allows(None));
+        assert!(cfg.api_access.is_trusted_proxy("127.0.0.1".parse().unwrap()));
+
+        let yaml = "hosts: {}\napi_access:\n  allowlist: [\"192.168.1.0/24\", \"10.0.0.7\", \"fd00::/8\", \"not-an-ip\"]\n";
+        let cfg: HostsConfig = serde_yaml::from_str(yaml).unwrap();
+        let allows = |ip: &str| cfg.api_access.allows(Some(ip.parse().unwrap()));
+        assert!(allows("192.168.1.44"));
+        assert!(allows("::ffff:192.168.1.44"));
+        assert!(allows("10.0.0.7"));
+        assert!(allows("fd00::12"));
+        assert!(!allows("10.0.0.8"));
+        assert!(!allows("203.0.113.9"));
+        assert!(!cfg.api_access.allows(None));
+    }
+
+    #[test]
+    fn test_stale_after_secs_defaults_when_absent() {
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\nwol: null\nmqtt: null\n").unwrap();
+        assert_eq!(cfg.stale_after_secs, DEFAULT_STALE_AFTER_SECS);
+
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\nstale_after_secs: 300\n").unwrap();
+        assert_eq!(cfg.stale_after_secs, 300);
+    }
+
+    #[test]
+    fn test_duplicate_agent_policy_parsing() {
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\n").unwrap();
+        assert_eq!(cfg.duplicate_agent_policy, DuplicateAgentPolicy::Reject);
+
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\nduplicate_agent_policy: rename\n").unwrap();
+        assert_eq!(cfg.duplicate_agent_policy, DuplicateAgentPolicy::Rename);
+    }
+
+    #[test]
+    fn test_heartbeat_sections_default_to_omitted() {
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\n").unwrap();
+        assert_eq!(cfg.heartbeat_sections, HeartbeatSections::default());
+        assert!(!cfg.heartbeat_sections.processes);
+
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\nheartbeat_sections:\n  processes: true\n").unwrap();
+        assert_eq!(cfg.heartbeat_sections, HeartbeatSections { processes: true, services: false, temperatures: false });
+    }
+
+    #[test]
+    fn test_plugin_startup_is_unlimited_by_default() {
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\n").unwrap();
+        assert_eq!(cfg.plugin_startup, PluginStartupConf { max_concurrent_starts: 0, ready_after_ms: 2000 });
+
+        let cfg: HostsConfig = serde_yaml::from_str("hosts: {}\nplugin_startup:\n  max_concurrent_starts: 1\n").unwrap();
+        assert_eq!(cfg.plugin_startup.max_concurrent_starts, 1);
+        assert_eq!(cfg.plugin_startup.ready_after_ms, 2000);
     }
 }
@@ -0,0 +1,166 @@
+/**
+ * CONTENT NEGOTIATION - Réponses HTTP en JSON ou MessagePack selon l'en-tête Accept
+ *
+ * RÔLE :
+ * Middleware Axum partagé par toutes les routes : un client qui annonce
+ * `Accept: application/msgpack` reçoit la même réponse encodée en MessagePack.
+ *
+ * FONCTIONNEMENT :
+ * - Handlers inchangés : ils produisent du JSON, ré-encodé ici si MessagePack est négocié
+ * - Types acceptés : application/msgpack, application/x-msgpack, application/vnd.msgpack
+ * - Poids q respectés : MessagePack choisi seulement s'il n'est pas moins bien noté qu'application/json
+ * - Réponses non JSON (texte, SSE, WebSocket, corps vides) transmises telles quelles
+ * - JSON par défaut ; `Vary: Accept` ajouté sur toute réponse JSON pour les caches intermédiaires
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Dashboards mobiles : charges utiles plus compactes sur réseau contraint
+ */
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+const MSGPACK_MEDIA_TYPES: &[&str] = &[MSGPACK_CONTENT_TYPE, "application/x-msgpack", "application/vnd.msgpack"];
+
+/// Poids q d'un élément d'Accept (1.0 si absent ou illisible)
+fn quality(params: &str) -> f32 {
+    params
+        .split(';')
+        .filter_map(|param| param.trim().strip_prefix("q="))
+        .find_map(|q| q.trim().parse::<f32>().ok())
+        .unwrap_or(1.0)
+}
+
+/// Vrai si l'en-tête Accept préfère MessagePack à JSON
+pub fn wants_msgpack(headers: &HeaderMap) -> bool {
+    let mut msgpack_q: f32 = 0.0;
+    let mut json_q: f32 = 0.0;
+    for accept in headers.get_all(header::ACCEPT).iter().filter_map(|value| value.to_str().ok()) {
+        for item in accept.split(',') {
+            let (media_type, params) = item.split_once(';').unwrap_or((item, ""));
+            let media_type = media_type.trim().to_ascii_lowercase();
+            if MSGPACK_MEDIA_TYPES.contains(&media_type.as_str()) {
+                msgpack_q = msgpack_q.max(quality(params));
+            } else if media_type == "application/json" {
+                json_q = json_q.max(quality(params));
+            }
+        }
+    }
+    msgpack_q > 0.0 && msgpack_q >= json_q
+}
+
+fn is_json(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"))
+}
+
+/// Middleware : ré-encode les réponses JSON en MessagePack si le client le demande
+pub async fn negotiate_response(req: Request, next: Next) -> Response {
+    let msgpack = wants_msgpack(req.headers());
+    let mut response = next.run(req).await;
+    if !is_json(&response) {
+        return response;
+    }
+    response.headers_mut().append(header::VARY, HeaderValue::from_static("accept"));
+    if !msgpack {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("[http] failed to buffer response for msgpack encoding: {}", e);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    // JSON illisible ou non représentable : réponse d'origine conservée
+    let encoded = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .map_err(|e| e.to_string())
+        .and_then(|value| rmp_serde::to_vec_named(&value).map_err(|e| e.to_string()));
+    match encoded {
+        Ok(encoded) => {
+            parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(MSGPACK_CONTENT_TYPE));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(encoded))
+        }
+        Err(e) => {
+            eprintln!("[http] msgpack encoding failed, sending JSON: {}", e);
+            Response::from_parts(parts, Body::from(bytes))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, middleware, routing::get, Json, Router};
+    use tower::ServiceExt;
+
+    fn router() -> Router {
+        Router::new()
+            .route("/agents", get(|| async { Json(serde_json::json!({ "agents": [{ "agent_id": "a1", "cpu": 12.5 }], "count": 1 })) }))
+            .route("/health", get(|| async { "ok" }))
+            .route("/missing", get(|| async { (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "not found" }))) }))
+            .layer(middleware::from_fn(negotiate_response))
+    }
+
+    async fn call(uri: &str, accept: Option<&str>) -> (StatusCode, Option<String>, Vec<u8>) {
+        let mut request = axum::http::Request::builder().uri(uri);
+        if let Some(accept) = accept {
+            request = request.header(header::ACCEPT, accept);
+        }
+        let response = router().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let content_type = response.headers().get(header::CONTENT_TYPE).map(|v| v.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec();
+        (status, content_type, body)
+    }
+
+    #[tokio::test]
+    async fn test_same_handler_returns_msgpack_when_negotiated() {
+        let expected = serde_json::json!({ "agents": [{ "agent_id": "a1", "cpu": 12.5 }], "count": 1 });
+
+        let (status, content_type, body) = call("/agents", Some("application/msgpack")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type.as_deref(), Some(MSGPACK_CONTENT_TYPE));
+        assert_eq!(rmp_serde::from_slice::<serde_json::Value>(&body).unwrap(), expected);
+
+        let (_, content_type, body) = call("/agents", None).await;
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), expected);
+
+        // Le statut d'erreur est conservé
+        let (status, content_type, _) = call("/missing", Some("application/x-msgpack")).await;
+        assert_eq!((status, content_type.as_deref()), (StatusCode::NOT_FOUND, Some(MSGPACK_CONTENT_TYPE)));
+    }
+
+    #[tokio::test]
+    async fn test_non_json_responses_pass_through() {
+        let (_, content_type, body) = call("/health", Some("application/msgpack")).await;
+        assert!(content_type.unwrap().starts_with("text/plain"));
+        assert_eq!(body, b"ok");
+    }
+
+    #[test]
+    fn test_accept_weights_decide_between_json_and_msgpack() {
+        let accept = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, value.parse().unwrap());
+            wants_msgpack(&headers)
+        };
+        assert!(accept("application/msgpack"));
+        assert!(accept("application/json;q=0.5, application/msgpack"));
+        assert!(!accept("application/json, application/msgpack;q=0.8"));
+        assert!(!accept("application/msgpack;q=0"));
+        assert!(!accept("*/*"));
+        assert!(!wants_msgpack(&HeaderMap::new()));
+    }
+}
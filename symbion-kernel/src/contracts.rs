@@ -7,9 +7,18 @@
  * 
  * FONCTIONNEMENT :
  * - Chargement automatique des contrats JSON depuis contracts/mqtt/
+ * - Contrats livrés par les plugins (plugins/{name}.contracts/) ajoutés à la découverte
  * - Validation des messages MQTT entrants contre les schémas
  * - Découverte dynamique des événements disponibles
  * - Versioning des contrats (heartbeat@v1, heartbeat@v2...)
+ * - Diff de schémas : classification breaking / non-breaking avant un bump de version
+ * - Valeurs par défaut (opt-in "apply_defaults": true) : les champs absents d'un message
+ *   entrant reçoivent le "default" de leur schéma avant désérialisation typée
+ * - Réglages de publication optionnels "qos" (0, 1, 2) et "retain" (dernier message gardé par le
+ *   broker pour les nouveaux abonnés), appliqués par le publisher health et l'envoi des commandes agents.
+ *   Priorité : valeur explicite de l'appelant (send_command_with_qos, en test) > contrat > défaut du code
+ *   (AtLeastOnce, ExactlyOnce pour les commandes d'alimentation, jamais retenu). Les commandes agents
+ *   ne sont jamais retenues et le contrat ne descend pas sous le QoS 2 des commandes d'alimentation
  * 
  * UTILITÉ DANS SYMBION :
  * 🎯 Évolutivité : ajouter nouveaux events sans casser l'existant  
@@ -39,9 +48,11 @@
  * ```
  */
 
+use rumqttc::QoS;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Component, Path, PathBuf};
 use tokio::fs;
 
 /// Définition d'un contrat d'événement MQTT
@@ -52,6 +63,48 @@ pub struct Contract {
     pub topic: String,
     /// Schéma JSON décrivant la structure des données attendues
     pub schema: serde_json::Value,
+    /// Opt-in : compléter les champs absents avec les "default" du schéma
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub apply_defaults: bool,
+    /// QoS des publications du kernel sur ce topic (0, 1, 2) ; absent : QoS du code appelant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qos: Option<u8>,
+    /// Message retenu par le broker ; absent : réglage du code appelant
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retain: Option<bool>,
+}
+
+/// QoS et retain d'une publication du kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublishSettings {
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+impl Default for PublishSettings {
+    /// Comportement historique : AtLeastOnce, non retenu
+    fn default() -> Self {
+        Self { qos: QoS::AtLeastOnce, retain: false }
+    }
+}
+
+impl Contract {
+    /// `defaults` remplacés champ par champ par ceux que déclare le contrat
+    pub fn publish_settings(&self, defaults: PublishSettings) -> PublishSettings {
+        PublishSettings {
+            qos: self.qos.and_then(qos_from_level).unwrap_or(defaults.qos),
+            retain: self.retain.unwrap_or(defaults.retain),
+        }
+    }
+}
+
+fn qos_from_level(level: u8) -> Option<QoS> {
+    match level {
+        0 => Some(QoS::AtMostOnce),
+        1 => Some(QoS::AtLeastOnce),
+        2 => Some(QoS::ExactlyOnce),
+        _ => None,
+    }
 }
 
 /// Registre central de tous les contrats MQTT disponibles
@@ -60,6 +113,8 @@ pub struct Contract {
 pub struct ContractRegistry {
     /// Map nom_contrat -> définition complète du contrat
     contracts: HashMap<String, Contract>, // "heartbeat@v2" -> Contract
+    /// Dossier source des contrats (pour charger des versions candidates)
+    contracts_dir: Option<PathBuf>,
 }
 
 impl ContractRegistry {
@@ -67,6 +122,7 @@ impl ContractRegistry {
     pub fn new() -> Self {
         Self {
             contracts: HashMap::new(),
+            contracts_dir: None,
         }
     }
 
@@ -74,30 +130,44 @@ impl ContractRegistry {
     /// Scan récursif des fichiers .json et parsing automatique
     pub async fn load_contracts_from_dir<P: AsRef<Path>>(contracts_dir: P) -> Result<Self, Box<dyn std::error::Error>> {
         let mut registry = Self::new();
-        let mut entries = fs::read_dir(contracts_dir).await?;
-        
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                match fs::read_to_string(&path).await {
-                    Ok(content) => {
-                        match serde_json::from_str::<Contract>(&content) {
-                            Ok(contract) => {
-                                let contract_name = extract_contract_name(&contract.topic);
-                                eprintln!("[contracts] loaded: {} from {:?}", contract_name, path.file_name().unwrap());
-                                registry.contracts.insert(contract_name, contract);
-                            }
-                            Err(e) => eprintln!("[contracts] JSON invalide dans {:?}: {}", path, e),
-                        }
-                    }
-                    Err(e) => eprintln!("[contracts] échec lecture {:?}: {}", path, e),
-                }
-            }
+        registry.contracts_dir = Some(contracts_dir.as_ref().to_path_buf());
+
+        for (path, contract) in read_contracts_dir(contracts_dir.as_ref()).await? {
+            let contract_name = extract_contract_name(&contract.topic);
+            eprintln!("[contracts] loaded: {} from {:?}", contract_name, path.file_name().unwrap());
+            registry.contracts.insert(contract_name, contract);
         }
         
         Ok(registry)
     }
 
+    /// Enregistre les contrats livrés avec un plugin (plugins/{name}.contracts/)
+    /// Seuls les contrats déclarés dans son manifest sont pris ; ceux de contracts/mqtt/ restent prioritaires
+    pub async fn register_plugin_contracts(&mut self, plugin: &str, dir: &Path, declared: &[String]) -> Vec<String> {
+        let bundled = match read_contracts_dir(dir).await {
+            Ok(bundled) => bundled,
+            Err(e) => {
+                eprintln!("[contracts] cannot read {:?} for plugin {}: {}", dir, plugin, e);
+                return Vec::new();
+            }
+        };
+
+        let mut registered = Vec::new();
+        for (path, contract) in bundled {
+            let contract_name = extract_contract_name(&contract.topic);
+            if !declared.contains(&contract_name) {
+                eprintln!("[contracts] {} ignored: {} not declared by plugin {}", contract_name, path.display(), plugin);
+            } else if self.contracts.contains_key(&contract_name) {
+                eprintln!("[contracts] {} already loaded, bundled copy of plugin {} ignored", contract_name, plugin);
+            } else {
+                eprintln!("[contracts] loaded: {} from plugin {}", contract_name, plugin);
+                self.contracts.insert(contract_name.clone(), contract);
+                registered.push(contract_name);
+            }
+        }
+        registered
+    }
+
     /// Valide qu'un message MQTT respecte son contrat
     /// Vérification que le payload JSON correspond au schéma attendu
     #[allow(dead_code)]
@@ -115,6 +185,23 @@ impl ContractRegistry {
         Ok(())
     }
 
+    /// Complète un message entrant avec les valeurs par défaut de son contrat (si opt-in)
+    /// Retourne le nombre de champs ajoutés
+    pub fn apply_defaults(&self, topic: &str, message: &mut Value) -> usize {
+        match self.contracts.get(&extract_contract_name(topic)) {
+            Some(contract) if contract.apply_defaults => fill_defaults(&contract.schema, message),
+            _ => 0,
+        }
+    }
+
+    /// Réglages de publication sur `topic` : ceux du contrat s'il en déclare, `defaults` sinon
+    pub fn publish_settings(&self, topic: &str, defaults: PublishSettings) -> PublishSettings {
+        match self.contracts.get(&extract_contract_name(topic)) {
+            Some(contract) => contract.publish_settings(defaults),
+            None => defaults,
+        }
+    }
+
     /// Liste tous les noms de contrats disponibles
     /// Utilisé par l'API /contracts pour découverte automatique
     pub fn list_contracts(&self) -> Vec<String> {
@@ -126,12 +213,201 @@ impl ContractRegistry {
     pub fn get_contract(&self, contract_name: &str) -> Option<&Contract> {
         self.contracts.get(contract_name)
     }
+
+    /// Charge un fichier de contrat candidat, relatif au dossier des contrats
+    /// Refuse les chemins absolus et les remontées (..) hors du dossier
+    pub async fn load_candidate(&self, file: &str) -> Result<Contract, String> {
+        let dir = self.contracts_dir.as_ref()
+            .ok_or_else(|| "contracts directory unknown".to_string())?;
+        let relative = Path::new(file);
+        if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("invalid contract path: {}", file));
+        }
+
+        let content = fs::read_to_string(dir.join(relative)).await
+            .map_err(|e| format!("cannot read {}: {}", file, e))?;
+        serde_json::from_str(&content).map_err(|e| format!("invalid contract {}: {}", file, e))
+    }
+}
+
+/// Lit les contrats (.json) d'un dossier ; les fichiers illisibles ou invalides sont ignorés
+async fn read_contracts_dir(dir: &Path) -> std::io::Result<Vec<(PathBuf, Contract)>> {
+    let mut contracts = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) == Some("json") {
+            match fs::read_to_string(&path).await {
+                Ok(content) => match serde_json::from_str::<Contract>(&content) {
+                    Ok(Contract { qos: Some(qos), .. }) if qos_from_level(qos).is_none() => {
+                        eprintln!("[contracts] qos {} invalide dans {:?} (0, 1 ou 2)", qos, path)
+                    }
+                    Ok(contract) => contracts.push((path, contract)),
+                    Err(e) => eprintln!("[contracts] JSON invalide dans {:?}: {}", path, e),
+                },
+                Err(e) => eprintln!("[contracts] échec lecture {:?}: {}", path, e),
+            }
+        }
+    }
+    Ok(contracts)
+}
+
+/// Ajoute les propriétés absentes ayant un "default" (récursif sur objets et tableaux)
+/// Les "examples" restent illustratifs : jamais utilisés comme valeurs
+pub fn fill_defaults(schema: &Value, message: &mut Value) -> usize {
+    match message {
+        Value::Object(fields) => {
+            let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) else { return 0 };
+            let mut filled = 0;
+            for (name, property) in properties {
+                match fields.get_mut(name) {
+                    Some(value) => filled += fill_defaults(property, value),
+                    None => {
+                        if let Some(default) = property.get("default") {
+                            fields.insert(name.clone(), default.clone());
+                            filled += 1;
+                        }
+                    }
+                }
+            }
+            filled
+        }
+        Value::Array(items) => match schema.get("items") {
+            Some(item_schema) => items.iter_mut().map(|item| fill_defaults(item_schema, item)).sum(),
+            None => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// Nature d'un changement entre deux versions de schéma
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    PropertyRemoved,
+    PropertyAdded,
+    RequiredAdded,
+    RequiredRemoved,
+    TypeChanged,
+    EnumValueRemoved,
+    EnumValueAdded,
+}
+
+impl ChangeKind {
+    /// Un changement est breaking s'il peut rejeter ou mal interpréter un message existant
+    pub fn is_breaking(&self) -> bool {
+        matches!(self, ChangeKind::PropertyRemoved | ChangeKind::RequiredAdded
+            | ChangeKind::TypeChanged | ChangeKind::EnumValueRemoved)
+    }
+}
+
+/// Changement individuel détecté (chemin pointé, ex: "system.cpu.percent")
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub breaking: bool,
+    pub detail: String,
+}
+
+/// Rapport de compatibilité entre deux schémas de contrat
+#[derive(Debug, Clone, Serialize)]
+pub struct ContractDiff {
+    pub breaking: bool,
+    pub changes: Vec<SchemaChange>,
+}
+
+/// Compare deux schémas JSON (ancien -> nouveau) et classe les changements
+pub fn diff_schemas(old: &Value, new: &Value) -> ContractDiff {
+    let mut changes = Vec::new();
+    diff_node("", old, new, &mut changes);
+    ContractDiff {
+        breaking: changes.iter().any(|c| c.breaking),
+        changes,
+    }
+}
+
+fn push_change(changes: &mut Vec<SchemaChange>, path: &str, kind: ChangeKind, detail: String) {
+    let breaking = kind.is_breaking();
+    changes.push(SchemaChange { path: path.to_string(), kind, breaking, detail });
+}
+
+fn child_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() { name.to_string() } else { format!("{}.{}", parent, name) }
+}
+
+/// Ensemble des types déclarés ("type": "string" ou ["string", "null"])
+fn schema_types(node: &Value) -> BTreeSet<String> {
+    match node.get("type") {
+        Some(Value::String(t)) => BTreeSet::from([t.clone()]),
+        Some(Value::Array(types)) => types.iter().filter_map(|t| t.as_str().map(String::from)).collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+fn string_set(node: &Value, key: &str) -> BTreeSet<String> {
+    node.get(key)
+        .and_then(|v| v.as_array())
+        .map(|items| items.iter().map(|i| i.as_str().map(String::from).unwrap_or_else(|| i.to_string())).collect())
+        .unwrap_or_default()
+}
+
+fn diff_node(path: &str, old: &Value, new: &Value, changes: &mut Vec<SchemaChange>) {
+    let (old_types, new_types) = (schema_types(old), schema_types(new));
+    if !old_types.is_empty() && !new_types.is_empty() && old_types != new_types {
+        push_change(changes, path, ChangeKind::TypeChanged,
+            format!("{:?} -> {:?}", old_types, new_types));
+        return;
+    }
+
+    let (old_enum, new_enum) = (string_set(old, "enum"), string_set(new, "enum"));
+    if !old_enum.is_empty() || !new_enum.is_empty() {
+        for value in old_enum.difference(&new_enum) {
+            push_change(changes, path, ChangeKind::EnumValueRemoved, format!("value {} removed", value));
+        }
+        for value in new_enum.difference(&old_enum) {
+            push_change(changes, path, ChangeKind::EnumValueAdded, format!("value {} added", value));
+        }
+    }
+
+    let empty = serde_json::Map::new();
+    let old_props = old.get("properties").and_then(|p| p.as_object()).unwrap_or(&empty);
+    let new_props = new.get("properties").and_then(|p| p.as_object()).unwrap_or(&empty);
+    let (old_required, new_required) = (string_set(old, "required"), string_set(new, "required"));
+
+    for (name, old_child) in old_props {
+        let child = child_path(path, name);
+        match new_props.get(name) {
+            Some(new_child) => diff_node(&child, old_child, new_child, changes),
+            None => push_change(changes, &child, ChangeKind::PropertyRemoved, "property removed".into()),
+        }
+    }
+    for name in new_props.keys().filter(|name| !old_props.contains_key(*name)) {
+        let child = child_path(path, name);
+        if new_required.contains(name) {
+            push_change(changes, &child, ChangeKind::RequiredAdded, "new required property".into());
+        } else {
+            push_change(changes, &child, ChangeKind::PropertyAdded, "new optional property".into());
+        }
+    }
+    // Propriétés existantes devenues requises / optionnelles
+    for name in new_required.difference(&old_required).filter(|n| old_props.contains_key(*n)) {
+        push_change(changes, &child_path(path, name), ChangeKind::RequiredAdded, "property became required".into());
+    }
+    for name in old_required.difference(&new_required).filter(|n| new_props.contains_key(*n)) {
+        push_change(changes, &child_path(path, name), ChangeKind::RequiredRemoved, "property became optional".into());
+    }
+
+    if let (Some(old_items), Some(new_items)) = (old.get("items"), new.get("items")) {
+        diff_node(&child_path(path, "[]"), old_items, new_items, changes);
+    }
 }
 
 /// Extrait le nom du contrat depuis le topic MQTT complet
 /// Transformation : "symbion/agents/command@v1" -> "agents.command@v1"
 /// Transformation : "symbion/hosts/heartbeat@v2" -> "hosts.heartbeat@v2" 
-fn extract_contract_name(topic: &str) -> String {
+pub fn extract_contract_name(topic: &str) -> String {
     let parts: Vec<&str> = topic.split('/').collect();
     if parts.len() >= 3 && parts[0] == "symbion" {
         // Nouveau format: symbion/{namespace}/{event}@{version} -> {namespace}.{event}@{version}
@@ -155,4 +431,173 @@ mod tests {
         assert_eq!(extract_contract_name("heartbeat@v2"), "heartbeat@v2");
         assert_eq!(extract_contract_name("symbion/memo/created@v1"), "memo.created@v1");
     }
-}
\ No newline at end of file
+
+    fn kinds(diff: &ContractDiff) -> Vec<(String, ChangeKind)> {
+        diff.changes.iter().map(|c| (c.path.clone(), c.kind.clone())).collect()
+    }
+
+    fn base_schema() -> Value {
+        serde_json::json!({
+            "type": "object",
+            "required": ["agent_id"],
+            "properties": {
+                "agent_id": { "type": "string" },
+                "status": { "type": "string", "enum": ["online", "idle"] },
+                "system": {
+                    "type": "object",
+                    "properties": { "cpu": { "type": "number" } }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_diff_identical_is_empty() {
+        let diff = diff_schemas(&base_schema(), &base_schema());
+        assert!(!diff.breaking);
+        assert!(diff.changes.is_empty());
+    }
+
+    #[test]
+    fn test_diff_optional_added_is_non_breaking() {
+        let mut new = base_schema();
+        new["properties"]["system"]["properties"]["ram"] = serde_json::json!({ "type": "number" });
+        new["properties"]["status"]["enum"] = serde_json::json!(["online", "idle", "busy"]);
+
+        let diff = diff_schemas(&base_schema(), &new);
+        assert!(!diff.breaking);
+        assert_eq!(kinds(&diff), vec![
+            ("status".to_string(), ChangeKind::EnumValueAdded),
+            ("system.ram".to_string(), ChangeKind::PropertyAdded),
+        ]);
+    }
+
+    #[test]
+    fn test_diff_removed_property_is_breaking() {
+        let mut new = base_schema();
+        new["properties"]["system"]["properties"].as_object_mut().unwrap().remove("cpu");
+
+        let diff = diff_schemas(&base_schema(), &new);
+        assert!(diff.breaking);
+        assert_eq!(kinds(&diff), vec![("system.cpu".to_string(), ChangeKind::PropertyRemoved)]);
+    }
+
+    #[test]
+    fn test_diff_required_added_is_breaking() {
+        let mut new = base_schema();
+        new["properties"]["hostname"] = serde_json::json!({ "type": "string" });
+        new["required"] = serde_json::json!(["agent_id", "hostname", "status"]);
+
+        let diff = diff_schemas(&base_schema(), &new);
+        assert!(diff.breaking);
+        assert_eq!(kinds(&diff), vec![
+            ("hostname".to_string(), ChangeKind::RequiredAdded),
+            ("status".to_string(), ChangeKind::RequiredAdded),
+        ]);
+
+        // L'inverse (required retiré) reste compatible
+        let relaxed = diff_schemas(&new, &base_schema());
+        assert!(relaxed.changes.iter().any(|c| c.kind == ChangeKind::RequiredRemoved && !c.breaking));
+    }
+
+    #[test]
+    fn test_diff_type_changed_is_breaking() {
+        let mut new = base_schema();
+        new["properties"]["system"]["properties"]["cpu"] = serde_json::json!({ "type": "string" });
+        new["properties"]["status"]["enum"] = serde_json::json!(["online"]);
+
+        let diff = diff_schemas(&base_schema(), &new);
+        assert!(diff.breaking);
+        assert_eq!(kinds(&diff), vec![
+            ("status".to_string(), ChangeKind::EnumValueRemoved),
+            ("system.cpu".to_string(), ChangeKind::TypeChanged),
+        ]);
+    }
+
+    fn response_contract(apply_defaults: bool) -> Contract {
+        Contract {
+            topic: "symbion/plugins/http_response@v1".into(),
+            schema: serde_json::json!({
+                "type": "object",
+                "required": ["request_id"],
+                "properties": {
+                    "request_id": { "type": "string" },
+                    "status": { "type": "integer", "default": 200 },
+                    "headers": {
+                        "type": "object",
+                        "properties": { "content_type": { "type": "string", "default": "application/json" } }
+                    },
+                    "items": {
+                        "type": "array",
+                        "items": { "type": "object", "properties": { "qty": { "type": "integer", "default": 1 } } }
+                    }
+                }
+            }),
+            apply_defaults,
+            qos: None,
+            retain: None,
+        }
+    }
+
+    fn registry_with(contract: Contract) -> ContractRegistry {
+        let mut registry = ContractRegistry::new();
+        registry.contracts.insert(extract_contract_name(&contract.topic), contract);
+        registry
+    }
+
+    #[test]
+    fn test_missing_optional_field_gets_contract_default() {
+        let registry = registry_with(response_contract(true));
+        let mut message = serde_json::json!({
+            "request_id": "r1",
+            "headers": {},
+            "items": [{ "qty": 5 }, {}]
+        });
+
+        let filled = registry.apply_defaults("symbion/plugins/http_response@v1", &mut message);
+        assert_eq!(filled, 3);
+        assert_eq!(message["status"], 200);
+        assert_eq!(message["headers"]["content_type"], "application/json");
+        assert_eq!(message["items"], serde_json::json!([{ "qty": 5 }, { "qty": 1 }]));
+
+        // Valeurs présentes conservées, pas de default inventé
+        let mut explicit = serde_json::json!({ "request_id": "r2", "status": 404 });
+        assert_eq!(registry.apply_defaults("symbion/plugins/http_response@v1", &mut explicit), 0);
+        assert_eq!(explicit, serde_json::json!({ "request_id": "r2", "status": 404 }));
+    }
+
+    #[test]
+    fn test_defaults_are_opt_in() {
+        let registry = registry_with(response_contract(false));
+        let mut message = serde_json::json!({ "request_id": "r1" });
+        assert_eq!(registry.apply_defaults("symbion/plugins/http_response@v1", &mut message), 0);
+        assert_eq!(registry.apply_defaults("symbion/unknown/event@v1", &mut message), 0);
+        assert_eq!(message, serde_json::json!({ "request_id": "r1" }));
+
+        // Le flag n'apparaît pas dans les contrats qui ne l'activent pas
+        let json = serde_json::to_value(response_contract(false)).unwrap();
+        assert!(json.get("apply_defaults").is_none());
+    }
+
+    #[test]
+    fn test_contract_publish_settings_override_code_defaults() {
+        let health = Contract {
+            topic: "symbion/kernel/health@v1".into(),
+            schema: serde_json::json!({}),
+            apply_defaults: false,
+            qos: None,
+            retain: Some(true),
+        };
+        let registry = registry_with(health);
+
+        // Retain du contrat, QoS du code (non déclaré)
+        let settings = registry.publish_settings("symbion/kernel/health@v1", PublishSettings::default());
+        assert_eq!(settings, PublishSettings { qos: QoS::AtLeastOnce, retain: true });
+        // Topic sans contrat : défauts de l'appelant inchangés
+        let exactly_once = PublishSettings { qos: QoS::ExactlyOnce, retain: false };
+        assert_eq!(registry.publish_settings("symbion/unknown/event@v1", exactly_once), exactly_once);
+
+        let json = serde_json::to_value(response_contract(false)).unwrap();
+        assert!(json.get("qos").is_none() && json.get("retain").is_none());
+    }
+}
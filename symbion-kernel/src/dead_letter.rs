@@ -0,0 +1,133 @@
+/**
+ * DEAD LETTER - Messages MQTT que le kernel n'a pas pu traiter
+ *
+ * RÔLE :
+ * Un message reçu par le listener qui échoue (JSON invalide, contrat non respecté,
+ * échec du handler) était seulement logué et perdu. Il est désormais conservé avec
+ * un id pour être inspecté puis rejoué une fois la cause corrigée.
+ *
+ * FONCTIONNEMENT :
+ * - Le listener MQTT range chaque échec (topic, payload, raison) avec un id croissant
+ * - Nombre borné : au-delà de MAX_DEAD_LETTERS le plus ancien est abandonné
+ * - GET /deadletter liste les messages, POST /deadletter/{id}/replay (route admin) republie
+ *   le message sur son topic d'origine, payload éventuellement corrigé, puis le retire :
+ *   il repasse par le handler normal (et revient en dead-letter s'il échoue encore)
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Récupération des messages perdus sur une panne transitoire ou un agent mal versionné
+ */
+
+use crate::mqtt_publish::MqttPublisher;
+use parking_lot::Mutex;
+use rumqttc::QoS;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// Messages conservés (les plus anciens sont abandonnés au-delà)
+pub const MAX_DEAD_LETTERS: usize = 500;
+
+/// Message non traité
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub id: u64,
+    pub topic: String,
+    /// Payload reçu (octets non UTF-8 remplacés)
+    pub payload: String,
+    pub reason: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub received_at: OffsetDateTime,
+}
+
+#[derive(Default)]
+struct StoreInner {
+    next_id: u64,
+    letters: VecDeque<DeadLetter>,
+}
+
+/// File partagée des messages en échec
+#[derive(Clone, Default)]
+pub struct DeadLetterStore {
+    inner: Arc<Mutex<StoreInner>>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ReplayError {
+    #[error("no dead letter {0}")]
+    NotFound(u64),
+    #[error("replay failed: {0}")]
+    Publish(String),
+}
+
+impl DeadLetterStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Conserve un message en échec ; retourne son id
+    pub fn record(&self, topic: &str, payload: &[u8], reason: &str) -> u64 {
+        let mut inner = self.inner.lock();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.letters.push_back(DeadLetter {
+            id,
+            topic: topic.to_string(),
+            payload: String::from_utf8_lossy(payload).into_owned(),
+            reason: reason.to_string(),
+            received_at: OffsetDateTime::now_utc(),
+        });
+        while inner.letters.len() > MAX_DEAD_LETTERS {
+            inner.letters.pop_front();
+        }
+        id
+    }
+
+    /// Messages du plus ancien au plus récent
+    pub fn list(&self) -> Vec<DeadLetter> {
+        self.inner.lock().letters.iter().cloned().collect()
+    }
+
+    /// Republie un message sur son topic d'origine (payload remplacé par `edited` si fourni)
+    /// puis le retire de la file
+    pub fn replay(&self, id: u64, edited: Option<&serde_json::Value>, publisher: &dyn MqttPublisher) -> Result<DeadLetter, ReplayError> {
+        let mut letter = self.inner.lock().letters.iter().find(|l| l.id == id).cloned().ok_or(ReplayError::NotFound(id))?;
+        if let Some(edited) = edited {
+            letter.payload = match edited {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+        }
+        publisher.publish(&letter.topic, QoS::AtLeastOnce, false, letter.payload.clone().into_bytes())
+            .map_err(ReplayError::Publish)?;
+        self.inner.lock().letters.retain(|l| l.id != id);
+        Ok(letter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mqtt_publish::RecordingPublisher;
+
+    #[test]
+    fn test_store_is_bounded_and_replay_removes_the_message() {
+        let store = DeadLetterStore::new();
+        for n in 0..MAX_DEAD_LETTERS + 2 {
+            store.record("symbion/agents/response@v1", format!("{{\"n\": {n}}}").as_bytes(), "missing field `agent_id`");
+        }
+        let letters = store.list();
+        assert_eq!(letters.len(), MAX_DEAD_LETTERS);
+        assert_eq!(letters[0].id, 3);
+
+        let publisher = RecordingPublisher::default();
+        assert_eq!(store.replay(1, None, &publisher).unwrap_err(), ReplayError::NotFound(1));
+        let replayed = store.replay(3, Some(&serde_json::json!({ "n": 42 })), &publisher).unwrap();
+        assert_eq!(replayed.payload, "{\"n\":42}");
+        let published = &publisher.published()[0];
+        assert_eq!((published.topic.as_str(), published.text()), ("symbion/agents/response@v1", "{\"n\":42}".to_string()));
+        assert_eq!(store.list().len(), MAX_DEAD_LETTERS - 1);
+        assert!(store.replay(3, None, &publisher).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+/**
+ * FLAPPING - Écarts entre heartbeats et détection des agents instables
+ *
+ * RÔLE :
+ * Distingue un agent stable d'un agent qui décroche et revient en boucle
+ * (Wi-Fi instable, crash loop, veille agressive).
+ *
+ * FONCTIONNEMENT :
+ * - Chaque heartbeat enregistre l'écart avec le précédent (derniers écarts conservés)
+ * - Chaque retour offline → online compte une transition horodatée
+ * - Score de flapping = transitions dans la fenêtre glissante (flapping.window_secs)
+ * - Score >= flapping.threshold → alerte unique, réarmée quand le score redescend
+ * - Alerte publiée sur symbion/agents/alert@v1, stats via GET /agents/{id}/liveness
+ */
+
+use crate::config::FlappingConf;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+/// Nombre d'écarts conservés par agent pour les statistiques
+const GAP_HISTORY: usize = 32;
+
+#[derive(Debug, Default)]
+struct AgentLiveness {
+    last_heartbeat: Option<OffsetDateTime>,
+    heartbeats: u64,
+    gaps_secs: VecDeque<f64>,
+    reconnects: VecDeque<OffsetDateTime>,
+    alerted: bool,
+}
+
+impl AgentLiveness {
+    /// Oublie les transitions sorties de la fenêtre, réarme l'alerte si besoin
+    fn prune(&mut self, now: OffsetDateTime, conf: &FlappingConf) -> u32 {
+        let cutoff = now - time::Duration::seconds(conf.window_secs as i64);
+        while self.reconnects.front().is_some_and(|t| *t < cutoff) {
+            self.reconnects.pop_front();
+        }
+        let score = self.reconnects.len() as u32;
+        if score < conf.threshold {
+            self.alerted = false;
+        }
+        score
+    }
+}
+
+/// Statistiques de liveness d'un agent
+#[derive(Debug, Clone, Serialize)]
+pub struct LivenessStats {
+    pub agent_id: String,
+    pub heartbeats: u64,
+    pub last_gap_secs: Option<f64>,
+    pub avg_gap_secs: Option<f64>,
+    pub max_gap_secs: Option<f64>,
+    /// Transitions offline → online dans la fenêtre
+    pub flapping_score: u32,
+    pub flapping: bool,
+    pub window_secs: u64,
+}
+
+/// Alerte émise quand un agent dépasse le seuil de flapping
+#[derive(Debug, Clone, Serialize)]
+pub struct FlappingAlert {
+    pub agent_id: String,
+    pub kind: &'static str,
+    pub flapping_score: u32,
+    pub threshold: u32,
+    pub window_secs: u64,
+    pub timestamp: String,
+}
+
+/// Suivi des heartbeats et transitions de tous les agents
+pub struct FlappingTracker {
+    conf: FlappingConf,
+    agents: Mutex<HashMap<String, AgentLiveness>>,
+}
+
+impl FlappingTracker {
+    pub fn new(conf: FlappingConf) -> Self {
+        Self { conf, agents: Mutex::new(HashMap::new()) }
+    }
+
+    /// Enregistre un heartbeat et l'écart depuis le précédent
+    pub fn record_heartbeat(&self, agent_id: &str, at: OffsetDateTime) {
+        let mut agents = self.agents.lock();
+        let liveness = agents.entry(agent_id.to_string()).or_default();
+        if let Some(previous) = liveness.last_heartbeat {
+            if liveness.gaps_secs.len() == GAP_HISTORY {
+                liveness.gaps_secs.pop_front();
+            }
+            liveness.gaps_secs.push_back((at - previous).as_seconds_f64().max(0.0));
+        }
+        liveness.last_heartbeat = Some(at);
+        liveness.heartbeats += 1;
+    }
+
+    /// Enregistre un retour offline → online ; retourne l'alerte si le seuil vient d'être franchi
+    pub fn record_reconnect(&self, agent_id: &str, at: OffsetDateTime) -> Option<FlappingAlert> {
+        let mut agents = self.agents.lock();
+        let liveness = agents.entry(agent_id.to_string()).or_default();
+        liveness.reconnects.push_back(at);
+        let score = liveness.prune(at, &self.conf);
+
+        if score >= self.conf.threshold && !liveness.alerted {
+            liveness.alerted = true;
+            return Some(FlappingAlert {
+                agent_id: agent_id.to_string(),
+                kind: "flapping",
+                flapping_score: score,
+                threshold: self.conf.threshold,
+                window_secs: self.conf.window_secs,
+                timestamp: at.format(&Rfc3339).unwrap_or_default(),
+            });
+        }
+        None
+    }
+
+    /// Statistiques courantes d'un agent (None si jamais vu)
+    pub fn stats(&self, agent_id: &str, now: OffsetDateTime) -> Option<LivenessStats> {
+        let mut agents = self.agents.lock();
+        let liveness = agents.get_mut(agent_id)?;
+        let score = liveness.prune(now, &self.conf);
+        let gaps = &liveness.gaps_secs;
+        Some(LivenessStats {
+            agent_id: agent_id.to_string(),
+            heartbeats: liveness.heartbeats,
+            last_gap_secs: gaps.back().copied(),
+            avg_gap_secs: (!gaps.is_empty()).then(|| gaps.iter().sum::<f64>() / gaps.len() as f64),
+            max_gap_secs: gaps.iter().copied().reduce(f64::max),
+            flapping_score: score,
+            flapping: score >= self.conf.threshold,
+            window_secs: self.conf.window_secs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conf() -> FlappingConf {
+        FlappingConf { window_secs: 600, threshold: 3 }
+    }
+
+    #[test]
+    fn test_heartbeat_gaps() {
+        let tracker = FlappingTracker::new(conf());
+        let t0 = OffsetDateTime::now_utc();
+        for offset in [0, 30, 60, 150] {
+            tracker.record_heartbeat("a1", t0 + time::Duration::seconds(offset));
+        }
+
+        let stats = tracker.stats("a1", t0 + time::Duration::seconds(150)).unwrap();
+        assert_eq!(stats.heartbeats, 4);
+        assert_eq!(stats.last_gap_secs, Some(90.0));
+        assert_eq!(stats.max_gap_secs, Some(90.0));
+        assert_eq!(stats.avg_gap_secs, Some(50.0));
+        assert_eq!(stats.flapping_score, 0);
+        assert!(tracker.stats("unknown", t0).is_none());
+    }
+
+    #[test]
+    fn test_flapping_sequence_scores_and_alerts_once() {
+        let tracker = FlappingTracker::new(conf());
+        let t0 = OffsetDateTime::now_utc();
+        let at = |secs: i64| t0 + time::Duration::seconds(secs);
+
+        // Décroche et revient toutes les 2 minutes
+        assert!(tracker.record_reconnect("a1", at(0)).is_none());
+        assert!(tracker.record_reconnect("a1", at(120)).is_none());
+        let alert = tracker.record_reconnect("a1", at(240)).expect("threshold reached");
+        assert_eq!(alert.flapping_score, 3);
+        assert_eq!(alert.kind, "flapping");
+        // Pas de nouvelle alerte tant que l'épisode dure
+        assert!(tracker.record_reconnect("a1", at(360)).is_none());
+
+        let stats = tracker.stats("a1", at(360)).unwrap();
+        assert_eq!(stats.flapping_score, 4);
+        assert!(stats.flapping);
+
+        // Fenêtre écoulée : score retombé, alerte réarmée
+        let calm = tracker.stats("a1", at(360 + 601)).unwrap();
+        assert_eq!(calm.flapping_score, 0);
+        assert!(!calm.flapping);
+        assert!(tracker.record_reconnect("a1", at(2000)).is_none());
+        assert!(tracker.record_reconnect("a1", at(2010)).is_none());
+        assert!(tracker.record_reconnect("a1", at(2020)).is_some());
+    }
+
+    #[test]
+    fn test_stable_agent_never_flaps() {
+        let tracker = FlappingTracker::new(conf());
+        let t0 = OffsetDateTime::now_utc();
+        // Une reconnexion par heure : jamais 3 dans la fenêtre de 10 min
+        for hour in 0..5 {
+            assert!(tracker.record_reconnect("a1", t0 + time::Duration::hours(hour)).is_none());
+        }
+        assert_eq!(tracker.stats("a1", t0 + time::Duration::hours(4)).unwrap().flapping_score, 1);
+    }
+}
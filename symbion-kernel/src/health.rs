@@ -7,7 +7,7 @@
  * 
  * FONCTIONNEMENT :
  * - Tracking continu des métriques vitales du kernel
- * - Auto-publication toutes les 30s sur symbion/kernel/health@v1
+ * - Auto-publication toutes les 30s sur symbion/kernel/health@v1 (QoS / retain du contrat kernel.health)
  * - API REST /system/health pour interrogation à la demande
  * - Surveillance état connexion MQTT avec compteur de reconnexions
  * 
@@ -24,6 +24,14 @@
  * - memory_usage_mb : consommation RAM du processus kernel
  * - mqtt_status : état connexion (connected/disconnected/reconnecting)
  * - mqtt_reconnects : nombre de tentatives de reconnexion
+ * - mqtt_reconnect_history : dernières déconnexions (horodatage + raison),
+ *   bornées à MAX_RECONNECT_HISTORY, pour corréler les coupures
+ * - http : résumé des requêtes API (volume, taux 5xx, route la plus lente)
+ * - plugins : santé métier publiée par les plugins (symbion/plugins/health@v1)
+ *
+ * INSPECTION MQTT (GET /mqtt/subscriptions) :
+ * Abonnements actifs de chaque client MQTT du kernel + messages reçus par topic
+ * Activité par contrat réutilisée par GET /plugins/{name}/metrics
  * 
  * PUBLICATION AUTOMATIQUE :
  * Toutes les 30s → topic symbion/kernel/health@v1 via MQTT
@@ -32,13 +40,18 @@
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 use std::sync::Arc;
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 use crate::state::Shared;
 use crate::config::HostsConfig;
-use crate::contracts::ContractRegistry;
-use rumqttc::{AsyncClient, MqttOptions, QoS};
+use crate::contracts::{ContractRegistry, PublishSettings};
+use rumqttc::{AsyncClient, MqttOptions};
 use tokio::task;
 
+/// Nombre maximum de reconnexions MQTT conservées dans l'historique
+const MAX_RECONNECT_HISTORY: usize = 50;
+
 /// Snapshot des métriques de santé du kernel à un instant T
 /// Structure sérialisable exposée via API REST et MQTT
 #[derive(Debug, Serialize, Deserialize)]
@@ -55,6 +68,12 @@ pub struct KernelHealth {
     pub mqtt_status: String,
     /// Compteur total des reconnexions MQTT depuis démarrage
     pub mqtt_reconnects: u32,
+    /// Raison de la dernière déconnexion MQTT
+    #[serde(default)]
+    pub mqtt_last_disconnect_reason: Option<String>,
+    /// Dernières reconnexions MQTT, de la plus ancienne à la plus récente
+    #[serde(default)]
+    pub mqtt_reconnect_history: Vec<MqttReconnectEvent>,
     /// Nombre total de plugins découverts
     pub plugins_total: u32,
     /// Nombre de plugins actuellement actifs (Running)
@@ -65,6 +84,57 @@ pub struct KernelHealth {
     pub mqtt_messages_per_minute: f32,
     /// Total des messages MQTT depuis le démarrage
     pub mqtt_messages_total: u64,
+    /// Résumé des métriques de l'API HTTP (détail sur /metrics)
+    #[serde(default)]
+    pub http: crate::http_metrics::HttpMetricsSummary,
+    /// Santé déclarée par les plugins, par nom de plugin
+    #[serde(default)]
+    pub plugins: std::collections::BTreeMap<String, crate::plugin_health::PluginHealthView>,
+}
+
+/// Déconnexion MQTT suivie d'une tentative de reconnexion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MqttReconnectEvent {
+    /// Horodatage RFC3339 de la déconnexion
+    pub at: String,
+    pub reason: String,
+}
+
+/// Abonnement MQTT actif d'un client du kernel
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionInfo {
+    /// Client MQTT du kernel (listener, webhooks...)
+    pub client: String,
+    /// Filtre de topic souscrit
+    pub filter: String,
+    /// Messages reçus par ce client sur des topics couverts par le filtre
+    pub received: u64,
+}
+
+/// Messages reçus sur un topic concret
+#[derive(Debug, Clone, Serialize)]
+pub struct TopicCount {
+    pub client: String,
+    pub topic: String,
+    pub received: u64,
+    pub last_received: String,
+}
+
+/// Compteurs d'un topic reçu par un client du kernel
+#[derive(Debug)]
+struct TopicActivity {
+    received: u64,
+    last_received: OffsetDateTime,
+    /// Réceptions de la dernière minute (messages/minute)
+    recent: VecDeque<Instant>,
+}
+
+/// Activité cumulée des topics d'un ensemble de contrats
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContractActivity {
+    pub messages_total: u64,
+    pub messages_per_minute: u64,
+    pub last_received: Option<OffsetDateTime>,
 }
 
 /// Tracker persistent des métriques de santé kernel
@@ -77,10 +147,24 @@ pub struct HealthTracker {
     mqtt_reconnects: Arc<AtomicU32>,
     /// État actuel de la connexion MQTT (partagé entre threads)
     mqtt_status: Arc<parking_lot::Mutex<String>>,
+    /// Dernières reconnexions MQTT (bornées à MAX_RECONNECT_HISTORY)
+    reconnect_history: Arc<parking_lot::Mutex<VecDeque<MqttReconnectEvent>>>,
     /// Compteur total des messages MQTT
     mqtt_message_counter: Arc<AtomicU64>,
     /// Historique des timestamps pour calcul messages/minute
     message_timestamps: Arc<parking_lot::Mutex<Vec<Instant>>>,
+    /// Latences et statuts des requêtes HTTP par route
+    http_metrics: crate::http_metrics::HttpMetrics,
+    /// Abonnements MQTT actifs : (client, filtre)
+    subscriptions: Arc<parking_lot::Mutex<Vec<(String, String)>>>,
+    /// État de connexion propre à chaque client MQTT du kernel (listener, ...)
+    client_status: Arc<parking_lot::Mutex<HashMap<String, String>>>,
+    /// Messages reçus par (client, topic)
+    topic_counts: Arc<parking_lot::Mutex<HashMap<(String, String), TopicActivity>>>,
+    /// Demande de reconnexion du listener MQTT (self-heal)
+    mqtt_reconnect: Arc<tokio::sync::Notify>,
+    /// Derniers rapports de santé des plugins
+    plugin_health: crate::plugin_health::PluginHealthRegistry,
 }
 
 impl HealthTracker {
@@ -89,24 +173,54 @@ impl HealthTracker {
             start_time: Instant::now(),
             mqtt_reconnects: Arc::new(AtomicU32::new(0)),
             mqtt_status: Arc::new(parking_lot::Mutex::new("connecting".to_string())),
+            reconnect_history: Arc::new(parking_lot::Mutex::new(VecDeque::new())),
             mqtt_message_counter: Arc::new(AtomicU64::new(0)),
             message_timestamps: Arc::new(parking_lot::Mutex::new(Vec::new())),
+            http_metrics: crate::http_metrics::HttpMetrics::new(),
+            subscriptions: Arc::new(parking_lot::Mutex::new(Vec::new())),
+            client_status: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+            topic_counts: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+            mqtt_reconnect: Arc::new(tokio::sync::Notify::new()),
+            plugin_health: crate::plugin_health::PluginHealthRegistry::new(),
         }
     }
 
-    #[allow(dead_code)]
     pub fn mark_mqtt_connected(&self) {
         *self.mqtt_status.lock() = "connected".to_string();
     }
 
+    /// Demande au listener MQTT d'abandonner sa session et de se reconnecter
+    pub fn request_mqtt_reconnect(&self) {
+        self.mqtt_reconnect.notify_one();
+    }
+
+    /// Attend une demande de reconnexion (côté listener)
+    pub async fn mqtt_reconnect_requested(&self) {
+        self.mqtt_reconnect.notified().await
+    }
+
     #[allow(dead_code)]
     pub fn mark_mqtt_disconnected(&self) {
         *self.mqtt_status.lock() = "disconnected".to_string();
     }
 
-    pub fn increment_reconnects(&self) {
+    /// Enregistre une déconnexion MQTT et la tentative de reconnexion qui suit
+    pub fn record_reconnect(&self, reason: &str) {
+        self.record_reconnect_at(reason, OffsetDateTime::now_utc());
+    }
+
+    fn record_reconnect_at(&self, reason: &str, at: OffsetDateTime) {
         self.mqtt_reconnects.fetch_add(1, Ordering::Relaxed);
         *self.mqtt_status.lock() = "reconnecting".to_string();
+
+        let mut history = self.reconnect_history.lock();
+        if history.len() >= MAX_RECONNECT_HISTORY {
+            history.pop_front();
+        }
+        history.push_back(MqttReconnectEvent {
+            at: at.format(&Rfc3339).unwrap_or_default(),
+            reason: reason.to_string(),
+        });
     }
 
     pub fn record_mqtt_message(&self) {
@@ -119,6 +233,105 @@ impl HealthTracker {
         timestamps.push(now);
     }
 
+    /// Note un abonnement MQTT réussi d'un client du kernel
+    pub fn record_subscription(&self, client: &str, filter: &str) {
+        let mut subscriptions = self.subscriptions.lock();
+        let entry = (client.to_string(), filter.to_string());
+        if !subscriptions.contains(&entry) {
+            subscriptions.push(entry);
+        }
+    }
+
+    /// Note l'état de connexion d'un client MQTT du kernel (connected/disconnected/reconnecting)
+    pub fn record_client_status(&self, client: &str, status: &str) {
+        self.client_status.lock().insert(client.to_string(), status.to_string());
+    }
+
+    /// État de connexion d'un client du kernel ; "connecting" tant qu'il n'a rien rapporté
+    pub fn client_status(&self, client: &str) -> String {
+        self.client_status.lock().get(client).cloned().unwrap_or_else(|| "connecting".to_string())
+    }
+
+    /// Compte un message reçu sur un topic par un client du kernel
+    pub fn record_topic_message(&self, client: &str, topic: &str) {
+        let now = Instant::now();
+        let mut counts = self.topic_counts.lock();
+        let entry = counts.entry((client.to_string(), topic.to_string()))
+            .or_insert_with(|| TopicActivity {
+                received: 0,
+                last_received: OffsetDateTime::now_utc(),
+                recent: VecDeque::new(),
+            });
+        entry.received += 1;
+        entry.last_received = OffsetDateTime::now_utc();
+        while entry.recent.front().is_some_and(|t| now.duration_since(*t).as_secs() >= 60) {
+            entry.recent.pop_front();
+        }
+        entry.recent.push_back(now);
+    }
+
+    /// Activité des topics appartenant aux contrats donnés ("notes.command@v1"...)
+    /// Un topic vu par plusieurs clients (listener, webhooks) compte une seule fois
+    pub fn contract_activity(&self, contracts: &[String]) -> ContractActivity {
+        let now = Instant::now();
+        let counts = self.topic_counts.lock();
+        let mut per_topic: HashMap<&str, ContractActivity> = HashMap::new();
+        for ((_, topic), activity) in counts.iter() {
+            if !contracts.contains(&crate::contracts::extract_contract_name(topic)) {
+                continue;
+            }
+            let per_minute = activity.recent.iter()
+                .filter(|t| now.duration_since(**t).as_secs() < 60)
+                .count() as u64;
+            let entry = per_topic.entry(topic.as_str()).or_default();
+            entry.messages_total = entry.messages_total.max(activity.received);
+            entry.messages_per_minute = entry.messages_per_minute.max(per_minute);
+            entry.last_received = entry.last_received.max(Some(activity.last_received));
+        }
+
+        per_topic.into_values().fold(ContractActivity::default(), |mut total, topic| {
+            total.messages_total += topic.messages_total;
+            total.messages_per_minute += topic.messages_per_minute;
+            total.last_received = total.last_received.max(topic.last_received);
+            total
+        })
+    }
+
+    /// Abonnements actifs avec leurs compteurs, et détail par topic (trié par volume)
+    pub fn subscriptions(&self) -> (Vec<SubscriptionInfo>, Vec<TopicCount>) {
+        let counts = self.topic_counts.lock();
+        let subscriptions = self.subscriptions.lock()
+            .iter()
+            .map(|(client, filter)| SubscriptionInfo {
+                client: client.clone(),
+                filter: filter.clone(),
+                received: counts.iter()
+                    .filter(|((c, topic), _)| c == client && crate::webhooks::topic_matches(filter, topic))
+                    .map(|(_, activity)| activity.received)
+                    .sum(),
+            })
+            .collect();
+
+        let mut topics: Vec<TopicCount> = counts.iter()
+            .map(|((client, topic), activity)| TopicCount {
+                client: client.clone(),
+                topic: topic.clone(),
+                received: activity.received,
+                last_received: activity.last_received.format(&Rfc3339).unwrap_or_default(),
+            })
+            .collect();
+        topics.sort_by(|a, b| b.received.cmp(&a.received).then_with(|| a.topic.cmp(&b.topic)));
+        (subscriptions, topics)
+    }
+
+    pub fn http_metrics(&self) -> &crate::http_metrics::HttpMetrics {
+        &self.http_metrics
+    }
+
+    pub fn plugin_health(&self) -> &crate::plugin_health::PluginHealthRegistry {
+        &self.plugin_health
+    }
+
     pub fn get_health(&self, contracts: &ContractRegistry, agents: &crate::agents::SharedAgentRegistry, plugins: &Shared<crate::plugins::PluginManager>) -> KernelHealth {
         let uptime = self.start_time.elapsed().as_secs();
         let contracts_count = contracts.list_contracts().len() as u32;
@@ -126,6 +339,7 @@ impl HealthTracker {
         let memory_mb = get_memory_usage_mb();
         let mqtt_status = self.mqtt_status.lock().clone();
         let reconnects = self.mqtt_reconnects.load(Ordering::Relaxed);
+        let reconnect_history: Vec<MqttReconnectEvent> = self.reconnect_history.lock().iter().cloned().collect();
         let total_messages = self.mqtt_message_counter.load(Ordering::Relaxed);
         
         // Calculer messages par minute
@@ -153,11 +367,15 @@ impl HealthTracker {
             memory_usage_mb: memory_mb,
             mqtt_status,
             mqtt_reconnects: reconnects,
+            mqtt_last_disconnect_reason: reconnect_history.last().map(|e| e.reason.clone()),
+            mqtt_reconnect_history: reconnect_history,
             plugins_total,
             plugins_active,
             plugins_failed,
             mqtt_messages_per_minute: messages_per_minute,
             mqtt_messages_total: total_messages,
+            http: self.http_metrics.summary(),
+            plugins: self.plugin_health.snapshot(now),
         }
     }
 
@@ -168,6 +386,7 @@ impl HealthTracker {
         contracts: ContractRegistry,
         agents: crate::agents::SharedAgentRegistry,
         plugins: Shared<crate::plugins::PluginManager>,
+        outbox: Option<crate::outbox::SharedOutbox>,
     ) {
         let health_tracker = self.clone();
         
@@ -192,7 +411,17 @@ impl HealthTracker {
                     _ = interval.tick() => {
                         let health = health_tracker.get_health(&contracts, &agents, &plugins);
                         if let Ok(payload) = serde_json::to_string(&health) {
-                            if let Err(e) = client.publish("symbion/kernel/health@v1", QoS::AtLeastOnce, false, payload).await {
+                            // QoS / retain du contrat kernel.health (défaut : AtLeastOnce, non retenu)
+                            let PublishSettings { qos, retain } = contracts.publish_settings(symbion_topics::kernel_health(), PublishSettings::default());
+                            // Broker coupé : mise en file, périmée au-delà de deux intervalles (un état plus récent suit)
+                            if let Some(outbox) = &outbox {
+                                let ttl = time::Duration::seconds(60);
+                                match outbox.send(&client, symbion_topics::kernel_health(), qos, retain, payload, Some(ttl)) {
+                                    crate::outbox::Delivery::Published => println!("[health] published kernel health (uptime: {}s, agents: {})",
+                                        health.uptime_seconds, health.agents_count),
+                                    crate::outbox::Delivery::Queued => println!("[health] broker unavailable, kernel health queued in outbox"),
+                                }
+                            } else if let Err(e) = client.publish(symbion_topics::kernel_health(), qos, retain, payload).await {
                                 eprintln!("[health] failed to publish: {:?}", e);
                             } else {
                                 println!("[health] published kernel health (uptime: {}s, agents: {})", 
@@ -202,10 +431,13 @@ impl HealthTracker {
                     },
                     event = eventloop.poll() => {
                         match event {
+                            Ok(rumqttc::Event::Incoming(rumqttc::Incoming::ConnAck(_))) => {
+                                health_tracker.mark_mqtt_connected();
+                            }
                             Ok(_) => {}, // Ignore normal MQTT events
                             Err(e) => {
                                 eprintln!("[health] MQTT error: {:?}", e);
-                                health_tracker.increment_reconnects();
+                                health_tracker.record_reconnect(&e.to_string());
                                 tokio::time::sleep(Duration::from_secs(2)).await;
                             }
                         }
@@ -237,4 +469,95 @@ fn get_memory_usage_mb() -> f32 {
     
     // Fallback approximatif
     12.0
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscribed_topic_reports_received_count() {
+        let tracker = HealthTracker::new();
+        tracker.record_subscription("listener", "symbion/agents/heartbeat@v1");
+        tracker.record_subscription("listener", "symbion/agents/heartbeat@v1");
+        tracker.record_subscription("webhooks", "symbion/agents/#");
+        for _ in 0..3 {
+            tracker.record_topic_message("listener", "symbion/agents/heartbeat@v1");
+        }
+        tracker.record_topic_message("webhooks", "symbion/agents/response@v1");
+
+        let (subscriptions, topics) = tracker.subscriptions();
+        assert_eq!(subscriptions.len(), 2);
+        let heartbeat = subscriptions.iter().find(|s| s.filter == "symbion/agents/heartbeat@v1").unwrap();
+        assert_eq!(heartbeat.client, "listener");
+        assert_eq!(heartbeat.received, 3);
+        let wildcard = subscriptions.iter().find(|s| s.client == "webhooks").unwrap();
+        assert_eq!(wildcard.received, 1);
+
+        assert_eq!(topics[0].topic, "symbion/agents/heartbeat@v1");
+        assert_eq!(topics[0].received, 3);
+    }
+
+    #[test]
+    fn test_contract_activity_counts_each_topic_once() {
+        let tracker = HealthTracker::new();
+        for _ in 0..4 {
+            tracker.record_topic_message("listener", "symbion/notes/response@v1");
+            tracker.record_topic_message("webhooks", "symbion/notes/response@v1");
+        }
+        tracker.record_topic_message("webhooks", "symbion/notes/command@v1");
+        tracker.record_topic_message("listener", "symbion/agents/heartbeat@v1");
+
+        let contracts = vec!["notes.command@v1".to_string(), "notes.response@v1".to_string()];
+        let activity = tracker.contract_activity(&contracts);
+        assert_eq!(activity.messages_total, 5);
+        assert_eq!(activity.messages_per_minute, 5);
+        assert!(activity.last_received.is_some());
+
+        assert_eq!(tracker.contract_activity(&["memo.created@v1".to_string()]), ContractActivity::default());
+    }
+
+    #[test]
+    fn test_recorded_reconnects_appear_in_health_snapshot() {
+        let tracker = HealthTracker::new();
+        let contracts = ContractRegistry::new();
+        let agents = Arc::new(crate::agents::AgentRegistry::new(&crate::agents::temp_data_file()));
+        let plugins = crate::state::new_state(crate::plugins::PluginManager::new("./no-plugins"));
+
+        let health = tracker.get_health(&contracts, &agents, &plugins);
+        assert_eq!(health.mqtt_reconnects, 0);
+        assert_eq!(health.mqtt_last_disconnect_reason, None);
+        assert!(health.mqtt_reconnect_history.is_empty());
+
+        let start = OffsetDateTime::from_unix_timestamp(1_756_720_800).unwrap();
+        tracker.record_reconnect_at("connection refused", start);
+        tracker.record_reconnect_at("keep alive timeout", start + time::Duration::seconds(30));
+        tracker.mark_mqtt_connected();
+
+        let health = tracker.get_health(&contracts, &agents, &plugins);
+        assert_eq!(health.mqtt_status, "connected");
+        assert_eq!(health.mqtt_reconnects, 2);
+        assert_eq!(health.mqtt_last_disconnect_reason.as_deref(), Some("keep alive timeout"));
+        assert_eq!(health.mqtt_reconnect_history, vec![
+            MqttReconnectEvent { at: "2025-09-01T10:00:00Z".into(), reason: "connection refused".into() },
+            MqttReconnectEvent { at: "2025-09-01T10:00:30Z".into(), reason: "keep alive timeout".into() },
+        ]);
+        let json = serde_json::to_value(&health).unwrap();
+        assert_eq!(json["mqtt_reconnect_history"][1]["reason"], "keep alive timeout");
+    }
+
+    #[test]
+    fn test_reconnect_history_is_bounded() {
+        let tracker = HealthTracker::new();
+        let start = OffsetDateTime::from_unix_timestamp(1_756_720_800).unwrap();
+        for i in 0..(MAX_RECONNECT_HISTORY as i64 + 10) {
+            tracker.record_reconnect_at(&format!("drop {}", i), start + time::Duration::seconds(i));
+        }
+
+        let history = tracker.reconnect_history.lock();
+        assert_eq!(history.len(), MAX_RECONNECT_HISTORY);
+        assert_eq!(history.front().unwrap().reason, "drop 10");
+        assert_eq!(history.back().unwrap().reason, format!("drop {}", MAX_RECONNECT_HISTORY + 9));
+        assert_eq!(tracker.mqtt_reconnects.load(Ordering::Relaxed), MAX_RECONNECT_HISTORY as u32 + 10);
+    }
+}
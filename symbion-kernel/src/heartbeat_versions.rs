@@ -0,0 +1,136 @@
+/**
+ * HEARTBEAT VERSIONS - Lecture des heartbeats selon la version déclarée par l'agent
+ *
+ * RÔLE :
+ * Un parc mixte mélange des agents qui ne parlent pas la même version du schéma
+ * de heartbeat. Chaque agent déclare la sienne à la registration
+ * (`heartbeat_version`, "v1" si absente) ; le kernel ramène chaque heartbeat au
+ * format courant avant de le désérialiser.
+ *
+ * FONCTIONNEMENT :
+ * - v1 : format de référence (agents.heartbeat@v1), lu tel quel
+ * - v2 : état regroupé dans `state` {status, queue_depth, busy}, métriques dans
+ *   `metrics` au lieu de `system`, horodatage `sent_at_ms` (epoch ms) au lieu de `timestamp`
+ * - Version inconnue : registration refusée (ses heartbeats seraient illisibles)
+ * - Defaults du contrat appliqués après adaptation, sur la forme v1
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Mises à jour progressives : anciens et nouveaux agents coexistent
+ */
+
+use crate::agents::AgentHeartbeatMessage;
+use crate::contracts::ContractRegistry;
+use serde_json::Value;
+use time::OffsetDateTime;
+
+/// Version supposée pour les agents qui n'en déclarent pas
+pub const DEFAULT_HEARTBEAT_VERSION: &str = "v1";
+
+/// Versions que le kernel sait lire
+pub const SUPPORTED_HEARTBEAT_VERSIONS: &[&str] = &["v1", "v2"];
+
+pub fn default_heartbeat_version() -> String {
+    DEFAULT_HEARTBEAT_VERSION.to_string()
+}
+
+pub fn is_supported(version: &str) -> bool {
+    SUPPORTED_HEARTBEAT_VERSIONS.contains(&version)
+}
+
+/// Ramène un heartbeat de la version déclarée au format v1
+pub fn upgrade(version: &str, message: Value) -> Result<Value, String> {
+    match version {
+        "v1" => Ok(message),
+        "v2" => v2_to_v1(message),
+        other => Err(format!("unsupported heartbeat version {}", other)),
+    }
+}
+
+fn v2_to_v1(message: Value) -> Result<Value, String> {
+    let Value::Object(mut fields) = message else {
+        return Err("v2 heartbeat is not an object".to_string());
+    };
+    if let Some(Value::Object(state)) = fields.remove("state") {
+        for (key, value) in state {
+            fields.insert(key, value);
+        }
+    }
+    if let Some(metrics) = fields.remove("metrics") {
+        fields.insert("system".to_string(), metrics);
+    }
+    if let Some(sent_at_ms) = fields.remove("sent_at_ms") {
+        let ms = sent_at_ms.as_i64().ok_or("v2 heartbeat sent_at_ms is not an integer")?;
+        let sent_at = OffsetDateTime::from_unix_timestamp_nanos(ms as i128 * 1_000_000)
+            .map_err(|e| format!("v2 heartbeat sent_at_ms out of range: {}", e))?;
+        let timestamp = sent_at.format(&time::format_description::well_known::Rfc3339).map_err(|e| e.to_string())?;
+        fields.insert("timestamp".to_string(), Value::String(timestamp));
+    }
+    Ok(Value::Object(fields))
+}
+
+/// Désérialise un heartbeat selon la version déclarée par son agent
+pub fn parse_heartbeat(contracts: Option<&ContractRegistry>, topic: &str, version: &str, payload: &[u8]) -> Result<AgentHeartbeatMessage, String> {
+    let message: Value = serde_json::from_slice(payload).map_err(|e| e.to_string())?;
+    let mut message = upgrade(version, message)?;
+    if let Some(contracts) = contracts {
+        contracts.apply_defaults(topic, &mut message);
+    }
+    serde_json::from_value(message).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TOPIC: &str = "symbion/agents/heartbeat@v1";
+
+    #[test]
+    fn test_v1_and_v2_heartbeats_parse_to_the_same_message() {
+        let v1 = json!({
+            "agent_id": "a1b2c3d4e5f6",
+            "status": "busy",
+            "queue_depth": 3,
+            "busy": true,
+            "system": {
+                "uptime_seconds": 100,
+                "cpu": { "percent": 42.5, "core_count": 8 },
+                "memory": { "total_mb": 16000, "used_mb": 4000, "percent_used": 25.0 }
+            },
+            "timestamp": "2026-10-17T12:00:00Z"
+        });
+        let v2 = json!({
+            "agent_id": "a1b2c3d4e5f6",
+            "state": { "status": "busy", "queue_depth": 3, "busy": true },
+            "metrics": {
+                "uptime_seconds": 100,
+                "cpu": { "percent": 42.5, "core_count": 8 },
+                "memory": { "total_mb": 16000, "used_mb": 4000, "percent_used": 25.0 }
+            },
+            "sent_at_ms": 1_792_238_400_000_i64
+        });
+
+        let contracts = ContractRegistry::new();
+        let from_v1 = parse_heartbeat(Some(&contracts), TOPIC, "v1", v1.to_string().as_bytes()).unwrap();
+        let from_v2 = parse_heartbeat(Some(&contracts), TOPIC, "v2", v2.to_string().as_bytes()).unwrap();
+        for msg in [&from_v1, &from_v2] {
+            assert_eq!(msg.agent_id, "a1b2c3d4e5f6");
+            assert_eq!(msg.status, "busy");
+            assert_eq!((msg.queue_depth, msg.busy), (Some(3), Some(true)));
+            assert_eq!(msg.system.cpu.percent, 42.5);
+            assert_eq!(msg.system.memory.total_mb, 16000);
+            assert_eq!(msg.timestamp, "2026-10-17T12:00:00Z");
+        }
+
+        // Un heartbeat v2 lu comme v1 (version mal déclarée) est rejeté
+        assert!(parse_heartbeat(None, TOPIC, "v1", v2.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_unknown_versions_are_rejected() {
+        assert!(is_supported("v1") && is_supported("v2"));
+        assert!(!is_supported("v3"));
+        assert_eq!(upgrade("v3", json!({})).unwrap_err(), "unsupported heartbeat version v3");
+        assert!(upgrade("v2", json!({ "sent_at_ms": "yesterday" })).is_err());
+    }
+}
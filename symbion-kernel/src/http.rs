@@ -6,9 +6,15 @@
  * Interface principale entre frontend/CLI et kernel backend.
  * 
  * FONCTIONNEMENT :
- * - Serveur Axum sur port 8080 avec middleware auth API key
- * - Routes organisées : /health, /system, /hosts, /contracts, /ports
- * - Sérialisation JSON automatique des réponses
+ * - Serveur Axum sur port 8080 (SYMBION_HTTP_PORT) avec middleware auth API key
+ * - Routes organisées : /health, /system, /hosts, /contracts, /ports (POST /ports/{name}/batch pour les imports)
+ * - /plugins/{name}/... : routes annoncées par les plugins, proxifiées via MQTT
+ * - /plugins/{name}/logs/stream : WebSocket poussant les lignes de log du plugin en direct
+ * - /ports/{name}/events : flux SSE des mutations d'un port (created/updated/deleted + id)
+ * - /version : version du kernel, commit git du build, version d'API et contrats supportés
+ * - /deadletter : messages MQTT en échec, rejouables sur leur topic d'origine (dead_letter.rs)
+ * - Middleware de métriques (latence/statuts par route) exposées sur /metrics
+ * - Sérialisation JSON automatique des réponses, MessagePack si Accept: application/msgpack (content_negotiation.rs)
  * - Gestion erreurs HTTP standardisée (404, 401, 500...)
  * 
  * UTILITÉ DANS SYMBION :
@@ -19,24 +25,36 @@
  * 
  * SÉCURITÉ :
  * - Header x-api-key obligatoire sur toutes routes sauf /health
+ * - SYMBION_API_KEY absent : tout est refusé, sauf en mode dev (SYMBION_DEV_MODE=1 explicite)
+ *   où les requêtes venant de loopback passent sans clé ; les requêtes distantes restent refusées
+ * - api_access (kernel.yaml) : liste blanche CIDR vérifiée avant la clé (403 hors liste) ; adresse cliente
+ *   lue dans l'en-tête du reverse proxy uniquement si la connexion vient d'un proxy de confiance
+ * - Chaque refus est logué avec l'adresse source, la méthode et le chemin
  * - Validation côté middleware avant traitement métier
  * - Logs des tentatives d'accès non autorisé
+ * - Routes admin (/config, /mqtt/publish, /deadletter/{id}/replay, /agents/{id}/firewall, transactions avec
+ *   commandes pare-feu) : header x-admin-key == SYMBION_ADMIN_KEY en plus
  */
 
 use axum::{extract::{Query, State}, routing::{get, post}, Json, Router};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use crate::models::{HostState, HostsMap};
 use crate::state::Shared;
-use crate::config::HostsConfig;
+use crate::config::{ApiAccessConf, HostsConfig};
 use crate::notes_bridge::{self, SharedNotesBridge};
-use crate::wol::trigger_wol_udp;
+use crate::wol::{resolve_wol_target, select_relay_agent, trigger_wol_udp};
 use serde::Deserialize;
 use axum::middleware::{self, Next};
-use axum::extract::Request;
-use axum::response::Response;
+use axum::extract::{ConnectInfo, Request};
+use axum::response::{IntoResponse, Response};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use time::{Duration, OffsetDateTime, format_description::well_known::Rfc3339};
 use axum::extract::Path;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use crate::plugin_logs::LogLine;
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use tokio::sync::broadcast;
 
 
 
@@ -44,29 +62,109 @@ use std::collections::HashMap;
 struct HostView {
     host_id: String,
     last_seen: String,       // format RFC3339 pour l’API
-    stale: bool,             // true si > 90s
+    stale: bool,             // true si > stale_after_secs (config, 90s par défaut)
     stale_for_seconds: i64,  // âge en secondes
     cpu: Option<f32>,
     ram: Option<f32>,
     ip: Option<String>,
+    mac: Option<String>,
+    broadcast: Option<String>,
 }
 
-fn to_view(h: &HostState) -> HostView {
-    let now = OffsetDateTime::now_utc();
+fn to_view(h: &HostState, stale_after_secs: u64, now: OffsetDateTime) -> HostView {
     let age = now - h.last_seen;
     let secs = age.whole_seconds().max(0);
     HostView {
         host_id: h.host_id.clone(),
         last_seen: h.last_seen.format(&Rfc3339).unwrap_or_default(),
-        stale: age > Duration::seconds(90),
+        stale: age > Duration::seconds(stale_after_secs as i64),
         stale_for_seconds: secs,
         cpu: h.cpu,
         ram: h.ram,
         ip: h.ip.clone(),
+        mac: h.mac.clone(),
+        broadcast: h.broadcast.clone(),
+    }
+}
+
+/// Réglages d'authentification de l'API, lus dans l'environnement
+#[derive(Debug, Clone, Default)]
+pub struct ApiAuth {
+    /// SYMBION_API_KEY (vide = non définie)
+    pub api_key: String,
+    /// SYMBION_DEV_MODE : jamais activé implicitement
+    pub dev_mode: bool,
+}
+
+impl ApiAuth {
+    pub fn from_env() -> Self {
+        Self {
+            api_key: std::env::var("SYMBION_API_KEY").unwrap_or_default(),
+            dev_mode: std::env::var("SYMBION_DEV_MODE")
+                .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+        }
+    }
+
+    /// Mode dev effectif : demandé explicitement et aucune clé configurée
+    pub fn dev_mode_active(&self) -> bool {
+        self.dev_mode && self.api_key.is_empty()
+    }
+
+    /// Décision d'accès pour une requête (hors /health), `client` = adresse cliente résolue
+    fn authorize(&self, headers: &HeaderMap, client: Option<IpAddr>) -> Result<(), StatusCode> {
+        if self.api_key.is_empty() {
+            // ::ffff:127.0.0.1 (socket dual-stack) compte comme loopback
+            let loopback = client.is_some_and(|ip| ip.to_canonical().is_loopback());
+            if self.dev_mode && loopback {
+                return Ok(());
+            }
+            if !self.dev_mode {
+                eprintln!("SECURITY: SYMBION_API_KEY not set - API access denied");
+            }
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+
+        let ok = headers
+            .get("x-api-key")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v == self.api_key)
+            .unwrap_or(false);
+
+        if ok { Ok(()) } else { Err(StatusCode::UNAUTHORIZED) }
     }
 }
 
-async fn require_api_key(req: Request, next: Next) -> Result<Response, StatusCode> {
+/// Adresse du client : celle de la connexion, ou celle de l'en-tête du proxy quand la connexion
+/// vient d'un proxy de confiance. x-forwarded-for est lu de droite à gauche en sautant les proxies
+/// de confiance : les entrées de gauche sont fournies par le client et falsifiables.
+fn client_ip(access: &ApiAccessConf, peer: Option<SocketAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+    let peer = peer.map(|addr| addr.ip().to_canonical());
+    let (Some(peer_ip), Some(header)) = (peer, access.trusted_proxy_header.as_deref()) else {
+        return peer;
+    };
+    if !access.is_trusted_proxy(peer_ip) {
+        return peer;
+    }
+    let Some(value) = headers.get(header).and_then(|v| v.to_str().ok()) else {
+        return peer;
+    };
+    let mut client = peer;
+    for entry in value.rsplit(',') {
+        let Ok(ip) = entry.trim().parse::<IpAddr>() else { break };
+        client = Some(ip.to_canonical());
+        if !access.is_trusted_proxy(ip) {
+            break;
+        }
+    }
+    client
+}
+
+fn describe_client(client: Option<IpAddr>) -> String {
+    client.map(|ip| ip.to_string()).unwrap_or_else(|| "unknown".to_string())
+}
+
+async fn require_api_key(State(cfg): State<Shared<HostsConfig>>, req: Request, next: Next) -> Result<Response, StatusCode> {
     let path = req.uri().path();
     
     // Health check toujours accessible
@@ -74,22 +172,38 @@ async fn require_api_key(req: Request, next: Next) -> Result<Response, StatusCod
         return Ok(next.run(req).await);
     }
 
-    let expected = std::env::var("SYMBION_API_KEY").unwrap_or_default();
+    // Adresse de connexion : fournie par into_make_service_with_connect_info (main.rs)
+    let peer = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| *addr);
+    let access = cfg.lock().api_access.clone();
+    let client = client_ip(&access, peer, req.headers());
+
+    // Liste blanche avant la clé : une adresse hors liste ne peut même pas tenter de clé
+    if !access.allows(client) {
+        eprintln!("SECURITY: {} {} from {} denied - not in api_access allowlist", req.method(), path, describe_client(client));
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if let Err(status) = ApiAuth::from_env().authorize(req.headers(), client) {
+        eprintln!("SECURITY: unauthorized {} {} from {}", req.method(), path, describe_client(client));
+        return Err(status);
+    }
+    Ok(next.run(req).await)
+}
+
+/// Vérifie la clé admin (x-admin-key == SYMBION_ADMIN_KEY) pour les routes sensibles
+fn require_admin(headers: &HeaderMap) -> Result<(), StatusCode> {
+    let expected = std::env::var("SYMBION_ADMIN_KEY").unwrap_or_default();
     if expected.is_empty() {
-        eprintln!("SECURITY: SYMBION_API_KEY not set - API access denied");
-        return Err(StatusCode::UNAUTHORIZED);
+        eprintln!("SECURITY: SYMBION_ADMIN_KEY not set - admin access denied");
+        return Err(StatusCode::FORBIDDEN);
     }
 
-    let ok = req.headers()
-        .get("x-api-key")
+    let ok = headers
+        .get("x-admin-key")
         .and_then(|v| v.to_str().ok())
         .map(|v| v == expected)
         .unwrap_or(false);
 
-    if !ok {
-        return Err(StatusCode::UNAUTHORIZED);
-    }
-    Ok(next.run(req).await)
+    if ok { Ok(()) } else { Err(StatusCode::FORBIDDEN) }
 }
 
 
@@ -103,46 +217,295 @@ pub struct AppState {
     pub plugins: Shared<crate::plugins::PluginManager>,
     pub notes_bridge: Option<SharedNotesBridge>,
     pub agents: crate::agents::SharedAgentRegistry,
+    /// Client MQTT partagé pour POST /mqtt/publish
+    pub mqtt_publisher: Option<crate::mqtt_publish::SharedMqttPublisher>,
+    /// Routes HTTP annoncées par les plugins
+    pub plugin_routes: crate::plugin_routes::SharedPluginRoutes,
+    /// Messages MQTT en échec, rejouables
+    pub dead_letters: crate::dead_letter::DeadLetterStore,
 }
 
 #[derive(Debug, Deserialize)]
 struct WakeParams { host_id: String }
 
+#[derive(Debug, Deserialize)]
+struct CommandResultParams { wait: Option<u64> }
+
+#[derive(Debug, Deserialize)]
+struct TailParams { path: String, lines: Option<u64>, follow: Option<u64>, timeout_secs: Option<u32> }
+
+/// ?timeout_secs= des routes de commandes agents
+#[derive(Debug, Deserialize)]
+struct CommandTimeoutParams { timeout_secs: Option<u32> }
+
+#[derive(Debug, Deserialize)]
+struct ContractDiffParams { against: String }
+
+/// Durée maximale d'attente acceptée pour le long-poll des résultats
+const MAX_RESULT_WAIT_SECONDS: u64 = 60;
+
+/// Suivi d'un fichier (follow) borné sous le timeout des commandes agents
+const MAX_TAIL_FOLLOW_SECONDS: u64 = 20;
+
+/// Attente de la réponse `tail_file` (sans follow)
+const TAIL_WAIT_SECONDS: u64 = 10;
+
+/// Attente de la réponse `describe` avant de renvoyer le command_id à suivre
+const DESCRIBE_WAIT_SECONDS: u64 = 10;
+
+/// Attente de la réponse `list_listeners` (ss/netstat borné à 10 s côté agent)
+const LISTENERS_WAIT_SECONDS: u64 = 15;
+
+/// Attente des commandes pare-feu (ufw/iptables/netsh bornés à 15 s côté agent)
+const FIREWALL_WAIT_SECONDS: u64 = 20;
+
+/// Attente du rapport `diagnostics` (détection des capacités et sondes disque côté agent)
+const DIAGNOSTICS_WAIT_SECONDS: u64 = 20;
+
+/// Attente de la politique d'extinction sur inactivité appliquée par l'agent
+const POWER_SCHEDULE_WAIT_SECONDS: u64 = 10;
+
+/// Attente du pong : au-delà, l'agent est considéré comme non réactif
+const PING_WAIT_SECONDS: u64 = 5;
+
+/// Nombre maximal d'enregistrements par POST /ports/{name}/batch
+const MAX_PORT_BATCH: usize = 5000;
+
+/// Version de l'API REST (incrémentée à chaque rupture de compatibilité)
+pub const API_VERSION: u32 = 1;
+
+/// Réponse de GET /version
+#[derive(Debug, serde::Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    /// Hash court du commit construit, `unknown` hors d'un dépôt git
+    pub git_commit: &'static str,
+    pub api_version: u32,
+    /// Versions supportées par contrat : "hosts.heartbeat" -> ["v2"]
+    pub contracts: std::collections::BTreeMap<String, Vec<String>>,
+}
+
+/// Versions du build courant et des contrats chargés
+pub fn version_info(contracts: &crate::contracts::ContractRegistry) -> VersionInfo {
+    let mut supported: std::collections::BTreeMap<String, Vec<String>> = std::collections::BTreeMap::new();
+    for name in contracts.list_contracts() {
+        let (contract, version) = name.split_once('@').unwrap_or((name.as_str(), ""));
+        supported.entry(contract.to_string()).or_default().push(version.to_string());
+    }
+    for versions in supported.values_mut() {
+        versions.sort();
+    }
+    VersionInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("SYMBION_GIT_COMMIT"),
+        api_version: API_VERSION,
+        contracts: supported,
+    }
+}
+
 pub fn build_router(app_state: AppState) -> Router {
+    let http_metrics = app_state.health_tracker.http_metrics().clone();
     Router::new()
         .route("/health", get(|| async { "ok" }))
         .route("/system/health", get(get_system_health))
+        .route("/version", get(get_version))
+        .route("/metrics", get(get_http_metrics))
+        .route("/config", get(get_config))
+        .route("/config/reload", post(reload_config_endpoint))
+        .route("/mqtt/publish", post(mqtt_publish_endpoint))
+        .route("/mqtt/subscriptions", get(mqtt_subscriptions_endpoint))
+        .route("/deadletter", get(dead_letters_endpoint))
+        .route("/deadletter/{id}/replay", post(dead_letter_replay_endpoint))
         .route("/hosts", get(get_hosts))
         .route("/hosts/{id}", get(get_host))
         .route("/wake", post(wake))
         .route("/contracts", get(list_contracts))
         .route("/contracts/{name}", get(get_contract))
+        .route("/contracts/{name}/diff", get(diff_contract))
         .route("/ports", get(list_ports))
         .route("/ports/memo", get(handle_memo_list).post(handle_memo_create))
+        .route("/ports/memo/dedup", post(handle_memo_dedup))
+        .route("/ports/memo/stats", get(handle_memo_stats))
         .route("/ports/memo/{id}", axum::routing::delete(handle_memo_delete).put(handle_memo_update))
+        .route("/ports/memo/{id}/backlinks", get(handle_memo_backlinks))
         .route("/ports/{port_name}", get(read_from_port).post(write_to_port))
+        .route("/ports/{port_name}/batch", post(write_batch_to_port))
+        .route("/ports/{port_name}/events", get(port_events_endpoint))
         .route("/ports/{port_name}/{id}", axum::routing::delete(delete_from_port))
         .route("/plugins", get(list_plugins_endpoint))
+        .route("/plugins/routes", get(list_plugin_routes_endpoint))
         .route("/plugins/{name}/start", post(start_plugin_endpoint))
         .route("/plugins/{name}/stop", post(stop_plugin_endpoint))
         .route("/plugins/{name}/restart", post(restart_plugin_endpoint))
+        .route("/plugins/{name}/metrics", get(plugin_metrics_endpoint))
+        .route("/plugins/{name}/logs/stream", get(plugin_logs_stream_endpoint))
+        .route("/plugins/{name}/{*path}", axum::routing::any(plugin_proxy_endpoint))
         .route("/agents", get(list_agents_endpoint))
+        .route("/agents/summary", get(agents_summary_endpoint))
+        .route("/agents/metrics", get(agents_metrics_endpoint))
+        .route("/agents/integrity", get(agents_integrity_endpoint))
+        .route("/agents/announce", post(agents_announce_endpoint))
+        .route("/agents/run-on-least-loaded", post(run_on_least_loaded_endpoint))
         .route("/agents/{id}", get(get_agent_endpoint))
         .route("/agents/{id}/shutdown", post(agent_shutdown_endpoint))
         .route("/agents/{id}/reboot", post(agent_reboot_endpoint))
         .route("/agents/{id}/hibernate", post(agent_hibernate_endpoint))
         .route("/agents/{id}/processes", get(agent_processes_endpoint))
         .route("/agents/{id}/processes/{pid}/kill", post(agent_kill_process_endpoint))
+        .route("/agents/{id}/processes/{pid}/priority", post(agent_process_priority_endpoint))
         .route("/agents/{id}/command", post(agent_command_endpoint))
+        .route("/agents/{id}/command/{command_id}/output", get(agent_command_output_endpoint))
+        .route("/agents/{id}/commands/{command_id}", get(agent_command_status_endpoint))
         .route("/agents/{id}/metrics", get(agent_metrics_endpoint))
-        .with_state(app_state)
-        .layer(middleware::from_fn(require_api_key))
+        .route("/agents/{id}/capabilities", get(agent_capabilities_endpoint))
+        .route("/agents/{id}/listeners", get(agent_listeners_endpoint))
+        .route("/agents/{id}/firewall", get(agent_firewall_list_endpoint).post(agent_firewall_add_endpoint))
+        .route("/agents/{id}/firewall/remove", post(agent_firewall_remove_endpoint))
+        .route("/agents/{id}/ping", post(agent_ping_endpoint))
+        .route("/agents/{id}/diagnostics", get(agent_diagnostics_endpoint))
+        .route("/agents/{id}/transactions", post(agent_transaction_endpoint))
+        .route("/agents/{id}/heartbeat_sections", post(agent_heartbeat_sections_endpoint))
+        .route("/agents/{id}/power_schedule", post(agent_power_schedule_endpoint))
+        .route("/agents/{id}/liveness", get(agent_liveness_endpoint))
+        .route("/agents/{id}/availability", get(agent_availability_endpoint))
+        .route("/agents/{id}/tail", get(agent_tail_endpoint))
+        .route("/commands/{command_id}/result", get(command_result_endpoint))
+        .with_state(app_state.clone())
+        .layer(middleware::from_fn(crate::content_negotiation::negotiate_response))
+        .layer(middleware::from_fn_with_state(app_state.cfg, require_api_key))
+        .layer(middleware::from_fn_with_state(http_metrics, crate::http_metrics::track_http_metrics))
+}
+
+
+// GET /config (configuration effective, admin, secrets masqués)
+async fn get_config(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    require_admin(&headers)?;
+    let cfg = app.cfg.lock().clone();
+    Ok(Json(cfg.redacted()))
+}
+
+// POST /config/reload (admin) - Relit kernel.yaml et diffuse la config partagée aux plugins
+// (et les sections de heartbeat aux agents)
+// Fichier absent, vide ou invalide : erreur, la config en cours est conservée
+async fn reload_config_endpoint(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, Response> {
+    require_admin(&headers).map_err(IntoResponse::into_response)?;
+    let cfg = match crate::config::try_load_config().await {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            use crate::config::ConfigError;
+            eprintln!("[http] config reload refused, keeping current config: {}", e);
+            let status = match e {
+                ConfigError::Empty(_) | ConfigError::Yaml { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+                ConfigError::NotFound(_) | ConfigError::Io { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            return Err((status, Json(serde_json::json!({ "success": false, "error": e.to_string() }))).into_response());
+        }
+    };
+    *app.cfg.lock() = cfg.clone();
+
+    // Rechargement appliqué même sans broker : la diffusion retenue suivra la prochaine fois
+    let config_id = match app.mqtt_publisher.as_ref() {
+        Some(publisher) => match crate::plugin_config::broadcast_plugin_config(publisher.as_ref(), &cfg) {
+            Ok(config_id) => Some(config_id),
+            Err(e) => {
+                eprintln!("[http] failed to broadcast plugin config: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    if let Some(publisher) = app.mqtt_publisher.as_ref() {
+        if let Err(e) = crate::agents::publish_heartbeat_sections(publisher.as_ref(), None, cfg.heartbeat_sections) {
+            eprintln!("[http] failed to broadcast heartbeat sections: {}", e);
+        }
+    }
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "broadcast": config_id.is_some(),
+        "config_id": config_id,
+        "plugin_settings": cfg.plugin_settings.len(),
+    })))
+}
+
+// POST /mqtt/publish (injection d'un message MQTT, admin, topics en liste blanche)
+async fn mqtt_publish_endpoint(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<crate::mqtt_publish::PublishRequest>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    use crate::mqtt_publish::{publish_request, PublishError};
+
+    require_admin(&headers)?;
+    let publisher = app.mqtt_publisher.as_ref().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let allowlist = app.cfg.lock().mqtt_publish_allowlist.clone();
+
+    match publish_request(publisher.as_ref(), &allowlist, &req) {
+        Ok(()) => Ok(Json(serde_json::json!({ "ok": true, "topic": req.topic }))),
+        Err(PublishError::NotAllowed(topic)) => {
+            eprintln!("SECURITY: MQTT publish to non-allowlisted topic {} rejected", topic);
+            Err(StatusCode::FORBIDDEN)
+        }
+        Err(PublishError::InvalidTopic(_)) | Err(PublishError::InvalidQos(_)) => Err(StatusCode::BAD_REQUEST),
+        Err(PublishError::Publish(e)) => {
+            eprintln!("[mqtt] publish via HTTP failed: {}", e);
+            Err(StatusCode::SERVICE_UNAVAILABLE)
+        }
+    }
+}
+
+// GET /deadletter - Messages MQTT que le kernel n'a pas pu traiter
+async fn dead_letters_endpoint(State(app): State<AppState>) -> Json<Vec<crate::dead_letter::DeadLetter>> {
+    Json(app.dead_letters.list())
+}
+
+/// Corps optionnel de POST /deadletter/{id}/replay : payload corrigé
+#[derive(Debug, Deserialize)]
+struct ReplayRequest {
+    payload: Option<serde_json::Value>,
+}
+
+// POST /deadletter/{id}/replay (admin) - Republie un message sur son topic d'origine
+async fn dead_letter_replay_endpoint(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<u64>,
+    body: Option<Json<ReplayRequest>>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    use crate::dead_letter::ReplayError;
+
+    require_admin(&headers)?;
+    let publisher = app.mqtt_publisher.as_ref().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let edited = body.and_then(|Json(req)| req.payload);
+    match app.dead_letters.replay(id, edited.as_ref(), publisher.as_ref()) {
+        Ok(letter) => Ok(Json(serde_json::json!({ "ok": true, "id": letter.id, "topic": letter.topic }))),
+        Err(ReplayError::NotFound(_)) => Err(StatusCode::NOT_FOUND),
+        Err(ReplayError::Publish(e)) => {
+            eprintln!("[mqtt] dead letter {} replay failed: {}", id, e);
+            Err(StatusCode::SERVICE_UNAVAILABLE)
+        }
+    }
 }
 
+// GET /mqtt/subscriptions (abonnements actifs + messages reçus par topic)
+async fn mqtt_subscriptions_endpoint(State(app): State<AppState>) -> Json<serde_json::Value> {
+    let (subscriptions, topics) = app.health_tracker.subscriptions();
+    Json(serde_json::json!({
+        "subscriptions": subscriptions,
+        "topics": topics,
+    }))
+}
 
 // GET /hosts (liste)
 async fn get_hosts(State(app): State<AppState>) -> Json<Vec<HostView>> {
-    let list: Vec<HostView> = app.states.lock().values().map(to_view).collect();
+    let stale_after_secs = app.cfg.lock().stale_after_secs;
+    let now = OffsetDateTime::now_utc();
+    let list: Vec<HostView> = app.states.lock().values().map(|h| to_view(h, stale_after_secs, now)).collect();
     Json(list)
 }
 
@@ -151,9 +514,10 @@ async fn get_host(
     State(app): State<AppState>,
     Path(id): Path<String>,
 ) -> Result<Json<HostView>, StatusCode> {
+    let stale_after_secs = app.cfg.lock().stale_after_secs;
     let map = app.states.lock();
     let Some(h) = map.get(&id) else { return Err(StatusCode::NOT_FOUND); };
-    Ok(Json(to_view(h)))
+    Ok(Json(to_view(h, stale_after_secs, OffsetDateTime::now_utc())))
 }
 
 
@@ -163,22 +527,57 @@ async fn wake(
 ) -> (StatusCode, Json<serde_json::Value>) {
     // D'abord essayer avec les agents (système moderne)
     let agents = app.agents.list_agents().await;
-    for agent in agents.values() {
-        if agent.agent_id == params.host_id {
+    let (direct, mac, target_ip) = match agents.get(&params.host_id) {
+        Some(agent) => {
             // Utiliser l'adresse MAC de l'agent pour WoL
-            let mac_str = format!("{}:{}:{}:{}:{}:{}",
-                &params.host_id[0..2], &params.host_id[2..4], &params.host_id[4..6],
-                &params.host_id[6..8], &params.host_id[8..10], &params.host_id[10..12]
-            );
-            
-            return send_magic_packet(&mac_str).await;
+            let mac_str = agent.network.primary_mac.clone();
+            let target_ip = agent.network.interfaces.iter()
+                .filter_map(|i| i.ip.parse::<std::net::Ipv4Addr>().ok())
+                .find(|ip| !ip.is_loopback());
+            (send_magic_packet(&mac_str).await, Some(mac_str), target_ip)
+        }
+        None => {
+            // Fallback vers ancien système hosts : MAC/broadcast stockés (heartbeat ou config)
+            let (target, known_ip) = {
+                let cfg = app.cfg.lock();
+                let states = app.states.lock();
+                let known_ip = states.get(&params.host_id).and_then(|h| h.ip.clone());
+                (resolve_wol_target(&states, &cfg, &params.host_id), known_ip)
+            };
+            let Some(target) = target else {
+                return (StatusCode::NOT_FOUND, Json(serde_json::json!({ "ok": false, "msg": "unknown host" })));
+            };
+            let (code, msg) = trigger_wol_udp(&target).await;
+            let target_ip = known_ip.iter()
+                .chain(target.broadcast.iter())
+                .filter_map(|ip| ip.parse::<std::net::Ipv4Addr>().ok())
+                .find(|ip| !ip.is_broadcast());
+            (
+                (code, Json(serde_json::json!({ "ok": code == StatusCode::OK, "msg": msg, "mac": target.mac }))),
+                Some(target.mac),
+                target_ip,
+            )
+        }
+    };
+    let (mut code, Json(mut body)) = direct;
+
+    // Relais via un agent du même sous-réseau (le broadcast du kernel ne traverse pas les routeurs)
+    let relay_agent = target_ip.and_then(|ip| select_relay_agent(&agents, ip, Some(&params.host_id)));
+    if let (Some(relay_id), Some(mac)) = (relay_agent, mac) {
+        match app.agents.send_command(&relay_id, "relay_wake", Some(serde_json::json!({ "mac": mac }))).await {
+            Ok(command_id) => {
+                body["relay"] = serde_json::json!({ "agent_id": relay_id, "command_id": command_id });
+                if code != StatusCode::OK {
+                    code = StatusCode::OK;
+                    body["ok"] = serde_json::json!(true);
+                    body["msg"] = serde_json::json!("relayed via agent");
+                }
+            }
+            Err(e) => eprintln!("[wake] relay via agent {} failed: {}", relay_id, e),
         }
     }
-    
-    // Fallback vers ancien système hosts
-    let cfg = app.cfg.lock().clone();
-    let (code, msg) = trigger_wol_udp(&cfg, &params.host_id).await;
-    (code, Json(serde_json::json!({ "ok": code == StatusCode::OK, "msg": msg })))
+
+    (code, Json(body))
 }
 
 /// Envoie un magic packet WoL pour l'adresse MAC donnée
@@ -250,12 +649,48 @@ async fn get_contract(
     }
 }
 
+// GET /contracts/{name}/diff?against=<fichier> (compatibilité contrat courant -> candidat)
+async fn diff_contract(
+    State(app): State<AppState>,
+    Path(name): Path<String>,
+    Query(params): Query<ContractDiffParams>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let current = app.contracts.get_contract(&name)
+        .ok_or((StatusCode::NOT_FOUND, format!("unknown contract {}", name)))?;
+    let candidate = app.contracts.load_candidate(&params.against).await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let diff = crate::contracts::diff_schemas(&current.schema, &candidate.schema);
+    Ok(Json(serde_json::json!({
+        "contract": name,
+        "against": params.against,
+        "candidate_topic": candidate.topic,
+        "breaking": diff.breaking,
+        "changes": diff.changes,
+    })))
+}
+
 // GET /system/health (état infrastructure)
 async fn get_system_health(State(app): State<AppState>) -> Json<crate::health::KernelHealth> {
     let health = app.health_tracker.get_health(&app.contracts, &app.agents, &app.plugins);
     Json(health)
 }
 
+// GET /version (build et versions supportées)
+async fn get_version(State(app): State<AppState>) -> Json<VersionInfo> {
+    Json(version_info(&app.contracts))
+}
+
+// GET /metrics (latence, volume et codes HTTP par route)
+async fn get_http_metrics(State(app): State<AppState>) -> Json<serde_json::Value> {
+    let metrics = app.health_tracker.http_metrics();
+    Json(serde_json::json!({
+        "buckets_ms": crate::http_metrics::LATENCY_BUCKETS_MS,
+        "summary": metrics.summary(),
+        "routes": metrics.snapshot(),
+    }))
+}
+
 // GET /ports (liste des ports disponibles)
 async fn list_ports(State(app): State<AppState>) -> Json<Vec<crate::ports::PortInfo>> {
     let ports = app.ports.lock();
@@ -336,6 +771,63 @@ async fn write_to_port(
     }
 }
 
+// POST /ports/{port_name}/batch (écriture groupée : tableau de données, IDs dans le même ordre)
+async fn write_batch_to_port(
+    State(app): State<AppState>,
+    Path(port_name): Path<String>,
+    Json(batch): Json<Vec<serde_json::Value>>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    if batch.len() > MAX_PORT_BATCH {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    let ports = app.ports.lock();
+    let port = ports.get(&port_name)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let now = time::OffsetDateTime::now_utc();
+    let records: Vec<_> = batch.into_iter()
+        .map(|data| crate::ports::PortData { id: String::new(), timestamp: now, data, metadata: HashMap::new() })
+        .collect();
+
+    match port.write_batch(&records) {
+        Ok(ids) => Ok(Json(serde_json::json!({"ids": ids, "count": ids.len(), "status": "created"}))),
+        Err(e) => {
+            eprintln!("[http] batch write to port {} failed: {}", port_name, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// GET /ports/{port_name}/events (flux SSE des mutations du port)
+async fn port_events_endpoint(
+    State(app): State<AppState>,
+    Path(port_name): Path<String>,
+) -> Response {
+    port_events_stream(&app.ports, &port_name)
+}
+
+/// Abonne le client aux mutations du port : un événement SSE par changement, nommé d'après
+/// sa nature (created/updated/deleted). Un client trop lent reçoit `lagged` et doit relire le port.
+/// 404 si le port est inconnu, 501 s'il ne notifie pas ses mutations
+fn port_events_stream(ports: &Shared<crate::ports::PortRegistry>, port_name: &str) -> Response {
+    let receiver = match ports.lock().get(port_name).map(|port| port.subscribe()) {
+        Some(Ok(receiver)) => receiver,
+        Some(Err(_)) => return StatusCode::NOT_IMPLEMENTED.into_response(),
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
+    let events = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        let event = match receiver.recv().await {
+            Ok(change) => Event::default().event(change.kind.as_str()).json_data(&change).ok()?,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => Event::default()
+                .event("lagged")
+                .data(serde_json::json!({ "skipped": skipped }).to_string()),
+            Err(broadcast::error::RecvError::Closed) => return None,
+        };
+        Some((Ok::<_, std::convert::Infallible>(event), receiver))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
+
 // DELETE /ports/{port_name}/{id} (suppression depuis un port)
 async fn delete_from_port(
     State(app): State<AppState>,
@@ -358,6 +850,59 @@ async fn list_plugins_endpoint(State(app): State<AppState>) -> Json<Vec<crate::p
     Json(plugin_info)
 }
 
+// GET /plugins/{name}/metrics (uptime, redémarrages, activité MQTT de ses contrats)
+async fn plugin_metrics_endpoint(
+    State(app): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<crate::plugins::PluginMetrics>, StatusCode> {
+    let plugins = app.plugins.lock();
+    plugins.plugin_metrics(&name, &app.health_tracker)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+// GET /plugins/{name}/logs/stream (WebSocket : lignes stdout/stderr du plugin en direct)
+async fn plugin_logs_stream_endpoint(
+    State(app): State<AppState>,
+    Path(name): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    plugin_logs_socket(&app.plugins, &name, ws)
+}
+
+/// Abonne la connexion aux logs du plugin avant l'upgrade (404 si inconnu ou capture désactivée)
+fn plugin_logs_socket(plugins: &Shared<crate::plugins::PluginManager>, name: &str, ws: WebSocketUpgrade) -> Response {
+    let Some(receiver) = plugins.lock().subscribe_logs(name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    ws.on_upgrade(move |socket| stream_plugin_logs(socket, receiver))
+}
+
+/// Pousse chaque ligne en JSON {stream, line} jusqu'à la fermeture par le client
+async fn stream_plugin_logs(mut socket: WebSocket, mut receiver: broadcast::Receiver<LogLine>) {
+    loop {
+        tokio::select! {
+            line = receiver.recv() => match line {
+                Ok(line) => {
+                    let Ok(text) = serde_json::to_string(&line) else { continue };
+                    if socket.send(Message::Text(text.into())).await.is_err() {
+                        break;
+                    }
+                }
+                // Client trop lent : les lignes perdues sont ignorées, on continue
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    eprintln!("[http] plugin log stream lagged, {} lines skipped", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
 // POST /plugins/{name}/start (démarre un plugin)
 async fn start_plugin_endpoint(
     State(app): State<AppState>,
@@ -446,6 +991,33 @@ async fn restart_plugin_endpoint(
     }
 }
 
+// GET /plugins/routes (routes HTTP annoncées par les plugins)
+async fn list_plugin_routes_endpoint(State(app): State<AppState>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "plugins": app.plugin_routes.list() }))
+}
+
+// ANY /plugins/{name}/{*path} (route annoncée par le plugin, proxifiée via MQTT)
+async fn plugin_proxy_endpoint(
+    State(app): State<AppState>,
+    Path((name, path)): Path<(String, String)>,
+    method: axum::http::Method,
+    Query(query): Query<std::collections::HashMap<String, String>>,
+    body: axum::body::Bytes,
+) -> Result<(StatusCode, Json<serde_json::Value>), StatusCode> {
+    let publisher = app.mqtt_publisher.as_ref().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    let body = if body.is_empty() {
+        None
+    } else {
+        Some(serde_json::from_slice(&body).map_err(|_| StatusCode::BAD_REQUEST)?)
+    };
+
+    let response = app.plugin_routes
+        .proxy(publisher.as_ref(), &name, method.as_str(), &path, query, body)
+        .await?;
+    let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::BAD_GATEWAY);
+    Ok((status, Json(response.body)))
+}
+
 // ============ MEMO HANDLERS (Plugin Bridge Only) ============
 
 async fn handle_memo_list(
@@ -486,6 +1058,9 @@ async fn handle_memo_create(
             status: note_data.get("status")
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string()),
+            links: note_data.get("links")
+                .and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect()),
         };
         
         return notes_bridge::create_note_endpoint(
@@ -498,6 +1073,38 @@ async fn handle_memo_create(
     Err(StatusCode::SERVICE_UNAVAILABLE)
 }
 
+async fn handle_memo_stats(
+    State(app): State<AppState>,
+    Query(params): Query<notes_bridge::StatsParams>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    // Notes uniquement via plugin - pas de fallback
+    if let Some(ref bridge) = app.notes_bridge {
+        return notes_bridge::stats_notes_endpoint(
+            axum::extract::State(bridge.clone()),
+            axum::extract::Query(params)
+        ).await;
+    }
+    
+    // Plugin notes non disponible
+    Err(StatusCode::SERVICE_UNAVAILABLE)
+}
+
+async fn handle_memo_dedup(
+    State(app): State<AppState>,
+    Query(params): Query<notes_bridge::DedupParams>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    // Notes uniquement via plugin - pas de fallback
+    if let Some(ref bridge) = app.notes_bridge {
+        return notes_bridge::dedup_notes_endpoint(
+            axum::extract::State(bridge.clone()),
+            axum::extract::Query(params)
+        ).await;
+    }
+    
+    // Plugin notes non disponible
+    Err(StatusCode::SERVICE_UNAVAILABLE)
+}
+
 async fn handle_memo_delete(
     State(app): State<AppState>,
     Path(id): Path<String>,
@@ -514,6 +1121,22 @@ async fn handle_memo_delete(
     Err(StatusCode::SERVICE_UNAVAILABLE)
 }
 
+async fn handle_memo_backlinks(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    // Notes uniquement via plugin - pas de fallback
+    if let Some(ref bridge) = app.notes_bridge {
+        return notes_bridge::backlinks_note_endpoint(
+            axum::extract::State(bridge.clone()),
+            axum::extract::Path(id)
+        ).await;
+    }
+    
+    // Plugin notes non disponible
+    Err(StatusCode::SERVICE_UNAVAILABLE)
+}
+
 async fn handle_memo_update(
     State(app): State<AppState>,
     Path(id): Path<String>,
@@ -536,6 +1159,9 @@ async fn handle_memo_update(
             status: note_data.get("status")
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string()),
+            links: note_data.get("links")
+                .and_then(|v| v.as_array())
+                .map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect()),
         };
         
         return notes_bridge::update_note_endpoint(
@@ -556,6 +1182,10 @@ struct AgentView {
     agent_id: String,
     hostname: String,
     os: String,
+    distro_name: Option<String>,
+    distro_version: Option<String>,
+    kernel_version: Option<String>,
+    windows_build: Option<u32>,
     architecture: String,
     capabilities: Vec<String>,
     primary_mac: String,
@@ -568,22 +1198,61 @@ struct AgentView {
     memory_percent: Option<f32>,
 }
 
+/// Filtres optionnels de GET /agents (ex: ?distro=Ubuntu&distro_version=22.04)
+#[derive(Debug, Default, Deserialize)]
+struct AgentListParams {
+    os: Option<String>,
+    status: Option<String>,
+    distro: Option<String>,
+    distro_version: Option<String>,
+    windows_build: Option<u32>,
+}
+
+impl AgentListParams {
+    fn matches(&self, agent: &crate::agents::Agent) -> bool {
+        let details = agent.os_details.as_ref();
+        let eq = |filter: &Option<String>, value: Option<&String>| match filter {
+            Some(f) => value.is_some_and(|v| v.eq_ignore_ascii_case(f)),
+            None => true,
+        };
+
+        eq(&self.os, Some(&agent.os))
+            && eq(&self.status, Some(&agent.status.status))
+            && eq(&self.distro, details.and_then(|d| d.distro_name.as_ref()))
+            && eq(&self.distro_version, details.and_then(|d| d.distro_version.as_ref()))
+            && self.windows_build.is_none_or(|b| details.and_then(|d| d.windows_build) == Some(b))
+    }
+}
+
 #[derive(Deserialize)]
 struct AgentCommandRequest {
     command: String,
     parameters: Option<serde_json::Value>,
+    /// Sortie publiée au fil de l'eau (GET /agents/{id}/command/{command_id}/output)
+    #[serde(default)]
+    stream: bool,
+    /// Entrée standard du processus (texte, ou base64 si stdin_encoding = "base64")
+    #[serde(default)]
+    stdin: Option<String>,
+    #[serde(default)]
+    stdin_encoding: Option<String>,
+    /// Priorité imposée à l'ordonnanceur de l'agent (low, normal, high) ; absente : défaut de run_command
+    #[serde(default)]
+    priority: Option<String>,
 }
 
 fn agent_to_view(agent: &crate::agents::Agent) -> AgentView {
-    let primary_ip = agent.network.interfaces
-        .first()
-        .map(|i| i.ip.clone())
-        .unwrap_or_else(|| "unknown".to_string());
+    let primary_ip = agent.network.primary_ip().unwrap_or("unknown").to_string();
+    let details = agent.os_details.as_ref();
 
     AgentView {
         agent_id: agent.agent_id.clone(),
         hostname: agent.hostname.clone(),
         os: agent.os.clone(),
+        distro_name: details.and_then(|d| d.distro_name.clone()),
+        distro_version: details.and_then(|d| d.distro_version.clone()),
+        kernel_version: details.and_then(|d| d.kernel_version.clone()),
+        windows_build: details.and_then(|d| d.windows_build),
         architecture: agent.architecture.clone(),
         capabilities: agent.capabilities.clone(),
         primary_mac: agent.network.primary_mac.clone(),
@@ -598,12 +1267,49 @@ fn agent_to_view(agent: &crate::agents::Agent) -> AgentView {
 }
 
 // GET /agents - Liste des agents
-async fn list_agents_endpoint(State(app): State<AppState>) -> Json<Vec<AgentView>> {
+async fn list_agents_endpoint(
+    State(app): State<AppState>,
+    Query(params): Query<AgentListParams>,
+) -> Json<Vec<AgentView>> {
     let agents = app.agents.list_agents().await;
-    let list: Vec<AgentView> = agents.values().map(agent_to_view).collect();
+    let list: Vec<AgentView> = agents.values()
+        .filter(|a| params.matches(a))
+        .map(agent_to_view)
+        .collect();
     Json(list)
 }
 
+// GET /agents/metrics - Dernières métriques de tous les agents (mêmes filtres que /agents)
+async fn agents_metrics_endpoint(
+    State(app): State<AppState>,
+    Query(params): Query<AgentListParams>,
+) -> Json<serde_json::Value> {
+    let metrics = app.agents.metrics_snapshot(|a| params.matches(a)).await;
+    Json(serde_json::json!({ "count": metrics.len(), "agents": metrics }))
+}
+
+// GET /agents/summary - Agrégats par OS, statut et capacité
+async fn agents_summary_endpoint(State(app): State<AppState>) -> Json<crate::agents::AgentsSummary> {
+    Json(app.agents.summary().await)
+}
+
+// GET /agents/integrity - Dernier contrôle d'intégrité du registre (?run=true : nouvelle passe)
+#[derive(Deserialize)]
+struct IntegrityParams {
+    #[serde(default)]
+    run: bool,
+}
+
+async fn agents_integrity_endpoint(
+    State(app): State<AppState>,
+    Query(params): Query<IntegrityParams>,
+) -> Json<Option<crate::registry_integrity::IntegrityReport>> {
+    if params.run {
+        return Json(Some(app.agents.check_integrity().await));
+    }
+    Json(app.agents.integrity_report())
+}
+
 // GET /agents/{id} - Détail d'un agent
 async fn get_agent_endpoint(
     State(app): State<AppState>,
@@ -615,39 +1321,142 @@ async fn get_agent_endpoint(
     }
 }
 
-// POST /agents/{id}/shutdown - Extinction système
-async fn agent_shutdown_endpoint(
-    State(app): State<AppState>,
-    Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    match app.agents.send_command(&id, "shutdown", None).await {
-        Ok(command_id) => Ok(Json(serde_json::json!({
-            "success": true,
-            "command_id": command_id,
-            "message": "Shutdown command sent"
-        }))),
-        Err(e) => {
-            eprintln!("[http] failed to send shutdown command to agent {}: {}", id, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+/// Erreur JSON des routes agents : `code` stable pour les clients, `error` lisible
+fn agent_api_error(status: StatusCode, code: &str, error: String) -> Response {
+    (status, Json(serde_json::json!({ "success": false, "code": code, "error": error }))).into_response()
+}
+
+/// Agent inconnu du registre (404, code agent_not_found)
+fn agent_not_found(agent_id: &str) -> Response {
+    agent_api_error(StatusCode::NOT_FOUND, "agent_not_found", format!("agent {} not found", agent_id))
+}
+
+/// Agent connu mais hors ligne : 409 agent_offline avec sa dernière activité
+fn agent_offline(agent: &crate::agents::Agent) -> Response {
+    (StatusCode::CONFLICT, Json(serde_json::json!({
+        "success": false,
+        "code": "agent_offline",
+        "status": "offline",
+        "error": format!("agent {} is offline", agent.agent_id),
+        "last_seen": agent.last_seen.format(&Rfc3339).ok(),
+    }))).into_response()
+}
+
+/// Refus d'une commande qui exige l'agent joignable : inconnu (404) ou hors ligne (409)
+async fn unreachable_agent(app: &AppState, agent_id: &str) -> Option<Response> {
+    match app.agents.get_agent(agent_id).await {
+        None => Some(agent_not_found(agent_id)),
+        Some(agent) if agent.status.status == "offline" => Some(agent_offline(&agent)),
+        Some(_) => None,
     }
 }
 
-// POST /agents/{id}/reboot - Redémarrage système  
-async fn agent_reboot_endpoint(
-    State(app): State<AppState>,
-    Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    match app.agents.send_command(&id, "reboot", None).await {
+/// Refus d'un ?timeout_secs= hors de 1 à MAX_COMMAND_TIMEOUT_SECONDS (400 invalid_timeout)
+fn invalid_timeout(requested: Option<u32>) -> Option<Response> {
+    use crate::agents::MAX_COMMAND_TIMEOUT_SECONDS;
+    requested.filter(|secs| *secs == 0 || *secs > MAX_COMMAND_TIMEOUT_SECONDS).map(|_| agent_api_error(
+        StatusCode::BAD_REQUEST,
+        "invalid_timeout",
+        format!("timeout_secs must be between 1 and {}", MAX_COMMAND_TIMEOUT_SECONDS),
+    ))
+}
+
+/// Attente synchrone de la réponse : délai demandé (borné au long-poll maximal) ou défaut de la route
+fn response_wait(timeout: Option<u32>, default_secs: u64) -> std::time::Duration {
+    std::time::Duration::from_secs(timeout.map_or(default_secs, |secs| u64::from(secs).min(MAX_RESULT_WAIT_SECONDS)))
+}
+
+/// Échec d'envoi d'une commande :
+/// - 429 rate_limited + limite et Retry-After si l'agent est limité en débit
+/// - 503 mqtt_not_configured / mqtt_publish_failed quand le broker est inaccessible
+/// - 500 command_failed sinon
+fn command_send_error(agent_id: &str, command_type: &str, e: anyhow::Error) -> Response {
+    eprintln!("[http] failed to send {} to agent {}: {}", command_type, agent_id, e);
+    let e = match e.downcast::<crate::rate_limit::RateLimited>() {
+        Ok(limited) => {
+            let retry_after_secs = limited.retry_after_ms.div_ceil(1000);
+            let body = serde_json::json!({
+                "success": false,
+                "code": "rate_limited",
+                "error": limited.to_string(),
+                "limit": { "burst": limited.burst, "per_second": limited.per_second },
+                "retry_after_ms": limited.retry_after_ms,
+            });
+            return (StatusCode::TOO_MANY_REQUESTS, [(axum::http::header::RETRY_AFTER, retry_after_secs.to_string())], Json(body)).into_response();
+        }
+        Err(e) => e,
+    };
+    match e.downcast_ref::<crate::agents::CommandSendError>() {
+        Some(err @ crate::agents::CommandSendError::MqttNotConfigured) => {
+            agent_api_error(StatusCode::SERVICE_UNAVAILABLE, "mqtt_not_configured", err.to_string())
+        }
+        Some(err @ crate::agents::CommandSendError::Publish(_)) => {
+            agent_api_error(StatusCode::SERVICE_UNAVAILABLE, "mqtt_publish_failed", err.to_string())
+        }
+        None => agent_api_error(StatusCode::INTERNAL_SERVER_ERROR, "command_failed", e.to_string()),
+    }
+}
+
+/// Avertissement optionnel des utilisateurs connectés avant une transition d'alimentation
+#[derive(Deserialize)]
+struct PowerNoticeRequest {
+    message: Option<String>,
+    warn_seconds: Option<u32>,
+}
+
+fn power_notice_params(notice: Option<Json<PowerNoticeRequest>>) -> Option<serde_json::Value> {
+    let Json(notice) = notice?;
+    if notice.message.is_none() && notice.warn_seconds.is_none() {
+        return None;
+    }
+    Some(serde_json::json!({ "message": notice.message, "warn_seconds": notice.warn_seconds }))
+}
+
+// POST /agents/{id}/shutdown - Extinction système
+async fn agent_shutdown_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<CommandTimeoutParams>,
+    notice: Option<Json<PowerNoticeRequest>>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+    match app.agents.send_command_with_timeout(&id, "shutdown", power_notice_params(notice), timeout).await {
+        Ok(command_id) => Ok(Json(serde_json::json!({
+            "success": true,
+            "command_id": command_id,
+            "message": "Shutdown command sent"
+        })).into_response()),
+        Err(e) => Ok(command_send_error(&id, "shutdown", e)),
+    }
+}
+
+// POST /agents/{id}/reboot - Redémarrage système  
+async fn agent_reboot_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<CommandTimeoutParams>,
+    notice: Option<Json<PowerNoticeRequest>>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+    match app.agents.send_command_with_timeout(&id, "reboot", power_notice_params(notice), timeout).await {
         Ok(command_id) => Ok(Json(serde_json::json!({
             "success": true,
             "command_id": command_id,
             "message": "Reboot command sent"
-        }))),
-        Err(e) => {
-            eprintln!("[http] failed to send reboot command to agent {}: {}", id, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+        })).into_response()),
+        Err(e) => Ok(command_send_error(&id, "reboot", e)),
     }
 }
 
@@ -655,17 +1464,23 @@ async fn agent_reboot_endpoint(
 async fn agent_hibernate_endpoint(
     State(app): State<AppState>,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    match app.agents.send_command(&id, "hibernate", None).await {
+    Query(query): Query<CommandTimeoutParams>,
+    notice: Option<Json<PowerNoticeRequest>>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+    match app.agents.send_command_with_timeout(&id, "hibernate", power_notice_params(notice), timeout).await {
         Ok(command_id) => Ok(Json(serde_json::json!({
             "success": true,
             "command_id": command_id,
             "message": "Hibernate command sent"
-        }))),
-        Err(e) => {
-            eprintln!("[http] failed to send hibernate command to agent {}: {}", id, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+        })).into_response()),
+        Err(e) => Ok(command_send_error(&id, "hibernate", e)),
     }
 }
 
@@ -673,27 +1488,31 @@ async fn agent_hibernate_endpoint(
 async fn agent_processes_endpoint(
     State(app): State<AppState>,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+    Query(query): Query<CommandTimeoutParams>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
     match app.agents.get_agent(&id).await {
         Some(agent) => {
             if let Some(processes) = &agent.status.processes {
-                Ok(Json(serde_json::to_value(processes).unwrap()))
+                Ok(Json(serde_json::to_value(processes).unwrap()).into_response())
+            } else if agent.status.status == "offline" {
+                Ok(agent_offline(&agent))
             } else {
                 // Demander les processus via MQTT
-                match app.agents.send_command(&id, "list_processes", None).await {
+                match app.agents.send_command_with_timeout(&id, "list_processes", None, timeout).await {
                     Ok(command_id) => Ok(Json(serde_json::json!({
                         "success": true,
                         "command_id": command_id,
                         "message": "Process list requested, check agent status for results"
-                    }))),
-                    Err(e) => {
-                        eprintln!("[http] failed to request processes from agent {}: {}", id, e);
-                        Err(StatusCode::INTERNAL_SERVER_ERROR)
-                    }
+                    })).into_response()),
+                    Err(e) => Ok(command_send_error(&id, "list_processes", e)),
                 }
             }
         }
-        None => Err(StatusCode::NOT_FOUND),
+        None => Ok(agent_not_found(&id)),
     }
 }
 
@@ -701,43 +1520,234 @@ async fn agent_processes_endpoint(
 async fn agent_kill_process_endpoint(
     State(app): State<AppState>,
     Path((id, pid)): Path<(String, u32)>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+    Query(query): Query<CommandTimeoutParams>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
     let params = serde_json::json!({ "pid": pid });
     
-    match app.agents.send_command(&id, "kill_process", Some(params)).await {
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+    match app.agents.send_command_with_timeout(&id, "kill_process", Some(params), timeout).await {
         Ok(command_id) => Ok(Json(serde_json::json!({
             "success": true,
             "command_id": command_id,
             "message": format!("Kill process {} command sent", pid)
-        }))),
-        Err(e) => {
-            eprintln!("[http] failed to send kill process command to agent {}: {}", id, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        })).into_response()),
+        Err(e) => Ok(command_send_error(&id, "kill_process", e)),
+    }
+}
+
+/// Corps de POST /agents/{id}/processes/{pid}/priority : au moins un des deux réglages
+#[derive(Debug, Deserialize)]
+struct ProcessPriorityRequest {
+    /// Priorité d'ordonnancement, -20 (haute) à 19 (basse)
+    nice: Option<i32>,
+    /// CPUs autorisés pour le processus
+    cpus: Option<Vec<u32>>,
+}
+
+impl ProcessPriorityRequest {
+    /// Commandes agent à envoyer (renice, set_affinity), ou l'erreur de validation
+    fn commands(&self, pid: u32) -> Result<Vec<(&'static str, serde_json::Value)>, String> {
+        if pid == 0 {
+            return Err("pid must be positive".to_string());
+        }
+        let mut commands = Vec::new();
+        if let Some(nice) = self.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(format!("nice must be between -20 and 19, got {}", nice));
+            }
+            commands.push(("renice", serde_json::json!({ "pid": pid, "nice": nice })));
+        }
+        if let Some(cpus) = &self.cpus {
+            if cpus.is_empty() {
+                return Err("cpus must list at least one CPU".to_string());
+            }
+            commands.push(("set_affinity", serde_json::json!({ "pid": pid, "cpus": cpus })));
+        }
+        if commands.is_empty() {
+            return Err("nice or cpus is required".to_string());
         }
+        Ok(commands)
     }
 }
 
+// POST /agents/{id}/processes/{pid}/priority - Priorité (renice) et/ou affinité CPU d'un processus
+async fn agent_process_priority_endpoint(
+    State(app): State<AppState>,
+    Path((id, pid)): Path<(String, u32)>,
+    Query(query): Query<CommandTimeoutParams>,
+    Json(req): Json<ProcessPriorityRequest>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
+    let commands = match req.commands(pid) {
+        Ok(commands) => commands,
+        Err(e) => return Ok(agent_api_error(StatusCode::BAD_REQUEST, "invalid_priority", e)),
+    };
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+
+    let mut command_ids = serde_json::Map::new();
+    for (command_type, params) in commands {
+        match app.agents.send_command_with_timeout(&id, command_type, Some(params), timeout).await {
+            Ok(command_id) => {
+                command_ids.insert(command_type.to_string(), command_id.into());
+            }
+            Err(e) => return Ok(command_send_error(&id, command_type, e)),
+        }
+    }
+    Ok(Json(serde_json::json!({
+        "success": true,
+        "command_ids": command_ids,
+        "message": format!("Priority change for process {} sent", pid)
+    })).into_response())
+}
+
 // POST /agents/{id}/command - Exécuter une commande shell
 async fn agent_command_endpoint(
     State(app): State<AppState>,
     Path(id): Path<String>,
+    Query(query): Query<CommandTimeoutParams>,
     Json(req): Json<AgentCommandRequest>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
-    let params = serde_json::json!({ 
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
+    if let Some(priority) = req.priority.as_deref().filter(|p| !crate::agents::COMMAND_PRIORITIES.contains(p)) {
+        return Ok(agent_api_error(StatusCode::BAD_REQUEST, "invalid_priority", format!("Unknown priority '{}' (low, normal, high)", priority)));
+    }
+    let mut params = serde_json::json!({ 
         "command": req.command,
         "parameters": req.parameters
     });
+    if req.stream {
+        params["stream"] = serde_json::Value::Bool(true);
+    }
+    if let Some(stdin) = req.stdin {
+        params["stdin"] = serde_json::Value::String(stdin);
+        params["stdin_encoding"] = serde_json::Value::String(req.stdin_encoding.unwrap_or_else(|| "text".to_string()));
+    }
     
-    match app.agents.send_command(&id, "run_command", Some(params)).await {
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+    match app.agents.send_command_with_priority(&id, "run_command", Some(params), timeout, req.priority.as_deref()).await {
+        Ok(command_id) if req.stream => Ok(Json(serde_json::json!({
+            "success": true,
+            "output_url": format!("/agents/{}/command/{}/output", id, command_id),
+            "command_id": command_id,
+            "message": "Command execution requested, output streamed"
+        })).into_response()),
         Ok(command_id) => Ok(Json(serde_json::json!({
             "success": true,
             "command_id": command_id,
             "message": "Command execution requested"
-        }))),
-        Err(e) => {
-            eprintln!("[http] failed to send command to agent {}: {}", id, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+        })).into_response()),
+        Err(e) => Ok(command_send_error(&id, "run_command", e)),
+    }
+}
+
+// GET /agents/{id}/commands/{command_id} - Réponse conservée d'une commande (202 tant qu'elle est en vol)
+async fn agent_command_status_endpoint(
+    State(app): State<AppState>,
+    Path((id, command_id)): Path<(String, String)>,
+) -> Response {
+    match app.agents.commands().get(&command_id).filter(|record| record.agent_id == id) {
+        Some(record) if record.is_pending() => (StatusCode::ACCEPTED, Json(record)).into_response(),
+        Some(record) => Json(record).into_response(),
+        None => agent_api_error(StatusCode::NOT_FOUND, "unknown_command", format!("no command {} for agent {}", command_id, id)),
+    }
+}
+
+#[derive(Deserialize)]
+struct CommandOutputParams {
+    /// Premier seq voulu (next_seq de l'appel précédent)
+    #[serde(default)]
+    since: u64,
+}
+
+// GET /agents/{id}/command/{command_id}/output - Sortie accumulée d'un run_command en flux ;
+// relever avec ?since=next_seq jusqu'à completed = true
+async fn agent_command_output_endpoint(
+    State(app): State<AppState>,
+    Path((id, command_id)): Path<(String, String)>,
+    Query(params): Query<CommandOutputParams>,
+) -> Response {
+    let Some((record, page)) = app.agents.commands().output(&command_id, params.since).filter(|(record, _)| record.agent_id == id) else {
+        return agent_api_error(StatusCode::NOT_FOUND, "unknown_command", format!("no command {} for agent {}", command_id, id));
+    };
+    Json(serde_json::json!({
+        "command_id": record.command_id,
+        "agent_id": record.agent_id,
+        "status": record.status,
+        "completed": page.completed,
+        "exit_code": page.exit_code,
+        "timed_out": page.timed_out,
+        "truncated": page.truncated,
+        "chunks": page.chunks,
+        "next_seq": page.next_seq,
+        "dropped_chunks": page.dropped_chunks,
+    })).into_response()
+}
+
+/// Corps de POST /agents/run-on-least-loaded
+#[derive(Deserialize)]
+struct LeastLoadedCommandRequest {
+    /// Mêmes filtres que GET /agents (os, status, distro...)
+    #[serde(default)]
+    selector: AgentListParams,
+    #[serde(default)]
+    weights: crate::agents::LoadWeights,
+    command: String,
+    parameters: Option<serde_json::Value>,
+}
+
+// POST /agents/run-on-least-loaded - run_command sur l'agent le moins chargé (CPU/RAM pondérés) du sélecteur
+async fn run_on_least_loaded_endpoint(
+    State(app): State<AppState>,
+    Query(query): Query<CommandTimeoutParams>,
+    Json(req): Json<LeastLoadedCommandRequest>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
+    if !req.weights.is_valid() {
+        return Ok(agent_api_error(
+            StatusCode::BAD_REQUEST,
+            "invalid_weights",
+            "weights must be non-negative and not all zero".to_string(),
+        ));
+    }
+    let Some(candidate) = app.agents.least_loaded(|a| req.selector.matches(a), req.weights).await else {
+        return Ok(agent_api_error(
+            StatusCode::NOT_FOUND,
+            "no_matching_agent",
+            "no reachable agent with metrics matches the selector".to_string(),
+        ));
+    };
+
+    let params = serde_json::json!({
+        "command": req.command,
+        "parameters": req.parameters
+    });
+    match app.agents.send_command_with_timeout(&candidate.agent_id, "run_command", Some(params), timeout).await {
+        Ok(command_id) => Ok(Json(serde_json::json!({
+            "success": true,
+            "command_id": command_id,
+            "agent": candidate,
+            "message": "Command execution requested"
+        })).into_response()),
+        Err(e) => Ok(command_send_error(&candidate.agent_id, "run_command", e)),
     }
 }
 
@@ -745,26 +1755,1023 @@ async fn agent_command_endpoint(
 async fn agent_metrics_endpoint(
     State(app): State<AppState>,
     Path(id): Path<String>,
-) -> Result<Json<serde_json::Value>, StatusCode> {
+    Query(query): Query<CommandTimeoutParams>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
     match app.agents.get_agent(&id).await {
         Some(agent) => {
             if let Some(system) = &agent.status.system {
-                Ok(Json(serde_json::to_value(system).unwrap()))
+                Ok(Json(serde_json::to_value(system).unwrap()).into_response())
+            } else if agent.status.status == "offline" {
+                Ok(agent_offline(&agent))
             } else {
                 // Demander les métriques via MQTT
-                match app.agents.send_command(&id, "get_metrics", None).await {
+                match app.agents.send_command_with_timeout(&id, "get_metrics", None, timeout).await {
                     Ok(command_id) => Ok(Json(serde_json::json!({
                         "success": true,
                         "command_id": command_id,
                         "message": "Metrics requested, check agent status for results"
-                    }))),
-                    Err(e) => {
-                        eprintln!("[http] failed to request metrics from agent {}: {}", id, e);
-                        Err(StatusCode::INTERNAL_SERVER_ERROR)
-                    }
+                    })).into_response()),
+                    Err(e) => Ok(command_send_error(&id, "get_metrics", e)),
                 }
             }
         }
+        None => Ok(agent_not_found(&id)),
+    }
+}
+
+// POST /agents/announce - Demande à tous les agents de se ré-enregistrer immédiatement
+async fn agents_announce_endpoint(
+    State(app): State<AppState>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    let publisher = app.mqtt_publisher.as_ref().ok_or(StatusCode::SERVICE_UNAVAILABLE)?;
+    match crate::agents::request_announce(publisher.as_ref()) {
+        Ok(request_id) => Ok(Json(serde_json::json!({
+            "success": true,
+            "request_id": request_id,
+            "agents_known": app.agents.agents_count(),
+        }))),
+        Err(e) => {
+            eprintln!("[http] failed to publish agents announce: {}", e);
+            Err(StatusCode::SERVICE_UNAVAILABLE)
+        }
+    }
+}
+
+// POST /agents/{id}/ping - Aller-retour immédiat avec l'agent (réactivité, pas fraîcheur du heartbeat)
+async fn agent_ping_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<CommandTimeoutParams>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+
+    let started = std::time::Instant::now();
+    let command_id = match app.agents.send_command_with_timeout(&id, "ping", None, timeout).await {
+        Ok(command_id) => command_id,
+        Err(e) => return Ok(command_send_error(&id, "ping", e)),
+    };
+
+    let wait = response_wait(timeout, PING_WAIT_SECONDS);
+    match app.agents.commands().wait_for_result(&command_id, wait).await {
+        Some(record) if record.status == "success" => Ok(Json(serde_json::json!({
+            "success": true,
+            "agent_id": id,
+            "command_id": command_id,
+            "round_trip_ms": started.elapsed().as_millis() as u64,
+        })).into_response()),
+        Some(record) if !record.is_pending() => Ok((StatusCode::BAD_GATEWAY, Json(record)).into_response()),
+        // Pas de pong dans le délai : l'agent ne répond pas en ce moment
+        _ => Ok(agent_api_error(
+            StatusCode::GATEWAY_TIMEOUT,
+            "ping_timeout",
+            format!("agent {} did not answer within {}s", id, wait.as_secs()),
+        )),
+    }
+}
+
+// POST /agents/{id}/transactions - Étapes séquentielles, compensées en ordre inverse sur échec
+// 200 committed ; 502 avec le rapport si une étape a échoué (rolled_back ou rollback_failed)
+async fn agent_transaction_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<crate::transactions::TransactionRequest>,
+) -> Result<Response, StatusCode> {
+    use crate::transactions::{run_transaction, TransactionStatus};
+
+    if let Err(e) = request.validate() {
+        return Ok(agent_api_error(StatusCode::BAD_REQUEST, "invalid_transaction", e));
+    }
+    // Les commandes admin ne contournent pas x-admin-key en passant par une transaction
+    if request.requires_admin() {
+        require_admin(&headers)?;
+    }
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+
+    let report = run_transaction(app.agents.as_ref(), &id, &request).await;
+    let status = match report.status {
+        TransactionStatus::Committed => StatusCode::OK,
+        TransactionStatus::RolledBack | TransactionStatus::RollbackFailed => StatusCode::BAD_GATEWAY,
+    };
+    Ok((status, Json(report)).into_response())
+}
+
+// POST /agents/{id}/heartbeat_sections - Sections incluses dans les heartbeats de cet agent
+// (jusqu'à la prochaine diffusion de heartbeat_sections au démarrage ou POST /config/reload)
+async fn agent_heartbeat_sections_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    Json(sections): Json<crate::config::HeartbeatSections>,
+) -> Result<Response, StatusCode> {
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+    match app.agents.request_heartbeat_sections(&id, sections) {
+        Ok(()) => Ok(Json(serde_json::json!({
+            "success": true,
+            "agent_id": id,
+            "sections": sections,
+        })).into_response()),
+        Err(e) => Ok(command_send_error(&id, "heartbeat_sections", e)),
+    }
+}
+
+/// Corps de POST /agents/{id}/power_schedule : seuls les champs fournis changent côté agent
+#[derive(Debug, Default, Deserialize, serde::Serialize)]
+struct PowerScheduleRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idle_minutes: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_hour: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_hour: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warn_seconds: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    maintenance: Option<bool>,
+}
+
+// POST /agents/{id}/power_schedule - Extinction automatique sur inactivité (seuil, plage horaire,
+// maintenance) ; renvoie la politique appliquée par l'agent (validation côté agent)
+async fn agent_power_schedule_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<CommandTimeoutParams>,
+    Json(req): Json<PowerScheduleRequest>,
+) -> Result<Response, StatusCode> {
+    let params = serde_json::to_value(&req).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(agent_command_result(&app, &id, "set_power_schedule", Some(params), query.timeout_secs, POWER_SCHEDULE_WAIT_SECONDS).await)
+}
+
+// GET /agents/{id}/liveness - Écarts entre heartbeats et score de flapping
+async fn agent_liveness_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    if app.agents.get_agent(&id).await.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    // Agent connu mais aucun heartbeat depuis le démarrage du kernel : stats vides
+    let stats = app.agents.liveness(&id);
+    Ok(Json(serde_json::json!({ "agent_id": id, "liveness": stats })))
+}
+
+#[derive(Deserialize)]
+struct AvailabilityParams {
+    days: Option<u32>,
+}
+
+// GET /agents/{id}/availability?days=N - Disponibilité (% online) et périodes offline, 7 jours par défaut
+async fn agent_availability_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<AvailabilityParams>,
+) -> Result<Json<crate::availability::AvailabilityReport>, StatusCode> {
+    if app.agents.get_agent(&id).await.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(Json(app.agents.availability(&id, params.days.unwrap_or(7))))
+}
+
+// GET /agents/{id}/capabilities - Détail des capacités (commande describe, raisons d'indisponibilité)
+async fn agent_capabilities_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<CommandTimeoutParams>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+
+    let command_id = match app.agents.send_command_with_timeout(&id, "describe", None, timeout).await {
+        Ok(command_id) => command_id,
+        Err(e) => return Ok(command_send_error(&id, "describe", e)),
+    };
+
+    let wait = response_wait(timeout, DESCRIBE_WAIT_SECONDS);
+    match app.agents.commands().wait_for_result(&command_id, wait).await {
+        Some(record) if record.status == "success" => {
+            let data = record.response.and_then(|r| r.data).unwrap_or(serde_json::Value::Null);
+            Ok(Json(data).into_response())
+        }
+        Some(record) if !record.is_pending() => {
+            eprintln!("[http] describe failed on agent {}: {}", id, record.status);
+            Ok((StatusCode::BAD_GATEWAY, Json(record)).into_response())
+        }
+        // Agent lent : le résultat reste consultable via /commands/{command_id}/result
+        _ => Ok((StatusCode::ACCEPTED, Json(serde_json::json!({
+            "success": true,
+            "command_id": command_id,
+            "message": "Capabilities requested, poll the command result"
+        }))).into_response()),
+    }
+}
+
+// GET /agents/{id}/listeners - Sockets TCP/UDP en écoute et processus propriétaires (audit)
+async fn agent_listeners_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<CommandTimeoutParams>,
+) -> Result<Response, StatusCode> {
+    let timeout = query.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+
+    let command_id = match app.agents.send_command_with_timeout(&id, "list_listeners", None, timeout).await {
+        Ok(command_id) => command_id,
+        Err(e) => return Ok(command_send_error(&id, "list_listeners", e)),
+    };
+
+    let wait = response_wait(timeout, LISTENERS_WAIT_SECONDS);
+    match app.agents.commands().wait_for_result(&command_id, wait).await {
+        Some(record) if record.status == "success" => {
+            let data = record.response.and_then(|r| r.data).unwrap_or(serde_json::Value::Null);
+            Ok(Json(data).into_response())
+        }
+        Some(record) if !record.is_pending() => {
+            eprintln!("[http] list_listeners failed on agent {}: {}", id, record.status);
+            Ok((StatusCode::BAD_GATEWAY, Json(record)).into_response())
+        }
+        _ => Ok((StatusCode::ACCEPTED, Json(serde_json::json!({
+            "success": true,
+            "command_id": command_id,
+            "message": "Listeners requested, poll the command result"
+        }))).into_response()),
+    }
+}
+
+// GET /agents/{id}/diagnostics - Auto-diagnostic de l'agent (config, MQTT, capacités, disque, élévation)
+async fn agent_diagnostics_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<CommandTimeoutParams>,
+) -> Response {
+    agent_command_result(&app, &id, "diagnostics", None, query.timeout_secs, DIAGNOSTICS_WAIT_SECONDS).await
+}
+
+/// Corps de POST /agents/{id}/firewall et /firewall/remove (règle validée par l'agent)
+#[derive(Debug, Deserialize)]
+struct FirewallRuleRequest {
+    rule: serde_json::Value,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Envoie une commande et attend sa réponse au plus `default_wait` s (202 + command_id si elle tarde)
+async fn agent_command_result(
+    app: &AppState,
+    id: &str,
+    command_type: &str,
+    params: Option<serde_json::Value>,
+    timeout: Option<u32>,
+    default_wait: u64,
+) -> Response {
+    if let Some(response) = invalid_timeout(timeout) {
+        return response;
+    }
+    if let Some(response) = unreachable_agent(app, id).await {
+        return response;
+    }
+
+    let command_id = match app.agents.send_command_with_timeout(id, command_type, params, timeout).await {
+        Ok(command_id) => command_id,
+        Err(e) => return command_send_error(id, command_type, e),
+    };
+
+    let wait = response_wait(timeout, default_wait);
+    match app.agents.commands().wait_for_result(&command_id, wait).await {
+        Some(record) if record.status == "success" => {
+            let data = record.response.and_then(|r| r.data).unwrap_or(serde_json::Value::Null);
+            Json(data).into_response()
+        }
+        Some(record) if !record.is_pending() => {
+            eprintln!("[http] {} failed on agent {}: {}", command_type, id, record.status);
+            (StatusCode::BAD_GATEWAY, Json(record)).into_response()
+        }
+        _ => (StatusCode::ACCEPTED, Json(serde_json::json!({
+            "success": true,
+            "command_id": command_id,
+            "message": format!("{} requested, poll the command result", command_type)
+        }))).into_response(),
+    }
+}
+
+// GET /agents/{id}/firewall - Règles actives du pare-feu de l'hôte (admin)
+async fn agent_firewall_list_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<CommandTimeoutParams>,
+) -> Result<Response, StatusCode> {
+    require_admin(&headers)?;
+    Ok(agent_command_result(&app, &id, "firewall_list", None, query.timeout_secs, FIREWALL_WAIT_SECONDS).await)
+}
+
+// POST /agents/{id}/firewall - Ajoute une règle allow/deny (admin, dry_run : commande prévue seulement)
+async fn agent_firewall_add_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<CommandTimeoutParams>,
+    Json(req): Json<FirewallRuleRequest>,
+) -> Result<Response, StatusCode> {
+    require_admin(&headers)?;
+    let params = serde_json::json!({ "rule": req.rule, "dry_run": req.dry_run });
+    Ok(agent_command_result(&app, &id, "firewall_add", Some(params), query.timeout_secs, FIREWALL_WAIT_SECONDS).await)
+}
+
+// POST /agents/{id}/firewall/remove - Retire une règle ajoutée avec les mêmes champs (admin)
+async fn agent_firewall_remove_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<CommandTimeoutParams>,
+    Json(req): Json<FirewallRuleRequest>,
+) -> Result<Response, StatusCode> {
+    require_admin(&headers)?;
+    let params = serde_json::json!({ "rule": req.rule, "dry_run": req.dry_run });
+    Ok(agent_command_result(&app, &id, "firewall_remove", Some(params), query.timeout_secs, FIREWALL_WAIT_SECONDS).await)
+}
+
+// GET /agents/{id}/tail?path=&lines=&follow= - Dernières lignes d'un fichier de l'agent
+// follow=N : flux NDJSON (une ligne par réponse agent) pendant N secondes au plus
+async fn agent_tail_endpoint(
+    State(app): State<AppState>,
+    Path(id): Path<String>,
+    Query(params): Query<TailParams>,
+) -> Result<Response, StatusCode> {
+    if let Some(response) = unreachable_agent(&app, &id).await {
+        return Ok(response);
+    }
+
+    let timeout = params.timeout_secs;
+    if let Some(response) = invalid_timeout(timeout) {
+        return Ok(response);
+    }
+    let follow = params.follow.unwrap_or(0).min(MAX_TAIL_FOLLOW_SECONDS);
+    let mut parameters = serde_json::json!({ "path": params.path, "follow_secs": follow });
+    if let Some(lines) = params.lines {
+        parameters["lines"] = serde_json::json!(lines);
+    }
+    let command_id = match app.agents.send_command_with_timeout(&id, "tail_file", Some(parameters), timeout).await {
+        Ok(command_id) => command_id,
+        Err(e) => return Ok(command_send_error(&id, "tail_file", e)),
+    };
+
+    if follow > 0 {
+        if let Some(responses) = app.agents.commands().subscribe(&command_id) {
+            let chunks = futures_util::stream::unfold(responses, |mut responses| async move {
+                let response = responses.recv().await?;
+                let line = serde_json::json!({
+                    "status": response.status,
+                    "data": response.data,
+                    "error": response.error,
+                });
+                Some((Ok::<_, std::convert::Infallible>(format!("{}\n", line)), responses))
+            });
+            return Ok((
+                [(axum::http::header::CONTENT_TYPE, "application/x-ndjson")],
+                axum::body::Body::from_stream(chunks),
+            ).into_response());
+        }
+    }
+
+    let wait = response_wait(timeout, TAIL_WAIT_SECONDS);
+    match app.agents.commands().wait_for_result(&command_id, wait).await {
+        Some(record) if record.status == "success" => {
+            let data = record.response.and_then(|r| r.data).unwrap_or(serde_json::Value::Null);
+            Ok(Json(data).into_response())
+        }
+        Some(record) if !record.is_pending() => Ok((StatusCode::BAD_GATEWAY, Json(record)).into_response()),
+        _ => Ok((StatusCode::ACCEPTED, Json(serde_json::json!({
+            "success": true,
+            "command_id": command_id,
+            "message": "Tail requested, poll the command result"
+        }))).into_response()),
+    }
+}
+
+// ====== COMMANDS ENDPOINTS ======
+
+// GET /commands/{command_id}/result?wait=30 - Long-poll du résultat d'une commande
+async fn command_result_endpoint(
+    State(app): State<AppState>,
+    Path(command_id): Path<String>,
+    Query(params): Query<CommandResultParams>,
+) -> Result<Response, StatusCode> {
+    let wait = std::time::Duration::from_secs(params.wait.unwrap_or(0).min(MAX_RESULT_WAIT_SECONDS));
+
+    match app.agents.commands().wait_for_result(&command_id, wait).await {
+        Some(record) if record.is_pending() => Ok(StatusCode::NO_CONTENT.into_response()),
+        Some(record) => Ok(Json(record).into_response()),
         None => Err(StatusCode::NOT_FOUND),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_flag_follows_configured_threshold() {
+        let now = OffsetDateTime::now_utc();
+        let host = HostState {
+            host_id: "desktop".into(),
+            last_seen: now - Duration::seconds(120),
+            cpu: None,
+            ram: None,
+            ip: None,
+            mac: None,
+            broadcast: None,
+        };
+
+        let view = to_view(&host, crate::config::DEFAULT_STALE_AFTER_SECS, now);
+        assert!(view.stale);
+        assert_eq!(view.stale_for_seconds, 120);
+
+        let view = to_view(&host, 300, now);
+        assert!(!view.stale);
+    }
+
+    fn peer(addr: &str) -> Option<IpAddr> {
+        Some(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn test_dev_mode_allows_loopback_without_key() {
+        let auth = ApiAuth { api_key: String::new(), dev_mode: true };
+        let headers = HeaderMap::new();
+        assert!(auth.dev_mode_active());
+        assert!(auth.authorize(&headers, peer("127.0.0.1")).is_ok());
+        assert!(auth.authorize(&headers, peer("::1")).is_ok());
+        assert!(auth.authorize(&headers, peer("::ffff:127.0.0.1")).is_ok());
+    }
+
+    #[test]
+    fn test_dev_mode_still_denies_remote_requests() {
+        let auth = ApiAuth { api_key: String::new(), dev_mode: true };
+        let headers = HeaderMap::new();
+        assert_eq!(auth.authorize(&headers, peer("192.168.1.50")), Err(StatusCode::UNAUTHORIZED));
+        assert_eq!(auth.authorize(&headers, peer("::ffff:192.168.1.50")), Err(StatusCode::UNAUTHORIZED));
+        // Adresse inconnue : jamais considérée comme locale
+        assert_eq!(auth.authorize(&headers, None), Err(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn test_unset_key_denies_everything_without_dev_mode() {
+        let headers = HeaderMap::new();
+        let auth = ApiAuth::default();
+        assert!(!auth.dev_mode_active());
+        assert_eq!(auth.authorize(&headers, peer("127.0.0.1")), Err(StatusCode::UNAUTHORIZED));
+
+        // Une clé configurée prime sur le mode dev, même en loopback
+        let auth = ApiAuth { api_key: "secret".into(), dev_mode: true };
+        assert!(!auth.dev_mode_active());
+        assert_eq!(auth.authorize(&headers, peer("127.0.0.1")), Err(StatusCode::UNAUTHORIZED));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "secret".parse().unwrap());
+        assert!(auth.authorize(&headers, peer("192.168.1.50")).is_ok());
+    }
+
+    fn access(yaml: &str) -> ApiAccessConf {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    /// Routeur minimal derrière le middleware d'authentification, appelé depuis `peer`
+    async fn call_protected(cfg: HostsConfig, peer: &str, headers: &[(&str, &str)]) -> StatusCode {
+        use tower::ServiceExt;
+        let router = Router::new()
+            .route("/hosts", get(|| async { "hosts" }))
+            .layer(middleware::from_fn_with_state(crate::state::new_state(cfg), require_api_key));
+        let mut request = axum::http::Request::builder().uri("/hosts");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let mut request = request.body(axum::body::Body::empty()).unwrap();
+        request.extensions_mut().insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    async fn error_body(response: Response) -> (StatusCode, serde_json::Value) {
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_command_send_failures_map_to_codes() {
+        let cases = [
+            (anyhow::Error::from(crate::agents::CommandSendError::MqttNotConfigured), StatusCode::SERVICE_UNAVAILABLE, "mqtt_not_configured"),
+            (crate::agents::CommandSendError::Publish("request channel closed".into()).into(), StatusCode::SERVICE_UNAVAILABLE, "mqtt_publish_failed"),
+            (crate::rate_limit::RateLimited { agent_id: "a1".into(), burst: 2, per_second: 1.0, retry_after_ms: 1500 }.into(), StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+            (anyhow::anyhow!("serialization failed"), StatusCode::INTERNAL_SERVER_ERROR, "command_failed"),
+        ];
+        for (error, status, code) in cases {
+            let (actual, body) = error_body(command_send_error("a1", "shutdown", error)).await;
+            assert_eq!((actual, body["code"].as_str().unwrap()), (status, code));
+            assert_eq!(body["success"], false);
+            assert!(body["error"].is_string());
+        }
+
+        let (status, body) = error_body(agent_not_found("ghost")).await;
+        assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::NOT_FOUND, "agent_not_found"));
+    }
+
+    #[tokio::test]
+    async fn test_command_timeout_override_is_validated() {
+        assert!(invalid_timeout(None).is_none());
+        assert!(invalid_timeout(Some(1800)).is_none());
+        for invalid in [0, crate::agents::MAX_COMMAND_TIMEOUT_SECONDS + 1] {
+            let (status, body) = error_body(invalid_timeout(Some(invalid)).unwrap()).await;
+            assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::BAD_REQUEST, "invalid_timeout"));
+        }
+
+        // Attente synchrone : délai demandé, borné au long-poll maximal
+        assert_eq!(response_wait(None, DESCRIBE_WAIT_SECONDS).as_secs(), DESCRIBE_WAIT_SECONDS);
+        assert_eq!(response_wait(Some(25), DESCRIBE_WAIT_SECONDS).as_secs(), 25);
+        assert_eq!(response_wait(Some(1800), DESCRIBE_WAIT_SECONDS).as_secs(), MAX_RESULT_WAIT_SECONDS);
+    }
+
+    /// État minimal autour d'un registre d'agents (sans broker)
+    fn agents_app_state(agents: crate::agents::AgentRegistry) -> AppState {
+        AppState {
+            states: crate::state::new_state(HostsMap::new()),
+            cfg: crate::state::new_state(HostsConfig::default()),
+            contracts: crate::contracts::ContractRegistry::new(),
+            health_tracker: crate::health::HealthTracker::new(),
+            ports: crate::state::new_state(crate::ports::PortRegistry::new()),
+            plugins: crate::state::new_state(crate::plugins::PluginManager::new("./plugins")),
+            notes_bridge: None,
+            agents: std::sync::Arc::new(agents),
+            mqtt_publisher: None,
+            plugin_routes: std::sync::Arc::new(crate::plugin_routes::PluginRouteRegistry::default()),
+            dead_letters: crate::dead_letter::DeadLetterStore::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_distinguishes_unknown_and_offline_agents() {
+        let (tx, rx) = flume::bounded(10);
+        let agents = crate::agents::AgentRegistry::new(&crate::agents::temp_data_file()).with_mqtt_client(rumqttc::AsyncClient::from_senders(tx));
+        agents.handle_agent_registration(serde_json::from_value(serde_json::json!({
+            "agent_id": "a1b2c3d4e5f6",
+            "hostname": "desktop",
+            "os": "linux",
+            "architecture": "x86_64",
+            "capabilities": ["power_management"],
+            "network": { "primary_mac": "a1:b2:c3:d4:e5:f6", "interfaces": [] },
+            "version": "1.0.0",
+            "timestamp": "2025-09-01T10:30:00Z"
+        })).unwrap()).await.unwrap();
+        let app = agents_app_state(agents);
+        let shutdown = |id: &str| agent_shutdown_endpoint(State(app.clone()), Path(id.to_string()), Query(CommandTimeoutParams { timeout_secs: None }), None);
+
+        let (status, body) = error_body(shutdown("ffffffffffff").await.unwrap()).await;
+        assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::NOT_FOUND, "agent_not_found"));
+
+        // En ligne : commande envoyée
+        assert_eq!(shutdown("a1b2c3d4e5f6").await.unwrap().status(), StatusCode::OK);
+        // Avertissement des utilisateurs transmis à l'agent
+        let notice = PowerNoticeRequest { message: Some("Maintenance".to_string()), warn_seconds: Some(120) };
+        let response = agent_shutdown_endpoint(State(app.clone()), Path("a1b2c3d4e5f6".to_string()), Query(CommandTimeoutParams { timeout_secs: None }), Some(Json(notice))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let parameters: Vec<serde_json::Value> = rx.try_iter()
+            .filter_map(|request| match request {
+                rumqttc::Request::Publish(publish) => Some(serde_json::from_slice::<serde_json::Value>(&publish.payload).unwrap()["parameters"].clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(parameters, vec![serde_json::Value::Null, serde_json::json!({ "message": "Maintenance", "warn_seconds": 120 })]);
+
+        app.agents.mark_agent_offline("a1b2c3d4e5f6").await;
+        let (status, body) = error_body(shutdown("a1b2c3d4e5f6").await.unwrap()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!((body["code"].as_str().unwrap(), body["status"].as_str().unwrap()), ("agent_offline", "offline"));
+        assert!(body["last_seen"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_power_schedule_sends_only_the_given_fields() {
+        let (tx, rx) = flume::bounded(10);
+        let agents = crate::agents::AgentRegistry::new(&crate::agents::temp_data_file()).with_mqtt_client(rumqttc::AsyncClient::from_senders(tx));
+        agents.handle_agent_registration(serde_json::from_value(serde_json::json!({
+            "agent_id": "a1b2c3d4e5f6",
+            "hostname": "desktop",
+            "os": "linux",
+            "architecture": "x86_64",
+            "capabilities": ["power_management"],
+            "network": { "primary_mac": "a1:b2:c3:d4:e5:f6", "interfaces": [] },
+            "version": "1.0.0",
+            "timestamp": "2025-09-01T10:30:00Z"
+        })).unwrap()).await.unwrap();
+        let app = agents_app_state(agents);
+
+        let req = PowerScheduleRequest { enabled: Some(true), idle_minutes: Some(45), ..Default::default() };
+        let request = tokio::spawn(agent_power_schedule_endpoint(
+            State(app.clone()), Path("a1b2c3d4e5f6".to_string()), Query(CommandTimeoutParams { timeout_secs: None }), Json(req)));
+        let command = match rx.recv_async().await.unwrap() {
+            rumqttc::Request::Publish(publish) => serde_json::from_slice::<serde_json::Value>(&publish.payload).unwrap(),
+            other => panic!("unexpected request {:?}", other),
+        };
+        request.abort();
+        assert_eq!(command["command_type"], "set_power_schedule");
+        // Champs absents : l'agent garde sa valeur courante
+        assert_eq!(command["parameters"], serde_json::json!({ "enabled": true, "idle_minutes": 45 }));
+    }
+
+    #[tokio::test]
+    async fn test_agent_command_status_is_accepted_until_the_response_arrives() {
+        let agents = crate::agents::AgentRegistry::new(&crate::agents::temp_data_file());
+        agents.commands().track("cmd-1", "a1b2c3d4e5f6", "run_command", 30);
+        let app = agents_app_state(agents);
+        let status = |id: &str| agent_command_status_endpoint(State(app.clone()), Path((id.to_string(), "cmd-1".to_string())));
+
+        assert_eq!(status("a1b2c3d4e5f6").await.status(), StatusCode::ACCEPTED);
+        let (code, body) = error_body(status("0a0b0c0d0e0f").await).await;
+        assert_eq!((code, body["code"].as_str().unwrap()), (StatusCode::NOT_FOUND, "unknown_command"));
+
+        app.agents.handle_command_response(serde_json::from_value(serde_json::json!({
+            "command_id": "cmd-1",
+            "agent_id": "a1b2c3d4e5f6",
+            "status": "success",
+            "data": { "stdout": "ok" },
+            "error": null,
+            "execution_time_ms": 12,
+            "timestamp": "2025-09-01T10:30:01Z"
+        })).unwrap());
+        let (code, body) = error_body(status("a1b2c3d4e5f6").await).await;
+        assert_eq!(code, StatusCode::OK);
+        assert_eq!((body["status"].as_str().unwrap(), &body["response"]["data"]), ("success", &serde_json::json!({ "stdout": "ok" })));
+    }
+
+    #[tokio::test]
+    async fn test_process_priority_sends_renice_and_affinity() {
+        let (tx, rx) = flume::bounded(10);
+        let agents = crate::agents::AgentRegistry::new(&crate::agents::temp_data_file()).with_mqtt_client(rumqttc::AsyncClient::from_senders(tx));
+        agents.handle_agent_registration(serde_json::from_value(serde_json::json!({
+            "agent_id": "a1b2c3d4e5f6",
+            "hostname": "build-box",
+            "os": "linux",
+            "architecture": "x86_64",
+            "capabilities": ["process_control"],
+            "network": { "primary_mac": "a1:b2:c3:d4:e5:f6", "interfaces": [] },
+            "version": "1.0.0",
+            "timestamp": "2025-09-01T10:30:00Z"
+        })).unwrap()).await.unwrap();
+        let app = agents_app_state(agents);
+        let priority = |pid: u32, body: serde_json::Value| agent_process_priority_endpoint(
+            State(app.clone()),
+            Path(("a1b2c3d4e5f6".to_string(), pid)),
+            Query(CommandTimeoutParams { timeout_secs: None }),
+            Json(serde_json::from_value(body).unwrap()),
+        );
+
+        for (pid, body) in [(4242, serde_json::json!({})), (4242, serde_json::json!({ "nice": 20 })), (4242, serde_json::json!({ "cpus": [] })), (0, serde_json::json!({ "nice": 5 }))] {
+            let (status, body) = error_body(priority(pid, body).await.unwrap()).await;
+            assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::BAD_REQUEST, "invalid_priority"));
+        }
+        assert!(rx.is_empty());
+
+        let (status, body) = error_body(priority(4242, serde_json::json!({ "nice": 10, "cpus": [0, 1] })).await.unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["command_ids"]["renice"].is_string() && body["command_ids"]["set_affinity"].is_string());
+        let sent: Vec<serde_json::Value> = rx.try_iter()
+            .filter_map(|request| match request {
+                rumqttc::Request::Publish(publish) => Some(serde_json::from_slice(&publish.payload).unwrap()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sent.len(), 2);
+        assert_eq!((sent[0]["command_type"].as_str().unwrap(), &sent[0]["parameters"]), ("renice", &serde_json::json!({ "pid": 4242, "nice": 10 })));
+        assert_eq!((sent[1]["command_type"].as_str().unwrap(), &sent[1]["parameters"]), ("set_affinity", &serde_json::json!({ "pid": 4242, "cpus": [0, 1] })));
+        // Priorité laissée à l'agent : le kernel n'en impose aucune
+        assert!(sent.iter().all(|command| command.get("priority").is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_forwards_only_an_explicit_priority() {
+        let (tx, rx) = flume::bounded(10);
+        let agents = crate::agents::AgentRegistry::new(&crate::agents::temp_data_file()).with_mqtt_client(rumqttc::AsyncClient::from_senders(tx));
+        agents.handle_agent_registration(serde_json::from_value(serde_json::json!({
+            "agent_id": "a1b2c3d4e5f6",
+            "hostname": "build-box",
+            "os": "linux",
+            "architecture": "x86_64",
+            "capabilities": ["command_execution"],
+            "network": { "primary_mac": "a1:b2:c3:d4:e5:f6", "interfaces": [] },
+            "version": "1.0.0",
+            "timestamp": "2025-09-01T10:30:00Z"
+        })).unwrap()).await.unwrap();
+        let app = agents_app_state(agents);
+        let run = |body: serde_json::Value| agent_command_endpoint(
+            State(app.clone()),
+            Path("a1b2c3d4e5f6".to_string()),
+            Query(CommandTimeoutParams { timeout_secs: None }),
+            Json(serde_json::from_value(body).unwrap()),
+        );
+
+        let (status, body) = error_body(run(serde_json::json!({ "command": "uptime", "priority": "urgent" })).await.unwrap()).await;
+        assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::BAD_REQUEST, "invalid_priority"));
+        assert!(rx.is_empty());
+
+        assert_eq!(run(serde_json::json!({ "command": "uptime" })).await.unwrap().status(), StatusCode::OK);
+        assert_eq!(run(serde_json::json!({ "command": "uptime", "priority": "high" })).await.unwrap().status(), StatusCode::OK);
+        let sent: Vec<serde_json::Value> = rx.try_iter()
+            .filter_map(|request| match request {
+                rumqttc::Request::Publish(publish) => Some(serde_json::from_slice(&publish.payload).unwrap()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].get("priority").is_none());
+        assert_eq!(sent[1]["priority"], "high");
+    }
+
+    #[tokio::test]
+    async fn test_ping_measures_round_trip_through_mock_agent() {
+        let (tx, rx) = flume::bounded(10);
+        let agents = crate::agents::AgentRegistry::new(&crate::agents::temp_data_file()).with_mqtt_client(rumqttc::AsyncClient::from_senders(tx));
+        agents.handle_agent_registration(serde_json::from_value(serde_json::json!({
+            "agent_id": "a1b2c3d4e5f6",
+            "hostname": "build-box",
+            "os": "linux",
+            "architecture": "x86_64",
+            "capabilities": [],
+            "network": { "primary_mac": "a1:b2:c3:d4:e5:f6", "interfaces": [] },
+            "version": "1.0.0",
+            "timestamp": "2025-09-01T10:30:00Z"
+        })).unwrap()).await.unwrap();
+        let app = agents_app_state(agents);
+
+        // Agent factice derrière le "broker" : pong après un délai injecté
+        let delay = std::time::Duration::from_millis(80);
+        let agents = app.agents.clone();
+        let mock_agent = tokio::spawn(async move {
+            let command: serde_json::Value = match rx.recv_async().await.unwrap() {
+                rumqttc::Request::Publish(publish) => serde_json::from_slice(&publish.payload).unwrap(),
+                other => panic!("unexpected request {:?}", other),
+            };
+            assert_eq!(command["command_type"], "ping");
+            assert!(command.get("priority").is_none());
+            tokio::time::sleep(delay).await;
+            agents.handle_command_response(serde_json::from_value(serde_json::json!({
+                "command_id": command["command_id"],
+                "agent_id": "a1b2c3d4e5f6",
+                "status": "success",
+                "data": { "pong": true },
+                "execution_time_ms": 0,
+                "timestamp": "2025-09-01T10:30:00Z"
+            })).unwrap());
+            rx
+        });
+
+        let ping = |id: &str, timeout_secs| agent_ping_endpoint(State(app.clone()), Path(id.to_string()), Query(CommandTimeoutParams { timeout_secs }));
+        let (status, body) = error_body(ping("a1b2c3d4e5f6", None).await.unwrap()).await;
+        let _rx = mock_agent.await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let round_trip_ms = body["round_trip_ms"].as_u64().unwrap();
+        assert!(round_trip_ms >= delay.as_millis() as u64, "round trip {}ms shorter than the injected delay", round_trip_ms);
+        assert!(round_trip_ms < 5000);
+
+        // Aucun pong : 504 une fois le délai écoulé
+        let (status, body) = error_body(ping("a1b2c3d4e5f6", Some(1)).await.unwrap()).await;
+        assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::GATEWAY_TIMEOUT, "ping_timeout"));
+        let (status, _) = error_body(ping("unknown", None).await.unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_sections_request_targets_agent() {
+        let (tx, rx) = flume::bounded(10);
+        let agents = crate::agents::AgentRegistry::new(&crate::agents::temp_data_file()).with_mqtt_client(rumqttc::AsyncClient::from_senders(tx));
+        agents.handle_agent_registration(serde_json::from_value(serde_json::json!({
+            "agent_id": "a1b2c3d4e5f6",
+            "hostname": "build-box",
+            "os": "linux",
+            "architecture": "x86_64",
+            "capabilities": [],
+            "network": { "primary_mac": "a1:b2:c3:d4:e5:f6", "interfaces": [] },
+            "version": "1.0.0",
+            "timestamp": "2025-09-01T10:30:00Z"
+        })).unwrap()).await.unwrap();
+        let app = agents_app_state(agents);
+        let request = |id: &str| agent_heartbeat_sections_endpoint(
+            State(app.clone()),
+            Path(id.to_string()),
+            Json(serde_json::from_value(serde_json::json!({ "processes": true })).unwrap()),
+        );
+
+        let (status, _) = error_body(request("unknown").await.unwrap()).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(rx.is_empty());
+
+        let (status, body) = error_body(request("a1b2c3d4e5f6").await.unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["sections"], serde_json::json!({ "processes": true, "services": false, "temperatures": false }));
+        let published: Vec<_> = rx.try_iter()
+            .filter_map(|request| match request {
+                rumqttc::Request::Publish(publish) => Some(publish),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].topic, crate::agents::HEARTBEAT_CONFIG_TOPIC);
+        // Demande ponctuelle : ne remplace pas la diffusion retenue
+        assert!(!published[0].retain);
+        let message: serde_json::Value = serde_json::from_slice(&published[0].payload).unwrap();
+        assert_eq!(message["agent_id"], "a1b2c3d4e5f6");
+        assert_eq!(message["sections"]["processes"], true);
+    }
+
+    #[tokio::test]
+    async fn test_system_health_includes_plugin_reports() {
+        let app = agents_app_state(crate::agents::AgentRegistry::new(&crate::agents::temp_data_file()));
+        app.health_tracker.plugin_health().record(serde_json::from_value(serde_json::json!({
+            "plugin": "notes-manager",
+            "status": "unhealthy",
+            "checks": { "storage": { "status": "unhealthy", "message": "./notes.json: permission denied" } },
+            "details": { "notes_count": 42 },
+            "timestamp": "2025-09-01T10:30:00Z"
+        })).unwrap());
+
+        let Json(health) = get_system_health(State(app)).await;
+        let health = serde_json::to_value(health).unwrap();
+        let notes = &health["plugins"]["notes-manager"];
+        assert_eq!(notes["status"], "unhealthy");
+        assert_eq!(notes["checks"]["storage"]["message"], "./notes.json: permission denied");
+        assert_eq!(notes["details"]["notes_count"], 42);
+        assert_eq!(notes["stale"], false);
+    }
+
+    #[tokio::test]
+    async fn test_version_reports_crate_version_and_contracts() {
+        let contracts = crate::contracts::ContractRegistry::load_contracts_from_dir("../contracts/mqtt").await.unwrap();
+        let info = version_info(&contracts);
+        assert_eq!(info.version, "0.1.0");
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_commit.is_empty());
+        assert_eq!(info.api_version, API_VERSION);
+        assert_eq!(info.contracts["agents.command"], vec!["v1"]);
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["version"], "0.1.0");
+        assert!(json["git_commit"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_is_enforced_before_api_key() {
+        let cfg = HostsConfig { api_access: access("allowlist: [\"192.168.1.0/24\"]"), ..HostsConfig::default() };
+        let wrong_key = [("x-api-key", "definitely-not-the-key")];
+
+        // Hors liste : refusé avant même la vérification de la clé
+        assert_eq!(call_protected(cfg.clone(), "203.0.113.9:40000", &wrong_key).await, StatusCode::FORBIDDEN);
+        // Dans la liste : la requête passe à la vérification de la clé (qui échoue ici)
+        assert_eq!(call_protected(cfg, "192.168.1.44:40000", &wrong_key).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_header_only_trusted_from_trusted_proxy() {
+        let cfg = HostsConfig {
+            api_access: access("allowlist: [\"192.168.1.0/24\"]\ntrusted_proxy_header: x-forwarded-for"),
+            ..HostsConfig::default()
+        };
+        let forwarded = [("x-forwarded-for", "192.168.1.44")];
+
+        // Reverse proxy local : l'adresse transmise fait foi
+        assert_eq!(call_protected(cfg.clone(), "127.0.0.1:40000", &forwarded).await, StatusCode::UNAUTHORIZED);
+        // Client distant qui forge l'en-tête : son adresse réelle est utilisée
+        assert_eq!(call_protected(cfg, "203.0.113.9:40000", &forwarded).await, StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn test_client_ip_skips_trusted_proxies_from_the_right() {
+        let access = access("trusted_proxy_header: x-forwarded-for\ntrusted_proxies: [\"127.0.0.1\", \"10.0.0.0/8\"]");
+        let mut headers = HeaderMap::new();
+        // Entrée de gauche forgée par le client, 10.0.0.2 = second proxy de confiance
+        headers.insert("x-forwarded-for", "1.2.3.4, 192.168.1.44, 10.0.0.2".parse().unwrap());
+        let proxy = Some("127.0.0.1:40000".parse().unwrap());
+        assert_eq!(client_ip(&access, proxy, &headers), peer("192.168.1.44"));
+
+        // En-tête absent ou illisible : adresse de connexion
+        assert_eq!(client_ip(&access, proxy, &HeaderMap::new()), peer("127.0.0.1"));
+        headers.insert("x-forwarded-for", "garbage".parse().unwrap());
+        assert_eq!(client_ip(&access, proxy, &headers), peer("127.0.0.1"));
+        assert_eq!(client_ip(&access, None, &headers), None);
+    }
+
+    #[tokio::test]
+    async fn test_port_write_is_streamed_as_sse_event() {
+        use futures_util::StreamExt;
+        let path = std::env::temp_dir().join(format!("symbion-port-{}.json", uuid::Uuid::new_v4()));
+        let mut registry = crate::ports::PortRegistry::new();
+        registry.register("journal", crate::ports::JsonFileDataPort::new("journal", &path));
+        let ports = crate::state::new_state(registry);
+
+        assert_eq!(port_events_stream(&ports, "ghost").status(), StatusCode::NOT_FOUND);
+        let response = port_events_stream(&ports, "journal");
+        assert_eq!(response.headers()[axum::http::header::CONTENT_TYPE], "text/event-stream");
+        let mut body = response.into_body().into_data_stream();
+
+        let id = ports.lock().get("journal").unwrap().write(&crate::ports::PortData {
+            id: String::new(),
+            timestamp: OffsetDateTime::now_utc(),
+            data: serde_json::json!({ "content": "écrit" }),
+            metadata: HashMap::new(),
+        }).unwrap();
+
+        let frame = tokio::time::timeout(std::time::Duration::from_secs(5), body.next()).await.unwrap().unwrap().unwrap();
+        let frame = String::from_utf8(frame.to_vec()).unwrap();
+        assert!(frame.starts_with("event: created\n"), "{}", frame);
+        let data = frame.lines().find_map(|l| l.strip_prefix("data: ")).unwrap();
+        let change: crate::ports::PortChange = serde_json::from_str(data).unwrap();
+        assert_eq!(change.id, id);
+        assert_eq!(change.port, "journal");
+        std::fs::remove_file(&path).ok();
+    }
+
+    async fn next_log_line<S>(client: &mut S) -> serde_json::Value
+    where
+        S: futures_util::Stream<Item = Result<tokio_tungstenite::tungstenite::Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        use futures_util::StreamExt;
+        let message = tokio::time::timeout(std::time::Duration::from_secs(5), client.next()).await.unwrap().unwrap().unwrap();
+        let tokio_tungstenite::tungstenite::Message::Text(text) = message else { panic!("unexpected frame: {:?}", message) };
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_plugin_log_stream_follows_restarts() {
+
+        // Plugin "probe" = uname : une ligne sur stdout puis fin du processus
+        let dir = std::env::temp_dir().join(format!("symbion-log-stream-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let binary = ["/usr/bin/uname", "/bin/uname"].into_iter().find(|p| std::path::Path::new(p).exists()).unwrap();
+        let manifest = serde_json::json!({
+            "name": "probe",
+            "version": "0.1.0",
+            "binary": binary,
+            "contracts": [],
+            "auto_start": false,
+            "restart_on_failure": false,
+            "startup_timeout_seconds": 5,
+            "shutdown_timeout_seconds": 5,
+            "depends_on": [],
+            "start_priority": 50
+        });
+        std::fs::write(dir.join("probe.json"), manifest.to_string()).unwrap();
+        let logs = crate::config::PluginLogsConf {
+            dir: dir.join("logs").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let mut manager = crate::plugins::PluginManager::new(&dir).with_log_capture(&logs);
+        manager.discover_plugins().await.unwrap();
+        let plugins = crate::state::new_state(manager);
+
+        let routes = plugins.clone();
+        let router = Router::new().route(
+            "/plugins/{name}/logs/stream",
+            get(move |Path(name): Path<String>, ws: WebSocketUpgrade| async move { plugin_logs_socket(&routes, &name, ws) }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let missing = tokio_tungstenite::connect_async(format!("ws://{}/plugins/ghost/logs/stream", addr)).await;
+        assert!(missing.is_err());
+
+        let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{}/plugins/probe/logs/stream", addr)).await.unwrap();
+        plugins.lock().start_plugin("probe").unwrap();
+        let first = next_log_line(&mut client).await;
+        assert_eq!(first["stream"], "stdout");
+        assert!(!first["line"].as_str().unwrap().is_empty());
+
+        // Le nouveau processus est diffusé sur la même connexion
+        let restarted = plugins.clone();
+        tokio::task::spawn_blocking(move || restarted.lock().restart_plugin("probe")).await.unwrap().unwrap();
+        assert_eq!(next_log_line(&mut client).await["line"], first["line"]);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,224 @@
+/**
+ * HTTP METRICS - Latence et taux d'erreur par route de l'API REST
+ *
+ * RÔLE :
+ * Middleware Axum enregistrant, pour chaque route (chemin gabarit, ex: /agents/{id}),
+ * le nombre de requêtes, un histogramme de latence et le décompte par code HTTP.
+ *
+ * FONCTIONNEMENT :
+ * - Clé = MatchedPath (pas l'URL brute → cardinalité bornée)
+ * - Histogramme cumulatif façon Prometheus : bucket "le" = latence <= borne
+ * - Erreur = statut >= 500 (les 4xx sont des erreurs client, comptées à part)
+ * - Exposé en détail sur GET /metrics, résumé dans /system/health
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Repérer un port lent ou un bridge plugin en échec
+ * 🎯 Suivre la charge de l'API depuis le dashboard
+ */
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Bornes supérieures des buckets de latence (ms), +Inf implicite en dernier
+pub const LATENCY_BUCKETS_MS: &[u64] = &[5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000];
+
+/// Compteurs d'une route
+#[derive(Debug, Clone, Default)]
+struct RouteStats {
+    count: u64,
+    server_errors: u64,
+    client_errors: u64,
+    total_ms: f64,
+    max_ms: f64,
+    /// Un compteur par borne + un pour +Inf (non cumulatif en interne)
+    buckets: Vec<u64>,
+    status_codes: BTreeMap<u16, u64>,
+}
+
+/// Bucket d'histogramme cumulatif exposé
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LatencyBucket {
+    /// Borne supérieure en ms (None = +Inf)
+    pub le_ms: Option<u64>,
+    pub count: u64,
+}
+
+/// Métriques d'une route exposées sur /metrics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteMetrics {
+    pub route: String,
+    pub method: String,
+    pub requests: u64,
+    pub server_errors: u64,
+    pub client_errors: u64,
+    pub avg_latency_ms: f64,
+    pub max_latency_ms: f64,
+    pub latency_buckets: Vec<LatencyBucket>,
+    pub status_codes: BTreeMap<u16, u64>,
+}
+
+/// Résumé pour /system/health
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HttpMetricsSummary {
+    pub requests_total: u64,
+    pub server_errors_total: u64,
+    /// Part des réponses 5xx (0.0 - 1.0)
+    pub error_rate: f64,
+    /// Route (méthode + chemin) à la latence moyenne la plus élevée
+    pub slowest_route: Option<String>,
+    pub slowest_avg_latency_ms: Option<f64>,
+}
+
+/// Registre partagé des métriques HTTP
+#[derive(Clone, Default)]
+pub struct HttpMetrics {
+    routes: Arc<Mutex<HashMap<(String, String), RouteStats>>>,
+}
+
+impl HttpMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enregistre une requête terminée
+    pub fn record(&self, method: &str, route: &str, status: u16, latency: Duration) {
+        let ms = latency.as_secs_f64() * 1000.0;
+        let mut routes = self.routes.lock();
+        let stats = routes.entry((route.to_string(), method.to_string())).or_insert_with(|| RouteStats {
+            buckets: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+            ..RouteStats::default()
+        });
+
+        stats.count += 1;
+        stats.total_ms += ms;
+        stats.max_ms = stats.max_ms.max(ms);
+        match status {
+            500.. => stats.server_errors += 1,
+            400..=499 => stats.client_errors += 1,
+            _ => {}
+        }
+        *stats.status_codes.entry(status).or_insert(0) += 1;
+
+        let bucket = LATENCY_BUCKETS_MS.iter()
+            .position(|bound| ms <= *bound as f64)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        stats.buckets[bucket] += 1;
+    }
+
+    /// Détail par route, trié par chemin puis méthode
+    pub fn snapshot(&self) -> Vec<RouteMetrics> {
+        let routes = self.routes.lock();
+        let mut metrics: Vec<RouteMetrics> = routes.iter()
+            .map(|((route, method), stats)| {
+                let mut cumulative = 0;
+                let latency_buckets = stats.buckets.iter()
+                    .enumerate()
+                    .map(|(i, count)| {
+                        cumulative += count;
+                        LatencyBucket { le_ms: LATENCY_BUCKETS_MS.get(i).copied(), count: cumulative }
+                    })
+                    .collect();
+                RouteMetrics {
+                    route: route.clone(),
+                    method: method.clone(),
+                    requests: stats.count,
+                    server_errors: stats.server_errors,
+                    client_errors: stats.client_errors,
+                    avg_latency_ms: stats.total_ms / stats.count.max(1) as f64,
+                    max_latency_ms: stats.max_ms,
+                    latency_buckets,
+                    status_codes: stats.status_codes.clone(),
+                }
+            })
+            .collect();
+        metrics.sort_by(|a, b| (&a.route, &a.method).cmp(&(&b.route, &b.method)));
+        metrics
+    }
+
+    pub fn summary(&self) -> HttpMetricsSummary {
+        let snapshot = self.snapshot();
+        let requests_total: u64 = snapshot.iter().map(|r| r.requests).sum();
+        let server_errors_total: u64 = snapshot.iter().map(|r| r.server_errors).sum();
+        let slowest = snapshot.iter().max_by(|a, b| a.avg_latency_ms.total_cmp(&b.avg_latency_ms));
+
+        HttpMetricsSummary {
+            requests_total,
+            server_errors_total,
+            error_rate: if requests_total == 0 { 0.0 } else { server_errors_total as f64 / requests_total as f64 },
+            slowest_route: slowest.map(|r| format!("{} {}", r.method, r.route)),
+            slowest_avg_latency_ms: slowest.map(|r| r.avg_latency_ms),
+        }
+    }
+}
+
+/// Middleware : mesure la latence et le statut de chaque requête routée
+pub async fn track_http_metrics(State(metrics): State<HttpMetrics>, req: Request, next: Next) -> Response {
+    let route = req.extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = req.method().to_string();
+
+    let start = Instant::now();
+    let response = next.run(req).await;
+    metrics.record(&method, &route, response.status().as_u16(), start.elapsed());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_record_updates_buckets_and_status_codes() {
+        let metrics = HttpMetrics::new();
+        metrics.record("GET", "/ports/{port_name}", 200, Duration::from_millis(3));
+        metrics.record("GET", "/ports/{port_name}", 200, Duration::from_millis(40));
+        metrics.record("GET", "/ports/{port_name}", 503, Duration::from_secs(10));
+
+        let snapshot = metrics.snapshot();
+        let route = &snapshot[0];
+        assert_eq!(route.requests, 3);
+        assert_eq!(route.server_errors, 1);
+        assert_eq!(route.status_codes.get(&200), Some(&2));
+
+        let bucket = |le: Option<u64>| route.latency_buckets.iter().find(|b| b.le_ms == le).unwrap().count;
+        assert_eq!(bucket(Some(5)), 1);
+        assert_eq!(bucket(Some(25)), 1);
+        assert_eq!(bucket(Some(50)), 2);
+        assert_eq!(bucket(Some(5000)), 2);
+        assert_eq!(bucket(None), 3);
+
+        let summary = metrics.summary();
+        assert_eq!(summary.requests_total, 3);
+        assert!((summary.error_rate - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(summary.slowest_route.as_deref(), Some("GET /ports/{port_name}"));
+    }
+
+    #[tokio::test]
+    async fn test_middleware_records_matched_route() {
+        let metrics = HttpMetrics::new();
+        let app = Router::new()
+            .route("/items/{id}", get(|| async { StatusCode::NOT_FOUND }))
+            .layer(middleware::from_fn_with_state(metrics.clone(), track_http_metrics));
+
+        let request = Request::builder().uri("/items/42").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].route, "/items/{id}");
+        assert_eq!(snapshot[0].method, "GET");
+        assert_eq!(snapshot[0].client_errors, 1);
+        assert_eq!(snapshot[0].latency_buckets.last().unwrap().count, 1);
+    }
+}
@@ -20,6 +20,27 @@ mod ports;
 mod plugins;
 mod notes_bridge;
 mod agents;
+mod commands;
+mod http_metrics;
+mod webhooks;
+mod mqtt_publish;
+mod flapping;
+mod persistence;
+mod plugin_routes;
+mod plugin_config;
+mod plugin_health;
+mod plugin_logs;
+mod rate_limit;
+mod availability;
+mod self_heal;
+mod transactions;
+mod outbox;
+mod content_negotiation;
+mod heartbeat_versions;
+mod registry_integrity;
+mod command_output;
+mod dead_letter;
+mod shutdown;
 
 use crate::models::HostsMap;
 use crate::state::{new_state, Shared};
@@ -31,6 +52,7 @@ use crate::ports::create_default_ports;
 use crate::plugins::PluginManager;
 use crate::notes_bridge::{NotesBridge, SharedNotesBridge};
 use crate::agents::{AgentRegistry, SharedAgentRegistry};
+use crate::plugin_routes::{PluginRouteRegistry, SharedPluginRoutes};
 
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -42,13 +64,28 @@ async fn main() {
     // Charger les variables d'environnement depuis .env (si présent)
     dotenvy::dotenv().ok(); // Ok si .env n'existe pas
     
+    // Sous-commandes d'administration (exécutées puis sortie, sans démarrer le kernel)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate-port") {
+        match crate::ports::migrate::run_migrate_command(&args[1..]) {
+            Ok(report) => {
+                println!("[ports] migration done: {}", serde_json::to_string(&report).unwrap_or_default());
+                return;
+            }
+            Err(e) => {
+                eprintln!("[ports] migration failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    
     // maps et conf partagées
     let states = new_state::<HostsMap>(HashMap::new());
     let cfg_loaded: HostsConfig = load_config().await;
     let cfg: Shared<HostsConfig> = new_state(cfg_loaded.clone());
     
     // chargement des contrats MQTT
-    let contracts = match ContractRegistry::load_contracts_from_dir("../contracts/mqtt").await {
+    let mut contracts = match ContractRegistry::load_contracts_from_dir("../contracts/mqtt").await {
         Ok(registry) => {
             println!("[kernel] loaded {} contracts", registry.list_contracts().len());
             registry
@@ -67,7 +104,7 @@ async fn main() {
         eprintln!("[kernel] warning: failed to create data dir: {}", e);
     });
     
-    let ports = match create_default_ports("./data") {
+    let ports = match create_default_ports("./data", &cfg_loaded.ports) {
         Ok(registry) => {
             println!("[kernel] initialized {} data ports", registry.list_ports().len());
             new_state(registry)
@@ -83,10 +120,18 @@ async fn main() {
         eprintln!("[kernel] warning: failed to create plugins dir: {}", e);
     });
     
-    let mut plugin_manager = PluginManager::new("./plugins");
+    let mut plugin_manager = PluginManager::new("./plugins")
+        .with_log_capture(&cfg_loaded.plugin_logs)
+        .with_startup(&cfg_loaded.plugin_startup);
     match plugin_manager.discover_plugins().await {
         Ok(discovered) => {
             println!("[kernel] discovered {} plugins", discovered.len());
+            // contrats livrés avec les plugins (plugins/{name}.contracts/)
+            for name in &discovered {
+                if let Some((dir, declared)) = plugin_manager.bundled_contracts(name) {
+                    contracts.register_plugin_contracts(name, &dir, &declared).await;
+                }
+            }
             plugin_manager.auto_start_plugins();
         }
         Err(e) => {
@@ -96,35 +141,74 @@ async fn main() {
     let plugins = new_state(plugin_manager);
 
     // Client MQTT partagé pour le kernel et bridge notes
-    let mqtt_client = match mqtt::create_mqtt_client(&cfg_loaded) {
-        Ok(client) => client,
-        Err(e) => {
-            eprintln!("[kernel] failed to create MQTT client: {}", e);
-            std::process::exit(1);
-        }
-    };
+    // Outbox : commandes et santé émises broker coupé, rejouées à la reconnexion
+    let outbox = Arc::new(outbox::Outbox::open(&cfg_loaded.outbox.path, cfg_loaded.outbox.max_messages));
+
+    // Broker absent au boot : reconnexion en arrière-plan, l'API démarre sans l'attendre
+    let mqtt_client = mqtt::create_mqtt_client(&cfg_loaded, outbox.clone());
+
+    // Sections de heartbeat attendues des agents (retenue : les agents connectés plus tard la reçoivent)
+    if let Err(e) = agents::publish_heartbeat_sections(&mqtt_client, None, cfg_loaded.heartbeat_sections) {
+        eprintln!("[kernel] failed to broadcast heartbeat sections: {}", e);
+    }
 
     // Bridge notes pour API /ports/memo → plugin via MQTT  
     let notes_bridge: Option<SharedNotesBridge> = Some(Arc::new(NotesBridge::new(mqtt_client.clone())));
 
     // Agent registry avec persistance et MQTT
-    let mut agent_registry = AgentRegistry::new("./data/agents.json").with_mqtt_client(mqtt_client.clone());
+    let mut agent_registry = AgentRegistry::new("./data/agents.json")
+        .with_mqtt_client(mqtt_client.clone())
+        .with_outbox(outbox.clone())
+        .with_contracts(contracts.clone())
+        .with_duplicate_policy(cfg_loaded.duplicate_agent_policy)
+        .with_flapping(cfg_loaded.flapping)
+        .with_clock_skew_threshold(cfg_loaded.agent_monitoring.clock_skew_threshold_secs)
+        .with_command_cache(cfg_loaded.command_cache.clone())
+        .with_command_results(cfg_loaded.command_results.clone())
+        .with_command_rate_limit(cfg_loaded.command_rate_limit.clone())
+        .with_availability_log("./data/agent_availability.jsonl")
+        .with_persist_format(cfg_loaded.persistence_format);
     if let Err(e) = agent_registry.load_agents().await {
         eprintln!("[kernel] failed to load agents: {}", e);
     }
     let agents: SharedAgentRegistry = Arc::new(agent_registry);
 
+    // routes HTTP annoncées par les plugins (proxy /plugins/{name}/... via MQTT)
+    let plugin_routes: SharedPluginRoutes = Arc::new(PluginRouteRegistry::default());
+
+    // messages MQTT en échec, rejouables via POST /deadletter/{id}/replay
+    let dead_letters = dead_letter::DeadLetterStore::new();
+
     // MQTT remplit les states + agents
-    mqtt::spawn_mqtt_listener(states.clone(), cfg.clone(), notes_bridge.clone(), Some(agents.clone()), Some(health_tracker.clone()));
+    mqtt::spawn_mqtt_listener(states.clone(), cfg.clone(), notes_bridge.clone(), Some(agents.clone()), Some(health_tracker.clone()), Some(plugin_routes.clone()), Some(contracts.clone()), Some(dead_letters.clone()));
+
+    // relais MQTT → HTTP vers les webhooks configurés
+    webhooks::spawn_webhook_relay(cfg.clone(), health_tracker.clone());
 
     // démarre le healthcheck périodique des plugins
     plugins::spawn_plugin_health_monitor(plugins.clone());
     
-    // démarre le monitoring des agents (timeout 2min)
-    AgentRegistry::start_agent_monitoring(agents.clone(), 2);
+    // démarre le monitoring des agents (intervalle, timeout et grâce configurables)
+    AgentRegistry::start_agent_monitoring(agents.clone(), cfg_loaded.agent_monitoring);
+
+    // contrôle d'intégrité du registre (doublons, entrées invalides), dès le chargement
+    AgentRegistry::start_integrity_checks(agents.clone(), cfg_loaded.agent_monitoring.integrity_check_interval_secs);
+
+    // expire les commandes agents restées sans réponse
+    commands::spawn_command_sweeper(agents.commands().clone());
+
+    // remédiations configurées (section self_heal) : plugins en échec, coupure MQTT
+    self_heal::spawn_self_heal_monitor(cfg.clone(), health_tracker.clone(), self_heal::KernelHealer {
+        plugins: plugins.clone(),
+        commands: agents.commands().clone(),
+        health: health_tracker.clone(),
+    });
 
     // démarre la publication auto du health
-    health_tracker.spawn_health_publisher(cfg.clone(), contracts.clone(), agents.clone(), plugins.clone());
+    health_tracker.spawn_health_publisher(cfg.clone(), contracts.clone(), agents.clone(), plugins.clone(), Some(outbox));
+
+    // gardés pour l'arrêt propre (l'état Axum est abandonné avec le serveur)
+    let (shutdown_plugins, shutdown_agents) = (plugins.clone(), agents.clone());
 
     // fabrique l'état unique pour Axum
     let app_state = AppState { 
@@ -135,14 +219,36 @@ async fn main() {
         ports, 
         plugins,
         notes_bridge,
-        agents
+        agents,
+        mqtt_publisher: Some(Arc::new(mqtt_client.clone())),
+        plugin_routes,
+        dead_letters,
     };
 
     // HTTP
     let app = http::build_router(app_state);
 
-    let addr = SocketAddr::from(([0,0,0,0], 8080));
+    // port HTTP (8080 par défaut, SYMBION_HTTP_PORT pour les tests d'intégration ou plusieurs kernels)
+    let port = std::env::var("SYMBION_HTTP_PORT").ok()
+        .and_then(|p| p.parse::<u16>().ok())
+        .unwrap_or(8080);
+    let addr = SocketAddr::from(([0,0,0,0], port));
+    let auth = http::ApiAuth::from_env();
+    if auth.dev_mode_active() {
+        // Bannière volontairement voyante : ce mode ne doit jamais rester actif par erreur
+        eprintln!("[kernel] ================================================================");
+        eprintln!("[kernel] SECURITY: DEV MODE ACTIVE - loopback requests accepted without API key");
+        eprintln!("[kernel] SYMBION_API_KEY not set; remote requests are still denied");
+        eprintln!("[kernel] never set SYMBION_DEV_MODE in production");
+        eprintln!("[kernel] ================================================================");
+    } else if auth.dev_mode {
+        println!("[kernel] SYMBION_DEV_MODE ignored: SYMBION_API_KEY is set");
+    }
     println!("[kernel] listening on http://{addr}");
     let listener = TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // SIGTERM / SIGINT : fin du service HTTP, arrêt des plugins et sauvegarde des agents (bornés)
+    shutdown::serve_until(listener, app, shutdown::shutdown_signal(), shutdown::HTTP_DRAIN_TIMEOUT).await.unwrap();
+    let clean = shutdown::shutdown_kernel(shutdown_plugins, shutdown_agents, shutdown::SHUTDOWN_TIMEOUT).await;
+    // Sortie immédiate : ni un plugin bloqué ni une tâche encore active ne retiennent le processus
+    std::process::exit(if clean { 0 } else { 1 });
 }
@@ -18,6 +18,10 @@ pub struct HostState {
     pub cpu: Option<f32>,
     pub ram: Option<f32>,
     pub ip: Option<String>,
+    /// MAC pour Wake-on-LAN (heartbeat, sinon config host)
+    pub mac: Option<String>,
+    /// Adresse de broadcast WOL (heartbeat, sinon hint de la config)
+    pub broadcast: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -31,6 +35,12 @@ pub struct HeartbeatIn {
 #[derive(Debug, Deserialize)]
 pub struct Metrics { pub cpu: f32, pub ram: f32 }
 #[derive(Debug, Deserialize)]
-pub struct NetInfo { pub ip: String }
+pub struct NetInfo {
+    pub ip: String,
+    #[serde(default)]
+    pub mac: Option<String>,
+    #[serde(default)]
+    pub broadcast: Option<String>,
+}
 
 pub type HostsMap = HashMap<String, HostState>;
@@ -5,6 +5,10 @@
  * Maintient l'état temps réel des machines connectées au système.
  * 
  * FONCTIONNEMENT : Client MQTT async, parsing JSON, mise à jour thread-safe des états.
+ * Les messages passent par leur contrat (defaults opt-in) avant la désérialisation typée.
+ * Abonnements rejoués à chaque connexion ; reconnexion forçable par le self-heal.
+ * Client bridge : état de connexion reporté sur l'outbox (publications rejouées au ConnAck).
+ * Message en échec (JSON invalide, handler en erreur) : conservé en dead-letter pour rejeu.
  * UTILITÉ : Télémétrie centralisée, monitoring distribué, resilience réseau.
  */
 
@@ -12,36 +16,204 @@ use crate::models::{HeartbeatIn, HostState, HostsMap};
 use crate::state::Shared;
 use crate::config::HostsConfig;
 use crate::notes_bridge::{SharedNotesBridge, NoteResponse};
-use crate::agents::{SharedAgentRegistry, AgentRegistrationMessage, AgentHeartbeatMessage};
+use crate::agents::{SharedAgentRegistry, AgentRegistrationMessage};
+use crate::heartbeat_versions;
+use crate::command_output::AgentOutputChunk;
+use crate::commands::AgentCommandResponse;
+use crate::plugin_routes::{SharedPluginRoutes, RouteAnnouncement, PluginHttpResponse};
+use crate::plugin_health::PluginHealthReport;
+use crate::contracts::ContractRegistry;
+use crate::outbox::SharedOutbox;
+use crate::dead_letter::DeadLetterStore;
 use rumqttc::{AsyncClient, Event, MqttOptions, QoS};
+use serde::de::DeserializeOwned;
+use std::time::Duration;
 use time::OffsetDateTime;
 use tokio::task;
 
+/// Nom du client listener dans l'inspection des abonnements
+pub const LISTENER_CLIENT: &str = "listener";
+
+/// Désérialise un payload après application des defaults de son contrat
+fn decode<T: DeserializeOwned>(contracts: Option<&ContractRegistry>, topic: &str, payload: &[u8]) -> serde_json::Result<T> {
+    let mut message: serde_json::Value = serde_json::from_slice(payload)?;
+    if let Some(contracts) = contracts {
+        contracts.apply_defaults(topic, &mut message);
+    }
+    serde_json::from_value(message)
+}
+
+/// Backoff de reconnexion du client bridge (exponentiel plafonné, sans limite de tentatives)
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectRetry {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+/// Broker absent : une tentative toutes les 30 s au plus, indéfiniment
+pub const CONNECT_RETRY: ConnectRetry = ConnectRetry {
+    initial_backoff: Duration::from_secs(1),
+    max_backoff: Duration::from_secs(30),
+};
+
+/// Échecs consécutifs de connexion et délai avant la prochaine tentative ; remis à zéro au ConnAck
+#[derive(Debug)]
+struct Backoff {
+    retry: ConnectRetry,
+    failures: u32,
+    next: Duration,
+}
+
+impl Backoff {
+    fn new(retry: ConnectRetry) -> Self {
+        Self { retry, failures: 0, next: retry.initial_backoff }
+    }
+
+    /// Enregistre un échec : (numéro de tentative, délai à attendre)
+    fn failed(&mut self) -> (u32, Duration) {
+        self.failures += 1;
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.retry.max_backoff);
+        (self.failures, delay)
+    }
+
+    /// Connexion établie : nombre d'échecs qui l'ont précédée
+    fn connected(&mut self) -> u32 {
+        let failures = self.failures;
+        *self = Self::new(self.retry);
+        failures
+    }
+}
+
 /// Crée un client MQTT configuré pour le kernel avec son eventloop
-pub fn create_mqtt_client(config: &HostsConfig) -> Result<AsyncClient, Box<dyn std::error::Error + Send + Sync>> {
+/// Ne bloque pas sur le broker : l'eventloop reconnecte en arrière-plan (CONNECT_RETRY) et
+/// l'outbox garde les publications jusqu'au premier ConnAck, un broker pas encore démarré
+/// au boot ne retarde ni ne tue le kernel
+pub fn create_mqtt_client(config: &HostsConfig, outbox: SharedOutbox) -> AsyncClient {
     let mqtt_cfg = config.mqtt.clone().unwrap_or_else(|| crate::config::MqttConf { 
         host: "localhost".into(), 
         port: 1883 
     });
     
     let mut opts = MqttOptions::new("symbion-kernel-bridge", &mqtt_cfg.host, mqtt_cfg.port);
-    opts.set_keep_alive(std::time::Duration::from_secs(15));
+    opts.set_keep_alive(Duration::from_secs(15));
     let (client, mut eventloop) = AsyncClient::new(opts, 10);
     
     // Lancer l'eventloop du client bridge en arrière-plan
+    let bridge = client.clone();
     tokio::spawn(async move {
+        let mut backoff = Backoff::new(CONNECT_RETRY);
         loop {
-            if let Err(e) = eventloop.poll().await {
-                eprintln!("[mqtt-bridge] eventloop error: {:?}", e);
-                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            match eventloop.poll().await {
+                Ok(Event::Incoming(rumqttc::Incoming::ConnAck(_))) => {
+                    let failures = backoff.connected();
+                    if failures > 0 {
+                        println!("[mqtt-bridge] broker reachable after {} failed attempts", failures);
+                    }
+                    outbox.mark_connected(&bridge);
+                }
+                Ok(_) => {
+                    // Vidage interrompu par un canal plein : reprise au fil des événements
+                    outbox.flush_pending(&bridge);
+                }
+                Err(e) => {
+                    let (attempt, delay) = backoff.failed();
+                    eprintln!("[mqtt-bridge] broker unreachable (attempt {}): {:?}, retrying in {:?}", attempt, e, delay);
+                    outbox.mark_disconnected();
+                    tokio::time::sleep(delay).await;
+                }
             }
         }
     });
     
-    Ok(client)
+    client
+}
+
+/// Destinataires des messages reçus par le listener
+struct Dispatch {
+    states: Shared<HostsMap>,
+    config: Shared<HostsConfig>,
+    notes_bridge: Option<SharedNotesBridge>,
+    agents: Option<SharedAgentRegistry>,
+    health_tracker: Option<crate::health::HealthTracker>,
+    plugin_routes: Option<SharedPluginRoutes>,
+    contracts: Option<ContractRegistry>,
 }
 
-pub fn spawn_mqtt_listener(states: Shared<HostsMap>, config: Shared<HostsConfig>, notes_bridge: Option<SharedNotesBridge>, agents: Option<SharedAgentRegistry>, health_tracker: Option<crate::health::HealthTracker>) {
+impl Dispatch {
+    /// Traite un message reçu ; Err(raison) si le message n'a pas pu être traité (dead-letter).
+    /// Un topic sans destinataire configuré est ignoré.
+    async fn handle(&self, topic: &str, payload: &[u8]) -> Result<(), String> {
+        let contracts = self.contracts.as_ref();
+        if topic == symbion_topics::hosts_heartbeat() {
+            let hb = decode::<HeartbeatIn>(contracts, topic, payload).map_err(|e| format!("heartbeat JSON invalide: {}", e))?;
+            // MAC/broadcast : le heartbeat prime, la config host complète
+            let host_conf = self.config.lock().hosts.get(&hb.host_id).cloned();
+            let st = HostState {
+                mac: hb.net.mac.or_else(|| host_conf.as_ref().map(|h| h.mac.clone())),
+                broadcast: hb.net.broadcast.or_else(|| host_conf.and_then(|h| h.hint)),
+                host_id: hb.host_id,
+                last_seen: OffsetDateTime::now_utc(),
+                cpu: Some(hb.metrics.cpu),
+                ram: Some(hb.metrics.ram),
+                ip: Some(hb.net.ip),
+            };
+            self.states.lock().insert(st.host_id.clone(), st);
+        } else if topic == symbion_topics::notes_response() {
+            if let Some(ref bridge) = self.notes_bridge {
+                let response = decode::<NoteResponse>(contracts, topic, payload).map_err(|e| format!("notes response JSON invalide: {}", e))?;
+                bridge.handle_response(response);
+            }
+        } else if topic == symbion_topics::agents_registration() {
+            if let Some(ref agent_registry) = self.agents {
+                let registration = decode::<AgentRegistrationMessage>(contracts, topic, payload)
+                    .map_err(|e| format!("agent registration JSON invalide: {}", e))?;
+                agent_registry.handle_agent_registration(registration).await
+                    .map_err(|e| format!("failed to handle agent registration: {}", e))?;
+            }
+        } else if topic == symbion_topics::agents_heartbeat() {
+            if let Some(ref agent_registry) = self.agents {
+                let agent_id = serde_json::from_slice::<serde_json::Value>(payload).ok()
+                    .and_then(|v| v.get("agent_id").and_then(|id| id.as_str()).map(str::to_string))
+                    .unwrap_or_default();
+                let version = agent_registry.heartbeat_version(&agent_id).await;
+                let heartbeat = heartbeat_versions::parse_heartbeat(contracts, topic, &version, payload)
+                    .map_err(|e| format!("agent heartbeat {} JSON invalide: {}", version, e))?;
+                agent_registry.handle_agent_heartbeat(heartbeat).await
+                    .map_err(|e| format!("failed to handle agent heartbeat: {}", e))?;
+            }
+        } else if topic == symbion_topics::agents_response() {
+            if let Some(ref agent_registry) = self.agents {
+                let response = decode::<AgentCommandResponse>(contracts, topic, payload).map_err(|e| format!("agent response JSON invalide: {}", e))?;
+                agent_registry.handle_command_response(response);
+            }
+        } else if topic == symbion_topics::agents_output() {
+            if let Some(ref agent_registry) = self.agents {
+                let chunk = decode::<AgentOutputChunk>(contracts, topic, payload).map_err(|e| format!("agent output JSON invalide: {}", e))?;
+                agent_registry.commands().record_output(chunk);
+            }
+        } else if topic == crate::plugin_routes::ROUTES_TOPIC {
+            if let Some(ref routes) = self.plugin_routes {
+                let announcement = decode::<RouteAnnouncement>(contracts, topic, payload).map_err(|e| format!("plugin routes JSON invalide: {}", e))?;
+                routes.handle_announcement(announcement);
+            }
+        } else if topic == crate::plugin_routes::RESPONSE_TOPIC {
+            if let Some(ref routes) = self.plugin_routes {
+                let response = decode::<PluginHttpResponse>(contracts, topic, payload).map_err(|e| format!("plugin http response JSON invalide: {}", e))?;
+                routes.handle_response(response);
+            }
+        } else if topic == crate::plugin_health::HEALTH_TOPIC {
+            if let Some(ref tracker) = self.health_tracker {
+                let report = decode::<PluginHealthReport>(contracts, topic, payload).map_err(|e| format!("plugin health JSON invalide: {}", e))?;
+                tracker.plugin_health().record(report);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_mqtt_listener(states: Shared<HostsMap>, config: Shared<HostsConfig>, notes_bridge: Option<SharedNotesBridge>, agents: Option<SharedAgentRegistry>, health_tracker: Option<crate::health::HealthTracker>, plugin_routes: Option<SharedPluginRoutes>, contracts: Option<ContractRegistry>, dead_letters: Option<DeadLetterStore>) {
     task::spawn(async move {
         let cfg = config.lock().clone();
         let mqtt_cfg = cfg.mqtt.unwrap_or_else(|| crate::config::MqttConf { 
@@ -53,97 +225,170 @@ pub fn spawn_mqtt_listener(states: Shared<HostsMap>, config: Shared<HostsConfig>
         opts.set_keep_alive(std::time::Duration::from_secs(15));
         let (client, mut eventloop) = AsyncClient::new(opts, 10);
         
-        if let Err(e) = client.subscribe("symbion/hosts/heartbeat@v2", QoS::AtLeastOnce).await {
-            eprintln!("[kernel] subscribe MQTT failed: {e:?}");
-            return;
-        }
-        
-        // S'abonner aux réponses des notes si bridge disponible
-        if notes_bridge.is_some() {
-            if let Err(e) = client.subscribe("symbion/notes/response@v1", QoS::AtLeastOnce).await {
-                eprintln!("[kernel] subscribe notes responses failed: {e:?}");
+        // Abonnements réussis visibles dans GET /mqtt/subscriptions
+        let note_subscription = |topic: &str| {
+            if let Some(ref tracker) = health_tracker {
+                tracker.record_subscription(LISTENER_CLIENT, topic);
             }
-        }
+        };
 
-        // S'abonner aux événements agents si registry disponible
+        let mut topics = vec![symbion_topics::hosts_heartbeat()];
+        // Réponses des notes si bridge disponible
+        if notes_bridge.is_some() {
+            topics.push(symbion_topics::notes_response());
+        }
+        // Événements agents si registry disponible
         if agents.is_some() {
-            if let Err(e) = client.subscribe("symbion/agents/registration@v1", QoS::AtLeastOnce).await {
-                eprintln!("[kernel] subscribe agents registration failed: {e:?}");
-            }
-            if let Err(e) = client.subscribe("symbion/agents/heartbeat@v1", QoS::AtLeastOnce).await {
-                eprintln!("[kernel] subscribe agents heartbeat failed: {e:?}");
-            }
+            topics.extend([symbion_topics::agents_registration(), symbion_topics::agents_heartbeat(), symbion_topics::agents_response(), symbion_topics::agents_output()]);
         }
+        // Annonces de routes et réponses HTTP des plugins
+        if plugin_routes.is_some() {
+            topics.extend([crate::plugin_routes::ROUTES_TOPIC, crate::plugin_routes::RESPONSE_TOPIC]);
+        }
+        // Santé métier des plugins, agrégée dans /system/health
+        if health_tracker.is_some() {
+            topics.push(crate::plugin_health::HEALTH_TOPIC);
+        }
+        let dispatch = Dispatch {
+            states,
+            config,
+            notes_bridge,
+            agents,
+            health_tracker: health_tracker.clone(),
+            plugin_routes,
+            contracts,
+        };
 
         loop {
-            match eventloop.poll().await {
+            // Reconnexion forcée (self-heal) : la session est abandonnée, le prochain poll reconnecte
+            let polled = match health_tracker {
+                Some(ref tracker) => tokio::select! {
+                    polled = eventloop.poll() => polled,
+                    _ = tracker.mqtt_reconnect_requested() => {
+                        println!("[kernel] MQTT listener reconnection requested");
+                        tracker.record_client_status(LISTENER_CLIENT, "reconnecting");
+                        eventloop.clean();
+                        continue;
+                    }
+                },
+                None => eventloop.poll().await,
+            };
+            match polled {
+                // (Ré)abonnement à chaque connexion : une session propre ne garde pas les abonnements
+                Ok(Event::Incoming(rumqttc::Incoming::ConnAck(_))) => {
+                    if let Some(ref tracker) = health_tracker {
+                        tracker.record_client_status(LISTENER_CLIENT, "connected");
+                    }
+                    for topic in &topics {
+                        if let Err(e) = client.try_subscribe(*topic, QoS::AtLeastOnce) {
+                            eprintln!("[kernel] subscribe {topic} failed: {e:?}");
+                        } else {
+                            note_subscription(topic);
+                        }
+                    }
+                }
                 Ok(Event::Incoming(rumqttc::Incoming::Publish(p))) => {
                     // Enregistrer l'activité MQTT
                     if let Some(ref tracker) = health_tracker {
                         tracker.record_mqtt_message();
+                        tracker.record_topic_message(LISTENER_CLIENT, &p.topic);
                     }
                     
-                    if p.topic == "symbion/hosts/heartbeat@v2" {
-                    if let Ok(txt) = String::from_utf8(p.payload.to_vec()) {
-                        match serde_json::from_str::<HeartbeatIn>(&txt) {
-                            Ok(hb) => {
-                                let st = HostState {
-                                    host_id: hb.host_id,
-                                    last_seen: OffsetDateTime::now_utc(),
-                                    cpu: Some(hb.metrics.cpu),
-                                    ram: Some(hb.metrics.ram),
-                                    ip: Some(hb.net.ip),
-                                };
-                                states.lock().insert(st.host_id.clone(), st);
-                            }
-                            Err(_) => eprintln!("[kernel] heartbeat JSON invalide: {txt}"),
-                        }
-                    }
-                } else if p.topic == "symbion/notes/response@v1" {
-                    if let Some(ref bridge) = notes_bridge {
-                        if let Ok(txt) = String::from_utf8(p.payload.to_vec()) {
-                            match serde_json::from_str::<NoteResponse>(&txt) {
-                                Ok(response) => {
-                                    bridge.handle_response(response);
-                                }
-                                Err(_) => eprintln!("[kernel] notes response JSON invalide: {txt}"),
-                            }
-                        }
-                    }
-                } else if p.topic == "symbion/agents/registration@v1" {
-                    if let Some(ref agent_registry) = agents {
-                        if let Ok(txt) = String::from_utf8(p.payload.to_vec()) {
-                            match serde_json::from_str::<AgentRegistrationMessage>(&txt) {
-                                Ok(registration) => {
-                                    if let Err(e) = agent_registry.handle_agent_registration(registration).await {
-                                        eprintln!("[kernel] failed to handle agent registration: {}", e);
-                                    }
-                                }
-                                Err(e) => eprintln!("[kernel] agent registration JSON invalide: {txt}, error: {}", e),
-                            }
-                        }
-                    }
-                } else if p.topic == "symbion/agents/heartbeat@v1" {
-                    if let Some(ref agent_registry) = agents {
-                        if let Ok(txt) = String::from_utf8(p.payload.to_vec()) {
-                            match serde_json::from_str::<AgentHeartbeatMessage>(&txt) {
-                                Ok(heartbeat) => {
-                                    if let Err(e) = agent_registry.handle_agent_heartbeat(heartbeat).await {
-                                        eprintln!("[kernel] failed to handle agent heartbeat: {}", e);
-                                    }
-                                }
-                                Err(e) => eprintln!("[kernel] agent heartbeat JSON invalide: {txt}, error: {}", e),
-                            }
+                    if let Err(reason) = dispatch.handle(&p.topic, &p.payload).await {
+                        eprintln!("[kernel] {} ({}), dead-lettered", reason, p.topic);
+                        if let Some(ref store) = dead_letters {
+                            store.record(&p.topic, &p.payload, &reason);
                         }
                     }
                 }
-                }
                 Ok(_) => {}
                 Err(e) => {
                     eprintln!("[kernel] MQTT erreur: {:?}", e);
+                    if let Some(ref tracker) = health_tracker {
+                        tracker.record_client_status(LISTENER_CLIENT, "disconnected");
+                    }
                     tokio::time::sleep(std::time::Duration::from_secs(2)).await;
                 }
             }
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST_RETRY: ConnectRetry = ConnectRetry {
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(4),
+    };
+
+    #[test]
+    fn test_backoff_grows_until_cap_and_resets_on_connack() {
+        let mut backoff = Backoff::new(FAST_RETRY);
+        let delays: Vec<_> = (0..5).map(|_| backoff.failed()).collect();
+        assert_eq!(delays, vec![
+            (1, Duration::from_millis(1)),
+            (2, Duration::from_millis(2)),
+            (3, Duration::from_millis(4)),
+            (4, Duration::from_millis(4)),
+            (5, Duration::from_millis(4)),
+        ]);
+        // Jamais d'abandon : le broker revenu, le compteur repart de zéro
+        assert_eq!(backoff.connected(), 5);
+        assert_eq!(backoff.failed(), (1, Duration::from_millis(1)));
+    }
+
+    #[tokio::test]
+    async fn test_client_is_created_while_broker_is_down() {
+        // Port fermé : la création rend la main sans attendre de ConnAck
+        let config = HostsConfig { mqtt: Some(crate::config::MqttConf { host: "127.0.0.1".into(), port: 1 }), ..Default::default() };
+        let outbox = std::sync::Arc::new(crate::outbox::Outbox::in_memory(10));
+        let client = create_mqtt_client(&config, outbox.clone());
+
+        // Publications gardées par l'outbox jusqu'à la connexion
+        outbox.send(&client, "symbion/test@v1", QoS::AtLeastOnce, false, "{}".into(), None);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!outbox.is_connected());
+        assert_eq!(outbox.pending(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replayed_dead_letter_reaches_the_normal_handler() {
+        let registry = std::sync::Arc::new(crate::agents::AgentRegistry::new(&crate::agents::temp_data_file()));
+        registry.commands().track("cmd-1", "a1b2c3d4e5f6", "run_command", 30);
+        let dispatch = Dispatch {
+            states: crate::state::new_state(HostsMap::new()),
+            config: crate::state::new_state(HostsConfig::default()),
+            notes_bridge: None,
+            agents: Some(registry.clone()),
+            health_tracker: None,
+            plugin_routes: None,
+            contracts: None,
+        };
+        let topic = symbion_topics::agents_response();
+
+        // Réponse d'un agent bogué : agent_id manquant
+        let broken = br#"{"command_id":"cmd-1","status":"success","timestamp":"2025-09-01T10:30:01Z"}"#;
+        let reason = dispatch.handle(topic, broken).await.unwrap_err();
+        let store = DeadLetterStore::new();
+        let id = store.record(topic, broken, &reason);
+        assert!(registry.commands().get("cmd-1").unwrap().is_pending());
+
+        // Rejeu corrigé : republié sur le topic d'origine puis traité comme un message normal
+        let (tx, rx) = flume::bounded(10);
+        let fixed = serde_json::json!({
+            "command_id": "cmd-1",
+            "agent_id": "a1b2c3d4e5f6",
+            "status": "success",
+            "timestamp": "2025-09-01T10:30:01Z"
+        });
+        store.replay(id, Some(&fixed), &AsyncClient::from_senders(tx)).unwrap();
+        let Ok(rumqttc::Request::Publish(publish)) = rx.try_recv() else { panic!("replay not published") };
+        assert_eq!(publish.topic, topic);
+        dispatch.handle(&publish.topic, &publish.payload).await.unwrap();
+        assert_eq!(registry.commands().get("cmd-1").unwrap().status, "success");
+        assert!(store.list().is_empty());
+    }
+
+}
@@ -0,0 +1,210 @@
+/**
+ * MQTT PUBLISH - Injection de messages MQTT depuis l'API HTTP
+ *
+ * RÔLE :
+ * Permet à des systèmes externes de déclencher des flux Symbion en publiant
+ * sur le bus via POST /mqtt/publish (route admin), à travers le client MQTT partagé du kernel.
+ *
+ * FONCTIONNEMENT :
+ * - Corps : { topic, payload, qos?, retain? } (payload string envoyé tel quel, sinon JSON)
+ * - Topic obligatoirement couvert par la liste blanche mqtt_publish_allowlist (filtres + / #)
+ * - Liste blanche vide = aucune publication autorisée
+ * - Wildcards interdits dans le topic publié
+ *
+ * SÉCURITÉ :
+ * 🔒 Route admin (x-admin-key) en plus de la clé API
+ * 🔒 Empêche l'usurpation des topics internes (commandes agents...) non listés
+ */
+
+use crate::webhooks::topic_matches;
+use rumqttc::{AsyncClient, QoS};
+use serde::Deserialize;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Publication MQTT abstraite (client partagé du kernel, mock en test)
+pub trait MqttPublisher: Send + Sync {
+    fn publish(&self, topic: &str, qos: QoS, retain: bool, payload: Vec<u8>) -> Result<(), String>;
+}
+
+impl MqttPublisher for AsyncClient {
+    fn publish(&self, topic: &str, qos: QoS, retain: bool, payload: Vec<u8>) -> Result<(), String> {
+        self.try_publish(topic, qos, retain, payload).map_err(|e| e.to_string())
+    }
+}
+
+pub type SharedMqttPublisher = Arc<dyn MqttPublisher>;
+
+/// Publication vue par `RecordingPublisher`
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Published {
+    pub topic: String,
+    pub qos: QoS,
+    pub retain: bool,
+    pub payload: Vec<u8>,
+}
+
+#[cfg(test)]
+impl Published {
+    pub fn text(&self) -> String {
+        String::from_utf8(self.payload.clone()).unwrap()
+    }
+
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.payload).unwrap()
+    }
+}
+
+/// Réaction d'un pair simulé (plugin, agent) à une publication : (topic, payload)
+#[cfg(test)]
+type PublishHook = Box<dyn Fn(&str, &[u8]) + Send + Sync>;
+
+/// Publisher de test partagé : enregistre les publications acceptées
+/// - `unavailable()` / `set_available` : broker absent, publications refusées
+/// - `on_publish` : un pair simulé réagit à chaque publication acceptée
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct RecordingPublisher {
+    published: parking_lot::Mutex<Vec<Published>>,
+    unavailable: std::sync::atomic::AtomicBool,
+    hook: Option<PublishHook>,
+}
+
+#[cfg(test)]
+impl RecordingPublisher {
+    /// Broker absent jusqu'à `set_available(true)`
+    pub fn unavailable() -> Self {
+        let publisher = Self::default();
+        publisher.set_available(false);
+        publisher
+    }
+
+    pub fn on_publish(hook: impl Fn(&str, &[u8]) + Send + Sync + 'static) -> Self {
+        Self { hook: Some(Box::new(hook)), ..Self::default() }
+    }
+
+    pub fn set_available(&self, available: bool) {
+        self.unavailable.store(!available, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn published(&self) -> Vec<Published> {
+        self.published.lock().clone()
+    }
+
+    pub fn topics(&self) -> Vec<String> {
+        self.published.lock().iter().map(|p| p.topic.clone()).collect()
+    }
+
+    pub fn payloads(&self) -> Vec<String> {
+        self.published.lock().iter().map(Published::text).collect()
+    }
+}
+
+#[cfg(test)]
+impl MqttPublisher for RecordingPublisher {
+    fn publish(&self, topic: &str, qos: QoS, retain: bool, payload: Vec<u8>) -> Result<(), String> {
+        if self.unavailable.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err("broker down".into());
+        }
+        if let Some(hook) = &self.hook {
+            hook(topic, &payload);
+        }
+        self.published.lock().push(Published { topic: topic.to_string(), qos, retain, payload });
+        Ok(())
+    }
+}
+
+/// Requête POST /mqtt/publish
+#[derive(Debug, Deserialize)]
+pub struct PublishRequest {
+    pub topic: String,
+    pub payload: serde_json::Value,
+    #[serde(default)]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PublishError {
+    #[error("invalid topic: {0}")]
+    InvalidTopic(String),
+    #[error("topic not allowed: {0}")]
+    NotAllowed(String),
+    #[error("invalid qos: {0}")]
+    InvalidQos(u8),
+    #[error("publish failed: {0}")]
+    Publish(String),
+}
+
+/// Valide puis publie une requête via le publisher fourni
+pub fn publish_request(publisher: &dyn MqttPublisher, allowlist: &[String], req: &PublishRequest) -> Result<(), PublishError> {
+    if req.topic.is_empty() || req.topic.contains(['+', '#']) {
+        return Err(PublishError::InvalidTopic(req.topic.clone()));
+    }
+    if !allowlist.iter().any(|filter| topic_matches(filter, &req.topic)) {
+        return Err(PublishError::NotAllowed(req.topic.clone()));
+    }
+    let qos = match req.qos {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        2 => QoS::ExactlyOnce,
+        other => return Err(PublishError::InvalidQos(other)),
+    };
+    let payload = match &req.payload {
+        serde_json::Value::String(s) => s.clone().into_bytes(),
+        other => other.to_string().into_bytes(),
+    };
+    publisher.publish(&req.topic, qos, req.retain, payload).map_err(PublishError::Publish)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(topic: &str) -> PublishRequest {
+        PublishRequest { topic: topic.into(), payload: serde_json::json!({"scene": "evening"}), qos: 1, retain: false }
+    }
+
+    #[test]
+    fn test_publish_rejected_outside_allowlist() {
+        let mock = RecordingPublisher::default();
+        let allowlist = vec!["symbion/external/#".to_string()];
+
+        assert_eq!(
+            publish_request(&mock, &allowlist, &request("symbion/agents/command@v1")),
+            Err(PublishError::NotAllowed("symbion/agents/command@v1".into()))
+        );
+        assert!(matches!(publish_request(&mock, &allowlist, &request("symbion/external/#")), Err(PublishError::InvalidTopic(_))));
+        assert!(matches!(publish_request(&mock, &[], &request("symbion/external/lights")), Err(PublishError::NotAllowed(_))));
+
+        let mut bad_qos = request("symbion/external/lights");
+        bad_qos.qos = 3;
+        assert_eq!(publish_request(&mock, &allowlist, &bad_qos), Err(PublishError::InvalidQos(3)));
+        assert!(mock.published().is_empty());
+    }
+
+    #[test]
+    fn test_publish_allowed_topic_reaches_client() {
+        let mock = RecordingPublisher::default();
+        let allowlist = vec!["symbion/external/+".to_string()];
+
+        let mut req = request("symbion/external/lights");
+        req.retain = true;
+        publish_request(&mock, &allowlist, &req).unwrap();
+
+        let mut raw = request("symbion/external/raw");
+        raw.payload = serde_json::json!("on");
+        raw.qos = 0;
+        publish_request(&mock, &allowlist, &raw).unwrap();
+
+        let published = mock.published();
+        assert_eq!(published.len(), 2);
+        assert_eq!(published[0].topic, "symbion/external/lights");
+        assert_eq!(published[0].qos, QoS::AtLeastOnce);
+        assert!(published[0].retain);
+        assert_eq!(published[0].json::<serde_json::Value>(), serde_json::json!({"scene": "evening"}));
+        assert_eq!(published[1].payload, b"on");
+    }
+}
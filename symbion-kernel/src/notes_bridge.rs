@@ -41,6 +41,8 @@ pub struct CreateNoteRequest {
     pub context: Option<String>, 
     pub tags: Option<Vec<String>>,
     pub status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<Vec<String>>,
 }
 
 /// Commandes MQTT envoyées au plugin (identique au plugin)
@@ -68,6 +70,33 @@ pub enum NoteCommand {
         id: String,
         note: CreateNoteRequest 
     },
+    #[serde(rename = "dedup")]
+    Dedup {
+        request_id: String,
+        dry_run: Option<bool>,
+    },
+    #[serde(rename = "stats")]
+    Stats {
+        request_id: String,
+        days: Option<u32>,
+    },
+    #[serde(rename = "backlinks")]
+    Backlinks {
+        request_id: String,
+        id: String,
+    },
+}
+
+/// Paramètres des statistiques (GET /ports/memo/stats?days=30)
+#[derive(Debug, Deserialize)]
+pub struct StatsParams {
+    pub days: Option<u32>,
+}
+
+/// Paramètres de déduplication (POST /ports/memo/dedup?dry_run=true)
+#[derive(Debug, Deserialize)]
+pub struct DedupParams {
+    pub dry_run: Option<bool>,
 }
 
 /// Réponses MQTT du plugin (identique au plugin)
@@ -132,6 +161,9 @@ impl NotesBridge {
             NoteCommand::List { request_id, .. } => request_id.clone(),
             NoteCommand::Delete { request_id, .. } => request_id.clone(),
             NoteCommand::Update { request_id, .. } => request_id.clone(),
+            NoteCommand::Dedup { request_id, .. } => request_id.clone(),
+            NoteCommand::Stats { request_id, .. } => request_id.clone(),
+            NoteCommand::Backlinks { request_id, .. } => request_id.clone(),
         };
         
         // Créer le canal pour la réponse
@@ -143,7 +175,7 @@ impl NotesBridge {
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         
         self.mqtt_client
-            .publish("symbion/notes/command@v1", QoS::AtLeastOnce, false, payload)
+            .publish(symbion_topics::notes_command(), QoS::AtLeastOnce, false, payload)
             .await
             .map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
         
@@ -164,6 +196,11 @@ impl NotesBridge {
     }
 }
 
+/// Erreur du plugin pour un lien vers une note inexistante
+fn is_dangling_link_error(error: &str) -> bool {
+    error.starts_with("Linked note not found")
+}
+
 /// Bridge state partagé dans Axum
 pub type SharedNotesBridge = Arc<NotesBridge>;
 
@@ -222,8 +259,12 @@ pub async fn create_note_endpoint(
     match bridge.send_command(command).await? {
         NoteResponse::Success { data, .. } => Ok(Json(data)),
         NoteResponse::Error { error, .. } => {
-            eprintln!("[notes-bridge] create error: {}", error);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            if is_dangling_link_error(&error) {
+                Err(StatusCode::BAD_REQUEST)
+            } else {
+                eprintln!("[notes-bridge] create error: {}", error);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
         }
     }
 }
@@ -272,10 +313,79 @@ pub async fn update_note_endpoint(
         NoteResponse::Error { error, .. } => {
             if error == "Note not found" {
                 Err(StatusCode::NOT_FOUND)
+            } else if is_dangling_link_error(&error) {
+                Err(StatusCode::BAD_REQUEST)
             } else {
                 eprintln!("[notes-bridge] update error: {}", error);
                 Err(StatusCode::INTERNAL_SERVER_ERROR)
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// POST /ports/memo/dedup - Fusionne les notes au contenu identique
+pub async fn dedup_notes_endpoint(
+    State(bridge): State<SharedNotesBridge>,
+    Query(params): Query<DedupParams>,
+) -> Result<Json<Value>, StatusCode> {
+    let request_id = Uuid::new_v4().to_string();
+    
+    let command = NoteCommand::Dedup {
+        request_id,
+        dry_run: params.dry_run,
+    };
+    
+    match bridge.send_command(command).await? {
+        NoteResponse::Success { data, .. } => Ok(Json(data)),
+        NoteResponse::Error { error, .. } => {
+            eprintln!("[notes-bridge] dedup error: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// GET /ports/memo/stats - Statistiques des notes (totaux, créations par jour)
+pub async fn stats_notes_endpoint(
+    State(bridge): State<SharedNotesBridge>,
+    Query(params): Query<StatsParams>,
+) -> Result<Json<Value>, StatusCode> {
+    let request_id = Uuid::new_v4().to_string();
+    
+    let command = NoteCommand::Stats {
+        request_id,
+        days: params.days,
+    };
+    
+    match bridge.send_command(command).await? {
+        NoteResponse::Success { data, .. } => Ok(Json(data)),
+        NoteResponse::Error { error, .. } => {
+            eprintln!("[notes-bridge] stats error: {}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// GET /ports/memo/{id}/backlinks - Notes qui référencent la note donnée
+pub async fn backlinks_note_endpoint(
+    State(bridge): State<SharedNotesBridge>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let request_id = Uuid::new_v4().to_string();
+    
+    let command = NoteCommand::Backlinks {
+        request_id,
+        id,
+    };
+    
+    match bridge.send_command(command).await? {
+        NoteResponse::Success { data, .. } => Ok(Json(data)),
+        NoteResponse::Error { error, .. } => {
+            if error == "Note not found" {
+                Err(StatusCode::NOT_FOUND)
+            } else {
+                eprintln!("[notes-bridge] backlinks error: {}", error);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
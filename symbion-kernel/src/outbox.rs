@@ -0,0 +1,306 @@
+/**
+ * OUTBOX - File persistante des messages MQTT émis par le kernel
+ *
+ * RÔLE :
+ * Évite de perdre une commande agent ou une publication de santé quand le broker
+ * est brièvement injoignable : le message attend sur disque et part à la reconnexion.
+ *
+ * FONCTIONNEMENT :
+ * - Broker connecté et file vide : publication directe (aucune écriture disque)
+ * - Broker coupé, publication refusée ou messages déjà en attente : mise en file (ordre FIFO préservé)
+ * - File réécrite dans un fichier JSON à chaque changement, rechargée au démarrage du kernel
+ * - ConnAck du client bridge : la file est vidée dans l'ordre, arrêt au premier échec
+ * - Taille plafonnée (outbox.max_messages) : au-delà le message le plus ancien est abandonné
+ * - Échéance optionnelle par message : une commande dont le timeout est passé n'est jamais rejouée
+ */
+
+use crate::mqtt_publish::MqttPublisher;
+use parking_lot::Mutex;
+use rumqttc::QoS;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use time::OffsetDateTime;
+
+/// Message en attente de publication
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutboxMessage {
+    /// Numéro d'ordre croissant (ordre de publication)
+    pub seq: u64,
+    pub topic: String,
+    pub qos: u8,
+    pub retain: bool,
+    pub payload: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub enqueued_at: OffsetDateTime,
+    /// Au-delà, le message est abandonné au lieu d'être publié
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+/// Issue d'un envoi via l'outbox
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Published,
+    Queued,
+}
+
+/// Bilan d'un vidage de la file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FlushReport {
+    pub published: usize,
+    pub expired: usize,
+    pub remaining: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OutboxFile {
+    next_seq: u64,
+    messages: VecDeque<OutboxMessage>,
+}
+
+pub struct Outbox {
+    path: Option<PathBuf>,
+    max_messages: usize,
+    state: Mutex<OutboxFile>,
+    connected: AtomicBool,
+    /// Messages abandonnés faute de place depuis le démarrage
+    dropped: AtomicU64,
+}
+
+pub type SharedOutbox = Arc<Outbox>;
+
+fn qos_to_u8(qos: QoS) -> u8 {
+    match qos {
+        QoS::AtMostOnce => 0,
+        QoS::AtLeastOnce => 1,
+        QoS::ExactlyOnce => 2,
+    }
+}
+
+fn qos_from_u8(qos: u8) -> QoS {
+    match qos {
+        0 => QoS::AtMostOnce,
+        2 => QoS::ExactlyOnce,
+        _ => QoS::AtLeastOnce,
+    }
+}
+
+impl Outbox {
+    /// Outbox adossée à `path` (file rechargée si le fichier existe), déconnectée tant que le bridge n'a pas de ConnAck
+    pub fn open(path: impl AsRef<Path>, max_messages: usize) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let state = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<OutboxFile>(&bytes).unwrap_or_else(|e| {
+                eprintln!("[outbox] ignoring unreadable {}: {}", path.display(), e);
+                OutboxFile::default()
+            }),
+            Err(_) => OutboxFile::default(),
+        };
+        if !state.messages.is_empty() {
+            println!("[outbox] {} message(s) pending from previous run", state.messages.len());
+        }
+        Self {
+            path: Some(path),
+            max_messages: max_messages.max(1),
+            state: Mutex::new(state),
+            connected: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Outbox sans fichier (tests)
+    #[cfg(test)]
+    pub fn in_memory(max_messages: usize) -> Self {
+        Self {
+            path: None,
+            max_messages: max_messages.max(1),
+            state: Mutex::new(OutboxFile::default()),
+            connected: AtomicBool::new(false),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Messages en attente de publication
+    pub fn pending(&self) -> usize {
+        self.state.lock().messages.len()
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Publie tout de suite si possible, sinon met en file (`ttl` : durée de validité du message)
+    pub fn send(&self, publisher: &dyn MqttPublisher, topic: &str, qos: QoS, retain: bool, payload: String, ttl: Option<time::Duration>) -> Delivery {
+        let now = OffsetDateTime::now_utc();
+        let mut state = self.state.lock();
+        // Verrou tenu pendant la publication directe : un vidage concurrent ne peut pas doubler la file
+        if self.is_connected() && state.messages.is_empty() {
+            match publisher.publish(topic, qos, retain, payload.clone().into_bytes()) {
+                Ok(()) => return Delivery::Published,
+                Err(e) => eprintln!("[outbox] publish on {} failed, queued: {}", topic, e),
+            }
+        }
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.messages.push_back(OutboxMessage {
+            seq,
+            topic: topic.to_string(),
+            qos: qos_to_u8(qos),
+            retain,
+            payload,
+            enqueued_at: now,
+            expires_at: ttl.map(|ttl| now + ttl),
+        });
+        while state.messages.len() > self.max_messages {
+            if let Some(oldest) = state.messages.pop_front() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                eprintln!("[outbox] full ({} messages), dropped oldest message {} on {}", self.max_messages, oldest.seq, oldest.topic);
+            }
+        }
+        self.save(&state);
+        Delivery::Queued
+    }
+
+    /// Broker perdu : les envois suivants passent par la file
+    pub fn mark_disconnected(&self) {
+        self.connected.store(false, Ordering::Relaxed);
+    }
+
+    /// Broker (re)joint : vide la file dans l'ordre via `publisher`
+    pub fn mark_connected(&self, publisher: &dyn MqttPublisher) -> FlushReport {
+        self.connected.store(true, Ordering::Relaxed);
+        self.flush(publisher, OffsetDateTime::now_utc())
+    }
+
+    /// Reprise d'un vidage interrompu (canal du client plein) : sans effet si déconnecté ou file vide
+    pub fn flush_pending(&self, publisher: &dyn MqttPublisher) -> Option<FlushReport> {
+        if !self.is_connected() || self.pending() == 0 {
+            return None;
+        }
+        Some(self.flush(publisher, OffsetDateTime::now_utc()))
+    }
+
+    fn flush(&self, publisher: &dyn MqttPublisher, now: OffsetDateTime) -> FlushReport {
+        let mut state = self.state.lock();
+        let mut report = FlushReport::default();
+        while let Some(message) = state.messages.front() {
+            if message.expires_at.is_some_and(|expires_at| expires_at <= now) {
+                state.messages.pop_front();
+                report.expired += 1;
+                continue;
+            }
+            if let Err(e) = publisher.publish(&message.topic, qos_from_u8(message.qos), message.retain, message.payload.clone().into_bytes()) {
+                eprintln!("[outbox] replay of message {} failed, keeping it: {}", message.seq, e);
+                break;
+            }
+            state.messages.pop_front();
+            report.published += 1;
+        }
+        report.remaining = state.messages.len();
+        if report.published + report.expired > 0 {
+            println!("[outbox] replayed {} message(s), {} expired, {} remaining ({} dropped since start)",
+                     report.published, report.expired, report.remaining, self.dropped());
+            self.save(&state);
+        }
+        report
+    }
+
+    fn save(&self, state: &OutboxFile) {
+        let Some(path) = &self.path else { return };
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let result = serde_json::to_vec(state)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| {
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, bytes).and_then(|_| std::fs::rename(&tmp, path)).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            eprintln!("[outbox] failed to persist {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mqtt_publish::RecordingPublisher;
+
+    #[test]
+    fn test_message_queued_while_disconnected_is_published_after_reconnect() {
+        let broker = RecordingPublisher::unavailable();
+        let outbox = Outbox::in_memory(10);
+
+        assert_eq!(outbox.send(&broker, "symbion/agents/command@v1", QoS::AtLeastOnce, false, "first".into(), None), Delivery::Queued);
+        assert_eq!(outbox.send(&broker, "symbion/kernel/health@v1", QoS::AtLeastOnce, false, "second".into(), None), Delivery::Queued);
+        assert!(broker.payloads().is_empty());
+
+        broker.set_available(true);
+        let report = outbox.mark_connected(&broker);
+        assert_eq!(report, FlushReport { published: 2, expired: 0, remaining: 0 });
+        assert_eq!(broker.payloads(), vec!["first", "second"]);
+
+        // Connecté et file vide : publication directe
+        assert_eq!(outbox.send(&broker, "symbion/agents/command@v1", QoS::AtLeastOnce, false, "third".into(), None), Delivery::Published);
+        assert_eq!(outbox.pending(), 0);
+    }
+
+    #[test]
+    fn test_failed_publish_keeps_order_behind_pending_messages() {
+        let broker = RecordingPublisher::unavailable();
+        let outbox = Outbox::in_memory(10);
+        outbox.mark_connected(&broker);
+
+        // Publication refusée alors que le bridge se croit connecté : mise en file
+        assert_eq!(outbox.send(&broker, "t", QoS::AtLeastOnce, false, "a".into(), None), Delivery::Queued);
+        broker.set_available(true);
+        // File non vide : le message suivant attend son tour
+        assert_eq!(outbox.send(&broker, "t", QoS::AtLeastOnce, false, "b".into(), None), Delivery::Queued);
+
+        outbox.mark_connected(&broker);
+        assert_eq!(broker.payloads(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_cap_drops_oldest_and_expired_messages_are_skipped() {
+        let broker = RecordingPublisher::unavailable();
+        let outbox = Outbox::in_memory(2);
+        outbox.send(&broker, "t", QoS::AtLeastOnce, false, "a".into(), None);
+        outbox.send(&broker, "t", QoS::AtLeastOnce, false, "b".into(), Some(time::Duration::seconds(5)));
+        outbox.send(&broker, "t", QoS::AtLeastOnce, false, "c".into(), None);
+        assert_eq!(outbox.pending(), 2);
+        assert_eq!(outbox.dropped(), 1);
+
+        broker.set_available(true);
+        let report = outbox.flush(&broker, OffsetDateTime::now_utc() + time::Duration::seconds(10));
+        assert_eq!(report, FlushReport { published: 1, expired: 1, remaining: 0 });
+        assert_eq!(broker.payloads(), vec!["c"]);
+    }
+
+    #[test]
+    fn test_pending_messages_survive_restart() {
+        let path = std::env::temp_dir().join(format!("symbion-outbox-{}", uuid::Uuid::new_v4())).join("outbox.json");
+        let broker = RecordingPublisher::unavailable();
+        {
+            let outbox = Outbox::open(&path, 10);
+            outbox.send(&broker, "t", QoS::ExactlyOnce, true, "a".into(), None);
+            outbox.send(&broker, "t", QoS::AtLeastOnce, false, "b".into(), None);
+        }
+
+        let outbox = Outbox::open(&path, 10);
+        assert_eq!(outbox.pending(), 2);
+        broker.set_available(true);
+        outbox.mark_connected(&broker);
+        assert_eq!(broker.payloads(), vec!["a", "b"]);
+        assert_eq!(Outbox::open(&path, 10).pending(), 0);
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,137 @@
+/**
+ * PERSISTENCE - Format des fichiers d'état du kernel (JSON ou MessagePack)
+ *
+ * RÔLE :
+ * Le registre d'agents est sauvegardé à chaque registration et chaque minute.
+ * JSON indenté reste lisible pour le debug ; MessagePack est plus compact et plus
+ * rapide pour les gros parcs.
+ *
+ * FONCTIONNEMENT :
+ * - Format d'écriture choisi par la config (persistence_format: json | msgpack)
+ * - Extension du fichier dérivée du format (agents.json / agents.msgpack)
+ * - Lecture : format détecté sur le contenu (JSON commence par '{' ou '['),
+ *   un fichier de l'autre format est repris tel quel au changement de config
+ * - MessagePack en mode "named" : champs nommés, compatible #[serde(default)]
+ */
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PersistFormat {
+    #[default]
+    Json,
+    Msgpack,
+}
+
+impl PersistFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            PersistFormat::Json => "json",
+            PersistFormat::Msgpack => "msgpack",
+        }
+    }
+
+    /// Détecte le format d'un contenu persisté
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') | Some(b'[') => PersistFormat::Json,
+            _ => PersistFormat::Msgpack,
+        }
+    }
+}
+
+/// Chemin du fichier pour un format : "./data/agents" + format → "./data/agents.msgpack"
+pub fn path_for(base: impl AsRef<Path>, format: PersistFormat) -> PathBuf {
+    base.as_ref().with_extension(format.extension())
+}
+
+pub fn encode<T: Serialize>(value: &T, format: PersistFormat) -> Result<Vec<u8>> {
+    Ok(match format {
+        PersistFormat::Json => serde_json::to_vec_pretty(value)?,
+        PersistFormat::Msgpack => rmp_serde::to_vec_named(value)?,
+    })
+}
+
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    Ok(match PersistFormat::detect(bytes) {
+        PersistFormat::Json => serde_json::from_slice(bytes)?,
+        PersistFormat::Msgpack => rmp_serde::from_slice(bytes)?,
+    })
+}
+
+/// Fichier à charger : celui du format configuré, sinon celui de l'autre format
+pub fn existing_path(base: impl AsRef<Path>, format: PersistFormat) -> Option<PathBuf> {
+    let other = match format {
+        PersistFormat::Json => PersistFormat::Msgpack,
+        PersistFormat::Msgpack => PersistFormat::Json,
+    };
+    [format, other].into_iter()
+        .map(|f| path_for(base.as_ref(), f))
+        .find(|p| p.exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Sample {
+        id: String,
+        tags: Vec<String>,
+        #[serde(default)]
+        note: Option<String>,
+        #[serde(rename = "type")]
+        kind: String,
+        seen: time::OffsetDateTime,
+    }
+
+    fn sample_map() -> HashMap<String, Sample> {
+        (0..3).map(|i| {
+            let sample = Sample {
+                id: format!("agent-{}", i),
+                tags: vec!["linux".into(), "x86_64".into()],
+                note: (i % 2 == 0).then(|| "pair".to_string()),
+                kind: "ethernet".into(),
+                seen: time::macros::datetime!(2025-09-01 10:30:00 UTC) + time::Duration::seconds(i),
+            };
+            (sample.id.clone(), sample)
+        }).collect()
+    }
+
+    #[test]
+    fn test_round_trip_both_formats() {
+        let original = sample_map();
+        for format in [PersistFormat::Json, PersistFormat::Msgpack] {
+            let bytes = encode(&original, format).unwrap();
+            assert_eq!(PersistFormat::detect(&bytes), format);
+            let decoded: HashMap<String, Sample> = decode(&bytes).unwrap();
+            assert_eq!(decoded, original);
+        }
+
+        let json = encode(&original, PersistFormat::Json).unwrap();
+        let msgpack = encode(&original, PersistFormat::Msgpack).unwrap();
+        assert!(msgpack.len() < json.len());
+    }
+
+    #[test]
+    fn test_paths_and_fallback() {
+        let dir = std::env::temp_dir().join(format!("symbion-persist-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let base = dir.join("agents.json");
+
+        assert_eq!(path_for(&base, PersistFormat::Msgpack), dir.join("agents.msgpack"));
+        assert_eq!(existing_path(&base, PersistFormat::Msgpack), None);
+
+        // Passage en msgpack : l'ancien agents.json est repris
+        std::fs::write(dir.join("agents.json"), b"{}").unwrap();
+        assert_eq!(existing_path(&base, PersistFormat::Msgpack), Some(dir.join("agents.json")));
+        std::fs::write(dir.join("agents.msgpack"), encode(&sample_map(), PersistFormat::Msgpack).unwrap()).unwrap();
+        assert_eq!(existing_path(&base, PersistFormat::Msgpack), Some(dir.join("agents.msgpack")));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,91 @@
+/**
+ * PLUGIN CONFIG - Diffusion de la configuration partagée aux plugins
+ *
+ * RÔLE :
+ * Quand un réglage commun change (identifiants du broker, locale...), tous les plugins
+ * doivent le connaître sans redémarrer. Le kernel publie la configuration partagée
+ * sur symbion/plugins/config@v1 ; le DevKit fournit PluginConfigWatcher pour l'appliquer.
+ *
+ * FONCTIONNEMENT :
+ * - POST /config/reload relit kernel.yaml puis publie la diffusion
+ * - Message : { config_id, mqtt: { host, port }?, settings: {...}, timestamp }
+ * - settings = section plugin_settings de kernel.yaml, transmise telle quelle (secrets compris :
+ *   les plugins en ont besoin, le broker est la frontière de confiance)
+ * - Message retenu : un plugin démarré après le rechargement reçoit la dernière version
+ *
+ * LIMITES :
+ * Les sections lues au démarrage du kernel (mqtt, ports, plugin_logs...) ne sont pas
+ * réappliquées au kernel lui-même ; seule la vue GET /config et les plugins sont mis à jour.
+ */
+
+use crate::config::{HostsConfig, MqttConf};
+use crate::mqtt_publish::MqttPublisher;
+use anyhow::Result;
+use rumqttc::QoS;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+pub const CONFIG_TOPIC: &str = symbion_topics::plugins_config();
+
+/// Message symbion/plugins/config@v1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginConfigBroadcast {
+    /// Identifiant de cette diffusion (les plugins ignorent une diffusion déjà appliquée)
+    pub config_id: String,
+    /// Broker MQTT configuré côté kernel
+    pub mqtt: Option<MqttConf>,
+    /// Réglages partagés (plugin_settings)
+    pub settings: serde_json::Map<String, Value>,
+    pub timestamp: String,
+}
+
+impl PluginConfigBroadcast {
+    /// Construit la diffusion à partir de la configuration courante
+    pub fn from_config(cfg: &HostsConfig) -> Result<Self> {
+        Ok(Self {
+            config_id: Uuid::new_v4().to_string(),
+            mqtt: cfg.mqtt.clone(),
+            settings: cfg.plugin_settings.clone(),
+            timestamp: OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339)?,
+        })
+    }
+}
+
+/// Publie (retenue) la configuration partagée ; retourne le config_id diffusé
+pub fn broadcast_plugin_config(publisher: &dyn MqttPublisher, cfg: &HostsConfig) -> Result<String> {
+    let broadcast = PluginConfigBroadcast::from_config(cfg)?;
+    publisher
+        .publish(CONFIG_TOPIC, QoS::AtLeastOnce, true, serde_json::to_vec(&broadcast)?)
+        .map_err(|e| anyhow::anyhow!(e))?;
+    println!("[plugin-config] configuration partagée diffusée ({} réglages, id {})", broadcast.settings.len(), broadcast.config_id);
+    Ok(broadcast.config_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mqtt_publish::RecordingPublisher;
+
+    #[test]
+    fn test_broadcast_carries_shared_settings_and_is_retained() {
+        let mut cfg = HostsConfig::default();
+        cfg.plugin_settings.insert("mqtt_username".into(), Value::from("plugins"));
+        cfg.plugin_settings.insert("mqtt_password".into(), Value::from("rotated"));
+
+        let publisher = RecordingPublisher::default();
+        let config_id = broadcast_plugin_config(&publisher, &cfg).unwrap();
+
+        let published = publisher.published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].topic, "symbion/plugins/config@v1");
+        assert_eq!(published[0].qos, QoS::AtLeastOnce);
+        assert!(published[0].retain);
+
+        let broadcast: PluginConfigBroadcast = published[0].json();
+        assert_eq!(broadcast.config_id, config_id);
+        assert_eq!(broadcast.settings["mqtt_password"], "rotated");
+        assert_eq!(broadcast.mqtt.unwrap().host, "localhost");
+    }
+}
@@ -0,0 +1,149 @@
+/**
+ * PLUGIN HEALTH - Santé métier déclarée par les plugins
+ *
+ * RÔLE :
+ * Le statut de processus (Running/Failed) ne dit rien de l'état fonctionnel d'un plugin :
+ * un plugin notes qui tourne mais ne peut plus écrire son stockage est en panne.
+ * Les plugins qui le souhaitent publient leur propre santé, agrégée par le kernel
+ * dans la section plugins de /system/health (et de symbion/kernel/health@v1).
+ *
+ * FONCTIONNEMENT :
+ * - Le plugin publie périodiquement sur symbion/plugins/health@v1 :
+ *   { plugin, status: healthy|degraded|unhealthy, checks: { nom: { status, message? } }, details, timestamp }
+ * - Le dernier rapport de chaque plugin remplace le précédent
+ * - Rapport plus vieux que STALE_AFTER : conservé mais marqué stale (plugin muet ou arrêté)
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Une seule vue de santé pour le kernel et les domaines des plugins
+ * 🎯 Contrat optionnel : les plugins qui ne publient rien n'apparaissent pas
+ */
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const HEALTH_TOPIC: &str = symbion_topics::plugins_health();
+
+/// Au-delà, le rapport n'est plus considéré comme reflétant l'état du plugin
+pub const STALE_AFTER: Duration = Duration::from_secs(180);
+
+/// État déclaré par le plugin (global ou par vérification)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginHealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+/// Résultat d'une vérification ("storage", "database"...)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginHealthCheck {
+    pub status: PluginHealthStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Message symbion/plugins/health@v1
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginHealthReport {
+    pub plugin: String,
+    pub status: PluginHealthStatus,
+    #[serde(default)]
+    pub checks: BTreeMap<String, PluginHealthCheck>,
+    /// Valeurs libres du domaine (nombre de notes, taille de file...)
+    #[serde(default)]
+    pub details: Map<String, Value>,
+    pub timestamp: String,
+}
+
+/// Santé d'un plugin dans /system/health
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginHealthView {
+    pub status: PluginHealthStatus,
+    pub checks: BTreeMap<String, PluginHealthCheck>,
+    pub details: Map<String, Value>,
+    /// Horodatage du rapport côté plugin
+    pub reported_at: String,
+    /// Aucun rapport depuis STALE_AFTER
+    pub stale: bool,
+}
+
+/// Derniers rapports reçus, par plugin
+#[derive(Clone, Default)]
+pub struct PluginHealthRegistry {
+    reports: Arc<Mutex<BTreeMap<String, (PluginHealthReport, Instant)>>>,
+}
+
+impl PluginHealthRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enregistre un rapport reçu (remplace le précédent du même plugin)
+    pub fn record(&self, report: PluginHealthReport) {
+        self.record_at(report, Instant::now());
+    }
+
+    fn record_at(&self, report: PluginHealthReport, at: Instant) {
+        if report.status != PluginHealthStatus::Healthy {
+            eprintln!("[plugin-health] {} reports {:?}", report.plugin, report.status);
+        }
+        self.reports.lock().insert(report.plugin.clone(), (report, at));
+    }
+
+    /// Vue agrégée de tous les plugins ayant publié leur santé
+    pub fn snapshot(&self, now: Instant) -> BTreeMap<String, PluginHealthView> {
+        self.reports.lock()
+            .iter()
+            .map(|(plugin, (report, received))| (plugin.clone(), PluginHealthView {
+                status: report.status,
+                checks: report.checks.clone(),
+                details: report.details.clone(),
+                reported_at: report.timestamp.clone(),
+                stale: now.saturating_duration_since(*received) >= STALE_AFTER,
+            }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_reports_are_aggregated_and_go_stale() {
+        let registry = PluginHealthRegistry::new();
+        let t0 = Instant::now();
+        // Rapport tel que publié par symbion-plugin-notes
+        let notes: PluginHealthReport = serde_json::from_value(json!({
+            "plugin": "notes-manager",
+            "status": "healthy",
+            "checks": { "storage": { "status": "healthy" } },
+            "details": { "notes_count": 42, "storage": "./notes.json" },
+            "timestamp": "2025-09-01T10:30:00Z"
+        })).unwrap();
+        registry.record_at(notes, t0);
+        let finance: PluginHealthReport = serde_json::from_value(json!({
+            "plugin": "finance",
+            "status": "degraded",
+            "timestamp": "2025-09-01T10:29:00Z"
+        })).unwrap();
+        registry.record_at(finance, t0);
+
+        let snapshot = registry.snapshot(t0 + Duration::from_secs(60));
+        assert_eq!(snapshot.len(), 2);
+        let notes = &snapshot["notes-manager"];
+        assert_eq!(notes.status, PluginHealthStatus::Healthy);
+        assert_eq!(notes.details["notes_count"], 42);
+        assert!(!notes.stale);
+        assert!(snapshot["finance"].checks.is_empty());
+
+        // Plugin muet depuis trop longtemps
+        assert!(registry.snapshot(t0 + STALE_AFTER)["notes-manager"].stale);
+    }
+}
@@ -0,0 +1,329 @@
+/**
+ * PLUGIN LOGS - Capture des sorties plugins et rotation des fichiers de log
+ *
+ * RÔLE :
+ * Les plugins écrivent sur stdout/stderr. Le kernel lit ces flux (un pipe jamais lu
+ * finit par bloquer le plugin), les recopie dans sa propre sortie et dans
+ * {dir}/{name}.log, sans laisser ce fichier grossir indéfiniment.
+ *
+ * FONCTIONNEMENT :
+ * - Un thread par flux (stdout, stderr) lit ligne à ligne et écrit dans le log partagé
+ * - Rotation à la taille : {name}.log devient {name}.log.1 (compressé en .1.gz si
+ *   compress), les archives existantes sont décalées (.1 → .2 ...) et celles au-delà
+ *   de keep supprimées
+ * - À chaque rotation, les archives plus vieilles que max_age_days sont supprimées
+ * - Politique globale (plugin_logs dans kernel.yaml), surchargée par le champ
+ *   "log_rotation" du manifest d'un plugin
+ * - Chaque ligne est aussi diffusée sur un canal broadcast par plugin (suivi en direct
+ *   via GET /plugins/{name}/logs/stream) ; le canal est conservé d'un processus à
+ *   l'autre, les abonnés continuent donc de recevoir après un redémarrage
+ */
+
+use crate::config::{LogRotationConf, PluginLogsConf};
+use flate2::{write::GzEncoder, Compression};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+
+/// Lignes en attente par abonné avant qu'un lecteur trop lent n'en perde
+const LOG_STREAM_CAPACITY: usize = 256;
+
+/// Ligne émise par un plugin, diffusée aux abonnés du flux de logs
+#[derive(Debug, Clone, Serialize)]
+pub struct LogLine {
+    /// "stdout" ou "stderr"
+    pub stream: &'static str,
+    pub line: String,
+}
+
+/// Fichier de log avec rotation à la taille
+pub struct RotatingLog {
+    path: PathBuf,
+    policy: LogRotationConf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingLog {
+    /// Ouvre (ou crée) le fichier en ajout
+    pub fn open(path: PathBuf, policy: LogRotationConf) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, policy, file, size })
+    }
+
+    /// Ajoute une ligne, en archivant d'abord le fichier si elle ferait dépasser max_bytes
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.policy.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Archive le fichier courant et repart d'un fichier vide
+    pub fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let keep = self.policy.keep;
+
+        // Décalage des archives, la plus ancienne sort du quota
+        for index in (1..=keep).rev() {
+            for compressed in [false, true] {
+                let from = archive_path(&self.path, index, compressed);
+                if !from.exists() {
+                    continue;
+                }
+                if index == keep {
+                    fs::remove_file(&from)?;
+                } else {
+                    fs::rename(&from, archive_path(&self.path, index + 1, compressed))?;
+                }
+            }
+        }
+
+        if keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let first = archive_path(&self.path, 1, false);
+            fs::rename(&self.path, &first)?;
+            if self.policy.compress {
+                compress(&first)?;
+            }
+        }
+
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        self.prune();
+        Ok(())
+    }
+
+    /// Supprime les archives hors quota (keep abaissé) ou trop anciennes
+    fn prune(&self) {
+        let (Some(dir), Some(base)) = (self.path.parent(), self.path.file_name().and_then(|n| n.to_str())) else {
+            return;
+        };
+        let Ok(entries) = fs::read_dir(dir) else { return };
+        let max_age = self.policy.max_age_days.map(|days| Duration::from_secs(days * 86_400));
+
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(index) = name.to_str().and_then(|n| archive_index(base, n)) else {
+                continue;
+            };
+            let too_old = max_age.is_some_and(|max_age| {
+                entry.metadata().and_then(|m| m.modified()).ok()
+                    .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                    .is_some_and(|age| age > max_age)
+            });
+            if index > self.policy.keep || too_old {
+                if let Err(e) = fs::remove_file(entry.path()) {
+                    eprintln!("[plugin_logs] failed to prune {}: {}", entry.path().display(), e);
+                }
+            }
+        }
+    }
+}
+
+/// Chemin de l'archive n°index : {name}.log.{index}[.gz]
+fn archive_path(path: &Path, index: usize, compressed: bool) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", index));
+    if compressed {
+        name.push(".gz");
+    }
+    path.with_file_name(name)
+}
+
+/// Numéro d'archive d'un fichier {base}.N ou {base}.N.gz
+fn archive_index(base: &str, file_name: &str) -> Option<usize> {
+    let suffix = file_name.strip_prefix(base)?.strip_prefix('.')?;
+    suffix.strip_suffix(".gz").unwrap_or(suffix).parse().ok()
+}
+
+/// Compresse `path` en `path.gz` puis supprime l'original
+fn compress(path: &Path) -> io::Result<()> {
+    let mut gz_name = path.as_os_str().to_os_string();
+    gz_name.push(".gz");
+    let mut encoder = GzEncoder::new(File::create(PathBuf::from(gz_name))?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(path)
+}
+
+/// Capture des sorties plugins vers {dir}/{name}.log
+#[derive(Debug, Clone)]
+pub struct LogCapture {
+    dir: PathBuf,
+    default_rotation: LogRotationConf,
+    streams: Arc<Mutex<HashMap<String, broadcast::Sender<LogLine>>>>,
+}
+
+impl LogCapture {
+    pub fn new(conf: &PluginLogsConf) -> Self {
+        Self {
+            dir: PathBuf::from(&conf.dir),
+            default_rotation: conf.rotation.clone(),
+            streams: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Canal de diffusion d'un plugin (créé au premier usage, partagé entre redémarrages)
+    fn stream(&self, plugin: &str) -> broadcast::Sender<LogLine> {
+        self.streams.lock()
+            .entry(plugin.to_string())
+            .or_insert_with(|| broadcast::channel(LOG_STREAM_CAPACITY).0)
+            .clone()
+    }
+
+    /// Abonnement aux prochaines lignes d'un plugin
+    pub fn subscribe(&self, plugin: &str) -> broadcast::Receiver<LogLine> {
+        self.stream(plugin).subscribe()
+    }
+
+    /// Fichier de log courant d'un plugin
+    pub fn log_path(&self, plugin: &str) -> PathBuf {
+        self.dir.join(format!("{}.log", plugin))
+    }
+
+    /// Lance la lecture des sorties pipées du processus (politique du manifest sinon globale)
+    pub fn attach(&self, plugin: &str, rotation: Option<&LogRotationConf>, child: &mut Child) {
+        let policy = rotation.unwrap_or(&self.default_rotation).clone();
+        let log = match RotatingLog::open(self.log_path(plugin), policy) {
+            Ok(log) => Some(Arc::new(Mutex::new(log))),
+            Err(e) => {
+                // Les flux restent lus (et recopiés) même sans fichier
+                eprintln!("[plugin_logs] cannot open log for {}: {}", plugin, e);
+                None
+            }
+        };
+        let stream = self.stream(plugin);
+        if let Some(stdout) = child.stdout.take() {
+            spawn_reader(plugin, false, stdout, log.clone(), stream.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            spawn_reader(plugin, true, stderr, log, stream);
+        }
+    }
+}
+
+/// Thread de lecture d'un flux jusqu'à sa fermeture (fin du processus)
+fn spawn_reader<R: Read + Send + 'static>(
+    plugin: &str,
+    stderr: bool,
+    reader: R,
+    mut log: Option<Arc<Mutex<RotatingLog>>>,
+    stream: broadcast::Sender<LogLine>,
+) {
+    let plugin = plugin.to_string();
+    let name = format!("plugin-log-{}", plugin);
+    let spawned = std::thread::Builder::new().name(name).spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut buffer = Vec::new();
+        loop {
+            buffer.clear();
+            match reader.read_until(b'\n', &mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let line = String::from_utf8_lossy(&buffer);
+            let line = line.trim_end_matches(['\r', '\n']);
+            if stderr {
+                eprintln!("[plugin:{}] {}", plugin, line);
+            } else {
+                println!("[plugin:{}] {}", plugin, line);
+            }
+            let failed = log.as_ref().and_then(|log| log.lock().write_line(line).err());
+            if let Some(e) = failed {
+                // Disque plein, droits... : on continue de lire sans écrire
+                eprintln!("[plugin_logs] writing {} log failed, file capture disabled: {}", plugin, e);
+                log = None;
+            }
+            // Sans abonné, send échoue : rien à faire
+            let _ = stream.send(LogLine { stream: if stderr { "stderr" } else { "stdout" }, line: line.to_string() });
+        }
+    });
+    if let Err(e) = spawned {
+        eprintln!("[plugin_logs] failed to spawn log reader: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("symbion-logs-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn gunzip(path: &Path) -> String {
+        let mut content = String::new();
+        GzDecoder::new(File::open(path).unwrap()).read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn test_size_threshold_rotates_and_prunes_to_keep() {
+        let dir = temp_dir();
+        let path = dir.join("notes.log");
+        let policy = LogRotationConf { max_bytes: 100, keep: 2, compress: true, max_age_days: None };
+        let mut log = RotatingLog::open(path.clone(), policy).unwrap();
+
+        // 20 octets par ligne : 5 lignes par fichier, 9 rotations
+        for i in 0..50 {
+            log.write_line(&format!("plugin line #{:05}", i)).unwrap();
+        }
+
+        let current = fs::read_to_string(&path).unwrap();
+        assert_eq!(current.lines().next(), Some("plugin line #00045"));
+        assert!(current.len() <= 100);
+        assert!(gunzip(&dir.join("notes.log.1.gz")).starts_with("plugin line #00040\n"));
+        assert!(gunzip(&dir.join("notes.log.2.gz")).starts_with("plugin line #00035\n"));
+
+        let mut files: Vec<String> = fs::read_dir(&dir).unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["notes.log", "notes.log.1.gz", "notes.log.2.gz"]);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_rotation_prunes_old_and_out_of_quota_archives() {
+        let dir = temp_dir();
+        let path = dir.join("hosts.log");
+        fs::write(&path, "current\n").unwrap();
+        // Archive vieille de 2 jours, et une archive laissée par un keep plus grand
+        let old = File::create(dir.join("hosts.log.1")).unwrap();
+        old.set_modified(SystemTime::now() - Duration::from_secs(2 * 86_400)).unwrap();
+        fs::write(dir.join("hosts.log.7.gz"), "stale").unwrap();
+        fs::write(dir.join("other.log.1"), "untouched").unwrap();
+
+        let policy = LogRotationConf { max_bytes: 1024, keep: 3, compress: false, max_age_days: Some(1) };
+        let mut log = RotatingLog::open(path.clone(), policy).unwrap();
+        log.rotate().unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("hosts.log.1")).unwrap(), "current\n");
+        assert!(!dir.join("hosts.log.2").exists());
+        assert!(!dir.join("hosts.log.7.gz").exists());
+        assert!(dir.join("other.log.1").exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,329 @@
+/**
+ * PLUGIN ROUTES - Routes HTTP déclarées par les plugins, proxifiées via MQTT
+ *
+ * RÔLE :
+ * Les plugins ne parlent que MQTT. Ce module leur permet d'exposer une surface HTTP
+ * sous /plugins/{name}/... sans modifier le code du kernel.
+ *
+ * FONCTIONNEMENT :
+ * - Le plugin annonce ses routes sur symbion/plugins/routes@v1 :
+ *   { plugin, routes: [{ method, path }] } (remplace l'annonce précédente, liste vide = retrait)
+ * - Chemins relatifs au préfixe du plugin, segments {param} acceptés ("/items/{id}")
+ * - Requête HTTP correspondante → symbion/plugins/http_request@v1 (champ plugin pour filtrer)
+ * - Le plugin répond sur symbion/plugins/http_response@v1 avec le même request_id
+ * - Route non annoncée → 404, pas de réponse avant le timeout → 504
+ *
+ * LIMITES :
+ * Les routes de gestion du kernel (/plugins/{name}/start|stop|restart|metrics|logs/stream) restent prioritaires.
+ */
+
+use crate::mqtt_publish::MqttPublisher;
+use axum::http::StatusCode;
+use parking_lot::Mutex;
+use rumqttc::QoS;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use tokio::time::{timeout, Duration};
+use uuid::Uuid;
+
+pub const ROUTES_TOPIC: &str = symbion_topics::plugins_routes();
+pub const REQUEST_TOPIC: &str = symbion_topics::plugins_http_request();
+pub const RESPONSE_TOPIC: &str = symbion_topics::plugins_http_response();
+
+/// Délai d'attente par défaut de la réponse du plugin
+const DEFAULT_PROXY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Route annoncée par un plugin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginRoute {
+    pub method: String,
+    pub path: String,
+}
+
+/// Annonce MQTT des routes d'un plugin
+#[derive(Debug, Deserialize)]
+pub struct RouteAnnouncement {
+    pub plugin: String,
+    #[serde(default)]
+    pub routes: Vec<PluginRoute>,
+}
+
+/// Requête HTTP transmise au plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginHttpRequest {
+    pub request_id: String,
+    pub plugin: String,
+    pub method: String,
+    /// Chemin relatif au préfixe /plugins/{name}
+    pub path: String,
+    /// Route annoncée qui correspond
+    pub route: String,
+    pub params: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+/// Réponse du plugin à une requête proxifiée
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginHttpResponse {
+    pub request_id: String,
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub body: Value,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+/// Normalise un chemin : "/" initial, pas de "/" final ni de segment vide
+fn normalize_path(path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    format!("/{}", segments.join("/"))
+}
+
+/// Compare un chemin à un motif de route ; retourne les paramètres {x} capturés
+fn match_path(pattern: &str, path: &str) -> Option<HashMap<String, String>> {
+    let pattern_segments: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+    let path_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if pattern_segments.len() != path_segments.len() {
+        return None;
+    }
+    let mut params = HashMap::new();
+    for (p, s) in pattern_segments.iter().zip(&path_segments) {
+        match p.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+            Some(name) => {
+                params.insert(name.to_string(), s.to_string());
+            }
+            None if p == s => {}
+            None => return None,
+        }
+    }
+    Some(params)
+}
+
+/// Retire la requête des attentes quand `proxy` se termine, y compris quand le client HTTP
+/// se déconnecte (axum abandonne alors le futur au milieu de l'attente)
+struct PendingGuard<'a> {
+    pending: &'a Mutex<HashMap<String, oneshot::Sender<PluginHttpResponse>>>,
+    request_id: &'a str,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().remove(self.request_id);
+    }
+}
+
+/// Registre des routes plugins et des requêtes en attente de réponse
+pub struct PluginRouteRegistry {
+    routes: Mutex<HashMap<String, Vec<PluginRoute>>>,
+    pending: Mutex<HashMap<String, oneshot::Sender<PluginHttpResponse>>>,
+    proxy_timeout: Duration,
+}
+
+pub type SharedPluginRoutes = Arc<PluginRouteRegistry>;
+
+impl Default for PluginRouteRegistry {
+    fn default() -> Self {
+        Self::new(DEFAULT_PROXY_TIMEOUT)
+    }
+}
+
+impl PluginRouteRegistry {
+    pub fn new(proxy_timeout: Duration) -> Self {
+        Self {
+            routes: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            proxy_timeout,
+        }
+    }
+
+    /// Enregistre (ou remplace) les routes annoncées par un plugin
+    pub fn handle_announcement(&self, announcement: RouteAnnouncement) {
+        let routes: Vec<PluginRoute> = announcement.routes.into_iter()
+            .map(|r| PluginRoute { method: r.method.to_ascii_uppercase(), path: normalize_path(&r.path) })
+            .collect();
+        println!("[plugin-routes] {} announced {} route(s)", announcement.plugin, routes.len());
+        let mut all = self.routes.lock();
+        if routes.is_empty() {
+            all.remove(&announcement.plugin);
+        } else {
+            all.insert(announcement.plugin, routes);
+        }
+    }
+
+    /// Routes annoncées, par plugin
+    pub fn list(&self) -> HashMap<String, Vec<PluginRoute>> {
+        self.routes.lock().clone()
+    }
+
+    /// Route annoncée correspondant à la requête, avec ses paramètres
+    pub fn match_route(&self, plugin: &str, method: &str, path: &str) -> Option<(PluginRoute, HashMap<String, String>)> {
+        let routes = self.routes.lock();
+        routes.get(plugin)?.iter()
+            .filter(|r| r.method.eq_ignore_ascii_case(method))
+            .find_map(|r| match_path(&r.path, path).map(|params| (r.clone(), params)))
+    }
+
+    /// Transmet la réponse d'un plugin à la requête HTTP en attente
+    pub fn handle_response(&self, response: PluginHttpResponse) {
+        match self.pending.lock().remove(&response.request_id) {
+            Some(sender) => {
+                let _ = sender.send(response);
+            }
+            None => eprintln!("[plugin-routes] response for unknown request {}", response.request_id),
+        }
+    }
+
+    /// Proxifie une requête HTTP vers le plugin et attend sa réponse
+    pub async fn proxy(
+        &self,
+        publisher: &dyn MqttPublisher,
+        plugin: &str,
+        method: &str,
+        path: &str,
+        query: HashMap<String, String>,
+        body: Option<Value>,
+    ) -> Result<PluginHttpResponse, StatusCode> {
+        let path = normalize_path(path);
+        let (route, params) = self.match_route(plugin, method, &path).ok_or(StatusCode::NOT_FOUND)?;
+
+        let request = PluginHttpRequest {
+            request_id: Uuid::new_v4().to_string(),
+            plugin: plugin.to_string(),
+            method: route.method.clone(),
+            path,
+            route: route.path,
+            params,
+            query,
+            body,
+        };
+        let payload = serde_json::to_vec(&request).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().insert(request.request_id.clone(), tx);
+        let _pending = PendingGuard { pending: &self.pending, request_id: &request.request_id };
+
+        if let Err(e) = publisher.publish(REQUEST_TOPIC, QoS::AtLeastOnce, false, payload) {
+            eprintln!("[plugin-routes] publish to {} failed: {}", plugin, e);
+            return Err(StatusCode::SERVICE_UNAVAILABLE);
+        }
+
+        match timeout(self.proxy_timeout, rx).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+            Err(_) => Err(StatusCode::GATEWAY_TIMEOUT),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mqtt_publish::RecordingPublisher;
+
+    /// Broker factice : chaque requête publiée est servie par un "plugin" en mémoire
+    fn responding_broker(registry: &SharedPluginRoutes) -> RecordingPublisher {
+        let registry = registry.clone();
+        RecordingPublisher::on_publish(move |topic, payload| {
+            assert_eq!(topic, REQUEST_TOPIC);
+            let request: PluginHttpRequest = serde_json::from_slice(payload).unwrap();
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                registry.handle_response(PluginHttpResponse {
+                    request_id: request.request_id,
+                    status: 201,
+                    body: serde_json::json!({ "id": request.params.get("id"), "echo": request.body }),
+                });
+            });
+        })
+    }
+
+    fn registry_with_routes() -> SharedPluginRoutes {
+        let registry = Arc::new(PluginRouteRegistry::new(Duration::from_millis(100)));
+        registry.handle_announcement(RouteAnnouncement {
+            plugin: "inventory".into(),
+            routes: vec![
+                PluginRoute { method: "get".into(), path: "/items/".into() },
+                PluginRoute { method: "PUT".into(), path: "items/{id}".into() },
+            ],
+        });
+        registry
+    }
+
+    #[test]
+    fn test_route_matching() {
+        let registry = registry_with_routes();
+        let (route, params) = registry.match_route("inventory", "put", "/items/42").unwrap();
+        assert_eq!(route.path, "/items/{id}");
+        assert_eq!(params.get("id").map(String::as_str), Some("42"));
+
+        assert!(registry.match_route("inventory", "GET", "/items").is_some());
+        assert!(registry.match_route("inventory", "DELETE", "/items/42").is_none());
+        assert!(registry.match_route("inventory", "GET", "/items/42/extra").is_none());
+        assert!(registry.match_route("notes", "GET", "/items").is_none());
+
+        // Annonce vide = retrait des routes
+        registry.handle_announcement(RouteAnnouncement { plugin: "inventory".into(), routes: vec![] });
+        assert!(registry.list().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_proxy_round_trip_through_mock_broker() {
+        let registry = registry_with_routes();
+        let broker = responding_broker(&registry);
+
+        let query = HashMap::from([("dry_run".to_string(), "true".to_string())]);
+        let response = registry
+            .proxy(&broker, "inventory", "PUT", "/items/42", query, Some(serde_json::json!({"qty": 3})))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 201);
+        assert_eq!(response.body, serde_json::json!({ "id": "42", "echo": { "qty": 3 } }));
+
+        let published: Vec<PluginHttpRequest> = broker.published().iter().map(|p| p.json()).collect();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].plugin, "inventory");
+        assert_eq!(published[0].route, "/items/{id}");
+        assert_eq!(published[0].query.get("dry_run").map(String::as_str), Some("true"));
+        assert!(registry.pending.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_proxy_unknown_route_and_timeout() {
+        let registry = registry_with_routes();
+        let silent = RecordingPublisher::default();
+
+        let unknown = registry.proxy(&silent, "inventory", "POST", "/items", HashMap::new(), None).await;
+        assert_eq!(unknown.unwrap_err(), StatusCode::NOT_FOUND);
+        assert!(silent.published().is_empty());
+
+        let timed_out = registry.proxy(&silent, "inventory", "GET", "/items", HashMap::new(), None).await;
+        assert_eq!(timed_out.unwrap_err(), StatusCode::GATEWAY_TIMEOUT);
+        assert!(registry.pending.lock().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_releases_the_pending_request() {
+        let registry = registry_with_routes();
+        let silent = RecordingPublisher::default();
+
+        // Client HTTP parti pendant l'attente : axum abandonne le futur du handler
+        let proxied = registry.proxy(&silent, "inventory", "GET", "/items", HashMap::new(), None);
+        assert!(tokio::time::timeout(Duration::from_millis(20), proxied).await.is_err());
+        assert_eq!(silent.published().len(), 1);
+        assert!(registry.pending.lock().is_empty());
+
+        let unavailable = RecordingPublisher::unavailable();
+        let refused = registry.proxy(&unavailable, "inventory", "GET", "/items", HashMap::new(), None).await;
+        assert_eq!(refused.unwrap_err(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(registry.pending.lock().is_empty());
+    }
+}
@@ -10,6 +10,15 @@
  * - Hot loading : chargement/déchargement sans redémarrer le kernel
  * - Sandbox : isolation processus + monitoring santé
  * - Manifest JSON : métadonnées et contrats de chaque plugin
+ * - Contrats livrés avec le plugin (fichiers .json de plugins/{name}.contracts/), enregistrés
+ *   dans le ContractRegistry à la découverte (seulement ceux déclarés au manifest)
+ * - Métriques par plugin (GET /plugins/{name}/metrics) : uptime, redémarrages,
+ *   messages/minute sur les topics de ses contrats, âge de la dernière activité
+ * - Démarrage au boot étalé par plugin_startup.max_concurrent_starts : au-delà de la limite,
+ *   le plugin suivant attend qu'un plugin lancé soit prêt (vivant depuis ready_after_ms)
+ * - Sorties stdout/stderr capturées dans data/logs/{name}.log avec rotation
+ *   (voir plugin_logs.rs ; politique surchargeable par "log_rotation" au manifest),
+ *   et suivables en direct par WebSocket (GET /plugins/{name}/logs/stream)
  * 
  * UTILITÉ DANS SYMBION :
  * 🎯 Extensibilité : ajouter fonctionnalités sans modifier le kernel
@@ -37,7 +46,10 @@ use std::path::{Path, PathBuf};
 use tokio::fs;
 use time::OffsetDateTime;
 use uuid::Uuid;
+use crate::config::{LogRotationConf, PluginLogsConf, PluginStartupConf};
+use crate::plugin_logs::{LogCapture, LogLine};
 use crate::state::Shared;
+use tokio::sync::broadcast;
 use tokio::task;
 
 /// Erreurs possibles lors des opérations sur les plugins
@@ -85,6 +97,9 @@ pub struct PluginManifest {
     pub depends_on: Vec<String>,
     /// Priorité de démarrage (plus petit = démarre en premier)
     pub start_priority: i32,
+    /// Rotation propre au log de ce plugin (sinon plugin_logs de kernel.yaml)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_rotation: Option<LogRotationConf>,
 }
 
 /// État d'exécution d'un plugin à un instant donné
@@ -156,6 +171,10 @@ pub struct PluginManager {
     plugins_dir: PathBuf,
     /// Configuration globale passée aux plugins
     global_env: HashMap<String, String>,
+    /// Capture des sorties plugins (None = flux pipés non lus)
+    log_capture: Option<LogCapture>,
+    /// Limite de démarrages simultanés pour start_plugins_ordered
+    startup: PluginStartupConf,
 }
 
 impl Default for PluginManifest {
@@ -173,6 +192,7 @@ impl Default for PluginManifest {
             env: None,
             depends_on: vec![],
             start_priority: 100,
+            log_rotation: None,
         }
     }
 }
@@ -196,7 +216,7 @@ impl PluginInstance {
     }
 
     /// Démarre le processus plugin avec sandbox et monitoring
-    fn start(&mut self, global_env: &HashMap<String, String>) -> Result<(), PluginError> {
+    fn start(&mut self, global_env: &HashMap<String, String>, log_capture: Option<&LogCapture>) -> Result<(), PluginError> {
         if matches!(self.status, PluginStatus::Running | PluginStatus::Starting) {
             return Err(PluginError::AlreadyLoaded(self.manifest.name.clone()));
         }
@@ -226,7 +246,10 @@ impl PluginInstance {
 
         // Démarrage processus
         match cmd.spawn() {
-            Ok(child) => {
+            Ok(mut child) => {
+                if let Some(capture) = log_capture {
+                    capture.attach(&self.manifest.name, self.manifest.log_rotation.as_ref(), &mut child);
+                }
                 self.process = Some(child);
                 self.status = PluginStatus::Running;
                 self.started_at = Some(OffsetDateTime::now_utc());
@@ -398,7 +421,7 @@ impl PluginInstance {
     }
 
     /// Tente un rollback vers le manifest précédent qui fonctionnait
-    fn attempt_rollback(&mut self, global_env: &HashMap<String, String>) -> Result<(), PluginError> {
+    fn attempt_rollback(&mut self, global_env: &HashMap<String, String>, log_capture: Option<&LogCapture>) -> Result<(), PluginError> {
         if let Some(working_manifest) = &self.last_working_manifest {
             eprintln!("[plugins] attempting rollback for {} to version {}", 
                      self.manifest.name, working_manifest.version);
@@ -410,7 +433,7 @@ impl PluginInstance {
             self.manifest = working_manifest.clone();
             
             // Tentative de démarrage avec l'ancienne version
-            match self.start(global_env) {
+            match self.start(global_env, log_capture) {
                 Ok(()) => {
                     eprintln!("[plugins] rollback successful for {}", self.manifest.name);
                     Ok(())
@@ -443,7 +466,30 @@ impl PluginManager {
             plugins: HashMap::new(),
             plugins_dir: plugins_dir.as_ref().to_path_buf(),
             global_env,
+            log_capture: None,
+            startup: PluginStartupConf::default(),
+        }
+    }
+
+    /// Active la capture des sorties plugins dans {dir}/{name}.log avec rotation
+    pub fn with_log_capture(mut self, conf: &PluginLogsConf) -> Self {
+        self.log_capture = Some(LogCapture::new(conf));
+        self
+    }
+
+    /// Limite le nombre de plugins en cours de démarrage au même moment
+    pub fn with_startup(mut self, conf: &PluginStartupConf) -> Self {
+        self.startup = conf.clone();
+        self
+    }
+
+    /// Abonnement aux lignes de log d'un plugin, y compris après ses redémarrages
+    /// None si le plugin est inconnu ou la capture désactivée
+    pub fn subscribe_logs(&self, name: &str) -> Option<broadcast::Receiver<LogLine>> {
+        if !self.plugins.contains_key(name) {
+            return None;
         }
+        self.log_capture.as_ref().map(|capture| capture.subscribe(name))
     }
 
     /// Scanne le dossier plugins/ et charge tous les manifests
@@ -474,6 +520,14 @@ impl PluginManager {
         Ok(discovered)
     }
 
+    /// Dossier des contrats livrés avec un plugin et contrats déclarés dans son manifest
+    /// None si le plugin est inconnu ou ne livre pas de contrats
+    pub fn bundled_contracts(&self, plugin_name: &str) -> Option<(PathBuf, Vec<String>)> {
+        let plugin = self.plugins.get(plugin_name)?;
+        let dir = self.plugins_dir.join(format!("{}.contracts", plugin_name));
+        dir.is_dir().then(|| (dir, plugin.manifest.contracts.clone()))
+    }
+
     /// Charge un manifest de plugin depuis un fichier JSON
     async fn load_manifest<P: AsRef<Path>>(&self, path: P) -> Result<PluginManifest, PluginError> {
         let content = fs::read_to_string(path).await?;
@@ -497,7 +551,7 @@ impl PluginManager {
         let plugin = self.plugins.get_mut(name)
             .ok_or_else(|| PluginError::NotFound(name.to_string()))?;
         
-        plugin.start(&self.global_env)
+        plugin.start(&self.global_env, self.log_capture.as_ref())
     }
 
     /// Arrête un plugin par son nom (arrêt intentionnel via API)
@@ -577,7 +631,7 @@ impl PluginManager {
         // Tentatives de rollback en priorité
         for name in to_rollback {
             if let Some(plugin) = self.plugins.get_mut(&name) {
-                match plugin.attempt_rollback(&self.global_env) {
+                match plugin.attempt_rollback(&self.global_env, self.log_capture.as_ref()) {
                     Ok(()) => {
                         eprintln!("[plugins] rollback successful for {}", name);
                     }
@@ -609,9 +663,15 @@ impl PluginManager {
 
         // Démarrage ordonné selon les dépendances et priorités
         match self.start_plugins_ordered(&auto_start_plugins) {
-            Ok(started) => {
+            Ok(report) => {
                 eprintln!("[plugins] auto-started {} plugins: [{}]", 
-                         started.len(), started.join(", "));
+                         report.started.len(), report.started.join(", "));
+                for failed in &report.failed {
+                    eprintln!("[plugins] auto-start failed for {}: {}", failed.name, failed.reason);
+                }
+                for skipped in &report.skipped {
+                    eprintln!("[plugins] auto-start skipped {} (dependency {} failed)", skipped.name, skipped.failed_dependency);
+                }
             }
             Err(e) => {
                 eprintln!("[plugins] auto-start failed: {}", e);
@@ -620,11 +680,14 @@ impl PluginManager {
     }
 
     /// Démarre une liste de plugins dans l'ordre des dépendances
-    pub fn start_plugins_ordered(&mut self, plugin_names: &[String]) -> Result<Vec<String>, PluginError> {
-        let mut started = Vec::new();
+    /// Un échec n'interrompt pas le démarrage : ses dépendants sont reportés comme ignorés
+    pub fn start_plugins_ordered(&mut self, plugin_names: &[String]) -> Result<StartReport, PluginError> {
+        let mut report = StartReport::default();
         let mut remaining: Vec<String> = plugin_names.to_vec();
         let max_iterations = remaining.len() + 5; // Éviter boucles infinies
         let mut iterations = 0;
+        // Plugins lancés mais pas encore prêts (limités par max_concurrent_starts)
+        let mut in_flight: Vec<(String, std::time::Instant)> = Vec::new();
 
         while !remaining.is_empty() && iterations < max_iterations {
             let mut progress = false;
@@ -641,11 +704,22 @@ impl PluginManager {
             while i < remaining.len() {
                 let name = &remaining[i];
                 
-                if self.can_start_plugin(name) {
+                if let Some(dependency) = self.blocking_dependency(name, &report) {
+                    // Dépendance en échec : ce plugin ne pourra pas démarrer
+                    eprintln!("[plugins] skipping {}: dependency {} did not start", name, dependency);
+                    if let Some(plugin) = self.plugins.get_mut(name) {
+                        plugin.status = PluginStatus::Stopped;
+                    }
+                    report.skipped.push(SkippedPlugin { name: name.clone(), failed_dependency: dependency });
+                    remaining.remove(i);
+                    progress = true;
+                } else if self.can_start_plugin(name) {
                     // Toutes les dépendances sont satisfaites
+                    self.wait_for_start_slot(&mut in_flight);
                     match self.start_plugin(name) {
                         Ok(()) => {
-                            started.push(name.clone());
+                            in_flight.push((name.clone(), std::time::Instant::now()));
+                            report.started.push(name.clone());
                             remaining.remove(i);
                             progress = true;
                             // Ne pas incrémenter i car on a supprimé un élément
@@ -656,7 +730,9 @@ impl PluginManager {
                             if let Some(plugin) = self.plugins.get_mut(name) {
                                 plugin.status = PluginStatus::Failed(format!("Start failed: {}", e));
                             }
+                            report.failed.push(FailedPlugin { name: name.clone(), reason: e.to_string() });
                             remaining.remove(i);
+                            progress = true;
                             // Ne pas incrémenter i
                         }
                     }
@@ -691,7 +767,41 @@ impl PluginManager {
                        remaining.join(", "))));
         }
 
-        Ok(started)
+        Ok(report)
+    }
+
+    /// Attend qu'un plugin en cours de démarrage soit prêt si max_concurrent_starts est atteint
+    fn wait_for_start_slot(&mut self, in_flight: &mut Vec<(String, std::time::Instant)>) {
+        let limit = self.startup.max_concurrent_starts;
+        if limit == 0 {
+            return;
+        }
+        loop {
+            in_flight.retain(|(name, launched)| !self.is_start_settled(name, *launched));
+            if in_flight.len() < limit {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+    }
+
+    /// Prêt : processus vivant depuis ready_after_ms, ou déjà terminé (ne consomme plus de ressources)
+    fn is_start_settled(&mut self, name: &str, launched: std::time::Instant) -> bool {
+        if launched.elapsed() >= std::time::Duration::from_millis(self.startup.ready_after_ms) {
+            return true;
+        }
+        let Some(process) = self.plugins.get_mut(name).and_then(|p| p.process.as_mut()) else {
+            return true;
+        };
+        !matches!(process.try_wait(), Ok(None))
+    }
+
+    /// Dépendance directe déjà en échec ou ignorée (bloque le plugin définitivement)
+    fn blocking_dependency(&self, plugin_name: &str, report: &StartReport) -> Option<String> {
+        let plugin = self.plugins.get(plugin_name)?;
+        plugin.manifest.depends_on.iter()
+            .find(|dep| report.failed.iter().any(|f| &f.name == *dep) || report.skipped.iter().any(|s| &s.name == *dep))
+            .cloned()
     }
 
     /// Vérifie si un plugin peut être démarré (dépendances satisfaites)
@@ -772,7 +882,7 @@ impl PluginManager {
         plugin.restart_count = 0;
 
         // Tenter le rollback
-        plugin.attempt_rollback(&self.global_env)
+        plugin.attempt_rollback(&self.global_env, self.log_capture.as_ref())
     }
 
     /// Récupère les statistiques détaillées d'un plugin pour debugging
@@ -795,6 +905,26 @@ impl PluginManager {
         })
     }
 
+    /// Métriques dérivées d'un plugin, activité MQTT attribuée via ses contrats
+    pub fn plugin_metrics(&self, plugin_name: &str, health: &crate::health::HealthTracker) -> Option<PluginMetrics> {
+        let plugin = self.plugins.get(plugin_name)?;
+        let now = OffsetDateTime::now_utc();
+        let activity = health.contract_activity(&plugin.manifest.contracts);
+        // Dernière activité : process (démarrage, heartbeat) ou message sur un de ses contrats
+        let last_activity = plugin.last_activity.max(activity.last_received);
+
+        Some(PluginMetrics {
+            name: plugin.manifest.name.clone(),
+            status: plugin.status.clone(),
+            uptime_seconds: plugin.started_at.map(|start| (now - start).whole_seconds().max(0) as u64),
+            restart_count: plugin.restart_count,
+            contracts: plugin.manifest.contracts.clone(),
+            messages_total: activity.messages_total,
+            messages_per_minute: activity.messages_per_minute,
+            last_activity_ago_seconds: last_activity.map(|last| (now - last).whole_seconds().max(0) as u64),
+        })
+    }
+
     /// Arrête proprement tous les plugins dans l'ordre inverse des dépendances
     pub fn shutdown_all(&mut self) {
         eprintln!("[plugins] shutting down all plugins...");
@@ -886,6 +1016,29 @@ impl PluginManager {
     }
 }
 
+/// Bilan d'un démarrage ordonné : chaque plugin demandé est démarré, en échec ou ignoré
+#[derive(Debug, Default, Serialize)]
+pub struct StartReport {
+    pub started: Vec<String>,
+    pub failed: Vec<FailedPlugin>,
+    /// Plugins non tentés car une dépendance n'a pas démarré
+    pub skipped: Vec<SkippedPlugin>,
+}
+
+/// Plugin dont le démarrage a échoué
+#[derive(Debug, Serialize)]
+pub struct FailedPlugin {
+    pub name: String,
+    pub reason: String,
+}
+
+/// Plugin ignoré à cause d'une dépendance en échec (directe ou elle-même ignorée)
+#[derive(Debug, Serialize)]
+pub struct SkippedPlugin {
+    pub name: String,
+    pub failed_dependency: String,
+}
+
 /// Informations publiques d'un plugin pour les APIs
 #[derive(Debug, Serialize)]
 pub struct PluginInfo {
@@ -897,6 +1050,20 @@ pub struct PluginInfo {
     pub contracts: Vec<String>,
 }
 
+/// Métriques d'observabilité d'un plugin
+#[derive(Debug, Serialize)]
+pub struct PluginMetrics {
+    pub name: String,
+    pub status: PluginStatus,
+    pub uptime_seconds: Option<u64>,
+    pub restart_count: u32,
+    pub contracts: Vec<String>,
+    /// Messages reçus par le kernel sur les topics des contrats du plugin
+    pub messages_total: u64,
+    pub messages_per_minute: u64,
+    pub last_activity_ago_seconds: Option<u64>,
+}
+
 /// Informations détaillées de debugging d'un plugin
 #[derive(Debug, Serialize)]
 #[allow(dead_code)]
@@ -949,4 +1116,159 @@ pub fn spawn_plugin_health_monitor(plugins: Shared<PluginManager>) {
             }
         }
     });
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::HealthTracker;
+
+    fn manager_with_notes_plugin() -> PluginManager {
+        let mut manager = PluginManager::new("./plugins-test-unused");
+        let mut instance = PluginInstance::new(PluginManifest {
+            name: "notes-manager".to_string(),
+            contracts: vec!["notes.command@v1".to_string(), "notes.response@v1".to_string()],
+            ..PluginManifest::default()
+        });
+        instance.status = PluginStatus::Running;
+        instance.started_at = Some(OffsetDateTime::now_utc() - time::Duration::seconds(120));
+        instance.restart_count = 2;
+        manager.plugins.insert("notes-manager".to_string(), instance);
+        manager
+    }
+
+    fn manager_with(plugins: &[(&str, &str, &[&str])]) -> PluginManager {
+        let mut manager = PluginManager::new("./plugins-test-unused");
+        for (name, binary, depends_on) in plugins {
+            let instance = PluginInstance::new(PluginManifest {
+                name: name.to_string(),
+                binary: PathBuf::from(binary),
+                depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+                ..PluginManifest::default()
+            });
+            manager.plugins.insert(name.to_string(), instance);
+        }
+        manager
+    }
+
+    #[test]
+    fn test_failed_dependency_reports_dependents_as_skipped() {
+        let mut manager = manager_with(&[
+            ("storage", "/nonexistent/symbion-storage", &[]),
+            ("indexer", "true", &["storage"]),
+            ("search", "true", &["indexer"]),
+            ("standalone", "true", &[]),
+        ]);
+        let names: Vec<String> = ["search", "indexer", "storage", "standalone"].iter().map(|n| n.to_string()).collect();
+
+        let report = manager.start_plugins_ordered(&names).unwrap();
+        assert_eq!(report.started, vec!["standalone".to_string()]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].name, "storage");
+        assert!(report.failed[0].reason.starts_with("Failed to start plugin: storage"));
+
+        let skipped: Vec<(&str, &str)> = report.skipped.iter()
+            .map(|s| (s.name.as_str(), s.failed_dependency.as_str()))
+            .collect();
+        assert_eq!(skipped, vec![("indexer", "storage"), ("search", "indexer")]);
+        assert!(matches!(manager.plugins["search"].status, PluginStatus::Stopped));
+    }
+
+    #[test]
+    fn test_start_limit_of_one_starts_plugins_sequentially() {
+        // yes tourne indéfiniment (stdout pipé non lu) : chaque plugin reste "en démarrage" ready_after_ms
+        let conf = PluginStartupConf { max_concurrent_starts: 1, ready_after_ms: 150 };
+        let mut manager = manager_with(&[("alpha", "yes", &[]), ("beta", "yes", &[]), ("gamma", "yes", &[])])
+            .with_startup(&conf);
+        let names: Vec<String> = ["alpha", "beta", "gamma"].iter().map(|n| n.to_string()).collect();
+
+        let report = manager.start_plugins_ordered(&names).unwrap();
+        assert_eq!(report.started.len(), 3);
+        let launches: Vec<OffsetDateTime> = report.started.iter()
+            .map(|name| manager.plugins[name].started_at.unwrap())
+            .collect();
+        for pair in launches.windows(2) {
+            assert!(pair[1] - pair[0] >= time::Duration::milliseconds(150), "plugins started concurrently: {:?}", launches);
+        }
+    }
+
+    #[test]
+    fn test_plugin_output_is_captured_to_log_file() {
+        let dir = std::env::temp_dir().join(format!("symbion-plugin-logs-{}", Uuid::new_v4()));
+        let conf = PluginLogsConf { dir: dir.to_string_lossy().to_string(), ..PluginLogsConf::default() };
+        // uname sans argument écrit le nom du système sur stdout puis se termine
+        let mut manager = manager_with(&[("probe", "uname", &[])]).with_log_capture(&conf);
+        manager.start_plugin("probe").unwrap();
+
+        let log = dir.join("probe.log");
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while std::fs::read_to_string(&log).map_or(true, |c| c.is_empty()) && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert!(!std::fs::read_to_string(&log).unwrap().trim().is_empty());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_discovered_plugin_bundled_contracts_are_registered() {
+        let dir = std::env::temp_dir().join(format!("symbion-plugins-{}", Uuid::new_v4()));
+        let bundled = dir.join("inventory.contracts");
+        std::fs::create_dir_all(&bundled).unwrap();
+        let manifest = serde_json::json!({
+            "name": "inventory",
+            "version": "0.1.0",
+            "binary": std::env::current_exe().unwrap(),
+            "contracts": ["inventory.item_updated@v1"],
+            "auto_start": false,
+            "restart_on_failure": false,
+            "startup_timeout_seconds": 5,
+            "shutdown_timeout_seconds": 5,
+            "depends_on": [],
+            "start_priority": 50
+        });
+        std::fs::write(dir.join("inventory.json"), manifest.to_string()).unwrap();
+        let contract = |topic: &str| serde_json::json!({ "topic": topic, "schema": { "type": "object" } }).to_string();
+        std::fs::write(bundled.join("inventory.item_updated.v1.json"), contract("symbion/inventory/item_updated@v1")).unwrap();
+        std::fs::write(bundled.join("inventory.debug.v1.json"), contract("symbion/inventory/debug@v1")).unwrap();
+
+        let mut manager = PluginManager::new(&dir);
+        let discovered = manager.discover_plugins().await.unwrap();
+        assert_eq!(discovered, vec!["inventory".to_string()]);
+
+        let mut contracts = crate::contracts::ContractRegistry::new();
+        let (contracts_dir, declared) = manager.bundled_contracts("inventory").unwrap();
+        let registered = contracts.register_plugin_contracts("inventory", &contracts_dir, &declared).await;
+        assert_eq!(registered, vec!["inventory.item_updated@v1".to_string()]);
+        // Contrat non déclaré au manifest : pas exposé sur /contracts
+        assert_eq!(contracts.list_contracts(), vec!["inventory.item_updated@v1".to_string()]);
+        assert!(manager.bundled_contracts("unknown").is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_plugin_metrics_reflect_simulated_activity() {
+        let manager = manager_with_notes_plugin();
+        let health = HealthTracker::new();
+
+        let idle = manager.plugin_metrics("notes-manager", &health).unwrap();
+        assert_eq!(idle.messages_total, 0);
+        assert_eq!(idle.last_activity_ago_seconds, None);
+        assert_eq!(idle.restart_count, 2);
+        assert!(idle.uptime_seconds.unwrap() >= 120);
+
+        for _ in 0..3 {
+            health.record_topic_message("listener", "symbion/notes/response@v1");
+        }
+        health.record_topic_message("listener", "symbion/notes/command@v1");
+        health.record_topic_message("listener", "symbion/agents/heartbeat@v1");
+
+        let active = manager.plugin_metrics("notes-manager", &health).unwrap();
+        assert_eq!(active.messages_total, 4);
+        assert_eq!(active.messages_per_minute, 4);
+        assert!(active.last_activity_ago_seconds.unwrap() <= 1);
+
+        assert!(manager.plugin_metrics("unknown", &health).is_none());
+    }
+}
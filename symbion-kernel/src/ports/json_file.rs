@@ -0,0 +1,331 @@
+/**
+ * JSON FILE DATA PORT - Backend fichier JSON pour les Data Ports
+ *
+ * RÔLE :
+ * Implémentation historique de DataPort : tous les enregistrements d'un port
+ * dans un fichier JSON (tableau de PortData), réécrit à chaque écriture.
+ *
+ * FONCTIONNEMENT :
+ * - Lecture complète du fichier puis filtrage/tri/pagination en mémoire
+ * - Écriture/suppression = réécriture complète du fichier (une seule pour un write_batch)
+ * - Chaque mutation est notifiée aux abonnés (subscribe) une fois le fichier réécrit
+ * - Source de la migration vers SQLite (voir migrate.rs)
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Format lisible et éditable à la main pour les petits ports
+ * 🎯 Compatibilité avec les données existantes
+ */
+
+use super::{change_channel, DataPort, PortChange, PortChangeKind, PortData, PortError, PortInfo, PortQuery};
+use parking_lot::Mutex;
+use serde::de::{self, Deserializer as _, SeqAccess, Visitor};
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Data Port stocké dans un fichier JSON unique
+pub struct JsonFileDataPort {
+    name: String,
+    path: PathBuf,
+    /// Sérialise les réécritures du fichier
+    lock: Mutex<()>,
+    events: broadcast::Sender<PortChange>,
+}
+
+impl JsonFileDataPort {
+    pub fn new<P: Into<PathBuf>>(name: &str, path: P) -> Self {
+        Self { name: name.to_string(), path: path.into(), lock: Mutex::new(()), events: change_channel() }
+    }
+
+    /// Notifie les abonnés (aucun abonné : rien à faire)
+    fn notify(&self, kind: PortChangeKind, id: &str) {
+        let _ = self.events.send(PortChange { port: self.name.clone(), kind, id: id.to_string() });
+    }
+
+    /// Charge tous les enregistrements du fichier (vide si absent)
+    pub fn load_all(&self) -> Result<Vec<PortData>, PortError> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.path)?;
+        if content.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Parcourt le fichier par lots sans le charger entièrement (migration) ; retourne le nombre lu
+    pub fn for_each_batch<F>(&self, batch_size: usize, mut on_batch: F) -> Result<usize, PortError>
+    where
+        F: FnMut(&[PortData]) -> Result<(), PortError>,
+    {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+        let mut reader = BufReader::new(fs::File::open(&self.path)?);
+        // Fichier vide ou blanc : aucun enregistrement, comme load_all
+        loop {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            let blank = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+            let found = blank < buf.len();
+            reader.consume(blank);
+            if found {
+                break;
+            }
+        }
+
+        let mut count = 0;
+        let mut failure = None;
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let visitor = BatchVisitor { batch_size: batch_size.max(1), on_batch: &mut on_batch, count: &mut count, failure: &mut failure };
+        let parsed = deserializer.deserialize_seq(visitor);
+        // Erreur du callback prioritaire sur l'erreur serde qui a interrompu la lecture
+        if let Some(err) = failure {
+            return Err(err);
+        }
+        parsed?;
+        deserializer.end()?;
+        Ok(count)
+    }
+
+    fn save_all(&self, records: &[PortData]) -> Result<(), PortError> {
+        fs::write(&self.path, serde_json::to_string_pretty(records)?)?;
+        Ok(())
+    }
+}
+
+/// Désérialise le tableau élément par élément et transmet les lots au callback
+struct BatchVisitor<'a, F> {
+    batch_size: usize,
+    on_batch: &'a mut F,
+    count: &'a mut usize,
+    failure: &'a mut Option<PortError>,
+}
+
+impl<F> BatchVisitor<'_, F>
+where
+    F: FnMut(&[PortData]) -> Result<(), PortError>,
+{
+    fn flush<E: de::Error>(&mut self, batch: &mut Vec<PortData>) -> Result<(), E> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        *self.count += batch.len();
+        if let Err(err) = (self.on_batch)(batch) {
+            *self.failure = Some(err);
+            return Err(E::custom("batch callback failed"));
+        }
+        batch.clear();
+        Ok(())
+    }
+}
+
+impl<'de, F> Visitor<'de> for BatchVisitor<'_, F>
+where
+    F: FnMut(&[PortData]) -> Result<(), PortError>,
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an array of port records")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        let mut batch = Vec::with_capacity(self.batch_size);
+        while let Some(record) = seq.next_element::<PortData>()? {
+            batch.push(record);
+            if batch.len() == self.batch_size {
+                self.flush(&mut batch)?;
+            }
+        }
+        self.flush(&mut batch)
+    }
+}
+
+impl DataPort for JsonFileDataPort {
+    fn read(&self, query: &PortQuery) -> Result<Vec<PortData>, PortError> {
+        let mut records: Vec<PortData> = self.load_all()?
+            .into_iter()
+            .filter(|r| query.filters.iter().all(|(field, value)| r.data.get(field).unwrap_or(&serde_json::Value::Null) == value))
+            .collect();
+
+        let (field, descending) = match query.order_by.as_deref() {
+            Some(order_by) => match order_by.strip_prefix('-') {
+                Some(field) => (field, true),
+                None => (order_by, false),
+            },
+            None => ("timestamp", false),
+        };
+        match field {
+            "timestamp" => records.sort_by_key(|r| r.timestamp),
+            "id" => records.sort_by(|a, b| a.id.cmp(&b.id)),
+            other => records.sort_by(|a, b| a.data.get(other).map(|v| v.to_string()).cmp(&b.data.get(other).map(|v| v.to_string()))),
+        }
+        if descending {
+            records.reverse();
+        }
+
+        Ok(records.into_iter()
+            .skip(query.offset.unwrap_or(0))
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect())
+    }
+
+    fn write(&self, data: &PortData) -> Result<String, PortError> {
+        Ok(self.write_batch(std::slice::from_ref(data))?.remove(0))
+    }
+
+    fn write_batch(&self, batch: &[PortData]) -> Result<Vec<String>, PortError> {
+        let _guard = self.lock.lock();
+        let mut records = self.load_all()?;
+        let mut ids = Vec::with_capacity(batch.len());
+        let mut changes = Vec::with_capacity(batch.len());
+        for data in batch {
+            let mut record = data.clone();
+            if record.id.is_empty() {
+                record.id = Uuid::new_v4().to_string();
+            }
+            let id = record.id.clone();
+            // Même id plus loin dans le lot : la dernière version gagne, comme des write successifs
+            let before = records.len();
+            records.retain(|r| r.id != id);
+            changes.push(if records.len() < before { PortChangeKind::Updated } else { PortChangeKind::Created });
+            records.push(record);
+            ids.push(id);
+        }
+        self.save_all(&records)?;
+        for (id, kind) in ids.iter().zip(changes) {
+            self.notify(kind, id);
+        }
+        Ok(ids)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), PortError> {
+        let _guard = self.lock.lock();
+        let mut records = self.load_all()?;
+        let before = records.len();
+        records.retain(|r| r.id != id);
+        if records.len() == before {
+            return Err(PortError::RecordNotFound(id.to_string()));
+        }
+        self.save_all(&records)?;
+        self.notify(PortChangeKind::Deleted, id);
+        Ok(())
+    }
+
+    fn subscribe(&self) -> Result<broadcast::Receiver<PortChange>, PortError> {
+        Ok(self.events.subscribe())
+    }
+
+    fn info(&self) -> PortInfo {
+        PortInfo {
+            name: self.name.clone(),
+            version: "v1".to_string(),
+            description: format!("Port {} (JSON file)", self.name),
+            schema: serde_json::json!({ "type": "object" }),
+            capabilities: vec!["read".into(), "write".into(), "delete".into(), "query".into()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use time::OffsetDateTime;
+
+    fn record(id: &str, content: &str) -> PortData {
+        PortData {
+            id: id.to_string(),
+            timestamp: OffsetDateTime::now_utc(),
+            data: serde_json::json!({ "content": content }),
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_write_batch_returns_ids_in_order() {
+        let path = std::env::temp_dir().join(format!("symbion-port-{}.json", Uuid::new_v4()));
+        let port = JsonFileDataPort::new("journal", &path);
+        port.write(&record("existing", "avant")).unwrap();
+
+        let ids = port.write_batch(&[
+            record("", "généré"),
+            record("imported-1", "un"),
+            record("existing", "remplacé"),
+            record("imported-2", "deux"),
+        ]).unwrap();
+        assert_eq!(ids.len(), 4);
+        assert!(!ids[0].is_empty());
+        assert_eq!(&ids[1..], ["imported-1", "existing", "imported-2"]);
+
+        let stored = port.load_all().unwrap();
+        assert_eq!(stored.len(), 4);
+        let existing = stored.iter().find(|r| r.id == "existing").unwrap();
+        assert_eq!(existing.data["content"], "remplacé");
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_mutations_are_notified_to_subscribers() {
+        let path = std::env::temp_dir().join(format!("symbion-port-{}.json", Uuid::new_v4()));
+        let port = JsonFileDataPort::new("journal", &path);
+        let mut events = port.subscribe().unwrap();
+
+        let id = port.write(&record("", "nouveau")).unwrap();
+        port.write(&record(&id, "modifié")).unwrap();
+        port.delete(&id).unwrap();
+        assert!(port.delete(&id).is_err());
+
+        let change = |kind| PortChange { port: "journal".into(), kind, id: id.clone() };
+        assert_eq!(events.try_recv().unwrap(), change(PortChangeKind::Created));
+        assert_eq!(events.try_recv().unwrap(), change(PortChangeKind::Updated));
+        assert_eq!(events.try_recv().unwrap(), change(PortChangeKind::Deleted));
+        // Suppression échouée : aucun événement
+        assert!(events.try_recv().is_err());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_for_each_batch_streams_in_chunks() {
+        let path = std::env::temp_dir().join(format!("symbion-port-{}.json", Uuid::new_v4()));
+        let port = JsonFileDataPort::new("journal", &path);
+        assert_eq!(port.for_each_batch(2, |_| Ok(())).unwrap(), 0);
+        fs::write(&path, "  \n").unwrap();
+        assert_eq!(port.for_each_batch(2, |_| Ok(())).unwrap(), 0);
+
+        let records: Vec<_> = (0..5).map(|i| record(&format!("r{}", i), "x")).collect();
+        port.write_batch(&records).unwrap();
+
+        let mut sizes = Vec::new();
+        let mut seen = Vec::new();
+        let count = port.for_each_batch(2, |batch| {
+            sizes.push(batch.len());
+            seen.extend(batch.iter().map(|r| r.id.clone()));
+            Ok(())
+        }).unwrap();
+        assert_eq!(count, 5);
+        assert_eq!(sizes, [2, 2, 1]);
+        assert_eq!(seen, ["r0", "r1", "r2", "r3", "r4"]);
+
+        // L'erreur du callback arrête la lecture et remonte telle quelle
+        let mut calls = 0;
+        let err = port.for_each_batch(2, |_| {
+            calls += 1;
+            Err(PortError::RecordNotFound("stop".into()))
+        }).unwrap_err();
+        assert!(matches!(err, PortError::RecordNotFound(_)));
+        assert_eq!(calls, 1);
+
+        fs::write(&path, "[{\"id\": 1}]").unwrap();
+        assert!(matches!(port.for_each_batch(2, |_| Ok(())), Err(PortError::Serialization(_))));
+
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,125 @@
+/**
+ * MIGRATION DE PORTS - Fichier JSON → SQLite
+ *
+ * RÔLE :
+ * Copie les enregistrements d'un JsonFileDataPort vers un SqliteDataPort en
+ * conservant id, timestamp (offset compris) et métadonnées, puis vérifie le résultat.
+ *
+ * FONCTIONNEMENT :
+ * - Lecture en flux du fichier source : un seul lot en mémoire à la fois
+ * - Écriture par lots transactionnels, chaque lot relu et vérifié aussitôt
+ * - Ré-exécutable : un enregistrement déjà migré est remplacé (même id)
+ *
+ * USAGE :
+ * symbion-kernel migrate-port <source.json> <destination.sqlite> [nom_port]
+ */
+
+use super::{DataPort, JsonFileDataPort, PortError, SqliteDataPort};
+use serde::Serialize;
+
+/// Taille des lots écrits dans une même transaction
+const MIGRATION_BATCH_SIZE: usize = 500;
+
+/// Bilan d'une migration de port
+#[derive(Debug, Serialize)]
+pub struct MigrationReport {
+    pub source_count: usize,
+    pub migrated: usize,
+    pub destination_count: usize,
+    /// Enregistrements source retrouvés à l'identique dans la destination
+    pub verified: usize,
+}
+
+/// Migre tous les enregistrements d'un port JSON vers un port SQLite
+pub fn migrate_port(from_json: &JsonFileDataPort, to_sqlite: &SqliteDataPort) -> Result<MigrationReport, PortError> {
+    let mut migrated = 0;
+    let mut verified = 0;
+    let source_count = from_json.for_each_batch(MIGRATION_BATCH_SIZE, |batch| {
+        migrated += to_sqlite.write_batch(batch)?.len();
+        // Vérification : chaque enregistrement du lot relu à l'identique, offset compris
+        for record in batch {
+            if to_sqlite.get(&record.id)?.is_some_and(|s| s == *record && s.timestamp.offset() == record.timestamp.offset()) {
+                verified += 1;
+            }
+        }
+        Ok(())
+    })?;
+
+    let report = MigrationReport {
+        source_count,
+        migrated,
+        destination_count: to_sqlite.count()?,
+        verified,
+    };
+
+    if report.verified != report.source_count {
+        return Err(PortError::InvalidQuery(format!(
+            "migration verification failed: {}/{} records match",
+            report.verified, report.source_count
+        )));
+    }
+    Ok(report)
+}
+
+/// Sous-commande `migrate-port <source.json> <destination.sqlite> [nom_port]`
+pub fn run_migrate_command(args: &[String]) -> Result<MigrationReport, PortError> {
+    let (Some(source), Some(destination)) = (args.first(), args.get(1)) else {
+        return Err(PortError::InvalidQuery(
+            "usage: symbion-kernel migrate-port <source.json> <destination.sqlite> [port_name]".into(),
+        ));
+    };
+    let name = args.get(2).map(String::as_str).unwrap_or("migrated");
+
+    let from_json = JsonFileDataPort::new(name, source);
+    let to_sqlite = SqliteDataPort::open(name, destination, &[])?;
+    migrate_port(&from_json, &to_sqlite)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::{PortData, PortQuery};
+    use std::collections::HashMap;
+    use time::{OffsetDateTime, UtcOffset};
+
+    fn sample_dataset() -> Vec<PortData> {
+        (0..1203)
+            .map(|i| PortData {
+                id: format!("rec-{:04}", i),
+                timestamp: OffsetDateTime::from_unix_timestamp_nanos(1_756_720_000_123_456_789 + i as i128 * 1_000_000_007)
+                    .unwrap()
+                    .to_offset(UtcOffset::from_hms((i % 3) as i8, 0, 0).unwrap()),
+                data: serde_json::json!({ "content": format!("note {}", i), "urgent": i % 2 == 0, "nested": { "n": i } }),
+                metadata: HashMap::from([("source".to_string(), format!("import-{}", i % 5))]),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_migration_roundtrip_preserves_records() {
+        let path = std::env::temp_dir().join(format!("symbion-port-{}.json", uuid::Uuid::new_v4()));
+        let dataset = sample_dataset();
+        std::fs::write(&path, serde_json::to_string(&dataset).unwrap()).unwrap();
+
+        let from_json = JsonFileDataPort::new("journal", &path);
+        let to_sqlite = SqliteDataPort::in_memory("journal", &[]).unwrap();
+
+        let report = migrate_port(&from_json, &to_sqlite).unwrap();
+        assert_eq!(report.source_count, dataset.len());
+        assert_eq!(report.migrated, dataset.len());
+        assert_eq!(report.destination_count, dataset.len());
+        assert_eq!(report.verified, dataset.len());
+
+        let migrated = to_sqlite.read(&PortQuery { limit: None, ..PortQuery::default() }).unwrap();
+        assert_eq!(migrated, dataset);
+        for (a, b) in migrated.iter().zip(&dataset) {
+            assert_eq!(a.timestamp.offset(), b.timestamp.offset());
+        }
+
+        // Ré-exécution idempotente
+        let again = migrate_port(&from_json, &to_sqlite).unwrap();
+        assert_eq!(again.destination_count, dataset.len());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -7,9 +7,13 @@
  * 
  * FONCTIONNEMENT :
  * - PortRegistry = catalogue central de tous les ports disponibles (memo, journal, finance...)
- * - DataPort trait = interface commune (read/write/delete) que chaque port implémente
+ * - DataPort trait = interface commune (read/write/write_batch/delete) que chaque port implémente
  * - PortData = format standardisé des données (timestamp + JSON + metadata)
  * - PortQuery = langage de requête unifié (filtres, pagination, tri)
+ * - PortChange = notification de mutation (created/updated/deleted + id), diffusée par
+ *   DataPort::subscribe() et relayée en SSE sur GET /ports/{name}/events
+ * - Backends : fichier JSON (json_file.rs) ou SQLite (sqlite.rs), sélectionnés par port
+ *   via la section `ports` de kernel.yaml ; migration JSON → SQLite dans migrate.rs
  * 
  * UTILITÉ POUR SYMBION :
  * ✅ Interface standardisée : même API pour notes, finance, journal...
@@ -31,7 +35,17 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::Path;
 use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use crate::config::PortConf;
+
+pub mod json_file;
+pub mod migrate;
+pub mod sqlite;
+
+pub use json_file::JsonFileDataPort;
+pub use sqlite::SqliteDataPort;
 
 /// Erreurs possibles lors des opérations sur les Data Ports
 #[derive(Debug, thiserror::Error)]
@@ -45,6 +59,10 @@ pub enum PortError {
     Serialization(#[from] serde_json::Error),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Record not found: {0}")]
+    RecordNotFound(String),
     #[error("Permission denied")]
     #[allow(dead_code)]
     PermissionDenied,
@@ -66,7 +84,7 @@ pub struct PortQuery {
 
 /// Format standardisé des données stockées dans tous les ports
 /// Structure commune : ID unique + timestamp + données JSON + métadonnées
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PortData {
     /// Identifiant unique de l'enregistrement
     pub id: String,
@@ -78,6 +96,41 @@ pub struct PortData {
     pub metadata: HashMap<String, String>,
 }
 
+/// Nature d'une mutation sur un port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+impl PortChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Deleted => "deleted",
+        }
+    }
+}
+
+/// Notification émise après chaque mutation réussie d'un port
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PortChange {
+    pub port: String,
+    pub kind: PortChangeKind,
+    pub id: String,
+}
+
+/// Événements conservés pour un abonné lent avant qu'il ne décroche (Lagged)
+pub const PORT_EVENTS_CAPACITY: usize = 256;
+
+/// Canal de notifications d'un port (les backends l'émettent après écriture réussie)
+pub fn change_channel() -> broadcast::Sender<PortChange> {
+    broadcast::channel(PORT_EVENTS_CAPACITY).0
+}
+
 /// Registre central qui maintient la liste de tous les Data Ports disponibles
 /// C'est le "catalogue" que consulte le kernel pour trouver memo, journal, finance, etc.
 pub struct PortRegistry {
@@ -96,11 +149,23 @@ pub trait DataPort {
     /// Retourne l'ID généré pour la donnée créée
     fn write(&self, data: &PortData) -> Result<String, PortError>; 
     
+    /// Écriture groupée (imports d'historique) : IDs retournés dans l'ordre des données.
+    /// Par défaut un `write` par enregistrement ; les backends la surchargent pour
+    /// n'écrire qu'une fois (réécriture unique du fichier, transaction unique)
+    fn write_batch(&self, records: &[PortData]) -> Result<Vec<String>, PortError> {
+        records.iter().map(|record| self.write(record)).collect()
+    }
+    
     /// Suppression d'un enregistrement par son ID (optionnel selon le port)
     fn delete(&self, _id: &str) -> Result<(), PortError> {
         Err(PortError::InvalidQuery("Delete not supported".into()))
     }
     
+    /// Flux des mutations du port (optionnel selon le port) : évite le polling
+    fn subscribe(&self) -> Result<broadcast::Receiver<PortChange>, PortError> {
+        Err(PortError::InvalidQuery("Subscribe not supported".into()))
+    }
+    
     /// Métadonnées du port : nom, version, schéma, capacités
     /// Permet au système de découvrir dynamiquement les ports disponibles
     fn info(&self) -> PortInfo;
@@ -172,10 +237,85 @@ impl Default for PortQuery {
 // NOTE: Les ports spécifiques sont maintenant implémentés comme plugins distribués
 // (ex: notes via symbion-plugin-notes, finance via symbion-plugin-finance, etc.)
 
-/// Helper pour initialiser le registre des ports (vide maintenant) 
-/// Les ports sont maintenant implémentés comme plugins distribués via MQTT
-pub fn create_default_ports(_data_dir: &str) -> Result<PortRegistry, PortError> {
-    let registry = PortRegistry::new();
-    eprintln!("[ports] initialized empty port registry (ports are now plugins)");
+/// Helper pour initialiser le registre des ports
+/// Seuls les ports déclarés dans la section `ports` de la config sont créés
+pub fn create_default_ports(data_dir: &str, ports_conf: &HashMap<String, PortConf>) -> Result<PortRegistry, PortError> {
+    let mut registry = PortRegistry::new();
+
+    for (name, conf) in ports_conf {
+        match conf.backend.as_str() {
+            "sqlite" => {
+                let path = conf.path.clone()
+                    .unwrap_or_else(|| Path::new(data_dir).join(format!("{}.sqlite", name)).to_string_lossy().into_owned());
+                let port = SqliteDataPort::open(name, &path, &conf.indexed_fields)?;
+                registry.register(name, port);
+                eprintln!("[ports] port {} ready (sqlite: {})", name, path);
+            }
+            "json" => {
+                let path = conf.path.clone()
+                    .unwrap_or_else(|| Path::new(data_dir).join(format!("{}.json", name)).to_string_lossy().into_owned());
+                registry.register(name, JsonFileDataPort::new(name, &path));
+                eprintln!("[ports] port {} ready (json: {})", name, path);
+            }
+            other => eprintln!("[ports] unknown backend '{}' for port {}, skipped", other, name),
+        }
+    }
+
+    if registry.list_ports().is_empty() {
+        eprintln!("[ports] initialized empty port registry (ports are now plugins)");
+    }
     Ok(registry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+
+    /// Port minimal sans surcharge de write_batch : compte les appels à write
+    #[derive(Default)]
+    struct CountingPort {
+        writes: Mutex<Vec<PortData>>,
+    }
+
+    impl DataPort for CountingPort {
+        fn read(&self, _query: &PortQuery) -> Result<Vec<PortData>, PortError> {
+            Ok(self.writes.lock().clone())
+        }
+
+        fn write(&self, data: &PortData) -> Result<String, PortError> {
+            let mut writes = self.writes.lock();
+            writes.push(data.clone());
+            Ok(format!("gen-{}", writes.len()))
+        }
+
+        fn info(&self) -> PortInfo {
+            PortInfo {
+                name: "counting".into(),
+                version: "v1".into(),
+                description: String::new(),
+                schema: serde_json::json!({}),
+                capabilities: vec!["write".into()],
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_write_batch_loops_write_in_order() {
+        let port = CountingPort::default();
+        let records: Vec<PortData> = (0..3)
+            .map(|i| PortData {
+                id: String::new(),
+                timestamp: OffsetDateTime::now_utc(),
+                data: serde_json::json!({ "n": i }),
+                metadata: HashMap::new(),
+            })
+            .collect();
+
+        assert_eq!(port.write_batch(&records).unwrap(), vec!["gen-1", "gen-2", "gen-3"]);
+        let written = port.writes.lock();
+        assert_eq!(written.iter().map(|r| r.data["n"].as_i64().unwrap()).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert!(port.write_batch(&[]).unwrap().is_empty());
+        assert!(matches!(port.subscribe(), Err(PortError::InvalidQuery(_))));
+    }
 }
\ No newline at end of file
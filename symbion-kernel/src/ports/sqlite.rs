@@ -0,0 +1,370 @@
+/**
+ * SQLITE DATA PORT - Backend SQLite pour les Data Ports
+ *
+ * RÔLE :
+ * Implémentation de DataPort persistée dans une base SQLite (rusqlite).
+ * Alternative robuste aux fichiers JSON pour les ports à fort volume.
+ *
+ * FONCTIONNEMENT :
+ * - Une table `records` par base : id, timestamp (ns UTC + offset), data JSON, metadata JSON
+ * - Index d'expression json_extract(data, '$.champ') sur les champs configurés
+ * - Filtres PortQuery traduits en WHERE json_extract(...) = ?, pagination LIMIT/OFFSET
+ * - Écritures groupées dans une transaction (write_batch)
+ * - Mutations notifiées aux abonnés (subscribe) après commit
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Persistance transactionnelle sans réécrire tout un fichier à chaque écriture
+ * 🎯 Filtrage indexé côté base au lieu d'un scan mémoire
+ * 🎯 Même forme PortData que les autres ports : transparent pour l'API /ports
+ */
+
+use super::{change_channel, DataPort, PortChange, PortChangeKind, PortData, PortError, PortInfo, PortQuery};
+use parking_lot::Mutex;
+use rusqlite::{params, params_from_iter, types::Value as SqlValue, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::path::Path;
+use time::{OffsetDateTime, UtcOffset};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Data Port stocké dans une base SQLite
+pub struct SqliteDataPort {
+    name: String,
+    /// Connection non-Sync : sérialisée par un mutex
+    conn: Mutex<Connection>,
+    events: broadcast::Sender<PortChange>,
+}
+
+impl SqliteDataPort {
+    /// Ouvre (ou crée) la base du port au chemin donné
+    pub fn open<P: AsRef<Path>>(name: &str, path: P, indexed_fields: &[String]) -> Result<Self, PortError> {
+        Self::from_connection(name, Connection::open(path)?, indexed_fields)
+    }
+
+    /// Base en mémoire (tests)
+    #[cfg(test)]
+    pub fn in_memory(name: &str, indexed_fields: &[String]) -> Result<Self, PortError> {
+        Self::from_connection(name, Connection::open_in_memory()?, indexed_fields)
+    }
+
+    fn from_connection(name: &str, conn: Connection, indexed_fields: &[String]) -> Result<Self, PortError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS records (
+                id TEXT PRIMARY KEY,
+                timestamp_ns INTEGER NOT NULL,
+                offset_seconds INTEGER NOT NULL,
+                data TEXT NOT NULL,
+                metadata TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_records_timestamp ON records(timestamp_ns);",
+        )?;
+
+        // Index d'expression sur les champs JSON filtrés fréquemment
+        for field in indexed_fields {
+            let path = json_path(field)?;
+            conn.execute_batch(&format!(
+                "CREATE INDEX IF NOT EXISTS idx_records_{field} ON records(json_extract(data, '{path}'));"
+            ))?;
+        }
+
+        Ok(Self { name: name.to_string(), conn: Mutex::new(conn), events: change_channel() })
+    }
+
+    /// Notifie les abonnés (aucun abonné : rien à faire)
+    fn notify(&self, kind: PortChangeKind, id: &str) {
+        let _ = self.events.send(PortChange { port: self.name.clone(), kind, id: id.to_string() });
+    }
+
+    /// Enregistrement par id (vérification de migration)
+    pub fn get(&self, id: &str) -> Result<Option<PortData>, PortError> {
+        let row = self.conn.lock()
+            .query_row(
+                "SELECT id, timestamp_ns, offset_seconds, data, metadata FROM records WHERE id = ?1",
+                params![id],
+                row_to_record,
+            )
+            .optional()?;
+        row.map(decode_record).transpose()
+    }
+
+    /// Nombre total d'enregistrements (vérification de migration)
+    pub fn count(&self) -> Result<usize, PortError> {
+        let conn = self.conn.lock();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM records", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+}
+
+/// Chemin JSON d'un champ, restreint à [A-Za-z0-9_] (interpolé dans le SQL)
+fn json_path(field: &str) -> Result<String, PortError> {
+    if field.is_empty() || !field.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(PortError::InvalidQuery(format!("invalid field name: {}", field)));
+    }
+    Ok(format!("$.{}", field))
+}
+
+/// Convertit une valeur de filtre JSON en valeur SQL comparable à json_extract
+fn filter_value(value: &serde_json::Value) -> Result<SqlValue, PortError> {
+    match value {
+        serde_json::Value::Bool(b) => Ok(SqlValue::Integer(*b as i64)),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Ok(SqlValue::Integer(i)),
+            None => Ok(SqlValue::Real(n.as_f64().unwrap_or_default())),
+        },
+        serde_json::Value::String(s) => Ok(SqlValue::Text(s.clone())),
+        other => Err(PortError::InvalidQuery(format!("unsupported filter value: {}", other))),
+    }
+}
+
+/// Colonne de tri : timestamp, id ou champ JSON ; préfixe "-" pour l'ordre décroissant
+fn order_clause(order_by: Option<&str>) -> Result<String, PortError> {
+    let Some(order_by) = order_by else { return Ok("timestamp_ns ASC".into()) };
+    let (field, direction) = match order_by.strip_prefix('-') {
+        Some(field) => (field, "DESC"),
+        None => (order_by, "ASC"),
+    };
+    let column = match field {
+        "timestamp" => "timestamp_ns".to_string(),
+        "id" => "id".to_string(),
+        other => format!("json_extract(data, '{}')", json_path(other)?),
+    };
+    Ok(format!("{} {}, id ASC", column, direction))
+}
+
+/// Insère ou remplace un enregistrement ; retourne l'id et la nature de la mutation
+fn insert_record(conn: &Connection, record: &PortData) -> Result<(String, PortChangeKind), PortError> {
+    let id = if record.id.is_empty() { Uuid::new_v4().to_string() } else { record.id.clone() };
+    let timestamp_ns = i64::try_from(record.timestamp.unix_timestamp_nanos())
+        .map_err(|_| PortError::InvalidQuery(format!("timestamp out of range: {}", record.timestamp)))?;
+    let exists = !record.id.is_empty()
+        && conn.query_row("SELECT 1 FROM records WHERE id = ?1", params![id], |_| Ok(())).is_ok();
+    conn.execute(
+        "INSERT OR REPLACE INTO records (id, timestamp_ns, offset_seconds, data, metadata)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            id,
+            timestamp_ns,
+            record.timestamp.offset().whole_seconds(),
+            serde_json::to_string(&record.data)?,
+            serde_json::to_string(&record.metadata)?,
+        ],
+    )?;
+    Ok((id, if exists { PortChangeKind::Updated } else { PortChangeKind::Created }))
+}
+
+fn row_to_record(row: &rusqlite::Row) -> rusqlite::Result<(String, i64, i32, String, String)> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+}
+
+/// Reconstruit un PortData (offset d'origine compris) à partir d'une ligne brute
+fn decode_record(
+    (id, timestamp_ns, offset_seconds, data, metadata): (String, i64, i32, String, String),
+) -> Result<PortData, PortError> {
+    let offset = UtcOffset::from_whole_seconds(offset_seconds).unwrap_or(UtcOffset::UTC);
+    let timestamp = OffsetDateTime::from_unix_timestamp_nanos(timestamp_ns as i128)
+        .map_err(|e| PortError::InvalidQuery(format!("invalid stored timestamp: {}", e)))?
+        .to_offset(offset);
+    Ok(PortData {
+        id,
+        timestamp,
+        data: serde_json::from_str(&data)?,
+        metadata: serde_json::from_str::<HashMap<String, String>>(&metadata)?,
+    })
+}
+
+impl DataPort for SqliteDataPort {
+    fn read(&self, query: &PortQuery) -> Result<Vec<PortData>, PortError> {
+        let mut clauses = Vec::new();
+        let mut values = Vec::new();
+        // Ordre déterministe des filtres (HashMap)
+        let mut filters: Vec<_> = query.filters.iter().collect();
+        filters.sort_by(|a, b| a.0.cmp(b.0));
+        for (field, value) in filters {
+            let path = json_path(field)?;
+            if value.is_null() {
+                clauses.push(format!("json_extract(data, '{}') IS NULL", path));
+            } else {
+                clauses.push(format!("json_extract(data, '{}') = ?", path));
+                values.push(filter_value(value)?);
+            }
+        }
+
+        let mut sql = "SELECT id, timestamp_ns, offset_seconds, data, metadata FROM records".to_string();
+        if !clauses.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&clauses.join(" AND "));
+        }
+        sql.push_str(&format!(" ORDER BY {}", order_clause(query.order_by.as_deref())?));
+        // SQLite : LIMIT -1 = sans limite (requis pour utiliser OFFSET seul)
+        sql.push_str(&format!(
+            " LIMIT {} OFFSET {}",
+            query.limit.map(|l| l as i64).unwrap_or(-1),
+            query.offset.unwrap_or(0)
+        ));
+
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(values), row_to_record)?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            records.push(decode_record(row?)?);
+        }
+        Ok(records)
+    }
+
+    fn write(&self, data: &PortData) -> Result<String, PortError> {
+        let (id, kind) = insert_record(&self.conn.lock(), data)?;
+        self.notify(kind, &id);
+        Ok(id)
+    }
+
+    /// Une seule transaction (tout ou rien)
+    fn write_batch(&self, records: &[PortData]) -> Result<Vec<String>, PortError> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        let mut changes = Vec::with_capacity(records.len());
+        for record in records {
+            changes.push(insert_record(&tx, record)?);
+        }
+        tx.commit()?;
+        drop(conn);
+        // Après commit uniquement : un lot annulé ne produit aucun événement
+        for (id, kind) in &changes {
+            self.notify(*kind, id);
+        }
+        Ok(changes.into_iter().map(|(id, _)| id).collect())
+    }
+
+    fn delete(&self, id: &str) -> Result<(), PortError> {
+        let conn = self.conn.lock();
+        match conn.execute("DELETE FROM records WHERE id = ?1", params![id])? {
+            0 => Err(PortError::RecordNotFound(id.to_string())),
+            _ => {
+                self.notify(PortChangeKind::Deleted, id);
+                Ok(())
+            }
+        }
+    }
+
+    fn subscribe(&self) -> Result<broadcast::Receiver<PortChange>, PortError> {
+        Ok(self.events.subscribe())
+    }
+
+    fn info(&self) -> PortInfo {
+        PortInfo {
+            name: self.name.clone(),
+            version: "v1".to_string(),
+            description: format!("Port {} (SQLite)", self.name),
+            schema: serde_json::json!({ "type": "object" }),
+            capabilities: vec!["read".into(), "write".into(), "delete".into(), "query".into()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(id: &str, seconds: i64, data: serde_json::Value) -> PortData {
+        PortData {
+            id: id.to_string(),
+            timestamp: OffsetDateTime::from_unix_timestamp(1_756_720_000 + seconds).unwrap(),
+            data,
+            metadata: HashMap::from([("source".to_string(), "test".to_string())]),
+        }
+    }
+
+    fn seeded_port() -> SqliteDataPort {
+        let port = SqliteDataPort::in_memory("memo", &["urgent".to_string(), "context".to_string()]).unwrap();
+        let ids = port.write_batch(&[
+            record("a", 1, json!({"content": "un", "urgent": true, "context": "cravate"})),
+            record("b", 2, json!({"content": "deux", "urgent": false, "context": "cravate"})),
+            record("c", 3, json!({"content": "trois", "urgent": true, "context": "intime"})),
+            record("d", 4, json!({"content": "quatre", "urgent": true, "context": "cravate", "priority": 2})),
+        ]).unwrap();
+        assert_eq!(ids, vec!["a", "b", "c", "d"]);
+        port
+    }
+
+    fn ids(records: &[PortData]) -> Vec<&str> {
+        records.iter().map(|r| r.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_filter_by_json_fields() {
+        let port = seeded_port();
+        let mut query = PortQuery { limit: None, ..PortQuery::default() };
+        query.filters.insert("urgent".into(), json!(true));
+        query.filters.insert("context".into(), json!("cravate"));
+
+        let result = port.read(&query).unwrap();
+        assert_eq!(ids(&result), vec!["a", "d"]);
+        assert_eq!(result[1].data["priority"], 2);
+        assert_eq!(result[0].metadata["source"], "test");
+
+        query.filters.insert("bad field;".into(), json!(1));
+        assert!(matches!(port.read(&query), Err(PortError::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn test_pagination_and_order() {
+        let port = seeded_port();
+        let page = |limit, offset| PortQuery { limit: Some(limit), offset: Some(offset), ..PortQuery::default() };
+
+        assert_eq!(ids(&port.read(&page(2, 0)).unwrap()), vec!["a", "b"]);
+        assert_eq!(ids(&port.read(&page(2, 2)).unwrap()), vec!["c", "d"]);
+        assert!(port.read(&page(2, 4)).unwrap().is_empty());
+
+        let newest_first = PortQuery { order_by: Some("-timestamp".into()), limit: Some(1), ..PortQuery::default() };
+        assert_eq!(ids(&port.read(&newest_first).unwrap()), vec!["d"]);
+    }
+
+    #[test]
+    fn test_delete_and_timestamp_roundtrip() {
+        let port = seeded_port();
+        port.delete("b").unwrap();
+        assert!(matches!(port.delete("b"), Err(PortError::RecordNotFound(_))));
+        assert_eq!(port.count().unwrap(), 3);
+
+        let offset = UtcOffset::from_hms(2, 0, 0).unwrap();
+        let mut local = record("", 10, json!({"content": "local"}));
+        local.timestamp = local.timestamp.to_offset(offset);
+        let id = port.write(&local).unwrap();
+        assert!(!id.is_empty());
+
+        let stored = port.read(&PortQuery::default()).unwrap().into_iter().find(|r| r.id == id).unwrap();
+        assert_eq!(stored.timestamp, local.timestamp);
+        assert_eq!(stored.timestamp.offset(), offset);
+    }
+
+    #[test]
+    fn test_write_rejects_timestamp_beyond_i64_nanos() {
+        let port = seeded_port();
+        let mut far = record("far", 0, json!({"content": "far"}));
+        far.timestamp = OffsetDateTime::from_unix_timestamp(i64::MAX / 1_000_000_000 + 1).unwrap();
+        assert!(matches!(port.write(&far), Err(PortError::InvalidQuery(_))));
+        assert_eq!(port.count().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_write_produces_change_events() {
+        let port = seeded_port();
+        let mut events = port.subscribe().unwrap();
+
+        let id = port.write(&record("", 5, json!({"content": "cinq"}))).unwrap();
+        port.write_batch(&[record("a", 1, json!({"content": "un bis"})), record("e", 6, json!({}))]).unwrap();
+        port.delete("b").unwrap();
+
+        let received: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|change| (change.kind, change.id))
+            .collect();
+        assert_eq!(received, vec![
+            (PortChangeKind::Created, id),
+            (PortChangeKind::Updated, "a".to_string()),
+            (PortChangeKind::Created, "e".to_string()),
+            (PortChangeKind::Deleted, "b".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,134 @@
+/**
+ * RATE LIMIT - Limitation du débit de commandes par agent
+ *
+ * RÔLE :
+ * Protège les agents d'une automatisation défaillante qui enverrait des commandes
+ * en boucle : au-delà du débit autorisé, send_command refuse sans rien publier.
+ *
+ * FONCTIONNEMENT :
+ * - Un seau à jetons par agent_id : capacité `burst`, rechargé de `per_second` jetons/s
+ * - Chaque commande envoyée consomme un jeton (les réponses servies depuis le cache non) ;
+ *   une commande que le kernel n'a pas pu publier (CommandSendError) rend le sien
+ * - Seau vide → erreur RateLimited avec la limite et le délai avant le prochain jeton,
+ *   exposée en HTTP 429 + Retry-After
+ */
+
+use crate::config::CommandRateLimitConf;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Commande refusée : l'agent a épuisé son seau
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[error("command rate limit exceeded for agent {agent_id} ({burst} burst, {per_second}/s), retry in {retry_after_ms}ms")]
+pub struct RateLimited {
+    pub agent_id: String,
+    pub burst: u32,
+    pub per_second: f64,
+    pub retry_after_ms: u64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Seaux à jetons par agent
+#[derive(Debug)]
+pub struct CommandRateLimiter {
+    conf: CommandRateLimitConf,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl CommandRateLimiter {
+    pub fn new(conf: CommandRateLimitConf) -> Self {
+        Self { conf, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    /// Consomme un jeton de l'agent, ou indique quand réessayer
+    pub fn try_acquire(&self, agent_id: &str, now: Instant) -> Result<(), RateLimited> {
+        let capacity = self.conf.burst as f64;
+        let mut buckets = self.buckets.lock();
+        let bucket = buckets.entry(agent_id.to_string())
+            .or_insert(Bucket { tokens: capacity, last_refill: now });
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.conf.per_second).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        let retry_after = Duration::from_secs_f64((1.0 - bucket.tokens) / self.conf.per_second);
+        Err(RateLimited {
+            agent_id: agent_id.to_string(),
+            burst: self.conf.burst,
+            per_second: self.conf.per_second,
+            retry_after_ms: retry_after.as_millis().max(1) as u64,
+        })
+    }
+
+    /// Rend le jeton d'une commande jamais partie vers l'agent (plafonné à burst)
+    pub fn refund(&self, agent_id: &str) {
+        if let Some(bucket) = self.buckets.lock().get_mut(agent_id) {
+            bucket.tokens = (bucket.tokens + 1.0).min(self.conf.burst as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_exhausts_then_recovers_after_refill() {
+        let limiter = CommandRateLimiter::new(CommandRateLimitConf { burst: 3, per_second: 2.0 });
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.try_acquire("a1b2c3d4e5f6", start).is_ok());
+        }
+        let rejected = limiter.try_acquire("a1b2c3d4e5f6", start).unwrap_err();
+        assert_eq!(rejected.burst, 3);
+        assert_eq!(rejected.retry_after_ms, 500);
+        // Les autres agents ont leur propre seau
+        assert!(limiter.try_acquire("ffeeddccbbaa", start).is_ok());
+
+        // Un jeton rechargé après 500 ms, pas deux
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", later).is_ok());
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", later).is_err());
+
+        // Longue pause : seau plein, plafonné à burst
+        let idle = later + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(limiter.try_acquire("a1b2c3d4e5f6", idle).is_ok());
+        }
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", idle).is_err());
+    }
+
+    #[test]
+    fn test_refund_returns_one_token_up_to_burst() {
+        let limiter = CommandRateLimiter::new(CommandRateLimitConf { burst: 2, per_second: 0.001 });
+        let start = Instant::now();
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", start).is_ok());
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", start).is_ok());
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", start).is_err());
+
+        limiter.refund("a1b2c3d4e5f6");
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", start).is_ok());
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", start).is_err());
+
+        // Jamais au-delà de burst, et sans effet sur un agent inconnu
+        for _ in 0..5 {
+            limiter.refund("a1b2c3d4e5f6");
+        }
+        limiter.refund("ffeeddccbbaa");
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", start).is_ok());
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", start).is_ok());
+        assert!(limiter.try_acquire("a1b2c3d4e5f6", start).is_err());
+    }
+}
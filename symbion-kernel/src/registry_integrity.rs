@@ -0,0 +1,261 @@
+/**
+ * REGISTRY INTEGRITY - Vérification et réparation périodiques du registre d'agents
+ *
+ * RÔLE :
+ * Le fichier agents.json vit longtemps : changements de politique de doublons,
+ * versions d'agents, éditions manuelles y laissent des entrées incohérentes.
+ * Ce contrôle vérifie les invariants du registre et répare ce qui peut l'être.
+ *
+ * INVARIANTS :
+ * - Clé de la map = agent_id de l'entrée (sinon l'agent_id est réaligné sur la clé)
+ * - agent_id au format MAC sans séparateurs, suffixe -N admis (politique rename) ; sinon supprimé
+ * - Au moins une interface réseau : un agent offline sans interface est supprimé
+ *   (il se ré-enregistrera) ; online, il est signalé et ses heartbeats la rétabliront
+ * - Une machine (même MAC et même hostname) n'apparaît qu'une fois : l'entrée vue le plus
+ *   récemment est gardée. Même MAC, hostname différent = clones rename, conservés
+ *
+ * FONCTIONNEMENT :
+ * - Au chargement du registre puis toutes les agent_monitoring.integrity_check_interval_secs
+ * - Chaque anomalie est loguée ([agents] integrity), le dernier rapport servi sur GET /agents/integrity
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Inventaire fiable sans nettoyage manuel du fichier de persistance
+ */
+
+use crate::agents::{normalize_mac, Agent, AgentsMap};
+use serde::Serialize;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+/// Invariant violé
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityProblem {
+    /// agent_id de l'entrée différent de sa clé
+    IdMismatch,
+    /// agent_id hors format
+    InvalidAgentId,
+    NoInterfaces,
+    /// Même machine enregistrée sous plusieurs ids
+    DuplicateMac,
+}
+
+/// Réparation appliquée
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityRepair {
+    Realigned,
+    Dropped,
+    /// Signalé seulement (corrigé par l'agent lui-même)
+    Kept,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IntegrityIssue {
+    pub agent_id: String,
+    pub problem: IntegrityProblem,
+    pub repair: IntegrityRepair,
+    pub detail: String,
+}
+
+/// Résultat d'une passe de vérification
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityReport {
+    #[serde(with = "time::serde::rfc3339")]
+    pub checked_at: OffsetDateTime,
+    pub agents_checked: usize,
+    pub agents_dropped: usize,
+    pub issues: Vec<IntegrityIssue>,
+}
+
+impl IntegrityReport {
+    /// Vrai si la passe a modifié le registre (à persister)
+    pub fn changed(&self) -> bool {
+        self.issues.iter().any(|issue| issue.repair != IntegrityRepair::Kept)
+    }
+}
+
+/// agent_id = 12 chiffres hexadécimaux, éventuellement suffixé -N par la politique rename
+pub fn is_valid_agent_id(agent_id: &str) -> bool {
+    let (mac, suffix) = agent_id.split_once('-').unwrap_or((agent_id, "1"));
+    mac.len() == 12
+        && mac.chars().all(|c| c.is_ascii_hexdigit())
+        && !suffix.is_empty()
+        && suffix.chars().all(|c| c.is_ascii_digit())
+}
+
+fn issue(agent_id: &str, problem: IntegrityProblem, repair: IntegrityRepair, detail: impl Into<String>) -> IntegrityIssue {
+    IntegrityIssue { agent_id: agent_id.to_string(), problem, repair, detail: detail.into() }
+}
+
+/// Vérifie les invariants du registre et le répare sur place
+pub fn check_and_repair(agents: &mut AgentsMap, now: OffsetDateTime) -> IntegrityReport {
+    let agents_checked = agents.len();
+    let mut issues = Vec::new();
+    let mut dropped = Vec::new();
+
+    // Ordre stable : rapports reproductibles d'une passe à l'autre
+    let mut ids: Vec<String> = agents.keys().cloned().collect();
+    ids.sort();
+
+    for id in &ids {
+        let agent = agents.get_mut(id).expect("id taken from the map");
+        if !is_valid_agent_id(id) {
+            issues.push(issue(id, IntegrityProblem::InvalidAgentId, IntegrityRepair::Dropped, format!("hostname {}", agent.hostname)));
+            dropped.push(id.clone());
+            continue;
+        }
+        if agent.agent_id != *id {
+            issues.push(issue(id, IntegrityProblem::IdMismatch, IntegrityRepair::Realigned, format!("entry claimed agent_id {}", agent.agent_id)));
+            agent.agent_id = id.clone();
+        }
+        if agent.network.interfaces.is_empty() {
+            if agent.status.status == "offline" {
+                issues.push(issue(id, IntegrityProblem::NoInterfaces, IntegrityRepair::Dropped, "offline agent without network interfaces"));
+                dropped.push(id.clone());
+            } else {
+                issues.push(issue(id, IntegrityProblem::NoInterfaces, IntegrityRepair::Kept, "online agent without network interfaces, waiting for a heartbeat"));
+            }
+        }
+    }
+    for id in &dropped {
+        agents.remove(id);
+    }
+
+    // Doublons : même machine (MAC + hostname), l'entrée vue le plus récemment gagne
+    let mut machines: HashMap<(String, String), Vec<&Agent>> = HashMap::new();
+    for agent in agents.values() {
+        let mac = normalize_mac(&agent.network.primary_mac);
+        if !mac.is_empty() {
+            machines.entry((mac, agent.hostname.to_ascii_lowercase())).or_default().push(agent);
+        }
+    }
+    let mut duplicates = Vec::new();
+    for mut entries in machines.into_values().filter(|entries| entries.len() > 1) {
+        entries.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then_with(|| a.agent_id.cmp(&b.agent_id)));
+        let kept = entries[0].agent_id.clone();
+        for stale in &entries[1..] {
+            let detail = format!("mac {} already registered as {}", stale.network.primary_mac, kept);
+            duplicates.push(issue(&stale.agent_id, IntegrityProblem::DuplicateMac, IntegrityRepair::Dropped, detail));
+        }
+    }
+    duplicates.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+    for duplicate in &duplicates {
+        agents.remove(&duplicate.agent_id);
+    }
+    dropped.extend(duplicates.iter().map(|d| d.agent_id.clone()));
+    issues.extend(duplicates);
+
+    IntegrityReport { checked_at: now, agents_checked, agents_dropped: dropped.len(), issues }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::{AgentInterface, AgentNetwork, AgentStatus};
+
+    fn agent(agent_id: &str, hostname: &str, mac: &str, status: &str, seen_ago_secs: i64) -> Agent {
+        let now = OffsetDateTime::now_utc();
+        Agent {
+            agent_id: agent_id.to_string(),
+            hostname: hostname.to_string(),
+            os: "linux".to_string(),
+            architecture: "x86_64".to_string(),
+            os_details: None,
+            capabilities: Vec::new(),
+            unavailable_capabilities: Vec::new(),
+            network: AgentNetwork {
+                primary_mac: mac.to_string(),
+                interfaces: vec![AgentInterface {
+                    name: "eth0".to_string(),
+                    mac: mac.to_string(),
+                    ip: "192.168.1.10".to_string(),
+                    interface_type: "ethernet".to_string(),
+                    speed_mbps: None,
+                    mtu: None,
+                }],
+            },
+            version: None,
+            heartbeat_version: "v1".to_string(),
+            status: AgentStatus {
+                status: status.to_string(),
+                last_heartbeat: None,
+                system: None,
+                processes: None,
+                services: None,
+                queue_depth: None,
+                busy: None,
+                clock_skew_secs: None,
+                clock_skewed: false,
+                recent_commands: Vec::new(),
+            },
+            last_seen: now - time::Duration::seconds(seen_ago_secs),
+            registration_time: now - time::Duration::days(1),
+        }
+    }
+
+    fn registry(entries: Vec<(&str, Agent)>) -> AgentsMap {
+        entries.into_iter().map(|(key, agent)| (key.to_string(), agent)).collect()
+    }
+
+    #[test]
+    fn test_injected_duplicate_and_invalid_entries_are_repaired() {
+        let mut no_interfaces = agent("0a0b0c0d0e0f", "old-nas", "0a:0b:0c:0d:0e:0f", "offline", 86_400);
+        no_interfaces.network.interfaces.clear();
+        let mut agents = registry(vec![
+            ("a1b2c3d4e5f6", agent("a1b2c3d4e5f6", "desktop", "a1:b2:c3:d4:e5:f6", "online", 5)),
+            // Même machine, ancien id (entrée périmée)
+            ("a1b2c3d4e5f7", agent("a1b2c3d4e5f7", "DESKTOP", "A1-B2-C3-D4-E5-F6", "offline", 3_600)),
+            // Clone rename : même MAC, autre machine
+            ("a1b2c3d4e5f6-2", agent("a1b2c3d4e5f6-2", "desktop-clone", "a1:b2:c3:d4:e5:f6", "online", 5)),
+            ("not-a-mac", agent("not-a-mac", "edited-by-hand", "11:22:33:44:55:66", "offline", 10)),
+            ("0a0b0c0d0e0f", no_interfaces),
+            ("112233445566", agent("665544332211", "laptop", "11:22:33:44:55:66", "online", 5)),
+        ]);
+
+        let report = check_and_repair(&mut agents, OffsetDateTime::now_utc());
+        assert_eq!(report.agents_checked, 6);
+        assert_eq!(report.agents_dropped, 3);
+        assert!(report.changed());
+        let found: Vec<(&str, IntegrityProblem, IntegrityRepair)> = report.issues.iter()
+            .map(|i| (i.agent_id.as_str(), i.problem, i.repair))
+            .collect();
+        assert_eq!(found, vec![
+            ("0a0b0c0d0e0f", IntegrityProblem::NoInterfaces, IntegrityRepair::Dropped),
+            ("112233445566", IntegrityProblem::IdMismatch, IntegrityRepair::Realigned),
+            ("not-a-mac", IntegrityProblem::InvalidAgentId, IntegrityRepair::Dropped),
+            ("a1b2c3d4e5f7", IntegrityProblem::DuplicateMac, IntegrityRepair::Dropped),
+        ]);
+
+        let mut remaining: Vec<&str> = agents.keys().map(String::as_str).collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["112233445566", "a1b2c3d4e5f6", "a1b2c3d4e5f6-2"]);
+        assert_eq!(agents["112233445566"].agent_id, "112233445566");
+
+        // Registre réparé : une seconde passe ne trouve plus rien
+        let report = check_and_repair(&mut agents, OffsetDateTime::now_utc());
+        assert!(report.issues.is_empty());
+        assert!(!report.changed());
+    }
+
+    #[test]
+    fn test_online_agent_without_interfaces_is_only_reported() {
+        let mut online = agent("a1b2c3d4e5f6", "desktop", "a1:b2:c3:d4:e5:f6", "online", 5);
+        online.network.interfaces.clear();
+        let mut agents = registry(vec![("a1b2c3d4e5f6", online)]);
+        let report = check_and_repair(&mut agents, OffsetDateTime::now_utc());
+        assert_eq!(report.issues[0].repair, IntegrityRepair::Kept);
+        assert!(!report.changed());
+        assert_eq!(agents.len(), 1);
+    }
+
+    #[test]
+    fn test_agent_id_format() {
+        assert!(is_valid_agent_id("a1b2c3d4e5f6"));
+        assert!(is_valid_agent_id("A1B2C3D4E5F6-3"));
+        assert!(!is_valid_agent_id("a1b2c3d4e5f"));
+        assert!(!is_valid_agent_id("a1b2c3d4e5f6-"));
+        assert!(!is_valid_agent_id("a1b2c3d4e5f6-x"));
+        assert!(!is_valid_agent_id("zzzzzzzzzzzz"));
+    }
+}
@@ -0,0 +1,297 @@
+/**
+ * SELF-HEAL - Remédiations automatiques des conditions anormales du kernel
+ *
+ * RÔLE :
+ * Au-delà du redémarrage des plugins par leur circuit breaker, le kernel applique
+ * des actions configurées (section self_heal de kernel.yaml) quand il détecte un
+ * état anormal : plugin en échec, connexion MQTT perdue.
+ *
+ * FONCTIONNEMENT :
+ * - Détection périodique (check_interval_secs) : plugins Failed, listener MQTT
+ *   disconnected/reconnecting (HealthTracker, statut du client listener : c'est lui
+ *   que reconnect_mqtt relance, le publisher de santé a sa propre connexion)
+ * - Chaque règle associe une condition à une action : restart_plugin, reconnect_mqtt
+ *   (reconnexion forcée du listener, abonnements rejoués), clear_pending_commands
+ *   (commandes agents en vol abandonnées : leurs réponses ne viendront pas)
+ * - Cooldown par (action, cible) : une action n'est pas rejouée avant cooldown_secs,
+ *   qu'elle ait réussi ou non — pas de boucle de redémarrages
+ * - Chaque action exécutée ou ignorée est loguée ([self-heal])
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Reprise sans intervention après une coupure broker ou un crash de plugin
+ * 🎯 Comportement borné et traçable, désactivé tant qu'aucune règle n'est configurée
+ */
+
+use crate::commands::CommandTracker;
+use crate::config::{HostsConfig, SelfHealConf};
+use crate::health::HealthTracker;
+use crate::plugins::{PluginInfo, PluginManager, PluginStatus};
+use crate::state::Shared;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task;
+
+/// Condition anormale détectée
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealCondition {
+    PluginFailed,
+    MqttDisconnected,
+}
+
+/// Remédiation applicable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealAction {
+    RestartPlugin,
+    ReconnectMqtt,
+    ClearPendingCommands,
+}
+
+/// Occurrence d'une condition ; `subject` = plugin concerné pour plugin_failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectedCondition {
+    pub condition: HealCondition,
+    pub subject: Option<String>,
+}
+
+/// Exécution effective des actions (le kernel, ou un double en test)
+pub trait HealExecutor {
+    /// Retourne un résumé pour le log, ou l'erreur rencontrée
+    fn execute(&self, action: HealAction, subject: Option<&str>) -> Result<String, String>;
+}
+
+/// Action décidée lors d'un passage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealOutcome {
+    pub action: HealAction,
+    pub subject: Option<String>,
+    pub result: Result<String, String>,
+}
+
+/// Conditions présentes d'après l'état des plugins et le statut MQTT
+pub fn detect(plugins: &[PluginInfo], mqtt_status: &str) -> Vec<DetectedCondition> {
+    let mut detected: Vec<_> = plugins.iter()
+        .filter(|p| matches!(p.status, PluginStatus::Failed(_)))
+        .map(|p| DetectedCondition { condition: HealCondition::PluginFailed, subject: Some(p.name.clone()) })
+        .collect();
+    if matches!(mqtt_status, "disconnected" | "reconnecting") {
+        detected.push(DetectedCondition { condition: HealCondition::MqttDisconnected, subject: None });
+    }
+    detected
+}
+
+/// Statut MQTT surveillé : celui du listener (réception agents/plugins), pas celui du publisher de santé
+fn listener_mqtt_status(health: &HealthTracker) -> String {
+    health.client_status(crate::mqtt::LISTENER_CLIENT)
+}
+
+/// Mémorise la dernière exécution de chaque (action, cible) pour appliquer les cooldowns
+#[derive(Default)]
+pub struct SelfHealRegistry {
+    last_run: HashMap<(HealAction, Option<String>), Instant>,
+}
+
+impl SelfHealRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applique les règles aux conditions détectées ; les actions en cooldown sont ignorées
+    pub fn run(&mut self, conf: &SelfHealConf, detected: &[DetectedCondition], executor: &dyn HealExecutor, now: Instant) -> Vec<HealOutcome> {
+        let mut outcomes = Vec::new();
+        for occurrence in detected {
+            for rule in conf.rules.iter().filter(|r| r.condition == occurrence.condition) {
+                // Seule restart_plugin vise un plugin : les autres actions sont globales
+                let subject = match rule.action {
+                    HealAction::RestartPlugin => match &occurrence.subject {
+                        Some(name) => Some(name.clone()),
+                        None => {
+                            eprintln!("[self-heal] rule {:?} -> {:?} ignored: no plugin to restart", rule.condition, rule.action);
+                            continue;
+                        }
+                    },
+                    _ => None,
+                };
+
+                let key = (rule.action, subject.clone());
+                let cooldown = Duration::from_secs(rule.cooldown_secs);
+                if self.last_run.get(&key).is_some_and(|last| now.duration_since(*last) < cooldown) {
+                    continue;
+                }
+                self.last_run.insert(key, now);
+
+                let result = executor.execute(rule.action, subject.as_deref());
+                match &result {
+                    Ok(summary) => println!("[self-heal] {:?} -> {:?} {}: {}", rule.condition, rule.action, subject.as_deref().unwrap_or(""), summary),
+                    Err(e) => eprintln!("[self-heal] {:?} -> {:?} {} failed: {}", rule.condition, rule.action, subject.as_deref().unwrap_or(""), e),
+                }
+                outcomes.push(HealOutcome { action: rule.action, subject, result });
+            }
+        }
+        outcomes
+    }
+}
+
+/// Exécuteur du kernel : plugins, listener MQTT et suivi des commandes
+pub struct KernelHealer {
+    pub plugins: Shared<PluginManager>,
+    pub commands: CommandTracker,
+    /// Porte le signal de reconnexion écouté par le listener MQTT
+    pub health: HealthTracker,
+}
+
+impl HealExecutor for KernelHealer {
+    fn execute(&self, action: HealAction, subject: Option<&str>) -> Result<String, String> {
+        match action {
+            HealAction::RestartPlugin => {
+                let name = subject.ok_or("no plugin given")?;
+                self.plugins.lock().restart_plugin(name).map_err(|e| e.to_string())?;
+                Ok("plugin restarted".to_string())
+            }
+            HealAction::ReconnectMqtt => {
+                self.health.request_mqtt_reconnect();
+                Ok("listener reconnection requested".to_string())
+            }
+            HealAction::ClearPendingCommands => {
+                let cancelled = self.commands.cancel_pending();
+                Ok(format!("{} pending commands cancelled", cancelled.len()))
+            }
+        }
+    }
+}
+
+/// Démarre la boucle de self-heal (règles relues à chaque passage : POST /config/reload s'applique)
+pub fn spawn_self_heal_monitor(config: Shared<HostsConfig>, health: HealthTracker, healer: KernelHealer) {
+    let healer = Arc::new(healer);
+    let registry = Arc::new(Mutex::new(SelfHealRegistry::new()));
+    task::spawn(async move {
+        loop {
+            let conf = config.lock().self_heal.clone();
+            tokio::time::sleep(Duration::from_secs(conf.check_interval_secs.max(1))).await;
+            if conf.rules.is_empty() {
+                continue;
+            }
+
+            let healer = healer.clone();
+            let registry = registry.clone();
+            let mqtt_status = listener_mqtt_status(&health);
+            // Redémarrer un plugin bloque (arrêt puis démarrage du processus)
+            let pass = task::spawn_blocking(move || {
+                let detected = detect(&healer.plugins.lock().list_plugins(), &mqtt_status);
+                registry.lock().run(&conf, &detected, healer.as_ref(), Instant::now());
+            });
+            if let Err(e) = pass.await {
+                eprintln!("[self-heal] pass failed: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SelfHealRule;
+
+    /// Enregistre les actions demandées
+    #[derive(Default)]
+    struct RecordingExecutor {
+        calls: Mutex<Vec<(HealAction, Option<String>)>>,
+    }
+
+    impl HealExecutor for RecordingExecutor {
+        fn execute(&self, action: HealAction, subject: Option<&str>) -> Result<String, String> {
+            self.calls.lock().push((action, subject.map(str::to_string)));
+            Ok("done".to_string())
+        }
+    }
+
+    fn plugin(name: &str, status: PluginStatus) -> PluginInfo {
+        PluginInfo {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            status,
+            uptime_seconds: None,
+            restart_count: 0,
+            contracts: vec![],
+        }
+    }
+
+    fn conf(rules: &[(HealCondition, HealAction)]) -> SelfHealConf {
+        SelfHealConf {
+            check_interval_secs: 30,
+            rules: rules.iter()
+                .map(|(condition, action)| SelfHealRule { condition: *condition, action: *action, cooldown_secs: 300 })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_failed_plugin_is_restarted_once_per_cooldown() {
+        let conf = conf(&[(HealCondition::PluginFailed, HealAction::RestartPlugin)]);
+        let plugins = [
+            plugin("notes-manager", PluginStatus::Failed("crashed".into())),
+            plugin("finance", PluginStatus::Running),
+        ];
+        let detected = detect(&plugins, "connected");
+        assert_eq!(detected, vec![DetectedCondition { condition: HealCondition::PluginFailed, subject: Some("notes-manager".into()) }]);
+
+        let executor = RecordingExecutor::default();
+        let mut registry = SelfHealRegistry::new();
+        let t0 = Instant::now();
+
+        assert_eq!(registry.run(&conf, &detected, &executor, t0).len(), 1);
+        // Toujours en échec, mais dans le cooldown : rien
+        assert!(registry.run(&conf, &detected, &executor, t0 + Duration::from_secs(30)).is_empty());
+        assert!(registry.run(&conf, &detected, &executor, t0 + Duration::from_secs(299)).is_empty());
+        // Cooldown écoulé : nouvelle tentative
+        assert_eq!(registry.run(&conf, &detected, &executor, t0 + Duration::from_secs(300)).len(), 1);
+
+        let expected = (HealAction::RestartPlugin, Some("notes-manager".to_string()));
+        assert_eq!(*executor.calls.lock(), vec![expected.clone(), expected]);
+    }
+
+    #[test]
+    fn test_cooldowns_are_per_target_and_rules_need_a_match() {
+        let conf = conf(&[
+            (HealCondition::PluginFailed, HealAction::RestartPlugin),
+            (HealCondition::MqttDisconnected, HealAction::ReconnectMqtt),
+            (HealCondition::MqttDisconnected, HealAction::ClearPendingCommands),
+            // Mal configurée : pas de plugin à redémarrer sur une coupure MQTT
+            (HealCondition::MqttDisconnected, HealAction::RestartPlugin),
+        ]);
+        let executor = RecordingExecutor::default();
+        let mut registry = SelfHealRegistry::new();
+        let t0 = Instant::now();
+
+        let first = detect(&[plugin("a", PluginStatus::Failed("x".into()))], "connected");
+        registry.run(&conf, &first, &executor, t0);
+        let second = detect(&[plugin("a", PluginStatus::Failed("x".into())), plugin("b", PluginStatus::Failed("y".into()))], "reconnecting");
+        registry.run(&conf, &second, &executor, t0 + Duration::from_secs(1));
+
+        assert_eq!(*executor.calls.lock(), vec![
+            (HealAction::RestartPlugin, Some("a".to_string())),
+            (HealAction::RestartPlugin, Some("b".to_string())),
+            (HealAction::ReconnectMqtt, None),
+            (HealAction::ClearPendingCommands, None),
+        ]);
+        assert!(detect(&[plugin("a", PluginStatus::Running)], "connected").is_empty());
+    }
+
+    #[test]
+    fn test_mqtt_condition_follows_the_listener_connection() {
+        let health = HealthTracker::new();
+        // Publisher de santé connecté, listener tombé : la coupure est détectée
+        health.mark_mqtt_connected();
+        health.record_client_status(crate::mqtt::LISTENER_CLIENT, "disconnected");
+        assert_eq!(detect(&[], &listener_mqtt_status(&health)), vec![DetectedCondition { condition: HealCondition::MqttDisconnected, subject: None }]);
+
+        // Listener reconnecté, publisher en reconnexion : rien à remédier côté listener
+        health.record_client_status(crate::mqtt::LISTENER_CLIENT, "connected");
+        health.record_reconnect("keep alive timeout");
+        assert!(detect(&[], &listener_mqtt_status(&health)).is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+/**
+ * SHUTDOWN - Arrêt propre du kernel sur SIGTERM / SIGINT
+ *
+ * RÔLE :
+ * Un Ctrl-C ou un `systemctl stop` tuait le kernel net : plugins orphelins, registre
+ * d'agents non sauvegardé. Le kernel s'arrête désormais en ordre.
+ *
+ * FONCTIONNEMENT :
+ * - shutdown_signal : attend SIGINT (Ctrl-C) ou SIGTERM (unix)
+ * - serve_until : le serveur HTTP n'accepte plus de connexions au signal ; les requêtes en
+ *   cours ont HTTP_DRAIN_TIMEOUT pour finir (WebSocket/SSE ouverts ne bloquent pas l'arrêt)
+ * - shutdown_kernel : arrêt des plugins et sauvegarde des agents en parallèle, bornés par
+ *   SHUTDOWN_TIMEOUT : un plugin bloqué n'empêche ni la sauvegarde ni la sortie ; les plugins
+ *   s'arrêtent sur un thread détaché que le runtime n'attend pas, et main sort par
+ *   process::exit (code 1 si la limite a été dépassée)
+ *
+ * UTILITÉ DANS SYMBION :
+ * 🎯 Redémarrages systemd sans perte d'état ni processus plugins orphelins
+ */
+
+use crate::agents::SharedAgentRegistry;
+use crate::plugins::PluginManager;
+use crate::state::Shared;
+use axum::Router;
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::time::{timeout, Duration};
+
+/// Temps laissé aux requêtes HTTP en cours après le signal
+pub const HTTP_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Temps maximal d'arrêt des plugins et de sauvegarde des agents
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Se termine à la réception de SIGINT ou SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            eprintln!("[kernel] SIGINT handler unavailable: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                eprintln!("[kernel] SIGTERM handler unavailable: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => println!("[kernel] SIGINT received, shutting down"),
+        _ = terminate => println!("[kernel] SIGTERM received, shutting down"),
+    }
+}
+
+/// Sert `app` jusqu'à `signal`, puis laisse au plus `drain` aux connexions en cours
+pub async fn serve_until<F>(listener: TcpListener, app: Router, signal: F, drain: Duration) -> std::io::Result<()>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let (stopping_tx, mut stopping) = watch::channel(false);
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            signal.await;
+            let _ = stopping_tx.send(true);
+        });
+
+    tokio::select! {
+        served = server => served,
+        _ = async {
+            let _ = stopping.wait_for(|stopping| *stopping).await;
+            tokio::time::sleep(drain).await;
+        } => {
+            eprintln!("[kernel] HTTP connections still open after {:?}, closing them", drain);
+            Ok(())
+        }
+    }
+}
+
+/// Arrête les plugins et sauvegarde les agents ; faux si `limit` a été dépassé
+pub async fn shutdown_kernel(plugins: Shared<PluginManager>, agents: SharedAgentRegistry, limit: Duration) -> bool {
+    // shutdown_all attend les processus de manière bloquante ; thread détaché plutôt que
+    // spawn_blocking : la destruction du runtime attendrait un plugin bloqué
+    let (stopped_tx, stop_plugins) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        plugins.lock().shutdown_all();
+        let _ = stopped_tx.send(());
+    });
+    let save_agents = async {
+        match agents.save_agents().await {
+            Ok(()) => println!("[kernel] agents saved"),
+            Err(e) => eprintln!("[kernel] failed to save agents: {}", e),
+        }
+    };
+
+    match timeout(limit, async { tokio::join!(stop_plugins, save_agents) }).await {
+        Ok(_) => {
+            println!("[kernel] shutdown complete");
+            true
+        }
+        Err(_) => {
+            eprintln!("[kernel] shutdown still running after {:?}, exiting anyway", limit);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::oneshot;
+
+    async fn bind() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        (listener, addr)
+    }
+
+    #[tokio::test]
+    async fn test_server_stops_on_signal_even_with_a_hung_request() {
+        let app = Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/hang", get(std::future::pending::<&'static str>));
+        let (listener, addr) = bind().await;
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve_until(listener, app, async { let _ = stopped.await; }, Duration::from_millis(200)));
+
+        // Serveur opérationnel avant le signal
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        client.write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut answer = String::new();
+        client.read_to_string(&mut answer).await.unwrap();
+        assert!(answer.starts_with("HTTP/1.1 200"));
+
+        // Requête qui ne se termine jamais : bornée par le drain
+        let mut hung = tokio::net::TcpStream::connect(addr).await.unwrap();
+        hung.write_all(b"GET /hang HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        stop.send(()).unwrap();
+        let served = timeout(Duration::from_secs(5), server).await.expect("server did not stop");
+        assert!(served.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_plugins_and_saves_agents() {
+        let data_file = std::env::temp_dir().join(format!("symbion-agents-{}.json", uuid::Uuid::new_v4()));
+        let agents: SharedAgentRegistry = std::sync::Arc::new(crate::agents::AgentRegistry::new(data_file.to_str().unwrap()));
+        let plugins = crate::state::new_state(PluginManager::new("./plugins"));
+
+        assert!(shutdown_kernel(plugins, agents, Duration::from_secs(5)).await);
+        assert!(data_file.exists());
+        let _ = std::fs::remove_file(&data_file);
+    }
+
+    #[test]
+    fn test_hung_plugin_lock_does_not_hold_the_exit() {
+        let data_file = std::env::temp_dir().join(format!("symbion-agents-{}.json", uuid::Uuid::new_v4()));
+        let agents: SharedAgentRegistry = std::sync::Arc::new(crate::agents::AgentRegistry::new(data_file.to_str().unwrap()));
+        let plugins = crate::state::new_state(PluginManager::new("./plugins"));
+        // Verrou jamais relâché : l'arrêt des plugins ne se termine pas
+        std::mem::forget(plugins.lock());
+
+        // La destruction du runtime ne doit pas attendre le thread bloqué
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let started = std::time::Instant::now();
+        assert!(!runtime.block_on(shutdown_kernel(plugins, agents, Duration::from_millis(100))));
+        drop(runtime);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(data_file.exists());
+        let _ = std::fs::remove_file(&data_file);
+    }
+}
@@ -0,0 +1,306 @@
+/**
+ * TRANSACTIONS - Séquences de commandes agent avec compensation
+ *
+ * RÔLE :
+ * Certaines opérations n'ont de sens qu'entières (arrêter un service, déployer un fichier,
+ * redémarrer le service). Une transaction enchaîne des commandes sur un agent et, si une
+ * étape échoue, annule ce qui a déjà été fait avec les commandes compensatoires déclarées.
+ *
+ * FONCTIONNEMENT :
+ * - POST /agents/{id}/transactions : { steps: [ { command_type, parameters?, timeout_secs?,
+ *   compensate?: { command_type, parameters?, timeout_secs? } } ] }
+ * - Étapes envoyées une par une : la suivante part quand la précédente a répondu success
+ * - Échec (erreur agent, timeout, envoi impossible) : compensations des étapes déjà réussies
+ *   jouées dans l'ordre inverse ; l'étape en échec n'est pas compensée
+ * - Une compensation en échec n'arrête pas les suivantes (rollback au mieux)
+ * - Rapport : committed | rolled_back | rollback_failed, résultat de chaque étape et compensation
+ *
+ * LIMITES :
+ * Pas d'atomicité réelle : les compensations sont des indications fournies par l'appelant,
+ * le kernel ne sait pas défaire une commande par lui-même.
+ */
+
+use crate::agents::{AgentRegistry, DEFAULT_COMMAND_TIMEOUT_SECONDS, MAX_COMMAND_TIMEOUT_SECONDS};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Nombre maximal d'étapes par transaction
+pub const MAX_TRANSACTION_STEPS: usize = 20;
+
+/// Marge d'attente au-delà du timeout transmis à l'agent (transport MQTT)
+const RESPONSE_SLACK_SECONDS: u64 = 5;
+
+/// Commande d'une étape ou de sa compensation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionCommand {
+    pub command_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u32>,
+}
+
+/// Étape : une commande et, éventuellement, de quoi l'annuler
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionStep {
+    #[serde(flatten)]
+    pub command: TransactionCommand,
+    /// Jouée si une étape suivante échoue
+    #[serde(default)]
+    pub compensate: Option<TransactionCommand>,
+}
+
+/// Corps de POST /agents/{id}/transactions
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransactionRequest {
+    pub steps: Vec<TransactionStep>,
+}
+
+impl TransactionRequest {
+    /// Vérifie la transaction avant tout envoi (rien n'est exécuté si elle est invalide)
+    pub fn validate(&self) -> Result<(), String> {
+        if self.steps.is_empty() {
+            return Err("a transaction needs at least one step".to_string());
+        }
+        if self.steps.len() > MAX_TRANSACTION_STEPS {
+            return Err(format!("a transaction has at most {} steps", MAX_TRANSACTION_STEPS));
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            for command in std::iter::once(&step.command).chain(step.compensate.as_ref()) {
+                if command.command_type.trim().is_empty() {
+                    return Err(format!("step {}: command_type is required", index));
+                }
+                if command.timeout_secs.is_some_and(|secs| secs == 0 || secs > MAX_COMMAND_TIMEOUT_SECONDS) {
+                    return Err(format!("step {}: timeout_secs must be between 1 and {}", index, MAX_COMMAND_TIMEOUT_SECONDS));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Une étape ou une compensation envoie une commande réservée à l'admin
+    pub fn requires_admin(&self) -> bool {
+        self.steps.iter()
+            .flat_map(|step| std::iter::once(&step.command).chain(step.compensate.as_ref()))
+            .any(|command| crate::agents::requires_admin(&command.command_type))
+    }
+}
+
+/// Résultat d'une commande exécutée sur l'agent
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandOutcome {
+    /// Absent si la commande n'a pas pu être envoyée
+    pub command_id: Option<String>,
+    /// Statut de la réponse agent, ou send_failed / timeout
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CommandOutcome {
+    pub fn is_success(&self) -> bool {
+        self.status == "success"
+    }
+}
+
+/// Exécution d'une commande jusqu'à sa réponse finale (le registre d'agents, ou un double en test)
+pub trait StepRunner {
+    async fn run(&self, agent_id: &str, command: &TransactionCommand) -> CommandOutcome;
+}
+
+impl StepRunner for AgentRegistry {
+    async fn run(&self, agent_id: &str, command: &TransactionCommand) -> CommandOutcome {
+        let command_id = match self.send_command_with_timeout(agent_id, &command.command_type, command.parameters.clone(), command.timeout_secs).await {
+            Ok(command_id) => command_id,
+            Err(e) => return CommandOutcome { command_id: None, status: "send_failed".to_string(), error: Some(e.to_string()) },
+        };
+        let timeout_secs = command.timeout_secs.unwrap_or(DEFAULT_COMMAND_TIMEOUT_SECONDS);
+        let wait = Duration::from_secs(u64::from(timeout_secs) + RESPONSE_SLACK_SECONDS);
+        match self.commands().wait_for_result(&command_id, wait).await {
+            Some(record) if !record.is_pending() => CommandOutcome {
+                error: record.response.and_then(|r| r.error).map(|e| format!("{}: {}", e.code, e.message)),
+                command_id: Some(command_id),
+                status: record.status,
+            },
+            _ => CommandOutcome {
+                command_id: Some(command_id),
+                status: "timeout".to_string(),
+                error: Some(format!("no response within {}s", wait.as_secs())),
+            },
+        }
+    }
+}
+
+/// Issue d'une transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    /// Toutes les étapes ont réussi
+    Committed,
+    /// Une étape a échoué, toutes les compensations ont réussi
+    RolledBack,
+    /// Une étape a échoué et au moins une compensation aussi : état de l'agent à vérifier
+    RollbackFailed,
+}
+
+/// Commande jouée pour une étape (exécution ou compensation)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepReport {
+    /// Index de l'étape dans la requête
+    pub step: usize,
+    pub command_type: String,
+    #[serde(flatten)]
+    pub outcome: CommandOutcome,
+}
+
+/// Rapport renvoyé par POST /agents/{id}/transactions
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionReport {
+    pub transaction_id: String,
+    pub agent_id: String,
+    pub status: TransactionStatus,
+    /// Étapes exécutées, jusqu'à celle en échec incluse
+    pub steps: Vec<StepReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub failed_step: Option<usize>,
+    /// Compensations jouées, dans l'ordre d'exécution (inverse des étapes)
+    pub compensations: Vec<StepReport>,
+}
+
+/// Exécute les étapes en séquence ; sur échec, compense les étapes réussies en ordre inverse
+pub async fn run_transaction<R: StepRunner>(runner: &R, agent_id: &str, request: &TransactionRequest) -> TransactionReport {
+    let transaction_id = Uuid::new_v4().to_string();
+    let mut steps = Vec::new();
+    let mut failed_step = None;
+
+    for (index, step) in request.steps.iter().enumerate() {
+        let outcome = runner.run(agent_id, &step.command).await;
+        let success = outcome.is_success();
+        steps.push(StepReport { step: index, command_type: step.command.command_type.clone(), outcome });
+        if !success {
+            eprintln!("[transactions] {} on agent {}: step {} ({}) failed, rolling back", transaction_id, agent_id, index, step.command.command_type);
+            failed_step = Some(index);
+            break;
+        }
+    }
+
+    let mut compensations = Vec::new();
+    if let Some(failed) = failed_step {
+        for (index, step) in request.steps[..failed].iter().enumerate().rev() {
+            let Some(compensate) = &step.compensate else { continue };
+            let outcome = runner.run(agent_id, compensate).await;
+            if !outcome.is_success() {
+                eprintln!("[transactions] {} on agent {}: compensation of step {} ({}) failed: {}",
+                          transaction_id, agent_id, index, compensate.command_type, outcome.status);
+            }
+            compensations.push(StepReport { step: index, command_type: compensate.command_type.clone(), outcome });
+        }
+    }
+
+    let status = match failed_step {
+        None => TransactionStatus::Committed,
+        Some(_) if compensations.iter().all(|c| c.outcome.is_success()) => TransactionStatus::RolledBack,
+        Some(_) => TransactionStatus::RollbackFailed,
+    };
+    println!("[transactions] {} on agent {}: {:?} ({} steps, {} compensations)", transaction_id, agent_id, status, steps.len(), compensations.len());
+    TransactionReport { transaction_id, agent_id: agent_id.to_string(), status, steps, failed_step, compensations }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use serde_json::json;
+
+    /// Enregistre les commandes jouées ; échoue sur les types listés
+    struct ScriptedRunner {
+        failing: Vec<&'static str>,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl StepRunner for ScriptedRunner {
+        async fn run(&self, _agent_id: &str, command: &TransactionCommand) -> CommandOutcome {
+            let calls = {
+                let mut calls = self.calls.lock();
+                calls.push(command.command_type.clone());
+                calls.len()
+            };
+            let failed = self.failing.contains(&command.command_type.as_str());
+            CommandOutcome {
+                command_id: Some(format!("cmd-{}", calls)),
+                status: if failed { "error" } else { "success" }.to_string(),
+                error: failed.then(|| "EXECUTION_FAILED: exit code 1".to_string()),
+            }
+        }
+    }
+
+    fn deploy_transaction() -> TransactionRequest {
+        serde_json::from_value(json!({
+            "steps": [
+                { "command_type": "stop_service", "parameters": { "name": "nginx" },
+                  "compensate": { "command_type": "start_service", "parameters": { "name": "nginx" } } },
+                { "command_type": "deploy_file", "parameters": { "path": "/etc/nginx/nginx.conf" },
+                  "compensate": { "command_type": "restore_file", "parameters": { "path": "/etc/nginx/nginx.conf" } } },
+                { "command_type": "reload_service", "parameters": { "name": "nginx" }, "timeout_secs": 60 }
+            ]
+        })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_failed_step_rolls_back_previous_steps_in_reverse() {
+        let runner = ScriptedRunner { failing: vec!["reload_service"], calls: Mutex::new(Vec::new()) };
+        let request = deploy_transaction();
+        assert_eq!(request.validate(), Ok(()));
+        assert_eq!(request.steps[2].command.timeout_secs, Some(60));
+
+        let report = run_transaction(&runner, "a1b2c3d4e5f6", &request).await;
+        assert_eq!(*runner.calls.lock(), ["stop_service", "deploy_file", "reload_service", "restore_file", "start_service"]);
+        assert_eq!(report.status, TransactionStatus::RolledBack);
+        assert_eq!(report.failed_step, Some(2));
+        assert_eq!(report.steps.len(), 3);
+        assert_eq!(report.steps[2].outcome.status, "error");
+        assert_eq!(report.compensations.iter().map(|c| c.step).collect::<Vec<_>>(), [1, 0]);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["status"], "rolled_back");
+        assert_eq!(json["compensations"][0]["command_type"], "restore_file");
+        assert_eq!(json["compensations"][0]["status"], "success");
+    }
+
+    #[tokio::test]
+    async fn test_committed_and_failed_rollback_outcomes() {
+        let runner = ScriptedRunner { failing: vec![], calls: Mutex::new(Vec::new()) };
+        let report = run_transaction(&runner, "a1b2c3d4e5f6", &deploy_transaction()).await;
+        assert_eq!(report.status, TransactionStatus::Committed);
+        assert!(report.compensations.is_empty() && report.failed_step.is_none());
+
+        // La compensation en échec n'empêche pas les suivantes
+        let runner = ScriptedRunner { failing: vec!["reload_service", "restore_file"], calls: Mutex::new(Vec::new()) };
+        let report = run_transaction(&runner, "a1b2c3d4e5f6", &deploy_transaction()).await;
+        assert_eq!(report.status, TransactionStatus::RollbackFailed);
+        assert_eq!(report.compensations.len(), 2);
+        assert!(report.compensations[1].outcome.is_success());
+    }
+
+    #[test]
+    fn test_invalid_transactions_are_rejected() {
+        let parse = |value| serde_json::from_value::<TransactionRequest>(value).unwrap().validate();
+        assert!(parse(json!({ "steps": [] })).is_err());
+        assert!(parse(json!({ "steps": [{ "command_type": " " }] })).is_err());
+        assert!(parse(json!({ "steps": [{ "command_type": "get_metrics", "compensate": { "command_type": "x", "timeout_secs": 0 } }] })).is_err());
+        let too_many: Vec<_> = (0..=MAX_TRANSACTION_STEPS).map(|_| json!({ "command_type": "get_metrics" })).collect();
+        assert!(parse(json!({ "steps": too_many })).is_err());
+        assert!(parse(json!({ "steps": [{ "command_type": "get_metrics" }] })).is_ok());
+    }
+
+    #[test]
+    fn test_firewall_steps_require_admin() {
+        let parse = |value| serde_json::from_value::<TransactionRequest>(value).unwrap();
+        assert!(!deploy_transaction().requires_admin());
+        assert!(parse(json!({ "steps": [{ "command_type": "firewall_add", "parameters": { "rule": { "action": "deny", "port": 22 } } }] })).requires_admin());
+        // Une compensation pare-feu suffit
+        assert!(parse(json!({ "steps": [{ "command_type": "stop_service", "compensate": { "command_type": "firewall_remove" } }] })).requires_admin());
+    }
+}
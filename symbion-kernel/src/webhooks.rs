@@ -0,0 +1,250 @@
+/**
+ * WEBHOOK RELAY - Transfert MQTT → HTTP vers des endpoints externes
+ *
+ * RÔLE :
+ * Permet aux intégrateurs de recevoir des événements MQTT choisis sur leurs propres
+ * endpoints HTTP, sans écrire de plugin.
+ *
+ * FONCTIONNEMENT :
+ * - Config kernel.yaml : webhooks: [{ topic_filter, url, method }]
+ * - Client MQTT dédié abonné à chaque filtre (wildcards MQTT + et #), réabonné à chaque
+ *   ConnAck : un redémarrage du broker ne rend pas le relais muet
+ * - Chaque message correspondant → requête HTTP (payload brut en body,
+ *   header x-symbion-topic), avec retry et backoff exponentiel
+ * - Un webhook lent n'en bloque pas un autre (une tâche par envoi)
+ *
+ * EXEMPLE KERNEL.YAML :
+ * ```yaml
+ * webhooks:
+ *   - topic_filter: "symbion/agents/alert@v1"
+ *     url: "https://hooks.example.org/symbion"
+ *     method: "POST"
+ * ```
+ */
+
+use crate::config::{HostsConfig, WebhookConf};
+use crate::state::Shared;
+use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
+use std::time::Duration;
+
+/// Politique de retry des envois HTTP
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_attempts: 3, base_delay: Duration::from_secs(1) }
+    }
+}
+
+/// Vérifie si un topic correspond à un filtre MQTT (`+` = un niveau, `#` = reste)
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut filter_levels = filter.split('/');
+    let mut topic_levels = topic.split('/');
+    loop {
+        match (filter_levels.next(), topic_levels.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(f), Some(t)) if f == t => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Envoie un message vers un webhook, avec retry ; retourne le statut HTTP final
+pub async fn forward_message(
+    http: &reqwest::Client,
+    hook: &WebhookConf,
+    topic: &str,
+    payload: &[u8],
+    retry: RetryPolicy,
+) -> Result<u16, String> {
+    let method = reqwest::Method::from_bytes(hook.method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("invalid method {}", hook.method))?;
+    let content_type = if serde_json::from_slice::<serde_json::Value>(payload).is_ok() {
+        "application/json"
+    } else {
+        "application/octet-stream"
+    };
+
+    let mut last_error = String::new();
+    for attempt in 1..=retry.max_attempts.max(1) {
+        let result = http.request(method.clone(), &hook.url)
+            .header("content-type", content_type)
+            .header("x-symbion-topic", topic)
+            .body(payload.to_vec())
+            .send()
+            .await;
+
+        match result {
+            Ok(response) if response.status().is_success() => return Ok(response.status().as_u16()),
+            // 4xx : inutile de réessayer, la requête elle-même est refusée
+            Ok(response) if response.status().is_client_error() => {
+                return Err(format!("rejected with {}", response.status()));
+            }
+            Ok(response) => last_error = format!("status {}", response.status()),
+            Err(e) => last_error = e.to_string(),
+        }
+
+        if attempt < retry.max_attempts {
+            tokio::time::sleep(retry.base_delay * 2u32.pow(attempt - 1)).await;
+        }
+    }
+    Err(last_error)
+}
+
+/// Transfère un message à tous les webhooks dont le filtre correspond
+pub fn relay_message(http: &reqwest::Client, hooks: &[WebhookConf], topic: &str, payload: &[u8], retry: RetryPolicy) -> usize {
+    let mut dispatched = 0;
+    for hook in hooks.iter().filter(|h| topic_matches(&h.topic_filter, topic)) {
+        let (http, hook, topic, payload) = (http.clone(), hook.clone(), topic.to_string(), payload.to_vec());
+        tokio::spawn(async move {
+            if let Err(e) = forward_message(&http, &hook, &topic, &payload, retry).await {
+                eprintln!("[webhooks] {} -> {} failed: {}", topic, hook.url, e);
+            }
+        });
+        dispatched += 1;
+    }
+    dispatched
+}
+
+/// Nom du client webhooks dans l'inspection des abonnements
+pub const WEBHOOKS_CLIENT: &str = "webhooks";
+
+/// Démarre le relais MQTT → HTTP si des webhooks sont configurés
+pub fn spawn_webhook_relay(config: Shared<HostsConfig>, health_tracker: crate::health::HealthTracker) {
+    let cfg = config.lock().clone();
+    if cfg.webhooks.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mqtt_cfg = cfg.mqtt.clone().unwrap_or_else(|| crate::config::MqttConf {
+            host: "localhost".into(),
+            port: 1883,
+        });
+        let mut opts = MqttOptions::new("symbion-kernel-webhooks", &mqtt_cfg.host, mqtt_cfg.port);
+        opts.set_keep_alive(Duration::from_secs(15));
+        let (client, mut eventloop) = AsyncClient::new(opts, 10);
+        println!("[webhooks] relaying {} webhook(s)", cfg.webhooks.len());
+
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+
+        loop {
+            match eventloop.poll().await {
+                // (Ré)abonnement à chaque connexion : une session propre ne garde pas les abonnements
+                Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                    for hook in &cfg.webhooks {
+                        if let Err(e) = client.try_subscribe(&hook.topic_filter, QoS::AtLeastOnce) {
+                            eprintln!("[webhooks] subscribe {} failed: {:?}", hook.topic_filter, e);
+                        } else {
+                            health_tracker.record_subscription(WEBHOOKS_CLIENT, &hook.topic_filter);
+                        }
+                    }
+                }
+                Ok(Event::Incoming(Incoming::Publish(p))) => {
+                    health_tracker.record_topic_message(WEBHOOKS_CLIENT, &p.topic);
+                    relay_message(&http, &cfg.webhooks, &p.topic, &p.payload, RetryPolicy::default());
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("[webhooks] MQTT erreur: {:?}", e);
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, http::{HeaderMap, StatusCode}, routing::post, Router};
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[derive(Clone, Default)]
+    struct MockServer {
+        received: Arc<Mutex<Vec<(String, String)>>>,
+        failures_left: Arc<AtomicU32>,
+    }
+
+    async fn mock_handler(State(mock): State<MockServer>, headers: HeaderMap, body: String) -> StatusCode {
+        if mock.failures_left.load(Ordering::SeqCst) > 0 {
+            mock.failures_left.fetch_sub(1, Ordering::SeqCst);
+            return StatusCode::SERVICE_UNAVAILABLE;
+        }
+        let topic = headers.get("x-symbion-topic").and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+        mock.received.lock().push((topic, body));
+        StatusCode::OK
+    }
+
+    async fn start_mock(failures: u32) -> (MockServer, String) {
+        let mock = MockServer::default();
+        mock.failures_left.store(failures, Ordering::SeqCst);
+        let app = Router::new().route("/hook", post(mock_handler)).with_state(mock.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (mock, url)
+    }
+
+    fn fast_retry() -> RetryPolicy {
+        RetryPolicy { max_attempts: 3, base_delay: Duration::from_millis(10) }
+    }
+
+    #[test]
+    fn test_topic_wildcards() {
+        assert!(topic_matches("symbion/agents/+", "symbion/agents/alert@v1"));
+        assert!(!topic_matches("symbion/agents/+", "symbion/agents/a1b2/alert@v1"));
+        assert!(topic_matches("symbion/#", "symbion/notes/response@v1"));
+        assert!(topic_matches("symbion/kernel/health@v1", "symbion/kernel/health@v1"));
+        assert!(!topic_matches("symbion/kernel/health@v1", "symbion/kernel/health@v2"));
+        assert!(!topic_matches("symbion/+", "symbion/notes/response@v1"));
+    }
+
+    #[tokio::test]
+    async fn test_matching_message_is_posted_to_webhook() {
+        let (mock, url) = start_mock(0).await;
+        let hooks = vec![
+            WebhookConf { topic_filter: "symbion/agents/alert@v1".into(), url: url.clone(), method: "POST".into() },
+            WebhookConf { topic_filter: "symbion/notes/#".into(), url, method: "POST".into() },
+        ];
+        let http = reqwest::Client::new();
+
+        let dispatched = relay_message(&http, &hooks, "symbion/agents/alert@v1", br#"{"level":"high"}"#, fast_retry());
+        assert_eq!(dispatched, 1);
+        assert_eq!(relay_message(&http, &hooks, "symbion/hosts/heartbeat@v2", b"{}", fast_retry()), 0);
+
+        for _ in 0..100 {
+            if !mock.received.lock().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let received = mock.received.lock().clone();
+        assert_eq!(received, vec![("symbion/agents/alert@v1".to_string(), r#"{"level":"high"}"#.to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_forward_retries_on_server_error() {
+        let (mock, url) = start_mock(2).await;
+        let hook = WebhookConf { topic_filter: "#".into(), url, method: "post".into() };
+        let http = reqwest::Client::new();
+
+        let status = forward_message(&http, &hook, "symbion/test", b"ping", fast_retry()).await;
+        assert_eq!(status, Ok(200));
+        assert_eq!(mock.received.lock().len(), 1);
+
+        mock.failures_left.store(5, Ordering::SeqCst);
+        assert!(forward_message(&http, &hook, "symbion/test", b"ping", fast_retry()).await.is_err());
+    }
+}
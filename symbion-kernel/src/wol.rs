@@ -5,10 +5,14 @@
  * Interface entre API REST /wake et commandes système (wakeonlan, etherwake...).
  * 
  * FONCTIONNEMENT : Substitution placeholders {host_id} {mac} → commande shell.
+ * RELAIS : si le kernel n'est pas sur le sous-réseau de la cible, un agent online
+ * du même sous-réseau peut émettre le magic packet localement (commande relay_wake).
  * UTILITÉ : Automation réveil machines, gestion parc informatique à distance.
  */
 
 use crate::config::HostsConfig;
+use crate::agents::AgentsMap;
+use crate::models::HostsMap;
 use axum::http::StatusCode;
 use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
 
@@ -44,18 +48,31 @@ fn parse_broadcast(hint: Option<&str>) -> Ipv4Addr {
     Ipv4Addr::new(255, 255, 255, 255)
 }
 
-/// Envoie le magic packet en UDP broadcast (ports 9 et 7).
-pub async fn trigger_wol_udp(cfg: &HostsConfig, host_id: &str) -> (StatusCode, &'static str) {
-    let Some(host) = cfg.hosts.get(host_id) else {
-        return (StatusCode::NOT_FOUND, "unknown host");
-    };
+/// Cible WOL d'un host legacy : MAC et broadcast effectivement connus
+#[derive(Debug, Clone, PartialEq)]
+pub struct WolTarget {
+    pub mac: String,
+    pub broadcast: Option<String>,
+}
 
-    let mac = match parse_mac(&host.mac) {
+/// Résout la cible WOL : état du host (heartbeat) d'abord, puis config hosts.
+/// La MAC n'est jamais dérivée de l'identifiant du host.
+pub fn resolve_wol_target(states: &HostsMap, cfg: &HostsConfig, host_id: &str) -> Option<WolTarget> {
+    let state = states.get(host_id);
+    let conf = cfg.hosts.get(host_id);
+    let mac = state.and_then(|s| s.mac.clone()).or_else(|| conf.map(|h| h.mac.clone()))?;
+    let broadcast = state.and_then(|s| s.broadcast.clone()).or_else(|| conf.and_then(|h| h.hint.clone()));
+    Some(WolTarget { mac, broadcast })
+}
+
+/// Envoie le magic packet en UDP broadcast (ports 9 et 7).
+pub async fn trigger_wol_udp(target: &WolTarget) -> (StatusCode, &'static str) {
+    let mac = match parse_mac(&target.mac) {
         Ok(m) => m,
         Err(_) => return (StatusCode::BAD_REQUEST, "invalid mac"),
     };
     let pkt = magic_packet(mac);
-    let bcast = parse_broadcast(host.hint.as_deref());
+    let bcast = parse_broadcast(target.broadcast.as_deref());
 
     // socket UDP avec broadcast
     let sock = match UdpSocket::bind(("0.0.0.0", 0)) {
@@ -78,3 +95,195 @@ pub async fn trigger_wol_udp(cfg: &HostsConfig, host_id: &str) -> (StatusCode, &
     }
     if ok { (StatusCode::OK, "ok") } else { (StatusCode::BAD_GATEWAY, "wol failed") }
 }
+
+/// Préfixe supposé pour comparer les sous-réseaux (les agents ne remontent pas leur masque)
+pub const RELAY_SUBNET_PREFIX: u8 = 24;
+
+/// Capacité annoncée par les agents capables de relayer un magic packet
+pub const WOL_RELAY_CAPABILITY: &str = "wol_relay";
+
+/// Vrai si les deux adresses partagent le même réseau pour le préfixe donné
+pub fn same_subnet(a: Ipv4Addr, b: Ipv4Addr, prefix: u8) -> bool {
+    let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - prefix.min(32) as u32) };
+    (u32::from(a) & mask) == (u32::from(b) & mask)
+}
+
+/// Choisit un agent online sur le même sous-réseau que la cible pour relayer le WOL
+/// Exclut la cible elle-même et les interfaces loopback ; un agent relié en filaire au
+/// sous-réseau passe avant un agent en Wi-Fi, puis l'agent vu le plus récemment gagne
+pub fn select_relay_agent(agents: &AgentsMap, target_ip: Ipv4Addr, target_agent_id: Option<&str>) -> Option<String> {
+    agents.values()
+        .filter(|a| Some(a.agent_id.as_str()) != target_agent_id)
+        .filter(|a| a.status.status != "offline")
+        .filter(|a| a.capabilities.iter().any(|c| c == WOL_RELAY_CAPABILITY))
+        .filter_map(|a| {
+            let on_subnet: Vec<_> = a.network.interfaces.iter()
+                .filter(|i| i.interface_type != "loopback")
+                .filter(|i| i.ip.parse::<Ipv4Addr>().is_ok_and(|ip| same_subnet(ip, target_ip, RELAY_SUBNET_PREFIX)))
+                .collect();
+            if on_subnet.is_empty() {
+                return None;
+            }
+            let wired = on_subnet.iter().any(|i| i.interface_type == "ethernet");
+            Some((wired, a))
+        })
+        .max_by_key(|(wired, a)| (*wired, a.last_seen))
+        .map(|(_, a)| a.agent_id.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::{Agent, AgentInterface, AgentNetwork, AgentStatus};
+    use time::OffsetDateTime;
+
+    fn agent(agent_id: &str, ip: &str, status: &str, seen_ago_secs: i64) -> Agent {
+        let now = OffsetDateTime::now_utc();
+        Agent {
+            agent_id: agent_id.to_string(),
+            hostname: format!("host-{}", agent_id),
+            os: "linux".to_string(),
+            architecture: "x86_64".to_string(),
+            os_details: None,
+            capabilities: vec!["system_metrics".to_string(), WOL_RELAY_CAPABILITY.to_string()],
+            unavailable_capabilities: Vec::new(),
+            network: AgentNetwork {
+                primary_mac: "aa:bb:cc:dd:ee:ff".to_string(),
+                interfaces: vec![AgentInterface {
+                    name: "eth0".to_string(),
+                    mac: "aa:bb:cc:dd:ee:ff".to_string(),
+                    ip: ip.to_string(),
+                    interface_type: "ethernet".to_string(),
+                    speed_mbps: Some(1000),
+                    mtu: Some(1500),
+                }],
+            },
+            version: None,
+            heartbeat_version: "v1".to_string(),
+            status: AgentStatus {
+                status: status.to_string(),
+                last_heartbeat: None,
+                system: None,
+                processes: None,
+                services: None,
+                queue_depth: None,
+                busy: None,
+                clock_skew_secs: None,
+                clock_skewed: false,
+                recent_commands: Vec::new(),
+            },
+            last_seen: now - time::Duration::seconds(seen_ago_secs),
+            registration_time: now,
+        }
+    }
+
+    fn agents_map(list: Vec<Agent>) -> AgentsMap {
+        list.into_iter().map(|a| (a.agent_id.clone(), a)).collect()
+    }
+
+    #[test]
+    fn test_same_subnet() {
+        let a: Ipv4Addr = "192.168.1.10".parse().unwrap();
+        assert!(same_subnet(a, "192.168.1.255".parse().unwrap(), 24));
+        assert!(!same_subnet(a, "192.168.2.10".parse().unwrap(), 24));
+        assert!(same_subnet(a, "192.168.2.10".parse().unwrap(), 16));
+    }
+
+    #[test]
+    fn test_select_relay_matches_subnet() {
+        let agents = agents_map(vec![
+            agent("000000000001", "10.0.0.5", "online", 10),
+            agent("000000000002", "192.168.1.20", "online", 10),
+        ]);
+        let target: Ipv4Addr = "192.168.1.44".parse().unwrap();
+        assert_eq!(select_relay_agent(&agents, target, None), Some("000000000002".to_string()));
+    }
+
+    #[test]
+    fn test_select_relay_skips_offline_target_and_incapable() {
+        let mut incapable = agent("000000000004", "192.168.1.22", "online", 1);
+        incapable.capabilities.retain(|c| c != WOL_RELAY_CAPABILITY);
+        let agents = agents_map(vec![
+            agent("000000000001", "192.168.1.44", "offline", 600),
+            agent("000000000002", "192.168.1.20", "offline", 300),
+            agent("000000000003", "192.168.1.21", "online", 30),
+            incapable,
+        ]);
+        let target: Ipv4Addr = "192.168.1.44".parse().unwrap();
+        assert_eq!(select_relay_agent(&agents, target, Some("000000000001")), Some("000000000003".to_string()));
+    }
+
+    #[test]
+    fn test_select_relay_prefers_most_recent_and_none_when_no_match() {
+        let agents = agents_map(vec![
+            agent("000000000002", "192.168.1.20", "online", 50),
+            agent("000000000003", "192.168.1.21", "online", 5),
+        ]);
+        let target: Ipv4Addr = "192.168.1.44".parse().unwrap();
+        assert_eq!(select_relay_agent(&agents, target, None), Some("000000000003".to_string()));
+
+        let elsewhere: Ipv4Addr = "172.16.0.9".parse().unwrap();
+        assert_eq!(select_relay_agent(&agents, elsewhere, None), None);
+    }
+
+    #[test]
+    fn test_select_relay_prefers_wired_and_ignores_loopback() {
+        let mut wireless = agent("000000000002", "192.168.1.20", "online", 1);
+        wireless.network.interfaces[0].interface_type = "wireless".to_string();
+        let mut loopback_only = agent("000000000003", "192.168.1.21", "online", 1);
+        loopback_only.network.interfaces[0].interface_type = "loopback".to_string();
+        let agents = agents_map(vec![
+            wireless,
+            loopback_only,
+            agent("000000000004", "192.168.1.22", "online", 120),
+        ]);
+        let target: Ipv4Addr = "192.168.1.44".parse().unwrap();
+        assert_eq!(select_relay_agent(&agents, target, None), Some("000000000004".to_string()));
+
+        let agents = agents_map(vec![agents["000000000002"].clone(), agents["000000000003"].clone()]);
+        assert_eq!(select_relay_agent(&agents, target, None), Some("000000000002".to_string()));
+    }
+
+    fn host_state(host_id: &str, mac: Option<&str>, broadcast: Option<&str>) -> crate::models::HostState {
+        crate::models::HostState {
+            host_id: host_id.to_string(),
+            last_seen: OffsetDateTime::now_utc(),
+            cpu: None,
+            ram: None,
+            ip: Some("192.168.1.44".to_string()),
+            mac: mac.map(str::to_string),
+            broadcast: broadcast.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_wake_target_uses_stored_mac_not_host_id() {
+        // Identifiant qui ressemble à une MAC : ne doit jamais servir de MAC
+        let host_id = "a1b2c3d4e5f6";
+        let mut states = HostsMap::new();
+        states.insert(host_id.to_string(), host_state(host_id, Some("10:20:30:40:50:60"), Some("192.168.1.255")));
+        let cfg = HostsConfig::default();
+
+        let target = resolve_wol_target(&states, &cfg, host_id).unwrap();
+        assert_eq!(target.mac, "10:20:30:40:50:60");
+        assert_eq!(target.broadcast.as_deref(), Some("192.168.1.255"));
+    }
+
+    #[test]
+    fn test_wake_target_falls_back_to_config() {
+        let mut cfg = HostsConfig::default();
+        cfg.hosts.insert("desktop-w11".to_string(), crate::config::HostConf {
+            mac: "AA:BB:CC:DD:EE:FF".to_string(),
+            hint: Some("192.168.1.255".to_string()),
+        });
+        let mut states = HostsMap::new();
+        states.insert("desktop-w11".to_string(), host_state("desktop-w11", None, None));
+
+        let target = resolve_wol_target(&states, &cfg, "desktop-w11").unwrap();
+        assert_eq!(target.mac, "AA:BB:CC:DD:EE:FF");
+        assert_eq!(target.broadcast.as_deref(), Some("192.168.1.255"));
+
+        // Host inconnu partout : pas de MAC inventée
+        assert_eq!(resolve_wol_target(&states, &cfg, "a1b2c3d4e5f6"), None);
+    }
+}
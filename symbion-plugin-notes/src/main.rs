@@ -6,9 +6,14 @@
  * Remplace le port memo intégré du kernel pour une architecture plus modulaire.
  * 
  * FONCTIONNEMENT :
- * - Stockage JSON local (./notes.json)
- * - Écoute MQTT : create, list, delete, update notes
+ * - Stockage JSON local (NOTES_STORAGE_PATH, défaut ./notes.json)
+ * - Sharding mensuel optionnel (NOTES_SHARDING=monthly) : notes-YYYY-MM.json,
+ *   seule la shard concernée est réécrite, la lecture fusionne les shards
+ * - Écoute MQTT : create, list, delete, update, dedup, stats, backlinks notes
+ * - Liens entre notes (`links`) : les IDs référencés doivent exister à la création/modification ;
+ *   supprimer une note retire son ID des liens des notes qui la citent
  * - Répond sur MQTT : résultats des opérations
+ * - Santé publiée toutes les HEALTH_INTERVAL (stockage accessible en écriture, nombre de notes)
  * 
  * UTILITÉ DANS SYMBION :
  * 🎯 Découplement : Notes séparées du kernel central
@@ -18,20 +23,26 @@
  * 
  * COMMUNICATION MQTT :
  * Écoute: symbion/notes/create@v1, symbion/notes/list@v1
- * Publie: symbion/notes/response@v1
+ * Publie: symbion/notes/response@v1, symbion/plugins/health@v1
  */
 
 use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use time::OffsetDateTime;
 use tokio::time::{sleep, Duration};
 use uuid::Uuid;
 use parking_lot::Mutex;
 use std::sync::Arc;
 
+/// Nom du plugin (manifeste plugins/symbion-plugin-notes.json)
+const PLUGIN_NAME: &str = "notes-manager";
+
+/// Période de publication de la santé du plugin
+const HEALTH_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Structure des données de note (identique au kernel)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NoteContent {
@@ -45,6 +56,9 @@ pub struct NoteContent {
     pub tags: Option<Vec<String>>,
     /// Statut de la note (pending, done, archived)
     pub status: Option<String>,
+    /// IDs des notes référencées par celle-ci
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<Vec<String>>,
 }
 
 /// Structure complète d'une note avec métadonnées
@@ -85,6 +99,139 @@ pub enum NoteCommand {
         id: String,
         note: NoteContent 
     },
+    #[serde(rename = "dedup")]
+    Dedup {
+        request_id: String,
+        /// Simulation : rapporte les doublons sans rien supprimer
+        dry_run: Option<bool>,
+    },
+    #[serde(rename = "stats")]
+    Stats {
+        request_id: String,
+        /// Nombre de jours d'historique pour les créations par jour (défaut 7)
+        days: Option<u32>,
+    },
+    #[serde(rename = "backlinks")]
+    Backlinks {
+        request_id: String,
+        /// Note dont on cherche les références entrantes
+        id: String,
+    },
+}
+
+/// Rapport de déduplication des notes
+#[derive(Debug, Serialize)]
+pub struct DedupReport {
+    pub dry_run: bool,
+    /// Nombre de notes supprimées (ou qui le seraient en dry-run)
+    pub removed: usize,
+    pub removed_ids: Vec<String>,
+    /// IDs des notes conservées (la plus ancienne de chaque groupe)
+    pub kept_ids: Vec<String>,
+}
+
+/// Nombre de jours d'historique par défaut pour les statistiques
+const DEFAULT_STATS_DAYS: u32 = 7;
+
+/// Nombre maximum de jours d'historique pour les statistiques
+const MAX_STATS_DAYS: u32 = 365;
+
+/// Créations de notes pour un jour donné (UTC)
+#[derive(Debug, Serialize, PartialEq)]
+pub struct DayCount {
+    pub date: String,
+    pub count: usize,
+}
+
+/// Statistiques agrégées des notes
+#[derive(Debug, Serialize)]
+pub struct NoteStats {
+    pub total: usize,
+    pub urgent: usize,
+    pub by_status: HashMap<String, usize>,
+    pub by_context: HashMap<String, usize>,
+    /// Créations par jour sur les N derniers jours, du plus ancien à aujourd'hui
+    pub created_per_day: Vec<DayCount>,
+}
+
+/// Calcule les statistiques des notes ; `today` inclus dans la fenêtre de `days` jours
+fn compute_stats(notes: &[Note], today: time::Date, days: u32) -> NoteStats {
+    let days = days.clamp(1, MAX_STATS_DAYS);
+    let first_day = today - time::Duration::days(days as i64 - 1);
+
+    let mut per_day = vec![0usize; days as usize];
+    let mut stats = NoteStats {
+        total: notes.len(),
+        urgent: 0,
+        by_status: HashMap::new(),
+        by_context: HashMap::new(),
+        created_per_day: Vec::new(),
+    };
+
+    for note in notes {
+        if note.data.urgent.unwrap_or(false) {
+            stats.urgent += 1;
+        }
+        let status = note.data.status.clone().unwrap_or_else(|| "pending".to_string());
+        *stats.by_status.entry(status).or_default() += 1;
+        let context = note.data.context.clone().unwrap_or_else(|| "none".to_string());
+        *stats.by_context.entry(context).or_default() += 1;
+
+        let day = note.timestamp.to_offset(time::UtcOffset::UTC).date();
+        if day >= first_day && day <= today {
+            per_day[(day - first_day).whole_days() as usize] += 1;
+        }
+    }
+
+    stats.created_per_day = per_day.into_iter()
+        .enumerate()
+        .map(|(i, count)| DayCount {
+            date: (first_day + time::Duration::days(i as i64)).to_string(),
+            count,
+        })
+        .collect();
+    stats
+}
+
+/// Normalise le contenu pour la détection de doublons (casse + espaces)
+fn normalize_content(content: &str) -> String {
+    content.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Fusionne les tags sans doublon en conservant l'ordre d'apparition
+fn merge_tags(target: &mut Option<Vec<String>>, extra: &Option<Vec<String>>) {
+    let Some(extra) = extra else { return };
+    let tags = target.get_or_insert_with(Vec::new);
+    for tag in extra {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+}
+
+/// Préfixe des erreurs de lien vers une note inexistante (400 côté kernel)
+const DANGLING_LINK_ERROR: &str = "Linked note not found";
+
+/// Vérifie que chaque lien référence une note existante ; `self_id` (note modifiée)
+/// ne peut pas se référencer elle-même
+fn validate_links(notes: &[Note], links: &[String], self_id: Option<&str>) -> Result<(), String> {
+    for link in links {
+        if Some(link.as_str()) == self_id || !notes.iter().any(|note| &note.id == link) {
+            return Err(format!("{}: {}", DANGLING_LINK_ERROR, link));
+        }
+    }
+    Ok(())
+}
+
+/// Notes qui référencent `id` dans leurs liens, dans l'ordre de stockage
+fn compute_backlinks(notes: &[Note], id: &str) -> Vec<Note> {
+    notes.iter()
+        .filter(|note| note.data.links.as_ref().is_some_and(|links| links.iter().any(|l| l == id)))
+        .cloned()
+        .collect()
 }
 
 /// Réponses MQTT pour les résultats d'opérations
@@ -105,58 +252,174 @@ pub enum NoteResponse {
     },
 }
 
+/// Organisation des fichiers de stockage
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageLayout {
+    /// Toutes les notes dans un seul fichier JSON
+    SingleFile(PathBuf),
+    /// Un fichier par mois de création dans le répertoire : notes-YYYY-MM.json
+    Monthly(PathBuf),
+}
+
+impl StorageLayout {
+    /// Lit NOTES_STORAGE_PATH (ou SYMBION_NOTES_STORAGE) et NOTES_SHARDING
+    pub fn from_env() -> Self {
+        let path = std::env::var("NOTES_STORAGE_PATH")
+            .or_else(|_| std::env::var("SYMBION_NOTES_STORAGE"))
+            .ok()
+            .filter(|p| !p.trim().is_empty());
+
+        match std::env::var("NOTES_SHARDING").as_deref() {
+            Ok("monthly") => StorageLayout::Monthly(PathBuf::from(path.unwrap_or_else(|| "./notes".into()))),
+            _ => StorageLayout::SingleFile(PathBuf::from(path.unwrap_or_else(|| "./notes.json".into()))),
+        }
+    }
+
+    /// Fichier unique ou répertoire des shards
+    fn root(&self) -> &Path {
+        match self {
+            StorageLayout::SingleFile(path) | StorageLayout::Monthly(path) => path,
+        }
+    }
+
+    /// Fichier (shard) qui contient une note créée à `timestamp`
+    fn shard_for(&self, timestamp: OffsetDateTime) -> PathBuf {
+        match self {
+            StorageLayout::SingleFile(path) => path.clone(),
+            StorageLayout::Monthly(dir) => {
+                let utc = timestamp.to_offset(time::UtcOffset::UTC);
+                dir.join(format!("notes-{:04}-{:02}.json", utc.year(), utc.month() as u8))
+            }
+        }
+    }
+}
+
 /// Gestionnaire de stockage des notes (similaire au port memo)
 #[derive(Debug)]
 pub struct NotesStorage {
-    /// Cache mémoire des notes
+    /// Cache mémoire des notes (toutes shards fusionnées)
     notes: Arc<Mutex<Vec<Note>>>,
-    /// Chemin du fichier de stockage
-    storage_path: PathBuf,
+    /// Index id -> shard contenant la note
+    index: Arc<Mutex<HashMap<String, PathBuf>>>,
+    /// Organisation des fichiers de stockage
+    layout: StorageLayout,
 }
 
 impl NotesStorage {
-    /// Crée un nouveau gestionnaire de notes
+    /// Crée un nouveau gestionnaire de notes sur un fichier unique
     pub fn new<P: Into<PathBuf>>(storage_path: P) -> Result<Self, Box<dyn std::error::Error>> {
-        let path = storage_path.into();
+        Self::with_layout(StorageLayout::SingleFile(storage_path.into()))
+    }
+
+    /// Crée un gestionnaire de notes avec l'organisation de stockage donnée
+    pub fn with_layout(layout: StorageLayout) -> Result<Self, Box<dyn std::error::Error>> {
         let mut storage = NotesStorage {
             notes: Arc::new(Mutex::new(Vec::new())),
-            storage_path: path,
+            index: Arc::new(Mutex::new(HashMap::new())),
+            layout,
         };
         
         // Charger les notes existantes du disque
         storage.load_from_disk()?;
         
-        eprintln!("[notes] storage initialized at {:?}", storage.storage_path);
+        eprintln!("[notes] storage initialized at {:?}", storage.layout);
         Ok(storage)
     }
     
-    /// Charge les notes depuis le fichier JSON
+    /// Charge les notes depuis le(s) fichier(s) JSON
     fn load_from_disk(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        if !self.storage_path.exists() {
-            // Créer fichier vide si inexistant
-            fs::write(&self.storage_path, "[]")?;
-            eprintln!("[notes] created empty storage file");
-            return Ok(());
-        }
+        let shards: Vec<PathBuf> = match &self.layout {
+            StorageLayout::SingleFile(path) => {
+                if !path.exists() {
+                    // Créer fichier vide si inexistant
+                    fs::write(path, "[]")?;
+                    eprintln!("[notes] created empty storage file");
+                    return Ok(());
+                }
+                vec![path.clone()]
+            }
+            StorageLayout::Monthly(dir) => {
+                fs::create_dir_all(dir)?;
+                let mut shards: Vec<PathBuf> = fs::read_dir(dir)?
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| path.file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| n.starts_with("notes-") && n.ends_with(".json")))
+                    .collect();
+                shards.sort();
+                shards
+            }
+        };
         
-        let content = fs::read_to_string(&self.storage_path)?;
-        let loaded_notes: Vec<Note> = serde_json::from_str(&content)?;
+        let mut loaded_notes: Vec<Note> = Vec::new();
+        let mut index = HashMap::new();
+        for shard in shards {
+            let content = fs::read_to_string(&shard)?;
+            let shard_notes: Vec<Note> = serde_json::from_str(&content)?;
+            for note in &shard_notes {
+                index.insert(note.id.clone(), shard.clone());
+            }
+            loaded_notes.extend(shard_notes);
+        }
         
         *self.notes.lock() = loaded_notes;
+        *self.index.lock() = index;
         eprintln!("[notes] loaded {} notes from disk", self.notes.lock().len());
         Ok(())
     }
     
-    /// Sauvegarde les notes sur disque
-    fn save_to_disk(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Réécrit une shard avec les notes qui lui appartiennent
+    fn save_shard(&self, shard: &Path) -> Result<(), Box<dyn std::error::Error>> {
         let notes = self.notes.lock();
-        let content = serde_json::to_string_pretty(&*notes)?;
-        fs::write(&self.storage_path, content)?;
+        let shard_notes: Vec<&Note> = notes.iter()
+            .filter(|note| self.layout.shard_for(note.timestamp) == shard)
+            .collect();
+        let content = serde_json::to_string_pretty(&shard_notes)?;
+        fs::write(shard, content)?;
         Ok(())
     }
     
+    /// Vérifie que le stockage accepte les écritures, sans modifier les notes
+    fn check_writable(&self) -> Result<(), String> {
+        let root = self.layout.root();
+        let result = match &self.layout {
+            StorageLayout::SingleFile(path) => fs::OpenOptions::new().append(true).open(path).map(|_| ()),
+            StorageLayout::Monthly(dir) => {
+                // Fichier sonde : les shards du mois suivant doivent pouvoir être créées
+                let probe = dir.join(format!(".health-{}", Uuid::new_v4()));
+                fs::write(&probe, b"").and_then(|_| fs::remove_file(&probe))
+            }
+        };
+        result.map_err(|e| format!("{}: {}", root.display(), e))
+    }
+
+    /// Rapport symbion/plugins/health@v1 : état du stockage et volume de notes
+    pub fn health_report(&self) -> serde_json::Value {
+        let storage = self.check_writable();
+        let status = if storage.is_ok() { "healthy" } else { "unhealthy" };
+        let mut storage_check = serde_json::json!({ "status": status });
+        if let Err(e) = &storage {
+            storage_check["message"] = serde_json::Value::from(e.as_str());
+        }
+        serde_json::json!({
+            "plugin": PLUGIN_NAME,
+            "status": status,
+            "checks": { "storage": storage_check },
+            "details": {
+                "notes_count": self.notes.lock().len(),
+                "storage": self.layout.root().display().to_string(),
+            },
+            "timestamp": OffsetDateTime::now_utc()
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default(),
+        })
+    }
+
     /// Crée une nouvelle note
     pub fn create_note(&self, content: NoteContent) -> Result<Note, Box<dyn std::error::Error>> {
+        if let Some(links) = &content.links {
+            validate_links(&self.notes.lock(), links, None)?;
+        }
         let note = Note {
             id: Uuid::new_v4().to_string(),
             timestamp: OffsetDateTime::now_utc(),
@@ -164,13 +427,20 @@ impl NotesStorage {
             metadata: HashMap::new(),
         };
         
-        self.notes.lock().push(note.clone());
-        self.save_to_disk()?;
+        self.insert_note(note.clone())?;
         
         eprintln!("[notes] created note {}", note.id);
         Ok(note)
     }
     
+    /// Ajoute une note dans la shard correspondant à sa date de création
+    fn insert_note(&self, note: Note) -> Result<(), Box<dyn std::error::Error>> {
+        let shard = self.layout.shard_for(note.timestamp);
+        self.index.lock().insert(note.id.clone(), shard.clone());
+        self.notes.lock().push(note);
+        self.save_shard(&shard)
+    }
+    
     /// Liste les notes avec filtrage optionnel
     pub fn list_notes(&self, filters: Option<HashMap<String, serde_json::Value>>) -> Vec<Note> {
         let notes = self.notes.lock();
@@ -185,26 +455,45 @@ impl NotesStorage {
         }
     }
     
-    /// Supprime une note par ID
+    /// Supprime une note par ID, ainsi que les liens qui la référencent
     pub fn delete_note(&self, id: &str) -> Result<bool, Box<dyn std::error::Error>> {
-        let mut notes = self.notes.lock();
-        let initial_len = notes.len();
-        notes.retain(|note| note.id != id);
+        let Some(shard) = self.index.lock().remove(id) else {
+            return Ok(false);
+        };
+        // Lien laissé pendant : la prochaine modification de la note qui le porte serait refusée
+        let mut shards = vec![shard];
+        {
+            let mut notes = self.notes.lock();
+            notes.retain(|note| note.id != id);
+            for note in notes.iter_mut() {
+                let Some(links) = note.data.links.as_mut() else { continue };
+                if links.iter().any(|link| link == id) {
+                    links.retain(|link| link != id);
+                    let linked_shard = self.layout.shard_for(note.timestamp);
+                    if !shards.contains(&linked_shard) {
+                        shards.push(linked_shard);
+                    }
+                }
+            }
+        }
         
-        if notes.len() < initial_len {
-            drop(notes); // Libérer le verrou avant save_to_disk
-            self.save_to_disk()?;
-            eprintln!("[notes] deleted note {}", id);
-            Ok(true)
-        } else {
-            Ok(false)
+        for shard in &shards {
+            self.save_shard(shard)?;
         }
+        eprintln!("[notes] deleted note {}", id);
+        Ok(true)
     }
     
     /// Met à jour une note existante
     pub fn update_note(&self, id: &str, new_content: NoteContent) -> Result<Option<Note>, Box<dyn std::error::Error>> {
         let mut notes = self.notes.lock();
         
+        if let Some(links) = &new_content.links {
+            if notes.iter().any(|note| note.id == id) {
+                validate_links(&notes, links, Some(id))?;
+            }
+        }
+        
         if let Some(note) = notes.iter_mut().find(|note| note.id == id) {
             note.data = new_content;
             // Garder timestamp original mais pouvoir ajouter last_modified
@@ -214,7 +503,7 @@ impl NotesStorage {
             let updated_note = note.clone();
             drop(notes); // Libérer le verrou
             
-            self.save_to_disk()?;
+            self.save_shard(&self.layout.shard_for(updated_note.timestamp))?;
             eprintln!("[notes] updated note {}", id);
             Ok(Some(updated_note))
         } else {
@@ -222,6 +511,81 @@ impl NotesStorage {
         }
     }
     
+    /// Notes qui référencent la note `id` ; None si la note n'existe pas
+    pub fn backlinks(&self, id: &str) -> Option<Vec<Note>> {
+        let notes = self.notes.lock();
+        if !notes.iter().any(|note| note.id == id) {
+            return None;
+        }
+        Some(compute_backlinks(&notes, id))
+    }
+
+    /// Statistiques des notes (totaux, répartition, créations par jour)
+    pub fn stats(&self, days: u32) -> NoteStats {
+        let notes = self.notes.lock();
+        compute_stats(&notes, OffsetDateTime::now_utc().date(), days)
+    }
+
+    /// Regroupe les notes au contenu identique (normalisé) : garde la plus ancienne,
+    /// fusionne les tags des doublons dans celle-ci et supprime les autres
+    pub fn dedup_notes(&self, dry_run: bool) -> Result<DedupReport, Box<dyn std::error::Error>> {
+        let mut notes = self.notes.lock();
+
+        // Groupes d'indices par contenu normalisé, dans l'ordre d'apparition
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of: HashMap<String, usize> = HashMap::new();
+        for (index, note) in notes.iter().enumerate() {
+            let key = normalize_content(&note.data.content);
+            match group_of.get(&key) {
+                Some(&g) => groups[g].push(index),
+                None => {
+                    group_of.insert(key, groups.len());
+                    groups.push(vec![index]);
+                }
+            }
+        }
+
+        let mut report = DedupReport { dry_run, removed: 0, removed_ids: Vec::new(), kept_ids: Vec::new() };
+        for group in groups.iter().filter(|g| g.len() > 1) {
+            let &oldest = group.iter().min_by_key(|&&i| notes[i].timestamp).unwrap();
+            let mut tags = notes[oldest].data.tags.clone();
+            for &i in group.iter().filter(|&&i| i != oldest) {
+                merge_tags(&mut tags, &notes[i].data.tags);
+                report.removed_ids.push(notes[i].id.clone());
+            }
+            report.kept_ids.push(notes[oldest].id.clone());
+            if !dry_run {
+                notes[oldest].data.tags = tags;
+            }
+        }
+        report.removed = report.removed_ids.len();
+
+        if !dry_run && report.removed > 0 {
+            // Shards touchées : celles des notes conservées (tags) et supprimées
+            let mut shards: Vec<PathBuf> = notes.iter()
+                .filter(|note| report.kept_ids.contains(&note.id) || report.removed_ids.contains(&note.id))
+                .map(|note| self.layout.shard_for(note.timestamp))
+                .collect();
+            shards.sort();
+            shards.dedup();
+
+            notes.retain(|note| !report.removed_ids.contains(&note.id));
+            drop(notes); // Libérer le verrou avant save_shard
+            {
+                let mut index = self.index.lock();
+                for id in &report.removed_ids {
+                    index.remove(id);
+                }
+            }
+            for shard in &shards {
+                self.save_shard(shard)?;
+            }
+            eprintln!("[notes] dedup removed {} duplicate notes", report.removed);
+        }
+
+        Ok(report)
+    }
+
     /// Vérifie si une note correspond aux filtres
     fn matches_filters(&self, note: &Note, filters: &HashMap<String, serde_json::Value>) -> bool {
         for (key, value) in filters {
@@ -269,7 +633,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     eprintln!("[notes] symbion plugin notes starting...");
     
     // Initialisation du stockage
-    let storage = NotesStorage::new("./notes.json")?;
+    let storage = NotesStorage::with_layout(StorageLayout::from_env())?;
     let storage = Arc::new(storage);
     
     // Configuration MQTT
@@ -279,15 +643,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let (client, mut eventloop) = AsyncClient::new(mqttopts, 10);
     
     // S'abonner aux topics de commandes
-    client.subscribe("symbion/notes/command@v1", QoS::AtLeastOnce).await?;
+    client.subscribe(symbion_topics::notes_command(), QoS::AtLeastOnce).await?;
     
     eprintln!("[notes] connected to MQTT, listening for commands...");
+
+    // Santé du plugin, agrégée par le kernel dans /system/health
+    tokio::spawn(publish_health(client.clone(), storage.clone()));
     
     // Boucle principale de traitement des messages
     loop {
         match eventloop.poll().await {
             Ok(Event::Incoming(Incoming::Publish(publish))) => {
-                if publish.topic == "symbion/notes/command@v1" {
+                if publish.topic == symbion_topics::notes_command() {
                     handle_command(&client, &storage, &publish.payload).await;
                 }
             }
@@ -302,27 +669,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 }
 
+/// Publie périodiquement la santé du plugin
+async fn publish_health(client: AsyncClient, storage: Arc<NotesStorage>) {
+    let mut interval = tokio::time::interval(HEALTH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let report = storage.health_report();
+        if let Err(e) = client
+            .publish(symbion_topics::plugins_health(), QoS::AtLeastOnce, false, report.to_string())
+            .await
+        {
+            eprintln!("[notes] failed to publish health: {:?}", e);
+        }
+    }
+}
+
 /// Traite une commande MQTT reçue
 async fn handle_command(
     client: &AsyncClient,
     storage: &NotesStorage,
     payload: &[u8],
 ) {
-    let command_result: Result<NoteCommand, _> = serde_json::from_slice(payload);
-    
-    let response = match command_result {
-        Ok(command) => process_command(storage, command).await,
-        Err(e) => NoteResponse::Error {
-            request_id: "unknown".to_string(),
-            action: "parse".to_string(),
-            error: format!("Invalid command JSON: {}", e),
-        },
-    };
+    let response = respond_to_payload(storage, payload).await;
     
     // Publier la réponse
     if let Ok(response_json) = serde_json::to_string(&response) {
         if let Err(e) = client
-            .publish("symbion/notes/response@v1", QoS::AtLeastOnce, false, response_json)
+            .publish(symbion_topics::notes_response(), QoS::AtLeastOnce, false, response_json)
             .await
         {
             eprintln!("[notes] failed to publish response: {:?}", e);
@@ -330,6 +703,34 @@ async fn handle_command(
     }
 }
 
+/// Réponse à un payload brut : toujours une NoteResponse, même pour un JSON invalide
+async fn respond_to_payload(storage: &NotesStorage, payload: &[u8]) -> NoteResponse {
+    match serde_json::from_slice::<NoteCommand>(payload) {
+        Ok(command) => process_command(storage, command).await,
+        Err(e) => NoteResponse::Error {
+            request_id: "unknown".to_string(),
+            action: "parse".to_string(),
+            error: format!("Invalid command JSON: {}", e),
+        },
+    }
+}
+
+/// Vérifie le type des filtres connus (un filtre mal typé ne doit pas être ignoré)
+fn validate_filters(filters: &HashMap<String, serde_json::Value>) -> Result<(), String> {
+    for (key, value) in filters {
+        let valid = match key.as_str() {
+            "urgent" => value.is_boolean(),
+            "context" => value.is_string(),
+            "tags" => value.as_array().is_some_and(|tags| tags.iter().all(|t| t.is_string())),
+            _ => true,
+        };
+        if !valid {
+            return Err(format!("Invalid filter '{}': {}", key, value));
+        }
+    }
+    Ok(())
+}
+
 /// Traite une commande et génère une réponse
 async fn process_command(
     storage: &NotesStorage,
@@ -352,6 +753,9 @@ async fn process_command(
         }
         
         NoteCommand::List { request_id, filters } => {
+            if let Some(Err(error)) = filters.as_ref().map(validate_filters) {
+                return NoteResponse::Error { request_id, action: "list".to_string(), error };
+            }
             let notes = storage.list_notes(filters);
             NoteResponse::Success {
                 request_id,
@@ -399,5 +803,320 @@ async fn process_command(
                 },
             }
         }
+        
+        NoteCommand::Dedup { request_id, dry_run } => {
+            match storage.dedup_notes(dry_run.unwrap_or(false)) {
+                Ok(report) => NoteResponse::Success {
+                    request_id,
+                    action: "dedup".to_string(),
+                    data: serde_json::to_value(report).unwrap_or_default(),
+                },
+                Err(e) => NoteResponse::Error {
+                    request_id,
+                    action: "dedup".to_string(),
+                    error: e.to_string(),
+                },
+            }
+        }
+        
+        NoteCommand::Stats { request_id, days } => {
+            let stats = storage.stats(days.unwrap_or(DEFAULT_STATS_DAYS));
+            NoteResponse::Success {
+                request_id,
+                action: "stats".to_string(),
+                data: serde_json::to_value(stats).unwrap_or_default(),
+            }
+        }
+        
+        NoteCommand::Backlinks { request_id, id } => {
+            match storage.backlinks(&id) {
+                Some(notes) => NoteResponse::Success {
+                    request_id,
+                    action: "backlinks".to_string(),
+                    data: serde_json::to_value(notes).unwrap_or_default(),
+                },
+                None => NoteResponse::Error {
+                    request_id,
+                    action: "backlinks".to_string(),
+                    error: "Note not found".to_string(),
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content(text: &str, tags: &[&str]) -> NoteContent {
+        NoteContent {
+            content: text.to_string(),
+            urgent: None,
+            context: None,
+            tags: Some(tags.iter().map(|t| t.to_string()).collect()),
+            status: None,
+            links: None,
+        }
+    }
+
+    fn temp_storage() -> (NotesStorage, PathBuf) {
+        let path = std::env::temp_dir().join(format!("symbion-notes-{}.json", Uuid::new_v4()));
+        (NotesStorage::new(&path).unwrap(), path)
+    }
+
+    #[test]
+    fn test_normalize_content() {
+        assert_eq!(normalize_content("  Acheter   du PAIN\n"), "acheter du pain");
+        assert_eq!(normalize_content("acheter du pain"), normalize_content("Acheter\tdu  Pain "));
+        assert_ne!(normalize_content("acheter du pain"), normalize_content("acheter des pains"));
+    }
+
+    #[test]
+    fn test_merge_tags() {
+        let mut tags = Some(vec!["courses".to_string(), "maison".to_string()]);
+        merge_tags(&mut tags, &Some(vec!["maison".to_string(), "urgent".to_string()]));
+        assert_eq!(tags.unwrap(), vec!["courses", "maison", "urgent"]);
+
+        let mut none = None;
+        merge_tags(&mut none, &Some(vec!["perso".to_string()]));
+        assert_eq!(none.unwrap(), vec!["perso"]);
+    }
+
+    #[test]
+    fn test_dedup_keeps_oldest_and_merges_tags() {
+        let (storage, path) = temp_storage();
+        let oldest = storage.create_note(content("Appeler le garage", &["auto"])).unwrap();
+        storage.create_note(content("appeler  le GARAGE ", &["urgent"])).unwrap();
+        storage.create_note(content("Autre note", &[])).unwrap();
+
+        let preview = storage.dedup_notes(true).unwrap();
+        assert_eq!(preview.removed, 1);
+        assert_eq!(storage.list_notes(None).len(), 3);
+
+        let report = storage.dedup_notes(false).unwrap();
+        assert_eq!(report.removed, 1);
+        assert_eq!(report.kept_ids, vec![oldest.id.clone()]);
+
+        let notes = storage.list_notes(None);
+        assert_eq!(notes.len(), 2);
+        let kept = notes.iter().find(|n| n.id == oldest.id).unwrap();
+        assert_eq!(kept.data.tags.as_ref().unwrap(), &vec!["auto".to_string(), "urgent".to_string()]);
+
+        let _ = fs::remove_file(path);
+    }
+
+    fn note_at(timestamp: OffsetDateTime, status: Option<&str>, context: Option<&str>, urgent: bool) -> Note {
+        Note {
+            id: Uuid::new_v4().to_string(),
+            timestamp,
+            data: NoteContent {
+                content: "note".to_string(),
+                urgent: Some(urgent),
+                context: context.map(|c| c.to_string()),
+                tags: None,
+                status: status.map(|s| s.to_string()),
+                links: None,
+            },
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_stats_buckets_across_day_boundaries() {
+        use time::macros::{date, datetime};
+
+        let notes = vec![
+            // Juste avant minuit UTC le 30/08, puis juste après
+            note_at(datetime!(2025-08-30 23:59:59 UTC), Some("done"), Some("cravate"), false),
+            note_at(datetime!(2025-08-31 00:00:00 UTC), None, Some("cravate"), true),
+            // 01/09 00:30 en +02:00 = 31/08 22:30 UTC
+            note_at(datetime!(2025-09-01 00:30 +02:00), None, None, false),
+            note_at(datetime!(2025-09-01 12:00 UTC), Some("done"), Some("intime"), true),
+            // Hors fenêtre
+            note_at(datetime!(2025-08-20 10:00 UTC), None, None, false),
+        ];
+
+        let stats = compute_stats(&notes, date!(2025-09-01), 3);
+        assert_eq!(stats.total, 5);
+        assert_eq!(stats.urgent, 2);
+        assert_eq!(stats.by_status["done"], 2);
+        assert_eq!(stats.by_status["pending"], 3);
+        assert_eq!(stats.by_context["cravate"], 2);
+        assert_eq!(stats.created_per_day, vec![
+            DayCount { date: "2025-08-30".to_string(), count: 1 },
+            DayCount { date: "2025-08-31".to_string(), count: 2 },
+            DayCount { date: "2025-09-01".to_string(), count: 1 },
+        ]);
+    }
+
+    #[test]
+    fn test_monthly_shard_selection_on_create() {
+        use time::macros::datetime;
+
+        let dir = std::env::temp_dir().join(format!("symbion-notes-shards-{}", Uuid::new_v4()));
+        let storage = NotesStorage::with_layout(StorageLayout::Monthly(dir.clone())).unwrap();
+
+        // 31/08 23:30 en -02:00 = 01/09 01:30 UTC -> shard de septembre
+        let late_august_local = note_at(datetime!(2025-08-31 23:30 -02:00), None, None, false);
+        let august = note_at(datetime!(2025-08-15 10:00 UTC), None, None, false);
+        storage.insert_note(late_august_local.clone()).unwrap();
+        storage.insert_note(august.clone()).unwrap();
+
+        let read_ids = |file: &str| -> Vec<String> {
+            let notes: Vec<Note> = serde_json::from_str(&fs::read_to_string(dir.join(file)).unwrap()).unwrap();
+            notes.into_iter().map(|n| n.id).collect()
+        };
+        assert_eq!(read_ids("notes-2025-09.json"), vec![late_august_local.id]);
+        assert_eq!(read_ids("notes-2025-08.json"), vec![august.id]);
+
+        // La note du jour va dans la shard du mois courant
+        let created = storage.create_note(content("Aujourd'hui", &[])).unwrap();
+        let current = StorageLayout::Monthly(dir.clone()).shard_for(created.timestamp);
+        assert!(fs::read_to_string(current).unwrap().contains(&created.id));
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_cross_shard_list_and_delete() {
+        use time::macros::datetime;
+
+        let dir = std::env::temp_dir().join(format!("symbion-notes-shards-{}", Uuid::new_v4()));
+        let storage = NotesStorage::with_layout(StorageLayout::Monthly(dir.clone())).unwrap();
+        let july = note_at(datetime!(2025-07-02 08:00 UTC), Some("done"), None, false);
+        let august = note_at(datetime!(2025-08-02 08:00 UTC), None, None, true);
+        storage.insert_note(july.clone()).unwrap();
+        storage.insert_note(august.clone()).unwrap();
+
+        // Rechargement : la lecture fusionne les shards
+        let reloaded = NotesStorage::with_layout(StorageLayout::Monthly(dir.clone())).unwrap();
+        assert_eq!(reloaded.list_notes(None).len(), 2);
+
+        assert!(reloaded.delete_note(&july.id).unwrap());
+        assert!(!reloaded.delete_note(&july.id).unwrap());
+        assert_eq!(fs::read_to_string(dir.join("notes-2025-07.json")).unwrap().trim(), "[]");
+        assert!(fs::read_to_string(dir.join("notes-2025-08.json")).unwrap().contains(&august.id));
+
+        let remaining = NotesStorage::with_layout(StorageLayout::Monthly(dir.clone())).unwrap().list_notes(None);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, august.id);
+
+        let _ = fs::remove_dir_all(dir);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_links_reject_dangling_references() {
+        let (storage, path) = temp_storage();
+        let target = storage.create_note(content("Cible", &[])).unwrap();
+
+        let mut dangling = content("Lien cassé", &[]);
+        dangling.links = Some(vec![target.id.clone(), "missing-id".to_string()]);
+        let err = storage.create_note(dangling).unwrap_err().to_string();
+        assert_eq!(err, format!("{}: missing-id", DANGLING_LINK_ERROR));
+        assert_eq!(storage.list_notes(None).len(), 1);
+
+        let mut linked = content("Lien valide", &[]);
+        linked.links = Some(vec![target.id.clone()]);
+        let source = storage.create_note(linked).unwrap();
+
+        // Modification : ni lien inexistant, ni auto-référence
+        let mut self_link = content("Auto", &[]);
+        self_link.links = Some(vec![source.id.clone()]);
+        assert!(storage.update_note(&source.id, self_link).is_err());
+        let mut unknown = content("Inconnu", &[]);
+        unknown.links = Some(vec!["missing-id".to_string()]);
+        assert!(storage.update_note(&source.id, unknown).is_err());
+        let unchanged = storage.list_notes(None).into_iter().find(|n| n.id == source.id).unwrap();
+        assert_eq!(unchanged.data.links, Some(vec![target.id.clone()]));
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_deleting_a_note_removes_links_to_it() {
+        let (storage, path) = temp_storage();
+        let target = storage.create_note(content("Cible", &[])).unwrap();
+        let other = storage.create_note(content("Autre", &[])).unwrap();
+        let mut linked = content("Source", &[]);
+        linked.links = Some(vec![target.id.clone(), other.id.clone()]);
+        let source = storage.create_note(linked).unwrap();
+
+        assert!(storage.delete_note(&target.id).unwrap());
+        let source_now = storage.list_notes(None).into_iter().find(|n| n.id == source.id).unwrap();
+        assert_eq!(source_now.data.links, Some(vec![other.id.clone()]));
+
+        // Modification gardant ses liens : acceptée
+        assert!(storage.update_note(&source.id, source_now.data.clone()).unwrap().is_some());
+        // Retrait persisté
+        let reloaded = NotesStorage::new(&path).unwrap();
+        let source_reloaded = reloaded.list_notes(None).into_iter().find(|n| n.id == source.id).unwrap();
+        assert_eq!(source_reloaded.data.links, Some(vec![other.id.clone()]));
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_backlinks_lists_referencing_notes() {
+        let (storage, path) = temp_storage();
+        let target = storage.create_note(content("Cible", &[])).unwrap();
+        let other = storage.create_note(content("Autre", &[])).unwrap();
+
+        let mut first = content("Premier", &[]);
+        first.links = Some(vec![target.id.clone()]);
+        let first = storage.create_note(first).unwrap();
+        let mut second = content("Second", &[]);
+        second.links = Some(vec![other.id.clone(), target.id.clone()]);
+        let second = storage.create_note(second).unwrap();
+
+        let ids = |notes: Vec<Note>| notes.into_iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids(storage.backlinks(&target.id).unwrap()), vec![first.id.clone(), second.id.clone()]);
+        assert_eq!(ids(storage.backlinks(&other.id).unwrap()), vec![second.id.clone()]);
+        assert!(storage.backlinks(&first.id).unwrap().is_empty());
+        assert!(storage.backlinks("missing-id").is_none());
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_health_report_reflects_storage_state() {
+        let dir = std::env::temp_dir().join(format!("symbion-notes-health-{}", Uuid::new_v4()));
+        let storage = NotesStorage::with_layout(StorageLayout::Monthly(dir.clone())).unwrap();
+        storage.create_note(content("acheter du pain", &[])).unwrap();
+
+        let report = storage.health_report();
+        assert_eq!(report["plugin"], "notes-manager");
+        assert_eq!(report["status"], "healthy");
+        assert_eq!(report["checks"]["storage"]["status"], "healthy");
+        assert_eq!(report["details"]["notes_count"], 1);
+
+        // Répertoire de stockage disparu : plus aucune note ne peut être écrite
+        fs::remove_dir_all(&dir).unwrap();
+        let report = storage.health_report();
+        assert_eq!(report["status"], "unhealthy");
+        assert!(report["checks"]["storage"]["message"].as_str().unwrap().contains("symbion-notes-health"));
+    }
+
+    #[tokio::test]
+    async fn test_command_handler_survives_contract_fuzzing() {
+        use symbion_devkit::{ContractLoader, PayloadFuzzer};
+
+        let mut loader = ContractLoader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../contracts"));
+        loader.load_mqtt_contracts().unwrap();
+        let command = loader.get_contract("notes.command").unwrap().clone();
+        let response = loader.get_contract("notes.response").unwrap().clone();
+
+        let (storage, path) = temp_storage();
+        let storage = Arc::new(storage);
+        let fuzzer = PayloadFuzzer::new(2025).with_response_contract(response);
+        let handler_storage = storage.clone();
+        let report = fuzzer.run(&command, move |payload| {
+            let storage = handler_storage.clone();
+            async move { serde_json::to_value(respond_to_payload(&storage, &payload).await).unwrap() }
+        }).await;
+
+        assert!(report.cases > 100, "only {} cases", report.cases);
+        report.assert_clean().unwrap();
+        let _ = fs::remove_file(path);
+    }
+}
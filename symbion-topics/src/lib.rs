@@ -0,0 +1,156 @@
+/*!
+# Symbion Topics - Source unique des topics MQTT
+
+Les topics suivent le format `symbion/{domaine}/{événement}@v{version}` et
+correspondent aux contrats de `contracts/mqtt/` (`agents.command.v1.json` ↔
+`symbion/agents/command@v1`). Kernel, agent, plugins et devkit passent par ces
+helpers au lieu de recomposer les chaînes à la main.
+
+```rust
+assert_eq!(symbion_topics::agents_command(), "symbion/agents/command@v1");
+assert_eq!(symbion_topics::topic("inventory", "item_updated", 1), "symbion/inventory/item_updated@v1");
+```
+*/
+
+/// Préfixe commun à tous les topics Symbion
+pub const PREFIX: &str = "symbion";
+
+/// Topic d'un contrat : `symbion/{domain}/{event}@v{version}`
+pub fn topic(domain: &str, event: &str, version: u32) -> String {
+    format!("{}/{}/{}@v{}", PREFIX, domain, event, version)
+}
+
+/// Heartbeats des hosts (contrat hosts.heartbeat@v2)
+pub const fn hosts_heartbeat() -> &'static str {
+    "symbion/hosts/heartbeat@v2"
+}
+
+/// Demandes de Wake-on-LAN (contrat hosts.wake@v1)
+pub const fn hosts_wake() -> &'static str {
+    "symbion/hosts/wake@v1"
+}
+
+/// Commandes CRUD vers le plugin notes
+pub const fn notes_command() -> &'static str {
+    "symbion/notes/command@v1"
+}
+
+/// Réponses du plugin notes
+pub const fn notes_response() -> &'static str {
+    "symbion/notes/response@v1"
+}
+
+/// Enregistrement d'un agent au démarrage
+pub const fn agents_registration() -> &'static str {
+    "symbion/agents/registration@v1"
+}
+
+/// Heartbeats et métriques des agents
+pub const fn agents_heartbeat() -> &'static str {
+    "symbion/agents/heartbeat@v1"
+}
+
+/// Commandes du kernel : topic partagé, chaque agent filtre sur l'agent_id du payload
+pub const fn agents_command() -> &'static str {
+    "symbion/agents/command@v1"
+}
+
+/// Réponses des agents aux commandes
+pub const fn agents_response() -> &'static str {
+    "symbion/agents/response@v1"
+}
+
+/// Sortie incrémentale d'une commande en flux (run_command avec stream: true)
+pub const fn agents_output() -> &'static str {
+    "symbion/agents/output@v1"
+}
+
+/// Demande de ré-enregistrement immédiat de tous les agents
+pub const fn agents_announce() -> &'static str {
+    "symbion/agents/announce@v1"
+}
+
+/// Alertes sur les agents (flapping...)
+pub const fn agents_alert() -> &'static str {
+    "symbion/agents/alert@v1"
+}
+
+/// Changement d'IP ou de hostname d'un agent (DHCP, portable itinérant)
+pub const fn agents_network_changed() -> &'static str {
+    "symbion/agents/network_changed@v1"
+}
+
+/// Sections des heartbeats demandées aux agents (diffusion retenue ou agent ciblé)
+pub const fn agents_heartbeat_config() -> &'static str {
+    "symbion/agents/heartbeat_config@v1"
+}
+
+/// Santé du kernel publiée périodiquement
+pub const fn kernel_health() -> &'static str {
+    "symbion/kernel/health@v1"
+}
+
+/// Routes HTTP annoncées par les plugins
+pub const fn plugins_routes() -> &'static str {
+    "symbion/plugins/routes@v1"
+}
+
+/// Requêtes HTTP proxifiées vers un plugin
+pub const fn plugins_http_request() -> &'static str {
+    "symbion/plugins/http_request@v1"
+}
+
+/// Réponses des plugins aux requêtes HTTP proxifiées
+pub const fn plugins_http_response() -> &'static str {
+    "symbion/plugins/http_response@v1"
+}
+
+/// Configuration partagée diffusée à tous les plugins (retenue, republiée au rechargement)
+pub const fn plugins_config() -> &'static str {
+    "symbion/plugins/config@v1"
+}
+
+/// Santé métier publiée par les plugins, agrégée dans /system/health
+pub const fn plugins_health() -> &'static str {
+    "symbion/plugins/health@v1"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_strings_are_pinned() {
+        let pinned = [
+            (hosts_heartbeat(), "symbion/hosts/heartbeat@v2"),
+            (hosts_wake(), "symbion/hosts/wake@v1"),
+            (notes_command(), "symbion/notes/command@v1"),
+            (notes_response(), "symbion/notes/response@v1"),
+            (agents_registration(), "symbion/agents/registration@v1"),
+            (agents_heartbeat(), "symbion/agents/heartbeat@v1"),
+            (agents_command(), "symbion/agents/command@v1"),
+            (agents_response(), "symbion/agents/response@v1"),
+            (agents_output(), "symbion/agents/output@v1"),
+            (agents_announce(), "symbion/agents/announce@v1"),
+            (agents_alert(), "symbion/agents/alert@v1"),
+            (agents_network_changed(), "symbion/agents/network_changed@v1"),
+            (agents_heartbeat_config(), "symbion/agents/heartbeat_config@v1"),
+            (kernel_health(), "symbion/kernel/health@v1"),
+            (plugins_routes(), "symbion/plugins/routes@v1"),
+            (plugins_http_request(), "symbion/plugins/http_request@v1"),
+            (plugins_http_response(), "symbion/plugins/http_response@v1"),
+            (plugins_config(), "symbion/plugins/config@v1"),
+            (plugins_health(), "symbion/plugins/health@v1"),
+        ];
+        for (built, expected) in pinned {
+            assert_eq!(built, expected);
+        }
+    }
+
+    #[test]
+    fn test_builder_matches_named_topics() {
+        assert_eq!(topic("agents", "command", 1), agents_command());
+        assert_eq!(topic("hosts", "heartbeat", 2), hosts_heartbeat());
+        assert_eq!(topic("inventory", "item_updated", 1), "symbion/inventory/item_updated@v1");
+    }
+}
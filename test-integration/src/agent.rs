@@ -0,0 +1,120 @@
+/*!
+Agent simulé : parle les contrats agents.* comme symbion-agent-host, sans
+toucher à la machine.
+
+- `register` / `heartbeat` publient registration@v1 et heartbeat@v1
+- Chaque commande reçue pour cet agent_id est enregistrée et reçoit une réponse
+  `success` dont `data` renvoie le type et les paramètres de la commande
+*/
+
+use anyhow::{bail, Result};
+use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub struct SimulatedAgent {
+    pub agent_id: String,
+    client: AsyncClient,
+    commands: Arc<Mutex<Vec<Value>>>,
+}
+
+impl SimulatedAgent {
+    /// Connecte l'agent au broker et l'abonne aux commandes
+    pub async fn connect(mqtt_port: u16, agent_id: &str) -> Result<Self> {
+        let mut options = MqttOptions::new(format!("sim-agent-{}", agent_id), "127.0.0.1", mqtt_port);
+        options.set_keep_alive(Duration::from_secs(15));
+        let (client, mut eventloop) = AsyncClient::new(options, 32);
+        client.subscribe(symbion_topics::agents_command(), QoS::ExactlyOnce).await?;
+
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let (responder, received, id) = (client.clone(), commands.clone(), agent_id.to_string());
+        tokio::spawn(async move {
+            loop {
+                match eventloop.poll().await {
+                    Ok(Event::Incoming(Incoming::Publish(publish))) if publish.topic == symbion_topics::agents_command() => {
+                        let Ok(command) = serde_json::from_slice::<Value>(&publish.payload) else { continue };
+                        if command["agent_id"] != id.as_str() {
+                            continue;
+                        }
+                        received.lock().unwrap().push(command.clone());
+                        let response = json!({
+                            "command_id": command["command_id"],
+                            "agent_id": id,
+                            "status": "success",
+                            "data": { "command_type": command["command_type"], "parameters": command["parameters"] },
+                            "error": null,
+                            "execution_time_ms": 3,
+                            "timestamp": "2025-09-01T10:31:00Z"
+                        });
+                        let _ = responder.publish(symbion_topics::agents_response(), QoS::AtLeastOnce, false, response.to_string()).await;
+                    }
+                    Ok(_) => {}
+                    Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+                }
+            }
+        });
+
+        Ok(Self { agent_id: agent_id.to_string(), client, commands })
+    }
+
+    pub async fn register(&self, hostname: &str) -> Result<()> {
+        let registration = json!({
+            "agent_id": self.agent_id,
+            "hostname": hostname,
+            "os": "linux",
+            "architecture": "x86_64",
+            "capabilities": ["system_metrics", "process_control"],
+            "network": {
+                "primary_mac": "a1:b2:c3:d4:e5:f6",
+                "interfaces": [{ "name": "eth0", "mac": "a1:b2:c3:d4:e5:f6", "ip": "192.168.1.50", "type": "ethernet" }]
+            },
+            "version": "1.0.2",
+            "timestamp": "2025-09-01T10:30:00Z"
+        });
+        self.publish(symbion_topics::agents_registration(), registration).await
+    }
+
+    pub async fn heartbeat(&self, cpu_percent: f32, total_mb: u64, used_mb: u64) -> Result<()> {
+        let heartbeat = json!({
+            "agent_id": self.agent_id,
+            "status": "online",
+            "system": {
+                "uptime_seconds": 3600,
+                "cpu": { "percent": cpu_percent, "core_count": 8 },
+                "memory": { "total_mb": total_mb, "used_mb": used_mb, "percent_used": used_mb as f32 * 100.0 / total_mb as f32 }
+            },
+            "timestamp": "2025-09-01T10:31:00Z"
+        });
+        self.publish(symbion_topics::agents_heartbeat(), heartbeat).await
+    }
+
+    /// Publie une réponse arbitraire (réponses orphelines, corrélation)
+    pub async fn respond(&self, response: Value) -> Result<()> {
+        self.publish(symbion_topics::agents_response(), response).await
+    }
+
+    /// Commandes reçues pour cet agent, dans l'ordre
+    pub fn received_commands(&self) -> Vec<Value> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    /// Attend la commande `command_id`
+    pub async fn wait_for_command(&self, command_id: &str, timeout: Duration) -> Result<Value> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if let Some(command) = self.received_commands().into_iter().find(|c| c["command_id"] == command_id) {
+                return Ok(command);
+            }
+            if tokio::time::Instant::now() > deadline {
+                bail!("agent {} never received command {}", self.agent_id, command_id);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    async fn publish(&self, topic: &str, payload: Value) -> Result<()> {
+        self.client.publish(topic, QoS::AtLeastOnce, false, payload.to_string()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+/*!
+Kernel Symbion lancé en sous-processus pour les tests de bout en bout.
+
+Le binaire est (re)construit une fois par processus de test (`cargo build -p
+symbion-kernel`, ou `SYMBION_KERNEL_BIN` pour un binaire déjà construit), puis
+démarré dans un dossier temporaire : data/, plugins/ et contrats isolés,
+kernel.yaml pointant sur le broker du test, port HTTP libre.
+*/
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+pub const API_KEY: &str = "integration-test-key";
+
+/// Racine du dépôt (parent de test-integration/)
+fn repo_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).parent().expect("repo root").to_path_buf()
+}
+
+/// Chemin du binaire kernel, construit au premier appel
+fn kernel_binary() -> Result<PathBuf> {
+    static BINARY: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+    BINARY.get_or_init(|| {
+        if let Ok(path) = std::env::var("SYMBION_KERNEL_BIN") {
+            return Ok(PathBuf::from(path));
+        }
+        let root = repo_root();
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let status = Command::new(cargo)
+            .args(["build", "-p", "symbion-kernel", "--manifest-path"])
+            .arg(root.join("Cargo.toml"))
+            .status()
+            .map_err(|e| format!("cannot run cargo: {}", e))?;
+        if !status.success() {
+            return Err(format!("cargo build -p symbion-kernel failed: {}", status));
+        }
+        let target = std::env::var("CARGO_TARGET_DIR").map(PathBuf::from).unwrap_or_else(|_| root.join("target"));
+        Ok(target.join("debug").join(format!("symbion-kernel{}", std::env::consts::EXE_SUFFIX)))
+    }).clone().map_err(anyhow::Error::msg)
+}
+
+/// Kernel en cours d'exécution, arrêté au drop
+pub struct Kernel {
+    child: Child,
+    dir: PathBuf,
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl Kernel {
+    /// Démarre le kernel connecté au broker `mqtt_port` et attend que l'API réponde
+    pub async fn start(mqtt_port: u16) -> Result<Self> {
+        let binary = kernel_binary()?;
+        let dir = std::env::temp_dir().join(format!("symbion-it-{}-{}", std::process::id(), mqtt_port));
+        let workdir = dir.join("kernel");
+        fs::create_dir_all(&workdir)?;
+
+        // Le kernel charge ses contrats depuis ../contracts/mqtt
+        let contracts = dir.join("contracts").join("mqtt");
+        fs::create_dir_all(&contracts)?;
+        for entry in fs::read_dir(repo_root().join("contracts").join("mqtt"))? {
+            let entry = entry?;
+            fs::copy(entry.path(), contracts.join(entry.file_name()))?;
+        }
+
+        let config = dir.join("kernel.yaml");
+        fs::write(&config, format!("mqtt:\n  host: \"127.0.0.1\"\n  port: {}\nhosts: {{}}\n", mqtt_port))?;
+        let http_port = free_port()?;
+        let log = fs::File::create(dir.join("kernel.log"))?;
+
+        let child = Command::new(&binary)
+            .current_dir(&workdir)
+            .env("SYMBION_KERNEL_CONFIG", &config)
+            .env("SYMBION_API_KEY", API_KEY)
+            .env("SYMBION_HTTP_PORT", http_port.to_string())
+            .stdout(Stdio::from(log.try_clone()?))
+            .stderr(Stdio::from(log))
+            .spawn()
+            .with_context(|| format!("cannot start {}", binary.display()))?;
+
+        let kernel = Self {
+            child,
+            dir,
+            base_url: format!("http://127.0.0.1:{}", http_port),
+            http: reqwest::Client::new(),
+        };
+        kernel.wait_ready(Duration::from_secs(20)).await?;
+        Ok(kernel)
+    }
+
+    async fn wait_ready(&self, timeout: Duration) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if let Ok(response) = self.http.get(format!("{}/health", self.base_url)).send().await {
+                if response.status().is_success() {
+                    return Ok(());
+                }
+            }
+            if tokio::time::Instant::now() > deadline {
+                bail!("kernel not ready after {:?}\n{}", timeout, self.log());
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Sortie du kernel (à joindre aux messages d'échec)
+    pub fn log(&self) -> String {
+        fs::read_to_string(self.dir.join("kernel.log")).unwrap_or_default()
+    }
+
+    /// GET authentifié : (statut HTTP, corps JSON ou Null)
+    pub async fn get(&self, path: &str) -> Result<(u16, Value)> {
+        let response = self.http.get(format!("{}{}", self.base_url, path))
+            .header("x-api-key", API_KEY)
+            .send().await?;
+        let status = response.status().as_u16();
+        Ok((status, response.json().await.unwrap_or(Value::Null)))
+    }
+
+    /// POST JSON authentifié : (statut HTTP, corps JSON ou Null)
+    pub async fn post(&self, path: &str, body: &Value) -> Result<(u16, Value)> {
+        let response = self.http.post(format!("{}{}", self.base_url, path))
+            .header("x-api-key", API_KEY)
+            .json(body)
+            .send().await?;
+        let status = response.status().as_u16();
+        Ok((status, response.json().await.unwrap_or(Value::Null)))
+    }
+
+    /// Relance `path` jusqu'à ce que `done` accepte la réponse
+    pub async fn get_until(&self, path: &str, timeout: Duration, done: impl Fn(u16, &Value) -> bool) -> Result<Value> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let (status, body) = self.get(path).await?;
+            if done(status, &body) {
+                return Ok(body);
+            }
+            if tokio::time::Instant::now() > deadline {
+                bail!("GET {} still {} {} after {:?}\n{}", path, status, body, timeout, self.log());
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+impl Drop for Kernel {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn free_port() -> Result<u16> {
+    Ok(std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
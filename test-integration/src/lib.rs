@@ -0,0 +1,16 @@
+/*!
+# Symbion - Tests d'intégration agent ↔ kernel
+
+Harnais de bout en bout : le broker MQTT embarqué du devkit, le vrai binaire du kernel
+et un agent simulé, vérifiés par l'API REST (voir tests/agent_kernel_flow.rs).
+
+```bash
+cd test-integration && cargo test
+```
+*/
+
+pub mod agent;
+pub mod kernel;
+
+pub use agent::SimulatedAgent;
+pub use kernel::Kernel;
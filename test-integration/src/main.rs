@@ -1,79 +0,0 @@
-use rumqttc::{AsyncClient, Event, Incoming, MqttOptions, QoS};
-use serde::{Deserialize, Serialize};
-use anyhow::Result;
-use log::{info, warn, error, debug};
-use tokio::time::{sleep, Duration};
-
-// ===== Configuration =====
-const MQTT_BROKER: &str = "127.0.0.1";
-const MQTT_PORT: u16 = 1883;
-const CLIENT_ID: &str = "test-integration-client";
-
-// ===== Data Structures =====
-// TODO: Ajouter les structures de données selon vos contrats
-
-#[tokio::main]
-async fn main() -> Result<()> {
-    env_logger::init();
-    info!("🚀 Starting test-integration plugin");
-
-    // Configuration MQTT
-    let mut mqttoptions = MqttOptions::new(CLIENT_ID, MQTT_BROKER, MQTT_PORT);
-    mqttoptions.set_keep_alive(Duration::from_secs(30));
-
-    let (client, mut eventloop) = AsyncClient::new(mqttoptions, 10);
-    
-    // Abonnements aux topics d'entrée
-    // TODO: S'abonner aux topics selon vos contrats
-    // Exemple: client.subscribe("symbion/hosts/heartbeat@v2", QoS::AtLeastOnce).await?;
-
-    // Boucle principale
-    loop {
-        match eventloop.poll().await {
-            Ok(Event::Incoming(Incoming::Publish(publish))) => {
-                let topic = &publish.topic;
-                let payload = &publish.payload;
-                
-                if let Err(e) = handle_message(topic, payload, &client).await {
-                    error!("❌ Error handling message from {}: {}", topic, e);
-                }
-            },
-            Ok(_) => {
-                // Autres événements MQTT (connexion, etc.)
-            },
-            Err(e) => {
-                warn!("⚠️ MQTT connection error: {}. Reconnecting...", e);
-                sleep(Duration::from_secs(5)).await;
-            }
-        }
-    }
-}
-
-async fn handle_message(topic: &str, payload: &[u8], client: &AsyncClient) -> Result<()> {
-    debug!("📨 Received message from topic: {}", topic);
-    
-    match topic {
-        // TODO: Gérer les topics selon vos contrats
-        // Exemple:
-        // "symbion/hosts/heartbeat@v2" => {
-        //     let heartbeat: HeartbeatV2 = serde_json::from_slice(payload)?;
-        //     handle_heartbeat(heartbeat, client).await?;
-        // },
-        _ => {
-            warn!("🤷 Unknown topic: {}", topic);
-        }
-    }
-    
-    Ok(())
-}
-
-// TODO: Ajouter vos handlers de messages
-// Exemple:
-// async fn handle_heartbeat(heartbeat: HeartbeatV2, client: &AsyncClient) -> Result<()> {
-//     info!("💓 Processing heartbeat from {}: CPU={}%, RAM={}%", 
-//           heartbeat.host_id, heartbeat.metrics.cpu, heartbeat.metrics.ram);
-//     
-//     // Logique métier ici
-//     
-//     Ok(())
-// }
\ No newline at end of file
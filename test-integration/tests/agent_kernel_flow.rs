@@ -0,0 +1,94 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+use symbion_devkit::EmbeddedBroker;
+use test_integration::{Kernel, SimulatedAgent};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const AGENT_ID: &str = "a1b2c3d4e5f6";
+
+/// Envoie run_command et renvoie le command_id attribué par le kernel
+async fn send_command(kernel: &Kernel, command: &str) -> String {
+    let (status, body) = kernel.post(&format!("/agents/{}/command", AGENT_ID), &json!({ "command": command })).await.unwrap();
+    assert_eq!(status, 200, "{}\n{}", body, kernel.log());
+    body["command_id"].as_str().expect("command_id").to_string()
+}
+
+async fn command_result(kernel: &Kernel, command_id: &str) -> Value {
+    let (status, record) = kernel.get(&format!("/commands/{}/result?wait=5", command_id)).await.unwrap();
+    assert_eq!(status, 200, "{}\n{}", record, kernel.log());
+    record
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_agent_registration_heartbeat_and_command_round_trip() {
+    let broker = EmbeddedBroker::start().await.unwrap();
+    let kernel = Kernel::start(broker.port()).await.unwrap();
+    for topic in [symbion_topics::agents_registration(), symbion_topics::agents_heartbeat(), symbion_topics::agents_response()] {
+        broker.wait_for_subscriber("symbion-kernel-listener", topic, TIMEOUT).await
+            .unwrap_or_else(|e| panic!("{}\n{}", e, kernel.log()));
+    }
+    let agent = SimulatedAgent::connect(broker.port(), AGENT_ID).await.unwrap();
+    broker.wait_for_subscriber("sim-agent-", symbion_topics::agents_command(), TIMEOUT).await.unwrap();
+
+    // Registration → visible dans GET /agents
+    agent.register("it-desktop").await.unwrap();
+    let agents = kernel.get_until("/agents", TIMEOUT, |_, body| {
+        body.as_array().is_some_and(|list| list.iter().any(|a| a["agent_id"] == AGENT_ID))
+    }).await.unwrap();
+    let listed = agents.as_array().unwrap().iter().find(|a| a["agent_id"] == AGENT_ID).unwrap();
+    assert_eq!(listed["hostname"], "it-desktop");
+    assert_eq!(listed["os"], "linux");
+    assert_eq!(listed["status"], "online");
+
+    // Heartbeat → métriques par agent et groupées
+    agent.heartbeat(42.5, 16384, 4096).await.unwrap();
+    let metrics = kernel.get_until(&format!("/agents/{}/metrics", AGENT_ID), TIMEOUT, |status, body| {
+        status == 200 && body["cpu"]["percent"] == 42.5
+    }).await.unwrap();
+    assert_eq!(metrics["memory"]["total_mb"], 16384);
+    assert_eq!(metrics["uptime_seconds"], 3600);
+    let (_, bulk) = kernel.get("/agents/metrics").await.unwrap();
+    assert_eq!(bulk["agents"][AGENT_ID]["system"], metrics);
+
+    // Commande → publiée sur le topic partagé, reçue par l'agent, réponse corrélée
+    let command_id = send_command(&kernel, "uptime").await;
+    let received = agent.wait_for_command(&command_id, TIMEOUT).await.unwrap();
+    assert_eq!(received["command_type"], "run_command");
+    assert_eq!(received["parameters"]["command"], "uptime");
+    assert!(broker.published(symbion_topics::agents_command()).iter()
+        .any(|p| serde_json::from_slice::<Value>(p).unwrap()["command_id"] == command_id.as_str()));
+
+    let record = command_result(&kernel, &command_id).await;
+    assert_eq!(record["status"], "success");
+    assert_eq!(record["agent_id"], AGENT_ID);
+    assert_eq!(record["command_type"], "run_command");
+    assert_eq!(record["response"]["command_id"], command_id.as_str());
+    assert_eq!(record["response"]["data"]["parameters"]["command"], "uptime");
+
+    // Corrélation : deux commandes en vol gardent chacune leur réponse
+    let first = send_command(&kernel, "hostname").await;
+    let second = send_command(&kernel, "whoami").await;
+    assert_ne!(first, second);
+    assert_eq!(command_result(&kernel, &second).await["response"]["data"]["parameters"]["command"], "whoami");
+    assert_eq!(command_result(&kernel, &first).await["response"]["data"]["parameters"]["command"], "hostname");
+
+    // Réponse orpheline : ignorée, aucun résultat créé, les résultats existants intacts
+    agent.respond(json!({
+        "command_id": "00000000-0000-0000-0000-000000000000",
+        "agent_id": AGENT_ID,
+        "status": "error",
+        "data": null,
+        "error": { "code": "BOGUS", "message": "not a tracked command" },
+        "execution_time_ms": 1,
+        "timestamp": "2025-09-01T10:32:00Z"
+    })).await.unwrap();
+    // Une commande envoyée après garantit que la réponse orpheline a été traitée
+    let after = send_command(&kernel, "date").await;
+    assert_eq!(command_result(&kernel, &after).await["status"], "success");
+    let (status, _) = kernel.get("/commands/00000000-0000-0000-0000-000000000000/result").await.unwrap();
+    assert_eq!(status, 404);
+    assert_eq!(command_result(&kernel, &command_id).await["status"], "success");
+    // GET /agents/{id}/metrics interrogé avant l'arrivée du heartbeat envoie aussi des get_metrics
+    let run_commands = agent.received_commands().into_iter().filter(|c| c["command_type"] == "run_command").count();
+    assert_eq!(run_commands, 4);
+}